@@ -0,0 +1,879 @@
+use crate::assertions::Assertion;
+use crate::requests::ErrorClass;
+use crate::results::{throughput_correction, BenchmarkReport, BenchmarkResults};
+use crate::scheduler::ExecutorType;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Prefix of the ids of the steps of the SLO search
+pub const SEARCH_STAGE_PREFIX: &str = "search@";
+
+/// Highest request rate before latency starts growing disproportionately
+#[derive(Debug, Clone)]
+pub struct OperatingPoint {
+    pub id: String,
+    pub rate: f64,
+    pub token_throughput_secs: f64,
+    pub e2e_latency_avg_secs: f64,
+}
+
+/// Detect the knee of the latency-vs-rate curve of constant arrival rate benchmarks.
+/// Uses the Kneedle method: after normalizing both axes to [0, 1], the knee is the point
+/// with the largest distance below the line joining the first and last points.
+pub fn recommended_operating_point(report: &BenchmarkReport) -> Option<OperatingPoint> {
+    let mut results = report
+        .get_results()
+        .into_iter()
+        .filter(|r| matches!(r.executor_type(), ExecutorType::ConstantArrivalRate))
+        .filter(|r| r.e2e_latency_avg().is_ok() && r.executor_config().rate.is_some())
+        .filter(|r| {
+            r.executor_config().ramp_to_rate.is_none() && r.executor_config().rate_curve.is_none()
+        })
+        .collect::<Vec<BenchmarkResults>>();
+    results.sort_by(|a, b| {
+        a.executor_config()
+            .rate
+            .partial_cmp(&b.executor_config().rate)
+            .unwrap()
+    });
+    let points = results
+        .iter()
+        .map(|r| {
+            (
+                r.executor_config().rate.unwrap(),
+                r.e2e_latency_avg().unwrap().as_secs_f64(),
+            )
+        })
+        .collect::<Vec<(f64, f64)>>();
+    let knee = knee_index(&points)?;
+    let results = &results[knee];
+    Some(OperatingPoint {
+        id: results.id.clone(),
+        rate: points[knee].0,
+        token_throughput_secs: results.token_throughput_secs().unwrap_or_default(),
+        e2e_latency_avg_secs: points[knee].1,
+    })
+}
+
+/// Benchmark step that is not dominated by any other step on throughput and p99 latency
+#[derive(Debug, Clone)]
+pub struct ParetoPoint {
+    pub id: String,
+    pub token_throughput_secs: f64,
+    pub e2e_latency_p99_secs: f64,
+}
+
+/// Compute the throughput-vs-p99-latency Pareto frontier across benchmark steps (warmup excluded).
+/// Points are sorted by increasing throughput.
+pub fn pareto_frontier(report: &BenchmarkReport) -> Vec<ParetoPoint> {
+    let points = report
+        .get_results()
+        .into_iter()
+        .filter(|r| r.id != "warmup")
+        .filter_map(|r| {
+            Some(ParetoPoint {
+                id: r.id.clone(),
+                token_throughput_secs: r.token_throughput_secs().ok()?,
+                e2e_latency_p99_secs: r.e2e_latency_percentile(0.99).ok()?.as_secs_f64(),
+            })
+        })
+        .collect::<Vec<ParetoPoint>>();
+    let mut frontier = points
+        .iter()
+        .filter(|p| !points.iter().any(|other| dominates(other, p)))
+        .cloned()
+        .collect::<Vec<ParetoPoint>>();
+    frontier.sort_by(|a, b| {
+        a.token_throughput_secs
+            .partial_cmp(&b.token_throughput_secs)
+            .unwrap()
+    });
+    frontier
+}
+
+fn dominates(a: &ParetoPoint, b: &ParetoPoint) -> bool {
+    a.token_throughput_secs >= b.token_throughput_secs
+        && a.e2e_latency_p99_secs <= b.e2e_latency_p99_secs
+        && (a.token_throughput_secs > b.token_throughput_secs
+            || a.e2e_latency_p99_secs < b.e2e_latency_p99_secs)
+}
+
+/// Requests sent while the arrival rate of a ramp was around `rate`
+#[derive(Debug, Clone)]
+pub struct CapacityPoint {
+    pub rate: f64,
+    pub requests: u64,
+    pub failed_requests: u64,
+    pub token_throughput_secs: f64,
+    pub e2e_latency_p50_secs: f64,
+    pub e2e_latency_p99_secs: f64,
+    /// Whether the requests of the bin meet the SLO objectives, always with none
+    pub meets_slo: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CapacityBin {
+    e2e_latencies: Vec<f64>,
+    generated_tokens: u64,
+    failed_requests: u64,
+    misses_slo: bool,
+}
+
+/// Latency-vs-rate curve of a ramp stage. Requests are binned by the arrival rate at the time
+/// they were sent, so the curve can be updated while the ramp is running.
+#[derive(Debug, Clone)]
+pub struct CapacityCurve {
+    pub id: String,
+    pub start_rate: f64,
+    pub end_rate: f64,
+    duration: Duration,
+    bins: Vec<CapacityBin>,
+    /// Whether the bins were checked against SLO objectives
+    slo_checked: bool,
+}
+
+impl CapacityCurve {
+    pub fn new(
+        id: String,
+        start_rate: f64,
+        end_rate: f64,
+        duration: Duration,
+        num_bins: usize,
+    ) -> CapacityCurve {
+        CapacityCurve {
+            id,
+            start_rate,
+            end_rate,
+            duration,
+            bins: vec![CapacityBin::default(); num_bins.max(1)],
+            slo_checked: false,
+        }
+    }
+
+    /// Build the curve of a ramp stage, `None` for any other stage. The SLO objectives are checked
+    /// on the requests of each bin.
+    pub fn from_results(
+        results: &BenchmarkResults,
+        num_bins: usize,
+        slo: &[Assertion],
+    ) -> Option<CapacityCurve> {
+        let config = results.executor_config();
+        let mut curve = CapacityCurve::new(
+            results.id.clone(),
+            config.rate?,
+            config.ramp_to_rate?,
+            config.duration,
+            num_bins,
+        );
+        // the ramp starts with the stage, which is when the first request is sent
+        let start = results.start_time()?;
+        let mut bin_results = (0..curve.bins.len())
+            .map(|_| {
+                BenchmarkResults::new(results.id.clone(), results.executor_type(), config.clone())
+            })
+            .collect::<Vec<_>>();
+        for response in results.responses() {
+            let Some(sent) = response.start_time else {
+                continue;
+            };
+            let offset = sent.duration_since(start);
+            curve.add(
+                offset,
+                response.e2e_latency(),
+                response.num_generated_tokens,
+                response.failed,
+            );
+            if !slo.is_empty() {
+                bin_results[curve.bin_index(offset)].add_response(response.clone());
+            }
+        }
+        if !slo.is_empty() {
+            for (bin, results) in curve.bins.iter_mut().zip(&bin_results) {
+                bin.misses_slo = !meets_slo(slo, results);
+            }
+            curve.slo_checked = true;
+        }
+        Some(curve)
+    }
+
+    fn bin_index(&self, offset: Duration) -> usize {
+        let position = offset.as_secs_f64() / self.duration.as_secs_f64();
+        ((position * self.bins.len() as f64) as usize).min(self.bins.len() - 1)
+    }
+
+    /// Add a request sent `offset` after the start of the ramp
+    pub fn add(
+        &mut self,
+        offset: Duration,
+        e2e_latency: Option<Duration>,
+        generated_tokens: u64,
+        failed: bool,
+    ) {
+        let index = self.bin_index(offset);
+        let bin = &mut self.bins[index];
+        match (failed, e2e_latency) {
+            (false, Some(latency)) => {
+                bin.e2e_latencies.push(latency.as_secs_f64());
+                bin.generated_tokens += generated_tokens;
+            }
+            _ => bin.failed_requests += 1,
+        }
+    }
+
+    /// One point per rate bin with successful requests, sorted by increasing rate
+    pub fn points(&self) -> Vec<CapacityPoint> {
+        let num_bins = self.bins.len() as f64;
+        let bin_secs = self.duration.as_secs_f64() / num_bins;
+        self.bins
+            .iter()
+            .enumerate()
+            .filter(|(_, bin)| !bin.e2e_latencies.is_empty())
+            .map(|(i, bin)| {
+                let mut latencies = bin.e2e_latencies.clone();
+                latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                CapacityPoint {
+                    rate: self.start_rate
+                        + (self.end_rate - self.start_rate) * (i as f64 + 0.5) / num_bins,
+                    requests: latencies.len() as u64 + bin.failed_requests,
+                    failed_requests: bin.failed_requests,
+                    token_throughput_secs: bin.generated_tokens as f64 / bin_secs,
+                    e2e_latency_p50_secs: nearest_rank(&latencies, 0.5),
+                    e2e_latency_p99_secs: nearest_rank(&latencies, 0.99),
+                    meets_slo: !bin.misses_slo,
+                }
+            })
+            .collect()
+    }
+
+    /// Highest rate before the median latency starts growing disproportionately
+    pub fn knee(&self) -> Option<CapacityPoint> {
+        let points = self.points();
+        let knee = knee_index(
+            &points
+                .iter()
+                .map(|p| (p.rate, p.e2e_latency_p50_secs))
+                .collect::<Vec<(f64, f64)>>(),
+        )?;
+        points.into_iter().nth(knee)
+    }
+
+    /// Highest rate bin meeting the SLO objectives, `None` if none did or there are no objectives
+    pub fn max_rate_at_slo(&self) -> Option<CapacityPoint> {
+        if !self.slo_checked {
+            return None;
+        }
+        self.points().into_iter().rev().find(|p| p.meets_slo)
+    }
+}
+
+/// Capacity curve of the first ramp stage of the report
+pub fn capacity_curve(
+    report: &BenchmarkReport,
+    num_bins: usize,
+    slo: &[Assertion],
+) -> Option<CapacityCurve> {
+    report
+        .get_results()
+        .iter()
+        .find_map(|r| CapacityCurve::from_results(r, num_bins, slo))
+}
+
+/// Step of the SLO search
+#[derive(Debug, Clone, Serialize)]
+pub struct SloStep {
+    pub id: String,
+    pub rate: f64,
+    pub passed: bool,
+}
+
+/// Highest arrival rate of the constant rate steps meeting every objective, `None` if no step met
+/// them
+#[derive(Debug, Clone, Serialize)]
+pub struct SloCapacity {
+    pub slo: Vec<String>,
+    pub rate: Option<f64>,
+    pub token_throughput_secs: Option<f64>,
+    pub steps: Vec<SloStep>,
+}
+
+/// Whether a stage meets every objective of the SLO
+pub fn meets_slo(slo: &[Assertion], results: &BenchmarkResults) -> bool {
+    slo.iter()
+        .all(|objective| objective.check(objective.value(results)))
+}
+
+/// Capacity found by the constant rate steps of the report (SLO search or sweep), in the order
+/// they ran. Reported next to the latency knee of sweeps, which doesn't account for the SLO
+pub fn slo_capacity(report: &BenchmarkReport, slo: &[Assertion]) -> Option<SloCapacity> {
+    if slo.is_empty() {
+        return None;
+    }
+    let steps = report
+        .get_results()
+        .into_iter()
+        .filter(|r| matches!(r.executor_type(), ExecutorType::ConstantArrivalRate))
+        .filter(|r| {
+            r.executor_config().ramp_to_rate.is_none() && r.executor_config().rate_curve.is_none()
+        })
+        .filter_map(|r| Some((r.executor_config().rate?, meets_slo(slo, &r), r)))
+        .collect::<Vec<_>>();
+    if steps.is_empty() {
+        return None;
+    }
+    let best = steps
+        .iter()
+        .filter(|(_, passed, _)| *passed)
+        .max_by(|(a, ..), (b, ..)| a.total_cmp(b));
+    Some(SloCapacity {
+        slo: slo.iter().map(|objective| objective.to_string()).collect(),
+        rate: best.map(|(rate, ..)| *rate),
+        token_throughput_secs: best.and_then(|(.., r)| r.token_throughput_secs().ok()),
+        steps: steps
+            .iter()
+            .map(|(rate, passed, r)| SloStep {
+                id: r.id.clone(),
+                rate: *rate,
+                passed: *passed,
+            })
+            .collect(),
+    })
+}
+
+/// Relative difference of the generated token counts of the client and the server above which the
+/// client tokenizer is considered not to be the one of the served model
+const TOKENIZER_MISMATCH_TOLERANCE: f64 = 0.05;
+
+/// Generated tokens counted by the client tokenizer and reported in the usage of the server, on
+/// the successful responses of a step carrying usage
+#[derive(Clone, Debug, Serialize)]
+pub struct TokenizerCheck {
+    pub step: String,
+    pub requests: u64,
+    pub client_tokens: u64,
+    pub server_tokens: u64,
+    /// Server tokens per client token: the factor token throughput is likely off by
+    pub error_factor: f64,
+    pub mismatch: bool,
+}
+
+impl TokenizerCheck {
+    pub fn from_results(results: &BenchmarkResults) -> Option<TokenizerCheck> {
+        let counts = results
+            .get_successful_responses()
+            .into_iter()
+            .filter_map(|r| Some((r.num_generated_tokens, r.usage_completion_tokens?)))
+            .collect::<Vec<_>>();
+        let client_tokens = counts.iter().map(|(client, _)| client).sum::<u64>();
+        let server_tokens = counts.iter().map(|(_, server)| server).sum::<u64>();
+        if client_tokens == 0 {
+            return None;
+        }
+        let error_factor = throughput_correction(server_tokens, client_tokens);
+        Some(TokenizerCheck {
+            step: results.id.clone(),
+            requests: counts.len() as u64,
+            client_tokens,
+            server_tokens,
+            error_factor,
+            mismatch: (error_factor - 1.0).abs() > TOKENIZER_MISMATCH_TOLERANCE,
+        })
+    }
+
+    pub fn warning(&self) -> String {
+        format!(
+            "Tokenizer mismatch: the server generated {} tokens where the client tokenizer counted \
+             {} over {} requests of {}. Token throughput is likely off by a factor of {:.2}, use \
+             the tokenizer of the served model (--tokenizer-name)",
+            self.server_tokens, self.client_tokens, self.requests, self.step, self.error_factor
+        )
+    }
+}
+
+/// Tokenizer check on the warmup, or on the first step with server usage without warmup
+pub fn tokenizer_check(report: &BenchmarkReport) -> Option<TokenizerCheck> {
+    let results = report.get_results();
+    let warmup = results.iter().find(|r| r.id == "warmup");
+    warmup
+        .and_then(TokenizerCheck::from_results)
+        .or_else(|| results.iter().find_map(TokenizerCheck::from_results))
+}
+
+/// Share of the requests of a step failing with a cause above which it gets a hint
+const HINT_FAILURE_RATE: f64 = 0.01;
+
+/// Achieved share of the target rate under which the server is considered saturated
+const HINT_PLATEAU_RATIO: f64 = 0.9;
+
+/// Likely bottleneck found in the results
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HintKind {
+    ClientSaturated,
+    ResponseProcessing,
+    RateLimited,
+    Timeouts,
+    ContextLength,
+    ConnectionErrors,
+    ServerErrors,
+    ThroughputPlateau,
+    PrefillQueueing,
+    DecodeSaturation,
+    KvCachePressure,
+}
+
+/// Heuristic reading of the results, pointing at a likely bottleneck
+#[derive(Clone, Debug, Serialize)]
+pub struct Hint {
+    pub kind: HintKind,
+    pub message: String,
+}
+
+/// Hints interpreting the results for users unfamiliar with inference serving: client overhead,
+/// failure causes, how throughput and latencies follow the rate steps and KV cache pressure
+pub fn bottleneck_hints(report: &BenchmarkReport) -> Vec<Hint> {
+    let mut hints = Vec::new();
+    let results = report
+        .get_results()
+        .into_iter()
+        .filter(|r| r.id != "warmup")
+        .collect::<Vec<_>>();
+    if let Some(saturation) = report.client_saturations().first() {
+        hints.push(Hint {
+            kind: HintKind::ClientSaturated,
+            message: format!(
+                "The client saturated its {} at {}: results may measure the client rather than the \
+                server. Run the benchmark from a larger machine or with fewer VUs",
+                saturation.resources.join(", "),
+                saturation.timestamp
+            ),
+        });
+    }
+    if let Some(r) = results.iter().find(|r| r.blocked_response_sends() > 0) {
+        hints.push(Hint {
+            kind: HintKind::ResponseProcessing,
+            message: format!(
+                "The client fell behind processing responses from {} ({} blocked sends), which \
+                delays the next requests. Raise --response-channel-capacity",
+                r.id,
+                r.blocked_response_sends()
+            ),
+        });
+    }
+    // first step each failure cause shows up in
+    let mut failures = BTreeMap::new();
+    for r in &results {
+        for (class, rate) in r.failure_rates() {
+            if rate >= HINT_FAILURE_RATE {
+                failures.entry(class).or_insert((r.id.clone(), rate));
+            }
+        }
+    }
+    for (class, (id, rate)) in failures {
+        let (kind, cause) = match class {
+            ErrorClass::Quota => (
+                HintKind::RateLimited,
+                "were rate limited: a gateway or API quota limits the load, not the model server",
+            ),
+            ErrorClass::Timeout => (
+                HintKind::Timeouts,
+                "timed out: the server is overloaded at this load, or the timeouts are too short \
+                for the request lengths",
+            ),
+            ErrorClass::ContextLength => (
+                HintKind::ContextLength,
+                "exceeded the model context length: shorten the prompt or decode options, or clamp \
+                them with --clamp-to-context",
+            ),
+            ErrorClass::Connection => (
+                HintKind::ConnectionErrors,
+                "failed to connect: the server dropped connections or the client ran out of \
+                sockets (see --open-files-limit)",
+            ),
+            ErrorClass::Server => (
+                HintKind::ServerErrors,
+                "failed with server errors: the server may be crashing or running out of memory \
+                at this load",
+            ),
+            _ => continue,
+        };
+        hints.push(Hint {
+            kind,
+            message: format!("{:.1}% of the requests of {id} {cause}", rate * 100.0),
+        });
+    }
+    hints.extend(rate_step_hints(&results));
+    if let Some((id, preemptions)) = results.iter().find_map(|r| {
+        let preemptions = r.vllm_stats()?.preemptions?;
+        (preemptions > 0.0).then(|| (r.id.clone(), preemptions))
+    }) {
+        hints.push(Hint {
+            kind: HintKind::KvCachePressure,
+            message: format!(
+                "The server preempted {preemptions:.0} requests in {id}: its KV cache is full, so \
+                requests are evicted and recomputed. Lower the concurrency or give the KV cache \
+                more memory"
+            ),
+        });
+    }
+    hints
+}
+
+/// Hints on how throughput, TTFT and ITL follow the rate of constant arrival rate steps
+fn rate_step_hints(results: &[BenchmarkResults]) -> Vec<Hint> {
+    let mut hints = Vec::new();
+    // (id, target rate, achieved rate, TTFT, ITL) by increasing target rate
+    let mut steps = results
+        .iter()
+        .filter(|r| matches!(r.executor_type(), ExecutorType::ConstantArrivalRate))
+        .filter(|r| {
+            r.executor_config().ramp_to_rate.is_none() && r.executor_config().rate_curve.is_none()
+        })
+        .filter_map(|r| {
+            Some((
+                r.id.clone(),
+                r.executor_config().rate?,
+                r.successful_request_rate().ok()?,
+                r.time_to_first_token_avg().ok()?.as_secs_f64(),
+                r.inter_token_latency_avg().ok()?.as_secs_f64(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    steps.sort_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((id, rate, achieved, ..)) = steps
+        .iter()
+        .find(|(_, rate, achieved, ..)| *achieved < rate * HINT_PLATEAU_RATIO)
+    {
+        hints.push(Hint {
+            kind: HintKind::ThroughputPlateau,
+            message: format!(
+                "Throughput plateaus from {id}: the server completed {achieved:.2} req/s of the \
+                {rate:.2} req/s sent. Higher rates only add queueing"
+            ),
+        });
+    }
+    let (Some(first), Some(last)) = (steps.first(), steps.last()) else {
+        return hints;
+    };
+    if steps.len() < 3 || first.3 <= 0.0 || first.4 <= 0.0 {
+        return hints;
+    }
+    let ttft_growth = last.3 / first.3;
+    let itl_growth = last.4 / first.4;
+    if ttft_growth >= 3.0 && itl_growth < 1.5 {
+        hints.push(Hint {
+            kind: HintKind::PrefillQueueing,
+            message: format!(
+                "TTFT grows {ttft_growth:.1}x from {:.2} to {:.2} req/s while ITL stays flat: \
+                requests queue before prefill, as the server admits them slower than they arrive",
+                first.1, last.1
+            ),
+        });
+    }
+    if itl_growth >= 2.0 {
+        hints.push(Hint {
+            kind: HintKind::DecodeSaturation,
+            message: format!(
+                "ITL grows {itl_growth:.1}x from {:.2} to {:.2} req/s: decode batches grow with the \
+                load and decoding becomes compute or memory bandwidth bound",
+                first.1, last.1
+            ),
+        });
+    }
+    hints
+}
+
+fn nearest_rank(sorted: &[f64], quantile: f64) -> f64 {
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn knee_index(points: &[(f64, f64)]) -> Option<usize> {
+    if points.len() < 3 {
+        return None;
+    }
+    let (x_min, x_max) = min_max(points.iter().map(|p| p.0));
+    let (y_min, y_max) = min_max(points.iter().map(|p| p.1));
+    if x_max <= x_min || y_max <= y_min {
+        return None;
+    }
+    let (knee, distance) = points
+        .iter()
+        .map(|(x, y)| (x - x_min) / (x_max - x_min) - (y - y_min) / (y_max - y_min))
+        .enumerate()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())?;
+    // a linear or concave curve has no knee
+    if distance <= 0.0 {
+        return None;
+    }
+    Some(knee)
+}
+
+fn min_max(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    values.fold((f64::MAX, f64::MIN), |(min, max), v| {
+        (min.min(v), max.max(v))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_knee_index() {
+        // latency is flat until 4 req/s then explodes
+        let points = vec![(1.0, 1.0), (2.0, 1.0), (3.0, 1.1), (4.0, 1.2), (5.0, 5.0)];
+        assert_eq!(knee_index(&points), Some(3));
+        // linear growth has no knee
+        let points = vec![(1.0, 1.0), (2.0, 2.0), (3.0, 3.0)];
+        assert_eq!(knee_index(&points), None);
+        // not enough points
+        let points = vec![(1.0, 1.0), (2.0, 5.0)];
+        assert_eq!(knee_index(&points), None);
+    }
+
+    #[test]
+    fn test_capacity_curve() {
+        let mut curve =
+            CapacityCurve::new("ramp".to_string(), 1.0, 5.0, Duration::from_secs(40), 4);
+        // latency is flat until the last quarter of the ramp then explodes
+        for offset in 0..40 {
+            let latency = if offset < 30 { 1.0 } else { 5.0 } + offset as f64 / 100.0;
+            curve.add(
+                Duration::from_secs(offset),
+                Some(Duration::from_secs_f64(latency)),
+                10,
+                false,
+            );
+        }
+        curve.add(Duration::from_secs(45), None, 0, true);
+        let points = curve.points();
+        assert_eq!(points.len(), 4);
+        assert_eq!(points[0].rate, 1.5);
+        assert_eq!(points[3].rate, 4.5);
+        assert_eq!(points[3].requests, 11);
+        assert_eq!(points[3].failed_requests, 1);
+        assert_eq!(points[0].token_throughput_secs, 10.0);
+        assert_eq!(curve.knee().map(|p| p.rate), Some(3.5));
+        assert!(curve.max_rate_at_slo().is_none());
+    }
+
+    #[test]
+    fn test_capacity_curve_slo() {
+        use crate::executors::ExecutorConfig;
+        use crate::requests::TextGenerationAggregatedResponse;
+        let mut results = BenchmarkResults::new(
+            "ramp".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                rate: Some(1.0),
+                ramp_to_rate: Some(5.0),
+                duration: Duration::from_secs(40),
+                ..Default::default()
+            },
+        );
+        let start = tokio::time::Instant::now();
+        // latency grows slowly then explodes in the last quarter of the ramp
+        for offset in 0..40 {
+            let latency = if offset < 30 {
+                1.0 + offset as f64 / 20.0
+            } else {
+                5.0
+            };
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start + Duration::from_secs(offset));
+            response.end_time = response
+                .start_time
+                .map(|sent| sent + Duration::from_secs_f64(latency));
+            response.times_to_tokens = vec![Duration::from_millis(100)];
+            results.add_response(response);
+        }
+        let slo = vec!["p99_e2e < 2000ms".parse().unwrap()];
+        let curve = CapacityCurve::from_results(&results, 4, &slo).unwrap();
+        let meets = curve
+            .points()
+            .iter()
+            .map(|p| p.meets_slo)
+            .collect::<Vec<_>>();
+        assert_eq!(meets, [true, true, false, false]);
+        // the knee ignores the SLO, both are reported
+        assert_eq!(curve.knee().map(|p| p.rate), Some(3.5));
+        assert_eq!(curve.max_rate_at_slo().map(|p| p.rate), Some(2.5));
+        let curve = CapacityCurve::from_results(&results, 4, &[]).unwrap();
+        assert!(curve.points().iter().all(|p| p.meets_slo));
+        assert!(curve.max_rate_at_slo().is_none());
+    }
+
+    #[test]
+    fn test_slo_capacity() {
+        use crate::executors::ExecutorConfig;
+        use crate::requests::TextGenerationAggregatedResponse;
+        let slo = vec!["p99_ttft < 500ms".parse().unwrap()];
+        let mut report = BenchmarkReport::new();
+        assert!(slo_capacity(&report, &slo).is_none());
+        // the max rate misses the SLO, half of it meets it
+        for (rate, ttft) in [(10.0, 800), (5.0, 200)] {
+            let mut results = BenchmarkResults::new(
+                format!("{SEARCH_STAGE_PREFIX}{rate:.2}req/s"),
+                ExecutorType::ConstantArrivalRate,
+                ExecutorConfig {
+                    rate: Some(rate),
+                    ..Default::default()
+                },
+            );
+            let mut response = TextGenerationAggregatedResponse::default();
+            let start = tokio::time::Instant::now();
+            response.start_time = Some(start);
+            response.end_time = Some(start + Duration::from_millis(1000));
+            response.times_to_tokens = vec![Duration::from_millis(ttft)];
+            results.add_response(response);
+            assert_eq!(meets_slo(&slo, &results), ttft < 500);
+            report.add_benchmark_result(results);
+        }
+        let capacity = slo_capacity(&report, &slo).unwrap();
+        assert_eq!(capacity.rate, Some(5.0));
+        assert_eq!(capacity.steps.len(), 2);
+        assert!(!capacity.steps[0].passed);
+        assert_eq!(capacity.slo, vec!["p99_ttft < 500ms".to_string()]);
+        assert!(slo_capacity(&report, &[]).is_none());
+
+        // the steps of a sweep are checked too, the highest rate meeting the SLO is reported
+        let mut report = BenchmarkReport::new();
+        for (rate, ttft) in [(1.0, 100), (2.0, 300), (3.0, 600)] {
+            let mut results = BenchmarkResults::new(
+                format!("constant@{rate:.2}req/s"),
+                ExecutorType::ConstantArrivalRate,
+                ExecutorConfig {
+                    rate: Some(rate),
+                    ..Default::default()
+                },
+            );
+            let mut response = TextGenerationAggregatedResponse::default();
+            let start = tokio::time::Instant::now();
+            response.start_time = Some(start);
+            response.end_time = Some(start + Duration::from_millis(1000));
+            response.times_to_tokens = vec![Duration::from_millis(ttft)];
+            results.add_response(response);
+            report.add_benchmark_result(results);
+        }
+        let capacity = slo_capacity(&report, &slo).unwrap();
+        assert_eq!(capacity.rate, Some(2.0));
+        assert_eq!(capacity.steps.len(), 3);
+    }
+
+    #[test]
+    fn test_tokenizer_check() {
+        use crate::executors::ExecutorConfig;
+        use crate::requests::TextGenerationAggregatedResponse;
+        let mut report = BenchmarkReport::new();
+        assert!(tokenizer_check(&report).is_none());
+        for (id, client_tokens, server_tokens) in [("warmup", 100, Some(130)), ("step", 100, None)]
+        {
+            let mut results = BenchmarkResults::new(
+                id.to_string(),
+                ExecutorType::ConstantVUs,
+                ExecutorConfig::default(),
+            );
+            for _ in 0..2 {
+                let mut response = TextGenerationAggregatedResponse::default();
+                response.num_generated_tokens = client_tokens;
+                response.usage_completion_tokens = server_tokens;
+                results.add_response(response);
+            }
+            assert_eq!(
+                TokenizerCheck::from_results(&results).is_some(),
+                server_tokens.is_some()
+            );
+            report.add_benchmark_result(results);
+        }
+        let check = tokenizer_check(&report).unwrap();
+        assert_eq!(check.step, "warmup");
+        assert_eq!(check.requests, 2);
+        assert_eq!((check.client_tokens, check.server_tokens), (200, 260));
+        assert!((check.error_factor - 1.3).abs() < 1e-9);
+        assert!(check.mismatch);
+        assert!(check.warning().contains("off by a factor of 1.30"));
+        assert!(!check.warning().contains("  "));
+
+        // within tolerance, e.g. end of sequence tokens counted by the server only
+        let mut results = BenchmarkResults::new(
+            "warmup".to_string(),
+            ExecutorType::ConstantVUs,
+            ExecutorConfig::default(),
+        );
+        let mut response = TextGenerationAggregatedResponse::default();
+        response.num_generated_tokens = 100;
+        response.usage_completion_tokens = Some(101);
+        results.add_response(response);
+        assert!(!TokenizerCheck::from_results(&results).unwrap().mismatch);
+    }
+
+    #[test]
+    fn test_bottleneck_hints() {
+        use crate::executors::ExecutorConfig;
+        use crate::requests::TextGenerationAggregatedResponse;
+        let mut report = BenchmarkReport::new();
+        // TTFT grows with the rate, ITL is flat and the last step completes half of its rate
+        for (rate, requests, ttft) in [(1.0, 10, 100), (2.0, 20, 200), (8.0, 40, 500)] {
+            let mut results = BenchmarkResults::new(
+                format!("constant_arrival_rate@{rate:.2}req/s"),
+                ExecutorType::ConstantArrivalRate,
+                ExecutorConfig {
+                    rate: Some(rate),
+                    ..Default::default()
+                },
+            );
+            let start = tokio::time::Instant::now();
+            if rate == 8.0 {
+                let mut response = TextGenerationAggregatedResponse::default();
+                response.start_time = Some(start);
+                response.end_time = Some(start + Duration::from_secs(1));
+                response.failed = true;
+                response.error_class = Some(ErrorClass::Timeout);
+                results.add_response(response);
+            }
+            for i in 0..requests {
+                let mut response = TextGenerationAggregatedResponse::default();
+                let sent = start + Duration::from_secs_f64(i as f64 * 10.0 / requests as f64);
+                response.start_time = Some(sent);
+                response.end_time = Some(sent + Duration::from_secs(1));
+                response.times_to_tokens = vec![
+                    Duration::from_millis(ttft),
+                    Duration::from_millis(20),
+                    Duration::from_millis(20),
+                ];
+                response.num_generated_tokens = 3;
+                results.add_response(response);
+            }
+            report.add_benchmark_result(results);
+        }
+        let hints = bottleneck_hints(&report);
+        let kinds = hints.iter().map(|hint| hint.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                HintKind::Timeouts,
+                HintKind::ThroughputPlateau,
+                HintKind::PrefillQueueing
+            ]
+        );
+        assert!(hints[1].message.contains("constant_arrival_rate@8.00req/s"));
+        assert!(hints[2]
+            .message
+            .starts_with("TTFT grows 5.0x from 1.00 to 8.00 req/s"));
+        assert!(bottleneck_hints(&BenchmarkReport::new()).is_empty());
+    }
+
+    #[test]
+    fn test_dominates() {
+        let point = |throughput: f64, latency: f64| ParetoPoint {
+            id: "test".to_string(),
+            token_throughput_secs: throughput,
+            e2e_latency_p99_secs: latency,
+        };
+        assert!(dominates(&point(100.0, 1.0), &point(50.0, 2.0)));
+        assert!(dominates(&point(100.0, 1.0), &point(100.0, 2.0)));
+        assert!(!dominates(&point(100.0, 1.0), &point(100.0, 1.0)));
+        assert!(!dominates(&point(100.0, 2.0), &point(50.0, 1.0)));
+    }
+}
@@ -1,13 +1,16 @@
 use crate::benchmark::Event as BenchmarkEvent;
 use crate::event::{terminal_event_task, AppEvent};
-use crate::flux::{Action, AppState, Dispatcher, Store};
+use crate::flux::{
+    Action, AlertThresholds, AppState, Dispatcher, Metric, MetricsBus, Store, Topic,
+};
+use crate::format::LatencyUnit;
 use crate::scheduler::ExecutorType;
 use crate::BenchmarkConfig;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::layout::{Constraint, Direction, Layout};
 use ratatui::text::Span;
 use ratatui::widgets::ListDirection::BottomToTop;
-use ratatui::widgets::{Cell, Dataset, List, ListItem, Row, Table};
+use ratatui::widgets::{Cell, Dataset, Gauge, List, ListItem, Row, Table};
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Rect},
@@ -31,12 +34,15 @@ pub struct App {
     dispatcher: Arc<Mutex<Dispatcher>>,
     receiver: Receiver<AppEvent>,
     benchmark_config: BenchmarkConfig,
+    alerts: AlertThresholds,
     stop_sender: broadcast::Sender<()>,
 }
 
 pub async fn run_console(
     benchmark_config: BenchmarkConfig,
+    alerts: AlertThresholds,
     mut receiver: UnboundedReceiver<BenchmarkEvent>,
+    metrics_bus: MetricsBus,
     stop_sender: broadcast::Sender<()>,
 ) {
     let (app_tx, app_rx) = mpsc::channel(8);
@@ -44,7 +50,7 @@ pub async fn run_console(
     let stop_receiver_signal = stop_sender.subscribe();
     tokio::spawn(terminal_event_task(250, app_tx, stop_receiver_signal));
 
-    let mut app = App::new(benchmark_config, app_rx, stop_sender.clone());
+    let mut app = App::new(benchmark_config, alerts, app_rx, stop_sender.clone());
     app.dispatcher
         .lock()
         .expect("lock")
@@ -54,6 +60,24 @@ pub async fn run_console(
             timestamp: chrono::Utc::now(),
         }));
     let dispatcher = app.dispatcher.clone();
+    let mut metrics = metrics_bus.subscribe(&[Topic::Request]);
+    let mut stop_receiver_signal = stop_sender.subscribe();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = async {
+                while let Some(metric) = metrics.recv().await {
+                    if let Metric::Request(sample) = metric {
+                        dispatcher.lock().expect("lock").dispatch(Action::AddRequestSample(sample));
+                    }
+                }
+            } => {}
+            _ = stop_receiver_signal.recv() => {}
+        }
+    });
+    let dispatcher = app.dispatcher.clone();
+    let format = app.benchmark_config.output_format.clone();
+    let throughput =
+        move |rate: Option<f64>| format.throughput(rate.unwrap_or_default(), "req/sec");
     let mut stop_receiver_signal = stop_sender.subscribe();
     let event_thread = tokio::spawn(async move {
         tokio::select! {
@@ -65,39 +89,76 @@ pub async fn run_console(
                                 id: event.id,
                                 status: BenchmarkStatus::Running,
                                 progress: 0.0,
-                                throughput: "0".to_string(),
+                                throughput: throughput(None),
                                 successful_requests: 0,
                                 failed_requests: 0,
+                                top_failure: None,
+                                goodput: None,
+                                target_rate: None,
+                                remaining: None,
                             }));
                         }
-                        BenchmarkEvent::BenchmarkProgress(event) => {
-                            let (successful_requests,failed_requests) = (event.successful_requests,event.failed_requests);
+                        BenchmarkEvent::BenchmarkProgress(progress) => {
                             dispatcher.lock().expect("lock").dispatch(Action::AddBenchmark(BenchmarkUI {
-                                id: event.id,
+                                id: progress.id,
                                 status: BenchmarkStatus::Running,
-                                progress: event.progress,
-                                throughput: event.request_throughput.map_or("0".to_string(), |e| format!("{e:.2}")),
-                                successful_requests,
-                                failed_requests,
+                                progress: progress.progress,
+                                throughput: throughput(Some(progress.requests_throughput)),
+                                successful_requests: progress.successful_requests,
+                                failed_requests: progress.failed_requests,
+                                top_failure: None,
+                                goodput: None,
+                                target_rate: progress.target_rate,
+                                remaining: Some(progress.remaining),
                             }));
                         }
+                        BenchmarkEvent::Heartbeat(heartbeat) => {
+                            // the progress table already shows live stages, only report stalled ones
+                            if heartbeat.stalled {
+                                dispatcher.lock().expect("lock").dispatch(Action::LogMessage(LogMessageUI {
+                                    message: format!("No response in {} for {}s", heartbeat.id, heartbeat.since_last_response.as_secs()),
+                                    level: LogLevel::Warning,
+                                    timestamp: chrono::Utc::now(),
+                                }));
+                            }
+                        }
                         BenchmarkEvent::BenchmarkEnd(event) => {
                             dispatcher.lock().expect("lock").dispatch(Action::LogMessage(LogMessageUI {
                                 message: format!("Benchmark {} ended", event.id),
                                 level: LogLevel::Info,
                                 timestamp: chrono::Utc::now(),
                             }));
-                            if let Some(results) = event.results {
-                                let (successful_requests,failed_requests) = (results.successful_requests() as u64,results.failed_requests() as u64);
+                            if let Some(summary) = event.summary {
+                                let (successful_requests,failed_requests) = (summary.successful_requests,summary.failed_requests);
+                                let top_failure = summary.failures.iter().max_by_key(|(_, count)| **count).map(|(class, _)| class.to_string());
+                                let goodput = summary.goodput.map(|goodput| throughput(Some(goodput)));
                                 dispatcher.lock().expect("lock").dispatch(Action::AddBenchmark(BenchmarkUI {
                                     id: event.id,
                                     status: BenchmarkStatus::Completed,
                                     progress: 100.0,
-                                    throughput: event.request_throughput.map_or("0".to_string(), |e| format!("{e:.2}")),
+                                    throughput: throughput(event.request_throughput),
                                     successful_requests,
                                     failed_requests,
+                                    top_failure,
+                                    goodput,
+                                    target_rate: None,
+                                    remaining: None,
+                                }));
+                                dispatcher.lock().expect("lock").dispatch(Action::AddBenchmarkResults(summary));
+                            } else {
+                                // stages reported per step have no summary of their own
+                                dispatcher.lock().expect("lock").dispatch(Action::AddBenchmark(BenchmarkUI {
+                                    id: event.id,
+                                    status: BenchmarkStatus::Completed,
+                                    progress: 100.0,
+                                    throughput: throughput(event.request_throughput),
+                                    successful_requests: event.successful_requests,
+                                    failed_requests: event.failed_requests,
+                                    top_failure: None,
+                                    goodput: None,
+                                    target_rate: None,
+                                    remaining: None,
                                 }));
-                                dispatcher.lock().expect("lock").dispatch(Action::AddBenchmarkResults(results));
                             }
                         }
                         BenchmarkEvent::Message(event) => {
@@ -107,6 +168,9 @@ pub async fn run_console(
                                 timestamp: event.timestamp,
                             }));
                         }
+                        BenchmarkEvent::DownloadProgress(event) => {
+                            dispatcher.lock().expect("lock").dispatch(Action::DownloadProgress(event));
+                        }
                         BenchmarkEvent::BenchmarkReportEnd => {
                             dispatcher.lock().expect("lock").dispatch(Action::LogMessage(LogMessageUI {
                                 message: "Benchmark report saved.".to_string(),
@@ -146,6 +210,7 @@ pub async fn run_console(
 impl App {
     pub fn new(
         benchmark_config: BenchmarkConfig,
+        alerts: AlertThresholds,
         receiver: Receiver<AppEvent>,
         stop_sender: Sender<()>,
     ) -> App {
@@ -157,6 +222,7 @@ impl App {
             dispatcher: dispatcher.clone(),
             receiver,
             benchmark_config,
+            alerts,
             stop_sender,
         }
     }
@@ -174,7 +240,7 @@ impl App {
     }
     async fn handle_events(&mut self) -> io::Result<()> {
         match self.receiver.recv().await {
-            None => Err(io::Error::new(io::ErrorKind::Other, "No event")),
+            None => Err(io::Error::other("No event")),
             Some(event) => match event {
                 AppEvent::Tick => Ok(()),
                 AppEvent::Key(key_event) => self.handle_key_event(key_event),
@@ -206,10 +272,10 @@ impl App {
         let token_throughput_rate = state
             .results
             .iter()
-            .filter_map(|r| match r.executor_type() {
-                ExecutorType::ConstantArrivalRate => {
-                    let throughput = r.token_throughput_secs().unwrap_or(0.0);
-                    Some((r.executor_config().rate.unwrap(), throughput))
+            .filter_map(|r| match r.executor_type {
+                ExecutorType::ConstantArrivalRate | ExecutorType::TraceReplay => {
+                    let throughput = r.token_throughput_secs.unwrap_or(0.0);
+                    Some((r.rate.unwrap(), throughput))
                 }
                 ExecutorType::ConstantVUs => None,
             })
@@ -217,24 +283,21 @@ impl App {
         let token_throughput_vus = state
             .results
             .iter()
-            .filter_map(|r| match r.executor_type() {
+            .filter_map(|r| match r.executor_type {
                 ExecutorType::ConstantVUs => {
-                    let throughput = r.token_throughput_secs().unwrap_or(0.0);
-                    Some((r.executor_config().max_vus as f64, throughput))
+                    let throughput = r.token_throughput_secs.unwrap_or(0.0);
+                    Some((r.max_vus as f64, throughput))
                 }
-                ExecutorType::ConstantArrivalRate => None,
+                ExecutorType::ConstantArrivalRate | ExecutorType::TraceReplay => None,
             })
             .collect::<Vec<_>>();
         let inter_token_latency_rate = state
             .results
             .iter()
-            .filter_map(|r| match r.executor_type() {
-                ExecutorType::ConstantArrivalRate => {
-                    let latency = r
-                        .inter_token_latency_avg()
-                        .unwrap_or_default()
-                        .as_secs_f64();
-                    Some((r.executor_config().rate.unwrap(), latency))
+            .filter_map(|r| match r.executor_type {
+                ExecutorType::ConstantArrivalRate | ExecutorType::TraceReplay => {
+                    let latency = r.inter_token_latency_avg.unwrap_or_default().as_secs_f64();
+                    Some((r.rate.unwrap(), latency))
                 }
                 ExecutorType::ConstantVUs => None,
             })
@@ -242,15 +305,12 @@ impl App {
         let inter_token_latency_vus = state
             .results
             .iter()
-            .filter_map(|r| match r.executor_type() {
+            .filter_map(|r| match r.executor_type {
                 ExecutorType::ConstantVUs => {
-                    let latency = r
-                        .inter_token_latency_avg()
-                        .unwrap_or_default()
-                        .as_secs_f64();
-                    Some((r.executor_config().max_vus as f64, latency))
+                    let latency = r.inter_token_latency_avg.unwrap_or_default().as_secs_f64();
+                    Some((r.max_vus as f64, latency))
                 }
-                ExecutorType::ConstantArrivalRate => None,
+                ExecutorType::ConstantArrivalRate | ExecutorType::TraceReplay => None,
             })
             .collect::<Vec<_>>();
         HashMap::from([
@@ -275,12 +335,16 @@ impl Widget for &App {
 
         let main_layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Length(1), Constraint::Min(20)])
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Length(3),
+                Constraint::Min(20),
+            ])
             .split(area);
         let bottom_layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(main_layout[1]);
+            .split(main_layout[2]);
         let steps_graph_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
@@ -327,8 +391,13 @@ impl Widget for &App {
             None => "Automatic".to_string(),
             Some(_) => "Manual".to_string(),
         };
+        let name = self
+            .benchmark_config
+            .name
+            .as_ref()
+            .map_or("".to_string(), |name| format!("{name} | "));
         let config_text = Text::from(vec![Line::from(vec![
-            format!("Benchmark: {kind} | Max VUs: {max_vus} | Duration: {duration} sec | Rates: {rates} | Warmup: {warmup} sec",
+            format!("{name}Benchmark: {kind} | Max VUs: {max_vus} | Duration: {duration} sec | Rates: {rates} | Warmup: {warmup} sec",
                     kind = self.benchmark_config.benchmark_kind,
                     max_vus = self.benchmark_config.max_vus,
                     duration = self.benchmark_config.duration.as_secs_f64(),
@@ -337,6 +406,72 @@ impl Widget for &App {
         ])]);
         Paragraph::new(config_text.clone()).render(main_layout[0], buf);
 
+        // DOWNLOAD progress, shown in place of the live metrics until the benchmark starts
+        if let Some(download) = &state.download {
+            let size = |bytes: u64| format!("{:.1} MB", bytes as f64 / 1e6);
+            let label = match download.total {
+                Some(total) => format!(
+                    "{} {} / {}",
+                    download.filename,
+                    size(download.downloaded),
+                    size(total)
+                ),
+                None => format!("{} {}", download.filename, size(download.downloaded)),
+            };
+            Gauge::default()
+                .block(
+                    Block::bordered()
+                        .title(Title::from("Downloading".bold()).alignment(Alignment::Center))
+                        .border_set(border::THICK),
+                )
+                .gauge_style(ratatui::style::Style::default().light_blue())
+                .ratio(download.percent().unwrap_or_default().clamp(0.0, 100.0) / 100.0)
+                .label(label)
+                .render(main_layout[1], buf);
+        } else {
+            let live_block = Block::bordered()
+                .title(Title::from("Live (last 10s)".bold()).alignment(Alignment::Center))
+                .border_set(border::THICK);
+            let format = &self.benchmark_config.output_format;
+            let error_rate = state.live.error_rate();
+            let e2e_p99 = state.live.e2e_latency_p99();
+            let error_rate_alert = self.alerts.error_rate.is_some_and(|max| error_rate > max);
+            let e2e_alert = self
+                .alerts
+                .e2e_latency_p99
+                .is_some_and(|max| e2e_p99.is_some_and(|p99| p99 > max));
+            // flash alerts every second
+            let flash = chrono::Utc::now().timestamp() % 2 == 0;
+            let alert = |span: Span<'static>, alert: bool| match (alert, flash) {
+                (false, _) => span.white().bold(),
+                (true, true) => span.white().on_red().bold(),
+                (true, false) => span.red().bold(),
+            };
+            Paragraph::new(Line::from(vec![
+                format
+                    .throughput(state.live.token_throughput_secs(), "tokens/s")
+                    .green()
+                    .bold(),
+                Span::raw("  |  "),
+                alert(
+                    Span::raw(format!("Errors {}", format.percent(error_rate))),
+                    error_rate_alert,
+                ),
+                Span::raw("  |  "),
+                alert(
+                    Span::raw(format!(
+                        "E2E p99 {}",
+                        e2e_p99.map_or("N/A".to_string(), |d| format
+                            .latency(d, LatencyUnit::Seconds))
+                    )),
+                    e2e_alert,
+                ),
+            ]))
+            .alignment(Alignment::Center)
+            .block(live_block)
+            .render(main_layout[1], buf);
+        }
+
         // STEPS
         let steps_block_title = Title::from("Benchmark steps".bold());
         let steps_block = Block::bordered()
@@ -362,8 +497,21 @@ impl Widget for &App {
                     b.id.clone().white(),
                     b.status.to_string().white(),
                     format!("{:4.0}%", b.progress).white(),
+                    b.remaining
+                        .map(|remaining| {
+                            let secs = remaining.as_secs();
+                            format!("{}m{:02}s", secs / 60, secs % 60)
+                        })
+                        .unwrap_or_default()
+                        .white(),
+                    b.target_rate
+                        .map(|rate| format!("{rate:.2} req/s"))
+                        .unwrap_or_default()
+                        .white(),
                     error_rate,
-                    format!("{:>6.6} req/sec avg", b.throughput).green().bold(),
+                    b.top_failure.clone().unwrap_or_default().light_red(),
+                    format!("{} avg", b.throughput).green().bold(),
+                    b.goodput.clone().unwrap_or_default().cyan().bold(),
                 ];
                 Row::new(cells)
             })
@@ -372,8 +520,12 @@ impl Widget for &App {
             Constraint::Length(30),
             Constraint::Length(10),
             Constraint::Length(5),
+            Constraint::Length(7),
+            Constraint::Length(12),
             Constraint::Length(5),
-            Constraint::Length(20),
+            Constraint::Length(14),
+            Constraint::Length(24),
+            Constraint::Length(18),
         ];
         // steps table
         Table::new(step_rows, widths)
@@ -381,8 +533,12 @@ impl Widget for &App {
                 Cell::new(Line::from("Bench").alignment(Alignment::Left)),
                 Cell::new(Line::from("Status").alignment(Alignment::Left)),
                 Cell::new(Line::from("%").alignment(Alignment::Left)),
+                Cell::new(Line::from("Left").alignment(Alignment::Left)),
+                Cell::new(Line::from("Target").alignment(Alignment::Left)),
                 Cell::new(Line::from("Err").alignment(Alignment::Left)),
+                Cell::new(Line::from("Top error").alignment(Alignment::Left)),
                 Cell::new(Line::from("Throughput").alignment(Alignment::Left)),
+                Cell::new(Line::from("Goodput").alignment(Alignment::Left)),
             ]))
             .block(steps_block)
             .render(steps_graph_layout[0], buf);
@@ -469,6 +625,13 @@ pub(crate) struct BenchmarkUI {
     throughput: String,
     successful_requests: u64,
     failed_requests: u64,
+    /// Most frequent failure cause, once the step completed
+    top_failure: Option<String>,
+    /// Throughput of the requests meeting the request SLO, once the step completed
+    goodput: Option<String>,
+    /// Arrival rate currently targeted and time left, while the step runs
+    target_rate: Option<f64>,
+    remaining: Option<std::time::Duration>,
 }
 
 #[derive(Clone, strum_macros::Display)]
@@ -0,0 +1,263 @@
+use crate::results::BenchmarkResults;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Stage metric an assertion is checked against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AssertionMetric {
+    /// Failed requests in percent
+    ErrorRate,
+    /// Successful requests per second
+    RequestThroughput,
+    /// Generated tokens per second
+    TokenThroughput,
+    /// Responses flagged as repetitive in percent, requires repetition detection
+    RepetitionRate,
+    /// Correct answers in percent, requires answer scoring
+    Accuracy,
+    /// Average latency in milliseconds
+    LatencyAvg(LatencyKind),
+    /// Latency percentile (0.0-1.0) in milliseconds
+    LatencyPercentile(LatencyKind, f64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LatencyKind {
+    TimeToFirstToken,
+    InterTokenLatency,
+    E2eLatency,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    LessThan,
+    LessOrEqual,
+    GreaterThan,
+    GreaterOrEqual,
+}
+
+/// Check evaluated on every stage (warmup excluded) once it ends, written as
+/// `<metric> <op> <value>` such as `error_rate < 0.5%` or `p90_itl < 40ms`.
+/// Metrics are `error_rate` (percent), `request_throughput` (req/s), `token_throughput`
+/// (tokens/s), `repetition_rate` and `accuracy` (percent) and `avg_<latency>` or `p<NN>_<latency>`
+/// for the `ttft`, `itl` and `e2e` latencies, in milliseconds unless suffixed with `s`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assertion {
+    pub metric: AssertionMetric,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    expression: String,
+}
+
+impl FromStr for Assertion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = s.trim().to_string();
+        let invalid = || anyhow::anyhow!("Invalid assertion: {expression}");
+        let op_start = expression.find(['<', '>']).ok_or_else(invalid)?;
+        let (metric, rest) = expression.split_at(op_start);
+        let (comparison, value) = if let Some(value) = rest.strip_prefix("<=") {
+            (Comparison::LessOrEqual, value)
+        } else if let Some(value) = rest.strip_prefix(">=") {
+            (Comparison::GreaterOrEqual, value)
+        } else if let Some(value) = rest.strip_prefix('<') {
+            (Comparison::LessThan, value)
+        } else {
+            (Comparison::GreaterThan, &rest[1..])
+        };
+        let metric = parse_metric(metric.trim()).ok_or_else(invalid)?;
+        let value = value.trim();
+        let threshold = match metric {
+            AssertionMetric::ErrorRate
+            | AssertionMetric::RepetitionRate
+            | AssertionMetric::Accuracy => value.strip_suffix('%').unwrap_or(value).trim().parse(),
+            AssertionMetric::LatencyAvg(_) | AssertionMetric::LatencyPercentile(..) => {
+                match value.strip_suffix("ms") {
+                    Some(ms) => ms.trim().parse(),
+                    None => match value.strip_suffix('s') {
+                        Some(secs) => secs.trim().parse::<f64>().map(|s| s * 1000.0),
+                        None => value.parse(),
+                    },
+                }
+            }
+            _ => value.parse(),
+        }
+        .map_err(|_| invalid())?;
+        Ok(Assertion {
+            metric,
+            comparison,
+            threshold,
+            expression,
+        })
+    }
+}
+
+impl Display for Assertion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl Serialize for Assertion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.expression)
+    }
+}
+
+fn parse_metric(name: &str) -> Option<AssertionMetric> {
+    match name.to_lowercase().as_str() {
+        "error_rate" => return Some(AssertionMetric::ErrorRate),
+        "request_throughput" => return Some(AssertionMetric::RequestThroughput),
+        "token_throughput" => return Some(AssertionMetric::TokenThroughput),
+        "repetition_rate" => return Some(AssertionMetric::RepetitionRate),
+        "accuracy" => return Some(AssertionMetric::Accuracy),
+        _ => {}
+    }
+    let (aggregate, latency) = name.split_once('_')?;
+    let latency = match latency.to_lowercase().as_str() {
+        "ttft" => LatencyKind::TimeToFirstToken,
+        "itl" => LatencyKind::InterTokenLatency,
+        "e2e" => LatencyKind::E2eLatency,
+        _ => return None,
+    };
+    if aggregate.eq_ignore_ascii_case("avg") {
+        return Some(AssertionMetric::LatencyAvg(latency));
+    }
+    let percentile: f64 = aggregate.strip_prefix(['p', 'P'])?.parse().ok()?;
+    if !(0.0..=100.0).contains(&percentile) {
+        return None;
+    }
+    Some(AssertionMetric::LatencyPercentile(
+        latency,
+        percentile / 100.0,
+    ))
+}
+
+impl Assertion {
+    /// Value of the metric for a stage, `None` if the stage has no response to compute it from
+    pub fn value(&self, results: &BenchmarkResults) -> Option<f64> {
+        let latency = |kind: LatencyKind, percentile: Option<f64>| {
+            // the averages are zero without a successful response
+            if results.successful_requests() == 0 {
+                return None;
+            }
+            let latency = match (kind, percentile) {
+                (LatencyKind::TimeToFirstToken, None) => results.time_to_first_token_avg(),
+                (LatencyKind::TimeToFirstToken, Some(p)) => {
+                    results.time_to_first_token_percentile(p)
+                }
+                (LatencyKind::InterTokenLatency, None) => results.inter_token_latency_avg(),
+                (LatencyKind::InterTokenLatency, Some(p)) => {
+                    results.inter_token_latency_percentile(p)
+                }
+                (LatencyKind::E2eLatency, None) => results.e2e_latency_avg(),
+                (LatencyKind::E2eLatency, Some(p)) => results.e2e_latency_percentile(p),
+            };
+            latency.ok().map(|l| l.as_secs_f64() * 1000.0)
+        };
+        match self.metric {
+            AssertionMetric::ErrorRate => match results.total_requests() {
+                0 => None,
+                total => Some(100.0 * results.failed_requests() as f64 / total as f64),
+            },
+            AssertionMetric::RequestThroughput => results.successful_request_rate().ok(),
+            AssertionMetric::TokenThroughput => results.token_throughput_secs().ok(),
+            AssertionMetric::RepetitionRate => results
+                .repetition()
+                .map(|r| 100.0 * r.repetitive_responses as f64 / r.requests as f64),
+            AssertionMetric::Accuracy => results.accuracy().map(|a| 100.0 * a.accuracy),
+            AssertionMetric::LatencyAvg(kind) => latency(kind, None),
+            AssertionMetric::LatencyPercentile(kind, p) => latency(kind, Some(p)),
+        }
+    }
+
+    /// A missing value fails the assertion
+    pub fn check(&self, value: Option<f64>) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+        match self.comparison {
+            Comparison::LessThan => value < self.threshold,
+            Comparison::LessOrEqual => value <= self.threshold,
+            Comparison::GreaterThan => value > self.threshold,
+            Comparison::GreaterOrEqual => value >= self.threshold,
+        }
+    }
+
+    pub fn evaluate(&self, results: &BenchmarkResults) -> AssertionResult {
+        let value = self.value(results);
+        AssertionResult {
+            expression: self.expression.clone(),
+            value,
+            passed: self.check(value),
+        }
+    }
+}
+
+/// Outcome of an assertion on a stage. The value is in the unit of the metric: percent for
+/// `error_rate`, `repetition_rate` and `accuracy`, milliseconds for latencies.
+#[derive(Clone, Debug, Serialize)]
+pub struct AssertionResult {
+    pub expression: String,
+    pub value: Option<f64>,
+    pub passed: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_assertion() {
+        let assertion: Assertion = "error_rate < 0.5%".parse().unwrap();
+        assert_eq!(assertion.metric, AssertionMetric::ErrorRate);
+        assert_eq!(assertion.comparison, Comparison::LessThan);
+        assert_eq!(assertion.threshold, 0.5);
+        assert!(assertion.check(Some(0.2)));
+        assert!(!assertion.check(Some(0.5)));
+        assert!(!assertion.check(None));
+        let assertion: Assertion = "p90_itl<=40ms".parse().unwrap();
+        assert_eq!(
+            assertion.metric,
+            AssertionMetric::LatencyPercentile(LatencyKind::InterTokenLatency, 0.9)
+        );
+        assert_eq!(assertion.comparison, Comparison::LessOrEqual);
+        assert!(assertion.check(Some(40.0)));
+        let assertion: Assertion = "avg_e2e < 1.5s".parse().unwrap();
+        assert_eq!(assertion.threshold, 1500.0);
+        let assertion: Assertion = " token_throughput >= 1000 ".parse().unwrap();
+        assert_eq!(assertion.to_string(), "token_throughput >= 1000");
+        assert!(assertion.check(Some(1000.0)));
+        assert!("p90_itl".parse::<Assertion>().is_err());
+        assert!("p101_ttft < 10".parse::<Assertion>().is_err());
+        assert!("latency < 10".parse::<Assertion>().is_err());
+        assert!("error_rate < high".parse::<Assertion>().is_err());
+    }
+
+    #[test]
+    fn test_assertion_value_failed_requests() {
+        use crate::executors::ExecutorConfig;
+        use crate::requests::TextGenerationAggregatedResponse;
+        use crate::scheduler::ExecutorType;
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantVUs,
+            ExecutorConfig::default(),
+        );
+        for _ in 0..2 {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.failed = true;
+            results.add_response(response);
+        }
+        for assertion in ["avg_ttft < 500ms", "avg_itl < 50ms", "p90_e2e < 2s"] {
+            let assertion: Assertion = assertion.parse().unwrap();
+            let value = assertion.value(&results);
+            assert_eq!(value, None);
+            assert!(!assertion.check(value));
+        }
+        let assertion: Assertion = "error_rate < 1%".parse().unwrap();
+        assert_eq!(assertion.value(&results), Some(100.0));
+    }
+}
@@ -0,0 +1,366 @@
+use crate::benchmark::{Event, MessageEvent};
+use crate::requests::{OpenAITextGenerationBackend, TextRequestGenerator};
+use log::{debug, info, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+const COMPLETION_WINDOW: &str = "24h";
+
+/// Runs a set of requests through an OpenAI-compatible Batch API: upload a JSONL file of requests,
+/// create a batch, poll until it reaches a terminal state and download its output
+pub struct BatchRunner {
+    backend: OpenAITextGenerationBackend,
+    poll_interval: Duration,
+    max_wait: Duration,
+}
+
+#[derive(Deserialize)]
+struct FileObject {
+    id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct BatchObject {
+    id: String,
+    status: String,
+    output_file_id: Option<String>,
+    request_counts: Option<BatchRequestCounts>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct BatchRequestCounts {
+    total: u64,
+    completed: u64,
+    failed: u64,
+}
+
+// one line of the batch output file
+#[derive(Deserialize)]
+struct BatchOutputLine {
+    response: Option<BatchOutputResponse>,
+}
+
+#[derive(Deserialize)]
+struct BatchOutputResponse {
+    status_code: u16,
+    body: Option<BatchOutputBody>,
+}
+
+#[derive(Deserialize)]
+struct BatchOutputBody {
+    usage: Option<BatchUsage>,
+}
+
+#[derive(Deserialize)]
+struct BatchUsage {
+    prompt_tokens: Option<u64>,
+    completion_tokens: Option<u64>,
+}
+
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchResults {
+    pub batch_id: String,
+    pub status: String,
+    pub total_requests: u64,
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    pub prompt_tokens: u64,
+    pub generated_tokens: u64,
+    /// Time to upload the requests file and create the batch
+    #[serde(rename = "submit_duration_ms")]
+    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
+    pub submit_duration: Duration,
+    /// Time from the start of the upload until the batch reached a terminal state
+    #[serde(rename = "turnaround_ms")]
+    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
+    pub turnaround: Duration,
+    pub request_throughput_secs: f64,
+    pub token_throughput_secs: f64,
+}
+
+impl BatchResults {
+    fn new(
+        batch: &BatchObject,
+        output: &str,
+        submit_duration: Duration,
+        turnaround: Duration,
+    ) -> BatchResults {
+        let mut successful_requests = 0;
+        let mut failed_requests = 0;
+        let mut prompt_tokens = 0;
+        let mut generated_tokens = 0;
+        for line in output.lines().filter(|l| !l.trim().is_empty()) {
+            let response = serde_json::from_str::<BatchOutputLine>(line)
+                .ok()
+                .and_then(|l| l.response);
+            match response {
+                Some(response) if response.status_code == 200 => {
+                    successful_requests += 1;
+                    if let Some(usage) = response.body.and_then(|b| b.usage) {
+                        prompt_tokens += usage.prompt_tokens.unwrap_or_default();
+                        generated_tokens += usage.completion_tokens.unwrap_or_default();
+                    }
+                }
+                _ => failed_requests += 1,
+            }
+        }
+        let counts = batch.request_counts.as_ref();
+        let total_requests = counts.map_or(successful_requests + failed_requests, |c| c.total);
+        // requests missing from the output file (e.g. expired batch) are failures too
+        let failed_requests =
+            failed_requests.max(total_requests.saturating_sub(successful_requests));
+        let secs = turnaround.as_secs_f64();
+        BatchResults {
+            batch_id: batch.id.clone(),
+            status: batch.status.clone(),
+            total_requests,
+            successful_requests,
+            failed_requests,
+            prompt_tokens,
+            generated_tokens,
+            submit_duration,
+            turnaround,
+            request_throughput_secs: successful_requests as f64 / secs,
+            token_throughput_secs: generated_tokens as f64 / secs,
+        }
+    }
+}
+
+impl BatchRunner {
+    /// `max_wait` bounds the time spent waiting for the batch, it is cancelled afterwards
+    pub fn new(
+        backend: OpenAITextGenerationBackend,
+        poll_interval: Duration,
+        max_wait: Duration,
+    ) -> Self {
+        Self {
+            backend,
+            poll_interval,
+            max_wait,
+        }
+    }
+
+    pub async fn run(
+        &self,
+        requests: &dyn TextRequestGenerator,
+        size: u64,
+        event_bus: mpsc::UnboundedSender<Event>,
+    ) -> anyhow::Result<BatchResults> {
+        let mut input = String::new();
+        for i in 0..size {
+            let request = requests.generate_request();
+            let line = serde_json::json!({
+                "custom_id": format!("request-{i}"),
+                "method": "POST",
+                "url": self.backend.options.endpoint.path(),
+                "body": self.backend.request_body(&request, false)?,
+            });
+            input.push_str(&line.to_string());
+            input.push('\n');
+        }
+        let start = Instant::now();
+        let file = self.upload(input).await?;
+        let mut batch = self.create(&file.id).await?;
+        let submit_duration = start.elapsed();
+        info!("Created batch {} with {} requests", batch.id, size);
+        send_message(&event_bus, format!("Batch {} submitted", batch.id));
+        while !is_terminal(&batch.status) {
+            if start.elapsed() > self.max_wait {
+                warn!("Batch {} did not complete in time, cancelling", batch.id);
+                self.cancel(&batch.id).await?;
+                return Err(anyhow::anyhow!(
+                    "batch {} did not complete within {:?} (status: {})",
+                    batch.id,
+                    self.max_wait,
+                    batch.status
+                ));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+            batch = self.retrieve(&batch.id).await?;
+            let counts = batch.request_counts.as_ref();
+            debug!("Batch {} status: {} {:?}", batch.id, batch.status, counts);
+            send_message(
+                &event_bus,
+                format!(
+                    "Batch {} {}: {}/{} requests done",
+                    batch.id,
+                    batch.status,
+                    counts.map_or(0, |c| c.completed + c.failed),
+                    counts.map_or(size, |c| c.total)
+                ),
+            );
+        }
+        let turnaround = start.elapsed();
+        let output = match &batch.output_file_id {
+            Some(file_id) => self.download(file_id).await?,
+            None => String::new(),
+        };
+        Ok(BatchResults::new(
+            &batch,
+            &output,
+            submit_duration,
+            turnaround,
+        ))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v1/{}", self.backend.base_url, path)
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let mut request = self
+            .backend
+            .client
+            .request(method, self.url(path))
+            .bearer_auth(&self.backend.api_key);
+        for (name, value) in &self.backend.options.headers {
+            request = request.header(name, value);
+        }
+        request
+    }
+
+    async fn upload(&self, input: String) -> anyhow::Result<FileObject> {
+        let boundary = format!("{:032x}", rand::thread_rng().gen::<u128>());
+        let body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\nbatch\r\n\
+             --{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"batch.jsonl\"\r\n\
+             Content-Type: application/jsonl\r\n\r\n{input}\r\n--{boundary}--\r\n"
+        );
+        Ok(self
+            .request(reqwest::Method::POST, "files")
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn create(&self, input_file_id: &str) -> anyhow::Result<BatchObject> {
+        Ok(self
+            .request(reqwest::Method::POST, "batches")
+            .json(&serde_json::json!({
+                "input_file_id": input_file_id,
+                "endpoint": self.backend.options.endpoint.path(),
+                "completion_window": COMPLETION_WINDOW,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn retrieve(&self, id: &str) -> anyhow::Result<BatchObject> {
+        Ok(self
+            .request(reqwest::Method::GET, &format!("batches/{id}"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    async fn cancel(&self, id: &str) -> anyhow::Result<()> {
+        self.request(reqwest::Method::POST, &format!("batches/{id}/cancel"))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn download(&self, file_id: &str) -> anyhow::Result<String> {
+        Ok(self
+            .request(reqwest::Method::GET, &format!("files/{file_id}/content"))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    }
+}
+
+fn is_terminal(status: &str) -> bool {
+    matches!(status, "completed" | "failed" | "expired" | "cancelled")
+}
+
+fn send_message(event_bus: &mpsc::UnboundedSender<Event>, message: String) {
+    let _ = event_bus.send(Event::Message(MessageEvent {
+        message,
+        timestamp: chrono::Utc::now(),
+        level: log::Level::Info,
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::{DummyTextRequestGenerator, RequestOptions};
+    use std::sync::Arc;
+    use tokenizers::models::bpe::BPE;
+    use tokenizers::Tokenizer;
+
+    #[tokio::test]
+    async fn test_batch_runner() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("POST", "/v1/files")
+            .match_header("x-tenant", "benchmark")
+            .match_body(mockito::Matcher::Regex(
+                "\"custom_id\":\"request-2\"".to_string(),
+            ))
+            .with_body(r#"{"id": "file-in"}"#)
+            .create_async()
+            .await;
+        s.mock("POST", "/v1/batches")
+            .match_header("x-tenant", "benchmark")
+            .with_body(r#"{"id": "batch-1", "status": "validating"}"#)
+            .create_async()
+            .await;
+        s.mock("GET", "/v1/batches/batch-1")
+            .with_body(
+                r#"{"id": "batch-1", "status": "completed", "output_file_id": "file-out",
+                "request_counts": {"total": 3, "completed": 2, "failed": 1}}"#,
+            )
+            .create_async()
+            .await;
+        s.mock("GET", "/v1/files/file-out/content")
+            .with_body(
+                r#"{"custom_id": "request-0", "response": {"status_code": 200, "body": {"usage": {"prompt_tokens": 10, "completion_tokens": 20}}}}
+{"custom_id": "request-1", "response": {"status_code": 200, "body": {"usage": {"prompt_tokens": 10, "completion_tokens": 30}}}}
+"#,
+            )
+            .create_async()
+            .await;
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            s.url(),
+            "model".to_string(),
+            Arc::new(Tokenizer::new(BPE::default())),
+            Duration::from_secs(10),
+            RequestOptions {
+                headers: vec![("X-Tenant".to_string(), "benchmark".to_string())],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let runner = BatchRunner::new(backend, Duration::from_millis(10), Duration::from_secs(10));
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let results = runner
+            .run(&DummyTextRequestGenerator::new(), 3, tx)
+            .await
+            .unwrap();
+        assert_eq!(results.status, "completed");
+        assert_eq!(results.total_requests, 3);
+        assert_eq!(results.successful_requests, 2);
+        assert_eq!(results.failed_requests, 1);
+        assert_eq!(results.generated_tokens, 50);
+    }
+}
@@ -1,22 +1,68 @@
-use crate::requests::{TextGenerationBackend, TextRequestGenerator, TokenizeOptions};
-use crate::results::{BenchmarkReport, BenchmarkResults};
-use crate::scheduler::{ExecutorType, SchedulerProgress};
-use crate::{executors, scheduler};
-use log::{debug, info};
+use crate::analysis::{self, CapacityCurve, TokenizerCheck, SEARCH_STAGE_PREFIX};
+use crate::assertions::Assertion;
+use crate::comparison::Baseline;
+use crate::event::StageSummary;
+use crate::executors::{ArrivalDistribution, BurstProfile, RateCurve, StepProfile, Trace, VuCap};
+use crate::flux::{Metric, MetricsBus, Topic};
+use crate::format::OutputFormat;
+use crate::headroom::{HeadroomMonitor, HeadroomPolicy};
+use crate::hooks::{HookContext, HookKind, HooksConfig};
+use crate::labels::StageLabels;
+use crate::pause::DispatchPause;
+use crate::processors::ResponseProcessors;
+use crate::requests::{
+    BackendKind, OpenAIEndpoint, PromptTemplate, RequestTimeouts, RetryPolicy, Tenant,
+    TextGenerationBackend, TextRequestGenerator, TokenizeOptions,
+};
+use crate::results::BenchmarkErrors::NoResponses;
+use crate::results::{
+    AnswerRegexes, AnswerScoring, BenchmarkReport, BenchmarkResults, RequestSlo, BASE_METRICS,
+};
+use crate::scheduler::{ExecutorType, Heartbeat, SchedulerProgress};
+use crate::scripting::DerivedMetric;
+use crate::server_metrics::{MetricsSnapshot, ServerMetricsScraper, VllmStatsCollector};
+use crate::sink::ResultsSink;
+use crate::writers::RawResultsFormat;
+use crate::{executors, hooks, labels, scheduler};
+use log::{debug, info, warn};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use serde::Serialize;
-use std::collections::HashMap;
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
 
 const THROUGHPUT_BUDGET: f64 = 1.2; // sweep up to 120% of max throughput
+const RAMP_FIT_INTERVAL: Duration = Duration::from_secs(10);
+/// Stages the run deadline leaves less time than this are skipped rather than shortened
+const MIN_STAGE_DURATION: Duration = Duration::from_secs(1);
 
 #[derive(Clone, Debug, strum_macros::Display, Serialize)]
 pub enum BenchmarkKind {
     Throughput,
     Sweep,
     Rate,
+    /// Ramp the arrival rate up over a single stage and fit latency against it
+    Ramp,
+    /// Submit requests through the OpenAI Batch API instead of streaming them
+    Batch,
+    /// Single stage whose arrival rate follows a rate curve, e.g. daily traffic over a soak test
+    Diurnal,
+    /// Single stage stepping through the rates of a step profile, reported per step
+    Step,
+    /// Single stage replaying the arrivals and request lengths of a recorded trace
+    Replay,
+    /// Single stage at a baseline rate with periodic spikes, reported per phase
+    Burst,
+    /// Constant VUs steps at increasing concurrencies, doubling from 1 VU up to the max VUs
+    ConcurrencySweep,
+    /// Constant arrival rate steps binary-searching the highest rate meeting the SLO
+    Search,
 }
 
 pub struct MessageEvent {
@@ -25,21 +71,38 @@ pub struct MessageEvent {
     pub level: log::Level,
 }
 
+#[derive(Clone, Debug)]
+pub struct DownloadProgressEvent {
+    pub filename: String,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+impl DownloadProgressEvent {
+    pub fn percent(&self) -> Option<f64> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| 100.0 * self.downloaded as f64 / total as f64)
+    }
+}
+
 pub struct BenchmarkEvent {
     pub id: String,
     pub scheduler_type: ExecutorType,
     pub request_throughput: Option<f64>,
     pub progress: f64,
-    pub results: Option<BenchmarkResults>,
+    pub summary: Option<StageSummary>,
     pub successful_requests: u64,
     pub failed_requests: u64,
 }
 
 pub enum Event {
     BenchmarkStart(BenchmarkEvent),
-    BenchmarkProgress(BenchmarkEvent),
+    BenchmarkProgress(SchedulerProgress),
+    Heartbeat(Heartbeat),
     BenchmarkEnd(BenchmarkEvent),
     Message(MessageEvent),
+    DownloadProgress(DownloadProgressEvent),
     BenchmarkReportEnd,
     BenchmarkError(String),
 }
@@ -48,35 +111,212 @@ pub struct Benchmark {
     start_time: Option<tokio::time::Instant>,
     end_time: Option<tokio::time::Instant>,
     backend: Box<dyn TextGenerationBackend + Send + Sync>,
-    requests: Arc<Mutex<dyn TextRequestGenerator + Send>>,
+    requests: Arc<dyn TextRequestGenerator>,
     report: BenchmarkReport,
     pub(crate) config: BenchmarkConfig,
     event_bus: mpsc::UnboundedSender<Event>,
+    metrics_bus: MetricsBus,
+    server_metrics: Option<ServerMetricsScraper>,
+    vllm_stats: Option<VllmStatsCollector>,
     stop_sender: broadcast::Sender<()>,
+    /// An assertion failed and the remaining stages are skipped
+    aborted: bool,
+    /// End of the run set by `max_total_duration`
+    deadline: Option<tokio::time::Instant>,
+    /// Stages started so far, numbering the stage ids
+    stages: usize,
+    /// VU limit of the executors, lowered by the client headroom monitor
+    vu_cap: VuCap,
+    /// Pause of the dispatch of the executors, toggled by SIGUSR1 and SIGUSR2
+    pause: DispatchPause,
 }
 
 #[serde_with::serde_as]
 #[derive(Clone, Serialize)]
 pub struct BenchmarkConfig {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Server of the benchmark in multi-endpoint runs, part of the file names and of the config
+    /// hash so the reports of the endpoints don't collide
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint_label: Option<String>,
     pub max_vus: u64,
     #[serde(rename = "duration_secs")]
     #[serde_as(as = "serde_with::DurationSeconds<u64>")]
     pub duration: Duration,
+    /// Steps end once this many requests completed, the duration capping them
+    pub num_requests: Option<u64>,
     pub benchmark_kind: BenchmarkKind,
     #[serde(rename = "warmup_duration_secs")]
     #[serde_as(as = "serde_with::DurationSeconds<u64>")]
     pub warmup_duration: Duration,
+    /// Number of first warmup requests compared with the next ones in the report, 0 to disable
+    pub warmup_cold_requests: u64,
+    /// Warmup at the start of each constant rate or VUs stage, whose responses are reported apart
+    #[serde(rename = "stage_warmup_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub stage_warmup: Option<Duration>,
+    /// Seed of the random order the rate or concurrency stages run in, in increasing order if not
+    /// set. Guards comparisons between stages against server drift over the run
+    pub shuffle_stages: Option<u64>,
     pub rates: Option<Vec<f64>>,
     pub num_rates: u64,
     pub prompt_options: Option<TokenizeOptions>,
     pub decode_options: Option<TokenizeOptions>,
+    pub endpoint: OpenAIEndpoint,
+    pub prompt_template: Option<PromptTemplate>,
     pub tokenizer: String,
+    pub tokenizer_revision: Option<String>,
+    pub sample_responses: u64,
+    pub response_channel_capacity: usize,
+    /// N-gram repetition score from which responses are flagged as repetitive
+    pub repetition_threshold: Option<f64>,
+    /// Tokens per second of the simulated reader of streamed responses
+    pub reading_rate: Option<f64>,
+    /// Share of the requests logged in full at debug level
+    pub trace_sample: Option<f64>,
+    /// Heartbeats with the stage counters are emitted at this interval while a stage runs
+    #[serde(rename = "heartbeat_interval_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub heartbeat_interval: Option<Duration>,
+    /// Responses stored per step, a uniform sample being kept past it to bound memory usage
+    pub max_stored_responses: Option<u64>,
+    /// Window of the metrics recorded over time for each stage
+    #[serde(rename = "time_series_window_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub time_series_window: Option<Duration>,
+    /// Responses are scored against the expected answers of the dataset
+    pub answer_scoring: Option<AnswerScoring>,
+    /// Expected answers of the dataset compiled for regex scoring. Not part of the config hash
+    #[serde(skip)]
+    pub answer_regexes: AnswerRegexes,
+    /// Latency limits of the requests counted in the goodput
+    pub request_slo: Option<RequestSlo>,
+    /// Model context length requests are clamped to
+    pub max_context_length: Option<u64>,
+    /// Duration over which the VUs of constant VUs steps are started
+    #[serde(rename = "vu_stagger_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub vu_stagger: Duration,
+    pub metrics_bus_capacity: usize,
+    pub hooks: HooksConfig,
+    pub assertions: Vec<Assertion>,
+    /// Objectives the search benchmark finds the highest rate meeting
+    pub slo: Vec<Assertion>,
+    pub abort_on_assertion_failure: bool,
+    pub derived_metrics: Vec<DerivedMetric>,
+    /// Percentiles of the per-request metrics in the report and the console summary
+    pub percentiles: Vec<f64>,
+    pub server_metrics_url: Option<String>,
+    pub server_metrics: Option<Vec<String>>,
+    pub vllm_stats: bool,
+    pub batch_size: u64,
+    #[serde(rename = "batch_poll_interval_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub batch_poll_interval: Duration,
+    /// Generated tokens are audio chunks of a realtime speech endpoint
+    pub realtime_audio: bool,
+    /// Server API requests are sent to
+    pub backend: BackendKind,
+    /// Deadlines of each request
+    pub timeouts: RequestTimeouts,
+    /// Retries of requests failing with transient errors
+    pub retry: RetryPolicy,
+    /// Identities requests are sent as, in proportion to their weights, reported separately
+    pub tenants: Vec<Tenant>,
+    /// Decode targets are scaled down to the completion lengths when the server stops early
+    pub adaptive_decode: bool,
+    /// Arrival rate of the diurnal benchmark
+    pub rate_curve: Option<RateCurve>,
+    /// Distribution of the inter-arrival times of rate steps
+    pub arrival_distribution: ArrivalDistribution,
+    /// Rates and step duration of the step benchmark
+    pub step_profile: Option<StepProfile>,
+    /// Requests of the replay benchmark
+    pub trace: Option<Trace>,
+    /// Baseline rate and spikes of the burst benchmark
+    pub burst_profile: Option<BurstProfile>,
+    /// Reaction to the saturation of the client resources
+    pub client_headroom: HeadroomPolicy,
+    /// Deadline of the whole run: the stage running at the deadline is shortened and the next
+    /// ones are skipped
+    #[serde(rename = "max_total_duration_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub max_total_duration: Option<Duration>,
     #[serde(rename = "meta")]
     pub extra_metadata: Option<HashMap<String, String>>,
+    /// Labels of the stages matching each pattern, passed to hooks and written with the results
+    pub stage_labels: Vec<StageLabels>,
+    /// Complete configuration of the run when started from the CLI, written separately in the
+    /// report as it includes the server URL, which isn't part of the config hash
+    #[serde(skip)]
+    pub effective_config: Option<serde_json::Value>,
+    /// UUID of the run, shared by the reports of all its endpoints and written in every output
+    /// to join them. Not part of the config hash
+    #[serde(skip)]
+    pub run_id: String,
+    /// Presentation only, not part of the report nor the config hash
+    #[serde(skip)]
+    pub output_format: OutputFormat,
+    /// Format of the per-request raw results written next to the report, if any. Not part of the
+    /// config hash
+    #[serde(skip)]
+    pub raw_results: Option<RawResultsFormat>,
+    /// Also write a CSV summary of the steps next to the report. Not part of the config hash
+    #[serde(skip)]
+    pub csv_summary: bool,
+    /// Also write Parquet tables of the steps and requests next to the report. Not part of the
+    /// config hash
+    #[serde(skip)]
+    pub parquet: bool,
+    /// File the Markdown summary is saved to, if any. Not part of the config hash
+    #[serde(skip)]
+    pub markdown_output: Option<PathBuf>,
+    /// File the HTML report is saved to, if any. Not part of the config hash
+    #[serde(skip)]
+    pub html_output: Option<PathBuf>,
+    /// Previous report the steps are compared with in the console summary. Not part of the config
+    /// hash
+    #[serde(skip)]
+    pub baseline: Option<Baseline>,
+    /// OTLP/HTTP collector the request spans are exported to, if any. Not part of the config hash
+    #[serde(skip)]
+    pub otlp_endpoint: Option<String>,
+    /// Custom scores computed on each successful response, summarized per step. Not part of the
+    /// config hash
+    #[serde(skip)]
+    pub response_processors: ResponseProcessors,
+    /// Store the JSON report is uploaded to, if any. Not part of the config hash
+    #[serde(skip)]
+    pub results_sink: Option<ResultsSink>,
+}
+
+/// Concurrencies of the concurrency sweep: powers of two below `max_vus`, then `max_vus`
+pub fn concurrency_steps(max_vus: u64) -> Vec<u64> {
+    let mut steps = std::iter::successors(Some(1u64), |vus| vus.checked_mul(2))
+        .take_while(|vus| *vus < max_vus)
+        .collect::<Vec<_>>();
+    steps.push(max_vus);
+    steps
+}
+
+/// Stages in the order they run: shuffled with `seed` if set, as given otherwise
+pub fn stage_order<T>(mut stages: Vec<T>, seed: Option<u64>) -> Vec<T> {
+    if let Some(seed) = seed {
+        stages.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    stages
 }
 
 impl BenchmarkConfig {
     pub fn validate(&self) -> anyhow::Result<()> {
+        if self
+            .name
+            .as_ref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            return Err(anyhow::anyhow!("name must not be empty"));
+        }
         if self.max_vus == 0 {
             return Err(anyhow::anyhow!("max_vus must be greater than 0"));
         }
@@ -86,6 +326,98 @@ impl BenchmarkConfig {
         if self.warmup_duration.as_secs() == 0 {
             return Err(anyhow::anyhow!("warmup_duration must be greater than 0"));
         }
+        if self.vu_stagger >= self.duration {
+            return Err(anyhow::anyhow!("vu_stagger must be shorter than duration"));
+        }
+        if self.response_channel_capacity == 0 {
+            return Err(anyhow::anyhow!(
+                "response_channel_capacity must be greater than 0"
+            ));
+        }
+        if self
+            .repetition_threshold
+            .is_some_and(|t| !(t > 0.0 && t <= 1.0))
+        {
+            return Err(anyhow::anyhow!(
+                "repetition_threshold must be between 0 and 1"
+            ));
+        }
+        if self
+            .percentiles
+            .iter()
+            .any(|percentile| *percentile <= 0.0 || *percentile > 100.0)
+        {
+            return Err(anyhow::anyhow!(
+                "percentiles must be greater than 0 and at most 100"
+            ));
+        }
+        if self.reading_rate.is_some_and(|rate| rate <= 0.0) {
+            return Err(anyhow::anyhow!("reading_rate must be greater than 0"));
+        }
+        if self
+            .trace_sample
+            .is_some_and(|sample| !(0.0..=1.0).contains(&sample))
+        {
+            return Err(anyhow::anyhow!("trace_sample must be between 0 and 1"));
+        }
+        if self
+            .heartbeat_interval
+            .is_some_and(|interval| interval.is_zero())
+        {
+            return Err(anyhow::anyhow!("heartbeat_interval must be greater than 0"));
+        }
+        if self
+            .time_series_window
+            .is_some_and(|window| window.is_zero())
+        {
+            return Err(anyhow::anyhow!("time_series_window must be greater than 0"));
+        }
+        if self.max_stored_responses == Some(0) {
+            return Err(anyhow::anyhow!(
+                "max_stored_responses must be greater than 0"
+            ));
+        }
+        if self.vllm_stats && self.server_metrics_url.is_none() {
+            return Err(anyhow::anyhow!(
+                "server_metrics_url must be specified to collect vLLM stats"
+            ));
+        }
+        if self.metrics_bus_capacity == 0 {
+            return Err(anyhow::anyhow!(
+                "metrics_bus_capacity must be greater than 0"
+            ));
+        }
+        for (i, tenant) in self.tenants.iter().enumerate() {
+            if self.tenants[..i].iter().any(|t| t.name == tenant.name) {
+                return Err(anyhow::anyhow!("tenant {} defined twice", tenant.name));
+            }
+        }
+        if self
+            .max_total_duration
+            .is_some_and(|max| max < MIN_STAGE_DURATION)
+        {
+            return Err(anyhow::anyhow!(
+                "max_total_duration must be at least {}s",
+                MIN_STAGE_DURATION.as_secs()
+            ));
+        }
+        // catch unknown metric names before running the benchmark
+        let base_metrics = BASE_METRICS
+            .iter()
+            .map(|name| (name.to_string(), 1.0))
+            .collect();
+        for (i, metric) in self.derived_metrics.iter().enumerate() {
+            if self.derived_metrics[..i]
+                .iter()
+                .any(|m| m.name == metric.name)
+            {
+                return Err(anyhow::anyhow!(
+                    "derived metric {} defined twice",
+                    metric.name
+                ));
+            }
+            metric.evaluate(&base_metrics)?;
+        }
         match self.benchmark_kind {
             BenchmarkKind::Throughput => {
                 if self.rates.is_some() {
@@ -101,6 +433,30 @@ impl BenchmarkConfig {
                     ));
                 }
             }
+            BenchmarkKind::Search => {
+                if self.slo.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "slo must be specified for search benchmark"
+                    ));
+                }
+                if self.rates.as_ref().is_some_and(|rates| rates.len() != 1) {
+                    return Err(anyhow::anyhow!(
+                        "rates must be a single max rate for search benchmark"
+                    ));
+                }
+                if self.num_rates == 0 {
+                    return Err(anyhow::anyhow!(
+                        "num_rates must be greater than 0 for search benchmark"
+                    ));
+                }
+            }
+            BenchmarkKind::ConcurrencySweep => {
+                if self.rates.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "rates must not be specified for concurrency sweep benchmark"
+                    ));
+                }
+            }
             BenchmarkKind::Rate => {
                 if self.rates.is_none() {
                     return Err(anyhow::anyhow!(
@@ -108,22 +464,237 @@ impl BenchmarkConfig {
                     ));
                 }
             }
+            BenchmarkKind::Ramp => {
+                if self.rates.as_ref().is_some_and(|rates| rates.len() != 1) {
+                    return Err(anyhow::anyhow!(
+                        "rates must be a single max rate for ramp benchmark"
+                    ));
+                }
+                if self.num_rates < 3 {
+                    return Err(anyhow::anyhow!(
+                        "num_rates must be at least 3 for ramp benchmark"
+                    ));
+                }
+            }
+            BenchmarkKind::Batch => {
+                if self.batch_size == 0 {
+                    return Err(anyhow::anyhow!("batch_size must be greater than 0"));
+                }
+                if !self.tenants.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "tenants are not supported by the batch benchmark"
+                    ));
+                }
+            }
+            BenchmarkKind::Diurnal => {
+                if self.rates.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "rates must not be specified for diurnal benchmark, use a rate curve"
+                    ));
+                }
+                match &self.rate_curve {
+                    Some(curve) => curve.validate()?,
+                    None => {
+                        return Err(anyhow::anyhow!(
+                            "rate_curve must be specified for diurnal benchmark"
+                        ))
+                    }
+                }
+            }
+            BenchmarkKind::Step => {
+                if self.rates.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "rates must not be specified for step benchmark, use a step profile"
+                    ));
+                }
+                match &self.step_profile {
+                    Some(profile) => profile.rate_curve().validate()?,
+                    None => {
+                        return Err(anyhow::anyhow!(
+                            "step_profile must be specified for step benchmark"
+                        ))
+                    }
+                }
+            }
+            BenchmarkKind::Burst => {
+                if self.rates.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "rates must not be specified for burst benchmark, use a burst profile"
+                    ));
+                }
+                match &self.burst_profile {
+                    Some(profile) => profile.rate_curve().validate()?,
+                    None => {
+                        return Err(anyhow::anyhow!(
+                            "burst_profile must be specified for burst benchmark"
+                        ))
+                    }
+                }
+            }
+            BenchmarkKind::Replay => {
+                if self.rates.is_some() {
+                    return Err(anyhow::anyhow!(
+                        "rates must not be specified for replay benchmark, the trace sets them"
+                    ));
+                }
+                if self.trace.is_none() {
+                    return Err(anyhow::anyhow!(
+                        "trace must be specified for replay benchmark"
+                    ));
+                }
+            }
+        }
+        if let Some(num_requests) = self.num_requests {
+            if num_requests == 0 {
+                return Err(anyhow::anyhow!("num_requests must be greater than 0"));
+            }
+            if !matches!(
+                self.benchmark_kind,
+                BenchmarkKind::Throughput
+                    | BenchmarkKind::Sweep
+                    | BenchmarkKind::Rate
+                    | BenchmarkKind::ConcurrencySweep
+            ) {
+                return Err(anyhow::anyhow!(
+                    "num_requests is only valid for throughput, sweep, rate and concurrency sweep benchmarks"
+                ));
+            }
+        }
+        if let Some(stage_warmup) = self.stage_warmup {
+            if stage_warmup.is_zero() {
+                return Err(anyhow::anyhow!("stage_warmup must be greater than 0"));
+            }
+            if !matches!(
+                self.benchmark_kind,
+                BenchmarkKind::Throughput
+                    | BenchmarkKind::Sweep
+                    | BenchmarkKind::Rate
+                    | BenchmarkKind::ConcurrencySweep
+                    | BenchmarkKind::Search
+            ) {
+                return Err(anyhow::anyhow!(
+                    "stage_warmup is only valid for throughput, sweep, rate, concurrency sweep and search benchmarks"
+                ));
+            }
+            if self.num_requests.is_some() {
+                return Err(anyhow::anyhow!(
+                    "stage_warmup can't be combined with num_requests"
+                ));
+            }
+        }
+        if self.shuffle_stages.is_some()
+            && !matches!(
+                self.benchmark_kind,
+                BenchmarkKind::Sweep | BenchmarkKind::Rate | BenchmarkKind::ConcurrencySweep
+            )
+        {
+            return Err(anyhow::anyhow!(
+                "shuffle_stages is only valid for sweep, rate and concurrency sweep benchmarks"
+            ));
+        }
+        if !self.slo.is_empty()
+            && !matches!(
+                self.benchmark_kind,
+                BenchmarkKind::Search | BenchmarkKind::Sweep | BenchmarkKind::Ramp
+            )
+        {
+            return Err(anyhow::anyhow!(
+                "slo is only valid for search, sweep and ramp benchmarks"
+            ));
+        }
+        if self.burst_profile.is_some() && !matches!(self.benchmark_kind, BenchmarkKind::Burst) {
+            return Err(anyhow::anyhow!(
+                "burst_profile is only valid for burst benchmark"
+            ));
+        }
+        if self.trace.is_some() && !matches!(self.benchmark_kind, BenchmarkKind::Replay) {
+            return Err(anyhow::anyhow!("trace is only valid for replay benchmark"));
+        }
+        if self.step_profile.is_some() && !matches!(self.benchmark_kind, BenchmarkKind::Step) {
+            return Err(anyhow::anyhow!(
+                "step_profile is only valid for step benchmark"
+            ));
+        }
+        if self.rate_curve.is_some() && !matches!(self.benchmark_kind, BenchmarkKind::Diurnal) {
+            return Err(anyhow::anyhow!(
+                "rate_curve is only valid for diurnal benchmark"
+            ));
         }
         Ok(())
     }
-}
 
-pub struct BenchmarkProgress {
-    id: String,
-    progress: SchedulerProgress,
+    /// Fingerprint of the configuration, identical for runs with the same parameters. The server
+    /// URL is not part of the configuration: name runs against different servers differently.
+    /// Only the endpoints of multi-endpoint runs are told apart, by their label.
+    pub fn config_hash(&self) -> String {
+        // `Value` objects are key-sorted, so metadata maps serialize deterministically
+        let config = serde_json::to_value(self).unwrap_or_default();
+        Sha1::digest(config.to_string().as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    /// Name used to identify the benchmark in file names: the benchmark name if set,
+    /// the tokenizer name otherwise, followed by the endpoint label if any, with characters
+    /// unsafe for file names replaced
+    pub fn file_stem(&self) -> String {
+        let name = self.name.as_ref().unwrap_or(&self.tokenizer);
+        let name = match &self.endpoint_label {
+            Some(label) => format!("{name}_{label}"),
+            None => name.clone(),
+        };
+        let stem: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        // device names are reserved on Windows whatever the extension
+        let reserved = matches!(
+            stem.to_ascii_uppercase().as_str(),
+            "CON"
+                | "PRN"
+                | "AUX"
+                | "NUL"
+                | "COM1"
+                | "COM2"
+                | "COM3"
+                | "COM4"
+                | "COM5"
+                | "COM6"
+                | "COM7"
+                | "COM8"
+                | "COM9"
+                | "LPT1"
+                | "LPT2"
+                | "LPT3"
+                | "LPT4"
+                | "LPT5"
+                | "LPT6"
+                | "LPT7"
+                | "LPT8"
+                | "LPT9"
+        );
+        if stem.is_empty() || reserved {
+            format!("_{stem}")
+        } else {
+            stem
+        }
+    }
 }
 
 impl Benchmark {
     pub fn new(
         config: BenchmarkConfig,
         backend: Box<dyn TextGenerationBackend + Send + Sync>,
-        requests: Arc<Mutex<dyn TextRequestGenerator + Send>>,
+        requests: Arc<dyn TextRequestGenerator>,
         event_bus: mpsc::UnboundedSender<Event>,
+        metrics_bus: MetricsBus,
         stop_sender: broadcast::Sender<()>,
     ) -> Benchmark {
         Benchmark {
@@ -134,7 +705,21 @@ impl Benchmark {
             backend,
             requests,
             event_bus,
+            metrics_bus,
+            server_metrics: config
+                .server_metrics_url
+                .clone()
+                .map(|url| ServerMetricsScraper::new(url, config.server_metrics.clone())),
+            vllm_stats: match (config.vllm_stats, &config.server_metrics_url) {
+                (true, Some(url)) => Some(VllmStatsCollector::new(url.clone())),
+                _ => None,
+            },
             stop_sender,
+            aborted: false,
+            deadline: None,
+            stages: 0,
+            vu_cap: VuCap::default(),
+            pause: DispatchPause::default(),
         }
     }
 
@@ -144,7 +729,50 @@ impl Benchmark {
 
     pub async fn run(&mut self) -> anyhow::Result<BenchmarkReport> {
         self.start_time = Some(tokio::time::Instant::now());
+        self.deadline = self
+            .config
+            .max_total_duration
+            .map(|max| tokio::time::Instant::now() + max);
         self.report.start();
+        self.run_hook(HookKind::PreRun, None, None).await;
+        self.handle_progress();
+        let headroom = (self.config.client_headroom != HeadroomPolicy::Off).then(|| {
+            HeadroomMonitor::spawn(
+                self.config.client_headroom,
+                self.vu_cap.clone(),
+                self.event_bus.clone(),
+            )
+        });
+        let result = self.run_stages().await;
+        self.end_time = Some(tokio::time::Instant::now());
+        // also run when the benchmark failed, e.g. to tear down what the pre-run hook set up
+        self.run_hook(HookKind::PostRun, None, None).await;
+        result?;
+        self.event_bus.send(Event::Message(MessageEvent {
+            message: format!(
+                "Benchmark complete in {:?}",
+                self.duration().expect("duration exists")
+            ),
+            timestamp: chrono::Utc::now(),
+            level: log::Level::Info,
+        }))?;
+        self.report.end();
+        self.report.set_dropped_metrics(self.metrics_bus.dropped());
+        if let Some(headroom) = headroom {
+            self.report.set_client_saturations(headroom.stop());
+        }
+        Ok(self.report.clone())
+    }
+
+    /// Prewarm the backend and run the stages of the benchmark kind
+    async fn run_stages(&mut self) -> anyhow::Result<()> {
+        let pause_signals = self.pause.listen_signals(self.event_bus.clone())?;
+        let result = self.run_benchmark_kind().await;
+        pause_signals.abort();
+        result
+    }
+
+    async fn run_benchmark_kind(&mut self) -> anyhow::Result<()> {
         info!("Prewarming backend");
         self.warmup().await?;
         info!("Prewarm complete");
@@ -158,18 +786,54 @@ impl Benchmark {
             BenchmarkKind::Rate => {
                 self.run_rates().await?;
             }
+            BenchmarkKind::ConcurrencySweep => {
+                self.run_concurrency_sweep().await?;
+            }
+            BenchmarkKind::Search => {
+                self.run_search().await?;
+            }
+            BenchmarkKind::Ramp => {
+                self.run_ramp().await?;
+            }
+            BenchmarkKind::Diurnal => {
+                let curve = self
+                    .config
+                    .rate_curve
+                    .clone()
+                    .expect("config already validated");
+                let id = format!(
+                    "diurnal@{:.2}req/s",
+                    curve.average_rate(self.config.duration)
+                );
+                self.run_constant_arrival_rate(id, curve.rate_at(Duration::ZERO), Some(curve))
+                    .await?;
+            }
+            BenchmarkKind::Step => {
+                self.run_steps().await?;
+            }
+            BenchmarkKind::Replay => {
+                self.run_replay().await?;
+            }
+            BenchmarkKind::Burst => {
+                let profile = self
+                    .config
+                    .burst_profile
+                    .clone()
+                    .expect("config already validated");
+                let id = format!(
+                    "burst@{:.2}-{:.2}req/s",
+                    profile.base_rate, profile.burst_rate
+                );
+                self.run_constant_arrival_rate(id, profile.base_rate, Some(profile.rate_curve()))
+                    .await?;
+            }
+            BenchmarkKind::Batch => {
+                return Err(anyhow::anyhow!(
+                    "batch benchmarks are not run by the streaming scheduler"
+                ));
+            }
         }
-        self.end_time = Some(tokio::time::Instant::now());
-        self.event_bus.send(Event::Message(MessageEvent {
-            message: format!(
-                "Benchmark complete in {:?}",
-                self.duration().expect("duration exists")
-            ),
-            timestamp: chrono::Utc::now(),
-            level: log::Level::Info,
-        }))?;
-        self.report.end();
-        Ok(self.report.clone())
+        Ok(())
     }
 
     pub fn duration(&self) -> Option<std::time::Duration> {
@@ -179,45 +843,210 @@ impl Benchmark {
         }
     }
 
-    async fn handle_progress(&self, id: String) -> Sender<Option<SchedulerProgress>> {
-        let (tx, mut rx): (
-            Sender<Option<SchedulerProgress>>,
-            Receiver<Option<SchedulerProgress>>,
-        ) = mpsc::channel(8);
+    /// Run the configured hook, if any, and record its output in the report
+    async fn run_hook(&mut self, kind: HookKind, stage: Option<&str>, stage_id: Option<&str>) {
+        let Some(command) = self.config.hooks.command(kind).cloned() else {
+            return;
+        };
+        let labels = stage
+            .map(|stage| labels::stage_labels(&self.config.stage_labels, stage, stage_id))
+            .unwrap_or_default();
+        let context = HookContext {
+            run_id: &self.config.run_id,
+            stage,
+            stage_id,
+            labels: &labels,
+        };
+        let output =
+            hooks::run_hook(kind, &command, self.config.hooks.timeout, context, None).await;
+        if !output.success {
+            warn!("Hook {kind} failed: {}", output.stderr);
+            let _ = self.event_bus.send(Event::Message(MessageEvent {
+                message: format!("Hook {kind} failed (exit code {:?})", output.exit_code),
+                timestamp: chrono::Utc::now(),
+                level: log::Level::Warn,
+            }));
+        }
+        self.report.add_hook_output(output);
+    }
+
+    /// Id of the next stage in the run, e.g. `02-constant@1.20req/s`: deterministic for a given
+    /// configuration and unique in the run even when stage names repeat
+    fn next_stage_id(&mut self, name: &str) -> String {
+        let stage_id = format!("{:02}-{name}", self.stages);
+        self.stages += 1;
+        stage_id
+    }
+
+    /// Time left before the run deadline, `None` without `max_total_duration`
+    fn time_left(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()))
+    }
+
+    /// Duration of stage `id`, shortened to end by the run deadline
+    fn stage_duration(&self, id: &str, duration: Duration) -> Duration {
+        match self.time_left() {
+            Some(left) if left < duration => {
+                warn!("Shortening {id} to {left:?} to end by the run deadline");
+                left
+            }
+            _ => duration,
+        }
+    }
+
+    /// Report the responses sent during the stage warmup, if any, apart from the measured ones
+    fn split_stage_warmup(
+        &mut self,
+        results: BenchmarkResults,
+    ) -> anyhow::Result<BenchmarkResults> {
+        if self.config.stage_warmup.is_none() {
+            return Ok(results);
+        }
+        let (warmup, measured) = results.split_warmup();
+        self.report.add_stage_warmup(warmup);
+        if measured.successful_requests() == 0 {
+            return Err(anyhow::anyhow!(NoResponses));
+        }
+        Ok(measured)
+    }
+
+    /// Record stage `id` as skipped if the run deadline leaves it no time to run
+    fn skip_past_deadline(&mut self, id: &str) -> anyhow::Result<bool> {
+        if self
+            .time_left()
+            .is_none_or(|left| left >= MIN_STAGE_DURATION)
+        {
+            return Ok(false);
+        }
+        self.report.add_skipped_stage(id.to_string());
+        self.event_bus.send(Event::Message(MessageEvent {
+            message: format!("Skipping {id}, past the run deadline"),
+            timestamp: chrono::Utc::now(),
+            level: log::Level::Warn,
+        }))?;
+        Ok(true)
+    }
+
+    /// Evaluate the assertions on a stage that just ended. If one fails and
+    /// `abort_on_assertion_failure` is set, the remaining stages are skipped.
+    fn check_assertions(&mut self, results: &mut BenchmarkResults) -> anyhow::Result<()> {
+        let assertions = self
+            .config
+            .assertions
+            .iter()
+            .map(|assertion| assertion.evaluate(results))
+            .collect();
+        results.set_assertions(assertions);
+        let failed = results.failed_assertions();
+        for assertion in &failed {
+            let value = assertion
+                .value
+                .map_or("N/A".to_string(), |v| format!("{v:.2}"));
+            let message = format!(
+                "Assertion `{}` failed on {}: {}",
+                assertion.expression, results.id, value
+            );
+            warn!("{message}");
+            self.event_bus.send(Event::Message(MessageEvent {
+                message,
+                timestamp: chrono::Utc::now(),
+                level: log::Level::Warn,
+            }))?;
+        }
+        if !failed.is_empty() && self.config.abort_on_assertion_failure {
+            self.aborted = true;
+            self.event_bus.send(Event::Message(MessageEvent {
+                message: "Skipping remaining stages after failed assertions".to_string(),
+                timestamp: chrono::Utc::now(),
+                level: log::Level::Warn,
+            }))?;
+        }
+        Ok(())
+    }
+
+    async fn scrape_server_metrics(&self) -> Option<MetricsSnapshot> {
+        let scraper = self.server_metrics.as_ref()?;
+        match scraper.scrape().await {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                warn!("Failed to scrape server metrics: {e}");
+                None
+            }
+        }
+    }
+
+    /// Scrape the server metrics again and diff them with the stage start snapshot
+    async fn server_metrics_diff(
+        &self,
+        start: Option<MetricsSnapshot>,
+    ) -> Option<BTreeMap<String, f64>> {
+        let start = start?;
+        let end = self.scrape_server_metrics().await?;
+        Some(end.diff(&start))
+    }
+
+    fn handle_progress(&self) {
+        // forward progress and heartbeats published on the metrics bus to the event bus,
+        // heartbeats also to their hook
+        let mut subscriber = self
+            .metrics_bus
+            .subscribe(&[Topic::Progress, Topic::Heartbeat]);
         let event_bus = self.event_bus.clone();
+        let heartbeat_hook = self.config.hooks.command(HookKind::Heartbeat).cloned();
+        let hook_timeout = self.config.hooks.timeout;
+        let run_id = self.config.run_id.clone();
+        let stage_labels = self.config.stage_labels.clone();
         tokio::spawn(async move {
-            while let Some(event) = rx.recv().await {
-                match event {
-                    None => {
-                        break;
+            while let Some(metric) = subscriber.recv().await {
+                match metric {
+                    Metric::Progress(progress) => {
+                        let _ = event_bus.send(Event::BenchmarkProgress(progress));
                     }
-                    Some(progress) => {
-                        let progress_evt = BenchmarkProgress {
-                            id: id.clone(),
-                            progress,
-                        };
-                        event_bus
-                            .send(Event::BenchmarkProgress(BenchmarkEvent {
-                                id: progress_evt.id,
-                                scheduler_type: ExecutorType::ConstantVUs,
-                                request_throughput: Some(progress_evt.progress.requests_throughput),
-                                progress: progress_evt.progress.progress,
-                                successful_requests: progress_evt.progress.successful_requests,
-                                failed_requests: progress_evt.progress.failed_requests,
-                                results: None,
-                            }))
-                            .unwrap();
+                    Metric::Heartbeat(heartbeat) => {
+                        if let Some(command) = heartbeat_hook.clone() {
+                            let run_id = run_id.clone();
+                            let heartbeat = heartbeat.clone();
+                            let labels = labels::stage_labels(&stage_labels, &heartbeat.id, None);
+                            // a slow hook must not delay the next heartbeats
+                            tokio::spawn(async move {
+                                let payload = serde_json::to_value(&heartbeat).ok();
+                                let context = HookContext {
+                                    run_id: &run_id,
+                                    stage: Some(&heartbeat.id),
+                                    stage_id: None,
+                                    labels: &labels,
+                                };
+                                let output = hooks::run_hook(
+                                    HookKind::Heartbeat,
+                                    &command,
+                                    hook_timeout,
+                                    context,
+                                    payload.as_ref(),
+                                )
+                                .await;
+                                if !output.success {
+                                    warn!("Hook heartbeat failed: {}", output.stderr);
+                                }
+                            });
+                        }
+                        let _ = event_bus.send(Event::Heartbeat(heartbeat));
                     }
+                    _ => {}
                 }
             }
         });
-        tx
     }
 
     pub async fn warmup(&mut self) -> anyhow::Result<()> {
         // run a warmup benchmark to prewarm the server
 
         let id = "warmup".to_string();
+        if self.skip_past_deadline(&id)? {
+            return Ok(());
+        }
+
+        let stage_id = self.next_stage_id(&id);
 
         // notify start event
         self.event_bus.send(Event::BenchmarkStart(BenchmarkEvent {
@@ -225,43 +1054,83 @@ impl Benchmark {
             scheduler_type: ExecutorType::ConstantVUs,
             request_throughput: None,
             progress: 0.0,
-            results: None,
+            summary: None,
             successful_requests: 0,
             failed_requests: 0,
         }))?;
 
-        // create progress handler
-        let tx = self.handle_progress(id.clone()).await;
-
         // start scheduler
+        self.run_hook(HookKind::PreStage, Some(&id), Some(&stage_id))
+            .await;
+        let server_metrics_start = self.scrape_server_metrics().await;
+        let vllm_stats = self.vllm_stats.as_ref().map(|c| c.start());
         let mut scheduler = scheduler::Scheduler::new(
-            id,
+            id.clone(),
             self.backend.clone(),
             ExecutorType::ConstantVUs,
             executors::ExecutorConfig {
                 max_vus: 1,
-                duration: self.config.warmup_duration,
+                duration: self.stage_duration(&id, self.config.warmup_duration),
                 rate: None,
+                ramp_to_rate: None,
+                sample_responses: self.config.sample_responses,
+                response_channel_capacity: self.config.response_channel_capacity,
+                repetition_threshold: self.config.repetition_threshold,
+                reading_rate: self.config.reading_rate,
+                answer_scoring: self.config.answer_scoring,
+                answer_regexes: self.config.answer_regexes.clone(),
+                request_slo: self.config.request_slo,
+                response_processors: self.config.response_processors.clone(),
+                vu_stagger: self.config.vu_stagger,
+                timeouts: self.config.timeouts,
+                rate_curve: None,
+                vu_cap: self.vu_cap.clone(),
+                pause: self.pause.clone(),
+                stage_warmup: None,
+                arrival_distribution: ArrivalDistribution::default(),
+                trace: None,
+                num_requests: None,
+                heartbeat_interval: self.config.heartbeat_interval,
+                max_stored_responses: self.config.max_stored_responses,
+                time_series_window: self.config.time_series_window,
             },
             self.requests.clone(),
-            tx.clone(),
+            self.metrics_bus.clone(),
             self.stop_sender.clone(),
         );
-        scheduler.run().await?;
+        let result = scheduler.run().await;
+        let server_metrics = self.server_metrics_diff(server_metrics_start).await;
+        let vllm_stats = match vllm_stats {
+            Some(collection) => Some(collection.stop().await),
+            None => None,
+        };
+        self.run_hook(HookKind::PostStage, Some(&id), Some(&stage_id))
+            .await;
+        result?;
 
-        let results = scheduler.get_results().lock().await.clone();
+        let mut results = scheduler.get_results().lock().await.clone();
+        results.set_server_metrics(server_metrics);
+        results.set_vllm_stats(vllm_stats);
+        results.set_stage_id(stage_id);
         self.report.add_benchmark_result(results.clone());
-
-        // send None to close the progress handler
-        tx.send(None).await.unwrap();
+        if let Some(check) = TokenizerCheck::from_results(&results).filter(|check| check.mismatch) {
+            warn!("{}", check.warning());
+            self.event_bus.send(Event::Message(MessageEvent {
+                message: check.warning(),
+                timestamp: chrono::Utc::now(),
+                level: log::Level::Warn,
+            }))?;
+        }
 
         // notify end event
+        let summary = StageSummary::new(&results);
+        self.metrics_bus.publish(Metric::Stage(summary.clone()));
         self.event_bus.send(Event::BenchmarkEnd(BenchmarkEvent {
             id: "warmup".to_string(),
             scheduler_type: ExecutorType::ConstantVUs,
             request_throughput: results.successful_request_rate().ok(),
             progress: 100.0,
-            results: Some(results.clone()),
+            summary: Some(summary),
             successful_requests: results.successful_requests() as u64,
             failed_requests: results.failed_requests() as u64,
         }))?;
@@ -270,8 +1139,33 @@ impl Benchmark {
 
     pub async fn run_throughput(&mut self) -> anyhow::Result<()> {
         info!("Running throughput benchmark");
+        self.run_constant_vus("throughput".to_string(), self.config.max_vus)
+            .await
+    }
+
+    /// Run a constant VUs step at each concurrency, doubling from 1 VU up to the max VUs
+    pub async fn run_concurrency_sweep(&mut self) -> anyhow::Result<()> {
+        info!("Running concurrency sweep benchmark");
+        for vus in stage_order(
+            concurrency_steps(self.config.max_vus),
+            self.config.shuffle_stages,
+        ) {
+            if self.aborted {
+                break;
+            }
+            self.run_constant_vus(format!("concurrency@{vus}vus"), vus)
+                .await?;
+        }
+        Ok(())
+    }
 
-        let id = "throughput".to_string();
+    /// Run a constant VUs stage with `max_vus` virtual users
+    async fn run_constant_vus(&mut self, id: String, max_vus: u64) -> anyhow::Result<()> {
+        if self.skip_past_deadline(&id)? {
+            return Ok(());
+        }
+
+        let stage_id = self.next_stage_id(&id);
 
         // notify start event
         self.event_bus.send(Event::BenchmarkStart(BenchmarkEvent {
@@ -279,43 +1173,78 @@ impl Benchmark {
             scheduler_type: ExecutorType::ConstantVUs,
             request_throughput: None,
             progress: 0.0,
-            results: None,
+            summary: None,
             successful_requests: 0,
             failed_requests: 0,
         }))?;
 
-        // create progress handler
-        let tx = self.handle_progress(id.clone()).await;
-
         // start scheduler
+        self.run_hook(HookKind::PreStage, Some(&id), Some(&stage_id))
+            .await;
+        let server_metrics_start = self.scrape_server_metrics().await;
+        let vllm_stats = self.vllm_stats.as_ref().map(|c| c.start());
+        let stage_warmup = self.config.stage_warmup.unwrap_or_default();
         let mut scheduler = scheduler::Scheduler::new(
             id.clone(),
             self.backend.clone(),
             ExecutorType::ConstantVUs,
             executors::ExecutorConfig {
-                max_vus: self.config.max_vus,
-                duration: self.config.duration,
+                max_vus,
+                duration: self.stage_duration(&id, self.config.duration + stage_warmup),
                 rate: None,
+                ramp_to_rate: None,
+                sample_responses: self.config.sample_responses,
+                response_channel_capacity: self.config.response_channel_capacity,
+                repetition_threshold: self.config.repetition_threshold,
+                reading_rate: self.config.reading_rate,
+                answer_scoring: self.config.answer_scoring,
+                answer_regexes: self.config.answer_regexes.clone(),
+                request_slo: self.config.request_slo,
+                response_processors: self.config.response_processors.clone(),
+                vu_stagger: self.config.vu_stagger,
+                timeouts: self.config.timeouts,
+                rate_curve: None,
+                vu_cap: self.vu_cap.clone(),
+                pause: self.pause.clone(),
+                stage_warmup: self.config.stage_warmup,
+                arrival_distribution: ArrivalDistribution::default(),
+                trace: None,
+                num_requests: self.config.num_requests,
+                heartbeat_interval: self.config.heartbeat_interval,
+                max_stored_responses: self.config.max_stored_responses,
+                time_series_window: self.config.time_series_window,
             },
             self.requests.clone(),
-            tx.clone(),
+            self.metrics_bus.clone(),
             self.stop_sender.clone(),
         );
-        scheduler.run().await?;
+        let result = scheduler.run().await;
+        let server_metrics = self.server_metrics_diff(server_metrics_start).await;
+        let vllm_stats = match vllm_stats {
+            Some(collection) => Some(collection.stop().await),
+            None => None,
+        };
+        self.run_hook(HookKind::PostStage, Some(&id), Some(&stage_id))
+            .await;
+        result?;
         let results = scheduler.get_results().lock().await.clone();
+        let mut results = self.split_stage_warmup(results)?;
+        results.set_server_metrics(server_metrics);
+        results.set_vllm_stats(vllm_stats);
+        results.set_stage_id(stage_id);
+        self.check_assertions(&mut results)?;
         let rate = results.successful_request_rate().ok();
         self.report.add_benchmark_result(results.clone());
 
-        // send None to close the progress handler
-        tx.send(None).await.unwrap();
-
         // notify end event
+        let summary = StageSummary::new(&results);
+        self.metrics_bus.publish(Metric::Stage(summary.clone()));
         self.event_bus.send(Event::BenchmarkEnd(BenchmarkEvent {
             id: id.clone(),
             scheduler_type: ExecutorType::ConstantVUs,
             request_throughput: rate,
             progress: 100.0,
-            results: Some(results.clone()),
+            summary: Some(summary),
             successful_requests: results.successful_requests() as u64,
             failed_requests: results.failed_requests() as u64,
         }))?;
@@ -325,8 +1254,11 @@ impl Benchmark {
     pub async fn run_sweep(&mut self) -> anyhow::Result<()> {
         // run a throughput benchmark to retrieve the maximum throughput of server
         self.run_throughput().await?;
-        // get the max throughput from the second benchmark result (first is warmup)
-        let throughput_results = &self.report.get_results()[1];
+        let results = self.report.get_results();
+        let Some(throughput_results) = results.iter().find(|r| r.id == "throughput") else {
+            // skipped past the run deadline, the rates to sweep are unknown
+            return Ok(());
+        };
         let max_throughput = throughput_results.successful_request_rate()?;
         let max_tokens_throughput = throughput_results.token_throughput_secs()?;
         // notify event bus
@@ -344,7 +1276,10 @@ impl Benchmark {
         for i in 1..=num_rates {
             rates.push(i as f64 * max_throughput * THROUGHPUT_BUDGET / num_rates as f64);
         }
-        for rate in rates {
+        for rate in stage_order(rates, self.config.shuffle_stages) {
+            if self.aborted {
+                break;
+            }
             self.run_rate(rate).await?;
         }
         Ok(())
@@ -352,16 +1287,277 @@ impl Benchmark {
 
     pub async fn run_rates(&mut self) -> anyhow::Result<()> {
         let rates = self.config.rates.clone().expect("config already validated");
-        for rate in rates {
+        for rate in stage_order(rates, self.config.shuffle_stages) {
+            if self.aborted {
+                break;
+            }
             self.run_rate(rate).await?;
         }
         Ok(())
     }
 
+    /// Single rate given as the max rate, or the detected max throughput with the sweep budget.
+    /// `None` if the throughput step was skipped past the run deadline
+    async fn max_rate(&mut self) -> anyhow::Result<Option<f64>> {
+        if let Some(rates) = &self.config.rates {
+            return Ok(Some(rates[0]));
+        }
+        // run a throughput benchmark to retrieve the maximum throughput of server
+        self.run_throughput().await?;
+        let results = self.report.get_results();
+        let Some(throughput_results) = results.iter().find(|r| r.id == "throughput") else {
+            return Ok(None);
+        };
+        let max_throughput = throughput_results.successful_request_rate()?;
+        self.event_bus.send(Event::Message(MessageEvent {
+            message: format!("Max throughput detected at: {:.2} req/s", max_throughput),
+            timestamp: chrono::Utc::now(),
+            level: log::Level::Info,
+        }))?;
+        Ok(Some(max_throughput * THROUGHPUT_BUDGET))
+    }
+
+    /// Binary search the highest arrival rate meeting the SLO between 0 and the max rate, in
+    /// `num_rates` steps. The max rate is tried first, the search ends if it meets the SLO
+    pub async fn run_search(&mut self) -> anyhow::Result<()> {
+        let Some(max_rate) = self.max_rate().await? else {
+            return Ok(());
+        };
+        let (mut low, mut high) = (0.0, max_rate);
+        let mut rate = max_rate;
+        for _ in 0..self.config.num_rates {
+            if self.aborted {
+                break;
+            }
+            let id = format!("{SEARCH_STAGE_PREFIX}{rate:.2}req/s");
+            self.run_constant_arrival_rate(id.clone(), rate, None)
+                .await?;
+            let results = self.report.get_results();
+            let Some(results) = results.iter().rev().find(|r| r.id == id) else {
+                // skipped past the run deadline
+                break;
+            };
+            if analysis::meets_slo(&self.config.slo, results) {
+                low = rate;
+                if rate == max_rate {
+                    break;
+                }
+            } else {
+                high = rate;
+            }
+            rate = (low + high) / 2.0;
+        }
+        let message = match low {
+            0.0 => "No searched rate meets the SLO".to_string(),
+            low => format!("Highest rate meeting the SLO: {low:.2} req/s"),
+        };
+        self.event_bus.send(Event::Message(MessageEvent {
+            message,
+            timestamp: chrono::Utc::now(),
+            level: log::Level::Info,
+        }))?;
+        Ok(())
+    }
+
+    pub async fn run_ramp(&mut self) -> anyhow::Result<()> {
+        let Some(max_rate) = self.max_rate().await? else {
+            return Ok(());
+        };
+        if self.aborted {
+            return Ok(());
+        }
+        // start from the lowest rate a sweep would run
+        let start_rate = max_rate / self.config.num_rates as f64;
+        debug!(
+            "Running ramp benchmark from {} req/s to {} req/s",
+            start_rate, max_rate
+        );
+
+        let id = format!("ramp@{:.2}-{:.2}req/s", start_rate, max_rate);
+        if self.skip_past_deadline(&id)? {
+            return Ok(());
+        }
+
+        let stage_id = self.next_stage_id(&id);
+
+        // notify start event
+        self.event_bus.send(Event::BenchmarkStart(BenchmarkEvent {
+            id: id.clone(),
+            scheduler_type: ExecutorType::ConstantArrivalRate,
+            request_throughput: None,
+            progress: 0.0,
+            summary: None,
+            successful_requests: 0,
+            failed_requests: 0,
+        }))?;
+
+        // start scheduler
+        self.run_hook(HookKind::PreStage, Some(&id), Some(&stage_id))
+            .await;
+        let server_metrics_start = self.scrape_server_metrics().await;
+        let vllm_stats = self.vllm_stats.as_ref().map(|c| c.start());
+        // a ramp shortened by the run deadline keeps its slope and ends below the max rate
+        let duration = self.stage_duration(&id, self.config.duration);
+        let end_rate = start_rate
+            + (max_rate - start_rate) * duration.as_secs_f64() / self.config.duration.as_secs_f64();
+        let executor_config = executors::ExecutorConfig {
+            max_vus: self.config.max_vus,
+            duration,
+            rate: Some(start_rate),
+            ramp_to_rate: Some(end_rate),
+            sample_responses: self.config.sample_responses,
+            response_channel_capacity: self.config.response_channel_capacity,
+            repetition_threshold: self.config.repetition_threshold,
+            reading_rate: self.config.reading_rate,
+            answer_scoring: self.config.answer_scoring,
+            answer_regexes: self.config.answer_regexes.clone(),
+            request_slo: self.config.request_slo,
+            response_processors: self.config.response_processors.clone(),
+            vu_stagger: Duration::ZERO,
+            timeouts: self.config.timeouts,
+            rate_curve: None,
+            vu_cap: self.vu_cap.clone(),
+            pause: self.pause.clone(),
+            stage_warmup: None,
+            arrival_distribution: self.config.arrival_distribution,
+            trace: None,
+            num_requests: None,
+            heartbeat_interval: self.config.heartbeat_interval,
+            max_stored_responses: self.config.max_stored_responses,
+            time_series_window: self.config.time_series_window,
+        };
+        let mut scheduler = scheduler::Scheduler::new(
+            id.clone(),
+            self.backend.clone(),
+            scheduler::ExecutorType::ConstantArrivalRate,
+            executor_config.clone(),
+            self.requests.clone(),
+            self.metrics_bus.clone(),
+            self.stop_sender.clone(),
+        );
+        let fit = self.fit_capacity_curve(id.clone(), executor_config);
+        let result = scheduler.run().await;
+        fit.abort();
+        let server_metrics = self.server_metrics_diff(server_metrics_start).await;
+        let vllm_stats = match vllm_stats {
+            Some(collection) => Some(collection.stop().await),
+            None => None,
+        };
+        self.run_hook(HookKind::PostStage, Some(&id), Some(&stage_id))
+            .await;
+        result?;
+        let mut results = scheduler.get_results().lock().await.clone();
+        results.set_server_metrics(server_metrics);
+        results.set_vllm_stats(vllm_stats);
+        results.set_stage_id(stage_id);
+        self.check_assertions(&mut results)?;
+        self.report.add_benchmark_result(results.clone());
+        let knee =
+            CapacityCurve::from_results(&results, self.config.num_rates as usize, &self.config.slo)
+                .and_then(|curve| curve.knee());
+        self.event_bus.send(Event::Message(MessageEvent {
+            message: match knee {
+                Some(knee) => format!("Latency knee detected at: {:.2} req/s", knee.rate),
+                None => format!("No latency knee detected up to {:.2} req/s", max_rate),
+            },
+            timestamp: chrono::Utc::now(),
+            level: log::Level::Info,
+        }))?;
+
+        // notify end event
+        let summary = StageSummary::new(&results);
+        self.metrics_bus.publish(Metric::Stage(summary.clone()));
+        self.event_bus.send(Event::BenchmarkEnd(BenchmarkEvent {
+            id,
+            scheduler_type: ExecutorType::ConstantArrivalRate,
+            request_throughput: results.successful_request_rate().ok(),
+            progress: 100.0,
+            summary: Some(summary),
+            successful_requests: results.successful_requests() as u64,
+            failed_requests: results.failed_requests() as u64,
+        }))?;
+        Ok(())
+    }
+
+    /// Fit the capacity curve of a ramp stage from the request metrics while it runs, and report
+    /// the latency knee estimate on the event bus every `RAMP_FIT_INTERVAL`
+    fn fit_capacity_curve(&self, id: String, config: executors::ExecutorConfig) -> JoinHandle<()> {
+        let mut subscriber = self.metrics_bus.subscribe(&[Topic::Request]);
+        let event_bus = self.event_bus.clone();
+        let mut curve = CapacityCurve::new(
+            id.clone(),
+            config.rate.unwrap_or_default(),
+            config.ramp_to_rate.unwrap_or_default(),
+            config.duration,
+            self.config.num_rates as usize,
+        );
+        let start = tokio::time::Instant::now();
+        tokio::spawn(async move {
+            let mut interval =
+                tokio::time::interval_at(start + RAMP_FIT_INTERVAL, RAMP_FIT_INTERVAL);
+            loop {
+                tokio::select! {
+                    metric = subscriber.recv() => match metric {
+                        Some(Metric::Request(sample)) if sample.id == id => {
+                            // requests are published when they end
+                            let sent = start
+                                .elapsed()
+                                .saturating_sub(sample.e2e_latency.unwrap_or_default());
+                            curve.add(
+                                sent,
+                                sample.e2e_latency,
+                                sample.num_generated_tokens,
+                                sample.failed,
+                            );
+                        }
+                        Some(_) => {}
+                        None => break,
+                    },
+                    _ = interval.tick() => {
+                        let rate = config.rate_at(start.elapsed()).unwrap_or_default();
+                        let message = match curve.knee() {
+                            Some(knee) => format!(
+                                "Ramp at {:.2} req/s, latency knee estimated at {:.2} req/s",
+                                rate, knee.rate
+                            ),
+                            None => format!("Ramp at {:.2} req/s, no latency knee yet", rate),
+                        };
+                        let _ = event_bus.send(Event::Message(MessageEvent {
+                            message,
+                            timestamp: chrono::Utc::now(),
+                            level: log::Level::Info,
+                        }));
+                    }
+                }
+            }
+        })
+    }
+
     pub async fn run_rate(&mut self, rate: f64) -> anyhow::Result<()> {
         debug!("Running benchmark with rate: {} req/s", rate);
-
         let id = format!("constant@{:.2}req/s", rate);
+        self.run_constant_arrival_rate(id, rate, None).await
+    }
+
+    /// Run the step profile as a single constant arrival rate stage, so the load isn't interrupted
+    /// between steps, and report each step as its own result
+    pub async fn run_steps(&mut self) -> anyhow::Result<()> {
+        let profile = self
+            .config
+            .step_profile
+            .clone()
+            .expect("config already validated");
+        let rates = profile
+            .rates
+            .iter()
+            .map(|rate| format!("{rate:.2}"))
+            .collect::<Vec<_>>();
+        let id = format!("steps@{}req/s", rates.join("-"));
+        if self.skip_past_deadline(&id)? {
+            return Ok(());
+        }
+
+        let stage_id = self.next_stage_id(&id);
 
         // notify start event
         self.event_bus.send(Event::BenchmarkStart(BenchmarkEvent {
@@ -369,42 +1565,276 @@ impl Benchmark {
             scheduler_type: ExecutorType::ConstantArrivalRate,
             request_throughput: None,
             progress: 0.0,
-            results: None,
+            summary: None,
             successful_requests: 0,
             failed_requests: 0,
         }))?;
 
-        // create progress handler
-        let tx = self.handle_progress(id.clone()).await;
+        // start scheduler, server metrics aren't scraped as they can't be split per step
+        self.run_hook(HookKind::PreStage, Some(&id), Some(&stage_id))
+            .await;
+        let duration = self.stage_duration(&id, profile.duration());
+        let mut scheduler = scheduler::Scheduler::new(
+            id.clone(),
+            self.backend.clone(),
+            scheduler::ExecutorType::ConstantArrivalRate,
+            executors::ExecutorConfig {
+                max_vus: self.config.max_vus,
+                duration,
+                rate: profile.rates.first().copied(),
+                ramp_to_rate: None,
+                sample_responses: self.config.sample_responses,
+                response_channel_capacity: self.config.response_channel_capacity,
+                repetition_threshold: self.config.repetition_threshold,
+                reading_rate: self.config.reading_rate,
+                answer_scoring: self.config.answer_scoring,
+                answer_regexes: self.config.answer_regexes.clone(),
+                request_slo: self.config.request_slo,
+                response_processors: self.config.response_processors.clone(),
+                vu_stagger: Duration::ZERO,
+                timeouts: self.config.timeouts,
+                rate_curve: Some(profile.rate_curve()),
+                vu_cap: self.vu_cap.clone(),
+                pause: self.pause.clone(),
+                stage_warmup: None,
+                arrival_distribution: self.config.arrival_distribution,
+                trace: None,
+                num_requests: None,
+                heartbeat_interval: self.config.heartbeat_interval,
+                max_stored_responses: self.config.max_stored_responses,
+                time_series_window: self.config.time_series_window,
+            },
+            self.requests.clone(),
+            self.metrics_bus.clone(),
+            self.stop_sender.clone(),
+        );
+        let result = scheduler.run().await;
+        self.run_hook(HookKind::PostStage, Some(&id), Some(&stage_id))
+            .await;
+        result?;
+        let results = scheduler.get_results().lock().await.clone();
+
+        // steps after a deadline shortening the profile didn't start
+        let started =
+            (duration.as_secs_f64() / profile.step_duration.as_secs_f64()).ceil() as usize;
+        for (i, mut step) in results.split_steps(&profile).into_iter().enumerate() {
+            if i >= started {
+                self.report.add_skipped_stage(step.id);
+                continue;
+            }
+            if step.total_requests() == 0 {
+                continue;
+            }
+            step.set_stage_id(self.next_stage_id(&step.id));
+            self.check_assertions(&mut step)?;
+            self.report.add_benchmark_result(step.clone());
+            let summary = StageSummary::new(&step);
+            self.metrics_bus.publish(Metric::Stage(summary.clone()));
+            self.event_bus.send(Event::BenchmarkEnd(BenchmarkEvent {
+                id: step.id.clone(),
+                scheduler_type: ExecutorType::ConstantArrivalRate,
+                request_throughput: step.successful_request_rate().ok(),
+                progress: 100.0,
+                summary: Some(summary),
+                successful_requests: step.successful_requests() as u64,
+                failed_requests: step.failed_requests() as u64,
+            }))?;
+        }
+
+        // notify end event, the results are in the steps
+        self.event_bus.send(Event::BenchmarkEnd(BenchmarkEvent {
+            id,
+            scheduler_type: ExecutorType::ConstantArrivalRate,
+            request_throughput: results.successful_request_rate().ok(),
+            progress: 100.0,
+            summary: None,
+            successful_requests: results.successful_requests() as u64,
+            failed_requests: results.failed_requests() as u64,
+        }))?;
+        Ok(())
+    }
+
+    /// Replay the trace as a single stage, sending its requests at their recorded times
+    pub async fn run_replay(&mut self) -> anyhow::Result<()> {
+        let trace = self.config.trace.clone().expect("config already validated");
+        let id = format!("replay@{:.2}req/s", trace.average_rate());
+        if self.skip_past_deadline(&id)? {
+            return Ok(());
+        }
+
+        let stage_id = self.next_stage_id(&id);
+
+        // notify start event
+        self.event_bus.send(Event::BenchmarkStart(BenchmarkEvent {
+            id: id.clone(),
+            scheduler_type: ExecutorType::TraceReplay,
+            request_throughput: None,
+            progress: 0.0,
+            summary: None,
+            successful_requests: 0,
+            failed_requests: 0,
+        }))?;
 
         // start scheduler
+        self.run_hook(HookKind::PreStage, Some(&id), Some(&stage_id))
+            .await;
+        let server_metrics_start = self.scrape_server_metrics().await;
+        let vllm_stats = self.vllm_stats.as_ref().map(|c| c.start());
         let mut scheduler = scheduler::Scheduler::new(
+            id.clone(),
+            self.backend.clone(),
+            scheduler::ExecutorType::TraceReplay,
+            executors::ExecutorConfig {
+                max_vus: self.config.max_vus,
+                duration: self.stage_duration(&id, trace.duration()),
+                rate: Some(trace.average_rate()),
+                ramp_to_rate: None,
+                sample_responses: self.config.sample_responses,
+                response_channel_capacity: self.config.response_channel_capacity,
+                repetition_threshold: self.config.repetition_threshold,
+                reading_rate: self.config.reading_rate,
+                answer_scoring: self.config.answer_scoring,
+                answer_regexes: self.config.answer_regexes.clone(),
+                request_slo: self.config.request_slo,
+                response_processors: self.config.response_processors.clone(),
+                vu_stagger: Duration::ZERO,
+                timeouts: self.config.timeouts,
+                rate_curve: None,
+                vu_cap: self.vu_cap.clone(),
+                pause: self.pause.clone(),
+                stage_warmup: None,
+                arrival_distribution: ArrivalDistribution::default(),
+                trace: Some(trace),
+                num_requests: None,
+                heartbeat_interval: self.config.heartbeat_interval,
+                max_stored_responses: self.config.max_stored_responses,
+                time_series_window: self.config.time_series_window,
+            },
+            self.requests.clone(),
+            self.metrics_bus.clone(),
+            self.stop_sender.clone(),
+        );
+        let result = scheduler.run().await;
+        let server_metrics = self.server_metrics_diff(server_metrics_start).await;
+        let vllm_stats = match vllm_stats {
+            Some(collection) => Some(collection.stop().await),
+            None => None,
+        };
+        self.run_hook(HookKind::PostStage, Some(&id), Some(&stage_id))
+            .await;
+        result?;
+        let mut results = scheduler.get_results().lock().await.clone();
+        results.set_server_metrics(server_metrics);
+        results.set_vllm_stats(vllm_stats);
+        results.set_stage_id(stage_id);
+        self.check_assertions(&mut results)?;
+        self.report.add_benchmark_result(results.clone());
+
+        // notify end event
+        let summary = StageSummary::new(&results);
+        self.metrics_bus.publish(Metric::Stage(summary.clone()));
+        self.event_bus.send(Event::BenchmarkEnd(BenchmarkEvent {
             id,
+            scheduler_type: ExecutorType::TraceReplay,
+            request_throughput: results.successful_request_rate().ok(),
+            progress: 100.0,
+            summary: Some(summary),
+            successful_requests: results.successful_requests() as u64,
+            failed_requests: results.failed_requests() as u64,
+        }))?;
+        Ok(())
+    }
+
+    /// Run a constant arrival rate stage, at `rate` or following `rate_curve` when set
+    async fn run_constant_arrival_rate(
+        &mut self,
+        id: String,
+        rate: f64,
+        rate_curve: Option<RateCurve>,
+    ) -> anyhow::Result<()> {
+        if self.skip_past_deadline(&id)? {
+            return Ok(());
+        }
+
+        let stage_id = self.next_stage_id(&id);
+
+        // notify start event
+        self.event_bus.send(Event::BenchmarkStart(BenchmarkEvent {
+            id: id.clone(),
+            scheduler_type: ExecutorType::ConstantArrivalRate,
+            request_throughput: None,
+            progress: 0.0,
+            summary: None,
+            successful_requests: 0,
+            failed_requests: 0,
+        }))?;
+
+        // start scheduler
+        self.run_hook(HookKind::PreStage, Some(&id), Some(&stage_id))
+            .await;
+        let server_metrics_start = self.scrape_server_metrics().await;
+        let vllm_stats = self.vllm_stats.as_ref().map(|c| c.start());
+        let stage_warmup = self.config.stage_warmup.unwrap_or_default();
+        let mut scheduler = scheduler::Scheduler::new(
+            id.clone(),
             self.backend.clone(),
             scheduler::ExecutorType::ConstantArrivalRate,
             executors::ExecutorConfig {
                 max_vus: self.config.max_vus,
-                duration: self.config.duration,
+                duration: self.stage_duration(&id, self.config.duration + stage_warmup),
                 rate: Some(rate),
+                ramp_to_rate: None,
+                sample_responses: self.config.sample_responses,
+                response_channel_capacity: self.config.response_channel_capacity,
+                repetition_threshold: self.config.repetition_threshold,
+                reading_rate: self.config.reading_rate,
+                answer_scoring: self.config.answer_scoring,
+                answer_regexes: self.config.answer_regexes.clone(),
+                request_slo: self.config.request_slo,
+                response_processors: self.config.response_processors.clone(),
+                vu_stagger: Duration::ZERO,
+                timeouts: self.config.timeouts,
+                rate_curve,
+                vu_cap: self.vu_cap.clone(),
+                pause: self.pause.clone(),
+                stage_warmup: self.config.stage_warmup,
+                arrival_distribution: self.config.arrival_distribution,
+                trace: None,
+                num_requests: self.config.num_requests,
+                heartbeat_interval: self.config.heartbeat_interval,
+                max_stored_responses: self.config.max_stored_responses,
+                time_series_window: self.config.time_series_window,
             },
             self.requests.clone(),
-            tx.clone(),
+            self.metrics_bus.clone(),
             self.stop_sender.clone(),
         );
-        scheduler.run().await?;
+        let result = scheduler.run().await;
+        let server_metrics = self.server_metrics_diff(server_metrics_start).await;
+        let vllm_stats = match vllm_stats {
+            Some(collection) => Some(collection.stop().await),
+            None => None,
+        };
+        self.run_hook(HookKind::PostStage, Some(&id), Some(&stage_id))
+            .await;
+        result?;
         let results = scheduler.get_results().lock().await.clone();
+        let mut results = self.split_stage_warmup(results)?;
+        results.set_server_metrics(server_metrics);
+        results.set_vllm_stats(vllm_stats);
+        results.set_stage_id(stage_id);
+        self.check_assertions(&mut results)?;
         self.report.add_benchmark_result(results.clone());
 
-        // send None to close the progress handler
-        tx.send(None).await.unwrap();
-
         // notify end event
+        let summary = StageSummary::new(&results);
+        self.metrics_bus.publish(Metric::Stage(summary.clone()));
         self.event_bus.send(Event::BenchmarkEnd(BenchmarkEvent {
-            id: format!("constant@{:.2}req/s", rate),
+            id,
             scheduler_type: ExecutorType::ConstantArrivalRate,
             request_throughput: results.successful_request_rate().ok(),
             progress: 100.0,
-            results: Some(results.clone()),
+            summary: Some(summary),
             successful_requests: results.successful_requests() as u64,
             failed_requests: results.failed_requests() as u64,
         }))?;
@@ -415,10 +1845,32 @@ impl Benchmark {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::requests::random_uuid;
     use crate::requests::DummyTextGenerationBackend;
     use crate::requests::DummyTextRequestGenerator;
+    use crate::results::DEFAULT_PERCENTILES;
     use std::time::Duration;
 
+    #[test]
+    fn test_concurrency_steps() {
+        assert_eq!(concurrency_steps(1), vec![1]);
+        assert_eq!(concurrency_steps(8), vec![1, 2, 4, 8]);
+        assert_eq!(concurrency_steps(12), vec![1, 2, 4, 8, 12]);
+    }
+
+    #[test]
+    fn test_stage_order() {
+        let stages = (1..=10).collect::<Vec<_>>();
+        assert_eq!(stage_order(stages.clone(), None), stages);
+        let shuffled = stage_order(stages.clone(), Some(42));
+        assert_ne!(shuffled, stages);
+        // the seed makes the order reproducible
+        assert_eq!(stage_order(stages.clone(), Some(42)), shuffled);
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, stages);
+    }
+
     #[tokio::test]
     async fn test_sweep_benchmark_timings() {
         let generation_time = Duration::from_secs(2);
@@ -427,23 +1879,85 @@ mod tests {
         let backend = Box::new(DummyTextGenerationBackend::new(Duration::from_secs(
             generation_time.as_secs(),
         )));
-        let requests_generator = Arc::from(Mutex::from(DummyTextRequestGenerator::new()));
+        let requests_generator = Arc::new(DummyTextRequestGenerator::new());
         let mut benchmark = Benchmark::new(
             BenchmarkConfig {
+                name: None,
+                description: None,
+                endpoint_label: None,
                 max_vus: 100,
                 duration: Duration::from_secs(10),
+                num_requests: None,
                 benchmark_kind: BenchmarkKind::Sweep,
                 warmup_duration: Duration::from_secs(1),
+                warmup_cold_requests: 0,
+                stage_warmup: None,
+                shuffle_stages: None,
                 rates: None,
                 num_rates: 2,
                 prompt_options: None,
                 decode_options: None,
                 tokenizer: "gpt2".to_string(),
+                tokenizer_revision: None,
+                sample_responses: 0,
+                response_channel_capacity: 16384,
+                repetition_threshold: None,
+                reading_rate: None,
+                trace_sample: None,
+                heartbeat_interval: None,
+                max_stored_responses: None,
+                time_series_window: None,
+                answer_scoring: None,
+                answer_regexes: AnswerRegexes::default(),
+                request_slo: None,
+                max_context_length: None,
+                vu_stagger: Duration::ZERO,
+                metrics_bus_capacity: 1024,
+                hooks: HooksConfig::default(),
+                assertions: Vec::new(),
+                slo: Vec::new(),
+                abort_on_assertion_failure: false,
+                endpoint: OpenAIEndpoint::ChatCompletions,
+                prompt_template: None,
+                derived_metrics: Vec::new(),
+                percentiles: DEFAULT_PERCENTILES.to_vec(),
+                server_metrics_url: None,
+                server_metrics: None,
+                vllm_stats: false,
+                batch_size: 100,
+                batch_poll_interval: Duration::from_secs(5),
+                realtime_audio: false,
+                backend: BackendKind::OpenAI,
+                timeouts: RequestTimeouts::default(),
+                retry: RetryPolicy::default(),
+                tenants: Vec::new(),
+                adaptive_decode: false,
+                rate_curve: None,
+                arrival_distribution: ArrivalDistribution::Constant,
+                step_profile: None,
+                trace: None,
+                burst_profile: None,
+                client_headroom: HeadroomPolicy::Warn,
+                max_total_duration: None,
                 extra_metadata: None,
+                stage_labels: Vec::new(),
+                effective_config: None,
+                run_id: random_uuid(),
+                output_format: OutputFormat::default(),
+                raw_results: None,
+                csv_summary: false,
+                parquet: false,
+                markdown_output: None,
+                html_output: None,
+                baseline: None,
+                otlp_endpoint: None,
+                response_processors: ResponseProcessors::default(),
+                results_sink: None,
             },
             backend,
             requests_generator,
             event_tx,
+            MetricsBus::new(1024),
             stop_sender,
         );
         let report = benchmark.run().await.unwrap();
@@ -477,4 +1991,180 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn test_max_total_duration() {
+        let (event_tx, mut _event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (stop_sender, _) = tokio::sync::broadcast::channel(1);
+        let backend = Box::new(DummyTextGenerationBackend::new(Duration::from_millis(500)));
+        let requests_generator = Arc::new(DummyTextRequestGenerator::new());
+        let mut benchmark = Benchmark::new(
+            BenchmarkConfig {
+                max_vus: 10,
+                duration: Duration::from_secs(3),
+                benchmark_kind: BenchmarkKind::Rate,
+                rates: Some(vec![1.0, 2.0, 3.0]),
+                num_rates: 3,
+                response_channel_capacity: 16384,
+                metrics_bus_capacity: 1024,
+                client_headroom: HeadroomPolicy::Off,
+                max_total_duration: Some(Duration::from_secs(5)),
+                ..test_config()
+            },
+            backend,
+            requests_generator,
+            event_tx,
+            MetricsBus::new(1024),
+            stop_sender,
+        );
+        let report = benchmark.run().await.unwrap();
+        // warmup and the first rate fit, the last rate is past the deadline
+        let ids = report
+            .get_results()
+            .iter()
+            .map(|r| r.id.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids[..2], ["warmup", "constant@1.00req/s"]);
+        assert_eq!(
+            report.get_results()[1].stage_id().as_deref(),
+            Some("01-constant@1.00req/s")
+        );
+        assert_eq!(ids.len() + report.skipped_stages().len(), 4);
+        assert_eq!(
+            report.skipped_stages().last().unwrap(),
+            "constant@3.00req/s"
+        );
+        let planned = Duration::from_secs(3);
+        assert!(report
+            .get_results()
+            .iter()
+            .all(|r| r.executor_config().duration <= planned));
+    }
+
+    /// Configuration of a short sweep
+    fn test_config() -> BenchmarkConfig {
+        BenchmarkConfig {
+            name: None,
+            description: None,
+            endpoint_label: None,
+            max_vus: 1,
+            duration: Duration::from_secs(1),
+            num_requests: None,
+            benchmark_kind: BenchmarkKind::Sweep,
+            warmup_duration: Duration::from_secs(1),
+            warmup_cold_requests: 0,
+            stage_warmup: None,
+            shuffle_stages: None,
+            rates: None,
+            num_rates: 1,
+            prompt_options: None,
+            decode_options: None,
+            tokenizer: "meta-llama/Llama-3.1-8B-Instruct".to_string(),
+            tokenizer_revision: None,
+            sample_responses: 0,
+            response_channel_capacity: 1,
+            repetition_threshold: None,
+            reading_rate: None,
+            trace_sample: None,
+            heartbeat_interval: None,
+            max_stored_responses: None,
+            time_series_window: None,
+            answer_scoring: None,
+            answer_regexes: AnswerRegexes::default(),
+            request_slo: None,
+            max_context_length: None,
+            vu_stagger: Duration::ZERO,
+            metrics_bus_capacity: 1,
+            hooks: HooksConfig::default(),
+            assertions: Vec::new(),
+            slo: Vec::new(),
+            abort_on_assertion_failure: false,
+            endpoint: OpenAIEndpoint::ChatCompletions,
+            prompt_template: None,
+            derived_metrics: Vec::new(),
+            percentiles: DEFAULT_PERCENTILES.to_vec(),
+            server_metrics_url: None,
+            server_metrics: None,
+            vllm_stats: false,
+            batch_size: 100,
+            batch_poll_interval: Duration::from_secs(5),
+            realtime_audio: false,
+            backend: BackendKind::OpenAI,
+            timeouts: RequestTimeouts::default(),
+            retry: RetryPolicy::default(),
+            tenants: Vec::new(),
+            adaptive_decode: false,
+            rate_curve: None,
+            arrival_distribution: ArrivalDistribution::Constant,
+            step_profile: None,
+            trace: None,
+            burst_profile: None,
+            client_headroom: HeadroomPolicy::Warn,
+            max_total_duration: None,
+            extra_metadata: None,
+            stage_labels: Vec::new(),
+            effective_config: None,
+            run_id: random_uuid(),
+            output_format: OutputFormat::default(),
+            raw_results: None,
+            csv_summary: false,
+            parquet: false,
+            markdown_output: None,
+            html_output: None,
+            baseline: None,
+            otlp_endpoint: None,
+            response_processors: ResponseProcessors::default(),
+            results_sink: None,
+        }
+    }
+
+    #[test]
+    fn test_file_stem() {
+        let mut config = test_config();
+        assert_eq!(config.file_stem(), "meta-llama_Llama-3_1-8B-Instruct");
+        config.name = Some("h100 tp2/fp8".to_string());
+        assert_eq!(config.file_stem(), "h100_tp2_fp8");
+        // metadata iteration order must not change the hash
+        let meta = |keys: &[&str]| {
+            Some(
+                keys.iter()
+                    .map(|k| (k.to_string(), k.to_string()))
+                    .collect(),
+            )
+        };
+        config.extra_metadata = meta(&["gpu", "tp", "dtype", "engine"]);
+        let hash = config.config_hash();
+        config.extra_metadata = meta(&["engine", "dtype", "tp", "gpu"]);
+        assert_eq!(config.config_hash(), hash);
+        config.max_vus = 2;
+        assert_ne!(config.config_hash(), hash);
+        config.name = Some("nul".to_string());
+        assert_eq!(config.file_stem(), "_nul");
+    }
+
+    #[tokio::test]
+    async fn test_post_run_hook_on_error() {
+        let (event_tx, mut _event_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (stop_sender, _) = tokio::sync::broadcast::channel(1);
+        let mut benchmark = Benchmark::new(
+            BenchmarkConfig {
+                // not run by the streaming scheduler
+                benchmark_kind: BenchmarkKind::Batch,
+                hooks: HooksConfig {
+                    post_run: Some("echo done".to_string()),
+                    ..Default::default()
+                },
+                ..test_config()
+            },
+            Box::new(DummyTextGenerationBackend::new(Duration::from_millis(10))),
+            Arc::new(DummyTextRequestGenerator::new()),
+            event_tx,
+            MetricsBus::new(1024),
+            stop_sender,
+        );
+        assert!(benchmark.run().await.is_err());
+        let hooks = benchmark.report.hooks();
+        assert_eq!(hooks.len(), 1);
+        assert_eq!(hooks[0].stdout, "done\n");
+    }
 }
@@ -0,0 +1,255 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::Value;
+use std::fs::File;
+use std::path::PathBuf;
+use tabled::builder::Builder;
+
+const REDACTED: &str = "<redacted>";
+
+pub struct BundleConfiguration {
+    pub report: PathBuf,
+    pub output: Option<PathBuf>,
+}
+
+/// Remove data that may be private from a benchmark report: prompts and generated texts of the
+/// response samples, hook commands and outputs, server, OTLP collector and results sink URLs,
+/// script paths and the host name
+pub fn anonymize(report: &mut Value) {
+    for results in report["results"].as_array_mut().into_iter().flatten() {
+        for sample in results["response_samples"]
+            .as_array_mut()
+            .into_iter()
+            .flatten()
+        {
+            for field in ["system_prompt", "prompt", "response"] {
+                if !sample[field].is_null() {
+                    sample[field] = Value::from(REDACTED);
+                }
+            }
+        }
+    }
+    for hook in report["hooks"].as_array_mut().into_iter().flatten() {
+        for field in ["command", "stdout", "stderr"] {
+            hook[field] = Value::from(REDACTED);
+        }
+    }
+    for key in ["config", "effective_config"] {
+        let config = &mut report[key];
+        if !config.is_object() {
+            continue;
+        }
+        for field in [
+            "url",
+            "server_metrics_url",
+            "request_script",
+            "otlp_endpoint",
+            "results_sink",
+        ] {
+            if !config[field].is_null() {
+                config[field] = Value::from(REDACTED);
+            }
+        }
+        for url in config["extra_urls"].as_array_mut().into_iter().flatten() {
+            *url = Value::from(REDACTED);
+        }
+        if let Some(hooks) = config["hooks"].as_object_mut() {
+            hooks
+                .values_mut()
+                .filter(|v| !v.is_null())
+                .for_each(|v| *v = Value::from(REDACTED));
+        }
+    }
+    if let Some(system) = report["system"].as_object_mut() {
+        system.remove("hostname");
+    }
+}
+
+fn summary(report: &Value) -> String {
+    let mut builder = Builder::default();
+    builder.set_header([
+        "Benchmark",
+        "QPS",
+        "Throughput (tokens/s)",
+        "TTFT p50 (ms)",
+        "ITL p50 (ms)",
+        "E2E Latency p99 (ms)",
+        "Failed requests",
+    ]);
+    for results in report["results"].as_array().into_iter().flatten() {
+        let number = |v: &Value| v.as_f64().map_or("N/A".to_string(), |v| format!("{v:.2}"));
+        builder.push_record([
+            results["id"].as_str().unwrap_or_default().to_string(),
+            number(&results["request_rate"]),
+            number(&results["token_throughput_secs"]),
+            number(&results["time_to_first_token_ms"]["p50"]),
+            number(&results["inter_token_latency_ms"]["p50"]),
+            number(&results["e2e_latency_ms"]["p99"]),
+            results["failed_requests"].to_string(),
+        ]);
+    }
+    let mut table = builder.build();
+    table.with(tabled::settings::Style::markdown());
+    let name = report["config"]["name"]
+        .as_str()
+        .or(report["config"]["tokenizer"].as_str())
+        .unwrap_or("benchmark");
+    // reports written before run ids have none
+    let run = report["run_id"]
+        .as_str()
+        .map_or(String::new(), |run_id| format!("Run `{run_id}`\n\n"));
+    format!("# {name}\n\n{run}{table}\n")
+}
+
+/// SVG scatter chart of token throughput against p99 end-to-end latency for each step
+fn throughput_latency_chart(report: &Value) -> String {
+    let points = report["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|r| r["id"] != "warmup")
+        .filter_map(|r| {
+            Some((
+                r["id"].as_str()?.to_string(),
+                r["token_throughput_secs"].as_f64()?,
+                r["e2e_latency_ms"]["p99"].as_f64()?,
+            ))
+        })
+        .collect::<Vec<_>>();
+    let (width, height, margin) = (640.0, 400.0, 60.0);
+    let max_x = points.iter().map(|p| p.1).fold(1.0, f64::max);
+    let max_y = points.iter().map(|p| p.2).fold(1.0, f64::max);
+    let x = |v: f64| margin + v / max_x * (width - 2.0 * margin);
+    let y = |v: f64| height - margin - v / max_y * (height - 2.0 * margin);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" font-family=\"sans-serif\" font-size=\"12\">\n\
+         <line x1=\"{margin}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"black\"/>\n\
+         <line x1=\"{margin}\" y1=\"{margin}\" x2=\"{margin}\" y2=\"{b}\" stroke=\"black\"/>\n\
+         <text x=\"{cx}\" y=\"{xl}\" text-anchor=\"middle\">Throughput (tokens/s), max {max_x:.0}</text>\n\
+         <text x=\"15\" y=\"{cy}\" text-anchor=\"middle\" transform=\"rotate(-90 15 {cy})\">E2E latency p99 (ms), max {max_y:.0}</text>\n",
+        b = height - margin,
+        r = width - margin,
+        cx = width / 2.0,
+        cy = height / 2.0,
+        xl = height - margin / 3.0,
+    );
+    for (id, throughput, latency) in &points {
+        svg += &format!(
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"steelblue\"><title>{id}</title></circle>\n",
+            x(*throughput),
+            y(*latency)
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+fn environment(report: &Value) -> Value {
+    serde_json::json!({
+        "run_id": report["run_id"],
+        "tool_version": env!("CARGO_PKG_VERSION"),
+        "git_sha": option_env!("VERGEN_GIT_SHA"),
+        "system": report["system"],
+        "start_time": report["start_time"],
+        "end_time": report["end_time"],
+        "bundle_created_at": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+fn append(
+    archive: &mut tar::Builder<GzEncoder<File>>,
+    path: &str,
+    content: &[u8],
+) -> anyhow::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    header.set_cksum();
+    archive.append_data(&mut header, path, content)?;
+    Ok(())
+}
+
+/// Package an anonymized report with its summary, charts and environment metadata in a
+/// `.tar.gz` archive, written next to the report unless an output path is given
+pub fn run_export_bundle(config: BundleConfiguration) -> anyhow::Result<PathBuf> {
+    let content = std::fs::read_to_string(&config.report)?;
+    let mut report: Value = serde_json::from_str(&content)?;
+    if !report["results"].is_array() && report["batch"].is_null() {
+        return Err(anyhow::anyhow!(
+            "{} is not a benchmark report",
+            config.report.display()
+        ));
+    }
+    anonymize(&mut report);
+    let output = config
+        .output
+        .unwrap_or_else(|| config.report.with_extension("bundle.tar.gz"));
+    let encoder = GzEncoder::new(File::create(&output)?, Compression::default());
+    let mut archive = tar::Builder::new(encoder);
+    append(
+        &mut archive,
+        "config.json",
+        serde_json::to_string_pretty(&report["config"])?.as_bytes(),
+    )?;
+    append(
+        &mut archive,
+        "report.json",
+        serde_json::to_string_pretty(&report)?.as_bytes(),
+    )?;
+    append(&mut archive, "summary.md", summary(&report).as_bytes())?;
+    append(
+        &mut archive,
+        "charts/throughput_latency.svg",
+        throughput_latency_chart(&report).as_bytes(),
+    )?;
+    append(
+        &mut archive,
+        "environment.json",
+        serde_json::to_string_pretty(&environment(&report))?.as_bytes(),
+    )?;
+    archive.into_inner()?.finish()?;
+    println!("Bundle saved to {}", output.display());
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anonymize() {
+        let mut report = serde_json::json!({
+            "config": {"tokenizer": "gpt2", "server_metrics_url": "http://10.0.0.1/metrics",
+                       "hooks": {"pre_run": "curl -H 'token: x'", "post_run": null}},
+            "effective_config": {"url": "http://10.0.0.1:8000", "request_script": null,
+                                 "extra_urls": ["http://10.0.0.2:8000"],
+                                 "otlp_endpoint": "http://10.0.0.3:4318",
+                                 "results_sink": "s3://private-bucket/runs", "results_sink_tags": null},
+            "results": [{"id": "warmup", "response_samples": [
+                {"system_prompt": null, "prompt": "secret", "response": "answer", "failed": false}
+            ]}],
+            "hooks": [{"command": "curl -H 'token: x'", "stdout": "ok", "stderr": "", "success": true}],
+            "system": {"hostname": "gpu-node-12", "os_name": "Linux"},
+        });
+        anonymize(&mut report);
+        let sample = &report["results"][0]["response_samples"][0];
+        assert_eq!(sample["prompt"], REDACTED);
+        assert_eq!(sample["response"], REDACTED);
+        assert!(sample["system_prompt"].is_null());
+        assert_eq!(report["config"]["server_metrics_url"], REDACTED);
+        assert_eq!(report["config"]["hooks"]["pre_run"], REDACTED);
+        assert_eq!(report["effective_config"]["url"], REDACTED);
+        assert!(report["effective_config"]["request_script"].is_null());
+        assert_eq!(report["effective_config"]["extra_urls"][0], REDACTED);
+        assert_eq!(report["effective_config"]["otlp_endpoint"], REDACTED);
+        assert_eq!(report["effective_config"]["results_sink"], REDACTED);
+        assert!(report["effective_config"]["results_sink_tags"].is_null());
+        assert!(report["config"]["hooks"]["post_run"].is_null());
+        assert_eq!(report["hooks"][0]["command"], REDACTED);
+        assert_eq!(report["hooks"][0]["success"], true);
+        assert!(report["system"].get("hostname").is_none());
+        assert!(!report.to_string().contains("secret"));
+        assert!(!report.to_string().contains("10.0.0."));
+    }
+}
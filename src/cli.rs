@@ -0,0 +1,855 @@
+//! Command line interface of the benchmarker. Embedding binaries can parse [`Args`] (or flatten
+//! it into their own clap parser) and map it to a [`RunConfiguration`] with
+//! [`Args::run_configuration`], so they expose the exact same flags.
+
+use crate::{
+    AlertThresholds, HooksConfig, IdempotencyKeyMode, Preset, RequestTimeouts, RetryPolicy,
+    RunConfiguration, TokenizeOptions, DEFAULT_FIM_TEMPLATE,
+};
+use clap::error::ErrorKind::InvalidValue;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Error, Parser, Subcommand};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Name of the benchmark, stored in the report and used in the results file name
+    /// instead of the tokenizer name
+    #[clap(long, env)]
+    pub benchmark_name: Option<String>,
+    /// Free-form description of the benchmark, stored in the report
+    #[clap(long, env)]
+    pub description: Option<String>,
+    /// Built-in workload defining the benchmark kind, virtual users, prompt and decode token
+    /// distributions and SLOs checked as assertions (chat-interactive, rag-long-context,
+    /// batch-summarization, long-summarization, code-completion). Options given explicitly take precedence.
+    #[clap(long, env)]
+    pub preset: Option<String>,
+
+    /// The name of the tokenizer to use
+    #[clap(short, long, env, required = true)]
+    pub tokenizer_name: Option<String>,
+    /// The revision (branch, tag or commit) of the tokenizer to use
+    #[clap(long, env)]
+    pub tokenizer_revision: Option<String>,
+    /// Compatible open tokenizer to use if the tokenizer can't be loaded, e.g. for gated models
+    #[clap(long, env)]
+    pub tokenizer_fallback: Option<String>,
+
+    /// The name of the model to use. If not provided, the same name as the tokenizer will be used.
+    #[clap(long, env)]
+    pub model_name: Option<String>,
+    /// API key sent as a bearer token, for authenticated gateways and hosted endpoints.
+    /// It is not recorded in the report.
+    #[clap(long, env = "INFERENCE_BENCHMARKER_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
+
+    /// The maximum number of virtual users to use
+    #[clap(default_value = "128", short, long, env)]
+    pub max_vus: u64,
+    /// The duration of each benchmark step (for "batch" benchmark, the max time to wait for the batch)
+    #[clap(default_value = "120s", short, long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub duration: Duration,
+    /// End each benchmark step once this many requests completed, for reproducible runs. The
+    /// duration still caps each step (only valid for "throughput", "sweep", "rate" and
+    /// "concurrency" benchmarks)
+    #[clap(long, env)]
+    pub num_requests: Option<u64>,
+    /// A list of rates of requests to send per second (only valid for the ConstantArrivalRate benchmark).
+    /// For the "ramp" benchmark, a single rate to ramp up to. For the "search" benchmark, a single
+    /// max rate to search below.
+    #[clap(short, long, env)]
+    pub rates: Option<Vec<f64>>,
+    /// The number of rates to sweep through (only valid for the "sweep" and "ramp" benchmarks)
+    /// The rates will be linearly spaced up to the detected maximum rate.
+    /// For the "search" benchmark, the max number of binary search steps
+    #[clap(default_value = "10", long, env)]
+    pub num_rates: u64,
+
+    /// Start the virtual users of constant VUs steps evenly over this duration instead of all at once,
+    /// so their first requests don't skew the start of the TTFT distribution
+    #[clap(default_value = "0s", long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub vu_stagger: Duration,
+    /// Generate this many upcoming requests ahead of time on a background thread, so request
+    /// generation never delays the arrivals of rate steps. 0 generates each request on dispatch
+    #[clap(default_value = "0", long, env)]
+    pub prefetch_requests: usize,
+    /// The kind of benchmark to run (throughput, sweep, rate, ramp, batch, diurnal, step, replay,
+    /// burst, concurrency, search)
+    #[clap(default_value = "sweep", short, long, env)]
+    pub benchmark_kind: String,
+    /// For the "diurnal" benchmark, the arrival rate curve followed over the duration:
+    /// `sine:min=<req/s>,max=<req/s>,period=<duration>` or `csv:<path>[,interval=<duration>]`
+    /// with one rate per interval (1h by default) in the last column of the CSV file
+    #[clap(long, env)]
+    pub rate_curve: Option<String>,
+    /// For the "step" benchmark, the rates stepped through in a single stage and the duration of each
+    /// step, e.g. `1,2,4,8@60s`. Each step is reported separately
+    #[clap(long, env)]
+    pub step_profile: Option<String>,
+    /// For the "replay" benchmark, the recorded trace replayed in a single stage:
+    /// `<path>[,time_scale=<factor>]` with one JSON request per line holding its `timestamp` (seconds
+    /// from the start of the trace), `prompt_tokens` and `output_tokens`. Timestamps are multiplied
+    /// by the time scale, 0.5 replays the trace twice as fast. Prompts are random tokens
+    #[clap(long, env)]
+    pub trace: Option<String>,
+    /// For the "burst" benchmark, the baseline rate and the spikes interrupting it over the duration:
+    /// `base=<req/s>,burst=<req/s>,duration=<duration>,period=<duration>`, e.g.
+    /// `base=2,burst=20,duration=10s,period=60s` spikes to 20 req/s for the last 10s of every minute.
+    /// Latencies are reported for the baseline and burst phases
+    #[clap(long, env)]
+    pub burst_profile: Option<String>,
+    /// How requests arrive in rate steps: `constant` evenly spaces them, `poisson` draws exponential
+    /// inter-arrival times (open-loop Poisson process), closer to bursty real traffic
+    #[clap(default_value = "constant", long, env)]
+    pub arrival_distribution: String,
+    /// Monitor the client CPU, memory and open files while benchmarking: `warn` when the client
+    /// saturates, `cap` to also cap the VUs at the ones in flight, or `off`. Saturations and caps
+    /// are recorded in the report
+    #[clap(default_value = "warn", long, env)]
+    pub client_headroom: String,
+    /// Bound the whole run (prewarm, all steps and hooks) to this duration, e.g. for time-boxed CI
+    /// jobs. The step running at the deadline is shortened and the next ones are skipped, which
+    /// the report records
+    #[clap(long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub max_total_duration: Option<Duration>,
+    /// Raise the open files limit of the process to this value (the hard limit by default). The run
+    /// fails early when the VUs can't all hold a connection within the limit
+    #[clap(long, env)]
+    pub open_files_limit: Option<u64>,
+    /// The duration of the prewarm step ran before the benchmark to warm up the backend (JIT, caches, etc.)
+    #[clap(default_value = "30s", short, long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub warmup: Duration,
+    /// Report the first N requests of the prewarm step separately (cold) and compare them with the next ones (warm),
+    /// to quantify compilation, graph capture or cache overheads. 0 disables the comparison.
+    #[clap(default_value = "0", long, env)]
+    pub warmup_cold_requests: u64,
+    /// Warm up each stage of throughput, sweep, rate, concurrency sweep and search benchmarks for this
+    /// duration (e.g. 10s) at its rate or VUs before measuring it, as the batching of the server settles
+    /// after a change. Warmups are reported apart from the stage results; disabled if not set.
+    #[clap(long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub stage_warmup: Option<Duration>,
+    /// Run the stages of sweep, rate and concurrency sweep benchmarks in a random order drawn from
+    /// this seed instead of increasing load, to control for server drift over long runs (thermal
+    /// throttling, memory fragmentation). The throughput step of sweeps still runs first
+    #[clap(long, env)]
+    pub shuffle_stages: Option<u64>,
+    /// Fail requests that can't connect to the server within this duration
+    #[clap(long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub connect_timeout: Option<Duration>,
+    /// Fail requests that receive no token within this duration, e.g. on a hung server
+    #[clap(long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub ttft_timeout: Option<Duration>,
+    /// Fail requests that don't complete within this duration, the step duration by default.
+    /// Timed out requests are counted as `timeout` failures
+    #[clap(long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub request_timeout: Option<Duration>,
+    /// Retry requests failing with a rate limit, server or connection error before any token, up
+    /// to this many times. Retries are counted by cause in the report. 0 disables retries
+    #[clap(default_value = "0", long, env)]
+    pub max_retries: u32,
+    /// Delay before the first retry of a request, doubled for each next one
+    #[clap(default_value = "500ms", long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub retry_backoff: Duration,
+    /// Idempotency keys of retried requests, with --idempotency-key-header: `per-request` reuses
+    /// the key of the request for its retries, `per-attempt` sends a new key for each attempt
+    #[clap(default_value = "per-request", long, env)]
+    pub idempotency_key_mode: IdempotencyKeyMode,
+    /// Send requests as this tenant, e.g. `name=gold,weight=3,api_key=<key>,header=X-Tenant:gold`,
+    /// to benchmark the fairness and per-tenant rate limits of serving gateways. Repeat for each
+    /// tenant: requests are spread over the tenants in proportion to their weights (1 by default),
+    /// with the tenant API key and headers replacing or adding to the ones of the run. Latency
+    /// and throughput are reported per tenant. API keys and header values are not recorded.
+    #[clap(long = "tenant", env = "TENANTS", value_delimiter = ';')]
+    pub tenants: Vec<String>,
+    /// When the server consistently stops before the decode target (EOS), scale the decode targets
+    /// of the next requests down to the observed completion lengths, so the requested token budget
+    /// matches the generated tokens. The scale reached at the end of each step is reported
+    #[clap(long, env)]
+    pub adaptive_decode: bool,
+    /// The URL of the backend to benchmark. Must be compatible with OpenAI Message API.
+    /// IPv6 literals (http://[::1]:8000) and Unix domain sockets (unix:///path/to/socket) are supported.
+    /// WebSocket URLs (ws://, wss://) are used as-is as the streaming endpoint.
+    /// gRPC URLs (grpc://) stream from Triton Inference Server's gRPC API.
+    #[clap(default_value = "http://localhost:8000", short, long, env)]
+    #[arg(value_parser = parse_url)]
+    pub url: String,
+    /// Additional backends benchmarked with the same configuration, each with its own schedulers
+    /// and report. Can be repeated.
+    #[clap(long = "extra-url", env = "EXTRA_URLS", value_delimiter = ',')]
+    #[arg(value_parser = parse_url)]
+    pub extra_urls: Vec<String>,
+    /// Number of endpoints benchmarked at the same time when extra URLs are set. Endpoints run in
+    /// parallel must be on distinct hosts, fit in the client CPUs and require --no-console
+    #[clap(default_value = "1", long, env)]
+    pub parallel_endpoints: usize,
+    /// Disable console UI
+    #[clap(short, long, env)]
+    pub no_console: bool,
+    /// Constraints for prompt length.
+    /// No value means use the input prompt as defined in input dataset.
+    /// We sample the number of tokens to generate from a normal distribution.
+    /// Specified as a comma-separated list of key=value pairs.
+    /// * num_tokens: target number of prompt tokens
+    /// * min_tokens: minimum number of prompt tokens
+    /// * max_tokens: maximum number of prompt tokens
+    /// * variance: variance in the number of prompt tokens
+    ///
+    /// Example: num_tokens=200,max_tokens=210,min_tokens=190,variance=10
+    #[clap(long, env, value_parser(parse_tokenizer_options))]
+    pub prompt_options: Option<TokenizeOptions>,
+    /// Constraints for the generated text.
+    /// We sample the number of tokens to generate from a normal distribution.
+    /// Specified as a comma-separated list of key=value pairs.
+    /// * num_tokens: target number of generated tokens
+    /// * min_tokens: minimum number of generated tokens
+    /// * max_tokens: maximum number of generated tokens
+    /// * variance: variance in the number of generated tokens
+    ///
+    /// Example: num_tokens=200,max_tokens=210,min_tokens=190,variance=10
+    #[clap(long, env, value_parser(parse_tokenizer_options))]
+    pub decode_options: Option<TokenizeOptions>,
+    /// Template wrapped around each prompt so every request is unique, with the variables
+    /// {prompt}, {index}, {uuid} and {timestamp}.
+    ///
+    /// Example: "Request {uuid}\n{prompt}"
+    #[clap(long, env)]
+    pub prompt_template: Option<String>,
+    /// Make every prompt unique so server-side caches can't inflate results (none, prefix, suffix).
+    /// `prefix` adds a random UUID line before the prompt, `suffix` after it to keep prefix caching
+    #[clap(default_value = "none", long, env)]
+    pub prompt_uniqueness: String,
+    /// Hugging Face dataset to use for prompt generation
+    #[clap(default_value = "hlarcher/share_gpt_small", long, env)]
+    pub dataset: String,
+    /// File to use in the Dataset
+    #[clap(default_value = "share_gpt_filtered_small.json", long, env)]
+    pub dataset_file: String,
+    /// Generate fill-in-the-middle code completion requests from a code dataset (objects with a
+    /// `content` field): each source file is cut around a few lines to complete. Requires the
+    /// completions endpoint or a native backend
+    #[clap(long, env)]
+    pub fim: bool,
+    /// Fill-in-the-middle prompt format with the {prefix} and {suffix} variables, StarCoder's by default
+    #[clap(default_value = DEFAULT_FIM_TEMPLATE, long, env)]
+    pub fim_template: String,
+    /// Generate retrieval-augmented generation requests from a corpus dataset (objects with a
+    /// `text` or `context` passage, and a `question` or `instruction` for queries): each query is
+    /// answered from its passage stuffed among distractor passages of the corpus
+    #[clap(long, env)]
+    pub rag: bool,
+    /// Passages per RAG prompt, the passage of the query included. With prompt options, more
+    /// passages are stuffed until the sampled prompt length is reached
+    #[clap(default_value = "8", long, env)]
+    pub rag_passages: u64,
+    /// Generate summarization requests of long documents from a dataset of documents (objects with
+    /// a `text`, `document` or `article` field), followed by the next ones or truncated to the
+    /// prompt options length
+    #[clap(long, env)]
+    pub summarization: bool,
+    /// Summarize synthetic documents of random tokens instead of dataset documents, their length
+    /// being given by the prompt options
+    #[clap(long, env)]
+    pub synthetic_documents: bool,
+    /// Generated tokens per prompt token of summarization requests, unless set by decode options
+    #[clap(default_value = "0.05", long, env)]
+    pub summary_ratio: f64,
+    /// Extra metadata to include in the benchmark results file, comma-separated key-value pairs.
+    /// It can be, for example, used to include information about the configuration of the
+    /// benched server.
+    /// Example: --extra-meta "key1=value1,key2=value2"
+    #[clap(long, env, value_parser(parse_key_val))]
+    pub extra_meta: Option<HashMap<String, String>>,
+    /// Number of requests per benchmark step for which the full prompt and response are saved
+    /// in the results file. Requests are sampled randomly, allowing to spot-check output quality
+    /// under load (truncation, repetition, etc.).
+    #[clap(default_value = "0", long, env)]
+    pub sample_responses: u64,
+    /// Capacity of the channel carrying responses from virtual users to the scheduler.
+    /// Virtual users wait when it is full; the number of blocked sends is reported in the results.
+    #[clap(default_value = "16384", long, env)]
+    pub response_channel_capacity: usize,
+    /// Flag responses whose word 4-gram repetition score (share of repeated 4-grams, 0 to 1) reaches this threshold.
+    /// Repetitive responses are counted for each benchmark step; disabled if not set.
+    #[clap(long, env)]
+    pub repetition_threshold: Option<f64>,
+    /// Simulate a user reading streamed responses at this many tokens per second and report how
+    /// often the stream stalls the reader and how far it runs ahead; disabled if not set.
+    #[clap(long, env)]
+    pub reading_rate: Option<f64>,
+    /// Log the full details of this share of the requests and their responses at debug level,
+    /// e.g. 0.01 for 1% of them. Logs go to log.txt with the console, or to stderr with
+    /// RUST_LOG=inference_benchmarker::trace=debug; disabled if not set.
+    #[clap(long, env)]
+    pub trace_sample: Option<f64>,
+    /// Export a span per request, with child spans for the time to first token and the streaming
+    /// phase, to this OpenTelemetry collector over OTLP/HTTP, e.g. http://localhost:4318. Requests
+    /// carry a `traceparent` header to correlate them with the server traces; disabled if not set.
+    #[clap(long, env)]
+    pub otlp_endpoint: Option<String>,
+    /// Score responses against the `answer` of dataset entries, by exact match ignoring case and
+    /// surrounding whitespace (`exact`) or by matching the answer as a regular expression (`regex`).
+    /// The accuracy of each benchmark step is reported; disabled if not set.
+    #[clap(long, env)]
+    pub answer_scoring: Option<String>,
+    /// Built-in processors computing a custom score of each successful response, summarized per
+    /// step and tenant and written with the raw results: `json_validity` (`json_valid`, 1 if the
+    /// response is valid JSON) and `length_compliance` (1 if within 10% of the requested tokens).
+    /// A `score_response` function of the request script is also run as a processor.
+    #[clap(
+        long = "response-processor",
+        env = "RESPONSE_PROCESSORS",
+        value_delimiter = ','
+    )]
+    pub response_processors: Vec<String>,
+    /// Latency limits a request must meet to count towards goodput, reported next to the raw
+    /// throughput of each benchmark step: `ttft=<duration>`, `itl=<duration>` (average of the
+    /// request) and `e2e=<duration>`, comma separated, e.g. "ttft=500ms,itl=50ms"
+    #[clap(long, env)]
+    pub request_slo: Option<String>,
+    /// Fit requests in the model context length, fetched from the server `/v1/models` metadata or
+    /// the model config on the Hub: max tokens are reduced when prompt and max tokens exceed it,
+    /// and prompts longer than the context are removed. Clamped requests are counted in the results.
+    #[clap(long, env)]
+    pub clamp_to_context: bool,
+    /// Model context length to clamp requests to, instead of fetching it
+    #[clap(long, env)]
+    pub max_context_length: Option<u64>,
+    /// Responses kept in memory per benchmark step for the detailed analyses (failures, goodput,
+    /// length buckets...), a uniform random sample of them being kept past it. Request counts,
+    /// throughput, averages, percentiles and the step, burst phase, warmup and tenant splits cover
+    /// all responses, so long high-rate runs stay bounded in memory.
+    #[clap(default_value = "100000", long, env)]
+    pub max_stored_responses: Option<u64>,
+    /// Record throughput, TTFT, ITL and active VUs of each benchmark step over time windows of this
+    /// duration (e.g. 5s) in the results, to show warmup effects, degradation or stalls; disabled if not set
+    #[clap(long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub time_series_window: Option<Duration>,
+    /// Capacity of the metrics bus feeding the UI. Lagging consumers skip metrics,
+    /// the number of dropped metrics is reported in the results.
+    #[clap(default_value = "4096", long, env)]
+    pub metrics_bus_capacity: usize,
+    /// Highlight the live error rate in the console when it exceeds this percentage
+    #[clap(long, env)]
+    pub alert_error_rate: Option<f64>,
+    /// Highlight the live E2E latency p99 in the console when it exceeds this duration
+    #[clap(long, env)]
+    #[arg(value_parser = parse_duration)]
+    alert_e2e_latency_p99: Option<Duration>,
+    /// Shell command (or webhook URL) to run before the benchmark. Its output is saved in the results file.
+    #[clap(long, env)]
+    pub pre_run_hook: Option<String>,
+    /// Shell command (or webhook URL) to run after the benchmark
+    #[clap(long, env)]
+    pub post_run_hook: Option<String>,
+    /// Shell command (or webhook URL) to run before each benchmark step.
+    /// The step id is available in the BENCHMARK_STAGE env variable.
+    #[clap(long, env)]
+    pub pre_stage_hook: Option<String>,
+    /// Shell command (or webhook URL) to run after each benchmark step
+    #[clap(long, env)]
+    pub post_stage_hook: Option<String>,
+    /// Emit a heartbeat with the current counters of the running step at this interval (e.g. 30s),
+    /// logged and sent to the heartbeat hook, so supervisors can detect a wedged run; disabled if not set
+    #[clap(long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub heartbeat_interval: Option<Duration>,
+    /// Shell command (or webhook URL) to run on each heartbeat. The counters are added to the
+    /// webhook body, or passed as JSON in the BENCHMARK_HOOK_PAYLOAD env variable.
+    #[clap(long, env)]
+    pub heartbeat_hook: Option<String>,
+    /// Kill hooks (or drop their webhook requests) still running after this duration and
+    /// record them as failed
+    #[clap(default_value = "10m", long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub hook_timeout: Duration,
+    /// Labels of the stages whose name or id matches a pattern (`*` matching anything), repeatable,
+    /// e.g. "constant@*:quantization=fp8,kv_cache=on". Passed to hooks as BENCHMARK_LABEL_<KEY>
+    /// env variables and written with the step results, and as columns of the summaries.
+    #[clap(long = "stage-label", env = "STAGE_LABELS")]
+    pub stage_labels: Vec<String>,
+    /// Assertion checked at the end of each benchmark step, repeatable (e.g. "error_rate < 0.5%", "p90_itl < 40ms").
+    /// Metrics: error_rate, request_throughput, token_throughput, avg_<latency> or p<NN>_<latency>
+    /// for ttft, itl and e2e latencies. Results are recorded for each step in the report.
+    /// Several assertions can be given comma-separated, e.g. in the ASSERTIONS env variable. An
+    /// empty assertion (--assert '') clears the SLOs of the preset.
+    #[clap(long = "assert", env = "ASSERTIONS", value_delimiter = ',')]
+    pub assertions: Vec<String>,
+    /// Objective the "search" benchmark binary-searches the highest arrival rate meeting, repeatable,
+    /// with the --assert syntax (e.g. "p99_ttft < 500ms", "error_rate < 1%"). With "sweep" and "ramp",
+    /// the highest rate meeting the objectives is reported next to the latency knee. Several
+    /// objectives can be given comma-separated, e.g. in the SLO env variable.
+    #[clap(long, env, value_delimiter = ',')]
+    pub slo: Vec<String>,
+    /// Skip the remaining benchmark steps once an assertion fails
+    #[clap(long, env)]
+    pub abort_on_assertion_failure: bool,
+    /// Metric computed for each benchmark step with a Rhai expression over its base metrics, repeatable,
+    /// e.g. "tokens_per_dollar=token_throughput_secs * 3600 / 2.5". Written in the report and the console summary.
+    #[clap(long = "derived-metric", env = "DERIVED_METRICS")]
+    pub derived_metrics: Vec<String>,
+    /// Percentiles of the TTFT, inter-token latency, end-to-end latency and output tokens per second
+    /// of the requests, reported with their max for each benchmark step (50,90,95,99 by default)
+    #[clap(long, env, value_delimiter = ',')]
+    pub percentiles: Vec<f64>,
+    /// Prometheus metrics endpoint of the benchmarked server (e.g. http://localhost:8080/metrics).
+    /// It is scraped at the start and end of each benchmark step and counter changes are saved in the results.
+    #[clap(long, env)]
+    pub server_metrics_url: Option<String>,
+    /// Names of the server metrics to keep. All counters are kept if not set.
+    #[clap(long, env)]
+    pub server_metrics: Option<Vec<String>>,
+    /// Sample vLLM engine stats (running/waiting requests, KV cache usage, preemptions) from
+    /// the server metrics endpoint during each benchmark step
+    #[clap(long, env)]
+    pub vllm_stats: bool,
+    /// The OpenAI-compatible endpoint to benchmark: chat (/v1/chat/completions) or completions
+    /// (/v1/completions, raw prompts without chat template)
+    #[clap(default_value = "chat", long, env)]
+    pub endpoint: String,
+    /// Request generation details from TGI and save server-side token counts in the results
+    #[clap(long, env)]
+    pub tgi_details: bool,
+    /// Send a unique key in this header with each request (e.g. "Idempotency-Key"),
+    /// so that gateways with replay protection do not reject benchmark traffic
+    #[clap(long, env)]
+    pub idempotency_key_header: Option<String>,
+    /// Mark system prompts as cacheable (`cache_control`) and request token usage to report
+    /// prompt cache hits and compare latency of cached vs uncached requests
+    #[clap(long, env)]
+    pub prompt_caching: bool,
+    /// Send each request twice, streamed and unstreamed, and report the latency overhead of
+    /// streaming and the difference in generated token counts (OpenAI backend only)
+    #[clap(long, env)]
+    pub compare_streaming: bool,
+    /// Request token usage and re-tokenize whole responses to report, per step, how the generated
+    /// token counts of the chunks, the client tokenizer and the server differ (OpenAI backend only)
+    #[clap(long, env)]
+    pub token_reconciliation: bool,
+    /// Request token usage to check the generated token counts of the client tokenizer against
+    /// the server's, warning on a mismatch (OpenAI backend)
+    #[clap(long, env)]
+    pub tokenizer_check: bool,
+    /// The number of requests submitted in a single batch (only valid for the "batch" benchmark)
+    #[clap(default_value = "100", long, env)]
+    pub batch_size: u64,
+    /// How often the batch status is polled (only valid for the "batch" benchmark)
+    #[clap(default_value = "5s", long, env)]
+    #[arg(value_parser = parse_duration)]
+    pub batch_poll_interval: Duration,
+    /// [experimental] Benchmark a realtime speech endpoint (--url ws://...). Each audio chunk counts
+    /// as a generated token, so TTFT is the time to first audio chunk and ITL the chunk cadence
+    #[clap(long, env)]
+    pub realtime_audio: bool,
+    /// Server API to benchmark:
+    /// * openai: OpenAI-compatible API, see --endpoint
+    /// * tgi: Text Generation Inference native /generate_stream API, using the server-side
+    ///   generated token counts
+    /// * vllm: vLLM native /generate API, recording the finish reasons and cumulative logprobs
+    /// * triton: Triton Inference Server (e.g. TensorRT-LLM) generate extension
+    ///   (/v2/models/{model}/generate_stream), or gRPC streaming with a grpc:// URL
+    #[clap(default_value = "openai", long, env)]
+    pub backend: String,
+    /// Header sent with every request by all backends, as `key:value`, e.g. tenant IDs, routing
+    /// hints or tracing headers required by API gateways. Can be repeated.
+    /// Only header names are recorded in the report.
+    #[clap(long = "header", alias = "backend-header", env = "HEADERS")]
+    pub headers: Vec<String>,
+    /// Route requests are sent to, replacing the default one of the backend (e.g. /v1/chat/completions)
+    #[clap(long, env)]
+    pub backend_path: Option<String>,
+    /// Rhai script defining `on_request(request)` to transform each request payload and headers,
+    /// `classify_response(response)` to decide whether a response is successful and/or
+    /// `score_response(response)` to compute custom scores of each response
+    #[clap(long, env)]
+    pub request_script: Option<PathBuf>,
+    /// What to do when a report for the same configuration already exists in `results/`
+    /// (error, overwrite, suffix, skip). Reports embed a `config_hash` to detect duplicates
+    #[clap(default_value = "suffix", long, env)]
+    pub on_duplicate: String,
+    /// Write the metrics of every request (timestamps, tokens, TTFT, inter-token latencies,
+    /// status) next to the report, as `jsonl` or `csv`, for custom analysis
+    #[clap(long, env)]
+    pub raw_results: Option<String>,
+    /// Also write a CSV summary next to the report, one row per step with its rate, throughput,
+    /// TTFT, ITL and E2E latency percentiles and error rate, for spreadsheets
+    #[clap(long, env)]
+    pub csv_summary: bool,
+    /// Also write Parquet tables next to the report: the steps summary and the metrics of every
+    /// request, to query benchmark campaigns with DuckDB or Spark
+    #[clap(long, env)]
+    pub parquet: bool,
+    /// Previous JSON report to compare the steps with, by id: the console summary shows the change
+    /// of the mean of each per-request metric with its 95% confidence interval and significance, as
+    /// `criterion` does
+    #[clap(long, env)]
+    pub baseline: Option<PathBuf>,
+    /// Unit of latencies in the tables and the console (auto, ms, s). "auto" shows end-to-end
+    /// latencies in seconds and token latencies in milliseconds
+    #[clap(default_value = "auto", long, env)]
+    pub latency_unit: String,
+    /// Scale throughputs with SI (k = 1000) or binary (Ki = 1024) prefixes (none, si, binary)
+    #[clap(default_value = "none", long, env)]
+    pub throughput_prefix: String,
+    /// Decimal separator of the numbers in the tables and the console
+    #[clap(default_value = ".", long, env)]
+    pub decimal_separator: char,
+    /// Thousands separator of the numbers in the tables and the console, none by default
+    #[clap(long, env)]
+    pub thousands_separator: Option<char>,
+    /// Print the tables in GitHub-flavored Markdown, to paste them in pull requests and issues
+    #[clap(long, env)]
+    pub markdown: bool,
+    /// Also save a Markdown summary of the run (parameters, results and per-request percentiles
+    /// tables) to this file
+    #[clap(long, env)]
+    pub markdown_output: Option<PathBuf>,
+    /// Also save a self-contained HTML report of the run (results table and charts of throughput
+    /// against the load and of the latency percentiles of each step) to this file
+    #[clap(long, env)]
+    pub html_output: Option<PathBuf>,
+    /// Upload the JSON report of the run to this results store: POSTed to an http(s):// endpoint,
+    /// or PUT under an s3://bucket/prefix/ location with the AWS_ACCESS_KEY_ID,
+    /// AWS_SECRET_ACCESS_KEY, AWS_REGION and AWS_ENDPOINT_URL environment variables. A failed
+    /// upload fails the run.
+    #[clap(long, env)]
+    pub results_sink: Option<String>,
+    /// Tags of the uploaded report, comma-separated key-value pairs, sent as S3 object tags or
+    /// `X-Benchmark-Tag-<key>` headers next to the run id and config hash.
+    /// Example: --results-sink-tags "branch=main,commit=abc123"
+    #[clap(long, env, value_parser(parse_key_val))]
+    pub results_sink_tags: Option<HashMap<String, String>>,
+    /// Bearer token of the HTTP results sink
+    #[clap(long, env, hide_env_values = true)]
+    pub results_sink_token: Option<String>,
+}
+
+impl Args {
+    /// Run configuration of the benchmark. `matches` are the parsed arguments, used to tell the
+    /// flags set on the command line (or in the environment) apart from the defaults a preset
+    /// overrides.
+    pub fn run_configuration(&self, matches: &ArgMatches) -> anyhow::Result<RunConfiguration> {
+        let tokenizer_name = self
+            .tokenizer_name
+            .clone()
+            .ok_or(anyhow::anyhow!("tokenizer_name is required"))?;
+        // get HF token
+        let token_env_key = "HF_TOKEN".to_string();
+        let hf_token = match std::env::var(token_env_key).ok() {
+            Some(token) => Some(token),
+            #[cfg(feature = "hub")]
+            None => hf_hub::Cache::default().token(),
+            #[cfg(not(feature = "hub"))]
+            None => None,
+        };
+        let model_name = self.model_name.clone().unwrap_or(tokenizer_name.clone());
+        let mut run_config = RunConfiguration {
+            benchmark_name: self.benchmark_name.clone(),
+            description: self.description.clone(),
+            preset: None,
+            url: self.url.clone(),
+            extra_urls: self.extra_urls.clone(),
+            endpoint_label: None,
+            parallel_endpoints: self.parallel_endpoints,
+            tokenizer_name,
+            max_vus: self.max_vus,
+            duration: self.duration,
+            num_requests: self.num_requests,
+            rates: self.rates.clone(),
+            num_rates: self.num_rates,
+            benchmark_kind: self.benchmark_kind.clone(),
+            rate_curve: self.rate_curve.clone(),
+            step_profile: self.step_profile.clone(),
+            trace: self.trace.clone(),
+            burst_profile: self.burst_profile.clone(),
+            arrival_distribution: self.arrival_distribution.clone(),
+            client_headroom: self.client_headroom.clone(),
+            max_total_duration: self.max_total_duration,
+            open_files_limit: self.open_files_limit,
+            warmup_duration: self.warmup,
+            warmup_cold_requests: self.warmup_cold_requests,
+            stage_warmup: self.stage_warmup,
+            shuffle_stages: self.shuffle_stages,
+            timeouts: RequestTimeouts {
+                connect: self.connect_timeout,
+                first_token: self.ttft_timeout,
+                total: self.request_timeout,
+            },
+            retry: RetryPolicy {
+                max_retries: self.max_retries,
+                backoff: self.retry_backoff,
+                idempotency_key: self.idempotency_key_mode,
+            },
+            tenants: self.tenants.clone(),
+            adaptive_decode: self.adaptive_decode,
+            interactive: !self.no_console,
+            prompt_options: self.prompt_options.clone(),
+            decode_options: self.decode_options.clone(),
+            dataset: self.dataset.clone(),
+            dataset_file: self.dataset_file.clone(),
+            fim: self.fim,
+            fim_template: self.fim_template.clone(),
+            rag: self.rag,
+            rag_passages: self.rag_passages,
+            summarization: self.summarization,
+            synthetic_documents: self.synthetic_documents,
+            summary_ratio: self.summary_ratio,
+            hf_token,
+            api_key: self.api_key.clone(),
+            extra_metadata: self.extra_meta.clone(),
+            model_name,
+            sample_responses: self.sample_responses,
+            response_channel_capacity: self.response_channel_capacity,
+            repetition_threshold: self.repetition_threshold,
+            reading_rate: self.reading_rate,
+            trace_sample: self.trace_sample,
+            otlp_endpoint: self.otlp_endpoint.clone(),
+            heartbeat_interval: self.heartbeat_interval,
+            max_stored_responses: self.max_stored_responses,
+            time_series_window: self.time_series_window,
+            answer_scoring: self.answer_scoring.clone(),
+            response_processors: self.response_processors.clone(),
+            request_slo: self.request_slo.clone(),
+            clamp_to_context: self.clamp_to_context,
+            max_context_length: self.max_context_length,
+            vu_stagger: self.vu_stagger,
+            prefetch_requests: self.prefetch_requests,
+            prompt_template: self.prompt_template.clone(),
+            prompt_uniqueness: self.prompt_uniqueness.clone(),
+            metrics_bus_capacity: self.metrics_bus_capacity,
+            alerts: AlertThresholds {
+                error_rate: self.alert_error_rate,
+                e2e_latency_p99: self.alert_e2e_latency_p99,
+            },
+            hooks: HooksConfig {
+                pre_run: self.pre_run_hook.clone(),
+                post_run: self.post_run_hook.clone(),
+                pre_stage: self.pre_stage_hook.clone(),
+                post_stage: self.post_stage_hook.clone(),
+                heartbeat: self.heartbeat_hook.clone(),
+                timeout: Some(self.hook_timeout),
+            },
+            stage_labels: self.stage_labels.clone(),
+            assertions: self.assertions.clone(),
+            slo: self.slo.clone(),
+            abort_on_assertion_failure: self.abort_on_assertion_failure,
+            derived_metrics: self.derived_metrics.clone(),
+            percentiles: self.percentiles.clone(),
+            server_metrics_url: self.server_metrics_url.clone(),
+            server_metrics: self.server_metrics.clone(),
+            vllm_stats: self.vllm_stats,
+            endpoint: self.endpoint.clone(),
+            tgi_details: self.tgi_details,
+            idempotency_key_header: self.idempotency_key_header.clone(),
+            tokenizer_revision: self.tokenizer_revision.clone(),
+            tokenizer_fallback: self.tokenizer_fallback.clone(),
+            prompt_caching: self.prompt_caching,
+            compare_streaming: self.compare_streaming,
+            token_reconciliation: self.token_reconciliation,
+            tokenizer_check: self.tokenizer_check,
+            batch_size: self.batch_size,
+            batch_poll_interval: self.batch_poll_interval,
+            realtime_audio: self.realtime_audio,
+            backend: self.backend.clone(),
+            headers: self.headers.clone(),
+            backend_path: self.backend_path.clone(),
+            request_script: self.request_script.clone(),
+            on_duplicate: self.on_duplicate.clone(),
+            raw_results: self.raw_results.clone(),
+            csv_summary: self.csv_summary,
+            parquet: self.parquet,
+            baseline: self.baseline.clone(),
+            latency_unit: self.latency_unit.clone(),
+            throughput_prefix: self.throughput_prefix.clone(),
+            decimal_separator: self.decimal_separator,
+            thousands_separator: self.thousands_separator,
+            markdown: self.markdown,
+            markdown_output: self.markdown_output.clone(),
+            html_output: self.html_output.clone(),
+            results_sink: self.results_sink.clone(),
+            results_sink_tags: self.results_sink_tags.clone(),
+            results_sink_token: self.results_sink_token.clone(),
+        };
+        if let Some(preset) = &self.preset {
+            preset.parse::<Preset>()?.apply(&mut run_config, |id| {
+                matches
+                    .value_source(id)
+                    .is_some_and(|source| source != ValueSource::DefaultValue)
+            });
+        }
+        Ok(run_config)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Generate a ranked leaderboard from a directory of benchmark reports
+    Leaderboard {
+        /// Directory containing the JSON benchmark reports
+        #[clap(default_value = "results", long)]
+        results_dir: PathBuf,
+        /// Ranking criterion (throughput, recommended-rate, goodput, cost). Goodput needs reports of
+        /// runs with a request SLO, cost a `cost_per_hour` extra-meta label
+        #[clap(default_value = "throughput", long)]
+        rank_by: String,
+        /// Output format (markdown, html)
+        #[clap(default_value = "markdown", long)]
+        format: String,
+        /// Only include reports for this model (tokenizer name)
+        #[clap(long)]
+        model: Option<String>,
+        /// Only include reports whose metadata matches these labels.
+        /// Example: --labels "hardware=H100,tp=2"
+        #[clap(long, value_parser(parse_key_val))]
+        labels: Option<HashMap<String, String>>,
+        /// File to write the leaderboard to, stdout if not set
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Package a benchmark report with its summary, charts and environment metadata into a
+    /// shareable .tar.gz archive. Prompts, responses, hook commands and host names are removed.
+    ExportBundle {
+        /// JSON benchmark report to package
+        #[clap(long)]
+        report: PathBuf,
+        /// Archive path, defaults to the report path with a .bundle.tar.gz extension
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Configure a first benchmark interactively (URL, model, workload preset and SLOs) and print
+    /// the equivalent command line
+    #[cfg(feature = "tui")]
+    Wizard,
+}
+
+/// Parses a human readable duration, e.g. `120s` or `1h30m`
+pub fn parse_duration(s: &str) -> Result<Duration, Error> {
+    humantime::parse_duration(s).map_err(|_| Error::new(InvalidValue))
+}
+
+/// Checks the argument is a valid URL
+pub fn parse_url(s: &str) -> Result<String, Error> {
+    match Url::parse(s) {
+        Ok(_) => Ok(s.to_string()),
+        Err(_) => Err(Error::new(InvalidValue)),
+    }
+}
+
+/// Parses comma-separated `key=value` pairs
+pub fn parse_key_val(s: &str) -> Result<HashMap<String, String>, Error> {
+    let mut key_val_map = HashMap::new();
+    let items = s.split(",").collect::<Vec<&str>>();
+    for item in items.iter() {
+        let key_value = item.split("=").collect::<Vec<&str>>();
+        if key_value.len() % 2 != 0 {
+            return Err(Error::new(InvalidValue));
+        }
+        for i in 0..key_value.len() / 2 {
+            key_val_map.insert(
+                key_value[i * 2].to_string(),
+                key_value[i * 2 + 1].to_string(),
+            );
+        }
+    }
+    Ok(key_val_map)
+}
+
+/// Parses tokenizer options, e.g. `num_tokens=50,min_tokens=40,max_tokens=60,variance=10`
+pub fn parse_tokenizer_options(s: &str) -> Result<TokenizeOptions, Error> {
+    let mut tokenizer_options = TokenizeOptions::new();
+    let items = s.split(",").collect::<Vec<&str>>();
+    for item in items.iter() {
+        let key_value = item.split("=").collect::<Vec<&str>>();
+        if key_value.len() != 2 {
+            return Err(Error::new(InvalidValue));
+        }
+        match key_value[0] {
+            "num_tokens" => {
+                tokenizer_options.num_tokens = Some(key_value[1].parse::<u64>().unwrap())
+            }
+            "min_tokens" => tokenizer_options.min_tokens = key_value[1].parse::<u64>().unwrap(),
+            "max_tokens" => tokenizer_options.max_tokens = key_value[1].parse::<u64>().unwrap(),
+            "variance" => tokenizer_options.variance = key_value[1].parse::<u64>().unwrap(),
+            _ => return Err(Error::new(InvalidValue)),
+        }
+    }
+    if tokenizer_options.num_tokens.is_some()
+        && (tokenizer_options.num_tokens.unwrap() == 0
+            || tokenizer_options.min_tokens == 0
+            || tokenizer_options.max_tokens == 0)
+    {
+        return Err(Error::new(InvalidValue));
+    }
+    if tokenizer_options.min_tokens > tokenizer_options.max_tokens {
+        return Err(Error::new(InvalidValue));
+    }
+    Ok(tokenizer_options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    #[test]
+    fn test_run_configuration() {
+        Args::command().debug_assert();
+        let matches = Args::command().get_matches_from([
+            "inference-benchmarker",
+            "--tokenizer-name",
+            "gpt2",
+            "--preset",
+            "batch-summarization",
+            "--max-vus",
+            "16",
+            "--assert",
+            "error_rate < 1%,p90_itl < 40ms",
+            "--slo",
+            "p99_ttft < 500ms,error_rate < 1%",
+        ]);
+        let args = Args::from_arg_matches(&matches).unwrap();
+        let run_config = args.run_configuration(&matches).unwrap();
+        assert_eq!(run_config.tokenizer_name, "gpt2");
+        assert_eq!(run_config.model_name, "gpt2");
+        // the preset sets the benchmark kind, the explicit max VUs take precedence
+        assert_eq!(run_config.benchmark_kind, "throughput");
+        assert_eq!(run_config.max_vus, 16);
+        assert_eq!(run_config.duration, Duration::from_secs(120));
+        // explicit assertions replace the SLOs of the preset
+        assert_eq!(run_config.assertions, ["error_rate < 1%", "p90_itl < 40ms"]);
+        assert_eq!(run_config.slo, ["p99_ttft < 500ms", "error_rate < 1%"]);
+
+        // an empty assertion clears the SLOs of the preset
+        let matches = Args::command().get_matches_from([
+            "inference-benchmarker",
+            "--tokenizer-name",
+            "gpt2",
+            "--preset",
+            "chat-interactive",
+            "--assert",
+            "",
+        ]);
+        let args = Args::from_arg_matches(&matches).unwrap();
+        let run_config = args.run_configuration(&matches).unwrap();
+        let config = crate::benchmark_config(&run_config, "gpt2".to_string()).unwrap();
+        assert!(config.assertions.is_empty());
+    }
+}
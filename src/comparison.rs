@@ -0,0 +1,316 @@
+use crate::results::{BenchmarkResults, RequestMetric};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// Changes with a p-value under this are significant
+const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// Significant changes within this relative change of the baseline are reported as noise, as
+/// `criterion` does
+const NOISE_THRESHOLD: f64 = 0.02;
+
+/// Two-sided 95% quantile of the normal distribution
+const Z_95: f64 = 1.959964;
+
+// subset of the report written by `BenchmarkReportWriter`
+#[derive(Deserialize)]
+struct ReportFile {
+    results: Vec<ReportResults>,
+}
+
+#[derive(Deserialize)]
+struct ReportResults {
+    id: String,
+    successful_requests: u64,
+    // absent from reports written before it was introduced
+    #[serde(default)]
+    request_percentiles: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+/// Per-request metrics of the steps of a previous report, compared with the steps of the same id
+#[derive(Clone, Debug)]
+pub struct Baseline {
+    path: PathBuf,
+    steps: BTreeMap<String, ReportStep>,
+}
+
+#[derive(Clone, Debug)]
+struct ReportStep {
+    successful_requests: u64,
+    metrics: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> anyhow::Result<Baseline> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Can't read baseline {}: {e}", path.display()))?;
+        let report: ReportFile = serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid baseline report {}: {e}", path.display()))?;
+        Ok(Baseline {
+            path: path.to_path_buf(),
+            steps: report
+                .results
+                .into_iter()
+                .map(|r| {
+                    let step = ReportStep {
+                        successful_requests: r.successful_requests,
+                        metrics: r.request_percentiles,
+                    };
+                    (r.id, step)
+                })
+                .collect(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Changes of the per-request metrics of the steps also in the baseline, or why they can't be
+    /// estimated
+    pub fn compare(&self, results: &[BenchmarkResults]) -> Vec<MetricChange> {
+        let mut changes = Vec::new();
+        for result in results {
+            let Some(step) = self.steps.get(&result.id) else {
+                continue;
+            };
+            for metric in RequestMetric::ALL {
+                let baseline = step.metrics.get(metric.name()).and_then(|values| {
+                    Some(Sample {
+                        mean: *values.get("avg")?,
+                        std_dev: *values.get("stddev")?,
+                        n: step.successful_requests,
+                    })
+                });
+                let current = result.request_avg(metric).and_then(|mean| {
+                    Some(Sample {
+                        mean,
+                        std_dev: result.request_std_dev(metric)?,
+                        n: result.successful_requests() as u64,
+                    })
+                });
+                changes.push(MetricChange {
+                    step: result.id.clone(),
+                    metric,
+                    estimate: baseline
+                        .zip(current)
+                        .and_then(|(b, c)| ChangeEstimate::new(&b, &c)),
+                    mean: current.map(|c| c.mean),
+                    baseline_mean: baseline.map(|b| b.mean),
+                });
+            }
+        }
+        changes
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    mean: f64,
+    std_dev: f64,
+    n: u64,
+}
+
+/// Relative change of the mean of a metric from the baseline, with its 95% confidence interval
+/// and the p-value of Welch's t-test, in the normal approximation as steps have many requests
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChangeEstimate {
+    pub lower: f64,
+    pub change: f64,
+    pub upper: f64,
+    pub p_value: f64,
+}
+
+impl ChangeEstimate {
+    fn new(baseline: &Sample, current: &Sample) -> Option<ChangeEstimate> {
+        if baseline.n < 2 || current.n < 2 || baseline.mean == 0.0 {
+            return None;
+        }
+        let diff = current.mean - baseline.mean;
+        let std_err = (baseline.std_dev.powi(2) / baseline.n as f64
+            + current.std_dev.powi(2) / current.n as f64)
+            .sqrt();
+        let p_value = if std_err > 0.0 {
+            erfc((diff / std_err).abs() / std::f64::consts::SQRT_2)
+        } else if diff == 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+        Some(ChangeEstimate {
+            lower: (diff - Z_95 * std_err) / baseline.mean,
+            change: diff / baseline.mean,
+            upper: (diff + Z_95 * std_err) / baseline.mean,
+            p_value,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Improved,
+    Regressed,
+    WithinNoise,
+    NoChange,
+}
+
+/// Change of a per-request metric of a step from the baseline
+#[derive(Clone, Debug)]
+pub struct MetricChange {
+    pub step: String,
+    pub metric: RequestMetric,
+    pub estimate: Option<ChangeEstimate>,
+    pub mean: Option<f64>,
+    pub baseline_mean: Option<f64>,
+}
+
+impl MetricChange {
+    /// As `criterion`: significant changes whose confidence interval is outside the noise
+    /// threshold are improvements or regressions, depending on whether the metric is better
+    /// higher or lower
+    pub fn verdict(&self) -> Option<Verdict> {
+        let estimate = self.estimate?;
+        if estimate.p_value >= SIGNIFICANCE_LEVEL {
+            return Some(Verdict::NoChange);
+        }
+        let increased = estimate.lower > NOISE_THRESHOLD;
+        if !increased && estimate.upper >= -NOISE_THRESHOLD {
+            return Some(Verdict::WithinNoise);
+        }
+        let higher_is_better = self.metric == RequestMetric::OutputTokensPerSec;
+        Some(if increased == higher_is_better {
+            Verdict::Improved
+        } else {
+            Verdict::Regressed
+        })
+    }
+}
+
+impl Display for MetricChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let indent = " ".repeat(24);
+        writeln!(f, "{}/{}", self.step, self.metric.name())?;
+        let mean = |value: Option<f64>| value.map_or("N/A".to_string(), |v| format!("{v:.4}"));
+        writeln!(
+            f,
+            "{indent}mean:   {} (baseline {})",
+            mean(self.mean),
+            mean(self.baseline_mean)
+        )?;
+        let Some(estimate) = self.estimate else {
+            return write!(
+                f,
+                "{indent}No spread of the metric in the baseline or this run to estimate a change."
+            );
+        };
+        let comparison = if estimate.p_value < SIGNIFICANCE_LEVEL {
+            "<"
+        } else {
+            ">"
+        };
+        writeln!(
+            f,
+            "{indent}change: [{:+.4}% {:+.4}% {:+.4}%] (p = {:.2} {comparison} {SIGNIFICANCE_LEVEL:.2})",
+            estimate.lower * 100.0,
+            estimate.change * 100.0,
+            estimate.upper * 100.0,
+            estimate.p_value,
+        )?;
+        let verdict = match self.verdict() {
+            Some(Verdict::Improved) => "Performance has improved.",
+            Some(Verdict::Regressed) => "Performance has regressed.",
+            Some(Verdict::WithinNoise) => "Change within noise threshold.",
+            Some(Verdict::NoChange) | None => "No change in performance detected.",
+        };
+        write!(f, "{indent}{verdict}")
+    }
+}
+
+/// Complementary error function, within 1.2e-7 (Numerical Recipes' Chebyshev approximation)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(metric: RequestMetric, baseline: Sample, current: Sample) -> MetricChange {
+        MetricChange {
+            step: "throughput".to_string(),
+            metric,
+            estimate: ChangeEstimate::new(&baseline, &current),
+            mean: Some(current.mean),
+            baseline_mean: Some(baseline.mean),
+        }
+    }
+
+    #[test]
+    fn test_change_estimate() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-6);
+        assert!((erfc(1.959964 / std::f64::consts::SQRT_2) - 0.05).abs() < 1e-6);
+
+        let sample = |mean, std_dev| Sample {
+            mean,
+            std_dev,
+            n: 100,
+        };
+        // TTFT down 10% with a standard error of 1ms
+        let ttft = change(
+            RequestMetric::TimeToFirstToken,
+            sample(100.0, 10.0 / 2f64.sqrt()),
+            sample(90.0, 10.0 / 2f64.sqrt()),
+        );
+        let estimate = ttft.estimate.unwrap();
+        assert!((estimate.change + 0.1).abs() < 1e-9);
+        assert!((estimate.lower + 0.1 + Z_95 / 100.0).abs() < 1e-6);
+        assert!(estimate.p_value < 1e-6);
+        assert_eq!(ttft.verdict(), Some(Verdict::Improved));
+        let output = ttft.to_string();
+        assert!(output.contains("change: [-11.9600% -10.0000% -8.0400%] (p = 0.00 < 0.05)"));
+        assert!(output.ends_with("Performance has improved."));
+        // higher output token rates are better
+        let rate = change(
+            RequestMetric::OutputTokensPerSec,
+            sample(100.0, 5.0),
+            sample(90.0, 5.0),
+        );
+        assert_eq!(rate.verdict(), Some(Verdict::Regressed));
+        // significant but within 2%
+        let noise = change(
+            RequestMetric::E2eLatency,
+            sample(1000.0, 1.0),
+            sample(1010.0, 1.0),
+        );
+        assert_eq!(noise.verdict(), Some(Verdict::WithinNoise));
+        let same = change(
+            RequestMetric::E2eLatency,
+            sample(1000.0, 100.0),
+            sample(1001.0, 100.0),
+        );
+        assert_eq!(same.verdict(), Some(Verdict::NoChange));
+        let unknown = MetricChange {
+            estimate: None,
+            ..same
+        };
+        assert_eq!(unknown.verdict(), None);
+    }
+}
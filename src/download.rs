@@ -0,0 +1,83 @@
+use crate::benchmark::{DownloadProgressEvent, Event};
+use hf_hub::api::tokio::ApiBuilder;
+use hf_hub::{Cache, Repo};
+use log::{debug, info};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::Instant;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Download a file from a Hub repository into the Hugging Face cache, reporting progress on the
+/// event bus. Files already in the cache are returned without any request.
+pub async fn download_file(
+    repo: Repo,
+    filename: &str,
+    hf_token: Option<String>,
+    event_bus: Option<&UnboundedSender<Event>>,
+) -> anyhow::Result<PathBuf> {
+    let cache = Cache::default().repo(repo.clone());
+    if let Some(path) = cache.get(filename) {
+        debug!("Using cached {} from {}", filename, repo.url());
+        return Ok(path);
+    }
+    let api = ApiBuilder::new()
+        .with_token(hf_token)
+        .with_progress(false)
+        .build()?;
+    let url = api.repo(repo.clone()).url(filename);
+    info!("Downloading {url}");
+    let mut response = api.client().get(&url).send().await?.error_for_status()?;
+    // snapshots are keyed by commit, the ref maps the requested revision to it
+    let commit = response
+        .headers()
+        .get("x-repo-commit")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(repo.revision())
+        .to_string();
+    let total = response.content_length();
+    let path = Cache::default()
+        .path()
+        .join(repo.folder_name())
+        .join("snapshots")
+        .join(&commit)
+        .join(filename);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let tmp_path = path.with_extension("part");
+    let mut file = std::fs::File::create(&tmp_path)?;
+    let mut progress = DownloadProgressEvent {
+        filename: filename.to_string(),
+        downloaded: 0,
+        total,
+    };
+    let mut last_update = Instant::now();
+    let mut last_logged_decile = 0;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)?;
+        progress.downloaded += chunk.len() as u64;
+        if last_update.elapsed() >= PROGRESS_INTERVAL {
+            last_update = Instant::now();
+            if let Some(decile) = progress.percent().map(|p| (p / 10.0) as u64) {
+                if decile > last_logged_decile {
+                    last_logged_decile = decile;
+                    info!("Downloaded {}%", decile * 10);
+                }
+            }
+            send_progress(event_bus, &progress);
+        }
+    }
+    file.flush()?;
+    std::fs::rename(&tmp_path, &path)?;
+    cache.create_ref(&commit)?;
+    progress.total = Some(progress.downloaded);
+    send_progress(event_bus, &progress);
+    Ok(path)
+}
+
+fn send_progress(event_bus: Option<&UnboundedSender<Event>>, progress: &DownloadProgressEvent) {
+    if let Some(event_bus) = event_bus {
+        let _ = event_bus.send(Event::DownloadProgress(progress.clone()));
+    }
+}
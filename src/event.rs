@@ -1,14 +1,74 @@
+use crate::requests::ErrorClass;
+use crate::results::BenchmarkResults;
+use crate::scheduler::ExecutorType;
+#[cfg(feature = "tui")]
 use crossterm::event;
+#[cfg(feature = "tui")]
 use crossterm::event::KeyEvent;
-use std::time::{Duration, Instant};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+#[cfg(feature = "tui")]
+use std::time::Instant;
+#[cfg(feature = "tui")]
 use tokio::sync::{broadcast, mpsc};
 
+#[cfg(feature = "tui")]
 pub enum AppEvent {
     Tick,
     Key(KeyEvent),
     Resize,
 }
 
+/// Typed summary of a completed benchmark step, shared by every consumer of benchmark events
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub struct StageSummary {
+    pub id: String,
+    pub executor_type: ExecutorType,
+    pub max_vus: u64,
+    pub rate: Option<f64>,
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    /// Failed requests by cause
+    pub failures: BTreeMap<ErrorClass, u64>,
+    pub request_throughput: Option<f64>,
+    /// Request throughput of the requests meeting the request SLO, if one is set
+    pub goodput: Option<f64>,
+    pub token_throughput_secs: Option<f64>,
+    #[serde(rename = "time_to_first_token_avg_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSecondsWithFrac<f64>>")]
+    pub time_to_first_token_avg: Option<Duration>,
+    #[serde(rename = "inter_token_latency_avg_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSecondsWithFrac<f64>>")]
+    pub inter_token_latency_avg: Option<Duration>,
+    #[serde(rename = "e2e_latency_avg_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSecondsWithFrac<f64>>")]
+    pub e2e_latency_avg: Option<Duration>,
+}
+
+impl StageSummary {
+    pub fn new(results: &BenchmarkResults) -> StageSummary {
+        let config = results.executor_config();
+        StageSummary {
+            id: results.id.clone(),
+            executor_type: results.executor_type(),
+            max_vus: config.max_vus,
+            rate: config.average_rate(),
+            successful_requests: results.successful_requests() as u64,
+            failed_requests: results.failed_requests() as u64,
+            failures: results.failures(),
+            request_throughput: results.successful_request_rate().ok(),
+            goodput: results.goodput().map(|goodput| goodput.goodput),
+            token_throughput_secs: results.token_throughput_secs().ok(),
+            time_to_first_token_avg: results.time_to_first_token_avg().ok(),
+            inter_token_latency_avg: results.inter_token_latency_avg().ok(),
+            e2e_latency_avg: results.e2e_latency_avg().ok(),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
 pub async fn terminal_event_task(
     fps: u32,
     event_sender: mpsc::Sender<AppEvent>,
@@ -23,6 +83,7 @@ pub async fn terminal_event_task(
     }
 }
 
+#[cfg(feature = "tui")]
 async fn event_loop(fps: u32, event_sender: mpsc::Sender<AppEvent>) {
     // Frame budget
     let per_frame = Duration::from_secs(1) / fps;
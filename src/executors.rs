@@ -1,18 +1,24 @@
-use std::sync::atomic::AtomicI64;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
 use log::{info, trace, warn};
-use serde::Serialize;
-use tokio::sync::mpsc::{Receiver, Sender, UnboundedSender};
-use tokio::sync::{broadcast, Mutex};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::task::JoinHandle;
 
+use crate::pause::DispatchPause;
+use crate::processors::ResponseProcessors;
 use crate::requests::{
-    TextGenerationAggregatedResponse, TextGenerationBackend, TextGenerationRequest,
-    TextRequestGenerator,
+    RequestTimeouts, TextGenerationAggregatedResponse, TextGenerationBackend,
+    TextGenerationRequest, TextRequestGenerator,
 };
+use crate::results::{AnswerRegexes, AnswerScoring, RequestSlo};
+use rand_distr::Distribution;
+use std::str::FromStr;
 
 #[serde_with::serde_as]
 #[derive(Clone, Serialize)]
@@ -22,14 +28,733 @@ pub struct ExecutorConfig {
     #[serde_as(as = "serde_with::DurationSeconds<u64>")]
     pub duration: Duration,
     pub rate: Option<f64>,
+    /// Rate reached at the end of the duration, the arrival rate ramps linearly from `rate`
+    pub ramp_to_rate: Option<f64>,
+    pub sample_responses: u64,
+    pub response_channel_capacity: usize,
+    /// Flag responses whose n-gram repetition score reaches this threshold
+    pub repetition_threshold: Option<f64>,
+    /// Tokens per second a simulated reader of the streamed responses consumes
+    pub reading_rate: Option<f64>,
+    /// Score responses against the expected answers of the requests
+    pub answer_scoring: Option<AnswerScoring>,
+    /// Expected answers compiled for regex scoring
+    #[serde(skip)]
+    pub answer_regexes: AnswerRegexes,
+    /// Latency limits of the requests counted in the goodput
+    pub request_slo: Option<RequestSlo>,
+    /// Custom scores computed on each successful response
+    #[serde(skip)]
+    pub response_processors: ResponseProcessors,
+    /// Constant VUs are started evenly over this duration rather than all at once
+    #[serde(rename = "vu_stagger_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub vu_stagger: Duration,
+    /// Deadlines of each request, enforced by the backend
+    pub timeouts: RequestTimeouts,
+    /// Arrival rate following a curve over the duration, takes precedence over `rate`
+    pub rate_curve: Option<RateCurve>,
+    /// Distribution of the inter-arrival times of the constant arrival rate executor
+    pub arrival_distribution: ArrivalDistribution,
+    /// Requests replayed by the trace replay executor
+    pub trace: Option<Trace>,
+    /// Requests after which the executor ends once they completed, before the duration if
+    /// reached first
+    pub num_requests: Option<u64>,
+    /// Interval of the heartbeats published while the stage runs
+    #[serde(rename = "heartbeat_interval_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub heartbeat_interval: Option<Duration>,
+    /// Responses stored for the analyses needing each of them, a uniform sample being kept past
+    /// it. Counters and latency percentiles cover all responses.
+    pub max_stored_responses: Option<u64>,
+    /// Window of the metrics recorded over time, not recorded if not set
+    #[serde(rename = "time_series_window_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub time_series_window: Option<Duration>,
+    /// Lower limit on the VUs, applied when the client saturates
+    #[serde(skip)]
+    pub vu_cap: VuCap,
+    /// Pause of the dispatch of new requests
+    #[serde(skip)]
+    pub pause: DispatchPause,
+    /// Requests sent during this time at the start of the stage are reported apart from the
+    /// measured ones
+    #[serde(skip)]
+    pub stage_warmup: Option<Duration>,
+}
+
+impl Default for ExecutorConfig {
+    fn default() -> Self {
+        Self {
+            max_vus: 1,
+            duration: Duration::from_secs(60),
+            rate: None,
+            ramp_to_rate: None,
+            sample_responses: 0,
+            response_channel_capacity: DEFAULT_RESPONSE_CHANNEL_CAPACITY,
+            repetition_threshold: None,
+            reading_rate: None,
+            answer_scoring: None,
+            answer_regexes: AnswerRegexes::default(),
+            request_slo: None,
+            response_processors: ResponseProcessors::default(),
+            vu_stagger: Duration::ZERO,
+            timeouts: RequestTimeouts::default(),
+            rate_curve: None,
+            arrival_distribution: ArrivalDistribution::default(),
+            trace: None,
+            num_requests: None,
+            heartbeat_interval: None,
+            max_stored_responses: None,
+            time_series_window: None,
+            vu_cap: VuCap::default(),
+            pause: DispatchPause::default(),
+            stage_warmup: None,
+        }
+    }
+}
+
+impl ExecutorConfig {
+    /// Target arrival rate `elapsed` after the start of the executor
+    pub fn rate_at(&self, elapsed: Duration) -> Option<f64> {
+        if let Some(curve) = &self.rate_curve {
+            return Some(curve.rate_at(elapsed));
+        }
+        let rate = self.rate?;
+        match self.ramp_to_rate {
+            Some(end) => {
+                let position = (elapsed.as_secs_f64() / self.duration.as_secs_f64()).min(1.0);
+                Some(rate + (end - rate) * position)
+            }
+            None => Some(rate),
+        }
+    }
+
+    /// Whether `started` requests exhaust the number of requests to send
+    fn requests_sent(&self, started: u64) -> bool {
+        self.num_requests
+            .is_some_and(|num_requests| started >= num_requests)
+    }
+
+    /// Arrival rate averaged over the duration
+    pub fn average_rate(&self) -> Option<f64> {
+        match &self.rate_curve {
+            Some(curve) => Some(curve.average_rate(self.duration)),
+            None => self.rate_at(self.duration / 2),
+        }
+    }
+}
+
+/// How requests arrive at the target rate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArrivalDistribution {
+    /// Evenly spaced requests
+    #[default]
+    Constant,
+    /// Open-loop Poisson process: exponential inter-arrival times, bursty like real traffic
+    Poisson,
+}
+
+impl FromStr for ArrivalDistribution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "constant" => Ok(ArrivalDistribution::Constant),
+            "poisson" => Ok(ArrivalDistribution::Poisson),
+            _ => Err(anyhow::anyhow!("Unknown arrival distribution: {s}")),
+        }
+    }
+}
+
+impl ArrivalDistribution {
+    /// Number of requests arriving in an interval where `expected` arrive on average. Constant
+    /// arrivals are fractional, the executor carries the fraction over to the next interval.
+    pub fn arrivals(&self, expected: f64) -> f64 {
+        match self {
+            ArrivalDistribution::Constant => expected,
+            ArrivalDistribution::Poisson => match rand_distr::Poisson::new(expected) {
+                Ok(poisson) => poisson.sample(&mut rand::thread_rng()),
+                // no arrival at a null rate
+                Err(_) => 0.0,
+            },
+        }
+    }
+}
+
+/// Samples used to average a rate curve over a duration
+const RATE_CURVE_SAMPLES: u32 = 1000;
+
+/// Periodic arrival rate of long runs, e.g. the daily traffic of a deployment
+#[serde_with::serde_as]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RateCurve {
+    /// Sine wave starting at `min` req/s and peaking at `max` req/s half a period later
+    Sine {
+        min: f64,
+        max: f64,
+        #[serde(rename = "period_secs")]
+        #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+        period: Duration,
+    },
+    /// Rates of consecutive intervals, linearly interpolated and repeated after the last one
+    Points {
+        rates: Vec<f64>,
+        #[serde(rename = "interval_secs")]
+        #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+        interval: Duration,
+    },
+    /// Rates held for one interval each, the last one held after the end
+    Steps {
+        rates: Vec<f64>,
+        #[serde(rename = "interval_secs")]
+        #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+        interval: Duration,
+    },
+    /// `base` req/s for most of each period, which ends with a spike at `burst` req/s held for
+    /// `burst_duration`
+    Burst {
+        base: f64,
+        burst: f64,
+        #[serde(rename = "burst_duration_secs")]
+        #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+        burst_duration: Duration,
+        #[serde(rename = "period_secs")]
+        #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+        period: Duration,
+    },
+}
+
+impl RateCurve {
+    pub fn rate_at(&self, elapsed: Duration) -> f64 {
+        match self {
+            RateCurve::Sine { min, max, period } => {
+                let phase = elapsed.as_secs_f64() / period.as_secs_f64();
+                min + (max - min) * (1.0 - (2.0 * std::f64::consts::PI * phase).cos()) / 2.0
+            }
+            RateCurve::Points { rates, interval } => {
+                let position = elapsed.as_secs_f64() / interval.as_secs_f64();
+                let index = position.floor() as usize % rates.len();
+                let next = rates[(index + 1) % rates.len()];
+                rates[index] + (next - rates[index]) * position.fract()
+            }
+            RateCurve::Steps { rates, interval } => {
+                let index = (elapsed.as_secs_f64() / interval.as_secs_f64()) as usize;
+                rates[index.min(rates.len() - 1)]
+            }
+            RateCurve::Burst { base, burst, .. } => match self.phase(elapsed) {
+                Some(BURST_PHASE) => *burst,
+                _ => *base,
+            },
+        }
+    }
+
+    /// Phase of a burst curve `elapsed` after its start, `None` for other curves
+    pub fn phase(&self, elapsed: Duration) -> Option<&'static str> {
+        let RateCurve::Burst {
+            burst_duration,
+            period,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let position = elapsed.as_secs_f64() % period.as_secs_f64();
+        if position >= (*period - *burst_duration).as_secs_f64() {
+            Some(BURST_PHASE)
+        } else {
+            Some(BASELINE_PHASE)
+        }
+    }
+
+    /// Time spent in `phase` over the first `duration` of a burst curve
+    pub fn phase_duration(&self, phase: &str, duration: Duration) -> Duration {
+        let RateCurve::Burst {
+            burst_duration,
+            period,
+            ..
+        } = self
+        else {
+            return Duration::ZERO;
+        };
+        let periods = (duration.as_secs_f64() / period.as_secs_f64()).floor();
+        let rest = duration.saturating_sub(period.mul_f64(periods));
+        let burst =
+            burst_duration.mul_f64(periods) + rest.saturating_sub(*period - *burst_duration);
+        match phase {
+            BURST_PHASE => burst,
+            _ => duration.saturating_sub(burst),
+        }
+    }
+
+    /// Arrival rate averaged over the first `duration` of the curve
+    pub fn average_rate(&self, duration: Duration) -> f64 {
+        (0..RATE_CURVE_SAMPLES)
+            .map(|i| self.rate_at(duration * i / RATE_CURVE_SAMPLES))
+            .sum::<f64>()
+            / RATE_CURVE_SAMPLES as f64
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            RateCurve::Sine { min, max, period } => {
+                if !(*min >= 0.0 && max > min) {
+                    return Err(anyhow::anyhow!(
+                        "sine rate curve requires 0 <= min < max, got min={min}, max={max}"
+                    ));
+                }
+                if period.is_zero() {
+                    return Err(anyhow::anyhow!(
+                        "sine rate curve period must be greater than 0"
+                    ));
+                }
+            }
+            RateCurve::Points { rates, interval } | RateCurve::Steps { rates, interval } => {
+                if rates.is_empty() || rates.iter().any(|r| r.is_nan() || *r < 0.0) {
+                    return Err(anyhow::anyhow!(
+                        "rate curve requires at least one rate, all of them non-negative"
+                    ));
+                }
+                if rates.iter().all(|r| *r == 0.0) {
+                    return Err(anyhow::anyhow!("rate curve rates can't all be 0"));
+                }
+                if interval.is_zero() {
+                    return Err(anyhow::anyhow!(
+                        "rate curve interval must be greater than 0"
+                    ));
+                }
+            }
+            RateCurve::Burst {
+                base,
+                burst,
+                burst_duration,
+                period,
+            } => {
+                if !(*base >= 0.0 && burst > base) {
+                    return Err(anyhow::anyhow!(
+                        "burst profile requires 0 <= base < burst, got base={base}, burst={burst}"
+                    ));
+                }
+                if burst_duration.is_zero() || burst_duration >= period {
+                    return Err(anyhow::anyhow!(
+                        "burst profile duration must be greater than 0 and shorter than its period"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse `sine:min=<req/s>,max=<req/s>,period=<duration>` or `csv:<path>[,interval=<duration>]`.
+/// CSV files hold one rate per line (1h intervals by default), in their last column.
+impl FromStr for RateCurve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, params) = s.split_once(':').ok_or_else(|| {
+            anyhow::anyhow!("Invalid rate curve: {s}, expected `sine:...` or `csv:...`")
+        })?;
+        let mut params = params.split(',').map(str::trim);
+        let path = match kind {
+            "csv" => Some(params.next().unwrap_or_default().to_string()),
+            "sine" => None,
+            _ => return Err(anyhow::anyhow!("Unknown rate curve: {kind}")),
+        };
+        let mut values = std::collections::HashMap::new();
+        for param in params.filter(|p| !p.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid rate curve parameter: {param}"))?;
+            values.insert(key.trim(), value.trim());
+        }
+        let duration = |key: &str, default: Option<Duration>| -> anyhow::Result<Duration> {
+            match values.get(key) {
+                Some(value) => Ok(humantime::parse_duration(value)?),
+                None => default.ok_or_else(|| anyhow::anyhow!("rate curve requires {key}")),
+            }
+        };
+        let rate = |key: &str| -> anyhow::Result<f64> {
+            values
+                .get(key)
+                .ok_or_else(|| anyhow::anyhow!("rate curve requires {key}"))?
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid rate curve {key}: {e}"))
+        };
+        let curve = match path {
+            None => RateCurve::Sine {
+                min: rate("min")?,
+                max: rate("max")?,
+                period: duration("period", None)?,
+            },
+            Some(path) => RateCurve::Points {
+                rates: read_rates(&path)?,
+                interval: duration("interval", Some(Duration::from_secs(3600)))?,
+            },
+        };
+        curve.validate()?;
+        Ok(curve)
+    }
+}
+
+/// Rates in the last column of a CSV file, a header line is skipped
+fn read_rates(path: &str) -> anyhow::Result<Vec<f64>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read rate curve {path}: {e}"))?;
+    let mut rates = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let field = line.rsplit(',').next().unwrap_or_default().trim();
+        if field.is_empty() {
+            continue;
+        }
+        match field.parse::<f64>() {
+            Ok(rate) => rates.push(rate),
+            Err(_) if i == 0 => {}
+            Err(e) => {
+                return Err(anyhow::anyhow!(
+                    "Invalid rate at line {} of {path}: {e}",
+                    i + 1
+                ))
+            }
+        }
+    }
+    Ok(rates)
+}
+
+/// Arrival rates held for `step_duration` each, run as a single stage and reported per step
+#[serde_with::serde_as]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StepProfile {
+    pub rates: Vec<f64>,
+    #[serde(rename = "step_duration_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub step_duration: Duration,
+}
+
+impl StepProfile {
+    pub fn duration(&self) -> Duration {
+        self.step_duration * self.rates.len() as u32
+    }
+
+    pub fn rate_curve(&self) -> RateCurve {
+        RateCurve::Steps {
+            rates: self.rates.clone(),
+            interval: self.step_duration,
+        }
+    }
+
+    /// Ids of the steps in the report, numbered as a rate can appear twice
+    pub fn step_ids(&self) -> Vec<String> {
+        self.rates
+            .iter()
+            .enumerate()
+            .map(|(i, rate)| format!("step{}@{:.2}req/s", i + 1, rate))
+            .collect()
+    }
+}
+
+/// Parse `<req/s>,<req/s>,...@<step duration>`, e.g. `1,2,4,8@60s`
+impl FromStr for StepProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rates, step_duration) = s.rsplit_once('@').ok_or_else(|| {
+            anyhow::anyhow!("Invalid step profile: {s}, expected `<rates>@<step duration>`")
+        })?;
+        let rates = rates
+            .split(',')
+            .map(|rate| {
+                rate.trim()
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid step profile rate {rate}: {e}"))
+            })
+            .collect::<anyhow::Result<Vec<f64>>>()?;
+        let profile = StepProfile {
+            rates,
+            step_duration: humantime::parse_duration(step_duration.trim())?,
+        };
+        profile.rate_curve().validate()?;
+        Ok(profile)
+    }
+}
+
+/// Request of a recorded trace, as a line of the trace JSONL file
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct TraceEntry {
+    /// Seconds since the start of the trace the request was sent at
+    pub timestamp: f64,
+    /// Recorded prompt length, counted again once the prompt is generated
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+    /// Prompt of `prompt_tokens` tokens, generated once the tokenizer is loaded. Without it, the
+    /// prompt of the request generator is sent
+    #[serde(skip)]
+    pub prompt: Option<String>,
+}
+
+/// Recorded production trace replayed with its arrival pattern, optionally time-scaled
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Trace {
+    pub path: String,
+    /// Factor applied to the trace timestamps: 0.5 replays the trace twice as fast
+    pub time_scale: f64,
+    #[serde(skip)]
+    pub entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    /// Time the `i`th request is sent at, after the start of the replay
+    pub fn arrival(&self, i: usize) -> Duration {
+        Duration::from_secs_f64(self.entries[i].timestamp * self.time_scale)
+    }
+
+    /// Duration of the replay, until the last request is sent
+    pub fn duration(&self) -> Duration {
+        self.entries
+            .len()
+            .checked_sub(1)
+            .map_or(Duration::ZERO, |last| self.arrival(last))
+    }
+
+    /// Arrival rate averaged over the replay
+    pub fn average_rate(&self) -> f64 {
+        self.entries.len() as f64 / self.duration().as_secs_f64().max(1.0)
+    }
+}
+
+/// Parse `<path>[,time_scale=<factor>]`. The JSONL file holds one request per line with its
+/// `timestamp` (seconds, relative to the start of the trace), `prompt_tokens` and `output_tokens`
+impl FromStr for Trace {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = s.split(',').map(str::trim);
+        let path = params.next().unwrap_or_default().to_string();
+        let mut time_scale = 1.0;
+        for param in params.filter(|p| !p.is_empty()) {
+            match param.split_once('=') {
+                Some(("time_scale", value)) => {
+                    time_scale = value
+                        .trim()
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("Invalid trace time_scale: {e}"))?
+                }
+                _ => return Err(anyhow::anyhow!("Invalid trace parameter: {param}")),
+            }
+        }
+        if !(time_scale > 0.0 && f64::is_finite(time_scale)) {
+            return Err(anyhow::anyhow!("trace time_scale must be greater than 0"));
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to read trace {path}: {e}"))?;
+        let mut entries = vec![];
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: TraceEntry = serde_json::from_str(line)
+                .map_err(|e| anyhow::anyhow!("Invalid request at line {} of {path}: {e}", i + 1))?;
+            if !(entry.timestamp >= 0.0 && f64::is_finite(entry.timestamp)) {
+                return Err(anyhow::anyhow!(
+                    "Invalid timestamp at line {} of {path}: {}",
+                    i + 1,
+                    entry.timestamp
+                ));
+            }
+            entries.push(entry);
+        }
+        if entries.is_empty() {
+            return Err(anyhow::anyhow!("Trace {path} has no request"));
+        }
+        // replay requests in the order they were sent, whatever the order they were logged in
+        entries.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        Ok(Trace {
+            path,
+            time_scale,
+            entries,
+        })
+    }
+}
+
+/// Phase of a burst curve between spikes
+pub const BASELINE_PHASE: &str = "baseline";
+/// Phase of a burst curve during a spike
+pub const BURST_PHASE: &str = "burst";
+
+/// Baseline arrival rate interrupted by periodic spikes, run as a single stage to evaluate how
+/// serving stacks absorb sudden load. Latencies are reported per phase.
+#[serde_with::serde_as]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BurstProfile {
+    pub base_rate: f64,
+    pub burst_rate: f64,
+    #[serde(rename = "burst_duration_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub burst_duration: Duration,
+    #[serde(rename = "period_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub period: Duration,
+}
+
+impl BurstProfile {
+    pub fn rate_curve(&self) -> RateCurve {
+        RateCurve::Burst {
+            base: self.base_rate,
+            burst: self.burst_rate,
+            burst_duration: self.burst_duration,
+            period: self.period,
+        }
+    }
+}
+
+/// Parse `base=<req/s>,burst=<req/s>,duration=<duration>,period=<duration>`, e.g.
+/// `base=2,burst=20,duration=10s,period=60s` spikes to 20 req/s for the last 10s of every minute
+impl FromStr for BurstProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = std::collections::HashMap::new();
+        for param in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid burst profile parameter: {param}"))?;
+            values.insert(key.trim(), value.trim());
+        }
+        let value = |key: &str| {
+            values
+                .get(key)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("burst profile requires {key}"))
+        };
+        let rate = |key: &str| -> anyhow::Result<f64> {
+            value(key)?
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid burst profile {key}: {e}"))
+        };
+        let profile = BurstProfile {
+            base_rate: rate("base")?,
+            burst_rate: rate("burst")?,
+            burst_duration: humantime::parse_duration(value("duration")?)?,
+            period: humantime::parse_duration(value("period")?)?,
+        };
+        profile.rate_curve().validate()?;
+        Ok(profile)
+    }
+}
+
+/// Limit on the VUs of the running executor, shared with the client headroom monitor which
+/// lowers it when the client saturates
+#[derive(Clone, Default)]
+pub struct VuCap {
+    active: Arc<AtomicI64>,
+    /// 0 when uncapped
+    limit: Arc<AtomicU64>,
+}
+
+impl VuCap {
+    /// Counter of the active VUs of an executor starting its run
+    pub fn track(&self) -> Arc<AtomicI64> {
+        self.active.store(0, Ordering::SeqCst);
+        self.active.clone()
+    }
+
+    /// Cap the VUs at the ones in flight, returns the new cap when it lowers the current one
+    pub fn cap(&self) -> Option<u64> {
+        let active = self.active.load(Ordering::SeqCst).max(0) as u64;
+        let limit = self.limit.load(Ordering::SeqCst);
+        if active == 0 || (limit != 0 && limit <= active) {
+            return None;
+        }
+        self.limit.store(active, Ordering::SeqCst);
+        Some(active)
+    }
+
+    /// Max VUs of an executor configured with `max_vus`
+    pub fn max_vus(&self, max_vus: u64) -> u64 {
+        match self.limit.load(Ordering::SeqCst) {
+            0 => max_vus,
+            limit => limit.min(max_vus),
+        }
+    }
+}
+
+pub const DEFAULT_RESPONSE_CHANNEL_CAPACITY: usize = 16384;
+
+#[derive(Default)]
+struct ResponseChannelStats {
+    blocked: AtomicU64,
+    discarded: AtomicU64,
+    cancelled: AtomicU64,
+}
+
+/// Sends responses from VUs to the scheduler, counting sends that had to wait for
+/// channel capacity and responses discarded because the scheduler stopped listening
+#[derive(Clone)]
+pub struct ResponseSender {
+    tx: Sender<TextGenerationAggregatedResponse>,
+    stats: Arc<ResponseChannelStats>,
+}
+
+impl ResponseSender {
+    pub fn new(tx: Sender<TextGenerationAggregatedResponse>) -> Self {
+        Self {
+            tx,
+            stats: Arc::new(ResponseChannelStats::default()),
+        }
+    }
+
+    pub async fn send(&self, response: TextGenerationAggregatedResponse) {
+        let response = match self.tx.try_send(response) {
+            Ok(_) => return,
+            Err(TrySendError::Full(response)) => {
+                self.stats.blocked.fetch_add(1, Ordering::Relaxed);
+                response
+            }
+            Err(TrySendError::Closed(_)) => {
+                self.stats.discarded.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+        if self.tx.send(response).await.is_err() {
+            self.stats.discarded.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Signal that the VU work is done
+    pub async fn send_end(&self) {
+        let _ = self
+            .tx
+            .send(TextGenerationAggregatedResponse::new_as_ended())
+            .await;
+    }
+
+    /// Record a request dropped in flight because the benchmark was stopped
+    pub fn cancel(&self) {
+        self.stats.cancelled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn blocked_sends(&self) -> u64 {
+        self.stats.blocked.load(Ordering::Relaxed)
+    }
+
+    pub fn discarded_responses(&self) -> u64 {
+        self.stats.discarded.load(Ordering::Relaxed)
+    }
+
+    pub fn cancelled_requests(&self) -> u64 {
+        self.stats.cancelled.load(Ordering::Relaxed)
+    }
 }
 
 #[async_trait]
 pub trait Executor {
     async fn run(
         &self,
-        requests: Arc<Mutex<dyn TextRequestGenerator + Send>>,
-        responses_tx: UnboundedSender<TextGenerationAggregatedResponse>,
+        requests: Arc<dyn TextRequestGenerator>,
+        responses_tx: ResponseSender,
         stop_sender: broadcast::Sender<()>,
     );
 }
@@ -42,17 +767,9 @@ pub struct ConstantVUsExecutor {
 impl ConstantVUsExecutor {
     pub fn new(
         backend: Box<dyn TextGenerationBackend + Send + Sync>,
-        max_vus: u64,
-        duration: Duration,
+        config: ExecutorConfig,
     ) -> ConstantVUsExecutor {
-        Self {
-            backend,
-            config: ExecutorConfig {
-                max_vus,
-                duration,
-                rate: None,
-            },
-        }
+        Self { backend, config }
     }
 }
 
@@ -60,20 +777,33 @@ impl ConstantVUsExecutor {
 impl Executor for ConstantVUsExecutor {
     async fn run(
         &self,
-        requests: Arc<Mutex<dyn TextRequestGenerator + Send>>,
-        responses_tx: UnboundedSender<TextGenerationAggregatedResponse>,
+        requests: Arc<dyn TextRequestGenerator>,
+        responses_tx: ResponseSender,
         stop_sender: broadcast::Sender<()>,
     ) {
-        let start = std::time::Instant::now();
+        let start = tokio::time::Instant::now();
         // channel to handle ending VUs
         let (end_tx, mut end_rx): (Sender<bool>, Receiver<bool>) =
             tokio::sync::mpsc::channel(self.config.max_vus as usize);
-        let active_vus = Arc::new(AtomicI64::new(0));
-        // start all VUs
-        for _ in 0..self.config.max_vus {
-            let mut requests_guard = requests.lock().await;
-            let request = Arc::from(requests_guard.generate_request());
-            drop(requests_guard);
+        let active_vus = self.config.vu_cap.track();
+        let mut stop_receiver = stop_sender.subscribe();
+        let mut started = 0;
+        // start all VUs, spread over the stagger duration so their first requests don't all hit
+        // the server at the same instant
+        for i in 0..self.config.vu_cap.max_vus(self.config.max_vus) {
+            if self.config.requests_sent(started) {
+                break;
+            }
+            let delay = self
+                .config
+                .vu_stagger
+                .mul_f64(i as f64 / self.config.max_vus as f64);
+            // also waits for the dispatch to be resumed if paused
+            tokio::select! {
+                _ = self.config.pause.sleep_until(start, delay) => {},
+                _ = stop_receiver.recv() => return,
+            }
+            let request = Arc::from(requests.generate_request());
             start_vu(
                 self.backend.clone(),
                 request,
@@ -82,9 +812,9 @@ impl Executor for ConstantVUsExecutor {
                 stop_sender.clone(),
             )
             .await;
+            started += 1;
             active_vus.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         }
-        let mut stop_receiver = stop_sender.subscribe();
         tokio::select! {
             _ = stop_receiver.recv() => {
                 return;
@@ -93,17 +823,27 @@ impl Executor for ConstantVUsExecutor {
                 // replenish VUs as they finish
                 while end_rx.recv().await.is_some() {
                     active_vus.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
-                    if start.elapsed() > self.config.duration{
+                    if self.config.pause.active_elapsed(start) > self.config.duration {
                         // signal that the VU work is done
-                        let _ = responses_tx.send(TextGenerationAggregatedResponse::new_as_ended());
+                        responses_tx.send_end().await;
                         info!("Duration reached, waiting for all VUs to finish...");
                         if active_vus.load(std::sync::atomic::Ordering::SeqCst) == 0 {
                             break;
                         }
-                    } else {
-                        let mut requests_guard = requests.lock().await;
-                        let request = Arc::from(requests_guard.generate_request());
-                        drop(requests_guard);
+                    } else if self.config.requests_sent(started) {
+                        // all requests were sent, end once they all completed
+                        if active_vus.load(std::sync::atomic::Ordering::SeqCst) == 0 {
+                            info!("{started} requests completed");
+                            responses_tx.send_end().await;
+                            break;
+                        }
+                    } else if active_vus.load(std::sync::atomic::Ordering::SeqCst)
+                        < self.config.vu_cap.max_vus(self.config.max_vus) as i64
+                    {
+                        // VUs above the cap are not replenished
+                        self.config.pause.resumed().await;
+                        let request = Arc::from(requests.generate_request());
+                        started += 1;
                         active_vus.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
                         start_vu(self.backend.clone(), request, responses_tx.clone(), end_tx.clone(), stop_sender.clone()).await;
                     }
@@ -116,14 +856,16 @@ impl Executor for ConstantVUsExecutor {
 async fn start_vu(
     backend: Box<dyn TextGenerationBackend + Send + Sync>,
     request: Arc<TextGenerationRequest>,
-    responses_tx: UnboundedSender<TextGenerationAggregatedResponse>,
+    responses_tx: ResponseSender,
     end_tx: Sender<bool>,
     stop_sender: broadcast::Sender<()>,
 ) -> JoinHandle<()> {
     let mut stop_receiver = stop_sender.subscribe();
+    let cancel_tx = responses_tx.clone();
     tokio::spawn(async move {
         tokio::select! {
             _ = stop_receiver.recv() => {
+                cancel_tx.cancel();
                 let _ = end_tx.send(true).await;
             },
             _ = async{
@@ -136,7 +878,7 @@ async fn start_vu(
                     while let Some(response) = rx.recv().await {
                         // ignore errors, if the receiver is gone we want to finish the request
                         // to leave remote server in clean state
-                        let _ = responses_tx.send(response);
+                        responses_tx.send(response).await;
                     }
                 });
                 req_thread.await.unwrap();
@@ -156,18 +898,9 @@ pub struct ConstantArrivalRateExecutor {
 impl ConstantArrivalRateExecutor {
     pub fn new(
         backend: Box<dyn TextGenerationBackend + Send + Sync>,
-        max_vus: u64,
-        duration: Duration,
-        rate: f64,
+        config: ExecutorConfig,
     ) -> ConstantArrivalRateExecutor {
-        Self {
-            backend,
-            config: ExecutorConfig {
-                max_vus,
-                duration,
-                rate: Some(rate),
-            },
-        }
+        Self { backend, config }
     }
 }
 
@@ -175,17 +908,17 @@ impl ConstantArrivalRateExecutor {
 impl Executor for ConstantArrivalRateExecutor {
     async fn run(
         &self,
-        requests: Arc<Mutex<dyn TextRequestGenerator + Send>>,
-        responses_tx: UnboundedSender<TextGenerationAggregatedResponse>,
+        requests: Arc<dyn TextRequestGenerator>,
+        responses_tx: ResponseSender,
         stop_sender: broadcast::Sender<()>,
     ) {
-        let start = std::time::Instant::now();
-        let active_vus = Arc::new(AtomicI64::new(0));
+        let start = tokio::time::Instant::now();
+        let active_vus = self.config.vu_cap.track();
         // channel to handle ending VUs
         let (end_tx, mut end_rx): (Sender<bool>, Receiver<bool>) =
             tokio::sync::mpsc::channel(self.config.max_vus as usize);
-        let rate = self.config.rate.expect("checked in new()");
-        // spawn new VUs every `tick_ms` to reach the expected `rate` per second, until the duration is reached
+        let config = self.config.clone();
+        // spawn new VUs every `tick_ms` to reach the expected rate per second, until the duration is reached
         let tick_ms = 10;
         let mut interval = tokio::time::interval(Duration::from_millis(tick_ms));
 
@@ -194,13 +927,22 @@ impl Executor for ConstantArrivalRateExecutor {
         let max_vus = self.config.max_vus;
         let active_vus_thread = active_vus.clone();
         let mut stop_receiver_signal = stop_sender.subscribe();
+        let end_responses_tx = responses_tx.clone();
         let vu_thread = tokio::spawn(async move {
             tokio::select! {
-                _ = stop_receiver_signal.recv() => {},
-                _= async {
+                _ = stop_receiver_signal.recv() => false,
+                requests_sent = async {
                     let mut spawn_queue = 0.; // start with at least one VU
-                    while start.elapsed() < duration {
-                        spawn_queue += rate * (tick_ms as f64) / 1000.0;
+                    let mut started = 0;
+                    while config.pause.active_elapsed(start) < duration && !config.requests_sent(started) {
+                        if config.pause.is_paused() {
+                            // arrivals don't accumulate while paused
+                            config.pause.resumed().await;
+                            interval.reset();
+                            continue;
+                        }
+                        let rate = config.rate_at(config.pause.active_elapsed(start)).expect("checked in scheduler");
+                        spawn_queue += config.arrival_distribution.arrivals(rate * (tick_ms as f64) / 1000.0);
                         // delay spawning if we can't spawn a full VU yet
                         if spawn_queue < 1.0 {
                             interval.tick().await;
@@ -210,10 +952,13 @@ impl Executor for ConstantArrivalRateExecutor {
                         let to_spawn = spawn_queue.floor() as u64;
                         spawn_queue -= to_spawn as f64;
                         for _ in 0..to_spawn {
-                            if active_vus_thread.load(std::sync::atomic::Ordering::SeqCst) < max_vus as i64 {
-                                let mut requests_guard = requests.lock().await;
-                                let request = Arc::from(requests_guard.generate_request());
+                            if config.requests_sent(started) {
+                                break;
+                            }
+                            if active_vus_thread.load(std::sync::atomic::Ordering::SeqCst) < config.vu_cap.max_vus(max_vus) as i64 {
+                                let request = Arc::from(requests.generate_request());
                                 start_vu(backend.clone(), request.clone(), responses_tx.clone(), end_tx.clone(),stop_sender.clone()).await;
+                                started += 1;
                                 active_vus_thread.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
                             } else {
                                 warn!("Max VUs reached, skipping request");
@@ -222,22 +967,289 @@ impl Executor for ConstantArrivalRateExecutor {
                         }
                         interval.tick().await;
                     }
+                    if config.requests_sent(started) {
+                        info!("{started} requests sent, waiting for them to complete...");
+                        return true;
+                    }
                     // signal that the VU work is done
                     info!("Duration reached, waiting for all VUs to finish...");
-                    let _ = responses_tx.send(TextGenerationAggregatedResponse::new_as_ended());
-                }=>{}
+                    responses_tx.send_end().await;
+                    false
+                }=> requests_sent
             }
         });
         while end_rx.recv().await.is_some() {
             active_vus.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
             // wait for all VUs to finish
-            if start.elapsed() > self.config.duration
+            if (self.config.pause.active_elapsed(start) > self.config.duration
+                || vu_thread.is_finished())
                 && active_vus.load(std::sync::atomic::Ordering::SeqCst) == 0
             {
                 break;
             }
         }
         // wait for the VU thread to finish
+        if vu_thread.await.unwrap() {
+            // unlike at the end of the duration, the requests in flight are waited for
+            end_responses_tx.send_end().await;
+        }
+    }
+}
+
+pub struct TraceReplayExecutor {
+    config: ExecutorConfig,
+    backend: Box<dyn TextGenerationBackend + Send + Sync>,
+}
+
+impl TraceReplayExecutor {
+    pub fn new(
+        backend: Box<dyn TextGenerationBackend + Send + Sync>,
+        config: ExecutorConfig,
+    ) -> TraceReplayExecutor {
+        Self { backend, config }
+    }
+
+    /// Request of a trace entry, with the recorded lengths
+    fn request(
+        entry: &TraceEntry,
+        requests: &Arc<dyn TextRequestGenerator>,
+    ) -> TextGenerationRequest {
+        match &entry.prompt {
+            Some(prompt) => TextGenerationRequest {
+                prompt: prompt.clone(),
+                num_prompt_tokens: entry.prompt_tokens,
+                num_decode_tokens: Some(entry.output_tokens),
+                system_prompt: None,
+                expected_answer: None,
+                context_clamped: false,
+            },
+            None => TextGenerationRequest {
+                num_decode_tokens: Some(entry.output_tokens),
+                ..requests.generate_request()
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for TraceReplayExecutor {
+    async fn run(
+        &self,
+        requests: Arc<dyn TextRequestGenerator>,
+        responses_tx: ResponseSender,
+        stop_sender: broadcast::Sender<()>,
+    ) {
+        let start = tokio::time::Instant::now();
+        let active_vus = self.config.vu_cap.track();
+        let trace = self.config.trace.clone().expect("checked in scheduler");
+        // channel to handle ending VUs
+        let (end_tx, mut end_rx): (Sender<bool>, Receiver<bool>) =
+            tokio::sync::mpsc::channel(self.config.max_vus as usize);
+        let config = self.config.clone();
+        let backend = self.backend.clone();
+        let active_vus_thread = active_vus.clone();
+        let mut stop_receiver_signal = stop_sender.subscribe();
+        let end_responses_tx = responses_tx.clone();
+        let vu_thread = tokio::spawn(async move {
+            tokio::select! {
+                _ = stop_receiver_signal.recv() => {},
+                _ = async {
+                    // send each request at its recorded time, until the end of the trace or the
+                    // duration, shortened by the run deadline
+                    for (i, entry) in trace.entries.iter().enumerate() {
+                        let arrival = trace.arrival(i);
+                        if arrival > config.duration {
+                            break;
+                        }
+                        config.pause.sleep_until(start, arrival).await;
+                        if active_vus_thread.load(Ordering::SeqCst) >= config.vu_cap.max_vus(config.max_vus) as i64 {
+                            warn!("Max VUs reached, skipping request");
+                            continue;
+                        }
+                        let request = Arc::from(Self::request(entry, &requests));
+                        start_vu(backend.clone(), request, responses_tx.clone(), end_tx.clone(), stop_sender.clone()).await;
+                        active_vus_thread.fetch_add(1, Ordering::SeqCst);
+                    }
+                    info!("Trace replayed, waiting for all VUs to finish...");
+                } => {}
+            }
+        });
+        while end_rx.recv().await.is_some() {
+            active_vus.fetch_sub(1, Ordering::SeqCst);
+            // wait for all VUs to finish
+            if vu_thread.is_finished() && active_vus.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+        }
+        // wait for the VU thread to finish
         vu_thread.await.unwrap();
+        // signal that the VU work is done once the last requests of the trace ended, unlike rate
+        // executors which drop the requests in flight at the end of the duration
+        end_responses_tx.send_end().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_curve() {
+        let sine: RateCurve = "sine:min=2,max=10,period=24h".parse().unwrap();
+        let day = Duration::from_secs(24 * 3600);
+        assert_eq!(sine.rate_at(Duration::ZERO), 2.0);
+        assert!((sine.rate_at(day / 2) - 10.0).abs() < 1e-9);
+        assert!((sine.rate_at(day / 4) - 6.0).abs() < 1e-9);
+        assert!((sine.average_rate(day) - 6.0).abs() < 1e-6);
+        assert!("sine:min=10,max=2,period=1h".parse::<RateCurve>().is_err());
+        assert!("sine:min=1,max=2".parse::<RateCurve>().is_err());
+        assert!("square:min=1,max=2".parse::<RateCurve>().is_err());
+
+        let path = std::env::temp_dir().join(format!("rates-{}.csv", std::process::id()));
+        std::fs::write(&path, "hour,rate\n0,1\n1,3\n2,5\n").unwrap();
+        let points: RateCurve = format!("csv:{},interval=10m", path.display())
+            .parse()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            points,
+            RateCurve::Points {
+                rates: vec![1.0, 3.0, 5.0],
+                interval: Duration::from_secs(600),
+            }
+        );
+        assert_eq!(points.rate_at(Duration::from_secs(300)), 2.0);
+        // wraps around to the first rate after the last interval
+        assert_eq!(points.rate_at(Duration::from_secs(1500)), 3.0);
+        assert_eq!(points.rate_at(Duration::from_secs(1800)), 1.0);
+
+        let config = ExecutorConfig {
+            rate: Some(2.0),
+            rate_curve: Some(sine),
+            duration: day,
+            ..Default::default()
+        };
+        assert!((config.rate_at(day / 2).unwrap() - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_step_profile() {
+        let profile: StepProfile = "1, 2,4,8@60s".parse().unwrap();
+        assert_eq!(profile.rates, vec![1.0, 2.0, 4.0, 8.0]);
+        assert_eq!(profile.duration(), Duration::from_secs(240));
+        assert_eq!(profile.step_ids()[2], "step3@4.00req/s");
+        let curve = profile.rate_curve();
+        assert_eq!(curve.rate_at(Duration::from_secs(59)), 1.0);
+        assert_eq!(curve.rate_at(Duration::from_secs(60)), 2.0);
+        assert_eq!(curve.rate_at(Duration::from_secs(300)), 8.0);
+        assert!("1,2,4".parse::<StepProfile>().is_err());
+        assert!("1,-2@60s".parse::<StepProfile>().is_err());
+        assert!("1,2@0s".parse::<StepProfile>().is_err());
+    }
+
+    #[test]
+    fn test_burst_profile() {
+        let profile: BurstProfile = "base=2, burst=20,duration=10s,period=60s".parse().unwrap();
+        assert_eq!(profile.base_rate, 2.0);
+        assert_eq!(profile.burst_rate, 20.0);
+        let curve = profile.rate_curve();
+        // spikes fill the last 10 seconds of every minute
+        assert_eq!(curve.phase(Duration::from_secs(49)), Some(BASELINE_PHASE));
+        assert_eq!(curve.phase(Duration::from_secs(50)), Some(BURST_PHASE));
+        assert_eq!(curve.phase(Duration::from_secs(61)), Some(BASELINE_PHASE));
+        assert_eq!(curve.rate_at(Duration::from_secs(55)), 20.0);
+        assert_eq!(curve.rate_at(Duration::from_secs(65)), 2.0);
+        assert_eq!(
+            curve.phase_duration(BURST_PHASE, Duration::from_secs(175)),
+            Duration::from_secs(25)
+        );
+        assert_eq!(
+            curve.phase_duration(BASELINE_PHASE, Duration::from_secs(175)),
+            Duration::from_secs(150)
+        );
+        let steps = "1,2@60s".parse::<StepProfile>().unwrap().rate_curve();
+        assert_eq!(steps.phase(Duration::ZERO), None);
+        assert!("base=2,burst=20,duration=10s"
+            .parse::<BurstProfile>()
+            .is_err());
+        assert!("base=20,burst=2,duration=10s,period=60s"
+            .parse::<BurstProfile>()
+            .is_err());
+        assert!("base=2,burst=20,duration=60s,period=60s"
+            .parse::<BurstProfile>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_arrival_distribution() {
+        assert_eq!(ArrivalDistribution::Constant.arrivals(0.25), 0.25);
+        assert_eq!(ArrivalDistribution::Poisson.arrivals(0.0), 0.0);
+        let samples = (0..10000)
+            .map(|_| ArrivalDistribution::Poisson.arrivals(2.0))
+            .collect::<Vec<f64>>();
+        assert!(samples.iter().all(|s| s.fract() == 0.0));
+        // a Poisson distribution has the same mean and variance
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance =
+            samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!((mean - 2.0).abs() < 0.1);
+        assert!((variance - 2.0).abs() < 0.2);
+        assert!("gaussian".parse::<ArrivalDistribution>().is_err());
+    }
+
+    #[test]
+    fn test_trace() {
+        let path = std::env::temp_dir().join(format!("trace-{}.jsonl", std::process::id()));
+        std::fs::write(
+            &path,
+            concat!(
+                "{\"timestamp\": 4.0, \"prompt_tokens\": 30, \"output_tokens\": 3}\n",
+                "{\"timestamp\": 0.0, \"prompt_tokens\": 10, \"output_tokens\": 1}\n",
+                "\n",
+                "{\"timestamp\": 2.0, \"prompt_tokens\": 20, \"output_tokens\": 2}\n",
+            ),
+        )
+        .unwrap();
+        let trace: Trace = format!("{},time_scale=0.5", path.display())
+            .parse()
+            .unwrap();
+        assert!(format!("{},speed=2", path.display())
+            .parse::<Trace>()
+            .is_err());
+        assert!(format!("{},time_scale=0", path.display())
+            .parse::<Trace>()
+            .is_err());
+        std::fs::remove_file(&path).unwrap();
+        // requests are replayed in the order of their timestamps
+        assert_eq!(
+            trace
+                .entries
+                .iter()
+                .map(|e| e.prompt_tokens)
+                .collect::<Vec<_>>(),
+            vec![10, 20, 30]
+        );
+        assert_eq!(trace.arrival(1), Duration::from_secs(1));
+        assert_eq!(trace.duration(), Duration::from_secs(2));
+        assert_eq!(trace.average_rate(), 1.5);
+        assert!("missing.jsonl".parse::<Trace>().is_err());
+    }
+
+    #[test]
+    fn test_vu_cap() {
+        let cap = VuCap::default();
+        assert_eq!(cap.max_vus(128), 128);
+        // no VU in flight, nothing to cap
+        assert_eq!(cap.cap(), None);
+        let active = cap.track();
+        active.fetch_add(10, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(cap.cap(), Some(10));
+        assert_eq!(cap.max_vus(128), 10);
+        assert_eq!(cap.max_vus(4), 4);
+        // the cap only goes down
+        active.fetch_add(5, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(cap.cap(), None);
+        active.fetch_sub(7, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(cap.cap(), Some(8));
     }
 }
@@ -1,12 +1,28 @@
-use crate::results::BenchmarkResults;
-use std::sync::{Arc, Mutex};
+use crate::event::StageSummary;
+use crate::requests::TextGenerationAggregatedResponse;
+use crate::scheduler::{Heartbeat, SchedulerProgress};
+use log::warn;
+use serde::Serialize;
+#[cfg(feature = "tui")]
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "tui")]
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(feature = "tui")]
+use std::time::Instant;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 
 // Flux pattern
+#[cfg(feature = "tui")]
 #[derive(Clone)]
 pub struct Dispatcher {
     store: Arc<Mutex<Store>>,
 }
 
+#[cfg(feature = "tui")]
 impl Dispatcher {
     pub(crate) fn new(store: Arc<Mutex<Store>>) -> Self {
         Self { store }
@@ -16,27 +32,112 @@ impl Dispatcher {
     }
 }
 
+#[cfg(feature = "tui")]
 #[derive(Clone)]
 pub struct AppState {
     pub(crate) messages: Vec<crate::app::LogMessageUI>,
     pub(crate) benchmarks: Vec<crate::app::BenchmarkUI>,
-    pub(crate) results: Vec<BenchmarkResults>,
+    pub(crate) results: Vec<StageSummary>,
+    pub(crate) live: LiveMetrics,
+    /// Dataset or tokenizer download in progress
+    pub(crate) download: Option<crate::benchmark::DownloadProgressEvent>,
 }
 
+#[cfg(feature = "tui")]
 impl AppState {
     fn new() -> Self {
         Self {
             messages: Vec::new(),
             benchmarks: Vec::new(),
             results: Vec::new(),
+            live: LiveMetrics::new(LIVE_METRICS_WINDOW),
+            download: None,
         }
     }
 }
 
+#[cfg(feature = "tui")]
+const LIVE_METRICS_WINDOW: Duration = Duration::from_secs(10);
+
+/// Limits above which the live metrics are highlighted in the console
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AlertThresholds {
+    /// Error rate in percent
+    pub error_rate: Option<f64>,
+    #[serde(rename = "e2e_latency_p99_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSeconds<u64>>")]
+    pub e2e_latency_p99: Option<Duration>,
+}
+
+/// Metrics computed over the requests received in a sliding time window
+#[cfg(feature = "tui")]
+#[derive(Clone)]
+pub struct LiveMetrics {
+    window: Duration,
+    samples: VecDeque<(Instant, RequestSample)>,
+}
+
+#[cfg(feature = "tui")]
+impl LiveMetrics {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, now: Instant, sample: RequestSample) {
+        self.samples.push_back((now, sample));
+        while let Some((t, _)) = self.samples.front() {
+            if now.duration_since(*t) <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    pub(crate) fn token_throughput_secs(&self) -> f64 {
+        let tokens: u64 = self
+            .samples
+            .iter()
+            .filter(|(_, s)| !s.failed)
+            .map(|(_, s)| s.num_generated_tokens)
+            .sum();
+        tokens as f64 / self.window.as_secs_f64()
+    }
+
+    /// Error rate in percent
+    pub(crate) fn error_rate(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let failed = self.samples.iter().filter(|(_, s)| s.failed).count();
+        failed as f64 / self.samples.len() as f64 * 100.0
+    }
+
+    pub(crate) fn e2e_latency_p99(&self) -> Option<Duration> {
+        let mut latencies = self
+            .samples
+            .iter()
+            .filter(|(_, s)| !s.failed)
+            .filter_map(|(_, s)| s.e2e_latency)
+            .collect::<Vec<_>>();
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort();
+        let index = ((latencies.len() as f64 * 0.99) as usize).min(latencies.len() - 1);
+        Some(latencies[index])
+    }
+}
+
+#[cfg(feature = "tui")]
 pub struct Store {
     state: AppState,
 }
 
+#[cfg(feature = "tui")]
 impl Store {
     pub(crate) fn new() -> Self {
         let state = AppState::new();
@@ -77,6 +178,11 @@ impl Store {
                     }
                 }
             }
+            Action::AddRequestSample(sample) => self.state.live.add(Instant::now(), sample),
+            Action::DownloadProgress(progress) => {
+                let done = progress.total == Some(progress.downloaded);
+                self.state.download = (!done).then_some(progress);
+            }
         }
     }
 
@@ -85,8 +191,189 @@ impl Store {
     }
 }
 
+#[cfg(feature = "tui")]
 pub enum Action {
     LogMessage(crate::app::LogMessageUI),
     AddBenchmark(crate::app::BenchmarkUI),
-    AddBenchmarkResults(BenchmarkResults),
+    AddBenchmarkResults(StageSummary),
+    AddRequestSample(RequestSample),
+    DownloadProgress(crate::benchmark::DownloadProgressEvent),
+}
+
+// Metrics bus
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Topic {
+    Progress,
+    Request,
+    Stage,
+    Heartbeat,
+}
+
+#[derive(Clone, Debug)]
+pub enum Metric {
+    Progress(SchedulerProgress),
+    Request(RequestSample),
+    Stage(StageSummary),
+    Heartbeat(Heartbeat),
+}
+
+impl Metric {
+    pub fn topic(&self) -> Topic {
+        match self {
+            Metric::Progress(_) => Topic::Progress,
+            Metric::Request(_) => Topic::Request,
+            Metric::Stage(_) => Topic::Stage,
+            Metric::Heartbeat(_) => Topic::Heartbeat,
+        }
+    }
+}
+
+/// Lightweight per-request metrics, without prompt and generated text
+#[derive(Clone, Debug)]
+pub struct RequestSample {
+    pub id: String,
+    pub num_prompt_tokens: u64,
+    pub num_generated_tokens: u64,
+    pub time_to_first_token: Option<Duration>,
+    pub inter_token_latency: Option<Duration>,
+    pub e2e_latency: Option<Duration>,
+    pub server_generated_tokens: Option<u64>,
+    pub failed: bool,
+}
+
+impl RequestSample {
+    pub fn new(id: String, response: &TextGenerationAggregatedResponse) -> Self {
+        Self {
+            id,
+            num_prompt_tokens: response.num_prompt_tokens,
+            num_generated_tokens: response.num_generated_tokens,
+            time_to_first_token: response.time_to_first_token(),
+            inter_token_latency: response.inter_token_latency(),
+            e2e_latency: response.e2e_latency(),
+            server_generated_tokens: response
+                .server_details
+                .as_ref()
+                .and_then(|d| d.generated_tokens),
+            failed: response.failed,
+        }
+    }
+}
+
+/// Broadcast bus carrying typed metrics to any number of subscribers (TUI, exporters, writers).
+/// Publishing never blocks: slow subscribers skip the metrics they lagged behind on.
+#[derive(Clone)]
+pub struct MetricsBus {
+    sender: broadcast::Sender<Metric>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl MetricsBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn publish(&self, metric: Metric) {
+        // no subscriber is not an error
+        let _ = self.sender.send(metric);
+    }
+
+    pub fn subscribe(&self, topics: &[Topic]) -> MetricsSubscriber {
+        MetricsSubscriber {
+            receiver: self.sender.subscribe(),
+            topics: topics.to_vec(),
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Total number of metrics skipped by subscribers that lagged behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+pub struct MetricsSubscriber {
+    receiver: broadcast::Receiver<Metric>,
+    topics: Vec<Topic>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl MetricsSubscriber {
+    /// Wait for the next metric on the subscribed topics. Returns `None` once the bus is closed.
+    pub async fn recv(&mut self) -> Option<Metric> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(metric) if self.topics.contains(&metric.topic()) => return Some(metric),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Metrics subscriber lagged behind, skipped {skipped} metrics");
+                    self.dropped.fetch_add(skipped, Ordering::Relaxed);
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::ExecutorType;
+
+    #[tokio::test]
+    async fn test_metrics_bus_topics() {
+        let bus = MetricsBus::new(16);
+        let mut requests = bus.subscribe(&[Topic::Request]);
+        let mut all = bus.subscribe(&[Topic::Progress, Topic::Request]);
+        bus.publish(Metric::Progress(SchedulerProgress {
+            id: "test".to_string(),
+            executor_type: ExecutorType::ConstantArrivalRate,
+            target_rate: Some(1.0),
+            elapsed: Duration::from_secs(5),
+            remaining: Duration::from_secs(5),
+            progress: 50.0,
+            requests_throughput: 1.0,
+            successful_requests: 1,
+            failed_requests: 0,
+        }));
+        bus.publish(Metric::Request(RequestSample::new(
+            "test".to_string(),
+            &TextGenerationAggregatedResponse::default(),
+        )));
+        drop(bus);
+        assert_eq!(requests.recv().await.unwrap().topic(), Topic::Request);
+        assert!(requests.recv().await.is_none());
+        assert_eq!(all.recv().await.unwrap().topic(), Topic::Progress);
+        assert_eq!(all.recv().await.unwrap().topic(), Topic::Request);
+        assert!(all.recv().await.is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "tui")]
+    fn test_live_metrics() {
+        let sample = |tokens: u64, e2e_ms: u64, failed: bool| RequestSample {
+            id: "test".to_string(),
+            num_prompt_tokens: 0,
+            num_generated_tokens: tokens,
+            time_to_first_token: None,
+            inter_token_latency: None,
+            e2e_latency: Some(Duration::from_millis(e2e_ms)),
+            server_generated_tokens: None,
+            failed,
+        };
+        let start = Instant::now();
+        let mut live = LiveMetrics::new(Duration::from_secs(10));
+        // falls out of the window
+        live.add(start, sample(1000, 5000, false));
+        live.add(start + Duration::from_secs(11), sample(100, 100, false));
+        live.add(start + Duration::from_secs(12), sample(100, 200, false));
+        live.add(start + Duration::from_secs(12), sample(0, 0, true));
+        assert_eq!(live.token_throughput_secs(), 20.0);
+        assert!((live.error_rate() - 100.0 / 3.0).abs() < 1e-9);
+        assert_eq!(live.e2e_latency_p99(), Some(Duration::from_millis(200)));
+    }
 }
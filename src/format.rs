@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+/// Unit of latencies in tables and the console
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LatencyUnit {
+    /// Seconds for end-to-end latencies, milliseconds for token latencies
+    #[default]
+    Auto,
+    Milliseconds,
+    Seconds,
+}
+
+/// Prefix scaling of throughputs: `1.23 k tokens/sec` (SI, powers of 1000) or
+/// `1.20 Ki tokens/sec` (binary, powers of 1024)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThroughputPrefix {
+    #[default]
+    None,
+    Si,
+    Binary,
+}
+
+/// Presentation of numbers in tables and the console. It doesn't depend on the system locale, so
+/// the output is the same on every machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputFormat {
+    pub latency_unit: LatencyUnit,
+    pub throughput_prefix: ThroughputPrefix,
+    pub decimal_separator: char,
+    pub thousands_separator: Option<char>,
+    /// Tables in GitHub-flavored Markdown instead of box drawing characters
+    pub markdown: bool,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self {
+            latency_unit: LatencyUnit::Auto,
+            throughput_prefix: ThroughputPrefix::None,
+            decimal_separator: '.',
+            thousands_separator: None,
+            markdown: false,
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn try_new(
+        latency_unit: &str,
+        throughput_prefix: &str,
+        decimal_separator: char,
+        thousands_separator: Option<char>,
+    ) -> anyhow::Result<Self> {
+        let latency_unit = match latency_unit.to_lowercase().as_str() {
+            "auto" => LatencyUnit::Auto,
+            "ms" => LatencyUnit::Milliseconds,
+            "s" | "sec" => LatencyUnit::Seconds,
+            _ => return Err(anyhow::anyhow!("Unknown latency unit: {latency_unit}")),
+        };
+        let throughput_prefix = match throughput_prefix.to_lowercase().as_str() {
+            "none" => ThroughputPrefix::None,
+            "si" => ThroughputPrefix::Si,
+            "binary" => ThroughputPrefix::Binary,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unknown throughput prefix: {throughput_prefix}"
+                ))
+            }
+        };
+        if decimal_separator.is_ascii_digit() || Some(decimal_separator) == thousands_separator {
+            return Err(anyhow::anyhow!(
+                "Invalid decimal separator: {decimal_separator:?}"
+            ));
+        }
+        Ok(Self {
+            latency_unit,
+            throughput_prefix,
+            decimal_separator,
+            thousands_separator,
+            markdown: false,
+        })
+    }
+
+    /// Apply the table style
+    pub fn style(&self, table: &mut tabled::Table) {
+        if self.markdown {
+            table.with(tabled::settings::Style::markdown());
+        } else {
+            table.with(tabled::settings::Style::sharp());
+        }
+    }
+
+    /// Format `value` with `decimals` digits after the decimal separator
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{value:.decimals$}");
+        let (sign, formatted) = match formatted.strip_prefix('-') {
+            Some(abs) => ("-", abs),
+            None => ("", formatted.as_str()),
+        };
+        let (integer, fraction) = formatted.split_once('.').unwrap_or((formatted, ""));
+        let mut out = sign.to_string();
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                if let Some(separator) = self.thousands_separator {
+                    out.push(separator);
+                }
+            }
+            out.push(digit);
+        }
+        if !fraction.is_empty() {
+            out.push(self.decimal_separator);
+            out.push_str(fraction);
+        }
+        out
+    }
+
+    /// Format a latency whose unit is `auto` when the latency unit is `Auto`
+    pub fn latency(&self, latency: Duration, auto: LatencyUnit) -> String {
+        self.latency_secs(latency.as_secs_f64(), auto)
+    }
+
+    pub fn latency_secs(&self, secs: f64, auto: LatencyUnit) -> String {
+        let unit = match self.latency_unit {
+            LatencyUnit::Auto => auto,
+            unit => unit,
+        };
+        match unit {
+            LatencyUnit::Milliseconds => format!("{} ms", self.number(secs * 1000.0, 2)),
+            _ => format!("{} sec", self.number(secs, 2)),
+        }
+    }
+
+    /// Format a rate such as `tokens/sec`, scaled according to the throughput prefix
+    pub fn throughput(&self, value: f64, unit: &str) -> String {
+        let (base, prefixes) = match self.throughput_prefix {
+            ThroughputPrefix::None => return format!("{} {unit}", self.number(value, 2)),
+            ThroughputPrefix::Si => (1000.0, ["k", "M", "G"]),
+            ThroughputPrefix::Binary => (1024.0, ["Ki", "Mi", "Gi"]),
+        };
+        let mut scaled = value;
+        let mut prefix = "";
+        for p in prefixes {
+            if scaled.abs() < base {
+                break;
+            }
+            scaled /= base;
+            prefix = p;
+        }
+        if prefix.is_empty() {
+            format!("{} {unit}", self.number(scaled, 2))
+        } else {
+            format!("{} {prefix} {unit}", self.number(scaled, 2))
+        }
+    }
+
+    pub fn percent(&self, value: f64) -> String {
+        format!("{}%", self.number(value, 2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_format() {
+        let default = OutputFormat::default();
+        assert_eq!(default.number(1234567.891, 2), "1234567.89");
+        assert_eq!(
+            default.latency(Duration::from_millis(1500), LatencyUnit::Seconds),
+            "1.50 sec"
+        );
+        assert_eq!(
+            default.throughput(2048.0, "tokens/sec"),
+            "2048.00 tokens/sec"
+        );
+        let format = OutputFormat::try_new("ms", "si", ',', Some('.')).unwrap();
+        assert_eq!(format.number(-1234567.891, 2), "-1.234.567,89");
+        assert_eq!(format.number(123.0, 0), "123");
+        assert_eq!(
+            format.latency(Duration::from_millis(1500), LatencyUnit::Seconds),
+            "1.500,00 ms"
+        );
+        assert_eq!(format.throughput(2048.0, "tokens/sec"), "2,05 k tokens/sec");
+        let format = OutputFormat::try_new("s", "binary", '.', None).unwrap();
+        assert_eq!(
+            format.throughput(2048.0, "tokens/sec"),
+            "2.00 Ki tokens/sec"
+        );
+        assert_eq!(format.throughput(512.0, "req/s"), "512.00 req/s");
+        assert!(OutputFormat::try_new("us", "none", '.', None).is_err());
+        assert!(OutputFormat::try_new("ms", "none", ',', Some(',')).is_err());
+    }
+
+    #[test]
+    fn test_markdown_style() {
+        let mut builder = tabled::builder::Builder::default();
+        builder.set_header(["Benchmark", "QPS"]);
+        builder.push_record(["throughput", "1.00 req/s"]);
+        let mut table = builder.build();
+        let format = OutputFormat {
+            markdown: true,
+            ..OutputFormat::default()
+        };
+        format.style(&mut table);
+        assert_eq!(
+            table.to_string(),
+            "| Benchmark  | QPS        |\n|------------|------------|\n| throughput | 1.00 req/s |"
+        );
+    }
+}
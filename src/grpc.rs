@@ -0,0 +1,295 @@
+//! Subset of the KServe v2 gRPC protocol (`inference.GRPCInferenceService`) served by Triton
+//! Inference Server, enough to stream generations with `ModelStreamInfer`.
+use std::collections::HashMap;
+
+/// Bidirectional streaming RPC of Triton decoupled models, such as TensorRT-LLM ensembles
+pub const MODEL_STREAM_INFER: &str = "/inference.GRPCInferenceService/ModelStreamInfer";
+/// Input tensor of the prompt
+const TEXT_INPUT: &str = "text_input";
+/// Output tensor of the generated text, the new text of each streamed response
+const TEXT_OUTPUT: &str = "text_output";
+/// Request parameter asking Triton to flag the last response of a decoupled model
+const ENABLE_FINAL_RESPONSE: &str = "triton_enable_empty_final_response";
+/// Response parameter set on the last response of a request
+const FINAL_RESPONSE: &str = "triton_final_response";
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InferParameter {
+    #[prost(oneof = "infer_parameter::ParameterChoice", tags = "1, 2, 3")]
+    pub parameter_choice: Option<infer_parameter::ParameterChoice>,
+}
+
+pub mod infer_parameter {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum ParameterChoice {
+        #[prost(bool, tag = "1")]
+        Bool(bool),
+        #[prost(int64, tag = "2")]
+        Int64(i64),
+        #[prost(string, tag = "3")]
+        String(String),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InferTensorContents {
+    #[prost(bool, repeated, tag = "1")]
+    pub bool_contents: Vec<bool>,
+    #[prost(int32, repeated, tag = "2")]
+    pub int_contents: Vec<i32>,
+    #[prost(bytes = "vec", repeated, tag = "8")]
+    pub bytes_contents: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InferInputTensor {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub datatype: String,
+    #[prost(int64, repeated, tag = "3")]
+    pub shape: Vec<i64>,
+    #[prost(message, optional, tag = "5")]
+    pub contents: Option<InferTensorContents>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ModelInferRequest {
+    #[prost(string, tag = "1")]
+    pub model_name: String,
+    #[prost(string, tag = "3")]
+    pub id: String,
+    #[prost(map = "string, message", tag = "4")]
+    pub parameters: HashMap<String, InferParameter>,
+    #[prost(message, repeated, tag = "5")]
+    pub inputs: Vec<InferInputTensor>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct InferOutputTensor {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub datatype: String,
+    #[prost(int64, repeated, tag = "3")]
+    pub shape: Vec<i64>,
+    #[prost(message, optional, tag = "5")]
+    pub contents: Option<InferTensorContents>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ModelInferResponse {
+    #[prost(string, tag = "1")]
+    pub model_name: String,
+    #[prost(string, tag = "3")]
+    pub id: String,
+    #[prost(map = "string, message", tag = "4")]
+    pub parameters: HashMap<String, InferParameter>,
+    #[prost(message, repeated, tag = "5")]
+    pub outputs: Vec<InferOutputTensor>,
+    #[prost(bytes = "vec", repeated, tag = "6")]
+    pub raw_output_contents: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ModelStreamInferResponse {
+    #[prost(string, tag = "1")]
+    pub error_message: String,
+    #[prost(message, optional, tag = "2")]
+    pub infer_response: Option<ModelInferResponse>,
+}
+
+fn tensor(name: &str, datatype: &str, contents: InferTensorContents) -> InferInputTensor {
+    InferInputTensor {
+        name: name.to_string(),
+        datatype: datatype.to_string(),
+        shape: vec![1, 1],
+        contents: Some(contents),
+    }
+}
+
+fn bool_tensor(name: &str, value: bool) -> InferInputTensor {
+    tensor(
+        name,
+        "BOOL",
+        InferTensorContents {
+            bool_contents: vec![value],
+            ..Default::default()
+        },
+    )
+}
+
+/// Streaming request of TensorRT-LLM ensembles: the prompt, the maximum number of tokens to
+/// generate and the streaming flags, asking for a flagged final response
+pub fn stream_request(
+    model_name: &str,
+    text_input: &str,
+    max_tokens: Option<u64>,
+) -> ModelInferRequest {
+    let mut inputs = vec![
+        tensor(
+            TEXT_INPUT,
+            "BYTES",
+            InferTensorContents {
+                bytes_contents: vec![text_input.as_bytes().to_vec()],
+                ..Default::default()
+            },
+        ),
+        bool_tensor("stream", true),
+        bool_tensor("exclude_input_in_output", true),
+    ];
+    if let Some(max_tokens) = max_tokens {
+        inputs.push(tensor(
+            "max_tokens",
+            "INT32",
+            InferTensorContents {
+                int_contents: vec![max_tokens.min(i32::MAX as u64) as i32],
+                ..Default::default()
+            },
+        ));
+    }
+    ModelInferRequest {
+        model_name: model_name.to_string(),
+        id: String::new(),
+        parameters: HashMap::from([(
+            ENABLE_FINAL_RESPONSE.to_string(),
+            InferParameter {
+                parameter_choice: Some(infer_parameter::ParameterChoice::Bool(true)),
+            },
+        )]),
+        inputs,
+    }
+}
+
+/// Text of the `text_output` tensor, from its typed contents or from the raw output contents,
+/// where each BYTES element is prefixed by its length as a 4-byte little-endian integer
+pub fn output_text(response: &ModelInferResponse) -> anyhow::Result<String> {
+    let Some(index) = response.outputs.iter().position(|o| o.name == TEXT_OUTPUT) else {
+        return Ok(String::new());
+    };
+    let mut elements = Vec::new();
+    if let Some(contents) = response.outputs[index]
+        .contents
+        .as_ref()
+        .filter(|c| !c.bytes_contents.is_empty())
+    {
+        elements.extend(contents.bytes_contents.iter().map(Vec::as_slice));
+    } else if let Some(mut raw) = response.raw_output_contents.get(index).map(Vec::as_slice) {
+        while !raw.is_empty() {
+            if raw.len() < 4 {
+                return Err(anyhow::anyhow!("Truncated {TEXT_OUTPUT} tensor"));
+            }
+            let (len, rest) = raw.split_at(4);
+            let len = u32::from_le_bytes(len.try_into()?) as usize;
+            if rest.len() < len {
+                return Err(anyhow::anyhow!("Truncated {TEXT_OUTPUT} tensor"));
+            }
+            let (element, rest) = rest.split_at(len);
+            elements.push(element);
+            raw = rest;
+        }
+    }
+    Ok(elements
+        .into_iter()
+        .map(String::from_utf8_lossy)
+        .collect::<String>())
+}
+
+/// Whether the response is the last one of its request
+pub fn is_final(response: &ModelInferResponse) -> bool {
+    matches!(
+        response.parameters.get(FINAL_RESPONSE),
+        Some(InferParameter {
+            parameter_choice: Some(infer_parameter::ParameterChoice::Bool(true))
+        })
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(outputs: Vec<InferOutputTensor>, raw: Vec<Vec<u8>>) -> ModelInferResponse {
+        ModelInferResponse {
+            model_name: "ensemble".to_string(),
+            id: String::new(),
+            parameters: HashMap::new(),
+            outputs,
+            raw_output_contents: raw,
+        }
+    }
+
+    fn output(name: &str, contents: Option<InferTensorContents>) -> InferOutputTensor {
+        InferOutputTensor {
+            name: name.to_string(),
+            datatype: "BYTES".to_string(),
+            shape: vec![1, 1],
+            contents,
+        }
+    }
+
+    #[test]
+    fn test_stream_request() {
+        let request = stream_request("ensemble", "Hello", Some(10));
+        assert_eq!(request.model_name, "ensemble");
+        let names = request
+            .inputs
+            .iter()
+            .map(|i| i.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "text_input",
+                "stream",
+                "exclude_input_in_output",
+                "max_tokens"
+            ]
+        );
+        let contents = request.inputs[3].contents.as_ref().unwrap();
+        assert_eq!(contents.int_contents, vec![10]);
+        assert!(request.parameters.contains_key(ENABLE_FINAL_RESPONSE));
+        assert_eq!(stream_request("ensemble", "Hello", None).inputs.len(), 3);
+    }
+
+    #[test]
+    fn test_output_text() {
+        let mut raw = Vec::new();
+        for element in ["Hello", " world"] {
+            raw.extend((element.len() as u32).to_le_bytes());
+            raw.extend(element.as_bytes());
+        }
+        let raw_response = response(
+            vec![output("cum_log_probs", None), output(TEXT_OUTPUT, None)],
+            vec![vec![0; 4], raw.clone()],
+        );
+        assert_eq!(output_text(&raw_response).unwrap(), "Hello world");
+        let typed_response = response(
+            vec![output(
+                TEXT_OUTPUT,
+                Some(InferTensorContents {
+                    bytes_contents: vec![b"Hello".to_vec()],
+                    ..Default::default()
+                }),
+            )],
+            vec![],
+        );
+        assert_eq!(output_text(&typed_response).unwrap(), "Hello");
+        let truncated = response(vec![output(TEXT_OUTPUT, None)], vec![raw[..7].to_vec()]);
+        assert!(output_text(&truncated).is_err());
+        assert_eq!(output_text(&response(vec![], vec![])).unwrap(), "");
+    }
+
+    #[test]
+    fn test_is_final() {
+        let mut final_response = response(vec![], vec![]);
+        assert!(!is_final(&final_response));
+        final_response.parameters.insert(
+            FINAL_RESPONSE.to_string(),
+            InferParameter {
+                parameter_choice: Some(infer_parameter::ParameterChoice::Bool(true)),
+            },
+        );
+        assert!(is_final(&final_response));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::benchmark::{Event, MessageEvent};
+use crate::executors::VuCap;
+use log::warn;
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use sysinfo::{MemoryRefreshKind, System};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+
+const HEADROOM_INTERVAL: Duration = Duration::from_secs(5);
+/// Client CPU usage (percent) from which the load generator is considered saturated
+const CPU_SATURATION: f32 = 90.0;
+/// Share of the client memory in use (percent) from which the load generator is considered saturated
+const MEMORY_SATURATION: f64 = 90.0;
+/// Share of the open files limit in use (percent) from which the load generator is considered saturated
+const OPEN_FILES_SATURATION: f64 = 90.0;
+
+/// What to do when the load generator itself approaches saturation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeadroomPolicy {
+    /// Don't monitor the client resources
+    Off,
+    /// Warn and record the saturation in the report
+    #[default]
+    Warn,
+    /// Also cap the VUs at the ones in flight, which caps the arrival rate of rate steps
+    Cap,
+}
+
+impl FromStr for HeadroomPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(HeadroomPolicy::Off),
+            "warn" => Ok(HeadroomPolicy::Warn),
+            "cap" => Ok(HeadroomPolicy::Cap),
+            _ => Err(anyhow::anyhow!("Unknown client headroom policy: {s}")),
+        }
+    }
+}
+
+/// Resource usage of the client running the benchmark
+#[derive(Clone, Debug, Serialize)]
+pub struct ClientUsage {
+    pub cpu_percent: f32,
+    pub memory_percent: f64,
+    /// Open file descriptors (sockets included) and their soft limit, where the OS exposes them
+    pub open_files: Option<u64>,
+    pub max_open_files: Option<u64>,
+}
+
+impl ClientUsage {
+    fn sample(system: &mut System) -> ClientUsage {
+        system.refresh_cpu_usage();
+        system.refresh_memory_specifics(MemoryRefreshKind::new().with_ram());
+        let (open_files, max_open_files) = open_files();
+        ClientUsage {
+            cpu_percent: system.global_cpu_usage(),
+            memory_percent: system.used_memory() as f64 / system.total_memory().max(1) as f64
+                * 100.0,
+            open_files,
+            max_open_files,
+        }
+    }
+
+    /// Resources close to exhaustion, empty when the client has headroom
+    pub fn saturated_resources(&self) -> Vec<String> {
+        let mut resources = vec![];
+        if self.cpu_percent > CPU_SATURATION {
+            resources.push(format!("CPU at {:.0}%", self.cpu_percent));
+        }
+        if self.memory_percent > MEMORY_SATURATION {
+            resources.push(format!("memory at {:.0}%", self.memory_percent));
+        }
+        if let (Some(open), Some(max)) = (self.open_files, self.max_open_files) {
+            if open as f64 > max as f64 * OPEN_FILES_SATURATION / 100.0 {
+                resources.push(format!("{open}/{max} open files"));
+            }
+        }
+        resources
+    }
+}
+
+/// Client saturation recorded in the report, with the VU cap applied if any
+#[derive(Clone, Debug, Serialize)]
+pub struct ClientSaturation {
+    /// RFC 3339 time of the sample
+    pub timestamp: String,
+    pub resources: Vec<String>,
+    pub usage: ClientUsage,
+    pub vu_cap: Option<u64>,
+}
+
+/// Samples the client resources while the benchmark runs. Saturation is warned about and recorded
+/// when it starts, and with the `cap` policy the VUs are capped at the ones in flight, lower each
+/// time the client is still saturated.
+pub struct HeadroomMonitor {
+    saturations: Arc<Mutex<Vec<ClientSaturation>>>,
+    task: JoinHandle<()>,
+}
+
+impl HeadroomMonitor {
+    pub fn spawn(
+        policy: HeadroomPolicy,
+        vu_cap: VuCap,
+        event_bus: UnboundedSender<Event>,
+    ) -> HeadroomMonitor {
+        let saturations = Arc::new(Mutex::new(Vec::new()));
+        let recorded = saturations.clone();
+        let task = tokio::spawn(async move {
+            let mut system = System::new();
+            let mut interval = tokio::time::interval(HEADROOM_INTERVAL);
+            let mut saturated = false;
+            loop {
+                interval.tick().await;
+                let usage = ClientUsage::sample(&mut system);
+                let resources = usage.saturated_resources();
+                if resources.is_empty() {
+                    saturated = false;
+                    continue;
+                }
+                let vu_cap = match policy {
+                    HeadroomPolicy::Cap => vu_cap.cap(),
+                    _ => None,
+                };
+                // report the start of a saturation, and every cap lowering the VUs
+                if saturated && vu_cap.is_none() {
+                    continue;
+                }
+                saturated = true;
+                let message = match vu_cap {
+                    Some(cap) => format!(
+                        "Client saturated ({}), VUs capped at {cap}",
+                        resources.join(", ")
+                    ),
+                    None => format!(
+                        "Client saturated ({}), results may be client-bound",
+                        resources.join(", ")
+                    ),
+                };
+                warn!("{message}");
+                let _ = event_bus.send(Event::Message(MessageEvent {
+                    message,
+                    timestamp: chrono::Utc::now(),
+                    level: log::Level::Warn,
+                }));
+                recorded.lock().expect("lock").push(ClientSaturation {
+                    timestamp: chrono::Utc::now().to_rfc3339(),
+                    resources,
+                    usage,
+                    vu_cap,
+                });
+            }
+        });
+        HeadroomMonitor { saturations, task }
+    }
+
+    /// Stop monitoring and return the recorded saturations
+    pub fn stop(self) -> Vec<ClientSaturation> {
+        self.task.abort();
+        self.saturations.lock().expect("lock").clone()
+    }
+}
+
+impl Drop for HeadroomMonitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Open file descriptors of the process and their soft limit, from procfs
+#[cfg(target_os = "linux")]
+fn open_files() -> (Option<u64>, Option<u64>) {
+    let open = std::fs::read_dir("/proc/self/fd")
+        .ok()
+        .map(|entries| entries.count() as u64);
+    let max = std::fs::read_to_string("/proc/self/limits")
+        .ok()
+        .and_then(|limits| {
+            limits
+                .lines()
+                .find(|line| line.starts_with("Max open files"))?
+                .split_whitespace()
+                .nth(3)?
+                .parse()
+                .ok()
+        });
+    (open, max)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_files() -> (Option<u64>, Option<u64>) {
+    (None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saturated_resources() {
+        let mut usage = ClientUsage {
+            cpu_percent: 50.0,
+            memory_percent: 40.0,
+            open_files: Some(100),
+            max_open_files: Some(1024),
+        };
+        assert!(usage.saturated_resources().is_empty());
+        usage.cpu_percent = 95.0;
+        usage.open_files = Some(1000);
+        assert_eq!(
+            usage.saturated_resources(),
+            vec!["CPU at 95%".to_string(), "1000/1024 open files".to_string()]
+        );
+    }
+}
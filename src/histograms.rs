@@ -0,0 +1,159 @@
+use crate::requests::TextGenerationAggregatedResponse;
+use crate::results::RequestMetric;
+use crate::timeseries::{TimeSeries, TimeSeriesPoint};
+use hdrhistogram::Histogram;
+use std::time::Duration;
+
+/// Significant figures of the values recorded in the histograms, i.e. a relative error under 0.1%
+const SIGNIFICANT_FIGURES: u8 = 3;
+
+/// Histogram values per metric unit: latencies are recorded in microseconds, tokens per second in
+/// thousandths, as histograms only hold integers
+const VALUE_SCALE: f64 = 1000.0;
+
+/// Distribution of a per-request metric in an HDR histogram, in constant memory whatever the
+/// number of recorded values
+#[derive(Clone, Debug)]
+pub(crate) struct MetricHistogram {
+    histogram: Histogram<u64>,
+    sum: f64,
+    sum_squares: f64,
+}
+
+impl MetricHistogram {
+    fn new() -> Self {
+        Self {
+            histogram: Histogram::new(SIGNIFICANT_FIGURES).expect("valid significant figures"),
+            sum: 0.0,
+            sum_squares: 0.0,
+        }
+    }
+
+    fn record(&mut self, value: f64) {
+        // saturating_record would clamp to the current bounds instead of resizing
+        self.histogram
+            .record((value.max(0.0) * VALUE_SCALE).round() as u64)
+            .expect("histograms resize to any value");
+        self.sum += value;
+        self.sum_squares += value * value;
+    }
+
+    /// Value at `quantile` (between 0 and 1), `None` if nothing was recorded
+    pub(crate) fn quantile(&self, quantile: f64) -> Option<f64> {
+        (!self.histogram.is_empty())
+            .then(|| self.histogram.value_at_quantile(quantile) as f64 / VALUE_SCALE)
+    }
+
+    /// Exact average of the recorded values, `None` if nothing was recorded
+    pub(crate) fn mean(&self) -> Option<f64> {
+        (!self.histogram.is_empty()).then(|| self.sum / self.histogram.len() as f64)
+    }
+
+    /// Sample standard deviation of the recorded values, `None` under two values
+    pub(crate) fn std_dev(&self) -> Option<f64> {
+        let n = self.histogram.len() as f64;
+        let mean = self.mean()?;
+        (n > 1.0).then(|| ((self.sum_squares - n * mean * mean).max(0.0) / (n - 1.0)).sqrt())
+    }
+}
+
+/// Counters and per-request metric histograms of all the responses of a step, updated as they
+/// arrive. They stay exact when only a sample of the responses is stored.
+#[derive(Clone, Debug)]
+pub(crate) struct ResponseAggregates {
+    pub(crate) total_requests: u64,
+    pub(crate) failed_requests: u64,
+    /// Generated tokens of the successful requests
+    pub(crate) generated_tokens: u64,
+    /// Prompt tokens of the successful requests
+    pub(crate) prompt_tokens: u64,
+    /// Start time of the first received response
+    pub(crate) start_time: Option<tokio::time::Instant>,
+    /// End time of the last received response
+    pub(crate) end_time: Option<tokio::time::Instant>,
+    time_to_first_token: MetricHistogram,
+    inter_token_latency: MetricHistogram,
+    e2e_latency: MetricHistogram,
+    output_tokens_per_sec: MetricHistogram,
+    time_series: Option<TimeSeries>,
+}
+
+impl ResponseAggregates {
+    /// Aggregates of new responses, bucketed over time in windows of `time_series_window` if set
+    pub(crate) fn new(time_series_window: Option<Duration>) -> Self {
+        Self {
+            total_requests: 0,
+            failed_requests: 0,
+            generated_tokens: 0,
+            prompt_tokens: 0,
+            start_time: None,
+            end_time: None,
+            time_to_first_token: MetricHistogram::new(),
+            inter_token_latency: MetricHistogram::new(),
+            e2e_latency: MetricHistogram::new(),
+            output_tokens_per_sec: MetricHistogram::new(),
+            time_series: time_series_window.map(TimeSeries::new),
+        }
+    }
+
+    pub(crate) fn from_responses(
+        responses: &[TextGenerationAggregatedResponse],
+        time_series_window: Option<Duration>,
+    ) -> Self {
+        let mut aggregates = Self::new(time_series_window);
+        for response in responses {
+            aggregates.add(response);
+        }
+        aggregates
+    }
+
+    pub(crate) fn add(&mut self, response: &TextGenerationAggregatedResponse) {
+        if self.total_requests == 0 {
+            self.start_time = response.start_time;
+        }
+        self.end_time = response.end_time;
+        self.total_requests += 1;
+        if let (Some(time_series), Some(start)) = (&mut self.time_series, self.start_time) {
+            time_series.add(start, response);
+        }
+        if response.failed {
+            self.failed_requests += 1;
+            return;
+        }
+        self.generated_tokens += response.num_generated_tokens;
+        self.prompt_tokens += response.num_prompt_tokens;
+        let ms = |latency: Option<Duration>| latency.unwrap_or_default().as_secs_f64() * 1000.0;
+        self.time_to_first_token
+            .record(ms(response.time_to_first_token()));
+        self.inter_token_latency
+            .record(ms(response.inter_token_latency()));
+        self.e2e_latency.record(ms(response.e2e_latency()));
+        if let Some(e2e) = response.e2e_latency().map(|e2e| e2e.as_secs_f64()) {
+            if e2e > 0.0 {
+                self.output_tokens_per_sec
+                    .record(response.num_generated_tokens as f64 / e2e);
+            }
+        }
+    }
+
+    pub(crate) fn successful_requests(&self) -> u64 {
+        self.total_requests - self.failed_requests
+    }
+
+    /// Metrics over time of a step lasting `duration`, if collected
+    pub(crate) fn time_series(&self, duration: Duration) -> Option<Vec<TimeSeriesPoint>> {
+        self.time_series
+            .as_ref()
+            .map(|time_series| time_series.points(duration))
+    }
+
+    /// Histogram of a per-request metric over the successful requests, latencies in milliseconds
+    pub(crate) fn metric(&self, metric: RequestMetric) -> &MetricHistogram {
+        match metric {
+            RequestMetric::TimeToFirstToken => &self.time_to_first_token,
+            RequestMetric::InterTokenLatency => &self.inter_token_latency,
+            RequestMetric::E2eLatency => &self.e2e_latency,
+            RequestMetric::OutputTokensPerSec => &self.output_tokens_per_sec,
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::labels;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Shell commands (or webhook URLs) run around the benchmark and each of its stages
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HooksConfig {
+    pub pre_run: Option<String>,
+    pub post_run: Option<String>,
+    pub pre_stage: Option<String>,
+    pub post_stage: Option<String>,
+    pub heartbeat: Option<String>,
+    /// Hooks running longer are killed and recorded as failed, no limit if not set
+    #[serde(rename = "timeout_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub timeout: Option<Duration>,
+}
+
+impl HooksConfig {
+    pub fn command(&self, kind: HookKind) -> Option<&String> {
+        match kind {
+            HookKind::PreRun => self.pre_run.as_ref(),
+            HookKind::PostRun => self.post_run.as_ref(),
+            HookKind::PreStage => self.pre_stage.as_ref(),
+            HookKind::PostStage => self.post_stage.as_ref(),
+            HookKind::Heartbeat => self.heartbeat.as_ref(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, strum_macros::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum HookKind {
+    PreRun,
+    PostRun,
+    PreStage,
+    PostStage,
+    Heartbeat,
+}
+
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub struct HookOutput {
+    pub kind: HookKind,
+    pub stage: Option<String>,
+    pub stage_id: Option<String>,
+    pub command: String,
+    pub success: bool,
+    /// Process exit code, or HTTP status for webhooks
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    #[serde(rename = "duration_ms")]
+    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
+    pub duration: Duration,
+}
+
+/// Run and stage a hook runs for
+#[derive(Clone, Copy, Debug)]
+pub struct HookContext<'a> {
+    pub run_id: &'a str,
+    pub stage: Option<&'a str>,
+    pub stage_id: Option<&'a str>,
+    pub labels: &'a BTreeMap<String, String>,
+}
+
+/// Run a hook and capture its output. Commands starting with `http://` or `https://` are
+/// called as webhooks with a JSON POST, anything else is run with `sh -c`.
+/// The hook kind, run id, stage and stage id are exposed as `BENCHMARK_HOOK`, `BENCHMARK_RUN_ID`,
+/// `BENCHMARK_STAGE` and `BENCHMARK_STAGE_ID` env variables, and the stage labels as
+/// `BENCHMARK_LABEL_<KEY>` ones (`labels` in webhook bodies). The fields of the optional JSON
+/// payload are added to the webhook body, or passed as `BENCHMARK_HOOK_PAYLOAD` to commands.
+/// Hooks still running after `timeout` are killed (or their request dropped) and fail.
+pub async fn run_hook(
+    kind: HookKind,
+    command: &str,
+    timeout: Option<Duration>,
+    context: HookContext<'_>,
+    payload: Option<&serde_json::Value>,
+) -> HookOutput {
+    let start = std::time::Instant::now();
+    let mut output = HookOutput {
+        kind,
+        stage: context.stage.map(|s| s.to_string()),
+        stage_id: context.stage_id.map(|s| s.to_string()),
+        command: command.to_string(),
+        success: false,
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        duration: Duration::default(),
+    };
+    let run = run_command(&mut output, command, context, payload);
+    match timeout {
+        Some(timeout) => {
+            if tokio::time::timeout(timeout, run).await.is_err() {
+                output.success = false;
+                output.stderr = format!("Hook timed out after {timeout:?}");
+            }
+        }
+        None => run.await,
+    }
+    output.duration = start.elapsed();
+    output
+}
+
+async fn run_command(
+    output: &mut HookOutput,
+    command: &str,
+    context: HookContext<'_>,
+    payload: Option<&serde_json::Value>,
+) {
+    let HookContext {
+        run_id,
+        stage,
+        stage_id,
+        labels,
+    } = context;
+    let kind = output.kind;
+    if command.starts_with("http://") || command.starts_with("https://") {
+        let mut body = serde_json::json!({
+            "hook": kind,
+            "run_id": run_id,
+            "stage": stage,
+            "stage_id": stage_id,
+            "labels": labels,
+        });
+        if let (Some(body), Some(serde_json::Value::Object(payload))) =
+            (body.as_object_mut(), payload)
+        {
+            body.extend(payload.clone());
+        }
+        match reqwest::Client::new()
+            .post(command)
+            .json(&body)
+            .send()
+            .await
+        {
+            Ok(response) => {
+                output.success = response.status().is_success();
+                output.exit_code = Some(response.status().as_u16() as i32);
+                output.stdout = response.text().await.unwrap_or_default();
+            }
+            Err(e) => output.stderr = e.to_string(),
+        }
+    } else {
+        match Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("BENCHMARK_HOOK", kind.to_string())
+            .env("BENCHMARK_RUN_ID", run_id)
+            .env("BENCHMARK_STAGE", stage.unwrap_or_default())
+            .env("BENCHMARK_STAGE_ID", stage_id.unwrap_or_default())
+            .env(
+                "BENCHMARK_HOOK_PAYLOAD",
+                payload.map(|p| p.to_string()).unwrap_or_default(),
+            )
+            .envs(
+                labels
+                    .iter()
+                    .map(|(key, value)| (labels::env_var(key), value)),
+            )
+            // killed when the hook times out
+            .kill_on_drop(true)
+            .output()
+            .await
+        {
+            Ok(result) => {
+                output.success = result.status.success();
+                output.exit_code = result.status.code();
+                output.stdout = String::from_utf8_lossy(&result.stdout).to_string();
+                output.stderr = String::from_utf8_lossy(&result.stderr).to_string();
+            }
+            Err(e) => output.stderr = e.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_hook() {
+        let labels = BTreeMap::from([("kv-cache".to_string(), "on".to_string())]);
+        let context = HookContext {
+            run_id: "run",
+            stage: Some("warmup"),
+            stage_id: Some("00-warmup"),
+            labels: &labels,
+        };
+        let output = run_hook(
+            HookKind::PreStage,
+            "echo $BENCHMARK_HOOK $BENCHMARK_RUN_ID $BENCHMARK_STAGE $BENCHMARK_STAGE_ID $BENCHMARK_LABEL_KV_CACHE",
+            None,
+            context,
+            None,
+        )
+        .await;
+        assert!(output.success);
+        assert_eq!(output.stdout, "pre_stage run warmup 00-warmup on\n");
+        let no_labels = BTreeMap::new();
+        let run = HookContext {
+            run_id: "run",
+            stage: None,
+            stage_id: None,
+            labels: &no_labels,
+        };
+        let output = run_hook(HookKind::PostRun, "exit 3", None, run, None).await;
+        assert!(!output.success);
+        assert_eq!(output.exit_code, Some(3));
+        let timeout = Some(Duration::from_millis(100));
+        let output = run_hook(HookKind::PostRun, "sleep 10", timeout, run, None).await;
+        assert!(!output.success);
+        assert!(output.stderr.contains("timed out"));
+        assert!(output.duration < Duration::from_secs(5));
+        let payload = serde_json::json!({"successful_requests": 3});
+        let context = HookContext {
+            stage: Some("warmup"),
+            ..run
+        };
+        let output = run_hook(
+            HookKind::Heartbeat,
+            "echo $BENCHMARK_HOOK $BENCHMARK_HOOK_PAYLOAD",
+            None,
+            context,
+            Some(&payload),
+        )
+        .await;
+        assert_eq!(output.stdout, "heartbeat {\"successful_requests\":3}\n");
+    }
+}
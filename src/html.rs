@@ -0,0 +1,331 @@
+use serde_json::Value;
+
+const COLORS: [&str; 8] = [
+    "#4682b4", "#e4572e", "#29a36a", "#9b5de5", "#f2a541", "#2ec4b6", "#7f7f7f", "#c0396b",
+];
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 360.0;
+const MARGIN: f64 = 60.0;
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Line of a chart, points sorted by x
+struct Series {
+    name: String,
+    points: Vec<(f64, f64)>,
+}
+
+/// SVG line chart of the series, with labeled ticks on the x axis
+fn line_chart(
+    title: &str,
+    x_label: &str,
+    y_label: &str,
+    ticks: &[(f64, String)],
+    series: &[Series],
+) -> String {
+    let xs = || {
+        series
+            .iter()
+            .flat_map(|s| s.points.iter().map(|p| p.0))
+            .chain(ticks.iter().map(|t| t.0))
+    };
+    let min_x = xs().fold(f64::INFINITY, f64::min);
+    let max_x = xs().fold(f64::NEG_INFINITY, f64::max);
+    let span_x = if max_x > min_x { max_x - min_x } else { 1.0 };
+    let max_y = series
+        .iter()
+        .flat_map(|s| s.points.iter().map(|p| p.1))
+        .fold(0.0, f64::max);
+    let max_y = if max_y > 0.0 { max_y * 1.1 } else { 1.0 };
+    let x = |v: f64| MARGIN + (v - min_x) / span_x * (WIDTH - 2.0 * MARGIN);
+    let y = |v: f64| HEIGHT - MARGIN - v / max_y * (HEIGHT - 2.0 * MARGIN);
+    let bottom = HEIGHT - MARGIN;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" font-family=\"sans-serif\" font-size=\"12\">\n\
+         <text x=\"{cx}\" y=\"20\" text-anchor=\"middle\" font-weight=\"bold\">{title}</text>\n\
+         <line x1=\"{MARGIN}\" y1=\"{bottom}\" x2=\"{right}\" y2=\"{bottom}\" stroke=\"black\"/>\n\
+         <line x1=\"{MARGIN}\" y1=\"{MARGIN}\" x2=\"{MARGIN}\" y2=\"{bottom}\" stroke=\"black\"/>\n\
+         <text x=\"{cx}\" y=\"{x_label_y}\" text-anchor=\"middle\">{x_label}</text>\n\
+         <text x=\"15\" y=\"{cy}\" text-anchor=\"middle\" transform=\"rotate(-90 15 {cy})\">{y_label}</text>\n\
+         <text x=\"{y_tick_x}\" y=\"{top}\" text-anchor=\"end\">{max_y:.1}</text>\n\
+         <text x=\"{y_tick_x}\" y=\"{bottom}\" text-anchor=\"end\">0</text>\n",
+        title = escape(title),
+        x_label = escape(x_label),
+        y_label = escape(y_label),
+        right = WIDTH - MARGIN,
+        cx = WIDTH / 2.0,
+        cy = HEIGHT / 2.0,
+        top = y(max_y) + 4.0,
+        x_label_y = HEIGHT - MARGIN / 4.0,
+        y_tick_x = MARGIN - 4.0,
+    );
+    for (value, label) in ticks {
+        svg += &format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+            x(*value),
+            bottom + 16.0,
+            escape(label)
+        );
+    }
+    for (i, s) in series.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let points = s
+            .points
+            .iter()
+            .map(|(px, py)| format!("{:.1},{:.1}", x(*px), y(*py)))
+            .collect::<Vec<_>>();
+        svg += &format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"2\"/>\n",
+            points.join(" ")
+        );
+        for (px, py) in &s.points {
+            svg += &format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{color}\"><title>{}: {py:.2}</title></circle>\n",
+                x(*px),
+                y(*py),
+                escape(&s.name)
+            );
+        }
+        svg += &format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" fill=\"{color}\">{}</text>\n",
+            WIDTH - MARGIN + 4.0,
+            MARGIN + 14.0 * i as f64,
+            escape(&s.name)
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+/// Steps of the report, warmup excluded
+fn steps(report: &Value) -> impl Iterator<Item = &Value> {
+    report["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|r| r["id"] != "warmup")
+}
+
+/// Token throughput against the rate of the constant arrival rate steps, or against the VUs of
+/// the constant VUs steps without any
+fn throughput_chart(report: &Value) -> Option<String> {
+    let by_rate = steps(report).any(|r| r["config"]["rate"].is_number());
+    let load = |r: &Value| {
+        if by_rate {
+            r["config"]["rate"].as_f64()
+        } else if r["executor_type"] == "constant_vus" {
+            r["config"]["max_vus"].as_f64()
+        } else {
+            None
+        }
+    };
+    let mut tokens = steps(report)
+        .filter_map(|r| Some((load(r)?, r["token_throughput_secs"].as_f64()?)))
+        .collect::<Vec<_>>();
+    tokens.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if tokens.len() < 2 {
+        return None;
+    }
+    let x_label = if by_rate {
+        "Request rate sent (req/s)"
+    } else {
+        "Virtual users"
+    };
+    let ticks = tokens
+        .iter()
+        .map(|(x, _)| (*x, format!("{x:.1}")))
+        .collect::<Vec<_>>();
+    Some(line_chart(
+        "Throughput",
+        x_label,
+        "Token throughput (tokens/s)",
+        &ticks,
+        &[Series {
+            name: "tokens/s".to_string(),
+            points: tokens,
+        }],
+    ))
+}
+
+/// Percentiles of a per-request metric, one line per step
+fn percentiles_chart(report: &Value, metric: &str, title: &str) -> Option<String> {
+    let mut percentiles = Vec::new();
+    let series = steps(report)
+        .filter_map(|r| {
+            let values = r["request_percentiles"][metric].as_object()?;
+            let mut points = values
+                .iter()
+                .filter_map(|(key, value)| {
+                    Some((key.strip_prefix('p')?.parse::<f64>().ok()?, value.as_f64()?))
+                })
+                .collect::<Vec<_>>();
+            points.sort_by(|a, b| a.0.total_cmp(&b.0));
+            for (p, _) in &points {
+                if !percentiles.contains(p) {
+                    percentiles.push(*p);
+                }
+            }
+            Some(Series {
+                name: r["id"].as_str()?.to_string(),
+                points,
+            })
+        })
+        .collect::<Vec<_>>();
+    if series.is_empty() {
+        return None;
+    }
+    percentiles.sort_by(|a, b| a.total_cmp(b));
+    // evenly spaced percentiles, p99 and p99.9 would be squeezed on a linear axis
+    let index = |p: f64| percentiles.iter().position(|q| *q == p).unwrap_or_default() as f64;
+    let series = series
+        .into_iter()
+        .map(|s| Series {
+            name: s.name,
+            points: s.points.into_iter().map(|(p, v)| (index(p), v)).collect(),
+        })
+        .collect::<Vec<_>>();
+    let ticks = percentiles
+        .iter()
+        .map(|p| (index(*p), format!("p{p}")))
+        .collect::<Vec<_>>();
+    Some(line_chart(title, "Percentile", title, &ticks, &series))
+}
+
+fn summary_table(report: &Value) -> String {
+    let number = |v: &Value| v.as_f64().map_or("N/A".to_string(), |v| format!("{v:.2}"));
+    let header = [
+        "Benchmark",
+        "QPS",
+        "Throughput (tokens/s)",
+        "TTFT p50 (ms)",
+        "ITL p50 (ms)",
+        "E2E Latency p99 (ms)",
+        "Failed requests",
+    ]
+    .iter()
+    .map(|h| format!("<th>{h}</th>"))
+    .collect::<String>();
+    let rows = report["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|r| {
+            let cells = [
+                escape(r["id"].as_str().unwrap_or_default()),
+                number(&r["request_rate"]),
+                number(&r["token_throughput_secs"]),
+                number(&r["time_to_first_token_ms"]["p50"]),
+                number(&r["inter_token_latency_ms"]["p50"]),
+                number(&r["e2e_latency_ms"]["p99"]),
+                r["failed_requests"].to_string(),
+            ];
+            let cells = cells
+                .iter()
+                .map(|c| format!("<td>{c}</td>"))
+                .collect::<String>();
+            format!("<tr>{cells}</tr>\n")
+        })
+        .collect::<String>();
+    format!("<table>\n<tr>{header}</tr>\n{rows}</table>\n")
+}
+
+/// Self-contained HTML page of a JSON benchmark report: its results table, hints and charts of
+/// throughput against the load and of the per-request latency percentiles of each step, inlined
+/// as SVG so the file can be shared alone
+pub fn report_html(report: &Value) -> String {
+    let name = report["config"]["name"]
+        .as_str()
+        .or(report["config"]["tokenizer"].as_str())
+        .unwrap_or("benchmark");
+    let mut body = format!("<h1>{}</h1>\n", escape(name));
+    if let Some(run_id) = report["run_id"].as_str() {
+        body += &format!("<p>Run <code>{}</code>", escape(run_id));
+        if let (Some(start), Some(end)) =
+            (report["start_time"].as_str(), report["end_time"].as_str())
+        {
+            body += &format!(", from {} to {}", escape(start), escape(end));
+        }
+        body += "</p>\n";
+    }
+    body += "<h2>Results</h2>\n";
+    body += &summary_table(report);
+    let hints = report["hints"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|h| h["message"].as_str())
+        .map(|message| format!("<li>{}</li>\n", escape(message)))
+        .collect::<String>();
+    if !hints.is_empty() {
+        body += &format!("<h2>Hints</h2>\n<ul>\n{hints}</ul>\n");
+    }
+    let charts = [
+        throughput_chart(report),
+        percentiles_chart(report, "time_to_first_token_ms", "Time to first token (ms)"),
+        percentiles_chart(report, "inter_token_latency_ms", "Inter-token latency (ms)"),
+        percentiles_chart(report, "e2e_latency_ms", "E2E latency (ms)"),
+    ];
+    let charts = charts.into_iter().flatten().collect::<String>();
+    if !charts.is_empty() {
+        body += &format!("<h2>Charts</h2>\n{charts}");
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+         body {{ font-family: sans-serif; margin: 2em; }}\n\
+         table {{ border-collapse: collapse; margin-bottom: 1em; }}\n\
+         th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: right; }}\n\
+         svg {{ display: block; margin: 1em 0; }}\n\
+         </style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(name)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_html() {
+        let step = |id: &str, rate: f64, throughput: f64| {
+            serde_json::json!({
+                "id": id,
+                "executor_type": "constant_arrival_rate",
+                "config": {"rate": rate, "max_vus": 100},
+                "request_rate": rate,
+                "token_throughput_secs": throughput,
+                "failed_requests": 0,
+                "time_to_first_token_ms": {"p50": 100.0},
+                "inter_token_latency_ms": {"p50": 20.0},
+                "e2e_latency_ms": {"p99": 1000.0},
+                "request_percentiles": {
+                    "e2e_latency_ms": {"p50": 800.0, "p99": 1000.0, "p99.9": 1200.0, "max": 1500.0, "avg": 850.0},
+                },
+            })
+        };
+        let report = serde_json::json!({
+            "config": {"name": "<llama>", "tokenizer": "gpt2"},
+            "run_id": "abc",
+            "results": [step("warmup", 1.0, 10.0), step("rate@2", 2.0, 200.0), step("rate@1", 1.0, 100.0)],
+            "hints": [{"kind": "throughput_plateau", "message": "Throughput plateaus"}],
+        });
+        let html = report_html(&report);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>&lt;llama&gt;</h1>"));
+        assert!(html.contains("<li>Throughput plateaus</li>"));
+        // throughput and E2E latency percentiles charts, the warmup excluded
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert!(html.contains("Request rate sent (req/s)"));
+        assert!(html.contains(">p99.9</text>"));
+        assert_eq!(html.matches("<title>warmup").count(), 0);
+        assert_eq!(html.matches("<title>rate@1").count(), 3);
+        // no external resources
+        assert!(!html.contains("src="));
+        assert!(!html.contains("href="));
+    }
+}
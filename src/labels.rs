@@ -0,0 +1,131 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Labels attached to the stages whose name or id matches a pattern, e.g.
+/// `constant@*:quantization=fp8,kv_cache=on`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StageLabels {
+    /// Stage name or id, `*` matching any characters
+    pub stage: String,
+    pub labels: BTreeMap<String, String>,
+}
+
+impl FromStr for StageLabels {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            anyhow::anyhow!("Invalid stage labels: {s}, expected `stage:key=value[,key=value...]`")
+        };
+        let (stage, labels) = s.split_once(':').ok_or_else(invalid)?;
+        let labels = labels
+            .split(',')
+            .map(|label| match label.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    Ok((key.trim().to_string(), value.trim().to_string()))
+                }
+                _ => Err(invalid()),
+            })
+            .collect::<anyhow::Result<_>>()?;
+        if stage.trim().is_empty() {
+            return Err(invalid());
+        }
+        Ok(StageLabels {
+            stage: stage.trim().to_string(),
+            labels,
+        })
+    }
+}
+
+/// Labels of the stage named `name`, with id `stage_id` if known. Later entries override the
+/// labels of earlier ones.
+pub fn stage_labels(
+    all: &[StageLabels],
+    name: &str,
+    stage_id: Option<&str>,
+) -> BTreeMap<String, String> {
+    all.iter()
+        .filter(|labels| {
+            glob_match(&labels.stage, name)
+                || stage_id.is_some_and(|id| glob_match(&labels.stage, id))
+        })
+        .flat_map(|labels| labels.labels.clone())
+        .collect()
+}
+
+/// Name of the environment variable exposing a label to hooks, e.g. `BENCHMARK_LABEL_KV_CACHE`
+pub fn env_var(key: &str) -> String {
+    let key = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("BENCHMARK_LABEL_{key}")
+}
+
+/// Whether `text` matches `pattern` in full, `*` matching any (possibly empty) sequence
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        // no wildcard
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_labels() {
+        assert!(glob_match("constant@*", "constant@1.20req/s"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("0*-*@*s", "02-constant@1.20req/s"));
+        assert!(!glob_match("constant@*", "warmup"));
+        assert!(!glob_match("warmup", "warmup2"));
+        assert!(!glob_match("*ab*ab", "ab"));
+
+        let all = [
+            "*:campaign=kv-cache".parse::<StageLabels>().unwrap(),
+            "constant@*:kv_cache=on, quantization = fp8"
+                .parse()
+                .unwrap(),
+            "01-*:kv_cache=off".parse().unwrap(),
+        ];
+        assert_eq!(
+            all[1].labels,
+            BTreeMap::from([
+                ("kv_cache".to_string(), "on".to_string()),
+                ("quantization".to_string(), "fp8".to_string()),
+            ])
+        );
+        let labels = stage_labels(&all, "constant@1.20req/s", Some("01-constant@1.20req/s"));
+        assert_eq!(labels["campaign"], "kv-cache");
+        assert_eq!(labels["kv_cache"], "off");
+        assert_eq!(labels["quantization"], "fp8");
+        assert_eq!(stage_labels(&all, "warmup", None).len(), 1);
+
+        assert!("constant@*".parse::<StageLabels>().is_err());
+        assert!("constant@*:=on".parse::<StageLabels>().is_err());
+        assert!(":kv_cache=on".parse::<StageLabels>().is_err());
+        assert_eq!(env_var("kv-cache"), "BENCHMARK_LABEL_KV_CACHE");
+    }
+}
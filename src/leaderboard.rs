@@ -0,0 +1,374 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tabled::builder::Builder;
+
+#[derive(Clone, Debug)]
+pub enum LeaderboardRanking {
+    /// Best token throughput reached by any benchmark step
+    Throughput,
+    /// Max request rate before latency grows disproportionately
+    RecommendedRate,
+    /// Best rate of requests meeting the request SLO reached by any benchmark step
+    Goodput,
+    /// Lowest cost per million generated tokens at the best throughput
+    Cost,
+}
+
+/// Metadata label of the hourly cost of the benchmarked deployment, e.g.
+/// `--extra-meta "cost_per_hour=2.5"`
+const COST_LABEL: &str = "cost_per_hour";
+
+pub struct LeaderboardConfiguration {
+    pub results_dir: PathBuf,
+    pub rank_by: String,
+    pub format: String,
+    pub model: Option<String>,
+    pub labels: HashMap<String, String>,
+    pub output: Option<PathBuf>,
+}
+
+// subset of the report written by `BenchmarkReportWriter`
+#[derive(Deserialize)]
+struct ReportFile {
+    config: ReportConfig,
+    // absent from reports written before it was introduced
+    config_hash: Option<String>,
+    results: Vec<ReportResults>,
+    recommended_operating_point: Option<ReportOperatingPoint>,
+}
+
+#[derive(Deserialize)]
+struct ReportConfig {
+    name: Option<String>,
+    tokenizer: String,
+    meta: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+struct ReportResults {
+    id: String,
+    token_throughput_secs: f64,
+    e2e_latency_ms: ReportPercentiles,
+    // only set with a request SLO
+    goodput: Option<ReportGoodput>,
+}
+
+#[derive(Deserialize)]
+struct ReportGoodput {
+    goodput: f64,
+}
+
+#[derive(Deserialize)]
+struct ReportPercentiles {
+    p99: f64,
+}
+
+#[derive(Deserialize)]
+struct ReportOperatingPoint {
+    rate: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub model: String,
+    pub labels: HashMap<String, String>,
+    pub token_throughput_secs: f64,
+    pub e2e_latency_p99_ms: f64,
+    pub recommended_rate: Option<f64>,
+    /// Best requests per second meeting the request SLO
+    pub goodput: Option<f64>,
+    /// Cost per million generated tokens at the best throughput, from the hourly cost label
+    pub cost_per_million_tokens: Option<f64>,
+}
+
+impl LeaderboardEntry {
+    fn from_report(path: &Path, report: ReportFile) -> Option<LeaderboardEntry> {
+        let best = report
+            .results
+            .iter()
+            .filter(|r| r.id != "warmup")
+            .max_by(|a, b| {
+                a.token_throughput_secs
+                    .partial_cmp(&b.token_throughput_secs)
+                    .unwrap()
+            })?;
+        let goodput = report
+            .results
+            .iter()
+            .filter(|r| r.id != "warmup")
+            .filter_map(|r| r.goodput.as_ref().map(|g| g.goodput))
+            .reduce(f64::max);
+        let labels = report.config.meta.unwrap_or_default();
+        let cost_per_million_tokens =
+            labels
+                .get(COST_LABEL)
+                .and_then(|cost| match cost.parse::<f64>() {
+                    Ok(cost) if best.token_throughput_secs > 0.0 => {
+                        Some(cost / (best.token_throughput_secs * 3600.0) * 1e6)
+                    }
+                    Ok(_) => None,
+                    Err(e) => {
+                        log::warn!("{:?}: invalid {COST_LABEL} {cost} ({e})", path);
+                        None
+                    }
+                });
+        let name = report.config.name.unwrap_or_else(|| {
+            path.file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+        Some(LeaderboardEntry {
+            name,
+            model: report.config.tokenizer,
+            labels,
+            token_throughput_secs: best.token_throughput_secs,
+            e2e_latency_p99_ms: best.e2e_latency_ms.p99,
+            recommended_rate: report.recommended_operating_point.map(|p| p.rate),
+            goodput,
+            cost_per_million_tokens,
+        })
+    }
+
+    fn score(&self, ranking: &LeaderboardRanking) -> f64 {
+        match ranking {
+            LeaderboardRanking::Throughput => self.token_throughput_secs,
+            LeaderboardRanking::RecommendedRate => self.recommended_rate.unwrap_or_default(),
+            LeaderboardRanking::Goodput => self.goodput.unwrap_or_default(),
+            // cheapest first, entries without a cost last
+            LeaderboardRanking::Cost => self
+                .cost_per_million_tokens
+                .map_or(f64::NEG_INFINITY, |cost| -cost),
+        }
+    }
+
+    fn matches(&self, model: &Option<String>, labels: &HashMap<String, String>) -> bool {
+        model.as_ref().is_none_or(|m| &self.model == m)
+            && labels.iter().all(|(k, v)| self.labels.get(k) == Some(v))
+    }
+}
+
+/// Load all the JSON reports of a directory, skipping files that are not benchmark reports
+pub fn load_entries(dir: &Path) -> anyhow::Result<Vec<LeaderboardEntry>> {
+    let mut entries = Vec::new();
+    let mut hashes: HashMap<String, PathBuf> = HashMap::new();
+    for file in std::fs::read_dir(dir)? {
+        let path = file?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let content = std::fs::read_to_string(&path)?;
+        match serde_json::from_str::<ReportFile>(&content) {
+            Ok(report) => {
+                if let Some(hash) = &report.config_hash {
+                    if let Some(other) = hashes.insert(hash.clone(), path.clone()) {
+                        log::warn!(
+                            "{:?} and {:?} are runs of the same configuration, both are counted",
+                            other,
+                            path
+                        );
+                    }
+                }
+                entries.extend(LeaderboardEntry::from_report(&path, report))
+            }
+            Err(e) => log::warn!("Skipping {:?}: not a benchmark report ({})", path, e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Filter and sort entries, best first
+pub fn rank(
+    entries: Vec<LeaderboardEntry>,
+    ranking: &LeaderboardRanking,
+    model: &Option<String>,
+    labels: &HashMap<String, String>,
+) -> Vec<LeaderboardEntry> {
+    let mut entries = entries
+        .into_iter()
+        .filter(|e| e.matches(model, labels))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| b.score(ranking).partial_cmp(&a.score(ranking)).unwrap());
+    entries
+}
+
+fn rows(entries: &[LeaderboardEntry]) -> Vec<Vec<String>> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let mut labels = e
+                .labels
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect::<Vec<_>>();
+            labels.sort();
+            vec![
+                (i + 1).to_string(),
+                e.name.clone(),
+                e.model.clone(),
+                labels.join(", "),
+                format!("{:.2}", e.token_throughput_secs),
+                format!("{:.2}", e.e2e_latency_p99_ms),
+                e.recommended_rate
+                    .map_or("N/A".to_string(), |r| format!("{:.2}", r)),
+                e.goodput.map_or("N/A".to_string(), |g| format!("{:.2}", g)),
+                e.cost_per_million_tokens
+                    .map_or("N/A".to_string(), |c| format!("{:.2}", c)),
+            ]
+        })
+        .collect()
+}
+
+const HEADER: [&str; 9] = [
+    "Rank",
+    "Benchmark",
+    "Model",
+    "Labels",
+    "Throughput (tokens/s)",
+    "E2E Latency p99 (ms)",
+    "Recommended max rate (req/s)",
+    "Goodput at SLO (req/s)",
+    "Cost ($/M tokens)",
+];
+
+pub fn markdown(entries: &[LeaderboardEntry]) -> String {
+    let mut builder = Builder::default();
+    builder.set_header(HEADER);
+    for row in rows(entries) {
+        builder.push_record(row);
+    }
+    let mut table = builder.build();
+    table.with(tabled::settings::Style::markdown());
+    table.to_string()
+}
+
+pub fn html(entries: &[LeaderboardEntry]) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let header = HEADER
+        .iter()
+        .map(|h| format!("<th>{}</th>", h))
+        .collect::<String>();
+    let rows = rows(entries)
+        .iter()
+        .map(|row| {
+            let cells = row
+                .iter()
+                .map(|c| format!("<td>{}</td>", escape(c)))
+                .collect::<String>();
+            format!("<tr>{cells}</tr>\n")
+        })
+        .collect::<String>();
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Leaderboard</title></head>\n<body>\n<table>\n<tr>{header}</tr>\n{rows}</table>\n</body>\n</html>\n"
+    )
+}
+
+pub fn run_leaderboard(config: LeaderboardConfiguration) -> anyhow::Result<()> {
+    let ranking = match config.rank_by.to_lowercase().as_str() {
+        "throughput" => LeaderboardRanking::Throughput,
+        "recommended-rate" => LeaderboardRanking::RecommendedRate,
+        "goodput" => LeaderboardRanking::Goodput,
+        "cost" => LeaderboardRanking::Cost,
+        _ => return Err(anyhow::anyhow!("Unknown ranking: {}", config.rank_by)),
+    };
+    let entries = load_entries(&config.results_dir)?;
+    let entries = rank(entries, &ranking, &config.model, &config.labels);
+    let content = match config.format.to_lowercase().as_str() {
+        "markdown" => markdown(&entries),
+        "html" => html(&entries),
+        _ => return Err(anyhow::anyhow!("Unknown format: {}", config.format)),
+    };
+    match config.output {
+        Some(path) => std::fs::write(path, content)?,
+        None => println!("{content}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank() {
+        let entry = |name: &str, model: &str, hardware: &str, throughput: f64| LeaderboardEntry {
+            name: name.to_string(),
+            model: model.to_string(),
+            labels: HashMap::from([("hardware".to_string(), hardware.to_string())]),
+            token_throughput_secs: throughput,
+            e2e_latency_p99_ms: 0.0,
+            recommended_rate: None,
+            goodput: None,
+            cost_per_million_tokens: None,
+        };
+        let entries = vec![
+            entry("a", "llama", "h100", 100.0),
+            entry("b", "llama", "a100", 300.0),
+            entry("c", "llama", "h100", 200.0),
+            entry("d", "mistral", "h100", 400.0),
+        ];
+        let ranked = rank(
+            entries,
+            &LeaderboardRanking::Throughput,
+            &Some("llama".to_string()),
+            &HashMap::from([("hardware".to_string(), "h100".to_string())]),
+        );
+        let names = ranked.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["c", "a"]);
+
+        let entries = vec![
+            LeaderboardEntry {
+                goodput: Some(2.0),
+                cost_per_million_tokens: Some(5.0),
+                ..entry("a", "llama", "h100", 100.0)
+            },
+            LeaderboardEntry {
+                goodput: Some(3.0),
+                ..entry("b", "llama", "h100", 300.0)
+            },
+            LeaderboardEntry {
+                cost_per_million_tokens: Some(2.0),
+                ..entry("c", "llama", "h100", 200.0)
+            },
+        ];
+        let names = |ranking| {
+            rank(entries.clone(), &ranking, &None, &HashMap::new())
+                .iter()
+                .map(|e| e.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(LeaderboardRanking::Goodput), ["b", "a", "c"]);
+        assert_eq!(names(LeaderboardRanking::Cost), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_from_report() {
+        let report: ReportFile = serde_json::from_value(serde_json::json!({
+            "config": {"tokenizer": "llama", "meta": {"cost_per_hour": "3.6"}},
+            "results": [
+                {"id": "warmup", "token_throughput_secs": 5000.0, "e2e_latency_ms": {"p99": 1.0}},
+                {
+                    "id": "constant@1.00req/s",
+                    "token_throughput_secs": 1000.0,
+                    "e2e_latency_ms": {"p99": 10.0},
+                    "goodput": {"goodput": 0.8}
+                },
+                {"id": "constant@2.00req/s", "token_throughput_secs": 500.0, "e2e_latency_ms": {"p99": 20.0}}
+            ],
+            "recommended_operating_point": null
+        }))
+        .unwrap();
+        let entry = LeaderboardEntry::from_report(Path::new("run.json"), report).unwrap();
+        assert_eq!(entry.name, "run");
+        assert_eq!(entry.token_throughput_secs, 1000.0);
+        assert_eq!(entry.goodput, Some(0.8));
+        // $3.6 per hour for 3.6M tokens
+        assert!((entry.cost_per_million_tokens.unwrap() - 1.0).abs() < 1e-9);
+    }
+}
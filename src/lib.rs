@@ -1,99 +1,1005 @@
 use std::collections::HashMap;
 use std::fs::File;
+#[cfg(feature = "tui")]
 use std::io;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+#[cfg(feature = "tui")]
 pub use crate::app::run_console;
-pub use crate::benchmark::{BenchmarkConfig, BenchmarkKind};
-use crate::benchmark::{Event, MessageEvent};
-use crate::requests::OpenAITextGenerationBackend;
-pub use crate::requests::TokenizeOptions;
+#[cfg(all(feature = "hub", feature = "datasets"))]
+use crate::benchmark::MessageEvent;
+pub use crate::benchmark::{Benchmark, BenchmarkConfig, BenchmarkKind, Event};
+pub use crate::bundle::{run_export_bundle, BundleConfiguration};
+use crate::comparison::Baseline;
+pub use crate::event::StageSummary;
+pub use crate::flux::{
+    AlertThresholds, Metric, MetricsBus, MetricsSubscriber, RequestSample, Topic,
+};
+pub use crate::format::{LatencyUnit, OutputFormat, ThroughputPrefix};
+pub use crate::hooks::HooksConfig;
+pub use crate::labels::StageLabels;
+pub use crate::leaderboard::{run_leaderboard, LeaderboardConfiguration};
+#[cfg(all(feature = "hub", feature = "datasets"))]
+use crate::otlp::{OtlpExporter, OtlpTracingBackend};
+pub use crate::presets::Preset;
+use crate::processors::builtin_processor;
+pub use crate::processors::{ResponseProcessor, ResponseProcessors};
+#[cfg(feature = "grpc")]
+use crate::requests::GrpcTextGenerationBackend;
+pub use crate::requests::{
+    fetch_max_context_length, load_tokenizer, random_uuid, BackendKind,
+    ConversationTextRequestGenerator, IdempotencyKeyMode, OpenAIEndpoint,
+    OpenAITextGenerationBackend, PrefetchingRequestGenerator, PromptTemplate, RequestOptions,
+    RequestTimeouts, RetryPolicy, Tenant, TextGenerationAggregatedResponse, TextGenerationBackend,
+    TextGenerationRequest, TextRequestGenerator, TokenizeOptions, DEFAULT_FIM_TEMPLATE,
+};
+#[cfg(all(feature = "hub", feature = "datasets"))]
+use crate::requests::{AdaptiveDecodeRequestGenerator, DecodeFeedback, DecodeFeedbackBackend};
+use crate::requests::{
+    RealtimeAudioBackend, RetryingBackend, TenantBackend, TgiTextGenerationBackend,
+    TraceSamplingBackend, TritonTextGenerationBackend, VllmTextGenerationBackend,
+    WebSocketTextGenerationBackend, TRACE_LOG_TARGET,
+};
+use crate::results::AnswerRegexes;
+pub use crate::results::{BenchmarkReport, DEFAULT_PERCENTILES};
+pub use crate::scheduler::{ExecutorType, SchedulerProgress};
+use crate::scripting::RequestScript;
+pub use crate::sink::{ResultsSink, SinkTarget};
+#[cfg(feature = "tui")]
+pub use crate::wizard::{run_wizard, WizardAnswers};
+pub use batch::{BatchResults, BatchRunner};
 use chrono::Local;
+#[cfg(feature = "tui")]
 use crossterm::ExecutableCommand;
-use log::{debug, error, info, warn, Level, LevelFilter};
-use tokenizers::{FromPretrainedParameters, Tokenizer};
+#[cfg(all(feature = "hub", feature = "datasets"))]
+use futures_util::StreamExt;
+use log::{debug, info, warn, LevelFilter};
+#[cfg(all(feature = "hub", feature = "datasets"))]
+use log::{error, Level};
+use serde::Serialize;
+use tokenizers::Tokenizer;
 use tokio::sync::broadcast::Sender;
-use tokio::sync::Mutex;
-use writers::BenchmarkReportWriter;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use writers::find_duplicate;
+pub use writers::{BatchReportWriter, BenchmarkReportWriter, DuplicatePolicy};
 
+mod analysis;
+#[cfg(feature = "tui")]
 mod app;
+mod assertions;
+mod batch;
 mod benchmark;
+mod bundle;
+pub mod cli;
+mod comparison;
+#[cfg(feature = "hub")]
+mod download;
 mod event;
 mod executors;
 mod flux;
+mod format;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod headroom;
+mod histograms;
+mod hooks;
+mod html;
+mod labels;
+mod leaderboard;
+#[cfg(all(feature = "hub", feature = "datasets"))]
+mod limits;
+#[cfg(all(feature = "hub", feature = "datasets"))]
+mod otlp;
+mod pause;
+mod presets;
+mod processors;
 mod requests;
 mod results;
 mod scheduler;
+mod scripting;
+mod server_metrics;
+mod sink;
 mod table;
+mod timeseries;
+#[cfg(feature = "tui")]
+mod wizard;
 mod writers;
 
+const RESULTS_DIR: &str = "results";
+
+/// Complete configuration of a CLI run. Serialized in the report, without the Hugging Face token
+/// and the API key, so the run can be reproduced from its output file.
+#[serde_with::serde_as]
+#[derive(Clone, Serialize)]
 pub struct RunConfiguration {
+    pub benchmark_name: Option<String>,
+    pub description: Option<String>,
+    pub preset: Option<String>,
     pub url: String,
+    pub extra_urls: Vec<String>,
+    /// Server of this endpoint in multi-endpoint runs, set by [`endpoint_configs`]
+    #[serde(skip)]
+    pub endpoint_label: Option<String>,
+    pub parallel_endpoints: usize,
     pub tokenizer_name: String,
     pub max_vus: u64,
+    #[serde(rename = "duration_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
     pub duration: std::time::Duration,
+    pub num_requests: Option<u64>,
     pub rates: Option<Vec<f64>>,
     pub num_rates: u64,
     pub benchmark_kind: String,
+    /// Arrival rate curve of the diurnal benchmark, e.g. `sine:min=1,max=20,period=24h`
+    pub rate_curve: Option<String>,
+    /// Rates and step duration of the step benchmark, e.g. `1,2,4,8@60s`
+    pub step_profile: Option<String>,
+    /// Trace of the replay benchmark, e.g. `trace.jsonl,time_scale=0.5`
+    pub trace: Option<String>,
+    /// Baseline rate and spikes of the burst benchmark, e.g. `base=2,burst=20,duration=10s,period=60s`
+    pub burst_profile: Option<String>,
+    /// Inter-arrival times of rate steps: constant or poisson
+    pub arrival_distribution: String,
+    /// Reaction to the saturation of the client resources: off, warn or cap
+    pub client_headroom: String,
+    /// Deadline of the whole run, remaining stages are skipped past it
+    #[serde(rename = "max_total_duration_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub max_total_duration: Option<std::time::Duration>,
+    /// Soft open files limit the process raises to, the hard limit when not set
+    pub open_files_limit: Option<u64>,
+    #[serde(rename = "warmup_duration_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
     pub warmup_duration: std::time::Duration,
+    pub warmup_cold_requests: u64,
+    #[serde(rename = "stage_warmup_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub stage_warmup: Option<std::time::Duration>,
+    pub shuffle_stages: Option<u64>,
+    pub timeouts: RequestTimeouts,
+    pub retry: RetryPolicy,
+    /// Tenants requests are sent as, e.g. `name=gold,weight=3,api_key=<key>,header=X-Tenant:gold`
+    #[serde(serialize_with = "tenant_specs")]
+    pub tenants: Vec<String>,
+    pub adaptive_decode: bool,
     pub interactive: bool,
     pub prompt_options: Option<TokenizeOptions>,
     pub decode_options: Option<TokenizeOptions>,
     pub dataset: String,
     pub dataset_file: String,
+    pub fim: bool,
+    pub fim_template: String,
+    pub rag: bool,
+    pub rag_passages: u64,
+    pub summarization: bool,
+    pub synthetic_documents: bool,
+    pub summary_ratio: f64,
+    #[serde(skip)]
     pub hf_token: Option<String>,
+    #[serde(skip)]
+    pub api_key: Option<String>,
     pub extra_metadata: Option<HashMap<String, String>>,
     pub model_name: String,
+    pub sample_responses: u64,
+    pub response_channel_capacity: usize,
+    pub repetition_threshold: Option<f64>,
+    pub reading_rate: Option<f64>,
+    pub trace_sample: Option<f64>,
+    pub otlp_endpoint: Option<String>,
+    #[serde(rename = "heartbeat_interval_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub heartbeat_interval: Option<std::time::Duration>,
+    pub max_stored_responses: Option<u64>,
+    #[serde(rename = "time_series_window_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub time_series_window: Option<std::time::Duration>,
+    pub answer_scoring: Option<String>,
+    pub response_processors: Vec<String>,
+    /// Latency limits of the requests counted in the goodput, e.g. `ttft=500ms,itl=50ms`
+    pub request_slo: Option<String>,
+    pub clamp_to_context: bool,
+    pub max_context_length: Option<u64>,
+    #[serde(rename = "vu_stagger_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub vu_stagger: std::time::Duration,
+    pub prefetch_requests: usize,
+    pub prompt_template: Option<String>,
+    pub prompt_uniqueness: String,
+    pub metrics_bus_capacity: usize,
+    pub alerts: AlertThresholds,
+    pub hooks: HooksConfig,
+    /// `stage:key=value[,key=value...]` labels of the stages matching `stage`
+    pub stage_labels: Vec<String>,
+    pub assertions: Vec<String>,
+    pub slo: Vec<String>,
+    pub abort_on_assertion_failure: bool,
+    pub derived_metrics: Vec<String>,
+    /// Percentiles of the per-request metrics, the default ones if empty
+    pub percentiles: Vec<f64>,
+    pub server_metrics_url: Option<String>,
+    pub server_metrics: Option<Vec<String>>,
+    pub vllm_stats: bool,
+    pub endpoint: String,
+    pub tgi_details: bool,
+    pub idempotency_key_header: Option<String>,
+    pub tokenizer_revision: Option<String>,
+    pub tokenizer_fallback: Option<String>,
+    pub prompt_caching: bool,
+    pub compare_streaming: bool,
+    pub token_reconciliation: bool,
+    pub tokenizer_check: bool,
+    pub batch_size: u64,
+    #[serde(rename = "batch_poll_interval_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub batch_poll_interval: std::time::Duration,
+    pub realtime_audio: bool,
+    pub backend: String,
+    #[serde(serialize_with = "header_names")]
+    pub headers: Vec<String>,
+    pub backend_path: Option<String>,
+    pub request_script: Option<std::path::PathBuf>,
+    pub on_duplicate: String,
+    pub raw_results: Option<String>,
+    pub csv_summary: bool,
+    pub parquet: bool,
+    pub baseline: Option<std::path::PathBuf>,
+    pub latency_unit: String,
+    pub throughput_prefix: String,
+    pub decimal_separator: char,
+    pub thousands_separator: Option<char>,
+    pub markdown: bool,
+    pub markdown_output: Option<std::path::PathBuf>,
+    pub html_output: Option<std::path::PathBuf>,
+    pub results_sink: Option<String>,
+    pub results_sink_tags: Option<HashMap<String, String>>,
+    #[serde(skip)]
+    pub results_sink_token: Option<String>,
 }
 
+/// Run a full benchmark as the CLI does: load the tokenizer and the dataset, run the benchmark
+/// with the optional console UI and save the report
+#[cfg(all(feature = "hub", feature = "datasets"))]
 pub async fn run(run_config: RunConfiguration, stop_sender: Sender<()>) -> anyhow::Result<()> {
     info!("Starting benchmark");
-    // set process system limits
-    sysinfo::set_open_files_limit(0);
+    // each VU holds a connection, fail before loading anything if they can't all be opened
+    if let Some(limit) = limits::raise_open_files_limit(run_config.open_files_limit)? {
+        if !run_config.benchmark_kind.eq_ignore_ascii_case("batch") {
+            let endpoints = 1 + run_config.extra_urls.len();
+            let vus = run_config.max_vus * run_config.parallel_endpoints.clamp(1, endpoints) as u64;
+            limits::check_open_files(limit, vus)?;
+        }
+    }
+    // download progress is buffered in the event channel until the UI starts
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
     // initialize tokenizer
-    let params = FromPretrainedParameters {
-        token: run_config.hf_token.clone(),
-        ..Default::default()
-    };
-    let tokenizer =
-        match Tokenizer::from_pretrained(run_config.tokenizer_name.clone(), Some(params)) {
-            Ok(tokenizer) => tokenizer,
-            Err(e) => {
-                return Err(anyhow::anyhow!("Error loading tokenizer: {e}"));
+    let (tokenizer, tokenizer_name) = load_tokenizer(
+        &run_config.tokenizer_name,
+        run_config.tokenizer_revision.clone(),
+        run_config.tokenizer_fallback.clone(),
+        run_config.hf_token.clone(),
+        Some(&tx),
+    )
+    .await?;
+    let tokenizer = Arc::new(tokenizer);
+    let backend = build_backend(&run_config, tokenizer.clone())?;
+    let mut config = benchmark_config(&run_config, tokenizer_name)?;
+    if run_config.clamp_to_context && config.max_context_length.is_none() {
+        config.max_context_length = requests::fetch_max_context_length(
+            &backend,
+            &run_config.tokenizer_name,
+            run_config.hf_token.clone(),
+        )
+        .await;
+        if config.max_context_length.is_none() {
+            warn!("Can't determine the model context length, requests won't be clamped");
+        }
+    }
+    if let Some(trace) = config.trace.as_mut() {
+        requests::synthesize_trace_prompts(trace, &tokenizer)?;
+    }
+    let metrics_bus = MetricsBus::new(config.metrics_bus_capacity);
+    init_logging(run_config.interactive);
+    let mut targets = Vec::new();
+    for endpoint in endpoint_configs(&run_config)? {
+        let mut endpoint_config = benchmark_config(&endpoint, config.tokenizer.clone())?;
+        endpoint_config.max_context_length = config.max_context_length;
+        // endpoints replay the same prompts
+        endpoint_config.trace = config.trace.clone();
+        // the reports of all endpoints share the run id
+        endpoint_config.run_id = config.run_id.clone();
+        if let Some(report_path) = output_path(&endpoint_config, &run_config.on_duplicate)? {
+            targets.push((endpoint, endpoint_config, report_path));
+        }
+    }
+    if targets.is_empty() {
+        return Ok(());
+    }
+    let ui_thread = spawn_ui(
+        run_config.interactive,
+        config.clone(),
+        run_config.alerts.clone(),
+        rx,
+        metrics_bus.clone(),
+        stop_sender.clone(),
+    )?;
+    let mut requests = load_requests(&run_config, tokenizer.clone(), &tx).await?;
+    if let Some(max_context_length) = config.max_context_length {
+        let (clamped, removed) = requests.clamp_to_context(max_context_length)?;
+        let message = format!(
+            "Context length {max_context_length}: {clamped} requests clamped, {removed} prompts too long removed"
+        );
+        info!("{message}");
+        let _ = tx.send(Event::Message(MessageEvent {
+            message,
+            timestamp: chrono::Utc::now(),
+            level: Level::Info,
+        }));
+    }
+    requests.prompt_template = config.prompt_template.clone();
+    // the expected answers are compiled once, an invalid one fails the run before it starts
+    if config.answer_scoring == Some(results::AnswerScoring::Regex) {
+        let answers = requests
+            .requests
+            .iter()
+            .filter_map(|r| r.expected_answer.as_deref());
+        let answer_regexes = AnswerRegexes::compile(answers)?;
+        for (_, endpoint_config, _) in targets.iter_mut() {
+            endpoint_config.answer_regexes = answer_regexes.clone();
+        }
+    }
+
+    if let BenchmarkKind::Batch = config.benchmark_kind {
+        let runner = batch::BatchRunner::new(backend, config.batch_poll_interval, config.duration);
+        let start_time = chrono::Utc::now();
+        let mut writer = None;
+        let mut stop_receiver = stop_sender.subscribe();
+        tokio::select! {
+            results = runner.run(&requests, config.batch_size, tx.clone()) => {
+                match results {
+                    Ok(results) => {
+                        let batch_writer = BatchReportWriter::new(config.clone(), results, start_time);
+                        let path = batch_writer.json(&targets[0].2).await?;
+                        info!("Report saved to {:?}", path);
+                        writer = Some(batch_writer);
+                    }
+                    Err(e) => {
+                        error!("Error running batch benchmark: {:?}", e.to_string());
+                        let _ = tx.send(Event::BenchmarkError(e.to_string()));
+                    }
+                }
             }
+            _ = stop_receiver.recv() => {
+                debug!("Received stop signal, stopping benchmark");
+            }
+        }
+        finish(run_config.interactive, tx, stop_sender, ui_thread).await?;
+        match writer {
+            Some(writer) => writer.stdout()?,
+            None => warn!("No results to report."),
+        }
+        return Ok(());
+    }
+
+    let parallel_endpoints = run_config.parallel_endpoints.max(1);
+    let multiple_endpoints = targets.len() > 1;
+    // futures are built upfront so each one subscribes to the stop signal before any run starts
+    let mut runs = Vec::new();
+    for (endpoint, config, report_path) in targets {
+        let backend = if multiple_endpoints {
+            build_backend(&endpoint, tokenizer.clone())?
+        } else {
+            backend.clone()
         };
-    let tokenizer = Arc::new(tokenizer);
-    let backend = OpenAITextGenerationBackend::try_new(
-        "".to_string(),
+        let url = endpoint.url;
+        let requests = requests.fork();
+        let tx = tx.clone();
+        let metrics_bus = metrics_bus.clone();
+        let stop_sender = stop_sender.clone();
+        let mut stop_receiver = stop_sender.subscribe();
+        let prefetch_requests = run_config.prefetch_requests;
+        runs.push(async move {
+            if multiple_endpoints {
+                let message = format!("Benchmarking {url}");
+                info!("{message}");
+                let _ = tx.send(Event::Message(MessageEvent {
+                    message,
+                    timestamp: chrono::Utc::now(),
+                    level: Level::Info,
+                }));
+            }
+            let mut backend = streaming_backend(&config, &url, backend)?;
+            // spans cover the retries of a request, as its final response
+            let exporter = config
+                .otlp_endpoint
+                .as_deref()
+                .map(|endpoint| OtlpExporter::spawn(endpoint, &config.run_id, &url));
+            if let Some(exporter) = &exporter {
+                backend = Box::new(OtlpTracingBackend::new(backend, exporter.clone()));
+            }
+            let mut requests: Arc<dyn TextRequestGenerator> = match prefetch_requests {
+                0 => Arc::new(requests),
+                capacity => Arc::new(PrefetchingRequestGenerator::new(requests, capacity)),
+            };
+            if config.adaptive_decode {
+                // decode targets are adjusted on dispatch, after prefetching
+                let feedback = Arc::new(DecodeFeedback::default());
+                backend = Box::new(DecodeFeedbackBackend::new(backend, feedback.clone()));
+                requests = Arc::new(AdaptiveDecodeRequestGenerator::new(requests, feedback));
+            }
+            let mut benchmark = Benchmark::new(
+                config.clone(),
+                backend,
+                requests,
+                tx.clone(),
+                metrics_bus,
+                stop_sender,
+            );
+            tokio::select! {
+                report = benchmark.run() => {
+                    match report {
+                        Ok(_) => {
+                            save_report(&config, benchmark.get_report(), &report_path).await?;
+                        },
+                        Err(e) => {
+                            error!("Error running benchmark: {:?}", e.to_string());
+                            let _ = tx.send(Event::BenchmarkError(e.to_string()));
+                        }
+                    };
+                }
+                _ = stop_receiver.recv() => {
+                    debug!("Received stop signal, stopping benchmark");
+                }
+            }
+            if let Some(exporter) = exporter {
+                exporter.flush().await;
+            }
+            anyhow::Ok((
+                url,
+                BenchmarkReportWriter::try_new(config, benchmark.get_report()).ok(),
+            ))
+        });
+    }
+    let results = futures_util::stream::iter(runs)
+        .buffered(parallel_endpoints)
+        .collect::<Vec<_>>()
+        .await;
+    finish(run_config.interactive, tx, stop_sender, ui_thread).await?;
+
+    for result in results {
+        let (url, writer) = result?;
+        if multiple_endpoints {
+            println!("\nResults for {url}");
+        }
+        match writer {
+            Some(writer) => {
+                writer.stdout().await?;
+            }
+            None => {
+                warn!("No results to report.");
+            }
+        };
+    }
+
+    Ok(())
+}
+
+/// Per-endpoint run configurations: the main URL followed by the extra ones, each benchmarked with
+/// its own schedulers and report. Endpoints run in parallel must be on distinct hosts and fit in
+/// the client CPUs, so the client and its network link don't become a shared bottleneck.
+pub fn endpoint_configs(run_config: &RunConfiguration) -> anyhow::Result<Vec<RunConfiguration>> {
+    if run_config.extra_urls.is_empty() {
+        return Ok(vec![run_config.clone()]);
+    }
+    if run_config.benchmark_kind.eq_ignore_ascii_case("batch") {
+        return Err(anyhow::anyhow!(
+            "batch benchmarks run against a single URL, extra URLs are not supported"
+        ));
+    }
+    let urls = std::iter::once(&run_config.url)
+        .chain(run_config.extra_urls.iter())
+        .collect::<Vec<_>>();
+    if run_config.parallel_endpoints > 1 {
+        if run_config.interactive {
+            return Err(anyhow::anyhow!(
+                "benchmarking endpoints in parallel requires --no-console"
+            ));
+        }
+        let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+        if run_config.parallel_endpoints > cpus {
+            return Err(anyhow::anyhow!(
+                "{} parallel endpoints exceed the {cpus} client CPUs, the client would be the bottleneck",
+                run_config.parallel_endpoints
+            ));
+        }
+        let mut hosts = std::collections::HashSet::new();
+        for url in urls.iter() {
+            if !hosts.insert(endpoint_host(url)) {
+                return Err(anyhow::anyhow!(
+                    "{url} shares its host with another endpoint, benchmark them sequentially"
+                ));
+            }
+        }
+    }
+    // endpoints sharing a host are told apart by their index
+    let mut labels = std::collections::HashSet::new();
+    Ok(urls
+        .into_iter()
+        .enumerate()
+        .map(|(index, url)| {
+            let mut label = endpoint_host(url);
+            if !labels.insert(label.clone()) {
+                label = format!("{label}_{index}");
+            }
+            RunConfiguration {
+                url: url.clone(),
+                extra_urls: Vec::new(),
+                endpoint_label: Some(label),
+                ..run_config.clone()
+            }
+        })
+        .collect())
+}
+
+/// `host:port` of an endpoint URL
+fn endpoint_host(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|parsed| {
+            let host = parsed.host_str()?.to_string();
+            Some(format!("{host}:{}", parsed.port_or_known_default()?))
+        })
+        .unwrap_or_else(|| url.to_string())
+}
+
+/// Build the HTTP backend for the configured server, model and request options
+pub fn build_backend(
+    run_config: &RunConfiguration,
+    tokenizer: Arc<Tokenizer>,
+) -> anyhow::Result<OpenAITextGenerationBackend> {
+    OpenAITextGenerationBackend::try_new(
+        run_config.api_key.clone().unwrap_or_default(),
         run_config.url.clone(),
         run_config.model_name.clone(),
         tokenizer,
         run_config.duration,
-    )?;
+        RequestOptions {
+            endpoint: run_config.endpoint.parse()?,
+            path: run_config.backend_path.clone(),
+            headers: run_config
+                .headers
+                .iter()
+                .map(|header| parse_header(header))
+                .collect::<anyhow::Result<_>>()?,
+            tgi_details: run_config.tgi_details,
+            idempotency_key_header: run_config.idempotency_key_header.clone(),
+            prompt_caching: run_config.prompt_caching,
+            script: run_config
+                .request_script
+                .as_deref()
+                .map(RequestScript::load)
+                .transpose()?,
+            timeouts: run_config.timeouts,
+            compare_streaming: run_config.compare_streaming,
+            token_reconciliation: run_config.token_reconciliation,
+            tokenizer_check: run_config.tokenizer_check,
+        },
+    )
+}
+
+/// Built-in processors by name, then the request script if it scores responses
+fn response_processors(run_config: &RunConfiguration) -> anyhow::Result<ResponseProcessors> {
+    let mut processors = run_config
+        .response_processors
+        .iter()
+        .map(|name| builtin_processor(name.trim()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if let Some(path) = &run_config.request_script {
+        let script = RequestScript::load(path)?;
+        if script.scores_responses() {
+            processors.push(Arc::new(script));
+        }
+    }
+    Ok(ResponseProcessors::new(processors))
+}
+
+/// Sink the reports are uploaded to, checked before the run
+fn results_sink(run_config: &RunConfiguration) -> anyhow::Result<Option<ResultsSink>> {
+    let Some(target) = &run_config.results_sink else {
+        return Ok(None);
+    };
+    let sink = ResultsSink {
+        target: target.parse()?,
+        tags: run_config
+            .results_sink_tags
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
+        token: run_config.results_sink_token.clone(),
+    };
+    sink.check()?;
+    Ok(Some(sink))
+}
+
+/// Parse a `Name: value` header
+fn parse_header(header: &str) -> anyhow::Result<(String, String)> {
+    match header.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(anyhow::anyhow!(
+            "Invalid header: {header}, expected `key:value`"
+        )),
+    }
+}
+
+/// Only header names are recorded in the report, values may be credentials
+fn header_names<S: serde::Serializer>(
+    headers: &[String],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(headers.iter().map(|header| {
+        header
+            .split_once(':')
+            .map_or(header.as_str(), |(name, _)| name.trim())
+    }))
+}
+
+/// Tenants are recorded as parsed, without API keys nor header values
+fn tenant_specs<S: serde::Serializer>(
+    tenants: &[String],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let tenants = tenants
+        .iter()
+        .map(|tenant| tenant.parse::<Tenant>().map_err(serde::ser::Error::custom))
+        .collect::<Result<Vec<_>, _>>()?;
+    serializer.collect_seq(tenants)
+}
 
+/// Build and validate the benchmark configuration recorded in the report
+pub fn benchmark_config(
+    run_config: &RunConfiguration,
+    tokenizer_name: String,
+) -> anyhow::Result<BenchmarkConfig> {
     let config = BenchmarkConfig {
+        name: run_config.benchmark_name.clone(),
+        description: run_config.description.clone(),
+        endpoint_label: run_config.endpoint_label.clone(),
         max_vus: run_config.max_vus,
         duration: run_config.duration,
+        num_requests: run_config.num_requests,
         benchmark_kind: match run_config.benchmark_kind.to_lowercase().as_str() {
             "throughput" => BenchmarkKind::Throughput,
             "sweep" => BenchmarkKind::Sweep,
             "rate" => BenchmarkKind::Rate,
+            "ramp" => BenchmarkKind::Ramp,
+            "batch" => BenchmarkKind::Batch,
+            "diurnal" => BenchmarkKind::Diurnal,
+            "step" => BenchmarkKind::Step,
+            "replay" => BenchmarkKind::Replay,
+            "burst" => BenchmarkKind::Burst,
+            "concurrency" => BenchmarkKind::ConcurrencySweep,
+            "search" => BenchmarkKind::Search,
             _ => BenchmarkKind::Sweep,
         },
         warmup_duration: run_config.warmup_duration,
-        rates: run_config.rates,
+        warmup_cold_requests: run_config.warmup_cold_requests,
+        stage_warmup: run_config.stage_warmup,
+        shuffle_stages: run_config.shuffle_stages,
+        timeouts: run_config.timeouts,
+        retry: run_config.retry,
+        tenants: run_config
+            .tenants
+            .iter()
+            .map(|tenant| tenant.parse())
+            .collect::<anyhow::Result<_>>()?,
+        adaptive_decode: run_config.adaptive_decode,
+        rate_curve: run_config
+            .rate_curve
+            .as_deref()
+            .map(str::parse)
+            .transpose()?,
+        step_profile: run_config
+            .step_profile
+            .as_deref()
+            .map(str::parse)
+            .transpose()?,
+        trace: run_config.trace.as_deref().map(str::parse).transpose()?,
+        burst_profile: run_config
+            .burst_profile
+            .as_deref()
+            .map(str::parse)
+            .transpose()?,
+        arrival_distribution: run_config.arrival_distribution.parse()?,
+        client_headroom: run_config.client_headroom.parse()?,
+        max_total_duration: run_config.max_total_duration,
+        rates: run_config.rates.clone(),
         num_rates: run_config.num_rates,
         prompt_options: run_config.prompt_options.clone(),
         decode_options: run_config.decode_options.clone(),
-        tokenizer: run_config.tokenizer_name.clone(),
+        endpoint: run_config.endpoint.parse()?,
+        prompt_template: PromptTemplate::try_new(
+            run_config.prompt_template.as_deref(),
+            &run_config.prompt_uniqueness,
+        )?,
+        tokenizer: tokenizer_name,
+        tokenizer_revision: run_config.tokenizer_revision.clone(),
+        sample_responses: run_config.sample_responses,
+        response_channel_capacity: run_config.response_channel_capacity,
+        repetition_threshold: run_config.repetition_threshold,
+        reading_rate: run_config.reading_rate,
+        trace_sample: run_config.trace_sample,
+        heartbeat_interval: run_config.heartbeat_interval,
+        max_stored_responses: run_config.max_stored_responses,
+        time_series_window: run_config.time_series_window,
+        answer_scoring: run_config
+            .answer_scoring
+            .as_deref()
+            .map(str::parse)
+            .transpose()?,
+        answer_regexes: AnswerRegexes::default(),
+        request_slo: run_config
+            .request_slo
+            .as_deref()
+            .map(str::parse)
+            .transpose()?,
+        max_context_length: run_config.max_context_length,
+        vu_stagger: run_config.vu_stagger,
+        metrics_bus_capacity: run_config.metrics_bus_capacity,
+        hooks: run_config.hooks.clone(),
+        // an empty assertion clears the ones of the preset
+        assertions: run_config
+            .assertions
+            .iter()
+            .filter(|assertion| !assertion.trim().is_empty())
+            .map(|assertion| assertion.parse())
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        slo: run_config
+            .slo
+            .iter()
+            .map(|objective| objective.parse())
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        abort_on_assertion_failure: run_config.abort_on_assertion_failure,
+        derived_metrics: run_config
+            .derived_metrics
+            .iter()
+            .map(|metric| metric.parse())
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        percentiles: if run_config.percentiles.is_empty() {
+            DEFAULT_PERCENTILES.to_vec()
+        } else {
+            run_config.percentiles.clone()
+        },
+        server_metrics_url: run_config.server_metrics_url.clone(),
+        server_metrics: run_config.server_metrics.clone(),
+        vllm_stats: run_config.vllm_stats,
+        batch_size: run_config.batch_size,
+        batch_poll_interval: run_config.batch_poll_interval,
+        realtime_audio: run_config.realtime_audio,
+        backend: run_config.backend.parse()?,
         extra_metadata: run_config.extra_metadata.clone(),
+        stage_labels: run_config
+            .stage_labels
+            .iter()
+            .map(|labels| labels.parse())
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        effective_config: Some(serde_json::to_value(run_config)?),
+        run_id: random_uuid(),
+        output_format: OutputFormat {
+            markdown: run_config.markdown,
+            ..OutputFormat::try_new(
+                &run_config.latency_unit,
+                &run_config.throughput_prefix,
+                run_config.decimal_separator,
+                run_config.thousands_separator,
+            )?
+        },
+        raw_results: run_config
+            .raw_results
+            .as_deref()
+            .map(str::parse)
+            .transpose()?,
+        csv_summary: run_config.csv_summary,
+        parquet: run_config.parquet,
+        markdown_output: run_config.markdown_output.clone(),
+        html_output: run_config.html_output.clone(),
+        baseline: run_config
+            .baseline
+            .as_deref()
+            .map(Baseline::load)
+            .transpose()?,
+        otlp_endpoint: run_config.otlp_endpoint.clone(),
+        response_processors: response_processors(run_config)?,
+        results_sink: results_sink(run_config)?,
     };
     config.validate()?;
-    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
-    if run_config.interactive {
-        // send logs to file
+    if matches!(config.benchmark_kind, BenchmarkKind::Batch)
+        && requests::is_websocket_url(&run_config.url)
+    {
+        return Err(anyhow::anyhow!(
+            "batch benchmark requires an HTTP URL, not a WebSocket one"
+        ));
+    }
+    if config.realtime_audio && !requests::is_websocket_url(&run_config.url) {
+        return Err(anyhow::anyhow!(
+            "realtime audio benchmark requires a WebSocket URL (ws:// or wss://)"
+        ));
+    }
+    if config.backend != BackendKind::OpenAI
+        && (config.realtime_audio
+            || requests::is_websocket_url(&run_config.url)
+            || matches!(config.benchmark_kind, BenchmarkKind::Batch)
+            || config.endpoint != OpenAIEndpoint::ChatCompletions)
+    {
+        return Err(anyhow::anyhow!(
+            "the {} backend can't be combined with WebSocket URLs, realtime audio, batch benchmarks or another endpoint",
+            config.backend
+        ));
+    }
+    if run_config.compare_streaming
+        && (config.backend != BackendKind::OpenAI
+            || config.realtime_audio
+            || requests::is_websocket_url(&run_config.url)
+            || requests::is_grpc_url(&run_config.url)
+            || matches!(config.benchmark_kind, BenchmarkKind::Batch))
+    {
+        return Err(anyhow::anyhow!(
+            "comparing streaming requires the OpenAI backend over HTTP, outside of batch benchmarks"
+        ));
+    }
+    if run_config.token_reconciliation
+        && (config.backend != BackendKind::OpenAI
+            || config.realtime_audio
+            || requests::is_websocket_url(&run_config.url)
+            || requests::is_grpc_url(&run_config.url)
+            || matches!(config.benchmark_kind, BenchmarkKind::Batch))
+    {
+        return Err(anyhow::anyhow!(
+            "token reconciliation requires the OpenAI backend over HTTP, outside of batch benchmarks"
+        ));
+    }
+    let timeouts = run_config.timeouts;
+    if [timeouts.connect, timeouts.first_token, timeouts.total]
+        .iter()
+        .flatten()
+        .any(|timeout| timeout.is_zero())
+    {
+        return Err(anyhow::anyhow!("Timeouts must be greater than zero"));
+    }
+    if let (Some(first_token), Some(total)) = (timeouts.first_token, timeouts.total) {
+        if first_token > total {
+            return Err(anyhow::anyhow!(
+                "The TTFT timeout can't exceed the request timeout"
+            ));
+        }
+    }
+    if [run_config.fim, run_config.rag, run_config.summarization]
+        .iter()
+        .filter(|enabled| **enabled)
+        .count()
+        > 1
+    {
+        return Err(anyhow::anyhow!(
+            "FIM, RAG and summarization prompts can't be combined"
+        ));
+    }
+    if run_config.synthetic_documents && !run_config.summarization {
+        return Err(anyhow::anyhow!(
+            "Synthetic documents are only used for summarization prompts"
+        ));
+    }
+    if run_config.summarization && run_config.summary_ratio <= 0.0 {
+        return Err(anyhow::anyhow!(
+            "The summary ratio must be greater than zero"
+        ));
+    }
+    if run_config.rag && run_config.rag_passages == 0 {
+        return Err(anyhow::anyhow!("RAG prompts need at least one passage"));
+    }
+    if run_config.fim {
+        if !run_config.fim_template.contains("{prefix}")
+            || !run_config.fim_template.contains("{suffix}")
+        {
+            return Err(anyhow::anyhow!(
+                "FIM template must contain the {{prefix}} and {{suffix}} variables"
+            ));
+        }
+        if config.backend == BackendKind::OpenAI
+            && config.endpoint == OpenAIEndpoint::ChatCompletions
+        {
+            return Err(anyhow::anyhow!(
+                "FIM prompts are sent raw, use the completions endpoint or a native backend"
+            ));
+        }
+    }
+    if run_config.parquet && !cfg!(feature = "parquet") {
+        return Err(anyhow::anyhow!(
+            "Parquet exports require the `parquet` feature"
+        ));
+    }
+    if requests::is_grpc_url(&run_config.url) {
+        if !cfg!(feature = "grpc") {
+            return Err(anyhow::anyhow!("gRPC URLs require the `grpc` feature"));
+        }
+        if config.backend != BackendKind::Triton {
+            return Err(anyhow::anyhow!("gRPC URLs require the triton backend"));
+        }
+    }
+    Ok(config)
+}
+
+/// Wrap the HTTP backend in the configured server API, with the transport matching the URL scheme
+/// and benchmark mode
+pub fn streaming_backend(
+    config: &BenchmarkConfig,
+    url: &str,
+    backend: OpenAITextGenerationBackend,
+) -> anyhow::Result<Box<dyn TextGenerationBackend + Send + Sync>> {
+    let backend: Box<dyn TextGenerationBackend + Send + Sync> = if !config.tenants.is_empty() {
+        // retries of a request are sent as the same tenant
+        let tenants = config
+            .tenants
+            .iter()
+            .map(|tenant| {
+                Ok((
+                    tenant.clone(),
+                    retrying_backend(config, url, tenant.backend(&backend))?,
+                ))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Box::new(TenantBackend::new(tenants))
+    } else {
+        retrying_backend(config, url, backend)?
+    };
+    // sampled requests are logged with the tenant and retries of their final response
+    Ok(match config.trace_sample {
+        Some(sample_rate) => Box::new(TraceSamplingBackend::new(backend, sample_rate)),
+        None => backend,
+    })
+}
+
+fn retrying_backend(
+    config: &BenchmarkConfig,
+    url: &str,
+    backend: OpenAITextGenerationBackend,
+) -> anyhow::Result<Box<dyn TextGenerationBackend + Send + Sync>> {
+    let backend = server_backend(config, url, backend)?;
+    if config.retry.max_retries == 0 {
+        return Ok(backend);
+    }
+    Ok(Box::new(RetryingBackend::new(backend, config.retry)))
+}
+
+fn server_backend(
+    config: &BenchmarkConfig,
+    url: &str,
+    backend: OpenAITextGenerationBackend,
+) -> anyhow::Result<Box<dyn TextGenerationBackend + Send + Sync>> {
+    #[cfg(feature = "grpc")]
+    if requests::is_grpc_url(url) {
+        return Ok(Box::new(GrpcTextGenerationBackend::try_new(backend)?));
+    }
+    Ok(match config.backend {
+        BackendKind::Tgi => Box::new(TgiTextGenerationBackend::new(backend)),
+        BackendKind::Vllm => Box::new(VllmTextGenerationBackend::new(backend)),
+        BackendKind::Triton => Box::new(TritonTextGenerationBackend::new(backend)),
+        BackendKind::OpenAI if config.realtime_audio => {
+            Box::new(RealtimeAudioBackend::new(backend))
+        }
+        BackendKind::OpenAI if requests::is_websocket_url(url) => {
+            Box::new(WebSocketTextGenerationBackend::new(backend))
+        }
+        BackendKind::OpenAI => Box::new(backend),
+    })
+}
+
+/// Send logs to `log.txt` when the console UI owns the terminal, to stderr otherwise
+pub fn init_logging(interactive: bool) {
+    if interactive {
         let target = Box::new(File::create("log.txt").expect("Can't create file"));
         env_logger::Builder::new()
             .target(env_logger::Target::Pipe(target))
@@ -111,99 +1017,314 @@ pub async fn run(run_config: RunConfiguration, stop_sender: Sender<()>) -> anyho
             })
             .init();
     } else {
-        env_logger::init();
+        // errors only unless RUST_LOG says otherwise, as env_logger::init() does. Sampled
+        // requests are only logged when --trace-sample is set
+        env_logger::Builder::new()
+            .filter_level(LevelFilter::Error)
+            .parse_default_env()
+            .filter(Some(TRACE_LOG_TARGET), LevelFilter::Debug)
+            .init();
     }
-    let config_clone = config.clone();
+}
+
+/// Interval between progress logs when running without the console UI
+const PLAIN_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Spawn the task consuming benchmark events, running the console UI if interactive
+pub fn spawn_ui(
+    interactive: bool,
+    config: BenchmarkConfig,
+    alerts: AlertThresholds,
+    mut rx: UnboundedReceiver<Event>,
+    metrics_bus: MetricsBus,
+    stop_sender: Sender<()>,
+) -> anyhow::Result<JoinHandle<()>> {
+    #[cfg(not(feature = "tui"))]
+    if interactive {
+        return Err(anyhow::anyhow!(
+            "interactive mode requires the `tui` feature"
+        ));
+    }
+    #[cfg(not(feature = "tui"))]
+    let _ = (config, alerts, metrics_bus);
     let mut stop_receiver = stop_sender.subscribe();
-    let stop_sender_clone = stop_sender.clone();
-    let ui_thread = tokio::spawn(async move {
+    Ok(tokio::spawn(async move {
         tokio::select! {
             _ = stop_receiver.recv() => {
                 debug!("Received stop signal, stopping benchmark");
             }
             _ = async{
-                if run_config.interactive {
-                    run_console(config_clone, rx, stop_sender_clone).await;
-                } else {
-                    // consume the channel to avoid closed channel error
-                    while rx.recv().await.is_some() {}
+                #[cfg(feature = "tui")]
+                if interactive {
+                    run_console(config, alerts, rx, metrics_bus, stop_sender).await;
+                    return;
+                }
+                // consume the channel to avoid closed channel error, logging the stage progress
+                let mut last_progress: Option<std::time::Instant> = None;
+                while let Some(event) = rx.recv().await {
+                    if let Event::Heartbeat(heartbeat) = &event {
+                        let message = format!(
+                            "Heartbeat {} ({}): {}s elapsed, {} successful, {} failed, last response {}s ago",
+                            heartbeat.id,
+                            heartbeat.executor_type,
+                            heartbeat.elapsed.as_secs(),
+                            heartbeat.successful_requests,
+                            heartbeat.failed_requests,
+                            heartbeat.since_last_response.as_secs()
+                        );
+                        if heartbeat.stalled {
+                            warn!("{message}, no response since the previous heartbeat");
+                        } else {
+                            info!("{message}");
+                        }
+                    }
+                    if let Event::BenchmarkProgress(progress) = event {
+                        if last_progress.is_some_and(|last| last.elapsed() < PLAIN_PROGRESS_INTERVAL) {
+                            continue;
+                        }
+                        last_progress = Some(std::time::Instant::now());
+                        let target = progress
+                            .target_rate
+                            .map(|rate| format!(", target {rate:.2} req/s"))
+                            .unwrap_or_default();
+                        info!(
+                            "{} ({}): {:.0}%, {}s left{target}, {} successful, {} failed",
+                            progress.id,
+                            progress.executor_type,
+                            progress.progress,
+                            progress.remaining.as_secs(),
+                            progress.successful_requests,
+                            progress.failed_requests
+                        );
+                    }
                 }
             } => {}
         }
-    });
+    }))
+}
 
-    // download prompts dataset
+/// Download the configured dataset from the Hub and tokenize its conversations
+#[cfg(all(feature = "hub", feature = "datasets"))]
+pub async fn load_requests(
+    run_config: &RunConfiguration,
+    tokenizer: Arc<Tokenizer>,
+    tx: &UnboundedSender<Event>,
+) -> anyhow::Result<ConversationTextRequestGenerator> {
+    if run_config.synthetic_documents {
+        return ConversationTextRequestGenerator::load_summarization(
+            None,
+            tokenizer,
+            run_config.prompt_options.clone(),
+            run_config.decode_options.clone(),
+            run_config.summary_ratio,
+        );
+    }
     info!("Downloading dataset");
     let _ = tx.send(Event::Message(MessageEvent {
         message: "Downloading dataset".to_string(),
         timestamp: chrono::Utc::now(),
         level: Level::Info,
     }));
-    let filepath = requests::ConversationTextRequestGenerator::download_dataset(
-        run_config.dataset,
-        run_config.dataset_file,
+    let filepath = ConversationTextRequestGenerator::download_dataset(
+        run_config.dataset.clone(),
+        run_config.dataset_file.clone(),
         run_config.hf_token.clone(),
+        Some(tx),
     )
-    .expect("Can't download dataset");
-    let requests = requests::ConversationTextRequestGenerator::load(
+    .await
+    .map_err(|e| anyhow::anyhow!("Can't download dataset {}: {e}", run_config.dataset))?;
+    if run_config.fim {
+        return ConversationTextRequestGenerator::load_fim(
+            filepath,
+            tokenizer,
+            run_config.prompt_options.clone(),
+            run_config.decode_options.clone(),
+            &run_config.fim_template,
+        );
+    }
+    if run_config.rag {
+        return ConversationTextRequestGenerator::load_rag(
+            filepath,
+            tokenizer,
+            run_config.prompt_options.clone(),
+            run_config.decode_options.clone(),
+            run_config.rag_passages,
+        );
+    }
+    if run_config.summarization {
+        return ConversationTextRequestGenerator::load_summarization(
+            Some(filepath),
+            tokenizer,
+            run_config.prompt_options.clone(),
+            run_config.decode_options.clone(),
+            run_config.summary_ratio,
+        );
+    }
+    ConversationTextRequestGenerator::load(
         filepath,
-        run_config.tokenizer_name.clone(),
-        run_config.prompt_options,
-        run_config.decode_options,
-        run_config.hf_token,
-    )?;
+        tokenizer,
+        run_config.prompt_options.clone(),
+        run_config.decode_options.clone(),
+    )
+}
 
-    let mut benchmark = benchmark::Benchmark::new(
-        config.clone(),
-        Box::new(backend),
-        Arc::from(Mutex::from(requests)),
-        tx.clone(),
-        stop_sender.clone(),
-    );
-    let mut stop_receiver = stop_sender.subscribe();
-    tokio::select! {
-        report = benchmark.run() => {
-            match report {
-                Ok(_) => {
-                    let report = benchmark.get_report();
-                    let path = format!("results/{}_{}.json",run_config.tokenizer_name.replace("/","_").replace(".","_"), chrono::Utc::now().format("%Y-%m-%d-%H-%M-%S"));
-                    let path=Path::new(&path);
-                    let writer=BenchmarkReportWriter::try_new(config.clone(), report)?;
-                    writer.json(path).await?;
-                    info!("Report saved to {:?}",path);
-                },
-                Err(e) => {
-                    error!("Error running benchmark: {:?}", e.to_string());
-                    let _ = tx.send(Event::BenchmarkError(e.to_string()));
-                }
-            };
+/// Write the JSON report to `path`, see [`output_path`]
+pub async fn save_report(
+    config: &BenchmarkConfig,
+    report: BenchmarkReport,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let writer = BenchmarkReportWriter::try_new(config.clone(), report)?;
+    let path = writer.json(path).await?;
+    info!("Report saved to {:?}", path);
+    if let Some(sink) = &config.results_sink {
+        let location = sink
+            .upload(&path, &config.run_id, &config.config_hash())
+            .await?;
+        info!("Report uploaded to {location}");
+    }
+    if config.csv_summary {
+        let path = writer.csv(&path.with_extension("csv")).await?;
+        info!("CSV summary saved to {:?}", path);
+    }
+    #[cfg(feature = "parquet")]
+    if config.parquet {
+        for path in writer.parquet(&path.with_extension("parquet")).await? {
+            info!("Parquet table saved to {:?}", path);
+        }
+    }
+    if let Some(path) = &config.markdown_output {
+        let path = writer.markdown(path).await?;
+        info!("Markdown summary saved to {:?}", path);
+    }
+    if let Some(path) = &config.html_output {
+        let path = writer.html(path).await?;
+        info!("HTML report saved to {:?}", path);
+    }
+    if let Some(format) = config.raw_results {
+        let path = writer.raw_results(format, &path).await?;
+        info!("Raw results saved to {:?}", path);
+    }
+    Ok(())
+}
+
+/// Path of the report in `results/`, taking reports of runs with the same configuration hash into
+/// account according to `on_duplicate` (error, overwrite, suffix or skip). `None` if the run must
+/// be skipped.
+pub fn output_path(
+    config: &BenchmarkConfig,
+    on_duplicate: &str,
+) -> anyhow::Result<Option<PathBuf>> {
+    let policy: DuplicatePolicy = on_duplicate.parse()?;
+    let path = report_path(config);
+    let Some(existing) = find_duplicate(Path::new(RESULTS_DIR), &config.config_hash()) else {
+        return Ok(Some(path));
+    };
+    match policy {
+        DuplicatePolicy::Error => Err(anyhow::anyhow!(
+            "A report for the same configuration already exists: {}. \
+            Use --on-duplicate to overwrite it, write a new one or skip the run",
+            existing.display()
+        )),
+        DuplicatePolicy::Overwrite => {
+            warn!("Overwriting report {}", existing.display());
+            Ok(Some(existing))
+        }
+        DuplicatePolicy::Suffix => {
+            info!(
+                "A report for the same configuration already exists: {}",
+                existing.display()
+            );
+            Ok(Some(path))
         }
-        _ = stop_receiver.recv() => {
-            debug!("Received stop signal, stopping benchmark");
+        DuplicatePolicy::Skip => {
+            println!(
+                "Skipping benchmark, a report for the same configuration already exists: {}",
+                existing.display()
+            );
+            Ok(None)
         }
     }
+}
+
+fn report_path(config: &BenchmarkConfig) -> PathBuf {
+    // no `:` in the timestamp, it is not allowed in Windows file names
+    Path::new(RESULTS_DIR).join(format!(
+        "{}_{}.json",
+        config.file_stem(),
+        chrono::Utc::now().format("%Y-%m-%d-%H-%M-%S")
+    ))
+}
+
+/// Wait for the UI to exit and restore the terminal
+pub async fn finish(
+    interactive: bool,
+    tx: UnboundedSender<Event>,
+    stop_sender: Sender<()>,
+    ui_thread: JoinHandle<()>,
+) -> anyhow::Result<()> {
     let _ = tx.send(Event::BenchmarkReportEnd);
     info!("Benchmark finished");
-    if !run_config.interactive {
+    if !interactive {
         // quit app if not interactive
         let _ = stop_sender.send(());
     }
     ui_thread.await?;
 
     // Revert terminal to original view
-    io::stdout().execute(ratatui::crossterm::terminal::LeaveAlternateScreen)?;
-    ratatui::crossterm::terminal::disable_raw_mode()?;
-    io::stdout().execute(ratatui::crossterm::cursor::Show)?;
+    #[cfg(feature = "tui")]
+    {
+        io::stdout().execute(ratatui::crossterm::terminal::LeaveAlternateScreen)?;
+        ratatui::crossterm::terminal::disable_raw_mode()?;
+        io::stdout().execute(ratatui::crossterm::cursor::Show)?;
+    }
+    Ok(())
+}
 
-    let report = benchmark.get_report();
-    match BenchmarkReportWriter::try_new(config.clone(), report) {
-        Ok(writer) => {
-            writer.stdout().await?;
-        }
-        Err(_) => {
-            warn!("No results to report.");
-        }
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Args;
+    use clap::{CommandFactory, FromArgMatches};
+    use std::collections::HashSet;
 
-    Ok(())
+    #[test]
+    fn test_endpoint_report_paths() {
+        let matches = Args::command().get_matches_from([
+            "inference-benchmarker",
+            "--tokenizer-name",
+            "gpt2",
+            "--url",
+            "http://10.0.0.1:8080",
+            "--extra-url",
+            "http://10.0.0.2:8080,http://10.0.0.1:8080/v2",
+        ]);
+        let args = Args::from_arg_matches(&matches).unwrap();
+        let run_config = args.run_configuration(&matches).unwrap();
+        let configs = endpoint_configs(&run_config)
+            .unwrap()
+            .iter()
+            .map(|endpoint| benchmark_config(endpoint, "gpt2".to_string()).unwrap())
+            .collect::<Vec<_>>();
+        let stems = configs.iter().map(|c| c.file_stem()).collect::<Vec<_>>();
+        assert_eq!(
+            stems,
+            [
+                "gpt2_10_0_0_1_8080",
+                "gpt2_10_0_0_2_8080",
+                "gpt2_10_0_0_1_8080_2"
+            ]
+        );
+        // reports written in the same second don't overwrite each other
+        let paths = configs.iter().map(report_path).collect::<HashSet<_>>();
+        assert_eq!(paths.len(), 3);
+        let hashes = configs
+            .iter()
+            .map(|c| c.config_hash())
+            .collect::<HashSet<_>>();
+        assert_eq!(hashes.len(), 3);
+        // single endpoint runs keep their file names
+        let config = benchmark_config(&run_config, "gpt2".to_string()).unwrap();
+        assert_eq!(config.file_stem(), "gpt2");
+    }
 }
@@ -0,0 +1,95 @@
+use log::{info, warn};
+
+/// Open files kept for everything but the VU connections: tokenizer, dataset, report, logs, metrics
+/// scraping and the console
+const RESERVED_OPEN_FILES: u64 = 256;
+
+/// Raise the soft open files limit of the process to `target`, or to the hard limit by default,
+/// and return the resulting limit. `None` where the limit can't be managed.
+#[cfg(unix)]
+pub fn raise_open_files_limit(target: Option<u64>) -> anyhow::Result<Option<u64>> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: getrlimit only writes to the provided struct
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        warn!(
+            "Failed to read the open files limit: {}",
+            std::io::Error::last_os_error()
+        );
+        return Ok(None);
+    }
+    // rlim_t is not u64 on every platform
+    #[allow(clippy::unnecessary_cast)]
+    let (soft, hard) = (limit.rlim_cur as u64, limit.rlim_max as u64);
+    let wanted = match target {
+        Some(target) if target > hard => {
+            return Err(anyhow::anyhow!(
+                "open files limit {target} is above the hard limit {hard} of the process, raise it \
+                 with `ulimit -Hn` (or `nofile` in /etc/security/limits.conf, `LimitNOFILE` for \
+                 systemd services, `--ulimit nofile` for Docker)"
+            ));
+        }
+        Some(target) => target,
+        None => hard,
+    };
+    if wanted <= soft {
+        return Ok(Some(soft));
+    }
+    limit.rlim_cur = wanted as libc::rlim_t;
+    // SAFETY: setrlimit only reads the provided struct
+    if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+        let error = std::io::Error::last_os_error();
+        if target.is_some() {
+            return Err(anyhow::anyhow!(
+                "Failed to raise the open files limit to {wanted}: {error}"
+            ));
+        }
+        // e.g. macOS rejects unlimited hard limits
+        warn!("Failed to raise the open files limit to {wanted}, keeping {soft}: {error}");
+        return Ok(Some(soft));
+    }
+    info!("Raised the open files limit from {soft} to {wanted}");
+    Ok(Some(wanted))
+}
+
+#[cfg(not(unix))]
+pub fn raise_open_files_limit(_target: Option<u64>) -> anyhow::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Check that `vus` concurrent VUs, each holding a connection, fit in the open files limit, so
+/// the run fails now rather than with connection errors once the VUs ramp up
+pub fn check_open_files(limit: u64, vus: u64) -> anyhow::Result<()> {
+    let required = vus + RESERVED_OPEN_FILES;
+    if required > limit {
+        return Err(anyhow::anyhow!(
+            "{vus} VUs need about {required} open files, above the open files limit of {limit}. \
+             Lower --max-vus (or --parallel-endpoints), or raise the limit with --open-files-limit \
+             after raising the hard limit (`ulimit -Hn`)"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_open_files() {
+        assert!(check_open_files(1024, 128).is_ok());
+        assert!(check_open_files(1024, 800).is_err());
+        assert!(check_open_files(1024, 1024 - RESERVED_OPEN_FILES).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_raise_open_files_limit() {
+        let limit = raise_open_files_limit(None).unwrap().unwrap();
+        // already at the hard limit or above the target, nothing changes
+        assert_eq!(raise_open_files_limit(None).unwrap(), Some(limit));
+        assert_eq!(raise_open_files_limit(Some(1)).unwrap(), Some(limit));
+    }
+}
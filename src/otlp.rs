@@ -0,0 +1,390 @@
+use crate::requests::{
+    TextGenerationAggregatedResponse, TextGenerationBackend, TextGenerationRequest,
+};
+use async_trait::async_trait;
+use log::{debug, warn};
+use rand::Rng;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{Sender, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+
+/// Spans sent in a single export request at most
+const MAX_BATCH_SPANS: usize = 512;
+
+/// Interval between exports of the pending spans
+const EXPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// OTLP span kinds
+const SPAN_KIND_INTERNAL: u8 = 1;
+const SPAN_KIND_CLIENT: u8 = 3;
+
+/// OTLP status codes
+const STATUS_OK: u8 = 1;
+const STATUS_ERROR: u8 = 2;
+
+tokio::task_local! {
+    /// W3C trace context of the request being sent, set by [`OtlpTracingBackend`]
+    static TRACEPARENT: String;
+}
+
+/// `traceparent` header of the request being sent from this task, if it is traced
+pub fn traceparent() -> Option<String> {
+    TRACEPARENT.try_with(Clone::clone).ok()
+}
+
+enum ExportMessage {
+    Span(Value),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Exports spans in batches to an OTLP/HTTP collector with the JSON encoding, from a background
+/// task. Export failures are logged and the spans dropped, so a missing collector doesn't affect
+/// the benchmark.
+#[derive(Clone)]
+pub struct OtlpExporter {
+    sender: UnboundedSender<ExportMessage>,
+}
+
+impl OtlpExporter {
+    /// Exporter to the collector at `endpoint`, e.g. `http://localhost:4318`, spans being sent to
+    /// its `/v1/traces` path. Resource attributes identify the run and the benchmarked server.
+    pub fn spawn(endpoint: &str, run_id: &str, server_url: &str) -> Self {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+        let resource = json!({
+            "attributes": attributes(&[
+                ("service.name", json!("inference-benchmarker")),
+                ("service.version", json!(env!("CARGO_PKG_VERSION"))),
+                ("benchmark.run_id", json!(run_id)),
+                ("server.url", json!(server_url)),
+            ])
+        });
+        tokio::spawn(export_spans(url, resource, receiver));
+        Self { sender }
+    }
+
+    fn export(&self, span: Value) {
+        // the export task only stops with the runtime
+        let _ = self.sender.send(ExportMessage::Span(span));
+    }
+
+    /// Wait for the spans sent so far to be exported
+    pub async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.sender.send(ExportMessage::Flush(tx)).is_ok() {
+            let _ = rx.await;
+        }
+    }
+}
+
+async fn export_spans(
+    url: String,
+    resource: Value,
+    mut receiver: UnboundedReceiver<ExportMessage>,
+) {
+    let client = reqwest::Client::new();
+    let mut spans = Vec::new();
+    let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+    loop {
+        let flushed = tokio::select! {
+            message = receiver.recv() => match message {
+                Some(ExportMessage::Span(span)) => {
+                    spans.push(span);
+                    if spans.len() < MAX_BATCH_SPANS {
+                        continue;
+                    }
+                    None
+                }
+                Some(ExportMessage::Flush(done)) => Some(done),
+                None => break,
+            },
+            _ = interval.tick() => None,
+        };
+        if !spans.is_empty() {
+            send_spans(&client, &url, &resource, std::mem::take(&mut spans)).await;
+        }
+        if let Some(done) = flushed {
+            let _ = done.send(());
+        }
+    }
+    if !spans.is_empty() {
+        send_spans(&client, &url, &resource, spans).await;
+    }
+}
+
+async fn send_spans(client: &reqwest::Client, url: &str, resource: &Value, spans: Vec<Value>) {
+    let count = spans.len();
+    let body = json!({
+        "resourceSpans": [{
+            "resource": resource,
+            "scopeSpans": [{
+                "scope": {"name": "inference-benchmarker", "version": env!("CARGO_PKG_VERSION")},
+                "spans": spans,
+            }],
+        }],
+    });
+    match client.post(url).json(&body).send().await {
+        Ok(response) if response.status().is_success() => {
+            debug!("Exported {count} spans to {url}");
+        }
+        Ok(response) => warn!(
+            "Error exporting {count} spans to {url}: HTTP {}",
+            response.status()
+        ),
+        Err(e) => warn!("Error exporting {count} spans to {url}: {e}"),
+    }
+}
+
+/// Exports a span per request to an OTLP collector, with child spans for the time to first token
+/// and the streaming of the response. Requests carry the trace context in a `traceparent` header,
+/// so servers exporting their own traces nest their spans under the request span.
+#[derive(Clone)]
+pub struct OtlpTracingBackend {
+    backend: Box<dyn TextGenerationBackend + Send + Sync>,
+    exporter: OtlpExporter,
+}
+
+impl OtlpTracingBackend {
+    pub fn new(
+        backend: Box<dyn TextGenerationBackend + Send + Sync>,
+        exporter: OtlpExporter,
+    ) -> Self {
+        Self { backend, exporter }
+    }
+}
+
+#[async_trait]
+impl TextGenerationBackend for OtlpTracingBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let trace_id = format!("{:032x}", rand::thread_rng().gen::<u128>());
+        let span_id = format!("{:016x}", rand::thread_rng().gen::<u64>());
+        let traceparent = format!("00-{trace_id}-{span_id}-01");
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<TextGenerationAggregatedResponse>(1);
+        let forward = async {
+            while let Some(response) = rx.recv().await {
+                for span in request_spans(&response, &trace_id, &span_id, SystemTime::now()) {
+                    self.exporter.export(span);
+                }
+                sender
+                    .send(response)
+                    .await
+                    .expect("Error sending response to channel");
+            }
+        };
+        tokio::join!(
+            TRACEPARENT.scope(traceparent, self.backend.generate(request, tx)),
+            forward
+        );
+    }
+}
+
+/// Spans of a response received at `now`: the request, and its time to first token and
+/// streaming phases when a token was received. Empty if the request wasn't sent.
+fn request_spans(
+    response: &TextGenerationAggregatedResponse,
+    trace_id: &str,
+    span_id: &str,
+    now: SystemTime,
+) -> Vec<Value> {
+    let Some(start) = response.start_time else {
+        return Vec::new();
+    };
+    // instants have no epoch, wall times are derived from their age
+    let start = now - start.elapsed();
+    let end = response
+        .e2e_latency()
+        .map_or(now, |latency| start + latency);
+    let mut request_attributes = vec![
+        (
+            "gen_ai.usage.input_tokens",
+            json!(response.num_prompt_tokens),
+        ),
+        (
+            "gen_ai.usage.output_tokens",
+            json!(response.num_generated_tokens),
+        ),
+        ("benchmark.failed", json!(response.failed)),
+        ("benchmark.retries", json!(response.retries.len())),
+    ];
+    if let Some(status) = response.http_status {
+        request_attributes.push(("http.response.status_code", json!(status)));
+    }
+    if let Some(class) = response.error_class {
+        request_attributes.push(("error.type", json!(class.to_string())));
+    }
+    if let Some(tenant) = &response.tenant {
+        request_attributes.push(("benchmark.tenant", json!(tenant)));
+    }
+    let status = if response.failed {
+        STATUS_ERROR
+    } else {
+        STATUS_OK
+    };
+    let mut request = span(
+        "request",
+        SPAN_KIND_CLIENT,
+        (start, end),
+        &request_attributes,
+        status,
+    );
+    request["spanId"] = json!(span_id);
+    let mut spans = vec![request];
+    if let Some(ttft) = response.time_to_first_token() {
+        let first_token = start + ttft;
+        spans.push(span(
+            "time_to_first_token",
+            SPAN_KIND_INTERNAL,
+            (start, first_token),
+            &[],
+            STATUS_OK,
+        ));
+        spans.push(span(
+            "streaming",
+            SPAN_KIND_INTERNAL,
+            (first_token, end.max(first_token)),
+            &[(
+                "gen_ai.usage.output_tokens",
+                json!(response.num_generated_tokens),
+            )],
+            status,
+        ));
+    }
+    for child in &mut spans[1..] {
+        child["spanId"] = json!(format!("{:016x}", rand::thread_rng().gen::<u64>()));
+        child["parentSpanId"] = json!(span_id);
+    }
+    for span in &mut spans {
+        span["traceId"] = json!(trace_id);
+    }
+    spans
+}
+
+/// Span without its ids, nor a parent
+fn span(
+    name: &str,
+    kind: u8,
+    (start, end): (SystemTime, SystemTime),
+    span_attributes: &[(&str, Value)],
+    status: u8,
+) -> Value {
+    json!({
+        "parentSpanId": "",
+        "name": name,
+        "kind": kind,
+        "startTimeUnixNano": unix_nanos(start),
+        "endTimeUnixNano": unix_nanos(end),
+        "attributes": attributes(span_attributes),
+        "status": {"code": status},
+    })
+}
+
+/// Nanoseconds since the epoch, as a string as 64-bit integers are in OTLP JSON
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+/// OTLP key-value list of attributes
+fn attributes(attributes: &[(&str, Value)]) -> Value {
+    attributes
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Bool(b) => json!({"boolValue": b}),
+                Value::Number(n) if n.is_i64() || n.is_u64() => json!({"intValue": n.to_string()}),
+                Value::Number(n) => json!({"doubleValue": n}),
+                other => json!({"stringValue": other.as_str().unwrap_or_default()}),
+            };
+            json!({"key": key, "value": value})
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::ErrorClass;
+
+    #[test]
+    fn test_request_spans() {
+        let mut response = TextGenerationAggregatedResponse::default();
+        let (trace_id, span_id) = ("0af7651916cd43dd8448eb211c80319c", "b7ad6b7169203331");
+        assert!(request_spans(&response, trace_id, span_id, SystemTime::now()).is_empty());
+
+        let start = tokio::time::Instant::now();
+        response.start_time = Some(start);
+        response.end_time = Some(start + Duration::from_millis(500));
+        response.times_to_tokens = vec![Duration::from_millis(100); 5];
+        response.num_generated_tokens = 5;
+        response.num_prompt_tokens = 100;
+        response.http_status = Some(200);
+        let now = SystemTime::now();
+        let spans = request_spans(&response, trace_id, span_id, now);
+        let [request, ttft, streaming] = &spans[..] else {
+            panic!("expected 3 spans, got {}", spans.len())
+        };
+        assert_eq!(request["name"], "request");
+        assert_eq!(request["kind"], SPAN_KIND_CLIENT);
+        assert_eq!(request["parentSpanId"], "");
+        assert_eq!(request["status"]["code"], STATUS_OK);
+        let nanos = |span: &Value, key: &str| span[key].as_str().unwrap().parse::<u128>().unwrap();
+        assert!(nanos(request, "startTimeUnixNano") <= unix_nanos(now).parse().unwrap());
+        assert_eq!(
+            nanos(request, "endTimeUnixNano") - nanos(request, "startTimeUnixNano"),
+            500_000_000
+        );
+        assert_eq!(
+            request["attributes"][0],
+            json!({"key": "gen_ai.usage.input_tokens", "value": {"intValue": "100"}})
+        );
+        assert!(request["attributes"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({"key": "http.response.status_code", "value": {"intValue": "200"}})));
+        for child in [ttft, streaming] {
+            assert_eq!(child["traceId"], trace_id);
+            assert_eq!(child["parentSpanId"], span_id);
+            assert_eq!(child["kind"], SPAN_KIND_INTERNAL);
+        }
+        assert_eq!(ttft["name"], "time_to_first_token");
+        assert_eq!(ttft["startTimeUnixNano"], request["startTimeUnixNano"]);
+        assert_eq!(
+            nanos(ttft, "endTimeUnixNano") - nanos(ttft, "startTimeUnixNano"),
+            100_000_000
+        );
+        assert_eq!(ttft["endTimeUnixNano"], streaming["startTimeUnixNano"]);
+        assert_eq!(streaming["endTimeUnixNano"], request["endTimeUnixNano"]);
+
+        // failed before the first token
+        let mut response = TextGenerationAggregatedResponse::default();
+        response.start_time = Some(start);
+        response.end_time = Some(start);
+        response.failed = true;
+        response.error_class = Some(ErrorClass::Server);
+        let spans = request_spans(&response, trace_id, span_id, SystemTime::now());
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0]["status"]["code"], STATUS_ERROR);
+        assert!(spans[0]["attributes"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({"key": "error.type", "value": {"stringValue": "server"}})));
+    }
+
+    #[tokio::test]
+    async fn test_traceparent() {
+        assert_eq!(traceparent(), None);
+        let header = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string();
+        let scoped = TRACEPARENT
+            .scope(header.clone(), async { traceparent() })
+            .await;
+        assert_eq!(scoped, Some(header));
+    }
+}
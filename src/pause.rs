@@ -0,0 +1,191 @@
+use crate::benchmark::{Event, MessageEvent};
+use log::info;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Pause of the dispatch of new requests, shared by the executors and the signal listener.
+/// Requests in flight complete while paused, and the paused intervals are left out of the stage
+/// clock and of the step durations.
+#[derive(Clone)]
+pub struct DispatchPause {
+    paused: Arc<watch::Sender<bool>>,
+    intervals: Arc<Mutex<Vec<PauseInterval>>>,
+}
+
+/// Start and end of a pause, ongoing ones having no end
+type PauseInterval = (Instant, Option<Instant>);
+
+impl Default for DispatchPause {
+    fn default() -> Self {
+        Self {
+            paused: Arc::new(watch::Sender::new(false)),
+            intervals: Arc::default(),
+        }
+    }
+}
+
+impl DispatchPause {
+    /// Pause the dispatch, returns false if already paused
+    pub fn pause(&self) -> bool {
+        let paused = self
+            .paused
+            .send_if_modified(|paused| !std::mem::replace(paused, true));
+        if paused {
+            self.intervals.lock().unwrap().push((Instant::now(), None));
+        }
+        paused
+    }
+
+    /// Resume the dispatch, returns false if not paused
+    pub fn resume(&self) -> bool {
+        let resumed = self
+            .paused
+            .send_if_modified(|paused| std::mem::replace(paused, false));
+        if resumed {
+            if let Some((_, end)) = self.intervals.lock().unwrap().last_mut() {
+                *end = Some(Instant::now());
+            }
+        }
+        resumed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Wait for the dispatch to be resumed, if paused
+    pub async fn resumed(&self) {
+        let mut paused = self.paused.subscribe();
+        // the sender lives as long as self
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+
+    /// Paused intervals overlapping `start..end`, clipped to it, the ongoing pause ending at `end`
+    pub fn intervals(&self, start: Instant, end: Instant) -> Vec<(Instant, Instant)> {
+        self.intervals
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(from, to)| ((*from).max(start), to.unwrap_or(end).min(end)))
+            .filter(|(from, to)| from < to)
+            .collect()
+    }
+
+    /// Time elapsed since `start`, without the pauses
+    pub fn active_elapsed(&self, start: Instant) -> Duration {
+        let now = Instant::now();
+        let paused = paused_duration(&self.intervals(start, now), start, now);
+        (now - start).saturating_sub(paused)
+    }
+
+    /// Wait until `delay` of active time elapsed since `start`, waiting out the pauses
+    pub async fn sleep_until(&self, start: Instant, delay: Duration) {
+        loop {
+            self.resumed().await;
+            let remaining = delay.saturating_sub(self.active_elapsed(start));
+            if remaining.is_zero() {
+                return;
+            }
+            tokio::time::sleep(remaining).await;
+        }
+    }
+
+    /// Pause on SIGUSR1 and resume on SIGUSR2 until aborted, announcing it on the event bus
+    #[cfg(unix)]
+    pub fn listen_signals(
+        &self,
+        event_bus: UnboundedSender<Event>,
+    ) -> anyhow::Result<JoinHandle<()>> {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut pause_signal = signal(SignalKind::user_defined1())?;
+        let mut resume_signal = signal(SignalKind::user_defined2())?;
+        let pause = self.clone();
+        Ok(tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    _ = pause_signal.recv() => pause
+                        .pause()
+                        .then_some("Received SIGUSR1, pausing the dispatch of new requests until SIGUSR2"),
+                    _ = resume_signal.recv() => pause
+                        .resume()
+                        .then_some("Received SIGUSR2, resuming the dispatch of requests"),
+                };
+                if let Some(message) = message {
+                    info!("{message}");
+                    let _ = event_bus.send(Event::Message(MessageEvent {
+                        message: message.to_string(),
+                        timestamp: chrono::Utc::now(),
+                        level: log::Level::Info,
+                    }));
+                }
+            }
+        }))
+    }
+
+    /// Signals are only handled on Unix
+    #[cfg(not(unix))]
+    pub fn listen_signals(
+        &self,
+        _event_bus: UnboundedSender<Event>,
+    ) -> anyhow::Result<JoinHandle<()>> {
+        Ok(tokio::spawn(async {}))
+    }
+}
+
+/// Total duration of `intervals` within `start..end`
+pub fn paused_duration(intervals: &[(Instant, Instant)], start: Instant, end: Instant) -> Duration {
+    intervals
+        .iter()
+        .map(|(from, to)| (*to).min(end).saturating_duration_since((*from).max(start)))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dispatch_pause() {
+        let ms = Duration::from_millis;
+        let pause = DispatchPause::default();
+        let start = Instant::now();
+        tokio::time::sleep(ms(100)).await;
+        assert!(pause.pause());
+        assert!(!pause.pause());
+        assert!(pause.is_paused());
+        tokio::time::sleep(ms(100)).await;
+        let active = pause.active_elapsed(start);
+        assert!(active >= ms(100) && active < ms(150), "{active:?}");
+
+        let sleeping = tokio::spawn({
+            let pause = pause.clone();
+            async move { pause.sleep_until(start, ms(250)).await }
+        });
+        tokio::time::sleep(ms(200)).await;
+        assert!(!sleeping.is_finished());
+        assert!(pause.resume());
+        assert!(!pause.resume());
+        // 300ms paused
+        sleeping.await.unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= ms(550) && elapsed < ms(650), "{elapsed:?}");
+
+        let end = Instant::now();
+        let intervals = pause.intervals(start, end);
+        assert_eq!(intervals.len(), 1);
+        let paused = paused_duration(&intervals, start, end);
+        assert!(paused >= ms(300) && paused < ms(350), "{paused:?}");
+        assert_eq!(
+            paused_duration(&intervals, start, intervals[0].0),
+            Duration::ZERO
+        );
+        assert_eq!(
+            paused_duration(&intervals, intervals[0].0 + ms(100), end),
+            paused - ms(100)
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use crate::requests::TokenizeOptions;
+use crate::RunConfiguration;
+use std::str::FromStr;
+
+/// Built-in workloads selectable with `--preset`, so meaningful results don't require designing
+/// token distributions and SLOs first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Short prompts and answers of a chat assistant, latency bound
+    ChatInteractive,
+    /// Long retrieved contexts with short answers, prefill bound
+    RagLongContext,
+    /// Long documents summarized offline, throughput bound
+    BatchSummarization,
+    /// Very long documents with summaries proportional to their length, prefill and memory bound
+    LongSummarization,
+    /// Code context with short completions, time to first token bound
+    CodeCompletion,
+}
+
+/// Token distributions, SLOs and executor settings of a preset
+struct Workload {
+    benchmark_kind: &'static str,
+    max_vus: u64,
+    /// num_tokens, min_tokens, max_tokens, variance
+    prompt: (u64, u64, u64, u64),
+    /// Proportional to the prompt length if not set
+    decode: Option<(u64, u64, u64, u64)>,
+    /// Checked as stage assertions
+    slos: &'static [&'static str],
+    /// Prompts other than chat conversations
+    generator: Option<Generator>,
+}
+
+/// Requests generated from other sources than chat conversations, with the Hugging Face dataset
+/// and file they are generated from
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Generator {
+    /// Fill-in-the-middle prompts cut from source files
+    Fim(&'static str, &'static str),
+    /// Questions stuffed with retrieved passages of a corpus
+    Rag(&'static str, &'static str),
+    /// Summaries of synthetic documents
+    SyntheticDocuments,
+}
+
+impl FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "chat-interactive" => Ok(Preset::ChatInteractive),
+            "rag-long-context" => Ok(Preset::RagLongContext),
+            "batch-summarization" => Ok(Preset::BatchSummarization),
+            "long-summarization" => Ok(Preset::LongSummarization),
+            "code-completion" => Ok(Preset::CodeCompletion),
+            _ => Err(anyhow::anyhow!(
+                "Unknown preset: {s}. Available presets: chat-interactive, rag-long-context, batch-summarization, long-summarization, code-completion"
+            )),
+        }
+    }
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [
+        Preset::ChatInteractive,
+        Preset::RagLongContext,
+        Preset::BatchSummarization,
+        Preset::LongSummarization,
+        Preset::CodeCompletion,
+    ];
+
+    /// SLOs of the preset workload, checked as stage assertions unless given explicitly
+    pub fn slos(&self) -> &'static [&'static str] {
+        self.workload().slos
+    }
+
+    fn workload(&self) -> Workload {
+        match self {
+            Preset::ChatInteractive => Workload {
+                benchmark_kind: "sweep",
+                max_vus: 128,
+                prompt: (200, 50, 800, 150),
+                decode: Some((250, 50, 500, 100)),
+                slos: &["p90_ttft < 500ms", "p90_itl < 50ms", "error_rate < 1%"],
+                generator: None,
+            },
+            Preset::RagLongContext => Workload {
+                benchmark_kind: "sweep",
+                max_vus: 32,
+                prompt: (12000, 4000, 32000, 6000),
+                decode: Some((100, 20, 200, 50)),
+                slos: &["p90_ttft < 4s", "p90_itl < 60ms", "error_rate < 1%"],
+                generator: Some(Generator::Rag(
+                    "databricks/databricks-dolly-15k",
+                    "databricks-dolly-15k.jsonl",
+                )),
+            },
+            Preset::BatchSummarization => Workload {
+                benchmark_kind: "throughput",
+                max_vus: 256,
+                prompt: (3000, 1000, 6000, 1000),
+                decode: Some((400, 200, 800, 100)),
+                slos: &["p99_e2e < 120s", "error_rate < 0.5%"],
+                generator: None,
+            },
+            Preset::LongSummarization => Workload {
+                benchmark_kind: "sweep",
+                max_vus: 16,
+                prompt: (32000, 8000, 100000, 16000),
+                decode: None,
+                slos: &["p90_ttft < 20s", "p90_itl < 100ms", "error_rate < 1%"],
+                generator: Some(Generator::SyntheticDocuments),
+            },
+            Preset::CodeCompletion => Workload {
+                benchmark_kind: "sweep",
+                max_vus: 128,
+                prompt: (1500, 500, 3000, 500),
+                decode: Some((32, 8, 64, 16)),
+                slos: &["p90_ttft < 150ms", "p90_e2e < 1s", "error_rate < 1%"],
+                generator: Some(Generator::Fim(
+                    "bigcode/the-stack-smol",
+                    "data/python/data.json",
+                )),
+            },
+        }
+    }
+
+    /// Fill the run configuration with the preset workload. `is_set` tells whether an option was
+    /// given by the user, on the command line or in the environment: it takes precedence.
+    pub fn apply(&self, run_config: &mut RunConfiguration, is_set: impl Fn(&str) -> bool) {
+        let workload = self.workload();
+        let options = |(num_tokens, min_tokens, max_tokens, variance)| TokenizeOptions {
+            num_tokens: Some(num_tokens),
+            min_tokens,
+            max_tokens,
+            variance,
+        };
+        if !is_set("benchmark_kind") {
+            run_config.benchmark_kind = workload.benchmark_kind.to_string();
+        }
+        if !is_set("max_vus") {
+            run_config.max_vus = workload.max_vus;
+        }
+        if !is_set("prompt_options") {
+            run_config.prompt_options = Some(options(workload.prompt));
+        }
+        if !is_set("decode_options") {
+            run_config.decode_options = workload.decode.map(options);
+        }
+        if !is_set("assertions") {
+            run_config.assertions = workload.slos.iter().map(|s| s.to_string()).collect();
+        }
+        let mut use_dataset = |dataset: &str, dataset_file: &str| {
+            if !is_set("dataset") && !is_set("dataset_file") {
+                run_config.dataset = dataset.to_string();
+                run_config.dataset_file = dataset_file.to_string();
+            }
+        };
+        match workload.generator {
+            Some(Generator::Fim(dataset, dataset_file)) => {
+                use_dataset(dataset, dataset_file);
+                run_config.fim = true;
+                // FIM prompts are sent raw, without chat template
+                if !is_set("endpoint") && run_config.backend == "openai" {
+                    run_config.endpoint = "completions".to_string();
+                }
+            }
+            Some(Generator::Rag(dataset, dataset_file)) => {
+                use_dataset(dataset, dataset_file);
+                run_config.rag = true;
+            }
+            Some(Generator::SyntheticDocuments) => {
+                run_config.summarization = true;
+                // documents of a dataset given by the user are summarized instead
+                run_config.synthetic_documents = !is_set("dataset") && !is_set("dataset_file");
+            }
+            None => {}
+        }
+        run_config.preset = Some(self.to_string());
+    }
+}
+
+impl std::fmt::Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Preset::ChatInteractive => "chat-interactive",
+            Preset::RagLongContext => "rag-long-context",
+            Preset::BatchSummarization => "batch-summarization",
+            Preset::LongSummarization => "long-summarization",
+            Preset::CodeCompletion => "code-completion",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assertions::Assertion;
+
+    #[test]
+    fn test_presets() {
+        for name in [
+            "chat-interactive",
+            "rag-long-context",
+            "batch-summarization",
+            "long-summarization",
+            "code-completion",
+        ] {
+            let preset: Preset = name.parse().unwrap();
+            assert_eq!(preset.to_string(), name);
+            let workload = preset.workload();
+            for slo in workload.slos {
+                slo.parse::<Assertion>().unwrap();
+            }
+            for (num_tokens, min_tokens, max_tokens, _) in
+                std::iter::once(workload.prompt).chain(workload.decode)
+            {
+                assert!(min_tokens <= num_tokens && num_tokens <= max_tokens);
+            }
+        }
+        assert!("chat".parse::<Preset>().is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use crate::requests::TextGenerationAggregatedResponse;
+use log::{debug, error};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Generated tokens within this relative difference of the requested ones comply with the
+/// requested length
+const LENGTH_TOLERANCE: f64 = 0.1;
+
+/// Computes custom scores of each successful response before it is aggregated in the results of
+/// its step, e.g. toxicity, compliance with the requested length or JSON validity. Scores are
+/// summarized per step and per tenant, and written with the raw results of each request.
+pub trait ResponseProcessor: Send + Sync {
+    /// Name of the processor, for errors
+    fn name(&self) -> &str;
+
+    /// Scores of `response` by name. A response without a given score is left out of its summary.
+    fn process(
+        &self,
+        response: &TextGenerationAggregatedResponse,
+    ) -> anyhow::Result<BTreeMap<String, f64>>;
+}
+
+/// `json_valid`: 1 if the generated text is a valid JSON document, 0 otherwise. Markdown code
+/// fences around the document are ignored.
+pub struct JsonValidity;
+
+impl ResponseProcessor for JsonValidity {
+    fn name(&self) -> &str {
+        "json_validity"
+    }
+
+    fn process(
+        &self,
+        response: &TextGenerationAggregatedResponse,
+    ) -> anyhow::Result<BTreeMap<String, f64>> {
+        let text = response.generated_text.trim();
+        let text = text
+            .strip_prefix("```json")
+            .or_else(|| text.strip_prefix("```"))
+            .and_then(|text| text.strip_suffix("```"))
+            .unwrap_or(text);
+        let valid = serde_json::from_str::<serde::de::IgnoredAny>(text).is_ok();
+        Ok(BTreeMap::from([(
+            "json_valid".to_string(),
+            f64::from(valid as u8),
+        )]))
+    }
+}
+
+/// `length_compliance`: 1 if the generated tokens are within 10% of the requested ones, 0
+/// otherwise, for requests with a requested length
+pub struct LengthCompliance;
+
+impl ResponseProcessor for LengthCompliance {
+    fn name(&self) -> &str {
+        "length_compliance"
+    }
+
+    fn process(
+        &self,
+        response: &TextGenerationAggregatedResponse,
+    ) -> anyhow::Result<BTreeMap<String, f64>> {
+        let Some(requested) = response
+            .request
+            .as_ref()
+            .and_then(|request| request.num_decode_tokens)
+            .filter(|tokens| *tokens > 0)
+        else {
+            return Ok(BTreeMap::new());
+        };
+        let difference = response.num_generated_tokens.abs_diff(requested) as f64;
+        let compliant = difference <= requested as f64 * LENGTH_TOLERANCE;
+        Ok(BTreeMap::from([(
+            "length_compliance".to_string(),
+            f64::from(compliant as u8),
+        )]))
+    }
+}
+
+/// Processors run on each successful response, in order, later scores replacing earlier ones of
+/// the same name
+#[derive(Clone, Default)]
+pub struct ResponseProcessors {
+    processors: Vec<Arc<dyn ResponseProcessor>>,
+    /// Whether each processor failed already, shared by the clones of the stages so only the first
+    /// failure of a processor in the run is logged as an error
+    failed: Arc<Vec<AtomicBool>>,
+}
+
+impl ResponseProcessors {
+    pub fn new(processors: Vec<Arc<dyn ResponseProcessor>>) -> Self {
+        let failed = processors.iter().map(|_| AtomicBool::new(false)).collect();
+        Self {
+            processors,
+            failed: Arc::new(failed),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processors.is_empty()
+    }
+
+    /// Score a successful response, before it is added to the results of its step
+    pub fn process(&self, response: &mut TextGenerationAggregatedResponse) {
+        if !response.failed && !self.is_empty() {
+            response.scores = self.scores(response);
+        }
+    }
+
+    /// Scores of `response` by all the processors, failing ones being logged and skipped
+    pub fn scores(&self, response: &TextGenerationAggregatedResponse) -> BTreeMap<String, f64> {
+        let mut scores = BTreeMap::new();
+        for (processor, failed) in self.processors.iter().zip(self.failed.iter()) {
+            match processor.process(response) {
+                Ok(processor_scores) => scores.extend(processor_scores),
+                Err(e) if !failed.swap(true, Ordering::Relaxed) => error!(
+                    "Response processor {} failed: {e}, its next failures are logged at debug level",
+                    processor.name()
+                ),
+                Err(e) => debug!("Response processor {} failed: {e}", processor.name()),
+            }
+        }
+        scores
+    }
+}
+
+impl Debug for ResponseProcessors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.processors.iter().map(|processor| processor.name()))
+            .finish()
+    }
+}
+
+/// Built-in processor from its name
+pub fn builtin_processor(name: &str) -> anyhow::Result<Arc<dyn ResponseProcessor>> {
+    match name {
+        "json_validity" => Ok(Arc::new(JsonValidity)),
+        "length_compliance" => Ok(Arc::new(LengthCompliance)),
+        _ => Err(anyhow::anyhow!(
+            "Unknown response processor: {name}, expected json_validity or length_compliance"
+        )),
+    }
+}
+
+/// Distribution of a score over the responses of a step
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ScoreSummary {
+    pub responses: u64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ScoreSummary {
+    /// Summaries of each score over the `scores` of the responses
+    pub fn by_name<'a>(
+        scores: impl IntoIterator<Item = &'a BTreeMap<String, f64>>,
+    ) -> BTreeMap<String, ScoreSummary> {
+        let mut values = BTreeMap::<&str, Vec<f64>>::new();
+        for response_scores in scores {
+            for (name, value) in response_scores {
+                values.entry(name).or_default().push(*value);
+            }
+        }
+        values
+            .into_iter()
+            .map(|(name, values)| {
+                let summary = ScoreSummary {
+                    responses: values.len() as u64,
+                    avg: values.iter().sum::<f64>() / values.len() as f64,
+                    min: values.iter().copied().fold(f64::INFINITY, f64::min),
+                    max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                };
+                (name.to_string(), summary)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::ExecutorConfig;
+    use crate::requests::TextGenerationRequest;
+    use crate::results::BenchmarkResults;
+    use crate::scheduler::ExecutorType;
+
+    fn response(
+        text: &str,
+        generated_tokens: u64,
+        requested: Option<u64>,
+    ) -> TextGenerationAggregatedResponse {
+        let mut response = TextGenerationAggregatedResponse::default();
+        response.start_time = Some(tokio::time::Instant::now());
+        response.end_time = response.start_time;
+        response.generated_text = text.to_string();
+        response.num_generated_tokens = generated_tokens;
+        response.request = Some(Arc::new(TextGenerationRequest {
+            prompt: "Answer in JSON".to_string(),
+            num_prompt_tokens: 3,
+            num_decode_tokens: requested,
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        }));
+        response
+    }
+
+    #[test]
+    fn test_response_processors() {
+        let processors = ResponseProcessors::new(vec![
+            builtin_processor("json_validity").unwrap(),
+            builtin_processor("length_compliance").unwrap(),
+        ]);
+        assert!(builtin_processor("toxicity").is_err());
+        let scores = processors.scores(&response("```json\n{\"a\": [1, 2]}\n```", 95, Some(100)));
+        assert_eq!(
+            scores,
+            BTreeMap::from([
+                ("json_valid".to_string(), 1.0),
+                ("length_compliance".to_string(), 1.0),
+            ])
+        );
+        let scores = processors.scores(&response("{\"a\": ", 50, None));
+        assert_eq!(scores, BTreeMap::from([("json_valid".to_string(), 0.0)]));
+
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantVUs,
+            ExecutorConfig {
+                response_processors: processors.clone(),
+                ..Default::default()
+            },
+        );
+        let mut failed = response("", 0, Some(100));
+        failed.failed = true;
+        for mut response in [
+            response("[1]", 100, Some(100)),
+            response("not json", 80, Some(100)),
+            failed,
+        ] {
+            processors.process(&mut response);
+            results.add_response(response);
+        }
+        let scores = results.scores();
+        assert_eq!(
+            scores["json_valid"],
+            ScoreSummary {
+                responses: 2,
+                avg: 0.5,
+                min: 0.0,
+                max: 1.0
+            }
+        );
+        assert_eq!(scores["length_compliance"].avg, 0.5);
+        // failed responses aren't scored
+        assert!(results
+            .get_responses()
+            .iter()
+            .all(|response| response.failed == response.scores.is_empty()));
+    }
+}
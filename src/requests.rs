@@ -1,21 +1,43 @@
+#[cfg(all(feature = "hub", feature = "datasets"))]
+use crate::executors::Trace;
+use crate::scripting::{RequestScript, ScriptedRequest};
 use async_trait::async_trait;
-use futures_util::StreamExt;
-use hf_hub::api::sync::ApiBuilder;
+use futures_util::{SinkExt, StreamExt};
+#[cfg(feature = "hub")]
+use hf_hub::Repo;
+#[cfg(feature = "datasets")]
 use indicatif::{ProgressBar, ProgressStyle};
-use log::{debug, error, info, trace, warn};
+#[cfg(feature = "datasets")]
+use log::info;
+use log::{debug, error, trace, warn};
+#[cfg(feature = "datasets")]
+use rand::seq::SliceRandom;
+use rand::Rng;
+#[cfg(feature = "datasets")]
 use rand_distr::Distribution;
+#[cfg(feature = "datasets")]
 use rayon::iter::split;
+#[cfg(feature = "datasets")]
 use rayon::prelude::*;
 use reqwest_eventsource::{Error, Event, EventSource};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt::Display;
+#[cfg(feature = "datasets")]
 use std::path::PathBuf;
-use std::sync::atomic::AtomicI64;
-use std::sync::{Arc, Mutex};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "datasets")]
+use std::sync::Mutex;
 use std::time;
-use tokenizers::{FromPretrainedParameters, Tokenizer};
-use tokio::sync::mpsc::Sender;
+use tokenizers::Tokenizer;
+use tokio::sync::mpsc::{Sender, UnboundedSender};
 use tokio::time::sleep;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request as WebSocketRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
 
 #[derive(Debug, Clone)]
 pub struct TextGenerationRequest {
@@ -23,6 +45,10 @@ pub struct TextGenerationRequest {
     pub num_prompt_tokens: u64, // this includes the system prompt if present
     pub num_decode_tokens: Option<u64>,
     pub system_prompt: Option<String>,
+    /// Ground-truth answer of the prompt, for accuracy scoring
+    pub expected_answer: Option<String>,
+    /// `num_decode_tokens` was reduced to fit the model context length
+    pub context_clamped: bool,
 }
 
 #[async_trait]
@@ -61,12 +87,138 @@ pub struct OpenAITextGenerationBackend {
     pub client: reqwest::Client,
     pub tokenizer: Arc<Tokenizer>,
     pub timeout: time::Duration,
+    pub options: RequestOptions,
+}
+
+/// OpenAI-compatible route requests are sent to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenAIEndpoint {
+    /// `/v1/chat/completions`, the prompt is sent as chat messages
+    #[default]
+    ChatCompletions,
+    /// `/v1/completions`, the raw prompt is sent without chat template
+    Completions,
+}
+
+impl OpenAIEndpoint {
+    pub fn path(&self) -> &'static str {
+        match self {
+            OpenAIEndpoint::ChatCompletions => "/v1/chat/completions",
+            OpenAIEndpoint::Completions => "/v1/completions",
+        }
+    }
+}
+
+impl FromStr for OpenAIEndpoint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "chat" | "chat_completions" => Ok(OpenAIEndpoint::ChatCompletions),
+            "completions" => Ok(OpenAIEndpoint::Completions),
+            _ => Err(anyhow::anyhow!("Unknown endpoint: {s}")),
+        }
+    }
+}
+
+/// Server API requests are sent to. The OpenAI-compatible client is the base of every backend,
+/// the others wrap it to speak the native API of their server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// OpenAI-compatible API, on the route of the [`OpenAIEndpoint`]
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAI,
+    /// Text Generation Inference native `/generate_stream` API
+    Tgi,
+    /// vLLM native `/generate` API
+    Vllm,
+    /// Triton Inference Server generate extension, or its gRPC API with a `grpc://` URL
+    Triton,
+}
+
+impl FromStr for BackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "openai" => Ok(BackendKind::OpenAI),
+            "tgi" => Ok(BackendKind::Tgi),
+            "vllm" => Ok(BackendKind::Vllm),
+            "triton" => Ok(BackendKind::Triton),
+            _ => Err(anyhow::anyhow!(
+                "Unknown backend: {s}. Available backends: openai, tgi, vllm, triton"
+            )),
+        }
+    }
+}
+
+impl Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            BackendKind::OpenAI => "openai",
+            BackendKind::Tgi => "tgi",
+            BackendKind::Vllm => "vllm",
+            BackendKind::Triton => "triton",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Optional, server-specific request features
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// Route the requests are sent to
+    pub endpoint: OpenAIEndpoint,
+    /// Route replacing the default one of the backend, e.g. for gateways with custom paths
+    pub path: Option<String>,
+    /// Headers sent with every request
+    pub headers: Vec<(String, String)>,
+    /// Request TGI generation details
+    pub tgi_details: bool,
+    /// Header carrying a unique key per request, for gateways with replay protection
+    pub idempotency_key_header: Option<String>,
+    /// Mark the system prompt as cacheable and request token usage to get cache hits
+    pub prompt_caching: bool,
+    /// User script transforming requests and classifying responses
+    pub script: Option<RequestScript>,
+    /// Deadlines of each request
+    pub timeouts: RequestTimeouts,
+    /// Send each request both streamed and unstreamed, to measure the overhead of streaming
+    pub compare_streaming: bool,
+    /// Request token usage and re-tokenize whole streamed texts, to reconcile token counts
+    pub token_reconciliation: bool,
+    /// Request token usage to check the generated token counts against the server's
+    pub tokenizer_check: bool,
+}
+
+/// Deadlines of a request, timed out requests fail with [`ErrorClass::Timeout`]
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct RequestTimeouts {
+    /// Establishing the connection to the server
+    #[serde(rename = "connect_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub connect: Option<time::Duration>,
+    /// Receiving the first token, from the start of the request
+    #[serde(rename = "first_token_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub first_token: Option<time::Duration>,
+    /// Receiving the whole response, the backend timeout by default
+    #[serde(rename = "total_secs")]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<f64>>")]
+    pub total: Option<time::Duration>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct OpenAITextGenerationMessage {
     pub content: String,
     pub role: String,
+    /// Prompt caching breakpoint, for Anthropic-compatible gateways
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<serde_json::Value>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -79,11 +231,192 @@ pub struct OpenAITextGenerationChoice {
     pub message: Option<OpenAITextGenerationMessage>,
     pub finish_reason: Option<String>,
     pub delta: Option<OpenAITextGenerationDelta>,
+    /// Generated text of the plain completions endpoint
+    pub text: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct OpenAITextGenerationResponse {
+    #[serde(default)]
     pub choices: Vec<OpenAITextGenerationChoice>,
+    /// Sent instead of choices when the generation fails
+    pub error: Option<OpenAIError>,
+    /// TGI error type, sent next to a string `error`
+    pub error_type: Option<String>,
+    /// Only sent by TGI when `details` is requested
+    pub details: Option<TgiDetails>,
+    /// Sent with unstreamed responses, and in a last chunk without choices when
+    /// `stream_options.include_usage` is set
+    pub usage: Option<OpenAIUsage>,
+}
+
+/// Error of an error response. OpenAI-compatible servers send an object with a message, a type
+/// and a code, TGI a plain message with the type next to it in `error_type`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum OpenAIError {
+    Object {
+        message: Option<String>,
+        #[serde(rename = "type")]
+        error_type: Option<String>,
+        code: Option<serde_json::Value>,
+    },
+    Message(String),
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIErrorResponse {
+    error: OpenAIError,
+    error_type: Option<String>,
+}
+
+/// Cause of a failed request, derived from the error body and HTTP status returned by the server
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// Rate limit or exhausted quota
+    Quota,
+    /// Prompt and requested tokens exceed the model context length
+    ContextLength,
+    /// Request rejected as invalid
+    Validation,
+    /// Internal server error
+    Server,
+    /// Connection, first token or total request deadline exceeded
+    Timeout,
+    /// Connection failed or dropped by the network
+    Connection,
+    /// Malformed response: invalid JSON, event stream or encoding
+    Parse,
+    /// Request still in flight when the benchmark was stopped
+    Cancelled,
+    /// Errors without a recognized cause, e.g. streams ending before completion
+    Other,
+}
+
+impl ErrorClass {
+    /// Classify an error from its type, code and message, falling back to the HTTP status
+    pub fn classify(status: Option<u16>, error: &OpenAIError, error_type: Option<&str>) -> Self {
+        let text = match error {
+            OpenAIError::Object {
+                message,
+                error_type: object_type,
+                code,
+            } => format!(
+                "{} {} {}",
+                object_type.as_deref().or(error_type).unwrap_or_default(),
+                code.as_ref().map(|c| c.to_string()).unwrap_or_default(),
+                message.as_deref().unwrap_or_default()
+            ),
+            OpenAIError::Message(message) => {
+                format!("{} {message}", error_type.unwrap_or_default())
+            }
+        }
+        .to_lowercase();
+        let matches = |patterns: &[&str]| patterns.iter().any(|p| text.contains(p));
+        if status == Some(429) || matches(&["quota", "rate_limit", "rate limit"]) {
+            ErrorClass::Quota
+        } else if matches(&["context_length", "context length", "maximum context"]) {
+            ErrorClass::ContextLength
+        } else if matches(&["invalid_request", "validation"]) {
+            ErrorClass::Validation
+        } else if matches(&["server_error", "internal", "overloaded"]) {
+            ErrorClass::Server
+        } else {
+            Self::from_status(status)
+        }
+    }
+
+    /// Classify an error response body, which may not be JSON
+    pub fn classify_body(status: Option<u16>, body: &str) -> Self {
+        match serde_json::from_str::<OpenAIErrorResponse>(body) {
+            Ok(response) => Self::classify(status, &response.error, response.error_type.as_deref()),
+            Err(_) => Self::from_status(status),
+        }
+    }
+
+    /// Transient failures, worth retrying: rate limits, server and connection errors
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ErrorClass::Quota | ErrorClass::Server | ErrorClass::Connection
+        )
+    }
+
+    fn from_status(status: Option<u16>) -> Self {
+        match status {
+            Some(429) => ErrorClass::Quota,
+            Some(400..=499) => ErrorClass::Validation,
+            Some(500..) => ErrorClass::Server,
+            _ => ErrorClass::Other,
+        }
+    }
+}
+
+impl Display for ErrorClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ErrorClass::Quota => "quota",
+            ErrorClass::ContextLength => "context_length",
+            ErrorClass::Validation => "validation",
+            ErrorClass::Server => "server",
+            ErrorClass::Timeout => "timeout",
+            ErrorClass::Connection => "connection",
+            ErrorClass::Parse => "parse",
+            ErrorClass::Cancelled => "cancelled",
+            ErrorClass::Other => "other",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct OpenAIUsage {
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub prompt_tokens_details: Option<OpenAIPromptTokensDetails>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct OpenAIPromptTokensDetails {
+    pub cached_tokens: Option<u64>,
+}
+
+/// Generation details returned by TGI with the final token
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct TgiDetails {
+    pub finish_reason: Option<String>,
+    pub generated_tokens: Option<u64>,
+    pub input_length: Option<u64>,
+    /// Time the request waited in the server queue, from the final payload or the
+    /// `x-queue-time` header
+    #[serde(default, alias = "queue_time")]
+    pub queue_time_ms: Option<f64>,
+    /// Time the server spent on prefill and decoding, from the final payload or the
+    /// `x-inference-time` header
+    #[serde(default, alias = "inference_time")]
+    pub inference_time_ms: Option<f64>,
+}
+
+impl TgiDetails {
+    /// Queue and inference times of TGI's `x-queue-time` and `x-inference-time` headers (in
+    /// milliseconds), completing the ones of the final payload
+    fn add_timing_headers(details: &mut Option<TgiDetails>, headers: &reqwest::header::HeaderMap) {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .and_then(|value| value.parse::<f64>().ok())
+        };
+        let (queue_time_ms, inference_time_ms) =
+            (header("x-queue-time"), header("x-inference-time"));
+        if queue_time_ms.is_none() && inference_time_ms.is_none() {
+            return;
+        }
+        let details = details.get_or_insert_with(TgiDetails::default);
+        details.queue_time_ms = details.queue_time_ms.or(queue_time_ms);
+        details.inference_time_ms = details.inference_time_ms.or(inference_time_ms);
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -94,6 +427,81 @@ pub struct OpenAITextGenerationRequest {
     pub stream: bool,
     pub stop: Option<String>,
     pub temperature: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct OpenAICompletionRequest {
+    pub model: String,
+    pub prompt: String,
+    pub max_tokens: Option<u64>,
+    pub stream: bool,
+    pub temperature: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<serde_json::Value>,
+}
+
+enum StreamStatus {
+    Continue,
+    Failed,
+}
+
+const UNIX_SOCKET_SCHEME: &str = "unix://";
+
+/// Build the HTTP client for a base URL. `unix:///path/to/socket` URLs send all requests
+/// through the Unix domain socket, the returned base URL is then only used for the Host header.
+fn build_client(
+    base_url: String,
+    connect_timeout: Option<time::Duration>,
+) -> anyhow::Result<(reqwest::Client, String)> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(connect_timeout) = connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    match base_url.strip_prefix(UNIX_SOCKET_SCHEME) {
+        #[cfg(unix)]
+        Some(path) => {
+            let client = builder
+                .unix_socket(std::path::PathBuf::from(path))
+                .build()?;
+            Ok((client, "http://localhost".to_string()))
+        }
+        #[cfg(not(unix))]
+        Some(_) => Err(anyhow::anyhow!(
+            "Unix domain sockets are not supported on this platform"
+        )),
+        None => Ok((builder.build()?, base_url)),
+    }
+}
+
+/// Await `future` until `deadline`, if any
+async fn until<F: std::future::Future>(
+    deadline: Option<tokio::time::Instant>,
+    future: F,
+) -> Result<F::Output, tokio::time::error::Elapsed> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await,
+        None => Ok(future.await),
+    }
+}
+
+tokio::task_local! {
+    /// Idempotency key shared by the attempts of the request being sent, set by
+    /// [`RetryingBackend`] with [`IdempotencyKeyMode::PerRequest`]
+    static IDEMPOTENCY_KEY: String;
+}
+
+/// Idempotency key of the request being sent from this task, a new one for each attempt unless
+/// the request has one
+fn idempotency_key() -> String {
+    IDEMPOTENCY_KEY
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| random_uuid())
 }
 
 impl OpenAITextGenerationBackend {
@@ -103,138 +511,532 @@ impl OpenAITextGenerationBackend {
         model_name: String,
         tokenizer: Arc<Tokenizer>,
         timeout: time::Duration,
+        options: RequestOptions,
     ) -> anyhow::Result<Self> {
+        let (client, base_url) = build_client(base_url, options.timeouts.connect)?;
         Ok(Self {
-            client: reqwest::Client::new(),
+            client,
             api_key,
             base_url,
             model_name,
             tokenizer,
             timeout,
+            options,
         })
     }
-}
 
-#[async_trait]
-impl TextGenerationBackend for OpenAITextGenerationBackend {
-    async fn generate(
+    /// Process one chunk of a streamed chat completion, recording token timings
+    fn handle_chunk(
         &self,
-        request: Arc<TextGenerationRequest>,
-        sender: Sender<TextGenerationAggregatedResponse>,
-    ) {
-        let url = format!("{base_url}/v1/chat/completions", base_url = self.base_url);
-        let mut aggregated_response = TextGenerationAggregatedResponse::default();
+        data: &str,
+        request: &TextGenerationRequest,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+        final_response: &mut String,
+    ) -> StreamStatus {
+        if data == "\n" || data == "[DONE]" {
+            aggregated_response.stop();
+            return StreamStatus::Continue;
+        }
+        // deserialize message data
+        let oai_response: OpenAITextGenerationResponse = match serde_json::from_str(data) {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Error deserializing OpenAI API response: {e}", e = e);
+                aggregated_response.fail_as(ErrorClass::Parse);
+                return StreamStatus::Failed;
+            }
+        };
+        if let Some(error) = &oai_response.error {
+            error!("Error from OpenAI API: {message}", message = data);
+            aggregated_response.error_class = Some(ErrorClass::classify(
+                None,
+                error,
+                oai_response.error_type.as_deref(),
+            ));
+            aggregated_response.fail();
+            return StreamStatus::Failed;
+        }
+        if let Some(usage) = oai_response.usage {
+            aggregated_response.add_usage(usage);
+            if !self.options.prompt_caching {
+                // usage is also requested to check token counts, only report cache hits when asked to
+                aggregated_response.cached_prompt_tokens = None;
+            }
+        }
+        if oai_response.choices.is_empty() {
+            // final usage chunk sent when `include_usage` is set
+            return StreamStatus::Continue;
+        }
+        let choices = oai_response.choices;
+        let content = match &choices[0].delta {
+            Some(delta) => delta.content.clone(),
+            None => choices[0].text.clone(),
+        }
+        .unwrap_or_default();
+        if content.is_empty() {
+            // skip empty responses
+            return StreamStatus::Continue;
+        }
+        // we need to count the number of tokens generated as each delta chunk may contain multiple tokens
+        // that's the case with vLLM chunked prefill or speculative decoding
+        let num_tokens = self.tokenizer.encode(content.clone(), false).unwrap().len() as u64;
+        if num_tokens > 1 {
+            warn!(
+                "Generated more than one token: {num_tokens}",
+                num_tokens = num_tokens
+            );
+        }
+        if oai_response.details.is_some() {
+            aggregated_response.server_details = oai_response.details;
+        }
+        match choices[0].clone().finish_reason {
+            None => {
+                aggregated_response.add_tokens(num_tokens);
+                *final_response += content.as_str();
+            }
+            Some(_) => {
+                aggregated_response.add_tokens(num_tokens);
+                *final_response += content.as_str();
+                aggregated_response.stop();
+                trace!("Generated text using OpenAI API | prompt: {prompt}, max tokens: {max_tokens:?}, response: {message}", prompt = request.prompt, max_tokens = request.num_decode_tokens,message = &content);
+            }
+        };
+        StreamStatus::Continue
+    }
+
+    /// Payload and extra headers, transformed by the user script if any
+    fn scripted_request(&self, body: serde_json::Value) -> anyhow::Result<ScriptedRequest> {
+        let scripted = ScriptedRequest {
+            body,
+            headers: BTreeMap::new(),
+        };
+        match &self.options.script {
+            Some(script) => script.transform_request(scripted),
+            None => Ok(scripted),
+        }
+    }
+
+    /// Let the user script decide whether a finished response is successful
+    fn classify_response(&self, aggregated_response: &mut TextGenerationAggregatedResponse) {
+        let Some(script) = &self.options.script else {
+            return;
+        };
+        match script.classify_response(
+            &aggregated_response.generated_text,
+            aggregated_response.num_generated_tokens,
+            aggregated_response.failed,
+        ) {
+            Ok(Some(success)) => {
+                aggregated_response.failed = !(success && aggregated_response.end_time.is_some())
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("{e}");
+                aggregated_response.failed = true;
+            }
+        }
+    }
+
+    /// Payload of a request for the configured endpoint
+    pub fn request_body(
+        &self,
+        request: &TextGenerationRequest,
+        stream: bool,
+    ) -> anyhow::Result<serde_json::Value> {
+        Ok(match self.options.endpoint {
+            OpenAIEndpoint::ChatCompletions => {
+                serde_json::to_value(self.chat_request(request, stream))?
+            }
+            OpenAIEndpoint::Completions => {
+                serde_json::to_value(self.completion_request(request, stream))?
+            }
+        })
+    }
+
+    /// Whether streamed requests ask for a final chunk with the token usage
+    fn include_usage(&self) -> bool {
+        self.options.prompt_caching
+            || self.options.token_reconciliation
+            || self.options.tokenizer_check
+    }
+
+    /// Plain completion payload for a request. Without chat template, the system prompt is
+    /// prepended to the prompt.
+    pub fn completion_request(
+        &self,
+        request: &TextGenerationRequest,
+        stream: bool,
+    ) -> OpenAICompletionRequest {
+        let prompt = match &request.system_prompt {
+            None => request.prompt.clone(),
+            Some(system_prompt) => format!("{system_prompt}\n\n{}", request.prompt),
+        };
+        OpenAICompletionRequest {
+            model: self.model_name.clone(),
+            prompt,
+            max_tokens: request.num_decode_tokens,
+            stream,
+            temperature: 0.0,
+            details: self.options.tgi_details.then_some(true),
+            stream_options: (stream && self.include_usage())
+                .then(|| serde_json::json!({"include_usage": true})),
+        }
+    }
+
+    /// Chat completion payload for a request, `stream_options` are only set when streaming
+    pub fn chat_request(
+        &self,
+        request: &TextGenerationRequest,
+        stream: bool,
+    ) -> OpenAITextGenerationRequest {
         let messages = match &request.system_prompt {
             None => vec![OpenAITextGenerationMessage {
                 role: "user".to_string(),
                 content: request.prompt.clone(),
+                cache_control: None,
             }],
             Some(system_prompt) => vec![
                 OpenAITextGenerationMessage {
                     role: "system".to_string(),
                     content: system_prompt.clone(),
+                    cache_control: self
+                        .options
+                        .prompt_caching
+                        .then(|| serde_json::json!({"type": "ephemeral"})),
                 },
                 OpenAITextGenerationMessage {
                     role: "user".to_string(),
                     content: request.prompt.clone(),
+                    cache_control: None,
                 },
             ],
         };
-        let body = OpenAITextGenerationRequest {
+        OpenAITextGenerationRequest {
             model: self.model_name.clone(),
             messages,
             max_tokens: request.num_decode_tokens,
-            stream: true,
+            stream,
             stop: None,
             temperature: 0.0,
+            details: self.options.tgi_details.then_some(true),
+            stream_options: (stream && self.include_usage())
+                .then(|| serde_json::json!({"include_usage": true})),
+        }
+    }
+}
+
+#[async_trait]
+impl TextGenerationBackend for OpenAITextGenerationBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        if !self.options.compare_streaming {
+            let aggregated_response = self.generate_streamed(&request).await;
+            sender
+                .send(aggregated_response)
+                .await
+                .expect("Error sending response to channel");
+            return;
+        }
+        // the variant sent second may hit a prefix cache warmed by the first one, so the order
+        // of each pair is drawn at random
+        let streamed_first = rand::thread_rng().gen_bool(0.5);
+        for streamed in [streamed_first, !streamed_first] {
+            let mut aggregated_response = match streamed {
+                true => self.generate_streamed(&request).await,
+                false => self.generate_unstreamed(&request).await,
+            };
+            aggregated_response.streamed = Some(streamed);
+            sender
+                .send(aggregated_response)
+                .await
+                .expect("Error sending response to channel");
+        }
+    }
+}
+
+impl OpenAITextGenerationBackend {
+    /// Stream a request, recording the arrival time of each token
+    async fn generate_streamed(
+        &self,
+        request: &Arc<TextGenerationRequest>,
+    ) -> TextGenerationAggregatedResponse {
+        let url = self.url(self.options.endpoint.path());
+        let mut aggregated_response = TextGenerationAggregatedResponse::default();
+        let scripted = match self
+            .request_body(request, true)
+            .and_then(|body| self.scripted_request(body))
+        {
+            Ok(scripted) => scripted,
+            Err(e) => {
+                error!("Error building request: {e}");
+                aggregated_response.start(request.clone());
+                aggregated_response.fail();
+                return aggregated_response;
+            }
+        };
+        let final_response = self
+            .stream_events(
+                url,
+                scripted,
+                request,
+                &mut aggregated_response,
+                |data, aggregated_response, final_response| {
+                    self.handle_chunk(data, request, aggregated_response, final_response)
+                },
+            )
+            .await;
+        if self.options.token_reconciliation {
+            // chunk boundaries may split tokens the whole text encodes as one
+            aggregated_response.retokenized_tokens = Some(
+                self.tokenizer
+                    .encode(final_response.clone(), false)
+                    .unwrap()
+                    .len() as u64,
+            );
+        }
+        aggregated_response.generated_text = final_response;
+        self.classify_response(&mut aggregated_response);
+        aggregated_response
+    }
+
+    /// Send a request without streaming, all tokens arriving at once with the response. There is
+    /// no first token to time out on, only the total timeout applies.
+    async fn generate_unstreamed(
+        &self,
+        request: &Arc<TextGenerationRequest>,
+    ) -> TextGenerationAggregatedResponse {
+        let url = self.url(self.options.endpoint.path());
+        let mut aggregated_response = TextGenerationAggregatedResponse::default();
+        let scripted = match self
+            .request_body(request, false)
+            .and_then(|body| self.scripted_request(body))
+        {
+            Ok(scripted) => scripted,
+            Err(e) => {
+                error!("Error building request: {e}");
+                aggregated_response.start(request.clone());
+                aggregated_response.fail();
+                return aggregated_response;
+            }
+        };
+        let req = self.post(url, &scripted, &mut aggregated_response);
+        aggregated_response.start(request.clone());
+        let mut headers = reqwest::header::HeaderMap::new();
+        let body = match req.send().await {
+            Ok(response) => {
+                aggregated_response.http_status = Some(response.status().as_u16());
+                headers = response.headers().clone();
+                response.text().await
+            }
+            Err(e) => Err(e),
+        };
+        let body = match body {
+            Ok(body) => body,
+            Err(e) if e.is_timeout() => {
+                error!("Request timed out: {e}");
+                aggregated_response.time_out();
+                return aggregated_response;
+            }
+            Err(e) => {
+                error!("Connection error: {e}");
+                aggregated_response.fail_as(ErrorClass::Connection);
+                return aggregated_response;
+            }
+        };
+        let status = aggregated_response.http_status;
+        if status.is_some_and(|status| status >= 300) {
+            error!("Error from OpenAI API ({status:?}): {body}");
+            aggregated_response.fail_as(ErrorClass::classify_body(status, &body));
+            return aggregated_response;
+        }
+        let oai_response: OpenAITextGenerationResponse = match serde_json::from_str(&body) {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Error deserializing OpenAI API response: {e}");
+                aggregated_response.fail_as(ErrorClass::Parse);
+                return aggregated_response;
+            }
         };
-        let req = self
+        if let Some(error) = &oai_response.error {
+            error!("Error from OpenAI API: {body}");
+            aggregated_response.fail_as(ErrorClass::classify(
+                status,
+                error,
+                oai_response.error_type.as_deref(),
+            ));
+            return aggregated_response;
+        }
+        if let Some(usage) = oai_response.usage {
+            aggregated_response.add_usage(usage);
+            if !self.options.prompt_caching {
+                // unstreamed responses always carry usage, only report cache hits when asked to
+                aggregated_response.cached_prompt_tokens = None;
+            }
+        }
+        if oai_response.details.is_some() {
+            aggregated_response.server_details = oai_response.details;
+        }
+        TgiDetails::add_timing_headers(&mut aggregated_response.server_details, &headers);
+        let content = oai_response
+            .choices
+            .first()
+            .and_then(|choice| match &choice.message {
+                Some(message) => Some(message.content.clone()),
+                None => choice.text.clone(),
+            })
+            .unwrap_or_default();
+        let num_tokens = self.tokenizer.encode(content.clone(), false).unwrap().len() as u64;
+        if num_tokens == 0 {
+            // server sent no data
+            aggregated_response.fail();
+            return aggregated_response;
+        }
+        aggregated_response.add_tokens(num_tokens);
+        aggregated_response.stop();
+        aggregated_response.generated_text = content;
+        self.classify_response(&mut aggregated_response);
+        aggregated_response
+    }
+
+    /// Deadline of the whole response, the backend timeout unless configured
+    fn total_timeout(&self) -> time::Duration {
+        self.options.timeouts.total.unwrap_or(self.timeout)
+    }
+
+    /// Deadline of the connection, for connections not made by the HTTP client
+    fn connect_deadline(&self) -> Option<tokio::time::Instant> {
+        self.options
+            .timeouts
+            .connect
+            .map(|timeout| tokio::time::Instant::now() + timeout)
+    }
+
+    /// Deadline of the next message while no token was received
+    fn first_token_deadline(
+        &self,
+        aggregated_response: &TextGenerationAggregatedResponse,
+    ) -> Option<tokio::time::Instant> {
+        if !aggregated_response.times_to_tokens.is_empty() {
+            return None;
+        }
+        Some(aggregated_response.start_time? + self.options.timeouts.first_token?)
+    }
+
+    /// URL of a route under the base URL, the configured path replacing the default one
+    fn url(&self, default_path: &str) -> String {
+        format!(
+            "{base_url}{path}",
+            base_url = self.base_url,
+            path = self.options.path.as_deref().unwrap_or(default_path)
+        )
+    }
+
+    /// POST request with the authorization, script and idempotency key headers
+    fn post(
+        &self,
+        url: String,
+        scripted: &ScriptedRequest,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+    ) -> reqwest::RequestBuilder {
+        let mut req = self
             .client
             .post(url)
             .header(
                 "Authorization",
                 format!("Bearer {token}", token = self.api_key),
             )
-            .json(&serde_json::json!(body))
-            .timeout(self.timeout);
+            .json(&scripted.body)
+            .timeout(self.total_timeout());
+        let headers = self
+            .options
+            .headers
+            .iter()
+            .map(|(name, value)| (name, value));
+        for (name, value) in headers.chain(&scripted.headers) {
+            req = req.header(name, value);
+        }
+        if let Some(header) = &self.options.idempotency_key_header {
+            let key = idempotency_key();
+            req = req.header(header, key.clone());
+            aggregated_response.idempotency_key = Some(key);
+        }
+        #[cfg(all(feature = "hub", feature = "datasets"))]
+        if let Some(traceparent) = crate::otlp::traceparent() {
+            req = req.header("traceparent", traceparent);
+        }
+        req
+    }
+
+    /// POST a request and process its SSE stream with `handle_chunk`, recording timings in
+    /// `aggregated_response`. Returns the generated text.
+    async fn stream_events<F>(
+        &self,
+        url: String,
+        scripted: ScriptedRequest,
+        request: &Arc<TextGenerationRequest>,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+        mut handle_chunk: F,
+    ) -> String
+    where
+        F: FnMut(&str, &mut TextGenerationAggregatedResponse, &mut String) -> StreamStatus + Send,
+    {
+        let req = self.post(url, &scripted, aggregated_response);
         // start timer
-        aggregated_response.start(request.num_prompt_tokens);
+        aggregated_response.start(request.clone());
         let mut es = EventSource::new(req).unwrap();
         let mut final_response = "".to_string();
-        while let Some(event) = es.next().await {
+        loop {
+            let deadline = self.first_token_deadline(aggregated_response);
+            let Ok(event) = until(deadline, es.next()).await else {
+                error!("No token received before the first token timeout");
+                aggregated_response.time_out();
+                es.close();
+                break;
+            };
+            let Some(event) = event else {
+                break;
+            };
             match event {
-                Ok(Event::Open) => trace!("SSE connection opened"),
+                Ok(Event::Open) => {
+                    trace!("SSE connection opened");
+                    // the event source only opens on 200 responses
+                    aggregated_response.http_status = Some(200);
+                }
                 Ok(Event::Message(message)) => {
-                    if message.data == "\n" || message.data == "[DONE]" {
-                        aggregated_response.stop();
-                        continue;
-                    }
-                    if message.data.starts_with("{\"error\":") {
-                        error!("Error from OpenAI API: {message}", message = message.data);
-                        aggregated_response.fail();
+                    if let StreamStatus::Failed =
+                        handle_chunk(&message.data, aggregated_response, &mut final_response)
+                    {
                         es.close();
                         break;
                     }
-                    // deserialize message data
-                    let oai_response: OpenAITextGenerationResponse =
-                        match serde_json::from_str(&message.data) {
-                            Ok(response) => response,
-                            Err(e) => {
-                                error!("Error deserializing OpenAI API response: {e}", e = e);
-                                aggregated_response.fail();
-                                es.close();
-                                break;
-                            }
-                        };
-                    let choices = oai_response.choices;
-                    let content = choices[0]
-                        .clone()
-                        .delta
-                        .unwrap()
-                        .content
-                        .unwrap_or("".to_string());
-                    if content.is_empty() {
-                        // skip empty responses
-                        continue;
-                    }
-                    // we need to count the number of tokens generated as each delta chunk may contain multiple tokens
-                    // that's the case with vLLM chunked prefill or speculative decoding
-                    let num_tokens =
-                        self.tokenizer.encode(content.clone(), false).unwrap().len() as u64;
-                    if num_tokens > 1 {
-                        warn!(
-                            "Generated more than one token: {num_tokens}",
-                            num_tokens = num_tokens
-                        );
-                    }
-                    match choices[0].clone().finish_reason {
-                        None => {
-                            aggregated_response.add_tokens(num_tokens);
-                            final_response += content.as_str();
-                        }
-                        Some(_) => {
-                            aggregated_response.add_tokens(num_tokens);
-                            aggregated_response.stop();
-                            trace!("Generated text using OpenAI API | prompt: {prompt}, max tokens: {max_tokens:?}, response: {message}", prompt = request.prompt, max_tokens = request.num_decode_tokens,message = &content);
-                        }
-                    };
                 }
                 Err(e) => {
                     match e {
-                        Error::Utf8(_) => {
-                            aggregated_response.fail();
+                        Error::Utf8(_) | Error::Parser(_) => {
+                            aggregated_response.fail_as(ErrorClass::Parse);
                         }
-                        Error::Parser(_) => {
-                            aggregated_response.fail();
+                        Error::Transport(e) if e.is_timeout() => {
+                            error!("Request timed out: {e}");
+                            aggregated_response.time_out();
                         }
-                        Error::Transport(_) => {
+                        Error::Transport(e) => {
+                            error!("Connection error: {e}");
+                            aggregated_response.error_class = Some(ErrorClass::Connection);
                             aggregated_response.fail();
                         }
-                        Error::InvalidContentType(_, _) => {
-                            aggregated_response.fail();
+                        Error::InvalidContentType(_, response) => {
+                            aggregated_response.http_status = Some(response.status().as_u16());
+                            aggregated_response.fail_as(ErrorClass::Parse);
                         }
-                        Error::InvalidStatusCode(_, _) => {
-                            aggregated_response.fail();
+                        Error::InvalidStatusCode(status, response) => {
+                            let body = response.text().await.unwrap_or_default();
+                            error!("Error from OpenAI API ({status}): {body}");
+                            aggregated_response.http_status = Some(status.as_u16());
+                            aggregated_response
+                                .fail_as(ErrorClass::classify_body(Some(status.as_u16()), &body));
                         }
                         Error::InvalidLastEventId(_) => {
                             aggregated_response.fail();
@@ -255,887 +1057,4576 @@ impl TextGenerationBackend for OpenAITextGenerationBackend {
                 }
             };
         }
-        sender
-            .send(aggregated_response.clone())
-            .await
-            .expect("Error sending response to channel");
-        //debug!("Final response: {response}", response = final_response);
+        final_response
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct DummyTextGenerationBackend {
-    time_to_generate: time::Duration,
+pub fn is_websocket_url(url: &str) -> bool {
+    url.starts_with("ws://") || url.starts_with("wss://")
 }
 
-impl DummyTextGenerationBackend {
-    pub fn new(time_to_generate: time::Duration) -> Self {
-        Self { time_to_generate }
-    }
+/// gRPC URLs (grpc://host:port) select the Triton gRPC streaming backend
+pub fn is_grpc_url(url: &str) -> bool {
+    url.starts_with("grpc://")
 }
 
-impl Default for DummyTextGenerationBackend {
-    fn default() -> Self {
-        Self::new(time::Duration::from_secs(1))
+/// WebSocket handshake request to the backend base URL, with the same headers as HTTP requests
+fn websocket_request(
+    backend: &OpenAITextGenerationBackend,
+    idempotency_key: Option<String>,
+) -> anyhow::Result<WebSocketRequest> {
+    let mut ws_request = backend.base_url.as_str().into_client_request()?;
+    if !backend.api_key.is_empty() {
+        ws_request.headers_mut().insert(
+            reqwest::header::AUTHORIZATION,
+            format!("Bearer {}", backend.api_key).parse()?,
+        );
+    }
+    for (name, value) in &backend.options.headers {
+        ws_request
+            .headers_mut()
+            .insert(HeaderName::from_bytes(name.as_bytes())?, value.parse()?);
     }
+    if let (Some(header), Some(key)) = (&backend.options.idempotency_key_header, idempotency_key) {
+        ws_request
+            .headers_mut()
+            .insert(HeaderName::from_bytes(header.as_bytes())?, key.parse()?);
+    }
+    Ok(ws_request)
 }
 
-#[async_trait]
-impl TextGenerationBackend for DummyTextGenerationBackend {
-    async fn generate(
-        &self,
-        request: Arc<TextGenerationRequest>,
-        sender: Sender<crate::requests::TextGenerationAggregatedResponse>,
-    ) {
-        let mut response = TextGenerationAggregatedResponse::default();
-        response.start(request.num_prompt_tokens);
-        let num_tokens = request.num_decode_tokens.unwrap_or(10);
-        let time_per_token = self
-            .time_to_generate
-            .checked_div(num_tokens as u32)
-            .unwrap();
-        for _ in 0..num_tokens {
-            sleep(time_per_token).await;
-            response.add_tokens(1);
+/// Mark the response as failed if the WebSocket stream errored, timed out or ended early
+fn check_websocket_result(
+    result: Result<anyhow::Result<()>, tokio::time::error::Elapsed>,
+    request: &TextGenerationRequest,
+    aggregated_response: &mut TextGenerationAggregatedResponse,
+    final_response: &str,
+) {
+    match result {
+        Ok(Ok(())) => {
+            if aggregated_response.num_generated_tokens == 0
+                || aggregated_response.end_time.is_none()
+            {
+                warn!("WebSocket closed before completion. Received :: {num_tokens}/{max_tokens} tokens. Response: {final_response}", num_tokens = aggregated_response.num_generated_tokens, max_tokens = request.num_decode_tokens.unwrap_or(0));
+                aggregated_response.fail();
+            }
+        }
+        Ok(Err(e)) if e.is::<tokio::time::error::Elapsed>() => {
+            error!("WebSocket connection or first token timed out");
+            aggregated_response.time_out();
+        }
+        Ok(Err(e)) => {
+            error!("Error from WebSocket stream: {e}");
+            if let Some(
+                tokio_tungstenite::tungstenite::Error::Io(_)
+                | tokio_tungstenite::tungstenite::Error::ConnectionClosed
+                | tokio_tungstenite::tungstenite::Error::AlreadyClosed,
+            ) = e.downcast_ref()
+            {
+                aggregated_response.error_class = Some(ErrorClass::Connection);
+            } else if e.is::<serde_json::Error>() {
+                aggregated_response.error_class = Some(ErrorClass::Parse);
+            }
+            aggregated_response.fail();
+        }
+        Err(_) => {
+            error!("WebSocket request timed out");
+            aggregated_response.time_out();
         }
-        response.stop();
-        sender
-            .send(response.clone())
-            .await
-            .expect("Error sending response to channel");
     }
 }
 
-pub trait TextRequestGenerator: Sync {
-    fn generate_request(&mut self) -> TextGenerationRequest;
+/// Streams chat completions over a WebSocket, for gateways and realtime APIs not using SSE.
+/// The request payload is sent as a text message, then each received text message is handled
+/// like an SSE `data` chunk until `[DONE]` or the connection is closed.
+#[derive(Debug, Clone)]
+pub struct WebSocketTextGenerationBackend {
+    backend: OpenAITextGenerationBackend,
 }
 
-#[derive(Clone)]
-pub struct ConversationTextRequestGenerator {
-    pub requests: Vec<TextGenerationRequest>,
-    current_index: Arc<AtomicI64>,
-}
+impl WebSocketTextGenerationBackend {
+    /// The backend base URL is used as the WebSocket endpoint
+    pub fn new(backend: OpenAITextGenerationBackend) -> Self {
+        Self { backend }
+    }
 
-#[derive(Deserialize, Serialize, Clone)]
-pub struct Conversation {
-    pub role: String,
-    pub content: String,
+    async fn stream(
+        &self,
+        request: &TextGenerationRequest,
+        idempotency_key: Option<String>,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+        final_response: &mut String,
+    ) -> anyhow::Result<()> {
+        let scripted = self
+            .backend
+            .scripted_request(self.backend.request_body(request, true)?)?;
+        let mut ws_request = websocket_request(&self.backend, idempotency_key)?;
+        for (name, value) in &scripted.headers {
+            ws_request
+                .headers_mut()
+                .insert(HeaderName::from_bytes(name.as_bytes())?, value.parse()?);
+        }
+        let (mut ws, _) = until(
+            self.backend.connect_deadline(),
+            tokio_tungstenite::connect_async(ws_request),
+        )
+        .await??;
+        trace!("WebSocket connection opened");
+        ws.send(Message::Text(scripted.body.to_string())).await?;
+        while let Some(message) = until(
+            self.backend.first_token_deadline(aggregated_response),
+            ws.next(),
+        )
+        .await?
+        {
+            match message? {
+                Message::Text(data) => {
+                    if let StreamStatus::Failed = self.backend.handle_chunk(
+                        &data,
+                        request,
+                        aggregated_response,
+                        final_response,
+                    ) {
+                        break;
+                    }
+                    if data == "[DONE]" {
+                        break;
+                    }
+                }
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        let _ = ws.close(None).await;
+        Ok(())
+    }
 }
 
-#[derive(Deserialize, Serialize, Clone)]
-pub struct ConversationEntry {
-    pub id: String,
-    pub conversations: Vec<Conversation>,
+#[async_trait]
+impl TextGenerationBackend for WebSocketTextGenerationBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let mut aggregated_response = TextGenerationAggregatedResponse::default();
+        let mut final_response = String::new();
+        if self.backend.options.idempotency_key_header.is_some() {
+            aggregated_response.idempotency_key = Some(idempotency_key());
+        }
+        let idempotency_key = aggregated_response.idempotency_key.clone();
+        // start timer
+        aggregated_response.start(request.clone());
+        let stream = self.stream(
+            &request,
+            idempotency_key,
+            &mut aggregated_response,
+            &mut final_response,
+        );
+        let result = tokio::time::timeout(self.backend.total_timeout(), stream).await;
+        check_websocket_result(result, &request, &mut aggregated_response, &final_response);
+        aggregated_response.generated_text = final_response;
+        self.backend.classify_response(&mut aggregated_response);
+        sender
+            .send(aggregated_response.clone())
+            .await
+            .expect("Error sending response to channel");
+    }
 }
 
-#[derive(Clone, Serialize, Debug)]
-pub struct TokenizeOptions {
-    pub num_tokens: Option<u64>,
-    pub min_tokens: u64,
-    pub max_tokens: u64,
-    pub variance: u64,
+#[derive(Deserialize, Debug)]
+struct TgiStreamToken {
+    text: String,
+    special: bool,
 }
 
-impl TokenizeOptions {
-    pub fn new() -> Self {
-        Self {
-            num_tokens: None,
-            min_tokens: 0,
-            max_tokens: u64::MAX,
-            variance: 0,
-        }
-    }
+/// Event of TGI's native `/generate_stream` SSE protocol, the last one carries the generated text
+/// and the generation details
+#[derive(Deserialize, Debug)]
+struct TgiStreamResponse {
+    token: Option<TgiStreamToken>,
+    generated_text: Option<String>,
+    details: Option<TgiDetails>,
+    error: Option<String>,
+    error_type: Option<String>,
 }
 
-impl Default for TokenizeOptions {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Backend for Text Generation Inference's native `/generate_stream` endpoint. TGI streams one
+/// token per event, and the final event details give the server-side count of generated tokens,
+/// which is used instead of re-tokenizing the text.
+#[derive(Debug, Clone)]
+pub struct TgiTextGenerationBackend {
+    backend: OpenAITextGenerationBackend,
 }
 
-impl Display for TokenizeOptions {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "num_tokens={num_tokens:?},min_tokens={min_tokens},max_tokens={max_tokens},variance={variance}",
-            num_tokens = self.num_tokens,
-            min_tokens = self.min_tokens,
-            max_tokens = self.max_tokens,
-            variance = self.variance
-        )
+impl TgiTextGenerationBackend {
+    /// Requests are sent to `/generate_stream` under the backend base URL
+    pub fn new(backend: OpenAITextGenerationBackend) -> Self {
+        Self { backend }
     }
-}
 
-impl ConversationTextRequestGenerator {
-    pub fn load(
-        filepath: PathBuf,
-        tokenizer: String,
-        prompt_tokenize_opts: Option<TokenizeOptions>,
-        decode_tokenize_opts: Option<TokenizeOptions>,
-        hf_token: Option<String>,
-    ) -> anyhow::Result<Self> {
-        let params = FromPretrainedParameters {
-            token: hf_token,
-            ..Default::default()
+    /// Native payload for a request, without chat template the system prompt is prepended to the
+    /// prompt
+    pub fn request_body(&self, request: &TextGenerationRequest) -> serde_json::Value {
+        let inputs = match &request.system_prompt {
+            None => request.prompt.clone(),
+            Some(system_prompt) => format!("{system_prompt}\n\n{}", request.prompt),
         };
-        let tokenizer = match Tokenizer::from_pretrained(tokenizer, Some(params)) {
-            Ok(tokenizer) => tokenizer,
+        let mut parameters = serde_json::json!({"details": true, "do_sample": false});
+        if let Some(max_tokens) = request.num_decode_tokens {
+            parameters["max_new_tokens"] = serde_json::json!(max_tokens);
+        }
+        serde_json::json!({"inputs": inputs, "parameters": parameters})
+    }
+
+    fn handle_chunk(
+        data: &str,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+        final_response: &mut String,
+    ) -> StreamStatus {
+        let event: TgiStreamResponse = match serde_json::from_str(data) {
+            Ok(event) => event,
             Err(e) => {
-                return Err(anyhow::anyhow!("Error loading tokenizer: {e}"));
+                error!("Error deserializing TGI response: {e}");
+                aggregated_response.fail_as(ErrorClass::Parse);
+                return StreamStatus::Failed;
             }
         };
-        let tokenizer = Arc::new(tokenizer);
-        // load json file
-        let input = std::fs::read_to_string(&filepath)?;
-        let data: Vec<ConversationEntry> = serde_json::from_str(&input).expect("Unable to parse input file. Check that it is valid JSON and matches the expected format.");
-        // generate requests
-        let requests: Arc<Mutex<Vec<TextGenerationRequest>>> = Arc::from(Mutex::from(Vec::new()));
-        info!(
-            "Generating requests from {filepath}",
-            filepath = filepath.display().to_string()
-        );
-        let bar = ProgressBar::new(data.len() as u64);
-        bar.set_style(ProgressStyle::with_template(
-            "Tokenizing prompts [{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
-        )?);
-        split(data, entry_splitter).for_each(|subrange| {
-            for entry in subrange {
-                bar.inc(1);
-                if entry.conversations.is_empty() {
-                    continue;
+        if let Some(error) = event.error {
+            error!("Error from TGI: {error}");
+            aggregated_response.error_class = Some(ErrorClass::classify(
+                None,
+                &OpenAIError::Message(error),
+                event.error_type.as_deref(),
+            ));
+            aggregated_response.fail();
+            return StreamStatus::Failed;
+        }
+        if let Some(token) = event.token {
+            aggregated_response.add_tokens(1);
+            if !token.special {
+                *final_response += token.text.as_str();
+            }
+        }
+        if event.generated_text.is_some() {
+            if let Some(generated_tokens) = event.details.as_ref().and_then(|d| d.generated_tokens)
+            {
+                // the inter token latency divides by the count, so it never drops below the streamed tokens
+                if generated_tokens >= aggregated_response.times_to_tokens.len() as u64 {
+                    aggregated_response.num_generated_tokens = generated_tokens;
                 }
-                let system_prompt = entry
-                    .conversations
-                    .iter()
-                    .find(|c| c.role == "system")
-                    .map(|c| c.content.clone());
-                let system_prompt_tokens = match system_prompt {
-                    Some(ref prompt) => {
-                        let (_, num_tokens) = match tokenize_prompt(
-                            prompt.clone(),
-                            tokenizer.clone(),
-                            &TokenizeOptions::default(),
-                        ) {
-                            Ok((prompt, num_tokens)) => (prompt, num_tokens),
-                            Err(e) => {
-                                debug!("Error tokenizing system prompt: {e}");
-                                return;
-                            }
-                        };
-                        num_tokens
-                    }
-                    None => 0,
-                };
-                entry
-                    .conversations
-                    .iter()
-                    .filter(|c| c.role == "user")
-                    .for_each(|c| {
-                        let prompt = c.content.clone();
-                        let num_decode_tokens = decode_tokenize_opts.clone().map_or_else(
-                            || None,
-                            |opts| {
-                                opts.num_tokens.map(|num_tokens| {
-                                    sample_num_tokens(
-                                        num_tokens,
-                                        opts.min_tokens,
-                                        opts.max_tokens,
-                                        opts.variance,
-                                    )
-                                })
-                            },
-                        );
-                        match &prompt_tokenize_opts {
-                            None => {
-                                let (_, num_tokens) = match tokenize_prompt(
-                                    prompt.clone(),
-                                    tokenizer.clone(),
-                                    &TokenizeOptions::default(),
-                                ) {
-                                    Ok((prompt, num_tokens)) => (prompt, num_tokens),
-                                    Err(e) => {
-                                        debug!("Error tokenizing prompt: {e}");
-                                        return;
-                                    }
-                                };
-                                requests.lock().unwrap().push(TextGenerationRequest {
-                                    prompt,
-                                    num_prompt_tokens: num_tokens + system_prompt_tokens,
-                                    num_decode_tokens,
-                                    system_prompt: system_prompt.clone(),
-                                });
-                            }
-                            Some(options) => {
-                                // compute number of tokens to generate using a Gaussian distribution
-                                let (sampled_prompt, prompt_tokens) = match tokenize_prompt(
-                                    prompt.clone(),
-                                    tokenizer.clone(),
-                                    options,
-                                ) {
-                                    Ok(prompt) => prompt,
-                                    Err(e) => {
-                                        debug!("Error tokenizing prompt: {e}");
-                                        return;
-                                    }
-                                };
-                                requests.lock().unwrap().push(TextGenerationRequest {
-                                    prompt: sampled_prompt,
-                                    num_prompt_tokens: prompt_tokens + system_prompt_tokens,
-                                    num_decode_tokens,
-                                    system_prompt: system_prompt.clone(),
-                                });
-                            }
-                        }
-                    });
-                // TODO: check that we have enough requests
             }
-        });
-        let requests = requests.lock().unwrap();
-        info!(
-            "Generated {num_requests} requests",
-            num_requests = requests.len()
-        );
-        Ok(Self {
-            current_index: Arc::from(AtomicI64::new(0)),
-            requests: requests.to_vec(),
-        })
+            aggregated_response.server_details = event.details;
+            aggregated_response.stop();
+        }
+        StreamStatus::Continue
     }
+}
 
-    pub fn download_dataset(
-        repo_name: String,
-        filename: String,
-        hf_token: Option<String>,
-    ) -> anyhow::Result<PathBuf> {
-        let api = ApiBuilder::new().with_token(hf_token).build()?;
-        let repo = api.dataset(repo_name);
-        let dataset = repo.get(&filename)?;
-        Ok(dataset)
+#[async_trait]
+impl TextGenerationBackend for TgiTextGenerationBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let url = self.backend.url("/generate_stream");
+        let mut aggregated_response = TextGenerationAggregatedResponse::default();
+        match self.backend.scripted_request(self.request_body(&request)) {
+            Ok(scripted) => {
+                aggregated_response.generated_text = self
+                    .backend
+                    .stream_events(
+                        url,
+                        scripted,
+                        &request,
+                        &mut aggregated_response,
+                        Self::handle_chunk,
+                    )
+                    .await;
+                self.backend.classify_response(&mut aggregated_response);
+            }
+            Err(e) => {
+                error!("Error building request: {e}");
+                aggregated_response.start(request.clone());
+                aggregated_response.fail();
+            }
+        }
+        sender
+            .send(aggregated_response)
+            .await
+            .expect("Error sending response to channel");
     }
 }
 
-fn sample_num_tokens(num_tokens: u64, min_tokens: u64, max_tokens: u64, variance: u64) -> u64 {
-    let normal = rand_distr::Normal::new(num_tokens as f64, variance as f64).unwrap();
-    let mut num_tokens = normal.sample(&mut rand::thread_rng()) as u64;
-    if num_tokens < min_tokens {
-        num_tokens = min_tokens;
-    }
-    if num_tokens > max_tokens {
-        num_tokens = max_tokens;
-    }
-    num_tokens
+/// Generation details of vLLM's native API, when the server returns them
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct VllmDetails {
+    pub finish_reason: Option<String>,
+    pub cumulative_logprob: Option<f64>,
 }
 
-fn entry_splitter(
-    gen: Vec<ConversationEntry>,
-) -> (Vec<ConversationEntry>, Option<Vec<ConversationEntry>>) {
-    if gen.len() <= 2 {
-        return (gen, None);
+/// Line of vLLM's native `/generate` stream. `text` holds the prompt followed by the text
+/// generated so far; servers returning generation details send them as lists parallel to `text`.
+#[derive(Deserialize, Debug)]
+struct VllmStreamResponse {
+    text: Vec<String>,
+    #[serde(default)]
+    finish_reason: Vec<Option<String>>,
+    #[serde(default)]
+    cumulative_logprob: Vec<Option<f64>>,
+}
+
+/// Backend for vLLM's native `/generate` endpoint. The response streams JSON objects separated
+/// by newlines (or NUL bytes with older vLLM versions), each carrying the whole text generated so
+/// far; the new text of each object is tokenized to count the generated tokens.
+#[derive(Debug, Clone)]
+pub struct VllmTextGenerationBackend {
+    backend: OpenAITextGenerationBackend,
+}
+
+impl VllmTextGenerationBackend {
+    /// Requests are sent to `/generate` under the backend base URL
+    pub fn new(backend: OpenAITextGenerationBackend) -> Self {
+        Self { backend }
+    }
+
+    /// Prompt sent to the server, without chat template the system prompt is prepended to it
+    fn prompt(request: &TextGenerationRequest) -> String {
+        match &request.system_prompt {
+            None => request.prompt.clone(),
+            Some(system_prompt) => format!("{system_prompt}\n\n{}", request.prompt),
+        }
+    }
+
+    /// Native payload for a request, sampling parameters are set at the top level
+    pub fn request_body(&self, request: &TextGenerationRequest) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "prompt": Self::prompt(request),
+            "stream": true,
+            "temperature": 0.0,
+            "logprobs": 0,
+        });
+        if let Some(max_tokens) = request.num_decode_tokens {
+            body["max_tokens"] = serde_json::json!(max_tokens);
+        }
+        body
+    }
+
+    fn handle_line(
+        &self,
+        data: &[u8],
+        prompt: &str,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+        final_response: &mut String,
+    ) -> StreamStatus {
+        if data.iter().all(u8::is_ascii_whitespace) {
+            return StreamStatus::Continue;
+        }
+        let event: VllmStreamResponse = match serde_json::from_slice(data) {
+            Ok(event) => event,
+            Err(e) => {
+                error!("Error deserializing vLLM response: {e}");
+                aggregated_response.fail_as(ErrorClass::Parse);
+                return StreamStatus::Failed;
+            }
+        };
+        let Some(text) = event.text.first() else {
+            return StreamStatus::Continue;
+        };
+        let generated = text.strip_prefix(prompt).unwrap_or(text);
+        let content = generated.get(final_response.len()..).unwrap_or_default();
+        if !content.is_empty() {
+            let num_tokens = self
+                .backend
+                .tokenizer
+                .encode(content, false)
+                .map_or(1, |e| e.len().max(1)) as u64;
+            aggregated_response.add_tokens(num_tokens);
+            *final_response = generated.to_string();
+        }
+        let details = VllmDetails {
+            finish_reason: event.finish_reason.first().cloned().flatten(),
+            cumulative_logprob: event.cumulative_logprob.first().copied().flatten(),
+        };
+        if details.finish_reason.is_some() || details.cumulative_logprob.is_some() {
+            if details.finish_reason.is_some() {
+                aggregated_response.stop();
+            }
+            aggregated_response.vllm_details = Some(details);
+        }
+        StreamStatus::Continue
+    }
+
+    async fn stream(
+        &self,
+        request: &Arc<TextGenerationRequest>,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+    ) -> anyhow::Result<String> {
+        let url = self.backend.url("/generate");
+        let scripted = self.backend.scripted_request(self.request_body(request))?;
+        let req = self.backend.post(url, &scripted, aggregated_response);
+        // start timer
+        aggregated_response.start(request.clone());
+        let mut final_response = String::new();
+        let mut response = req.send().await?;
+        let status = response.status();
+        aggregated_response.http_status = Some(status.as_u16());
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            aggregated_response.error_class =
+                Some(ErrorClass::classify_body(Some(status.as_u16()), &body));
+            return Err(anyhow::anyhow!("{status}: {body}"));
+        }
+        let prompt = Self::prompt(request);
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n' || *b == 0) {
+                let line = buffer.drain(..=end).collect::<Vec<u8>>();
+                if let StreamStatus::Failed = self.handle_line(
+                    &line[..end],
+                    &prompt,
+                    aggregated_response,
+                    &mut final_response,
+                ) {
+                    return Ok(final_response);
+                }
+            }
+        }
+        if let StreamStatus::Failed =
+            self.handle_line(&buffer, &prompt, aggregated_response, &mut final_response)
+        {
+            return Ok(final_response);
+        }
+        if aggregated_response.num_generated_tokens == 0 {
+            // server sent no data
+            aggregated_response.fail();
+        } else if aggregated_response.end_time.is_none() {
+            // without finish reason, the end of the stream marks the end of the generation
+            aggregated_response.stop();
+        }
+        Ok(final_response)
     }
-    let middle = gen.len() / 2;
-    let (left, right) = gen.split_at(middle);
-    let left = left.to_vec();
-    let right = right.to_vec();
-    (left, Some(right))
 }
 
-impl TextRequestGenerator for ConversationTextRequestGenerator {
-    fn generate_request(&mut self) -> TextGenerationRequest {
-        let idx = self
-            .current_index
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        if idx >= (self.requests.len() - 1) as i64 {
-            self.current_index
-                .store(0, std::sync::atomic::Ordering::SeqCst);
+#[async_trait]
+impl TextGenerationBackend for VllmTextGenerationBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let mut aggregated_response = TextGenerationAggregatedResponse::default();
+        match self.stream(&request, &mut aggregated_response).await {
+            Ok(final_response) => {
+                aggregated_response.generated_text = final_response;
+                self.backend.classify_response(&mut aggregated_response);
+            }
+            Err(e) => {
+                error!("Error from vLLM: {e}");
+                if aggregated_response.start_time.is_none() {
+                    aggregated_response.start(request.clone());
+                }
+                aggregated_response.fail();
+            }
         }
-        self.requests[idx as usize].clone()
+        sender
+            .send(aggregated_response)
+            .await
+            .expect("Error sending response to channel");
     }
 }
 
-pub struct DummyTextRequestGenerator {}
+/// Event of Triton's `generate_stream` SSE protocol
+#[derive(Deserialize, Debug)]
+struct TritonStreamResponse {
+    text_output: Option<String>,
+    error: Option<String>,
+}
 
-impl DummyTextRequestGenerator {
-    pub fn new() -> Self {
-        Self {}
-    }
+/// Backend for Triton Inference Server's generate extension, as served by TensorRT-LLM
+/// deployments at `/v2/models/{model}/generate_stream`. Each event carries the new text. The
+/// stream has no final event, the last token received ends the generation.
+#[derive(Debug, Clone)]
+pub struct TritonTextGenerationBackend {
+    backend: OpenAITextGenerationBackend,
 }
 
-impl Default for DummyTextRequestGenerator {
-    fn default() -> Self {
-        Self::new()
+impl TritonTextGenerationBackend {
+    /// Requests are sent to the `generate_stream` endpoint of the backend model name
+    pub fn new(backend: OpenAITextGenerationBackend) -> Self {
+        Self { backend }
+    }
+
+    /// Generate extension payload for a request, without chat template the system prompt is
+    /// prepended to the prompt
+    pub fn request_body(&self, request: &TextGenerationRequest) -> serde_json::Value {
+        let text_input = match &request.system_prompt {
+            None => request.prompt.clone(),
+            Some(system_prompt) => format!("{system_prompt}\n\n{}", request.prompt),
+        };
+        let mut body = serde_json::json!({
+            "text_input": text_input,
+            "stream": true,
+            "temperature": 0.0,
+            "exclude_input_in_output": true,
+        });
+        if let Some(max_tokens) = request.num_decode_tokens {
+            body["max_tokens"] = serde_json::json!(max_tokens);
+        }
+        body
+    }
+
+    fn handle_chunk(
+        &self,
+        data: &str,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+        final_response: &mut String,
+    ) -> StreamStatus {
+        let event: TritonStreamResponse = match serde_json::from_str(data) {
+            Ok(event) => event,
+            Err(e) => {
+                error!("Error deserializing Triton response: {e}");
+                aggregated_response.fail_as(ErrorClass::Parse);
+                return StreamStatus::Failed;
+            }
+        };
+        if let Some(error) = event.error {
+            error!("Error from Triton: {error}");
+            aggregated_response.error_class = Some(ErrorClass::classify(
+                None,
+                &OpenAIError::Message(error),
+                None,
+            ));
+            aggregated_response.fail();
+            return StreamStatus::Failed;
+        }
+        let content = event.text_output.unwrap_or_default();
+        if content.is_empty() {
+            return StreamStatus::Continue;
+        }
+        let num_tokens = self
+            .backend
+            .tokenizer
+            .encode(content.as_str(), false)
+            .map_or(1, |e| e.len().max(1)) as u64;
+        aggregated_response.add_tokens(num_tokens);
+        *final_response += content.as_str();
+        // without final event, the generation ends with the last token received
+        aggregated_response.stop();
+        StreamStatus::Continue
     }
 }
 
-impl TextRequestGenerator for DummyTextRequestGenerator {
-    fn generate_request(&mut self) -> TextGenerationRequest {
-        TextGenerationRequest {
-            prompt: "Hello, world!".to_string(),
-            num_prompt_tokens: 2,
-            num_decode_tokens: Some(10),
-            system_prompt: None,
+#[async_trait]
+impl TextGenerationBackend for TritonTextGenerationBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let url = self.backend.url(&format!(
+            "/v2/models/{}/generate_stream",
+            self.backend.model_name
+        ));
+        let mut aggregated_response = TextGenerationAggregatedResponse::default();
+        match self.backend.scripted_request(self.request_body(&request)) {
+            Ok(scripted) => {
+                aggregated_response.generated_text = self
+                    .backend
+                    .stream_events(
+                        url,
+                        scripted,
+                        &request,
+                        &mut aggregated_response,
+                        |data, aggregated_response, final_response| {
+                            self.handle_chunk(data, aggregated_response, final_response)
+                        },
+                    )
+                    .await;
+                self.backend.classify_response(&mut aggregated_response);
+            }
+            Err(e) => {
+                error!("Error building request: {e}");
+                aggregated_response.start(request.clone());
+                aggregated_response.fail();
+            }
         }
+        sender
+            .send(aggregated_response)
+            .await
+            .expect("Error sending response to channel");
     }
 }
 
-fn tokenize_prompt(
-    prompt: String,
-    tokenizer: Arc<Tokenizer>,
-    options: &TokenizeOptions,
-) -> anyhow::Result<(String, u64)> {
-    let prompt_tokens = tokenizer
-        .encode(prompt.clone(), false)
-        .map_err(|_| anyhow::anyhow!("Error tokenizing prompt"))?;
-    match options.num_tokens {
-        None => {
-            // check if we have a min/max number of tokens, skip prompts that are too short or too long
-            if prompt_tokens.len() > options.max_tokens as usize
-                || prompt_tokens.len() < options.min_tokens as usize
-            {
-                return Err(anyhow::anyhow!(format!(
-                    "Prompt is too short or too long, skipping: {}<{}<{}",
-                    options.min_tokens,
-                    prompt_tokens.len(),
-                    options.max_tokens
-                )));
+/// Backend for Triton Inference Server's gRPC API, streaming generations of decoupled models
+/// (e.g. TensorRT-LLM ensembles) with `ModelStreamInfer`. It sends the same inputs as the
+/// generate extension, so both transports can be compared under the same load.
+#[cfg(feature = "grpc")]
+#[derive(Debug, Clone)]
+pub struct GrpcTextGenerationBackend {
+    backend: OpenAITextGenerationBackend,
+    channel: tonic::transport::Channel,
+}
+
+#[cfg(feature = "grpc")]
+impl GrpcTextGenerationBackend {
+    /// Connects lazily to the backend base URL, `grpc://host:port` being served as plaintext HTTP/2
+    pub fn try_new(backend: OpenAITextGenerationBackend) -> anyhow::Result<Self> {
+        let uri = backend.base_url.replacen("grpc://", "http://", 1);
+        let mut endpoint = tonic::transport::Endpoint::from_shared(uri)?;
+        if let Some(connect_timeout) = backend.options.timeouts.connect {
+            endpoint = endpoint.connect_timeout(connect_timeout);
+        }
+        let channel = endpoint.connect_lazy();
+        Ok(Self { backend, channel })
+    }
+
+    async fn stream(
+        &self,
+        request: &TextGenerationRequest,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+        final_response: &mut String,
+    ) -> anyhow::Result<()> {
+        let text_input = match &request.system_prompt {
+            None => request.prompt.clone(),
+            Some(system_prompt) => format!("{system_prompt}\n\n{}", request.prompt),
+        };
+        let infer_request = crate::grpc::stream_request(
+            &self.backend.model_name,
+            &text_input,
+            request.num_decode_tokens,
+        );
+        // the request stream stays open until the final response, as closing it early may cancel
+        // the generation
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tx.send(infer_request).await?;
+        let mut grpc_request = tonic::Request::new(tokio_stream::wrappers::ReceiverStream::new(rx));
+        if !self.backend.api_key.is_empty() {
+            grpc_request.metadata_mut().insert(
+                "authorization",
+                format!("Bearer {}", self.backend.api_key).parse()?,
+            );
+        }
+        for (name, value) in &self.backend.options.headers {
+            grpc_request.metadata_mut().insert(
+                tonic::metadata::MetadataKey::from_bytes(name.to_lowercase().as_bytes())?,
+                value.parse()?,
+            );
+        }
+        let mut client = tonic::client::Grpc::new(self.channel.clone());
+        client.ready().await?;
+        let codec = tonic::codec::ProstCodec::<
+            crate::grpc::ModelInferRequest,
+            crate::grpc::ModelStreamInferResponse,
+        >::default();
+        let path =
+            tonic::codegen::http::uri::PathAndQuery::from_static(crate::grpc::MODEL_STREAM_INFER);
+        let mut responses = client
+            .streaming(grpc_request, path, codec)
+            .await
+            .inspect_err(|status| {
+                aggregated_response.error_class = Some(grpc_error_class(status));
+            })?
+            .into_inner();
+        loop {
+            let deadline = self.backend.first_token_deadline(aggregated_response);
+            let Some(response) =
+                until(deadline, responses.message())
+                    .await?
+                    .inspect_err(|status| {
+                        aggregated_response.error_class = Some(grpc_error_class(status));
+                    })?
+            else {
+                break;
+            };
+            if !response.error_message.is_empty() {
+                aggregated_response.error_class = Some(ErrorClass::classify(
+                    None,
+                    &OpenAIError::Message(response.error_message.clone()),
+                    None,
+                ));
+                return Err(anyhow::anyhow!("{}", response.error_message));
+            }
+            let Some(infer_response) = response.infer_response else {
+                continue;
+            };
+            let content = crate::grpc::output_text(&infer_response)?;
+            if !content.is_empty() {
+                let num_tokens = self
+                    .backend
+                    .tokenizer
+                    .encode(content.as_str(), false)
+                    .map_or(1, |e| e.len().max(1)) as u64;
+                aggregated_response.add_tokens(num_tokens);
+                *final_response += content.as_str();
+                aggregated_response.stop();
+            }
+            if crate::grpc::is_final(&infer_response) {
+                break;
             }
-            Ok((prompt, prompt_tokens.len() as u64))
         }
-        Some(num_tokens) => {
-            if prompt_tokens.len() < num_tokens as usize {
-                return Err(anyhow::anyhow!(format!(
-                    "Prompt is too short to tokenize: {}<{}",
-                    prompt_tokens.len(),
-                    num_tokens
-                )));
+        drop(tx);
+        Ok(())
+    }
+}
+
+/// Classify a gRPC status like the HTTP status it maps to, deadlines and connection timeouts
+/// being timeouts
+#[cfg(feature = "grpc")]
+fn grpc_error_class(status: &tonic::Status) -> ErrorClass {
+    let mut source = std::error::Error::source(status);
+    while let Some(error) = source {
+        if error
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::TimedOut)
+        {
+            return ErrorClass::Timeout;
+        }
+        source = error.source();
+    }
+    if status.code() == tonic::Code::DeadlineExceeded {
+        return ErrorClass::Timeout;
+    }
+    let http_status = match status.code() {
+        tonic::Code::ResourceExhausted => Some(429),
+        tonic::Code::InvalidArgument
+        | tonic::Code::NotFound
+        | tonic::Code::FailedPrecondition
+        | tonic::Code::OutOfRange => Some(400),
+        tonic::Code::Internal | tonic::Code::Unavailable | tonic::Code::Unknown => Some(500),
+        _ => None,
+    };
+    ErrorClass::classify(
+        http_status,
+        &OpenAIError::Message(status.message().to_string()),
+        None,
+    )
+}
+
+#[cfg(feature = "grpc")]
+#[async_trait]
+impl TextGenerationBackend for GrpcTextGenerationBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let mut aggregated_response = TextGenerationAggregatedResponse::default();
+        let mut final_response = String::new();
+        aggregated_response.start(request.clone());
+        let stream = self.stream(&request, &mut aggregated_response, &mut final_response);
+        match tokio::time::timeout(self.backend.total_timeout(), stream).await {
+            Ok(Ok(())) => {
+                if aggregated_response.num_generated_tokens == 0
+                    || aggregated_response.end_time.is_none()
+                {
+                    warn!("gRPC stream ended before completion. Received :: {num_tokens}/{max_tokens} tokens. Response: {final_response}", num_tokens = aggregated_response.num_generated_tokens, max_tokens = request.num_decode_tokens.unwrap_or(0));
+                    aggregated_response.fail();
+                }
+            }
+            Ok(Err(e)) if e.is::<tokio::time::error::Elapsed>() => {
+                error!("No token received before the first token timeout");
+                aggregated_response.time_out();
+            }
+            Ok(Err(e)) => {
+                error!("Error from gRPC stream: {e}");
+                aggregated_response.fail();
+            }
+            Err(_) => {
+                error!("gRPC request timed out");
+                aggregated_response.time_out();
             }
-            let tokens = prompt_tokens
-                .get_ids()
-                .iter()
-                .take(num_tokens as usize)
-                .copied()
-                .collect::<Vec<u32>>();
-            let prompt = tokenizer.decode(&tokens, true).unwrap();
-            Ok((prompt, num_tokens))
         }
+        aggregated_response.generated_text = final_response;
+        self.backend.classify_response(&mut aggregated_response);
+        sender
+            .send(aggregated_response)
+            .await
+            .expect("Error sending response to channel");
     }
 }
 
+#[derive(Deserialize)]
+struct RealtimeEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    delta: Option<String>,
+}
+
+/// Experimental backend for OpenAI-compatible realtime speech endpoints over WebSocket.
+/// The prompt is sent as a user message and each `response.audio.delta` event counts as one
+/// generated chunk: time to first token is then the time to first audio chunk, and inter token
+/// latency the audio chunk cadence. The audio transcript is kept as generated text.
 #[derive(Debug, Clone)]
-pub struct TextGenerationAggregatedResponse {
-    pub start_time: Option<tokio::time::Instant>,
-    pub end_time: Option<tokio::time::Instant>,
-    pub num_generated_tokens: u64,
-    pub num_prompt_tokens: u64,
-    pub times_to_tokens: Vec<std::time::Duration>,
-    last_received_token_time: tokio::time::Instant,
-    pub failed: bool,
-    pub ended: bool,
+pub struct RealtimeAudioBackend {
+    backend: OpenAITextGenerationBackend,
 }
 
-impl Default for TextGenerationAggregatedResponse {
-    fn default() -> Self {
-        Self {
-            start_time: None,
-            end_time: None,
-            num_generated_tokens: 0,
-            num_prompt_tokens: 0,
-            times_to_tokens: Vec::new(),
-            last_received_token_time: tokio::time::Instant::now(),
-            failed: false,
-            ended: false,
+impl RealtimeAudioBackend {
+    /// The backend base URL is used as the realtime endpoint, including its `model` query parameter
+    pub fn new(backend: OpenAITextGenerationBackend) -> Self {
+        Self { backend }
+    }
+
+    async fn stream(
+        &self,
+        request: &TextGenerationRequest,
+        idempotency_key: Option<String>,
+        aggregated_response: &mut TextGenerationAggregatedResponse,
+        final_response: &mut String,
+    ) -> anyhow::Result<()> {
+        let mut ws_request = websocket_request(&self.backend, idempotency_key)?;
+        ws_request
+            .headers_mut()
+            .insert("OpenAI-Beta", HeaderValue::from_static("realtime=v1"));
+        let (mut ws, _) = until(
+            self.backend.connect_deadline(),
+            tokio_tungstenite::connect_async(ws_request),
+        )
+        .await??;
+        trace!("Realtime connection opened");
+        let item = serde_json::json!({
+            "type": "conversation.item.create",
+            "item": {
+                "type": "message",
+                "role": "user",
+                "content": [{"type": "input_text", "text": request.prompt}],
+            },
+        });
+        ws.send(Message::Text(item.to_string())).await?;
+        let mut response = serde_json::json!({"modalities": ["audio", "text"]});
+        if let Some(system_prompt) = &request.system_prompt {
+            response["instructions"] = serde_json::json!(system_prompt);
+        }
+        if let Some(max_tokens) = request.num_decode_tokens {
+            response["max_output_tokens"] = serde_json::json!(max_tokens);
         }
+        let create = serde_json::json!({"type": "response.create", "response": response});
+        ws.send(Message::Text(create.to_string())).await?;
+        while let Some(message) = until(
+            self.backend.first_token_deadline(aggregated_response),
+            ws.next(),
+        )
+        .await?
+        {
+            let data = match message? {
+                Message::Text(data) => data,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            let event: RealtimeEvent = serde_json::from_str(&data)?;
+            match event.kind.as_str() {
+                "response.audio.delta" => aggregated_response.add_tokens(1),
+                "response.audio_transcript.delta" => {
+                    *final_response += event.delta.unwrap_or_default().as_str()
+                }
+                "response.done" => {
+                    aggregated_response.stop();
+                    break;
+                }
+                "error" => {
+                    error!("Error from realtime API: {data}");
+                    aggregated_response.fail();
+                    break;
+                }
+                kind => trace!("Ignoring realtime event {kind}"),
+            }
+        }
+        let _ = ws.close(None).await;
+        Ok(())
     }
 }
 
-impl TextGenerationAggregatedResponse {
-    pub fn new_as_ended() -> Self {
-        Self {
-            start_time: None,
-            end_time: None,
-            num_generated_tokens: 0,
-            num_prompt_tokens: 0,
-            times_to_tokens: Vec::new(),
-            last_received_token_time: tokio::time::Instant::now(),
-            failed: false,
-            ended: true,
+#[async_trait]
+impl TextGenerationBackend for RealtimeAudioBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let mut aggregated_response = TextGenerationAggregatedResponse::default();
+        let mut final_response = String::new();
+        if self.backend.options.idempotency_key_header.is_some() {
+            aggregated_response.idempotency_key = Some(idempotency_key());
         }
+        let idempotency_key = aggregated_response.idempotency_key.clone();
+        // start timer
+        aggregated_response.start(request.clone());
+        let stream = self.stream(
+            &request,
+            idempotency_key,
+            &mut aggregated_response,
+            &mut final_response,
+        );
+        let result = tokio::time::timeout(self.backend.total_timeout(), stream).await;
+        check_websocket_result(result, &request, &mut aggregated_response, &final_response);
+        aggregated_response.generated_text = final_response;
+        sender
+            .send(aggregated_response.clone())
+            .await
+            .expect("Error sending response to channel");
     }
-    fn start(&mut self, num_prompt_tokens: u64) {
-        self.start_time = Some(tokio::time::Instant::now());
-        self.last_received_token_time = tokio::time::Instant::now();
-        self.num_prompt_tokens = num_prompt_tokens;
+}
+
+/// Retries of requests failing with a transient error before receiving any token
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct RetryPolicy {
+    /// Retries per request, 0 disables retries
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each next one
+    #[serde(rename = "backoff_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub backoff: time::Duration,
+    /// Whether retries reuse the idempotency key of the request
+    pub idempotency_key: IdempotencyKeyMode,
+}
+
+/// Idempotency keys sent for the attempts of a request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdempotencyKeyMode {
+    /// One key per request, reused by its retries so that gateways can deduplicate them
+    #[default]
+    PerRequest,
+    /// A new key per attempt, for gateways rejecting the replay of a key
+    PerAttempt,
+}
+
+impl FromStr for IdempotencyKeyMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "per-request" => Ok(IdempotencyKeyMode::PerRequest),
+            "per-attempt" => Ok(IdempotencyKeyMode::PerAttempt),
+            _ => Err(anyhow::anyhow!("Unknown idempotency key mode: {s}")),
+        }
+    }
+}
+
+impl std::fmt::Display for IdempotencyKeyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdempotencyKeyMode::PerRequest => write!(f, "per-request"),
+            IdempotencyKeyMode::PerAttempt => write!(f, "per-attempt"),
+        }
+    }
+}
+
+/// Longest delay between two attempts of a request
+const MAX_RETRY_BACKOFF: time::Duration = time::Duration::from_secs(30);
+
+impl RetryPolicy {
+    /// Delay before the retry `retry`, counted from 0
+    fn backoff(&self, retry: u32) -> time::Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(MAX_RETRY_BACKOFF)
+    }
+}
+
+/// Retries requests of the wrapped backend failing with a transient error (rate limit, server or
+/// connection error) before any token was received, as streams can't be resumed. The response
+/// of the last attempt is sent, with the causes of the retried ones, so its latencies don't
+/// include the failed attempts. Backends sending several responses per request (compared
+/// streaming) are retried as a whole if any of them failed so. The attempts of a request send
+/// the same idempotency key with [`IdempotencyKeyMode::PerRequest`].
+#[derive(Clone)]
+pub struct RetryingBackend {
+    backend: Box<dyn TextGenerationBackend + Send + Sync>,
+    policy: RetryPolicy,
+}
+
+impl RetryingBackend {
+    pub fn new(backend: Box<dyn TextGenerationBackend + Send + Sync>, policy: RetryPolicy) -> Self {
+        Self { backend, policy }
     }
+}
+
+#[async_trait]
+impl TextGenerationBackend for RetryingBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let mut retries = Vec::new();
+        let mut retry_keys = Vec::new();
+        let key = random_uuid();
+        loop {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+            let collect = async {
+                let mut responses = Vec::new();
+                while let Some(response) = rx.recv().await {
+                    responses.push(response);
+                }
+                responses
+            };
+            let attempt = self.backend.generate(request.clone(), tx);
+            let ((), responses) = match self.policy.idempotency_key {
+                IdempotencyKeyMode::PerRequest => {
+                    tokio::join!(IDEMPOTENCY_KEY.scope(key.clone(), attempt), collect)
+                }
+                IdempotencyKeyMode::PerAttempt => tokio::join!(attempt, collect),
+            };
+            let transient = responses.iter().find(|response| {
+                let cause = response.error_class.unwrap_or(ErrorClass::Other);
+                response.failed && response.num_generated_tokens == 0 && cause.is_transient()
+            });
+            if let Some(failed) = transient {
+                if retries.len() < self.policy.max_retries as usize {
+                    let cause = failed.error_class.unwrap_or(ErrorClass::Other);
+                    let backoff = self.policy.backoff(retries.len() as u32);
+                    debug!("Retrying request failed with a {cause} error in {backoff:?}");
+                    retries.push(cause);
+                    retry_keys.extend(failed.idempotency_key.clone());
+                    tokio::time::sleep(backoff).await;
+                    continue;
+                }
+            }
+            for mut response in responses {
+                response.retries = retries.clone();
+                response.retry_idempotency_keys = retry_keys.clone();
+                sender
+                    .send(response)
+                    .await
+                    .expect("Error sending response to channel");
+            }
+            return;
+        }
+    }
+}
+
+/// Identity requests are sent as, to benchmark the fairness and per-tenant rate limits of
+/// serving gateways
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Tenant {
+    pub name: String,
+    /// Share of the requests sent as this tenant, relative to the weights of the other tenants
+    pub weight: u32,
+    /// API key replacing the one of the run, not recorded in the report
+    #[serde(skip)]
+    pub api_key: Option<String>,
+    /// Headers sent in addition to the ones of the run, only names are recorded in the report
+    #[serde(serialize_with = "tenant_header_names")]
+    pub headers: Vec<(String, String)>,
+}
+
+fn tenant_header_names<S: serde::Serializer>(
+    headers: &[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(headers.iter().map(|(name, _)| name))
+}
+
+impl Tenant {
+    /// Copy of the backend sending requests with the credentials and headers of the tenant
+    pub fn backend(&self, backend: &OpenAITextGenerationBackend) -> OpenAITextGenerationBackend {
+        let mut backend = backend.clone();
+        if let Some(api_key) = &self.api_key {
+            backend.api_key = api_key.clone();
+        }
+        backend.options.headers.extend(self.headers.iter().cloned());
+        backend
+    }
+}
+
+impl FromStr for Tenant {
+    type Err = anyhow::Error;
+
+    /// Parse `name=<name>[,weight=<n>][,api_key=<key>][,header=<key>:<value>...]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut tenant = Tenant {
+            name: String::new(),
+            weight: 1,
+            api_key: None,
+            headers: Vec::new(),
+        };
+        for field in s.split(',') {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("Invalid tenant field: {field}"))?;
+            match key.trim() {
+                "name" => name = Some(value.trim().to_string()),
+                "weight" => tenant.weight = value.trim().parse()?,
+                "api_key" => tenant.api_key = Some(value.trim().to_string()),
+                "header" => match value.split_once(':') {
+                    Some((header, value)) if !header.trim().is_empty() => tenant
+                        .headers
+                        .push((header.trim().to_string(), value.trim().to_string())),
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "Invalid tenant header: {value}, expected `key:value`"
+                        ))
+                    }
+                },
+                _ => return Err(anyhow::anyhow!("Unknown tenant field: {key}")),
+            }
+        }
+        tenant.name = name
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Tenant name must be specified: {s}"))?;
+        if tenant.weight == 0 {
+            return Err(anyhow::anyhow!("Tenant weight must be greater than 0: {s}"));
+        }
+        Ok(tenant)
+    }
+}
+
+/// Sends each request as one of the tenants, in proportion to their weights, through the backend
+/// of the tenant. Responses are tagged with the tenant name to report per-tenant metrics.
+#[derive(Clone)]
+pub struct TenantBackend {
+    tenants: Vec<(String, u32, Box<dyn TextGenerationBackend + Send + Sync>)>,
+    total_weight: u64,
+    /// Requests sent so far by all clones, spreading requests deterministically over the tenants
+    sent: Arc<AtomicU64>,
+}
+
+impl TenantBackend {
+    pub fn new(tenants: Vec<(Tenant, Box<dyn TextGenerationBackend + Send + Sync>)>) -> Self {
+        let total_weight = tenants.iter().map(|(t, _)| t.weight as u64).sum();
+        Self {
+            tenants: tenants
+                .into_iter()
+                .map(|(tenant, backend)| (tenant.name, tenant.weight, backend))
+                .collect(),
+            total_weight,
+            sent: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Index of the tenant of the `n`th request: each cycle of `total_weight` requests gives
+    /// `weight` consecutive requests to each tenant
+    fn tenant(&self, n: u64) -> usize {
+        let mut slot = n % self.total_weight;
+        for (i, (_, weight, _)) in self.tenants.iter().enumerate() {
+            if slot < *weight as u64 {
+                return i;
+            }
+            slot -= *weight as u64;
+        }
+        self.tenants.len() - 1
+    }
+}
+
+#[async_trait]
+impl TextGenerationBackend for TenantBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let (name, _, backend) =
+            &self.tenants[self.tenant(self.sent.fetch_add(1, Ordering::SeqCst))];
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<TextGenerationAggregatedResponse>(1);
+        // backends may send several responses per request, forwarded as they come
+        let forward = async {
+            while let Some(mut response) = rx.recv().await {
+                response.tenant = Some(name.clone());
+                sender
+                    .send(response)
+                    .await
+                    .expect("Error sending response to channel");
+            }
+        };
+        tokio::join!(backend.generate(request, tx), forward);
+    }
+}
+
+/// Log target of the sampled requests, see [`TraceSamplingBackend`]
+pub const TRACE_LOG_TARGET: &str = "inference_benchmarker::trace";
+
+/// Logs the full details of a random sample of the requests and their responses at debug level,
+/// for visibility into live traffic without logging every request at high rates
+#[derive(Clone)]
+pub struct TraceSamplingBackend {
+    backend: Box<dyn TextGenerationBackend + Send + Sync>,
+    /// Probability of a request to be logged
+    sample_rate: f64,
+}
+
+impl TraceSamplingBackend {
+    pub fn new(backend: Box<dyn TextGenerationBackend + Send + Sync>, sample_rate: f64) -> Self {
+        Self {
+            backend,
+            sample_rate,
+        }
+    }
+}
+
+#[async_trait]
+impl TextGenerationBackend for TraceSamplingBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        if !rand::thread_rng().gen_bool(self.sample_rate) {
+            return self.backend.generate(request, sender).await;
+        }
+        // correlates the request with its responses among concurrent logs
+        let trace_id = format!("{:016x}", rand::thread_rng().gen::<u64>());
+        debug!(
+            target: TRACE_LOG_TARGET,
+            "Request {trace_id}: {} prompt tokens, {:?} max tokens, system prompt: {:?}, prompt: {:?}",
+            request.num_prompt_tokens,
+            request.num_decode_tokens,
+            request.system_prompt,
+            request.prompt
+        );
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<TextGenerationAggregatedResponse>(1);
+        let forward = async {
+            while let Some(response) = rx.recv().await {
+                debug!(
+                    target: TRACE_LOG_TARGET,
+                    "Response {trace_id}: failed: {}, error: {:?}, HTTP status: {:?}, retries: {:?}, tenant: {:?}, {} generated tokens, TTFT: {:?}, E2E latency: {:?}, text: {:?}",
+                    response.failed,
+                    response.error_class,
+                    response.http_status,
+                    response.retries,
+                    response.tenant,
+                    response.num_generated_tokens,
+                    response.time_to_first_token(),
+                    response.e2e_latency(),
+                    response.generated_text
+                );
+                sender
+                    .send(response)
+                    .await
+                    .expect("Error sending response to channel");
+            }
+        };
+        tokio::join!(self.backend.generate(request, tx), forward);
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct DummyTextGenerationBackend {
+    time_to_generate: time::Duration,
+}
+
+#[allow(dead_code)]
+impl DummyTextGenerationBackend {
+    pub fn new(time_to_generate: time::Duration) -> Self {
+        Self { time_to_generate }
+    }
+}
+
+impl Default for DummyTextGenerationBackend {
+    fn default() -> Self {
+        Self::new(time::Duration::from_secs(1))
+    }
+}
+
+#[async_trait]
+impl TextGenerationBackend for DummyTextGenerationBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<crate::requests::TextGenerationAggregatedResponse>,
+    ) {
+        let mut response = TextGenerationAggregatedResponse::default();
+        response.start(request.clone());
+        let num_tokens = request.num_decode_tokens.unwrap_or(10);
+        let time_per_token = self
+            .time_to_generate
+            .checked_div(num_tokens as u32)
+            .unwrap();
+        for _ in 0..num_tokens {
+            sleep(time_per_token).await;
+            response.add_tokens(1);
+        }
+        response.stop();
+        sender
+            .send(response.clone())
+            .await
+            .expect("Error sending response to channel");
+    }
+}
+
+/// Source of the requests to send. It is shared by all VUs of a benchmark without a lock, so
+/// implementations must be safe to call concurrently.
+pub trait TextRequestGenerator: Send + Sync {
+    fn generate_request(&self) -> TextGenerationRequest;
+
+    /// Factor applied to the sampled decode targets from the completion lengths observed so far,
+    /// `None` when the targets aren't adapted
+    fn decode_scale(&self) -> Option<f64> {
+        None
+    }
+}
+
+#[derive(Clone)]
+pub struct ConversationTextRequestGenerator {
+    pub requests: Vec<TextGenerationRequest>,
+    /// Applied to the prompt of each generated request
+    pub prompt_template: Option<PromptTemplate>,
+    current_index: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "datasets")]
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Conversation {
+    pub role: String,
+    pub content: String,
+}
+
+#[cfg(feature = "datasets")]
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ConversationEntry {
+    pub id: String,
+    pub conversations: Vec<Conversation>,
+    /// Expected answer to the user prompts of the entry
+    #[serde(default)]
+    pub answer: Option<String>,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct TokenizeOptions {
+    pub num_tokens: Option<u64>,
+    pub min_tokens: u64,
+    pub max_tokens: u64,
+    pub variance: u64,
+}
+
+impl TokenizeOptions {
+    pub fn new() -> Self {
+        Self {
+            num_tokens: None,
+            min_tokens: 0,
+            max_tokens: u64::MAX,
+            variance: 0,
+        }
+    }
+}
+
+impl Default for TokenizeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for TokenizeOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "num_tokens={num_tokens:?},min_tokens={min_tokens},max_tokens={max_tokens},variance={variance}",
+            num_tokens = self.num_tokens,
+            min_tokens = self.min_tokens,
+            max_tokens = self.max_tokens,
+            variance = self.variance
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplateSegment {
+    Text(String),
+    Prompt,
+    Index,
+    Uuid,
+    Timestamp,
+}
+
+/// Template wrapped around each dataset prompt so that every request is unique and server-side
+/// caches or dedup layers can't serve it from a previous one. `{prompt}` is the dataset prompt,
+/// `{index}` the request index in the run, `{uuid}` a random UUID and `{timestamp}` the RFC 3339
+/// time the request is generated. `{{` and `}}` are literal braces and `\n` a newline. The
+/// injected text isn't counted in the prompt tokens.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PromptTemplate {
+    segments: Vec<TemplateSegment>,
+    template: String,
+}
+
+impl PromptTemplate {
+    /// Template for a uniqueness mode: `none`, `prefix` (a UUID line before the prompt, which
+    /// also defeats prefix caching) or `suffix` (after the prompt, keeping prefix caching), or a
+    /// custom template, which can't be combined with another mode than `none`
+    pub fn try_new(template: Option<&str>, uniqueness: &str) -> anyhow::Result<Option<Self>> {
+        let template = match (uniqueness.to_lowercase().as_str(), template) {
+            ("none", template) => template,
+            ("prefix", None) => Some("{uuid}\n{prompt}"),
+            ("suffix", None) => Some("{prompt}\n{uuid}"),
+            ("prefix" | "suffix", Some(_)) => {
+                return Err(anyhow::anyhow!(
+                    "A prompt template can't be combined with the {uniqueness} uniqueness mode"
+                ))
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unknown prompt uniqueness mode: {uniqueness}"
+                ))
+            }
+        };
+        template.map(str::parse).transpose()
+    }
+
+    pub fn render(&self, prompt: &str, index: u64) -> String {
+        let mut rendered = String::with_capacity(prompt.len() + self.template.len());
+        for segment in &self.segments {
+            match segment {
+                TemplateSegment::Text(text) => rendered.push_str(text),
+                TemplateSegment::Prompt => rendered.push_str(prompt),
+                TemplateSegment::Index => rendered.push_str(&index.to_string()),
+                TemplateSegment::Uuid => rendered.push_str(&random_uuid()),
+                TemplateSegment::Timestamp => rendered.push_str(
+                    &chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+                ),
+            }
+        }
+        rendered
+    }
+}
+
+impl FromStr for PromptTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut segments = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '\\' if chars.as_str().starts_with('n') => {
+                    chars.next();
+                    text.push('\n');
+                }
+                '{' => {
+                    let (name, rest) = chars.as_str().split_once('}').ok_or_else(|| {
+                        anyhow::anyhow!("Unclosed variable in prompt template: {template}")
+                    })?;
+                    let segment = match name {
+                        "prompt" => TemplateSegment::Prompt,
+                        "index" => TemplateSegment::Index,
+                        "uuid" => TemplateSegment::Uuid,
+                        "timestamp" => TemplateSegment::Timestamp,
+                        _ => {
+                            return Err(anyhow::anyhow!(
+                                "Unknown variable {{{name}}} in prompt template"
+                            ))
+                        }
+                    };
+                    if !text.is_empty() {
+                        segments.push(TemplateSegment::Text(std::mem::take(&mut text)));
+                    }
+                    segments.push(segment);
+                    chars = rest.chars();
+                }
+                '}' => {
+                    return Err(anyhow::anyhow!(
+                        "Unmatched '}}' in prompt template: {template}"
+                    ))
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            segments.push(TemplateSegment::Text(text));
+        }
+        if !segments.contains(&TemplateSegment::Prompt) {
+            return Err(anyhow::anyhow!(
+                "Prompt template must contain {{prompt}}: {template}"
+            ));
+        }
+        Ok(Self {
+            segments,
+            template: template.to_string(),
+        })
+    }
+}
+
+impl Display for PromptTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.template)
+    }
+}
+
+impl Serialize for PromptTemplate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.template)
+    }
+}
+
+/// Random version 4 UUID, identifying runs and requests
+pub fn random_uuid() -> String {
+    let mut bits = rand::thread_rng().gen::<u128>();
+    // version 4 in the 13th hex digit, variant 0b10 in the top bits of the 17th
+    bits = bits & !(0xf << 76) | (0x4 << 76);
+    bits = bits & !(0x3 << 62) | (0x2 << 62);
+    let hex = format!("{bits:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[derive(Deserialize)]
+struct ModelList {
+    data: Vec<ModelCard>,
+}
+
+/// Model of the `/v1/models` response, servers report the context length under different names
+#[derive(Deserialize)]
+struct ModelCard {
+    id: String,
+    #[serde(alias = "context_length", alias = "max_context_length")]
+    max_model_len: Option<u64>,
+}
+
+/// Context length of the benchmarked model from the server `/v1/models` metadata (vLLM and other
+/// servers reporting `max_model_len` or `context_length`), else from the `max_position_embeddings`
+/// of the model config on the Hugging Face Hub
+pub async fn fetch_max_context_length(
+    backend: &OpenAITextGenerationBackend,
+    model_config_repo: &str,
+    hf_token: Option<String>,
+) -> Option<u64> {
+    let url = format!("{}/v1/models", backend.base_url);
+    let models = async {
+        let mut request = backend
+            .client
+            .get(url)
+            .header("Authorization", format!("Bearer {}", backend.api_key))
+            .timeout(time::Duration::from_secs(10));
+        for (name, value) in &backend.options.headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await?.error_for_status()?;
+        response.json::<ModelList>().await
+    };
+    match models.await {
+        Ok(models) => {
+            let model = models
+                .data
+                .iter()
+                .find(|m| m.id == backend.model_name)
+                .or(models.data.first());
+            if let Some(max_model_len) = model.and_then(|m| m.max_model_len) {
+                return Some(max_model_len);
+            }
+        }
+        Err(e) => debug!("Can't get the model context length from the server: {e}"),
+    }
+    model_config_context_length(model_config_repo, hf_token).await
+}
+
+#[cfg(feature = "hub")]
+async fn model_config_context_length(repo: &str, hf_token: Option<String>) -> Option<u64> {
+    #[derive(Deserialize)]
+    struct ModelConfig {
+        max_position_embeddings: Option<u64>,
+    }
+    let path = crate::download::download_file(
+        Repo::model(repo.to_string()),
+        "config.json",
+        hf_token,
+        None,
+    )
+    .await
+    .map_err(|e| debug!("Can't download the model config of {repo}: {e}"))
+    .ok()?;
+    let config = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str::<ModelConfig>(&config)
+        .ok()?
+        .max_position_embeddings
+}
+
+#[cfg(not(feature = "hub"))]
+async fn model_config_context_length(_repo: &str, _hf_token: Option<String>) -> Option<u64> {
+    None
+}
+
+/// Load a tokenizer from a local `tokenizer.json` file or from the Hugging Face Hub, falling back
+/// to `fallback` if it can't be loaded. Returns the tokenizer and the name of the tokenizer
+/// actually loaded.
+pub async fn load_tokenizer(
+    name: &str,
+    revision: Option<String>,
+    fallback: Option<String>,
+    hf_token: Option<String>,
+    event_bus: Option<&UnboundedSender<crate::benchmark::Event>>,
+) -> anyhow::Result<(Tokenizer, String)> {
+    let err = match tokenizer_from_file_or_hub(name, revision, hf_token.clone(), event_bus).await {
+        Ok(tokenizer) => return Ok((tokenizer, name.to_string())),
+        Err(e) => tokenizer_error(name, e),
+    };
+    let Some(fallback) = fallback else {
+        return Err(err);
+    };
+    warn!("{err}. Using fallback tokenizer {fallback}");
+    match tokenizer_from_file_or_hub(&fallback, None, hf_token, event_bus).await {
+        Ok(tokenizer) => Ok((tokenizer, fallback)),
+        Err(e) => Err(tokenizer_error(&fallback, e)),
+    }
+}
+
+async fn tokenizer_from_file_or_hub(
+    name: &str,
+    revision: Option<String>,
+    hf_token: Option<String>,
+    event_bus: Option<&UnboundedSender<crate::benchmark::Event>>,
+) -> Result<Tokenizer, String> {
+    if std::path::Path::new(name).is_file() {
+        return Tokenizer::from_file(name).map_err(|e| e.to_string());
+    }
+    #[cfg(feature = "hub")]
+    {
+        let repo = Repo::with_revision(
+            name.to_string(),
+            hf_hub::RepoType::Model,
+            revision.unwrap_or("main".to_string()),
+        );
+        let path = crate::download::download_file(repo, "tokenizer.json", hf_token, event_bus)
+            .await
+            .map_err(|e| e.to_string())?;
+        Tokenizer::from_file(path).map_err(|e| e.to_string())
+    }
+    #[cfg(not(feature = "hub"))]
+    {
+        let _ = (revision, hf_token, event_bus);
+        Err("not a local tokenizer file, and Hub downloads require the `hub` feature".to_string())
+    }
+}
+
+fn tokenizer_error(name: &str, error: String) -> anyhow::Error {
+    // the Hub answers 401 for gated models when no token is given, 403 when access is not granted
+    if error.contains("401") || error.contains("403") {
+        anyhow::anyhow!(
+            "Error loading tokenizer {name}: the model is gated or private ({error}). \
+            Request access on https://huggingface.co/{name} and set HF_TOKEN, \
+            or use --tokenizer-fallback with a compatible open tokenizer"
+        )
+    } else {
+        anyhow::anyhow!("Error loading tokenizer {name}: {error}")
+    }
+}
+
+#[cfg(feature = "datasets")]
+impl ConversationTextRequestGenerator {
+    pub fn load(
+        filepath: PathBuf,
+        tokenizer: Arc<Tokenizer>,
+        prompt_tokenize_opts: Option<TokenizeOptions>,
+        decode_tokenize_opts: Option<TokenizeOptions>,
+    ) -> anyhow::Result<Self> {
+        // load json file
+        let input = std::fs::read_to_string(&filepath)?;
+        let data: Vec<ConversationEntry> = serde_json::from_str(&input).expect("Unable to parse input file. Check that it is valid JSON and matches the expected format.");
+        // generate requests
+        let requests: Arc<Mutex<Vec<TextGenerationRequest>>> = Arc::from(Mutex::from(Vec::new()));
+        info!(
+            "Generating requests from {filepath}",
+            filepath = filepath.display()
+        );
+        let bar = ProgressBar::new(data.len() as u64);
+        bar.set_style(ProgressStyle::with_template(
+            "Tokenizing prompts [{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
+        )?);
+        split(data, entry_splitter).for_each(|subrange| {
+            for entry in subrange {
+                bar.inc(1);
+                if entry.conversations.is_empty() {
+                    continue;
+                }
+                let system_prompt = entry
+                    .conversations
+                    .iter()
+                    .find(|c| c.role == "system")
+                    .map(|c| c.content.clone());
+                let system_prompt_tokens = match system_prompt {
+                    Some(ref prompt) => {
+                        let (_, num_tokens) = match tokenize_prompt(
+                            prompt.clone(),
+                            tokenizer.clone(),
+                            &TokenizeOptions::default(),
+                        ) {
+                            Ok((prompt, num_tokens)) => (prompt, num_tokens),
+                            Err(e) => {
+                                debug!("Error tokenizing system prompt: {e}");
+                                return;
+                            }
+                        };
+                        num_tokens
+                    }
+                    None => 0,
+                };
+                entry
+                    .conversations
+                    .iter()
+                    .filter(|c| c.role == "user")
+                    .for_each(|c| {
+                        let prompt = c.content.clone();
+                        let num_decode_tokens = decode_tokenize_opts.clone().map_or_else(
+                            || None,
+                            |opts| {
+                                opts.num_tokens.map(|num_tokens| {
+                                    sample_num_tokens(
+                                        num_tokens,
+                                        opts.min_tokens,
+                                        opts.max_tokens,
+                                        opts.variance,
+                                    )
+                                })
+                            },
+                        );
+                        match &prompt_tokenize_opts {
+                            None => {
+                                let (_, num_tokens) = match tokenize_prompt(
+                                    prompt.clone(),
+                                    tokenizer.clone(),
+                                    &TokenizeOptions::default(),
+                                ) {
+                                    Ok((prompt, num_tokens)) => (prompt, num_tokens),
+                                    Err(e) => {
+                                        debug!("Error tokenizing prompt: {e}");
+                                        return;
+                                    }
+                                };
+                                requests.lock().unwrap().push(TextGenerationRequest {
+                                    prompt,
+                                    num_prompt_tokens: num_tokens + system_prompt_tokens,
+                                    num_decode_tokens,
+                                    system_prompt: system_prompt.clone(),
+                                    expected_answer: entry.answer.clone(),
+                                    context_clamped: false,
+                                });
+                            }
+                            Some(options) => {
+                                // compute number of tokens to generate using a Gaussian distribution
+                                let (sampled_prompt, prompt_tokens) = match tokenize_prompt(
+                                    prompt.clone(),
+                                    tokenizer.clone(),
+                                    options,
+                                ) {
+                                    Ok(prompt) => prompt,
+                                    Err(e) => {
+                                        debug!("Error tokenizing prompt: {e}");
+                                        return;
+                                    }
+                                };
+                                requests.lock().unwrap().push(TextGenerationRequest {
+                                    prompt: sampled_prompt,
+                                    num_prompt_tokens: prompt_tokens + system_prompt_tokens,
+                                    num_decode_tokens,
+                                    system_prompt: system_prompt.clone(),
+                                    expected_answer: entry.answer.clone(),
+                                    context_clamped: false,
+                                });
+                            }
+                        }
+                    });
+                // TODO: check that we have enough requests
+            }
+        });
+        let requests = requests.lock().unwrap();
+        info!(
+            "Generated {num_requests} requests",
+            num_requests = requests.len()
+        );
+        Ok(Self {
+            current_index: Arc::from(AtomicU64::new(0)),
+            requests: requests.to_vec(),
+            prompt_template: None,
+        })
+    }
+
+    /// Fit the requests in a model context length: requests whose prompt doesn't leave room for a
+    /// generated token are removed, the others get their max tokens reduced so that prompt and
+    /// generated tokens fit. Returns the number of clamped and removed requests.
+    pub fn clamp_to_context(&mut self, max_context_length: u64) -> anyhow::Result<(u64, u64)> {
+        let total = self.requests.len();
+        self.requests
+            .retain(|request| request.num_prompt_tokens < max_context_length);
+        let removed = (total - self.requests.len()) as u64;
+        if self.requests.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No prompt fits in the model context length of {max_context_length} tokens"
+            ));
+        }
+        let mut clamped = 0;
+        for request in self.requests.iter_mut() {
+            let available = max_context_length - request.num_prompt_tokens;
+            if let Some(max_tokens) = request.num_decode_tokens.filter(|t| *t > available) {
+                debug!("Clamping max tokens from {max_tokens} to {available}");
+                request.num_decode_tokens = Some(available);
+                request.context_clamped = true;
+                clamped += 1;
+            }
+        }
+        Ok((clamped, removed))
+    }
+
+    /// Fill-in-the-middle requests from a dataset of source files, a JSON array or JSON lines of
+    /// objects with a `content` field. Each file is cut around 1 to 3 lines to complete and the
+    /// prompt is the code before and after them in the FIM `template`, the removed lines being
+    /// the expected answer. Prompt options bound the tokens kept around the cut, 3/4 before and
+    /// 1/4 after it. Without decode options, requests generate up to the length of the removed
+    /// lines.
+    pub fn load_fim(
+        filepath: PathBuf,
+        tokenizer: Arc<Tokenizer>,
+        prompt_tokenize_opts: Option<TokenizeOptions>,
+        decode_tokenize_opts: Option<TokenizeOptions>,
+        template: &str,
+    ) -> anyhow::Result<Self> {
+        let entries: Vec<CodeEntry> = read_json_entries(&filepath, "code dataset")?;
+        info!(
+            "Generating fill-in-the-middle requests from {filepath}",
+            filepath = filepath.display()
+        );
+        let requests: Vec<TextGenerationRequest> = entries
+            .par_iter()
+            .filter_map(|entry| {
+                let budget = prompt_tokenize_opts
+                    .as_ref()
+                    .map(|opts| match opts.num_tokens {
+                        Some(num_tokens) => sample_num_tokens(
+                            num_tokens,
+                            opts.min_tokens,
+                            opts.max_tokens,
+                            opts.variance,
+                        ),
+                        None => opts.max_tokens,
+                    });
+                let (prefix, middle, suffix) = fim_split(
+                    &entry.content,
+                    &tokenizer,
+                    budget.map(|budget| budget * 3 / 4),
+                    budget.map(|budget| budget - budget * 3 / 4),
+                    &mut rand::thread_rng(),
+                )?;
+                let prompt = render_fim(template, &prefix, &suffix);
+                let num_prompt_tokens = tokenizer.encode(prompt.as_str(), false).ok()?.len() as u64;
+                let num_decode_tokens = match &decode_tokenize_opts {
+                    Some(TokenizeOptions {
+                        num_tokens: Some(num_tokens),
+                        min_tokens,
+                        max_tokens,
+                        variance,
+                    }) => sample_num_tokens(*num_tokens, *min_tokens, *max_tokens, *variance),
+                    _ => tokenizer.encode(middle.as_str(), false).ok()?.len().max(1) as u64,
+                };
+                Some(TextGenerationRequest {
+                    prompt,
+                    num_prompt_tokens,
+                    num_decode_tokens: Some(num_decode_tokens),
+                    system_prompt: None,
+                    expected_answer: Some(middle),
+                    context_clamped: false,
+                })
+            })
+            .collect();
+        info!(
+            "Generated {num_requests} requests",
+            num_requests = requests.len()
+        );
+        if requests.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No fill-in-the-middle request could be generated from {}",
+                filepath.display()
+            ));
+        }
+        Ok(Self {
+            requests,
+            prompt_template: None,
+            current_index: Arc::from(AtomicU64::new(0)),
+        })
+    }
+
+    /// Retrieval-augmented generation requests from a corpus, a JSON array or JSON lines of objects
+    /// with a `text` (or `context`) passage and, for entries used as queries, a `question` (or
+    /// `instruction`). Each prompt stuffs the passage of the query with distractor passages drawn
+    /// from the corpus, `num_passages` in total, in random order. With prompt options, passages are
+    /// added until the sampled prompt length is reached, without exceeding the maximum. The
+    /// `answer` (or `response`) of an entry, if any, is the expected answer.
+    pub fn load_rag(
+        filepath: PathBuf,
+        tokenizer: Arc<Tokenizer>,
+        prompt_tokenize_opts: Option<TokenizeOptions>,
+        decode_tokenize_opts: Option<TokenizeOptions>,
+        num_passages: u64,
+    ) -> anyhow::Result<Self> {
+        let entries: Vec<RagEntry> = read_json_entries(&filepath, "RAG corpus")?;
+        // the same passage can be shared by several queries, e.g. in reading comprehension datasets
+        let mut passage_index = BTreeMap::new();
+        let mut passages = Vec::new();
+        let mut queries = Vec::new();
+        for entry in &entries {
+            let text = entry.text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let index = *passage_index.entry(text).or_insert_with(|| {
+                passages.push(text);
+                passages.len() - 1
+            });
+            if let Some(question) = entry.question.as_deref().filter(|q| !q.trim().is_empty()) {
+                queries.push((question.trim(), index, entry.answer.as_deref()));
+            }
+        }
+        if queries.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No entry with a passage and a question in RAG corpus {}",
+                filepath.display()
+            ));
+        }
+        info!(
+            "Tokenizing {num_passages} passages of {filepath}",
+            num_passages = passages.len(),
+            filepath = filepath.display()
+        );
+        let passage_tokens = passages
+            .par_iter()
+            .map(|passage| {
+                tokenizer
+                    .encode(*passage, false)
+                    .map(|encoding| encoding.len() as u64)
+                    .map_err(|e| anyhow::anyhow!("Unable to tokenize passage: {e}"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let requests: Vec<TextGenerationRequest> = queries
+            .par_iter()
+            .filter_map(|(question, gold, answer)| {
+                let mut rng = rand::thread_rng();
+                let (target, max_tokens) = match &prompt_tokenize_opts {
+                    Some(opts) => (
+                        opts.num_tokens.map(|num_tokens| {
+                            sample_num_tokens(
+                                num_tokens,
+                                opts.min_tokens,
+                                opts.max_tokens,
+                                opts.variance,
+                            )
+                        }),
+                        Some(opts.max_tokens),
+                    ),
+                    None => (None, None),
+                };
+                let mut selected = vec![*gold];
+                let mut num_tokens = passage_tokens[*gold];
+                let candidates = rand::seq::index::sample(
+                    &mut rng,
+                    passages.len(),
+                    passages.len().min(MAX_RAG_CANDIDATES),
+                );
+                for candidate in candidates {
+                    let enough_passages = selected.len() as u64 >= num_passages;
+                    if enough_passages && target.is_none_or(|target| num_tokens >= target) {
+                        break;
+                    }
+                    if candidate == *gold
+                        || max_tokens
+                            .is_some_and(|max| num_tokens + passage_tokens[candidate] > max)
+                    {
+                        continue;
+                    }
+                    selected.push(candidate);
+                    num_tokens += passage_tokens[candidate];
+                }
+                selected.shuffle(&mut rng);
+                let prompt = rag_prompt(question, selected.iter().map(|i| passages[*i]));
+                let num_prompt_tokens = tokenizer.encode(prompt.as_str(), false).ok()?.len() as u64;
+                let num_decode_tokens = match &decode_tokenize_opts {
+                    Some(TokenizeOptions {
+                        num_tokens: Some(num_tokens),
+                        min_tokens,
+                        max_tokens,
+                        variance,
+                    }) => Some(sample_num_tokens(
+                        *num_tokens,
+                        *min_tokens,
+                        *max_tokens,
+                        *variance,
+                    )),
+                    _ => None,
+                };
+                Some(TextGenerationRequest {
+                    prompt,
+                    num_prompt_tokens,
+                    num_decode_tokens,
+                    system_prompt: None,
+                    expected_answer: answer
+                        .map(str::trim)
+                        .filter(|a| !a.is_empty())
+                        .map(str::to_string),
+                    context_clamped: false,
+                })
+            })
+            .collect();
+        info!(
+            "Generated {num_requests} requests",
+            num_requests = requests.len()
+        );
+        Ok(Self {
+            requests,
+            prompt_template: None,
+            current_index: Arc::from(AtomicU64::new(0)),
+        })
+    }
+
+    /// Summarization requests of long documents, from a dataset of documents (a JSON array or JSON
+    /// lines of objects with a `text`, `document` or `article` field) or synthetic documents of
+    /// random tokens when `filepath` is `None`. Prompt options give the document length: dataset
+    /// documents are followed by the next ones until it is reached, then truncated to it. Without
+    /// decode options, requests generate `summary_ratio` times the prompt length.
+    pub fn load_summarization(
+        filepath: Option<PathBuf>,
+        tokenizer: Arc<Tokenizer>,
+        prompt_tokenize_opts: Option<TokenizeOptions>,
+        decode_tokenize_opts: Option<TokenizeOptions>,
+        summary_ratio: f64,
+    ) -> anyhow::Result<Self> {
+        let sample_length = || {
+            prompt_tokenize_opts
+                .as_ref()
+                .map(|opts| match opts.num_tokens {
+                    Some(num_tokens) => sample_num_tokens(
+                        num_tokens,
+                        opts.min_tokens,
+                        opts.max_tokens,
+                        opts.variance,
+                    ),
+                    None => opts.max_tokens,
+                })
+        };
+        let documents: Vec<String> = match &filepath {
+            Some(filepath) => {
+                let entries: Vec<DocumentEntry> = read_json_entries(filepath, "document dataset")?;
+                let documents = entries
+                    .into_iter()
+                    .map(|entry| entry.text)
+                    .filter(|text| !text.trim().is_empty())
+                    .collect::<Vec<_>>();
+                info!(
+                    "Tokenizing {num_documents} documents of {filepath}",
+                    num_documents = documents.len(),
+                    filepath = filepath.display()
+                );
+                let document_tokens = documents
+                    .par_iter()
+                    .map(|document| {
+                        tokenizer
+                            .encode(document.as_str(), false)
+                            .map(|encoding| encoding.len() as u64)
+                            .map_err(|e| anyhow::anyhow!("Unable to tokenize document: {e}"))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                (0..documents.len())
+                    .into_par_iter()
+                    .filter_map(|start| {
+                        let Some(length) = sample_length() else {
+                            return Some(documents[start].clone());
+                        };
+                        // documents shorter than the sampled length are followed by the next ones
+                        let mut document = documents[start].clone();
+                        let mut num_tokens = document_tokens[start];
+                        let mut next = (start + 1) % documents.len();
+                        while num_tokens < length && next != start {
+                            document.push_str("\n\n");
+                            document.push_str(&documents[next]);
+                            num_tokens += document_tokens[next];
+                            next = (next + 1) % documents.len();
+                        }
+                        truncate_tokens(&tokenizer, document, length, false)
+                    })
+                    .collect()
+            }
+            None => {
+                if prompt_tokenize_opts.is_none() {
+                    return Err(anyhow::anyhow!(
+                        "Synthetic documents require prompt options to set their length"
+                    ));
+                }
+                info!("Generating {SYNTHETIC_DOCUMENTS} synthetic documents");
+                let vocab_size = tokenizer.get_vocab_size(false) as u32;
+                (0..SYNTHETIC_DOCUMENTS)
+                    .into_par_iter()
+                    .filter_map(|_| {
+                        let length = sample_length()?;
+                        let mut rng = rand::thread_rng();
+                        let ids = (0..length)
+                            .map(|_| rng.gen_range(0..vocab_size))
+                            .collect::<Vec<_>>();
+                        tokenizer.decode(&ids, true).ok()
+                    })
+                    .collect()
+            }
+        };
+        let requests: Vec<TextGenerationRequest> = documents
+            .par_iter()
+            .filter_map(|document| {
+                let prompt = format!("Summarize the following document.\n\n{document}\n\nSummary:");
+                let num_prompt_tokens = tokenizer.encode(prompt.as_str(), false).ok()?.len() as u64;
+                let num_decode_tokens = match &decode_tokenize_opts {
+                    Some(TokenizeOptions {
+                        num_tokens: Some(num_tokens),
+                        min_tokens,
+                        max_tokens,
+                        variance,
+                    }) => sample_num_tokens(*num_tokens, *min_tokens, *max_tokens, *variance),
+                    _ => ((num_prompt_tokens as f64 * summary_ratio) as u64).max(1),
+                };
+                Some(TextGenerationRequest {
+                    prompt,
+                    num_prompt_tokens,
+                    num_decode_tokens: Some(num_decode_tokens),
+                    system_prompt: None,
+                    expected_answer: None,
+                    context_clamped: false,
+                })
+            })
+            .collect();
+        info!(
+            "Generated {num_requests} requests",
+            num_requests = requests.len()
+        );
+        if requests.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No summarization request could be generated"
+            ));
+        }
+        Ok(Self {
+            requests,
+            prompt_template: None,
+            current_index: Arc::from(AtomicU64::new(0)),
+        })
+    }
+
+    /// Copy of the generator starting again from the first request. Clones share their cursor,
+    /// forks don't, so benchmarks of several endpoints replay the same sequence of requests.
+    pub fn fork(&self) -> Self {
+        Self {
+            requests: self.requests.clone(),
+            prompt_template: self.prompt_template.clone(),
+            current_index: Arc::from(AtomicU64::new(0)),
+        }
+    }
+
+    #[cfg(feature = "hub")]
+    pub async fn download_dataset(
+        repo_name: String,
+        filename: String,
+        hf_token: Option<String>,
+        event_bus: Option<&UnboundedSender<crate::benchmark::Event>>,
+    ) -> anyhow::Result<PathBuf> {
+        crate::download::download_file(Repo::dataset(repo_name), &filename, hf_token, event_bus)
+            .await
+    }
+}
+
+/// Source file of a code dataset, for fill-in-the-middle prompts
+#[cfg(feature = "datasets")]
+#[derive(Deserialize)]
+struct CodeEntry {
+    #[serde(alias = "code", alias = "text")]
+    content: String,
+}
+
+/// Passage of a retrieval corpus, with the question and answer of the entries used as queries
+#[cfg(feature = "datasets")]
+#[derive(Deserialize)]
+struct RagEntry {
+    #[serde(alias = "context", alias = "passage")]
+    text: String,
+    #[serde(default, alias = "query", alias = "instruction")]
+    question: Option<String>,
+    #[serde(default, alias = "response")]
+    answer: Option<String>,
+}
+
+/// Long document to summarize
+#[cfg(feature = "datasets")]
+#[derive(Deserialize)]
+struct DocumentEntry {
+    #[serde(alias = "document", alias = "article")]
+    text: String,
+}
+
+/// Number of synthetic documents generated for summarization requests, reused in turn
+#[cfg(feature = "datasets")]
+const SYNTHETIC_DOCUMENTS: usize = 256;
+
+/// Distractor passages drawn per RAG prompt, enough to stuff 32k tokens of short passages
+#[cfg(feature = "datasets")]
+const MAX_RAG_CANDIDATES: usize = 2048;
+
+/// Entries of a dataset file, a JSON array or JSON lines
+#[cfg(feature = "datasets")]
+fn read_json_entries<T: serde::de::DeserializeOwned>(
+    filepath: &std::path::Path,
+    kind: &str,
+) -> anyhow::Result<Vec<T>> {
+    let input = std::fs::read_to_string(filepath)?;
+    match serde_json::from_str(&input) {
+        Ok(entries) => Ok(entries),
+        Err(_) => input
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()
+            .map_err(|e| anyhow::anyhow!("Unable to parse {kind} {}: {e}", filepath.display())),
+    }
+}
+
+/// Prompt asking to answer `question` from numbered passages
+#[cfg(feature = "datasets")]
+fn rag_prompt<'a>(question: &str, passages: impl Iterator<Item = &'a str>) -> String {
+    let mut prompt =
+        "Answer the question briefly, using only the information in the passages below.\n\n"
+            .to_string();
+    for (i, passage) in passages.enumerate() {
+        prompt.push_str(&format!("[{}] {passage}\n\n", i + 1));
+    }
+    prompt.push_str(&format!("Question: {question}\nAnswer:"));
+    prompt
+}
+
+/// StarCoder fill-in-the-middle format, the default FIM template
+pub const DEFAULT_FIM_TEMPLATE: &str = "<fim_prefix>{prefix}<fim_suffix>{suffix}<fim_middle>";
+
+/// Replace the `{prefix}` and `{suffix}` variables of a FIM template in a single pass, so code
+/// containing them is kept as is
+#[cfg(feature = "datasets")]
+fn render_fim(template: &str, prefix: &str, suffix: &str) -> String {
+    let mut rendered = String::with_capacity(template.len() + prefix.len() + suffix.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("{prefix}") {
+            rendered.push_str(prefix);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{suffix}") {
+            rendered.push_str(suffix);
+            rest = after;
+        } else {
+            rendered.push('{');
+            rest = &rest[1..];
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Cut source code around a span of 1 to 3 lines, returning the prefix, the span and the suffix.
+/// The prefix keeps its last `max_prefix_tokens` tokens, the suffix its first `max_suffix_tokens`.
+#[cfg(feature = "datasets")]
+fn fim_split(
+    content: &str,
+    tokenizer: &Tokenizer,
+    max_prefix_tokens: Option<u64>,
+    max_suffix_tokens: Option<u64>,
+    rng: &mut impl Rng,
+) -> Option<(String, String, String)> {
+    let lines = content.split_inclusive('\n').collect::<Vec<_>>();
+    if lines.len() < 3 {
+        return None;
+    }
+    // the span never starts on the first line nor ends on the last one, so there is always
+    // code on both sides
+    let start = rng.gen_range(1..lines.len() - 1);
+    let end = (start + rng.gen_range(1..=3)).min(lines.len() - 1);
+    let middle = lines[start..end].concat();
+    if middle.trim().is_empty() {
+        return None;
+    }
+    let truncate = |text: String, max_tokens: Option<u64>, keep_end: bool| match max_tokens {
+        Some(max_tokens) => truncate_tokens(tokenizer, text, max_tokens, keep_end),
+        None => Some(text),
+    };
+    let prefix = truncate(lines[..start].concat(), max_prefix_tokens, true)?;
+    let suffix = truncate(lines[end..].concat(), max_suffix_tokens, false)?;
+    Some((prefix, middle, suffix))
+}
+
+/// Keep the first `max_tokens` tokens of `text`, or the last ones with `keep_end`
+#[cfg(feature = "datasets")]
+fn truncate_tokens(
+    tokenizer: &Tokenizer,
+    text: String,
+    max_tokens: u64,
+    keep_end: bool,
+) -> Option<String> {
+    let encoding = tokenizer.encode(text.as_str(), false).ok()?;
+    let ids = encoding.get_ids();
+    if ids.len() as u64 <= max_tokens {
+        return Some(text);
+    }
+    let kept = if keep_end {
+        &ids[ids.len() - max_tokens as usize..]
+    } else {
+        &ids[..max_tokens as usize]
+    };
+    tokenizer.decode(kept, false).ok()
+}
+
+/// Give the requests of a trace prompts of random tokens of their recorded lengths. Random tokens
+/// don't always encode back to as many tokens, the prompt lengths are counted again.
+#[cfg(all(feature = "hub", feature = "datasets"))]
+pub fn synthesize_trace_prompts(trace: &mut Trace, tokenizer: &Tokenizer) -> anyhow::Result<()> {
+    info!(
+        "Generating prompts of {num_requests} trace requests",
+        num_requests = trace.entries.len()
+    );
+    let vocab_size = tokenizer.get_vocab_size(false) as u32;
+    trace.entries.par_iter_mut().try_for_each(|entry| {
+        let mut rng = rand::thread_rng();
+        let ids = (0..entry.prompt_tokens)
+            .map(|_| rng.gen_range(0..vocab_size))
+            .collect::<Vec<_>>();
+        let prompt = tokenizer
+            .decode(&ids, true)
+            .map_err(|e| anyhow::anyhow!("Unable to generate trace prompt: {e}"))?;
+        entry.prompt_tokens = tokenizer
+            .encode(prompt.as_str(), false)
+            .map_err(|e| anyhow::anyhow!("Unable to tokenize trace prompt: {e}"))?
+            .len() as u64;
+        entry.prompt = Some(prompt);
+        Ok(())
+    })
+}
+
+#[cfg(feature = "datasets")]
+fn sample_num_tokens(num_tokens: u64, min_tokens: u64, max_tokens: u64, variance: u64) -> u64 {
+    let normal = rand_distr::Normal::new(num_tokens as f64, variance as f64).unwrap();
+    let mut num_tokens = normal.sample(&mut rand::thread_rng()) as u64;
+    if num_tokens < min_tokens {
+        num_tokens = min_tokens;
+    }
+    if num_tokens > max_tokens {
+        num_tokens = max_tokens;
+    }
+    num_tokens
+}
+
+#[cfg(feature = "datasets")]
+fn entry_splitter(
+    gen: Vec<ConversationEntry>,
+) -> (Vec<ConversationEntry>, Option<Vec<ConversationEntry>>) {
+    if gen.len() <= 2 {
+        return (gen, None);
+    }
+    let middle = gen.len() / 2;
+    let (left, right) = gen.split_at(middle);
+    let left = left.to_vec();
+    let right = right.to_vec();
+    (left, Some(right))
+}
+
+impl TextRequestGenerator for ConversationTextRequestGenerator {
+    fn generate_request(&self) -> TextGenerationRequest {
+        // a single atomic cursor cycles through the dataset, concurrent callers each get the next
+        // request without waiting on each other
+        let index = self.current_index.fetch_add(1, Ordering::Relaxed);
+        let mut request = self.requests[index as usize % self.requests.len()].clone();
+        if let Some(template) = &self.prompt_template {
+            request.prompt = template.render(&request.prompt, index);
+        }
+        request
+    }
+}
+
+#[allow(dead_code)]
+pub struct DummyTextRequestGenerator {}
+
+#[allow(dead_code)]
+impl DummyTextRequestGenerator {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for DummyTextRequestGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextRequestGenerator for DummyTextRequestGenerator {
+    fn generate_request(&self) -> TextGenerationRequest {
+        TextGenerationRequest {
+            prompt: "Hello, world!".to_string(),
+            num_prompt_tokens: 2,
+            num_decode_tokens: Some(10),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        }
+    }
+}
+
+/// Generates requests ahead of time on a background thread into a bounded queue, so slow
+/// generation (tokenization, lock contention) never delays the dispatch of open-loop arrivals.
+/// Requests come out in the order the wrapped generator produces them. The thread stops once the
+/// prefetcher is dropped.
+pub struct PrefetchingRequestGenerator {
+    queue: crossbeam_channel::Receiver<TextGenerationRequest>,
+    misses: AtomicU64,
+    /// Panic message of the wrapped generator, once the thread stopped on it
+    failure: Arc<std::sync::OnceLock<String>>,
+}
+
+impl PrefetchingRequestGenerator {
+    pub fn new<G: TextRequestGenerator + 'static>(generator: G, capacity: usize) -> Self {
+        let (tx, rx) = crossbeam_channel::bounded(capacity);
+        let failure = Arc::new(std::sync::OnceLock::new());
+        let thread_failure = failure.clone();
+        std::thread::spawn(move || {
+            let generate = std::panic::AssertUnwindSafe(|| {
+                while tx.send(generator.generate_request()).is_ok() {}
+            });
+            if let Err(cause) = std::panic::catch_unwind(generate) {
+                let message = cause
+                    .downcast_ref::<&str>()
+                    .map(|m| m.to_string())
+                    .or_else(|| cause.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_string());
+                error!("Request generator panicked: {message}");
+                // set before the sender is dropped, so waiting VUs see it
+                let _ = thread_failure.set(message);
+            }
+        });
+        Self {
+            queue: rx,
+            misses: AtomicU64::new(0),
+            failure,
+        }
+    }
+
+    /// Number of requests that were not generated yet when asked for
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+impl TextRequestGenerator for PrefetchingRequestGenerator {
+    fn generate_request(&self) -> TextGenerationRequest {
+        match self.queue.try_recv() {
+            Ok(request) => request,
+            Err(_) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                trace!("Prefetch queue empty, waiting for the next request");
+                self.queue.recv().unwrap_or_else(|_| {
+                    panic!(
+                        "Request prefetch thread stopped: {}",
+                        self.failure.get().map_or("no cause", |m| m.as_str())
+                    )
+                })
+            }
+        }
+    }
+}
+
+impl Drop for PrefetchingRequestGenerator {
+    fn drop(&mut self) {
+        if self.misses() > 0 {
+            debug!("Request prefetch queue was empty {} times", self.misses());
+        }
+    }
+}
+
+/// Responses observed before each adjustment of the decode targets
+#[cfg(all(feature = "hub", feature = "datasets"))]
+const DECODE_FEEDBACK_WINDOW: u64 = 50;
+/// Share of the responses of a window stopping before their decode target (EOS) from which the
+/// server is considered to stop early consistently
+#[cfg(all(feature = "hub", feature = "datasets"))]
+const DECODE_FEEDBACK_EARLY_STOPS: f64 = 0.9;
+
+#[cfg(all(feature = "hub", feature = "datasets"))]
+#[derive(Debug)]
+struct DecodeFeedbackWindow {
+    responses: u64,
+    early_stops: u64,
+    /// Sum of the completion lengths of early stops, relative to their decode targets
+    early_stop_ratios: f64,
+}
+
+/// Completion lengths observed relative to the decode targets of the requests. When the server
+/// consistently stops before `max_tokens` (EOS), the decode targets of the next requests are
+/// scaled down to the observed completion lengths, so the token budget of the requests matches the
+/// tokens actually generated. Targets are only lowered, by window of responses.
+#[cfg(all(feature = "hub", feature = "datasets"))]
+#[derive(Debug)]
+pub struct DecodeFeedback {
+    window: Mutex<DecodeFeedbackWindow>,
+    /// Bits of the `f64` scale, read by every request without locking the window
+    scale: AtomicU64,
+}
+
+#[cfg(all(feature = "hub", feature = "datasets"))]
+impl Default for DecodeFeedback {
+    fn default() -> Self {
+        Self {
+            window: Mutex::new(DecodeFeedbackWindow {
+                responses: 0,
+                early_stops: 0,
+                early_stop_ratios: 0.0,
+            }),
+            scale: AtomicU64::new(1.0f64.to_bits()),
+        }
+    }
+}
+
+#[cfg(all(feature = "hub", feature = "datasets"))]
+impl DecodeFeedback {
+    pub fn observe(&self, response: &TextGenerationAggregatedResponse) {
+        let Some(target) = response
+            .request
+            .as_ref()
+            .and_then(|r| r.num_decode_tokens)
+            .filter(|target| *target > 0)
+        else {
+            return;
+        };
+        if response.failed {
+            return;
+        }
+        let mut window = self.window.lock().expect("decode feedback lock poisoned");
+        window.responses += 1;
+        if response.num_generated_tokens < target {
+            window.early_stops += 1;
+            window.early_stop_ratios += response.num_generated_tokens as f64 / target as f64;
+        }
+        if window.responses < DECODE_FEEDBACK_WINDOW {
+            return;
+        }
+        if window.early_stops as f64 >= DECODE_FEEDBACK_EARLY_STOPS * window.responses as f64 {
+            let ratio = window.early_stop_ratios / window.early_stops as f64;
+            // only updated under the window lock
+            let scale = self.scale() * ratio;
+            self.scale.store(scale.to_bits(), Ordering::Relaxed);
+            info!(
+                "Server stopped {}/{} requests early, scaling decode targets to {:.2}",
+                window.early_stops, window.responses, scale
+            );
+        }
+        window.responses = 0;
+        window.early_stops = 0;
+        window.early_stop_ratios = 0.0;
+    }
+
+    /// Factor applied to the sampled decode targets
+    pub fn scale(&self) -> f64 {
+        f64::from_bits(self.scale.load(Ordering::Relaxed))
+    }
+
+    /// Decode target of a request sampled with `num_decode_tokens`, at least 1 token
+    pub fn adjust(&self, num_decode_tokens: u64) -> u64 {
+        ((num_decode_tokens as f64 * self.scale()).round() as u64).max(1)
+    }
+}
+
+/// Scales the decode targets of the requests of the wrapped generator with the completion lengths
+/// observed by a [`DecodeFeedbackBackend`]
+#[cfg(all(feature = "hub", feature = "datasets"))]
+pub struct AdaptiveDecodeRequestGenerator {
+    requests: Arc<dyn TextRequestGenerator>,
+    feedback: Arc<DecodeFeedback>,
+}
+
+#[cfg(all(feature = "hub", feature = "datasets"))]
+impl AdaptiveDecodeRequestGenerator {
+    pub fn new(requests: Arc<dyn TextRequestGenerator>, feedback: Arc<DecodeFeedback>) -> Self {
+        Self { requests, feedback }
+    }
+}
+
+#[cfg(all(feature = "hub", feature = "datasets"))]
+impl TextRequestGenerator for AdaptiveDecodeRequestGenerator {
+    fn generate_request(&self) -> TextGenerationRequest {
+        let mut request = self.requests.generate_request();
+        request.num_decode_tokens = request
+            .num_decode_tokens
+            .map(|num_decode_tokens| self.feedback.adjust(num_decode_tokens));
+        request
+    }
+
+    fn decode_scale(&self) -> Option<f64> {
+        Some(self.feedback.scale())
+    }
+}
+
+/// Records the completion lengths of the responses of the wrapped backend in the decode feedback
+#[cfg(all(feature = "hub", feature = "datasets"))]
+#[derive(Clone)]
+pub struct DecodeFeedbackBackend {
+    backend: Box<dyn TextGenerationBackend + Send + Sync>,
+    feedback: Arc<DecodeFeedback>,
+}
+
+#[cfg(all(feature = "hub", feature = "datasets"))]
+impl DecodeFeedbackBackend {
+    pub fn new(
+        backend: Box<dyn TextGenerationBackend + Send + Sync>,
+        feedback: Arc<DecodeFeedback>,
+    ) -> Self {
+        Self { backend, feedback }
+    }
+}
+
+#[cfg(all(feature = "hub", feature = "datasets"))]
+#[async_trait]
+impl TextGenerationBackend for DecodeFeedbackBackend {
+    async fn generate(
+        &self,
+        request: Arc<TextGenerationRequest>,
+        sender: Sender<TextGenerationAggregatedResponse>,
+    ) {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let forward = async {
+            while let Some(response) = rx.recv().await {
+                self.feedback.observe(&response);
+                sender
+                    .send(response)
+                    .await
+                    .expect("Error sending response to channel");
+            }
+        };
+        tokio::join!(self.backend.generate(request, tx), forward);
+    }
+}
+
+#[cfg(feature = "datasets")]
+fn tokenize_prompt(
+    prompt: String,
+    tokenizer: Arc<Tokenizer>,
+    options: &TokenizeOptions,
+) -> anyhow::Result<(String, u64)> {
+    let prompt_tokens = tokenizer
+        .encode(prompt.clone(), false)
+        .map_err(|_| anyhow::anyhow!("Error tokenizing prompt"))?;
+    match options.num_tokens {
+        None => {
+            // check if we have a min/max number of tokens, skip prompts that are too short or too long
+            if prompt_tokens.len() > options.max_tokens as usize
+                || prompt_tokens.len() < options.min_tokens as usize
+            {
+                return Err(anyhow::anyhow!(format!(
+                    "Prompt is too short or too long, skipping: {}<{}<{}",
+                    options.min_tokens,
+                    prompt_tokens.len(),
+                    options.max_tokens
+                )));
+            }
+            Ok((prompt, prompt_tokens.len() as u64))
+        }
+        Some(num_tokens) => {
+            if prompt_tokens.len() < num_tokens as usize {
+                return Err(anyhow::anyhow!(format!(
+                    "Prompt is too short to tokenize: {}<{}",
+                    prompt_tokens.len(),
+                    num_tokens
+                )));
+            }
+            let tokens = prompt_tokens
+                .get_ids()
+                .iter()
+                .take(num_tokens as usize)
+                .copied()
+                .collect::<Vec<u32>>();
+            let prompt = tokenizer.decode(&tokens, true).unwrap();
+            Ok((prompt, num_tokens))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextGenerationAggregatedResponse {
+    pub start_time: Option<tokio::time::Instant>,
+    pub end_time: Option<tokio::time::Instant>,
+    pub num_generated_tokens: u64,
+    pub num_prompt_tokens: u64,
+    pub times_to_tokens: Vec<std::time::Duration>,
+    last_received_token_time: tokio::time::Instant,
+    pub failed: bool,
+    pub ended: bool,
+    pub request: Option<Arc<TextGenerationRequest>>,
+    pub generated_text: String,
+    pub server_details: Option<TgiDetails>,
+    pub idempotency_key: Option<String>,
+    /// Prompt tokens served from the server prompt cache, when usage is reported
+    pub cached_prompt_tokens: Option<u64>,
+    /// N-gram repetition score of the generated text, when repetition detection is enabled
+    pub repetition_score: Option<f64>,
+    /// Whether the generated text matches the expected answer, when answers are scored
+    pub correct: Option<bool>,
+    /// Generation details returned by vLLM's native API
+    pub vllm_details: Option<VllmDetails>,
+    /// Cause of the failure, when the server returned an error
+    pub error_class: Option<ErrorClass>,
+    /// HTTP status of the response, for HTTP backends once the server answered
+    pub http_status: Option<u16>,
+    /// Causes of the failed attempts retried before this response
+    pub retries: Vec<ErrorClass>,
+    /// Idempotency keys sent by the retried attempts, when an idempotency key header is set
+    pub retry_idempotency_keys: Vec<String>,
+    /// Name of the tenant the request was sent as, when tenants are configured
+    pub tenant: Option<String>,
+    /// Whether the request was streamed, when streamed and unstreamed requests are compared
+    pub streamed: Option<bool>,
+    /// Generated tokens reported in the token usage of the response, if any
+    pub usage_completion_tokens: Option<u64>,
+    /// Tokens of the whole generated text, when token counts are reconciled
+    pub retokenized_tokens: Option<u64>,
+    /// Custom scores of the response by name, when response processors are configured
+    pub scores: BTreeMap<String, f64>,
+}
+
+impl Default for TextGenerationAggregatedResponse {
+    fn default() -> Self {
+        Self {
+            start_time: None,
+            end_time: None,
+            num_generated_tokens: 0,
+            num_prompt_tokens: 0,
+            times_to_tokens: Vec::new(),
+            last_received_token_time: tokio::time::Instant::now(),
+            failed: false,
+            ended: false,
+            request: None,
+            generated_text: String::new(),
+            server_details: None,
+            idempotency_key: None,
+            cached_prompt_tokens: None,
+            repetition_score: None,
+            correct: None,
+            vllm_details: None,
+            error_class: None,
+            http_status: None,
+            retries: Vec::new(),
+            retry_idempotency_keys: Vec::new(),
+            tenant: None,
+            streamed: None,
+            usage_completion_tokens: None,
+            retokenized_tokens: None,
+            scores: BTreeMap::new(),
+        }
+    }
+}
+
+impl TextGenerationAggregatedResponse {
+    pub fn new_as_ended() -> Self {
+        Self {
+            start_time: None,
+            end_time: None,
+            num_generated_tokens: 0,
+            num_prompt_tokens: 0,
+            times_to_tokens: Vec::new(),
+            last_received_token_time: tokio::time::Instant::now(),
+            failed: false,
+            ended: true,
+            request: None,
+            generated_text: String::new(),
+            server_details: None,
+            idempotency_key: None,
+            cached_prompt_tokens: None,
+            repetition_score: None,
+            correct: None,
+            vllm_details: None,
+            error_class: None,
+            http_status: None,
+            retries: Vec::new(),
+            retry_idempotency_keys: Vec::new(),
+            tenant: None,
+            streamed: None,
+            usage_completion_tokens: None,
+            retokenized_tokens: None,
+            scores: BTreeMap::new(),
+        }
+    }
+    fn start(&mut self, request: Arc<TextGenerationRequest>) {
+        self.start_time = Some(tokio::time::Instant::now());
+        self.last_received_token_time = tokio::time::Instant::now();
+        self.num_prompt_tokens = request.num_prompt_tokens;
+        self.request = Some(request);
+    }
+
+    /// Fail the request on a connection, first token or total timeout
+    fn time_out(&mut self) {
+        self.fail_as(ErrorClass::Timeout);
+    }
+
+    fn fail_as(&mut self, class: ErrorClass) {
+        self.error_class = Some(class);
+        self.fail();
+    }
+
+    /// Record the token usage reported by the server
+    fn add_usage(&mut self, usage: OpenAIUsage) {
+        self.cached_prompt_tokens = Some(
+            usage
+                .prompt_tokens_details
+                .and_then(|d| d.cached_tokens)
+                .unwrap_or(0),
+        );
+        self.usage_completion_tokens = usage.completion_tokens;
+    }
+
+    fn stop(&mut self) {
+        self.end_time = Some(tokio::time::Instant::now());
+    }
+
+    fn fail(&mut self) {
+        self.end_time = Some(tokio::time::Instant::now());
+        self.failed = true;
+    }
+
+    fn add_tokens(&mut self, num_tokens: u64) {
+        self.num_generated_tokens += num_tokens;
+        let time_to_generate = self.last_received_token_time.elapsed();
+        // make the assumption that when returned simultaneously, tokens were generated at a constant rate
+        time_to_generate.checked_div(num_tokens as u32).unwrap();
+        self.last_received_token_time = tokio::time::Instant::now();
+        self.times_to_tokens.push(time_to_generate);
+    }
+
+    pub fn time_to_first_token(&self) -> Option<std::time::Duration> {
+        match self.start_time {
+            Some(_) => self.times_to_tokens.first().copied(),
+            None => None,
+        }
+    }
+
+    pub fn inter_token_latency(&self) -> Option<std::time::Duration> {
+        match self.times_to_tokens.len() {
+            0 => None,
+            1 => Some(std::time::Duration::new(0, 0)),
+            _ => {
+                let mut total_time = std::time::Duration::new(0, 0);
+                for i in 1..self.times_to_tokens.len() {
+                    total_time += self.times_to_tokens[i];
+                }
+                Some(total_time / (self.num_generated_tokens as u32 - 1))
+            }
+        }
+    }
+    pub fn e2e_latency(&self) -> Option<std::time::Duration> {
+        match self.start_time {
+            Some(start_time) => self.end_time.map(|end_time| end_time - start_time),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::ExecutorConfig;
+    use crate::results::BenchmarkResults;
+    use crate::scheduler::ExecutorType;
+    use std::sync::atomic::AtomicU64;
+    use std::thread::sleep;
+    use std::time::Duration;
+    use tokio::sync::RwLock;
+
+    /// Tokenizer of a fixed vocabulary, the first word being the unknown token. Without a
+    /// pre-tokenizer, any text outside the vocabulary is a single unknown token
+    fn word_level_tokenizer(words: &[&str]) -> Tokenizer {
+        let model = tokenizers::models::wordlevel::WordLevel::builder()
+            .vocab(
+                words
+                    .iter()
+                    .enumerate()
+                    .map(|(id, word)| (word.to_string(), id as u32))
+                    .collect(),
+            )
+            .unk_token(words[0].to_string())
+            .build()
+            .unwrap();
+        Tokenizer::new(model)
+    }
+
+    #[tokio::test]
+    async fn test_openai_token_count() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("POST", "/v1/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(|w| {
+                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                w.write_all(b"data: {\"choices\": [{\"message\": {\"content\": \"Hello, world!Hello, world!Hello, world!Hello, world!\", \"role\": \"user\"}, \"finish_reason\": \"stop\", \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                w.write_all(b"data: [DONE]\n\n")
+            })
+            .create_async().await;
+        let url = s.url();
+        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            url,
+            "gpt2".to_string(),
+            tokenizer,
+            time::Duration::from_secs(10),
+            RequestOptions::default(),
+        )
+        .unwrap();
+        let request = TextGenerationRequest {
+            prompt: "Hello, world!".to_string(),
+            num_prompt_tokens: 2,
+            num_decode_tokens: Some(10),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let request = Arc::new(request);
+        tokio::spawn(async move {
+            backend.generate(request.clone(), tx).await;
+        });
+        let num_tokens = Arc::new(AtomicU64::new(0));
+        let num_tokens_clone = num_tokens.clone();
+        let t = tokio::spawn(async move {
+            while let Some(item) = rx.recv().await {
+                let response = item;
+                assert!(!response.failed);
+                num_tokens_clone.fetch_add(
+                    response.num_generated_tokens,
+                    std::sync::atomic::Ordering::SeqCst,
+                );
+            }
+        });
+        t.await.unwrap();
+        assert_eq!(num_tokens.load(std::sync::atomic::Ordering::SeqCst), 16_u64);
+    }
+
+    /// Test that the timings are correct
+    /// The tests may be flaky due to the nature of the SSE connection (it may depend on the testing environment)
+    /// We need to account for the time it takes to establish the connection
+    /// and the time it takes to receive the first message
+    #[tokio::test]
+    async fn test_openai_timings() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("POST", "/v1/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(|w| {
+                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                // sleep for 500ms
+                sleep(std::time::Duration::from_millis(500));
+                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                w.write_all(b"data: {\"choices\": [{\"message\": {\"content\": \"Hello, world!Hello, world!Hello, world!Hello, world!\", \"role\": \"user\"}, \"finish_reason\": \"stop\", \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                w.write_all(b"data: [DONE]\n\n")
+            })
+            .create_async().await;
+        let url = s.url();
+        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            url,
+            "gpt2".to_string(),
+            tokenizer,
+            time::Duration::from_secs(10),
+            RequestOptions::default(),
+        )
+        .unwrap();
+        let request = TextGenerationRequest {
+            prompt: "Hello, world!".to_string(),
+            num_prompt_tokens: 2,
+            num_decode_tokens: Some(16),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let request = Arc::new(request);
+        tokio::spawn(async move {
+            backend.generate(request.clone(), tx.clone()).await;
+        });
+        let results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                max_vus: 1,
+                duration: Duration::from_secs(10),
+                rate: None,
+                ..Default::default()
+            },
+        );
+        let results = Arc::new(RwLock::new(results));
+        let results_clone = results.clone();
+        let t = tokio::spawn(async move {
+            let mut handle = results_clone.write().await;
+            while let Some(item) = rx.recv().await {
+                let response = item;
+                handle.add_response(response);
+            }
+        });
+        t.await.unwrap();
+        let results = results.read().await;
+        let e2e_latency_avg = results.e2e_latency_avg().unwrap();
+        let inter_token_latency_avg = results.inter_token_latency_avg().unwrap();
+        let ttft = results.time_to_first_token_avg().unwrap();
+
+        let e2e_timing_overhead = Duration::from_millis(10);
+        let expected_e2e_latency_avg = Duration::from_millis(500);
+        let expected_inter_token_latency_avg = Duration::from_millis(33); // 16-1 tokens with a 500ms delay
+        let inter_token_latency_overhead = Duration::from_millis(3);
+        let expected_ttft = Duration::from_millis(3); // account for http overhead
+        assert!(
+            e2e_latency_avg > expected_e2e_latency_avg
+                && e2e_latency_avg < expected_e2e_latency_avg + e2e_timing_overhead,
+            "e2e_latency_avg: {:?} < {:?} < {:?}",
+            expected_e2e_latency_avg,
+            e2e_latency_avg,
+            expected_e2e_latency_avg + e2e_timing_overhead
+        );
+        assert!(
+            inter_token_latency_avg > expected_inter_token_latency_avg
+                && inter_token_latency_avg
+                    < expected_inter_token_latency_avg + inter_token_latency_overhead,
+            "inter_token_latency_avg: {:?} < {:?} < {:?}",
+            expected_inter_token_latency_avg,
+            inter_token_latency_avg,
+            expected_inter_token_latency_avg + inter_token_latency_overhead
+        );
+        assert!(
+            ttft < expected_ttft,
+            "TTFT: {:?} < {:?}",
+            ttft,
+            expected_ttft
+        );
+    }
+
+    /// Test that server errors are handled correctly
+    #[tokio::test]
+    async fn test_openai_fails_on_error() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("POST", "/v1/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(|w| w.write_all(b"data: {\"error\": \"Internal server error\"}\n\n"))
+            .create_async()
+            .await;
+        let url = s.url();
+        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            url,
+            "gpt2".to_string(),
+            tokenizer,
+            time::Duration::from_secs(10),
+            RequestOptions::default(),
+        )
+        .unwrap();
+        let request = TextGenerationRequest {
+            prompt: "Hello, world!".to_string(),
+            num_prompt_tokens: 2,
+            num_decode_tokens: Some(16),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let request = Arc::new(request);
+        tokio::spawn(async move {
+            backend.generate(request.clone(), tx.clone()).await;
+        });
+        let responses = Arc::new(RwLock::new(Vec::new()));
+        let responses_clone = responses.clone();
+        let t = tokio::spawn(async move {
+            let mut handle = responses_clone.write().await;
+            while let Some(item) = rx.recv().await {
+                let response = item;
+                handle.push(response);
+            }
+        });
+        t.await.unwrap();
+        let responses = responses.read().await;
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].failed);
+    }
+
+    /// Test that bad responses are handled correctly
+    #[tokio::test]
+    async fn test_openai_fails_on_bad_response() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("POST", "/v1/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(|w| w.write_all(b"this is wrong\n\n"))
+            .create_async()
+            .await;
+        let url = s.url();
+        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            url,
+            "gpt2".to_string(),
+            tokenizer,
+            time::Duration::from_secs(10),
+            RequestOptions::default(),
+        )
+        .unwrap();
+        let request = TextGenerationRequest {
+            prompt: "Hello, world!".to_string(),
+            num_prompt_tokens: 2,
+            num_decode_tokens: Some(16),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let request = Arc::new(request);
+        tokio::spawn(async move {
+            backend.generate(request.clone(), tx.clone()).await;
+        });
+        let responses = Arc::new(RwLock::new(Vec::new()));
+        let responses_clone = responses.clone();
+        let t = tokio::spawn(async move {
+            let mut handle = responses_clone.write().await;
+            while let Some(item) = rx.recv().await {
+                let response = item;
+                handle.push(response);
+            }
+        });
+        t.await.unwrap();
+        let responses = responses.read().await;
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].failed);
+    }
+
+    /// Test that malformed JSON responses are handled correctly
+    #[tokio::test]
+    async fn test_openai_fails_on_malformed_json() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("POST", "/v1/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(|w| w.write_all(b"data: {\"foo\": \"bar\"}\n\n"))
+            .create_async()
+            .await;
+        let url = s.url();
+        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            url,
+            "gpt2".to_string(),
+            tokenizer,
+            time::Duration::from_secs(10),
+            RequestOptions::default(),
+        )
+        .unwrap();
+        let request = TextGenerationRequest {
+            prompt: "Hello, world!".to_string(),
+            num_prompt_tokens: 2,
+            num_decode_tokens: Some(16),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let request = Arc::new(request);
+        tokio::spawn(async move {
+            backend.generate(request.clone(), tx.clone()).await;
+        });
+        let responses = Arc::new(RwLock::new(Vec::new()));
+        let responses_clone = responses.clone();
+        let t = tokio::spawn(async move {
+            let mut handle = responses_clone.write().await;
+            while let Some(item) = rx.recv().await {
+                let response = item;
+                handle.push(response);
+            }
+        });
+        t.await.unwrap();
+        let responses = responses.read().await;
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].failed);
+    }
+
+    /// Test that request timeout is handled correctly
+    #[tokio::test]
+    async fn test_timeout_should_fail_request() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("POST", "/v1/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(|w| {
+                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                // sleep for 5s
+                sleep(std::time::Duration::from_secs(5));
+                w.write_all(b"data: [DONE]\n\n")
+            })
+            .create_async().await;
+        let url = s.url();
+        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            url,
+            "gpt2".to_string(),
+            tokenizer,
+            time::Duration::from_secs(1),
+            RequestOptions::default(),
+        )
+        .unwrap();
+        let request = TextGenerationRequest {
+            prompt: "Hello, world!".to_string(),
+            num_prompt_tokens: 2,
+            num_decode_tokens: Some(16),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let request = Arc::new(request);
+        tokio::spawn(async move {
+            backend.generate(request.clone(), tx.clone()).await;
+        });
+        let reponses = Arc::new(RwLock::new(Vec::new()));
+        let responses_clone = reponses.clone();
+        let t = tokio::spawn(async move {
+            while let Some(item) = rx.recv().await {
+                responses_clone.write().await.push(item);
+            }
+        });
+        t.await.unwrap();
+        let responses = reponses.read().await;
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].failed);
+        assert_eq!(responses[0].error_class, Some(ErrorClass::Timeout));
+    }
+
+    /// Test that a request receiving no token before the first token timeout fails as timed out
+    #[tokio::test]
+    async fn test_first_token_timeout() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("POST", "/v1/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_chunked_body(|w| {
+                sleep(std::time::Duration::from_secs(2));
+                w.write_all(b"data: [DONE]\n\n")
+            })
+            .create_async()
+            .await;
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            s.url(),
+            "gpt2".to_string(),
+            Arc::new(Tokenizer::new(tokenizers::models::bpe::BPE::default())),
+            time::Duration::from_secs(10),
+            RequestOptions {
+                timeouts: RequestTimeouts {
+                    first_token: Some(time::Duration::from_millis(200)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello, world!".to_string(),
+            num_prompt_tokens: 2,
+            num_decode_tokens: Some(16),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        });
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let start = std::time::Instant::now();
+        backend.generate(request, tx).await;
+        let response = rx.recv().await.unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        assert!(response.failed);
+        assert_eq!(response.error_class, Some(ErrorClass::Timeout));
+    }
+
+    /// Test that conversations are correctly loaded
+    #[tokio::test]
+    #[cfg(feature = "datasets")]
+    async fn test_load_conversations_from_file() {
+        let filepath = PathBuf::from("test_data/conversations.json");
+        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+        let prompt_tokenize_opts = TokenizeOptions::default();
+        let decode_tokenize_opts = TokenizeOptions::default();
+        let generator = ConversationTextRequestGenerator::load(
+            filepath,
+            tokenizer,
+            Some(prompt_tokenize_opts),
+            Some(decode_tokenize_opts),
+        )
+        .unwrap();
+        assert_eq!(generator.requests.len(), 17005);
+    }
+
+    /// Test that conversations are bounded by the min/max number of tokens
+    #[tokio::test]
+    #[cfg(feature = "datasets")]
+    async fn test_load_conversations_bounded() {
+        let filepath = PathBuf::from("test_data/conversations.json");
+        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+        let prompt_tokenize_opts = TokenizeOptions {
+            num_tokens: None,
+            min_tokens: 4,
+            max_tokens: 1024,
+            variance: 0,
+        };
+        let decode_tokenize_opts = TokenizeOptions::default();
+        let generator = ConversationTextRequestGenerator::load(
+            filepath,
+            tokenizer,
+            Some(prompt_tokenize_opts),
+            Some(decode_tokenize_opts),
+        )
+        .unwrap();
+        let min_tokens = generator
+            .requests
+            .iter()
+            .map(|r| r.num_prompt_tokens)
+            .min()
+            .unwrap();
+        let max_tokens = generator
+            .requests
+            .iter()
+            .map(|r| r.num_prompt_tokens)
+            .max()
+            .unwrap();
+        assert!(min_tokens >= 4, "Min tokens: {}", min_tokens);
+        assert!(max_tokens <= 1024, "Max tokens: {}", max_tokens);
+    }
+
+    /// Test that conversations prompts have the correct number of tokens
+    #[tokio::test]
+    #[cfg(feature = "datasets")]
+    async fn test_load_conversations_fixed_tokens() {
+        let filepath = PathBuf::from("test_data/conversations.json");
+        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+        let prompt_tokenize_opts = TokenizeOptions {
+            num_tokens: Some(200),
+            min_tokens: 200,
+            max_tokens: 200,
+            variance: 0,
+        };
+        let decode_tokenize_opts = TokenizeOptions::default();
+        let generator = ConversationTextRequestGenerator::load(
+            filepath,
+            tokenizer,
+            Some(prompt_tokenize_opts),
+            Some(decode_tokenize_opts),
+        )
+        .unwrap();
+        for r in generator.requests.iter() {
+            assert_eq!(r.num_prompt_tokens, 200);
+        }
+    }
+
+    #[test]
+    fn test_random_uuid() {
+        let uuid = random_uuid();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(uuid.matches('-').count(), 4);
+        assert_eq!(&uuid[14..15], "4");
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"));
+        assert_ne!(uuid, random_uuid());
+    }
+
+    #[test]
+    fn test_prompt_template() {
+        let template: PromptTemplate = "{{id}} {index}/{uuid}\\n{prompt}".parse().unwrap();
+        let rendered = template.render("Hello", 42);
+        let (header, prompt) = rendered.split_once('\n').unwrap();
+        assert_eq!(prompt, "Hello");
+        let (index, uuid) = header
+            .strip_prefix("{id} ")
+            .unwrap()
+            .split_once('/')
+            .unwrap();
+        assert_eq!(index, "42");
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert_ne!(rendered, template.render("Hello", 42));
+        let suffix = PromptTemplate::try_new(None, "suffix").unwrap().unwrap();
+        assert!(suffix.render("Hello", 0).starts_with("Hello\n"));
+        assert!(PromptTemplate::try_new(None, "none").unwrap().is_none());
+        assert!(PromptTemplate::try_new(Some("{prompt}"), "prefix").is_err());
+        assert!(PromptTemplate::try_new(None, "random").is_err());
+        assert!("{index}".parse::<PromptTemplate>().is_err());
+        assert!("{prompt} {user}".parse::<PromptTemplate>().is_err());
+        assert!("{prompt".parse::<PromptTemplate>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "datasets")]
+    fn test_fim() {
+        use rand::SeedableRng;
+        assert_eq!(
+            render_fim("<PRE> {prefix} <SUF>{suffix} <MID>", "a{suffix}", "b"),
+            "<PRE> a{suffix} <SUF>b <MID>"
+        );
+        let words = [
+            "[UNK]", "def", "add", "(", "a", ",", "b", "):", "c", "=", "+", "d", "*", "2", "e",
+            "-", "1", "return",
+        ];
+        let mut tokenizer = word_level_tokenizer(&words);
+        tokenizer.with_pre_tokenizer(Some(tokenizers::pre_tokenizers::whitespace::Whitespace {}));
+        let content = "def add(a, b):\n    c = a + b\n    d = c * 2\n    e = d - 1\n    return e\n";
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        for _ in 0..20 {
+            let (prefix, middle, suffix) =
+                fim_split(content, &tokenizer, None, None, &mut rng).unwrap();
+            assert!(!prefix.is_empty() && !suffix.is_empty());
+            assert!((1..=3).contains(&middle.lines().count()));
+            assert_eq!(format!("{prefix}{middle}{suffix}"), content);
+        }
+        let (prefix, _, suffix) =
+            fim_split(content, &tokenizer, Some(2), Some(1), &mut rng).unwrap();
+        assert_eq!(tokenizer.encode(prefix, false).unwrap().len(), 2);
+        assert_eq!(tokenizer.encode(suffix, false).unwrap().len(), 1);
+        assert!(fim_split("one line\n", &tokenizer, None, None, &mut rng).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "datasets")]
+    fn test_load_rag() {
+        let mut tokenizer = word_level_tokenizer(&["[UNK]"]);
+        tokenizer.with_pre_tokenizer(Some(tokenizers::pre_tokenizers::whitespace::Whitespace {}));
+        let tokenizer = Arc::new(tokenizer);
+        // passages of 10 words, the first one shared by two queries
+        let mut corpus = String::new();
+        for i in 0..50 {
+            let text = format!("passage{i} {}", "word ".repeat(9));
+            let entry = match i {
+                0 => serde_json::json!({"context": text, "instruction": "what?", "response": "a"}),
+                1 => serde_json::json!({"text": text, "question": "who?"}),
+                _ => serde_json::json!({"text": text}),
+            };
+            corpus.push_str(&format!("{entry}\n"));
+        }
+        corpus.push_str(&format!(
+            "{}\n",
+            serde_json::json!({"text": "passage0 word word word word word word word word word", "question": "why?"})
+        ));
+        let path = std::env::temp_dir().join(format!("rag-{}.jsonl", std::process::id()));
+        std::fs::write(&path, corpus).unwrap();
+
+        let generator = ConversationTextRequestGenerator::load_rag(
+            path.clone(),
+            tokenizer.clone(),
+            None,
+            None,
+            4,
+        )
+        .unwrap();
+        assert_eq!(generator.requests.len(), 3);
+        for request in &generator.requests {
+            assert_eq!(request.prompt.matches("[4] passage").count(), 1);
+            assert!(!request.prompt.contains("[5]"));
+            assert!(request.num_decode_tokens.is_none());
+        }
+        let what = generator
+            .requests
+            .iter()
+            .find(|r| r.prompt.ends_with("Question: what?\nAnswer:"))
+            .unwrap();
+        assert!(what.prompt.contains("passage0 "));
+        assert_eq!(what.expected_answer.as_deref(), Some("a"));
+
+        // stuffed up to the sampled prompt length, never above the maximum
+        let prompt_options = TokenizeOptions {
+            num_tokens: Some(200),
+            min_tokens: 200,
+            max_tokens: 205,
+            variance: 0,
+        };
+        let generator = ConversationTextRequestGenerator::load_rag(
+            path.clone(),
+            tokenizer.clone(),
+            Some(prompt_options),
+            None,
+            4,
+        )
+        .unwrap();
+        for request in &generator.requests {
+            assert!(request.prompt.contains("[20] passage"));
+            assert!(!request.prompt.contains("[21]"));
+        }
+
+        std::fs::write(&path, "{\"text\": \"no question\"}\n").unwrap();
+        assert!(
+            ConversationTextRequestGenerator::load_rag(path.clone(), tokenizer, None, None, 4)
+                .is_err()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "datasets")]
+    fn test_load_summarization() {
+        // the unknown token is a word, so random tokens decode and encode back to as many tokens
+        let words = ["unk", "alpha", "beta", "gamma", "delta"];
+        let mut tokenizer = word_level_tokenizer(&words);
+        tokenizer.with_pre_tokenizer(Some(tokenizers::pre_tokenizers::whitespace::Whitespace {}));
+        let tokenizer = Arc::new(tokenizer);
+        // "Summarize the following document . Summary :"
+        let instruction_tokens = 7;
+        let length = |num_tokens| TokenizeOptions {
+            num_tokens: Some(num_tokens),
+            min_tokens: num_tokens,
+            max_tokens: num_tokens,
+            variance: 0,
+        };
+        let path = std::env::temp_dir().join(format!("documents-{}.jsonl", std::process::id()));
+        let documents = (0..4)
+            .map(|i| serde_json::json!({"text": format!("{} ", words[1 + i]).repeat(10)}))
+            .map(|entry| format!("{entry}\n"))
+            .collect::<String>();
+        std::fs::write(&path, documents).unwrap();
+
+        let generator = ConversationTextRequestGenerator::load_summarization(
+            Some(path.clone()),
+            tokenizer.clone(),
+            None,
+            None,
+            0.5,
+        )
+        .unwrap();
+        assert_eq!(generator.requests.len(), 4);
+        for request in &generator.requests {
+            assert_eq!(request.num_prompt_tokens, 10 + instruction_tokens);
+            assert_eq!(request.num_decode_tokens, Some(8));
+        }
 
-    fn stop(&mut self) {
-        self.end_time = Some(tokio::time::Instant::now());
-    }
+        // short documents are followed by the next ones up to the sampled length
+        let generator = ConversationTextRequestGenerator::load_summarization(
+            Some(path.clone()),
+            tokenizer.clone(),
+            Some(length(25)),
+            Some(length(3)),
+            0.5,
+        )
+        .unwrap();
+        for request in &generator.requests {
+            assert_eq!(request.num_prompt_tokens, 25 + instruction_tokens);
+            assert_eq!(request.num_decode_tokens, Some(3));
+        }
+        std::fs::remove_file(path).unwrap();
 
-    fn fail(&mut self) {
-        self.end_time = Some(tokio::time::Instant::now());
-        self.failed = true;
+        let generator = ConversationTextRequestGenerator::load_summarization(
+            None,
+            tokenizer.clone(),
+            Some(length(40)),
+            None,
+            0.1,
+        )
+        .unwrap();
+        assert_eq!(generator.requests.len(), SYNTHETIC_DOCUMENTS);
+        for request in &generator.requests {
+            assert_eq!(request.num_prompt_tokens, 40 + instruction_tokens);
+            assert_eq!(request.num_decode_tokens, Some(4));
+        }
+        assert!(ConversationTextRequestGenerator::load_summarization(
+            None, tokenizer, None, None, 0.1
+        )
+        .is_err());
     }
 
-    fn add_tokens(&mut self, num_tokens: u64) {
-        self.num_generated_tokens += num_tokens;
-        let time_to_generate = self.last_received_token_time.elapsed();
-        // make the assumption that when returned simultaneously, tokens were generated at a constant rate
-        time_to_generate.checked_div(num_tokens as u32).unwrap();
-        self.last_received_token_time = tokio::time::Instant::now();
-        self.times_to_tokens.push(time_to_generate);
+    #[test]
+    #[cfg(feature = "datasets")]
+    fn test_clamp_to_context() {
+        let requests = [
+            (10, Some(50)),
+            (100, Some(50)),
+            (200, None),
+            (300, Some(10)),
+        ]
+        .into_iter()
+        .map(
+            |(num_prompt_tokens, num_decode_tokens)| TextGenerationRequest {
+                prompt: "prompt".to_string(),
+                num_prompt_tokens,
+                num_decode_tokens,
+                system_prompt: None,
+                expected_answer: None,
+                context_clamped: false,
+            },
+        )
+        .collect();
+        let mut generator = ConversationTextRequestGenerator {
+            requests,
+            prompt_template: None,
+            current_index: Arc::from(AtomicU64::new(0)),
+        };
+        assert_eq!(generator.clamp_to_context(128).unwrap(), (1, 2));
+        assert_eq!(generator.requests.len(), 2);
+        assert_eq!(generator.requests[0].num_decode_tokens, Some(50));
+        assert!(!generator.requests[0].context_clamped);
+        assert_eq!(generator.requests[1].num_decode_tokens, Some(28));
+        assert!(generator.requests[1].context_clamped);
+        assert!(generator.clamp_to_context(10).is_err());
     }
 
-    pub fn time_to_first_token(&self) -> Option<std::time::Duration> {
-        match self.start_time {
-            Some(_) => self.times_to_tokens.first().copied(),
-            None => None,
-        }
+    #[tokio::test]
+    async fn test_fetch_max_context_length() {
+        let mut s = mockito::Server::new_async().await;
+        s.mock("GET", "/v1/models")
+            .with_status(200)
+            .with_body(r#"{"object": "list", "data": [{"id": "other", "max_model_len": 2048}, {"id": "model", "max_model_len": 8192}]}"#)
+            .create_async()
+            .await;
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            s.url(),
+            "model".to_string(),
+            Arc::new(word_level_tokenizer(&["[UNK]"])),
+            Duration::from_secs(10),
+            RequestOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            fetch_max_context_length(&backend, "model", None).await,
+            Some(8192)
+        );
     }
 
-    pub fn inter_token_latency(&self) -> Option<std::time::Duration> {
-        match self.times_to_tokens.len() {
-            0 => None,
-            1 => Some(std::time::Duration::new(0, 0)),
-            _ => {
-                let mut total_time = std::time::Duration::new(0, 0);
-                for i in 1..self.times_to_tokens.len() {
-                    total_time += self.times_to_tokens[i];
-                }
-                Some(total_time / (self.num_generated_tokens as u32 - 1))
+    #[test]
+    fn test_request_generators() {
+        let requests = (0..3)
+            .map(|i| TextGenerationRequest {
+                prompt: format!("prompt {i}"),
+                num_prompt_tokens: 2,
+                num_decode_tokens: None,
+                system_prompt: None,
+                expected_answer: None,
+                context_clamped: false,
+            })
+            .collect();
+        let generator = ConversationTextRequestGenerator {
+            requests,
+            prompt_template: None,
+            current_index: Arc::from(AtomicU64::new(0)),
+        };
+        // concurrent callers share the cursor, so every request is handed out equally often
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..30)
+                            .map(|_| generator.generate_request().prompt)
+                            .filter(|p| p == "prompt 0")
+                            .count()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(counts.iter().sum::<usize>(), 40);
+        let prefetcher = PrefetchingRequestGenerator::new(generator, 2);
+        let prompts: Vec<String> = (0..7)
+            .map(|_| prefetcher.generate_request().prompt)
+            .collect();
+        assert_eq!(
+            prompts,
+            vec![
+                "prompt 0", "prompt 1", "prompt 2", "prompt 0", "prompt 1", "prompt 2", "prompt 0"
+            ]
+        );
+        // the panic of the wrapped generator is the cause of the panic of the callers
+        struct PanickingGenerator;
+        impl TextRequestGenerator for PanickingGenerator {
+            fn generate_request(&self) -> TextGenerationRequest {
+                panic!("no prompts left");
             }
         }
+        let prefetcher = PrefetchingRequestGenerator::new(PanickingGenerator, 2);
+        let cause = std::panic::catch_unwind(|| prefetcher.generate_request()).unwrap_err();
+        assert_eq!(
+            cause.downcast_ref::<String>().unwrap(),
+            "Request prefetch thread stopped: no prompts left"
+        );
     }
-    pub fn e2e_latency(&self) -> Option<std::time::Duration> {
-        match self.start_time {
-            Some(start_time) => self.end_time.map(|end_time| end_time - start_time),
-            None => None,
-        }
+
+    #[tokio::test]
+    async fn test_build_client() {
+        let (_, base_url) = build_client("http://[::1]:8080".to_string(), None).unwrap();
+        assert_eq!(base_url, "http://[::1]:8080");
+        // serve a single response over a Unix domain socket
+        let path = std::env::temp_dir().join(format!("benchmark-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .await
+                .unwrap();
+        });
+        let (client, base_url) = build_client(format!("unix://{}", path.display()), None).unwrap();
+        assert_eq!(base_url, "http://localhost");
+        let response = client
+            .get(format!("{base_url}/health"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        let _ = std::fs::remove_file(&path);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::executors::ExecutorConfig;
-    use crate::results::BenchmarkResults;
-    use crate::scheduler::ExecutorType;
-    use std::sync::atomic::AtomicU64;
-    use std::thread::sleep;
-    use std::time::Duration;
-    use tokio::sync::RwLock;
+    #[test]
+    fn test_tokenizer_error() {
+        let err = tokenizer_error(
+            "meta-llama/Llama-3.1-8B-Instruct",
+            "request error: status code 401".to_string(),
+        );
+        assert!(err.to_string().contains("gated"));
+        let err = tokenizer_error("gpt2", "connection refused".to_string());
+        assert!(!err.to_string().contains("gated"));
+    }
 
     #[tokio::test]
-    async fn test_openai_token_count() {
+    async fn test_completions_endpoint() {
         let mut s = mockito::Server::new_async().await;
-        s.mock("POST", "/v1/chat/completions")
+        let mock = s
+            .mock("POST", "/v1/completions")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "prompt": "Be brief\n\nHello",
+                "stream": true,
+            })))
             .with_status(200)
             .with_header("content-type", "text/event-stream")
             .with_chunked_body(|w| {
-                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
-                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
-                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
-                w.write_all(b"data: {\"choices\": [{\"message\": {\"content\": \"Hello, world!Hello, world!Hello, world!Hello, world!\", \"role\": \"user\"}, \"finish_reason\": \"stop\", \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                w.write_all(b"data: {\"choices\": [{\"text\": \"Hello\", \"finish_reason\": null}]}\n\n").unwrap();
+                w.write_all(b"data: {\"choices\": [{\"text\": \"world\", \"finish_reason\": \"length\"}]}\n\n").unwrap();
                 w.write_all(b"data: [DONE]\n\n")
             })
-            .create_async().await;
-        let url = s.url();
-        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+            .create_async()
+            .await;
+        // every chunk is a single unknown token
         let backend = OpenAITextGenerationBackend::try_new(
             "".to_string(),
-            url,
-            "gpt2".to_string(),
-            tokenizer,
-            time::Duration::from_secs(10),
+            s.url(),
+            "model".to_string(),
+            Arc::new(word_level_tokenizer(&["[UNK]"])),
+            Duration::from_secs(10),
+            RequestOptions {
+                endpoint: "completions".parse().unwrap(),
+                ..Default::default()
+            },
         )
         .unwrap();
-        let request = TextGenerationRequest {
-            prompt: "Hello, world!".to_string(),
-            num_prompt_tokens: 2,
-            num_decode_tokens: Some(10),
-            system_prompt: None,
-        };
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let request = Arc::new(request);
-        tokio::spawn(async move {
-            backend.generate(request.clone(), tx).await;
-        });
-        let num_tokens = Arc::new(AtomicU64::new(0));
-        let num_tokens_clone = num_tokens.clone();
-        let t = tokio::spawn(async move {
-            while let Some(item) = rx.recv().await {
-                let response = item;
-                assert_eq!(response.failed, false);
-                num_tokens_clone.fetch_add(
-                    response.num_generated_tokens,
-                    std::sync::atomic::Ordering::SeqCst,
-                );
-            }
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(2),
+            system_prompt: Some("Be brief".to_string()),
+            expected_answer: None,
+            context_clamped: false,
         });
-        t.await.unwrap();
-        assert_eq!(
-            num_tokens.load(std::sync::atomic::Ordering::SeqCst),
-            16 as u64
-        );
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        backend.generate(request, tx).await;
+        let response = rx.recv().await.unwrap();
+        mock.assert_async().await;
+        assert!(!response.failed);
+        assert_eq!(response.num_generated_tokens, 2);
+        assert_eq!(response.generated_text, "Helloworld");
+        assert!("responses".parse::<OpenAIEndpoint>().is_err());
     }
 
-    /// Test that the timings are correct
-    /// The tests may be flaky due to the nature of the SSE connection (it may depend on the testing environment)
-    /// We need to account for the time it takes to establish the connection
-    /// and the time it takes to receive the first message
     #[tokio::test]
-    async fn test_openai_timings() {
+    async fn test_compare_streaming() {
         let mut s = mockito::Server::new_async().await;
-        s.mock("POST", "/v1/chat/completions")
+        let streamed = s
+            .mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"stream": true}),
+            ))
             .with_status(200)
             .with_header("content-type", "text/event-stream")
             .with_chunked_body(|w| {
-                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
-                // sleep for 500ms
-                sleep(std::time::Duration::from_millis(500));
-                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
-                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
-                w.write_all(b"data: {\"choices\": [{\"message\": {\"content\": \"Hello, world!Hello, world!Hello, world!Hello, world!\", \"role\": \"user\"}, \"finish_reason\": \"stop\", \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
+                w.write_all(b"data: {\"choices\": [{\"delta\": {\"content\": \"Hello\"}, \"finish_reason\": null}]}\n\n").unwrap();
+                w.write_all(b"data: {\"choices\": [{\"delta\": {\"content\": \"world\"}, \"finish_reason\": \"length\"}]}\n\n").unwrap();
                 w.write_all(b"data: [DONE]\n\n")
             })
-            .create_async().await;
-        let url = s.url();
-        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+            .create_async()
+            .await;
+        let unstreamed = s
+            .mock("POST", "/v1/chat/completions")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"stream": false}),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_header("x-queue-time", "3")
+            .with_header("x-inference-time", "42")
+            .with_body(
+                serde_json::json!({
+                    "choices": [{
+                        "message": {"role": "assistant", "content": "Hello world"},
+                        "finish_reason": "length",
+                    }],
+                    "usage": {"prompt_tokens": 1, "completion_tokens": 2},
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        // every chunk, and the whole unstreamed text, is a single unknown token
         let backend = OpenAITextGenerationBackend::try_new(
             "".to_string(),
-            url,
-            "gpt2".to_string(),
-            tokenizer,
-            time::Duration::from_secs(10),
+            s.url(),
+            "model".to_string(),
+            Arc::new(word_level_tokenizer(&["[UNK]"])),
+            Duration::from_secs(10),
+            RequestOptions {
+                compare_streaming: true,
+                ..Default::default()
+            },
         )
         .unwrap();
-        let request = TextGenerationRequest {
-            prompt: "Hello, world!".to_string(),
-            num_prompt_tokens: 2,
-            num_decode_tokens: Some(16),
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(2),
             system_prompt: None,
-        };
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let request = Arc::new(request);
-        tokio::spawn(async move {
-            backend.generate(request.clone(), tx.clone()).await;
-        });
-        let results = BenchmarkResults::new(
-            "test".to_string(),
-            ExecutorType::ConstantArrivalRate,
-            ExecutorConfig {
-                max_vus: 1,
-                duration: Duration::from_secs(10),
-                rate: None,
-            },
-        );
-        let results = Arc::new(RwLock::new(results));
-        let results_clone = results.clone();
-        let t = tokio::spawn(async move {
-            let mut handle = results_clone.write().await;
-            while let Some(item) = rx.recv().await {
-                let response = item;
-                handle.add_response(response);
-            }
+            expected_answer: None,
+            context_clamped: false,
         });
-        t.await.unwrap();
-        let results = results.read().await;
-        let e2e_latency_avg = results.e2e_latency_avg().unwrap();
-        let inter_token_latency_avg = results.inter_token_latency_avg().unwrap();
-        let ttft = results.time_to_first_token_avg().unwrap();
-
-        let e2e_timing_overhead = Duration::from_millis(10);
-        let expected_e2e_latency_avg = Duration::from_millis(500);
-        let expected_inter_token_latency_avg = Duration::from_millis(33); // 16-1 tokens with a 500ms delay
-        let inter_token_latency_overhead = Duration::from_millis(3);
-        let expected_ttft = Duration::from_millis(3); // account for http overhead
-        assert!(
-            e2e_latency_avg > expected_e2e_latency_avg
-                && e2e_latency_avg < expected_e2e_latency_avg + e2e_timing_overhead,
-            "e2e_latency_avg: {:?} < {:?} < {:?}",
-            expected_e2e_latency_avg,
-            e2e_latency_avg,
-            expected_e2e_latency_avg + e2e_timing_overhead
-        );
-        assert!(
-            inter_token_latency_avg > expected_inter_token_latency_avg
-                && inter_token_latency_avg
-                    < expected_inter_token_latency_avg + inter_token_latency_overhead,
-            "inter_token_latency_avg: {:?} < {:?} < {:?}",
-            expected_inter_token_latency_avg,
-            inter_token_latency_avg,
-            expected_inter_token_latency_avg + inter_token_latency_overhead
-        );
-        assert!(
-            ttft < expected_ttft,
-            "TTFT: {:?} < {:?}",
-            ttft,
-            expected_ttft
-        );
+        let (tx, mut rx) = tokio::sync::mpsc::channel(2);
+        backend.generate(request, tx).await;
+        let mut responses = [rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+        responses.sort_by_key(|r| r.streamed);
+        streamed.assert_async().await;
+        unstreamed.assert_async().await;
+        let (unstreamed, streamed) = (&responses[0], &responses[1]);
+        assert_eq!(unstreamed.streamed, Some(false));
+        assert!(!unstreamed.failed);
+        assert_eq!(unstreamed.generated_text, "Hello world");
+        assert_eq!(unstreamed.num_generated_tokens, 1);
+        assert_eq!(unstreamed.usage_completion_tokens, Some(2));
+        // usage is only requested with prompt caching
+        assert_eq!(unstreamed.cached_prompt_tokens, None);
+        let details = unstreamed.server_details.as_ref().unwrap();
+        assert_eq!(details.queue_time_ms, Some(3.0));
+        assert_eq!(details.inference_time_ms, Some(42.0));
+        assert_eq!(streamed.streamed, Some(true));
+        assert!(!streamed.failed);
+        assert_eq!(streamed.num_generated_tokens, 2);
     }
 
-    /// Test that server errors are handled correctly
     #[tokio::test]
-    async fn test_openai_fails_on_error() {
+    async fn test_tgi_backend() {
         let mut s = mockito::Server::new_async().await;
-        s.mock("POST", "/v1/chat/completions")
+        let mock = s
+            .mock("POST", "/generate_stream")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "inputs": "Hello",
+                "parameters": {"max_new_tokens": 3, "details": true},
+            })))
             .with_status(200)
             .with_header("content-type", "text/event-stream")
-            .with_chunked_body(|w| w.write_all(b"data: {\"error\": \"Internal server error\"}\n\n"))
+            .with_chunked_body(|w| {
+                w.write_all(b"data: {\"token\": {\"id\": 1, \"text\": \"Hello\", \"special\": false}, \"generated_text\": null, \"details\": null}\n\n").unwrap();
+                w.write_all(b"data: {\"token\": {\"id\": 2, \"text\": \" world\", \"special\": false}, \"generated_text\": null, \"details\": null}\n\n").unwrap();
+                w.write_all(b"data: {\"token\": {\"id\": 0, \"text\": \"</s>\", \"special\": true}, \"generated_text\": \"Hello world\", \"details\": {\"finish_reason\": \"eos_token\", \"generated_tokens\": 3, \"seed\": null, \"queue_time\": 1.5, \"inference_time\": 20}}\n\n")
+            })
             .create_async()
             .await;
-        let url = s.url();
-        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
         let backend = OpenAITextGenerationBackend::try_new(
             "".to_string(),
-            url,
-            "gpt2".to_string(),
-            tokenizer,
-            time::Duration::from_secs(10),
+            s.url(),
+            "model".to_string(),
+            Arc::new(word_level_tokenizer(&["[UNK]"])),
+            Duration::from_secs(10),
+            RequestOptions::default(),
         )
         .unwrap();
-        let request = TextGenerationRequest {
-            prompt: "Hello, world!".to_string(),
-            num_prompt_tokens: 2,
-            num_decode_tokens: Some(16),
+        let backend = TgiTextGenerationBackend::new(backend);
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(3),
             system_prompt: None,
-        };
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let request = Arc::new(request);
-        tokio::spawn(async move {
-            backend.generate(request.clone(), tx.clone()).await;
+            expected_answer: None,
+            context_clamped: false,
         });
-        let responses = Arc::new(RwLock::new(Vec::new()));
-        let responses_clone = responses.clone();
-        let t = tokio::spawn(async move {
-            let mut handle = responses_clone.write().await;
-            while let Some(item) = rx.recv().await {
-                let response = item;
-                handle.push(response);
-            }
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        backend.generate(request.clone(), tx).await;
+        let response = rx.recv().await.unwrap();
+        mock.assert_async().await;
+        assert!(!response.failed);
+        assert_eq!(response.num_generated_tokens, 3);
+        assert_eq!(response.times_to_tokens.len(), 3);
+        assert_eq!(response.generated_text, "Hello world");
+        let details = response.server_details.unwrap();
+        assert_eq!(details.finish_reason.as_deref(), Some("eos_token"));
+        assert_eq!(details.queue_time_ms, Some(1.5));
+        assert_eq!(details.inference_time_ms, Some(20.0));
+
+        // a count below the streamed tokens is ignored
+        let mut response = TextGenerationAggregatedResponse::default();
+        response.start(request);
+        let mut final_response = String::new();
+        for data in [
+            "{\"token\": {\"id\": 1, \"text\": \"Hello\", \"special\": false}, \"generated_text\": null, \"details\": null}",
+            "{\"token\": {\"id\": 2, \"text\": \" world\", \"special\": false}, \"generated_text\": \"Hello world\", \"details\": {\"finish_reason\": \"length\", \"generated_tokens\": 1}}",
+        ] {
+            TgiTextGenerationBackend::handle_chunk(data, &mut response, &mut final_response);
+        }
+        assert_eq!(response.num_generated_tokens, 2);
+        assert!(response.inter_token_latency().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_vllm_backend() {
+        let mut s = mockito::Server::new_async().await;
+        let mock = s
+            .mock("POST", "/generate")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "prompt": "Hello",
+                "max_tokens": 2,
+                "stream": true,
+            })))
+            .with_status(200)
+            .with_chunked_body(|w| {
+                w.write_all(b"{\"text\": [\"Hello world\"]}\n")?;
+                w.write_all(b"{\"text\": [\"Hello world world\"], \"finish_reason\": [\"length\"], \"cumulative_logprob\": [-0.5]}\n")
+            })
+            .create_async()
+            .await;
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            s.url(),
+            "model".to_string(),
+            Arc::new(word_level_tokenizer(&["[UNK]"])),
+            Duration::from_secs(10),
+            RequestOptions::default(),
+        )
+        .unwrap();
+        let backend = VllmTextGenerationBackend::new(backend);
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(2),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
         });
-        t.await.unwrap();
-        let responses = responses.read().await;
-        assert_eq!(responses.len(), 1);
-        assert_eq!(responses[0].failed, true);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        backend.generate(request, tx).await;
+        let response = rx.recv().await.unwrap();
+        mock.assert_async().await;
+        assert!(!response.failed);
+        assert!(response.end_time.is_some());
+        assert_eq!(response.num_generated_tokens, 2);
+        assert_eq!(response.generated_text, " world world");
+        let details = response.vllm_details.unwrap();
+        assert_eq!(details.finish_reason.as_deref(), Some("length"));
+        assert_eq!(details.cumulative_logprob, Some(-0.5));
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let classify = |status, body| ErrorClass::classify_body(status, body);
+        assert_eq!(
+            classify(
+                None,
+                r#"{"error": {"message": "This model's maximum context length is 4096 tokens", "type": "invalid_request_error", "code": "context_length_exceeded"}}"#
+            ),
+            ErrorClass::ContextLength
+        );
+        assert_eq!(
+            classify(
+                None,
+                r#"{"error": {"message": "You exceeded your current quota", "type": "insufficient_quota", "code": null}}"#
+            ),
+            ErrorClass::Quota
+        );
+        assert_eq!(
+            classify(
+                None,
+                r#"{"error": "Input validation error: temperature must be strictly positive", "error_type": "validation"}"#
+            ),
+            ErrorClass::Validation
+        );
+        assert_eq!(
+            classify(None, r#"{"error": "Internal server error"}"#),
+            ErrorClass::Server
+        );
+        assert_eq!(classify(Some(429), "Too Many Requests"), ErrorClass::Quota);
+        assert_eq!(classify(Some(503), "<html></html>"), ErrorClass::Server);
+        assert_eq!(classify(None, "not json"), ErrorClass::Other);
     }
 
-    /// Test that bad responses are handled correctly
     #[tokio::test]
-    async fn test_openai_fails_on_bad_response() {
+    async fn test_openai_classifies_error_status() {
         let mut s = mockito::Server::new_async().await;
         s.mock("POST", "/v1/chat/completions")
-            .with_status(200)
-            .with_header("content-type", "text/event-stream")
-            .with_chunked_body(|w| w.write_all(b"this is wrong\n\n"))
+            .with_status(400)
+            .with_body(r#"{"error": {"message": "max_tokens is too large", "type": "invalid_request_error", "code": null}}"#)
             .create_async()
             .await;
-        let url = s.url();
-        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
         let backend = OpenAITextGenerationBackend::try_new(
             "".to_string(),
-            url,
-            "gpt2".to_string(),
-            tokenizer,
-            time::Duration::from_secs(10),
+            s.url(),
+            "model".to_string(),
+            Arc::new(word_level_tokenizer(&["[UNK]"])),
+            Duration::from_secs(10),
+            RequestOptions::default(),
         )
         .unwrap();
-        let request = TextGenerationRequest {
-            prompt: "Hello, world!".to_string(),
-            num_prompt_tokens: 2,
-            num_decode_tokens: Some(16),
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(100000),
             system_prompt: None,
-        };
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let request = Arc::new(request);
-        tokio::spawn(async move {
-            backend.generate(request.clone(), tx.clone()).await;
-        });
-        let responses = Arc::new(RwLock::new(Vec::new()));
-        let responses_clone = responses.clone();
-        let t = tokio::spawn(async move {
-            let mut handle = responses_clone.write().await;
-            while let Some(item) = rx.recv().await {
-                let response = item;
-                handle.push(response);
-            }
+            expected_answer: None,
+            context_clamped: false,
         });
-        t.await.unwrap();
-        let responses = responses.read().await;
-        assert_eq!(responses.len(), 1);
-        assert_eq!(responses[0].failed, true);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        backend.generate(request, tx).await;
+        let response = rx.recv().await.unwrap();
+        assert!(response.failed);
+        assert_eq!(response.error_class, Some(ErrorClass::Validation));
+        assert_eq!(response.http_status, Some(400));
     }
 
-    /// Test that malformed JSON responses are handled correctly
     #[tokio::test]
-    async fn test_openai_fails_on_malformed_json() {
+    async fn test_backend_options() {
         let mut s = mockito::Server::new_async().await;
-        s.mock("POST", "/v1/chat/completions")
+        let mock = s
+            .mock("POST", "/gateway/chat")
+            .match_header("X-Tenant", "benchmark")
             .with_status(200)
             .with_header("content-type", "text/event-stream")
-            .with_chunked_body(|w| w.write_all(b"data: {\"foo\": \"bar\"}\n\n"))
+            .with_chunked_body(|w| {
+                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello\"}}]}\n\n")?;
+                w.write_all(b"data: [DONE]\n\n")
+            })
             .create_async()
             .await;
-        let url = s.url();
-        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
         let backend = OpenAITextGenerationBackend::try_new(
             "".to_string(),
-            url,
+            s.url(),
             "gpt2".to_string(),
-            tokenizer,
-            time::Duration::from_secs(10),
+            Arc::new(word_level_tokenizer(&["[UNK]"])),
+            Duration::from_secs(10),
+            RequestOptions {
+                path: Some("/gateway/chat".to_string()),
+                headers: vec![("X-Tenant".to_string(), "benchmark".to_string())],
+                ..Default::default()
+            },
         )
         .unwrap();
-        let request = TextGenerationRequest {
-            prompt: "Hello, world!".to_string(),
-            num_prompt_tokens: 2,
-            num_decode_tokens: Some(16),
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(1),
             system_prompt: None,
-        };
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let request = Arc::new(request);
-        tokio::spawn(async move {
-            backend.generate(request.clone(), tx.clone()).await;
-        });
-        let responses = Arc::new(RwLock::new(Vec::new()));
-        let responses_clone = responses.clone();
-        let t = tokio::spawn(async move {
-            let mut handle = responses_clone.write().await;
-            while let Some(item) = rx.recv().await {
-                let response = item;
-                handle.push(response);
-            }
+            expected_answer: None,
+            context_clamped: false,
         });
-        t.await.unwrap();
-        let responses = responses.read().await;
-        assert_eq!(responses.len(), 1);
-        assert_eq!(responses[0].failed, true);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        backend.generate(request, tx).await;
+        let response = rx.recv().await.unwrap();
+        mock.assert_async().await;
+        assert!(!response.failed);
+        assert_eq!(response.generated_text, "Hello");
+        assert_eq!("vLLM".parse::<BackendKind>().unwrap(), BackendKind::Vllm);
+        assert!("sglang".parse::<BackendKind>().is_err());
     }
 
-    /// Test that request timeout is handled correctly
     #[tokio::test]
-    async fn test_timeout_should_fail_request() {
+    async fn test_triton_backend() {
         let mut s = mockito::Server::new_async().await;
-        s.mock("POST", "/v1/chat/completions")
+        let mock = s
+            .mock("POST", "/v2/models/ensemble/generate_stream")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "text_input": "Hello",
+                "max_tokens": 2,
+                "stream": true,
+            })))
             .with_status(200)
             .with_header("content-type", "text/event-stream")
             .with_chunked_body(|w| {
-                w.write_all(b"data: {\"choices\": [{\"message\": null, \"finish_reason\": null, \"delta\": {\"content\": \"Hello, world!\"}}]}\n\n").unwrap();
-                // sleep for 5s
-                sleep(std::time::Duration::from_secs(5));
-                w.write_all(b"data: [DONE]\n\n")
+                w.write_all(b"data: {\"model_name\": \"ensemble\", \"model_version\": \"1\", \"text_output\": \"Hello\"}\n\n")?;
+                w.write_all(b"data: {\"model_name\": \"ensemble\", \"model_version\": \"1\", \"text_output\": \" world\"}\n\n")
             })
-            .create_async().await;
-        let url = s.url();
-        let tokenizer = Arc::new(Tokenizer::from_pretrained("gpt2", None).unwrap());
+            .create_async()
+            .await;
         let backend = OpenAITextGenerationBackend::try_new(
             "".to_string(),
-            url,
-            "gpt2".to_string(),
-            tokenizer,
-            time::Duration::from_secs(1),
+            s.url(),
+            "ensemble".to_string(),
+            Arc::new(word_level_tokenizer(&["[UNK]"])),
+            Duration::from_secs(10),
+            RequestOptions::default(),
         )
         .unwrap();
-        let request = TextGenerationRequest {
-            prompt: "Hello, world!".to_string(),
-            num_prompt_tokens: 2,
-            num_decode_tokens: Some(16),
+        let backend = TritonTextGenerationBackend::new(backend);
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(2),
             system_prompt: None,
-        };
+            expected_answer: None,
+            context_clamped: false,
+        });
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let request = Arc::new(request);
-        tokio::spawn(async move {
-            backend.generate(request.clone(), tx.clone()).await;
+        backend.generate(request, tx).await;
+        let response = rx.recv().await.unwrap();
+        mock.assert_async().await;
+        assert!(!response.failed);
+        assert_eq!(response.num_generated_tokens, 2);
+        assert_eq!(response.generated_text, "Hello world");
+    }
+
+    /// Backend failing its first requests with the same cause, sending an idempotency key
+    #[derive(Clone)]
+    struct FlakyBackend {
+        failures: Arc<AtomicU64>,
+        cause: ErrorClass,
+    }
+
+    #[async_trait]
+    impl TextGenerationBackend for FlakyBackend {
+        async fn generate(
+            &self,
+            request: Arc<TextGenerationRequest>,
+            sender: Sender<TextGenerationAggregatedResponse>,
+        ) {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start(request);
+            response.idempotency_key = Some(idempotency_key());
+            let failing = self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if failing {
+                response.error_class = Some(self.cause);
+                response.fail();
+            } else {
+                response.add_tokens(1);
+                response.stop();
+            }
+            sender.send(response).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrying_backend() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(1),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
         });
-        let reponses = Arc::new(RwLock::new(Vec::new()));
-        let responses_clone = reponses.clone();
-        let t = tokio::spawn(async move {
-            while let Some(item) = rx.recv().await {
-                responses_clone.write().await.push(item);
+        let generate_with = |failures, cause, idempotency_key| {
+            let backend = RetryingBackend::new(
+                Box::new(FlakyBackend {
+                    failures: Arc::new(AtomicU64::new(failures)),
+                    cause,
+                }),
+                RetryPolicy {
+                    idempotency_key,
+                    ..policy
+                },
+            );
+            let request = request.clone();
+            async move {
+                let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+                backend.generate(request, tx).await;
+                rx.recv().await.unwrap()
+            }
+        };
+        let generate =
+            |failures, cause| generate_with(failures, cause, IdempotencyKeyMode::PerRequest);
+        let response = generate(2, ErrorClass::Server).await;
+        assert!(!response.failed);
+        assert_eq!(response.retries, vec![ErrorClass::Server; 2]);
+        // retries reuse the key of the request
+        let key = response.idempotency_key.unwrap();
+        assert_eq!(response.retry_idempotency_keys, vec![key; 2]);
+        let response = generate_with(2, ErrorClass::Server, IdempotencyKeyMode::PerAttempt).await;
+        let mut keys = response.retry_idempotency_keys;
+        keys.push(response.idempotency_key.unwrap());
+        keys.dedup();
+        assert_eq!(keys.len(), 3);
+        let response = generate(5, ErrorClass::Connection).await;
+        assert!(response.failed);
+        assert_eq!(response.retries, vec![ErrorClass::Connection; 3]);
+        // permanent errors are not retried
+        let response = generate(1, ErrorClass::Validation).await;
+        assert!(response.failed);
+        assert!(response.retries.is_empty());
+        assert_eq!(policy.backoff(2), Duration::from_millis(4));
+        assert_eq!(policy.backoff(40), MAX_RETRY_BACKOFF);
+    }
+
+    /// Backend sending a streamed and an unstreamed response per request, as when comparing
+    /// streaming, the first failing with a transient error on the first `failures` requests
+    #[derive(Clone)]
+    struct PairedBackend {
+        failures: Arc<AtomicU64>,
+    }
+
+    #[async_trait]
+    impl TextGenerationBackend for PairedBackend {
+        async fn generate(
+            &self,
+            request: Arc<TextGenerationRequest>,
+            sender: Sender<TextGenerationAggregatedResponse>,
+        ) {
+            let failing = self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            for streamed in [true, false] {
+                let mut response = TextGenerationAggregatedResponse::default();
+                response.start(request.clone());
+                response.streamed = Some(streamed);
+                if failing && streamed {
+                    response.error_class = Some(ErrorClass::Quota);
+                    response.fail();
+                } else {
+                    response.add_tokens(1);
+                    response.stop();
+                }
+                sender.send(response).await.unwrap();
             }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wrapped_paired_responses() {
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(1),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
         });
-        t.await.unwrap();
-        let responses = reponses.read().await;
-        assert_eq!(responses.len(), 1);
-        assert_eq!(responses[0].failed, true);
+        let paired = || -> Box<dyn TextGenerationBackend + Send + Sync> {
+            Box::new(PairedBackend {
+                failures: Arc::new(AtomicU64::new(1)),
+            })
+        };
+        let tenant = Tenant {
+            name: "a".to_string(),
+            weight: 1,
+            api_key: None,
+            headers: Vec::new(),
+        };
+        let policy = RetryPolicy {
+            max_retries: 2,
+            backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        #[allow(unused_mut)]
+        let mut backends: Vec<Box<dyn TextGenerationBackend + Send + Sync>> = vec![
+            Box::new(TenantBackend::new(vec![(tenant, paired())])),
+            Box::new(RetryingBackend::new(paired(), policy)),
+        ];
+        #[cfg(all(feature = "hub", feature = "datasets"))]
+        backends.push(Box::new(DecodeFeedbackBackend::new(
+            paired(),
+            Arc::new(DecodeFeedback::default()),
+        )));
+        for backend in backends {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+            let collect = async {
+                let mut responses = Vec::new();
+                while let Some(response) = rx.recv().await {
+                    responses.push(response);
+                }
+                responses
+            };
+            // both responses go through the wrappers without blocking the first send
+            let ((), responses) = tokio::time::timeout(Duration::from_secs(5), async {
+                tokio::join!(backend.generate(request.clone(), tx), collect)
+            })
+            .await
+            .expect("wrapped backend deadlocked");
+            assert_eq!(responses.len(), 2);
+            assert_eq!(
+                responses.iter().map(|r| r.streamed).collect::<Vec<_>>(),
+                [Some(true), Some(false)]
+            );
+        }
+        // the pair is retried as a whole
+        let backend = RetryingBackend::new(paired(), policy);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(2);
+        backend.generate(request, tx).await;
+        for _ in 0..2 {
+            let response = rx.recv().await.unwrap();
+            assert!(!response.failed);
+            assert_eq!(response.retries, [ErrorClass::Quota]);
+        }
+    }
+
+    #[cfg(all(feature = "hub", feature = "datasets"))]
+    #[test]
+    fn test_decode_feedback() {
+        let feedback = Arc::new(DecodeFeedback::default());
+        let requests = AdaptiveDecodeRequestGenerator::new(
+            Arc::new(DummyTextRequestGenerator::new()),
+            feedback.clone(),
+        );
+        let response = |target, generated| {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start(Arc::new(TextGenerationRequest {
+                prompt: "Hello".to_string(),
+                num_prompt_tokens: 1,
+                num_decode_tokens: Some(target),
+                system_prompt: None,
+                expected_answer: None,
+                context_clamped: false,
+            }));
+            response.num_generated_tokens = generated;
+            response
+        };
+        for _ in 0..DECODE_FEEDBACK_WINDOW - 1 {
+            feedback.observe(&response(100, 50));
+        }
+        // targets are adjusted once a full window is observed
+        assert_eq!(requests.generate_request().num_decode_tokens, Some(10));
+        feedback.observe(&response(100, 50));
+        assert_eq!(requests.decode_scale(), Some(0.5));
+        assert_eq!(requests.generate_request().num_decode_tokens, Some(5));
+        // requests reaching their targets don't change the scale
+        for i in 0..DECODE_FEEDBACK_WINDOW {
+            let generated = if i % 2 == 0 { 50 } else { 10 };
+            feedback.observe(&response(50, generated));
+        }
+        assert_eq!(feedback.scale(), 0.5);
+        assert_eq!(feedback.adjust(1), 1);
     }
 
-    /// Test that conversations are correctly loaded
     #[tokio::test]
-    async fn test_load_conversations_from_file() {
-        let filepath = PathBuf::from("test_data/conversations.json");
-        let tokenizer = "gpt2".to_string();
-        let prompt_tokenize_opts = TokenizeOptions::default();
-        let decode_tokenize_opts = TokenizeOptions::default();
-        let hf_token = None;
-        let generator = ConversationTextRequestGenerator::load(
-            filepath,
-            tokenizer,
-            Some(prompt_tokenize_opts),
-            Some(decode_tokenize_opts),
-            hf_token,
+    async fn test_trace_sampling_backend() {
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(1),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        });
+        // sampled or not, responses are forwarded unchanged
+        for sample_rate in [0.0, 1.0] {
+            let backend = TraceSamplingBackend::new(
+                Box::new(FlakyBackend {
+                    failures: Arc::new(AtomicU64::new(1)),
+                    cause: ErrorClass::Server,
+                }),
+                sample_rate,
+            );
+            let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+            backend.generate(request.clone(), tx).await;
+            let response = rx.recv().await.unwrap();
+            assert!(response.failed);
+            assert_eq!(response.error_class, Some(ErrorClass::Server));
+            assert!(rx.recv().await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tenant_backend() {
+        let gold: Tenant = "name=gold,weight=3,api_key=secret,header=X-Tenant:gold"
+            .parse()
+            .unwrap();
+        assert_eq!(gold.weight, 3);
+        assert_eq!(gold.api_key.as_deref(), Some("secret"));
+        assert_eq!(
+            gold.headers,
+            vec![("X-Tenant".to_string(), "gold".to_string())]
+        );
+        let free: Tenant = "name=free".parse().unwrap();
+        assert_eq!(free.weight, 1);
+        assert!("weight=2".parse::<Tenant>().is_err());
+        assert!("name=free,weight=0".parse::<Tenant>().is_err());
+        assert!("name=free,header=X-Tenant".parse::<Tenant>().is_err());
+        assert!("name=free,quota=2".parse::<Tenant>().is_err());
+
+        let backend = TenantBackend::new(vec![
+            (
+                gold,
+                Box::new(FlakyBackend {
+                    failures: Arc::new(AtomicU64::new(0)),
+                    cause: ErrorClass::Server,
+                }),
+            ),
+            (
+                free,
+                Box::new(FlakyBackend {
+                    failures: Arc::new(AtomicU64::new(0)),
+                    cause: ErrorClass::Server,
+                }),
+            ),
+        ]);
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(1),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        });
+        let mut tenants = Vec::new();
+        for _ in 0..8 {
+            let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+            backend.generate(request.clone(), tx).await;
+            tenants.push(rx.recv().await.unwrap().tenant.unwrap());
+        }
+        // requests are spread over the tenants in proportion to their weights
+        assert_eq!(
+            tenants,
+            vec!["gold", "gold", "gold", "free", "gold", "gold", "gold", "free"]
+        );
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_grpc_backend_fails_without_server() {
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            "grpc://127.0.0.1:1".to_string(),
+            "ensemble".to_string(),
+            Arc::new(Tokenizer::new(tokenizers::models::bpe::BPE::default())),
+            Duration::from_secs(10),
+            RequestOptions::default(),
         )
         .unwrap();
-        assert_eq!(generator.requests.len(), 17005);
+        let backend = GrpcTextGenerationBackend::try_new(backend).unwrap();
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(2),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        });
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        backend.generate(request, tx).await;
+        let response = rx.recv().await.unwrap();
+        assert!(response.failed);
+        assert_eq!(response.num_generated_tokens, 0);
     }
 
-    /// Test that conversations are bounded by the min/max number of tokens
     #[tokio::test]
-    async fn test_load_conversations_bounded() {
-        let filepath = PathBuf::from("test_data/conversations.json");
-        let tokenizer = "gpt2".to_string();
-        let prompt_tokenize_opts = TokenizeOptions {
-            num_tokens: None,
-            min_tokens: 4,
-            max_tokens: 1024,
-            variance: 0,
-        };
-        let decode_tokenize_opts = TokenizeOptions::default();
-        let hf_token = None;
-        let generator = ConversationTextRequestGenerator::load(
-            filepath,
-            tokenizer,
-            Some(prompt_tokenize_opts),
-            Some(decode_tokenize_opts),
-            hf_token,
+    async fn test_websocket_backend() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let request = ws.next().await.unwrap().unwrap();
+            assert!(request.to_text().unwrap().contains("\"stream\":true"));
+            for chunk in [
+                r#"{"choices": [{"message": null, "finish_reason": null, "delta": {"content": "Hello"}}]}"#,
+                r#"{"choices": [{"message": null, "finish_reason": "stop", "delta": {"content": "world"}}]}"#,
+                "[DONE]",
+            ] {
+                ws.send(Message::Text(chunk.to_string())).await.unwrap();
+            }
+        });
+        // every chunk is a single unknown token
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            format!("ws://{addr}"),
+            "model".to_string(),
+            Arc::new(word_level_tokenizer(&["[UNK]"])),
+            Duration::from_secs(10),
+            RequestOptions::default(),
         )
         .unwrap();
-        let min_tokens = generator
-            .requests
-            .iter()
-            .map(|r| r.num_prompt_tokens)
-            .min()
-            .unwrap();
-        let max_tokens = generator
-            .requests
-            .iter()
-            .map(|r| r.num_prompt_tokens)
-            .max()
-            .unwrap();
-        assert!(min_tokens >= 4, "Min tokens: {}", min_tokens);
-        assert!(max_tokens <= 1024, "Max tokens: {}", max_tokens);
+        let backend = WebSocketTextGenerationBackend::new(backend);
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(2),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        });
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        backend.generate(request, tx).await;
+        let response = rx.recv().await.unwrap();
+        assert!(!response.failed);
+        assert_eq!(response.num_generated_tokens, 2);
+        assert_eq!(response.times_to_tokens.len(), 2);
+        assert_eq!(response.generated_text, "Helloworld");
     }
 
-    /// Test that conversations prompts have the correct number of tokens
     #[tokio::test]
-    async fn test_load_conversations_fixed_tokens() {
-        let filepath = PathBuf::from("test_data/conversations.json");
-        let tokenizer = "gpt2".to_string();
-        let prompt_tokenize_opts = TokenizeOptions {
-            num_tokens: Some(200),
-            min_tokens: 200,
-            max_tokens: 200,
-            variance: 0,
-        };
-        let decode_tokenize_opts = TokenizeOptions::default();
-        let hf_token = None;
-        let generator = ConversationTextRequestGenerator::load(
-            filepath,
-            tokenizer,
-            Some(prompt_tokenize_opts),
-            Some(decode_tokenize_opts),
-            hf_token,
+    async fn test_realtime_audio_backend() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let item = ws.next().await.unwrap().unwrap();
+            assert!(item.to_text().unwrap().contains("conversation.item.create"));
+            let create = ws.next().await.unwrap().unwrap();
+            assert!(create
+                .to_text()
+                .unwrap()
+                .contains("\"max_output_tokens\":2"));
+            for event in [
+                r#"{"type": "response.created"}"#,
+                r#"{"type": "response.audio_transcript.delta", "delta": "Hi"}"#,
+                r#"{"type": "response.audio.delta", "delta": "AAAA"}"#,
+                r#"{"type": "response.audio.delta", "delta": "AAAA"}"#,
+                r#"{"type": "response.audio.delta", "delta": "AAAA"}"#,
+                r#"{"type": "response.done"}"#,
+            ] {
+                ws.send(Message::Text(event.to_string())).await.unwrap();
+            }
+        });
+        let backend = OpenAITextGenerationBackend::try_new(
+            "".to_string(),
+            format!("ws://{addr}"),
+            "model".to_string(),
+            Arc::new(Tokenizer::new(tokenizers::models::bpe::BPE::default())),
+            Duration::from_secs(10),
+            RequestOptions::default(),
         )
         .unwrap();
-        for r in generator.requests.iter() {
-            assert_eq!(r.num_prompt_tokens, 200);
-        }
+        let backend = RealtimeAudioBackend::new(backend);
+        let request = Arc::new(TextGenerationRequest {
+            prompt: "Hello".to_string(),
+            num_prompt_tokens: 1,
+            num_decode_tokens: Some(2),
+            system_prompt: None,
+            expected_answer: None,
+            context_clamped: false,
+        });
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        backend.generate(request, tx).await;
+        let response = rx.recv().await.unwrap();
+        assert!(!response.failed);
+        assert_eq!(response.num_generated_tokens, 3);
+        assert_eq!(response.generated_text, "Hi");
     }
 }
@@ -1,9 +1,25 @@
-use crate::executors::ExecutorConfig;
-use crate::requests::TextGenerationAggregatedResponse;
+use crate::assertions::AssertionResult;
+use crate::executors::{ExecutorConfig, RateCurve, StepProfile};
+use crate::headroom::ClientSaturation;
+use crate::histograms::ResponseAggregates;
+use crate::hooks::HookOutput;
+use crate::pause::paused_duration;
+use crate::processors::ScoreSummary;
+use crate::requests::{ErrorClass, TextGenerationAggregatedResponse};
 use crate::results::BenchmarkErrors::NoResponses;
 use crate::scheduler::ExecutorType;
+use crate::scripting::DerivedMetric;
+use crate::server_metrics::VllmStats;
+use crate::timeseries::TimeSeriesPoint;
 use chrono::Utc;
+use log::debug;
+use rand::Rng;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Debug)]
@@ -19,12 +35,458 @@ impl Display for BenchmarkErrors {
     }
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub struct TgiDetailsSummary {
+    pub requests: u64,
+    pub generated_tokens: u64,
+    pub prompt_tokens: u64,
+    /// Average time requests waited in the server queue, when reported
+    pub queue_time_avg_ms: Option<f64>,
+    /// Average time the server spent generating, when reported
+    pub inference_time_avg_ms: Option<f64>,
+}
+
+/// Generation details returned by vLLM's native API
+#[derive(Clone, Debug, Serialize)]
+pub struct VllmDetailsSummary {
+    pub requests: u64,
+    pub finish_reasons: BTreeMap<String, u64>,
+    pub cumulative_logprob_avg: Option<f64>,
+}
+
+/// Prompt cache hits reported in the token usage of responses
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub struct PromptCacheSummary {
+    pub requests: u64,
+    pub cache_hits: u64,
+    pub prompt_tokens: u64,
+    pub cached_prompt_tokens: u64,
+    pub hit_rate: f64,
+    #[serde(rename = "cached_ttft_avg_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSeconds<u64>>")]
+    pub cached_ttft_avg: Option<Duration>,
+    #[serde(rename = "uncached_ttft_avg_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSeconds<u64>>")]
+    pub uncached_ttft_avg: Option<Duration>,
+}
+
+/// Responses of one side of the streaming comparison
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamingVariantSummary {
+    pub requests: u64,
+    pub failed_requests: u64,
+    #[serde(rename = "e2e_latency_avg_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSeconds<u64>>")]
+    pub e2e_latency_avg: Option<Duration>,
+    pub generated_tokens_avg: Option<f64>,
+    /// Successful responses whose token count differs from the server-reported usage
+    pub usage_mismatches: u64,
+}
+
+/// Latency and token counts of identical requests sent streamed and unstreamed
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamingComparison {
+    pub streamed: StreamingVariantSummary,
+    pub unstreamed: StreamingVariantSummary,
+    /// Average end-to-end latency of streamed requests minus the one of unstreamed requests
+    pub e2e_latency_overhead_ms: Option<f64>,
+    /// Average generated tokens of streamed responses minus the ones of unstreamed responses
+    pub generated_tokens_delta: Option<f64>,
+}
+
+/// Count the other generated token counts are reconciled with
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenCountSource {
+    /// Completion tokens reported by the server
+    Usage,
+    /// Whole generated texts re-tokenized by the client
+    Retokenized,
+}
+
+/// Generated tokens of the successful responses of a step, counted by every available source
+#[derive(Clone, Debug, Serialize)]
+pub struct TokenReconciliation {
+    pub requests: u64,
+    /// Streamed chunks carrying text
+    pub chunks: u64,
+    /// Chunks re-tokenized one by one, the count token throughput is computed with
+    pub chunk_tokens: u64,
+    /// Whole generated texts re-tokenized
+    pub retokenized_tokens: u64,
+    /// Completion tokens reported by the server, when all the responses carry usage
+    pub usage_tokens: Option<u64>,
+    /// Server usage when reported, whole-text re-tokenization otherwise
+    pub reference: TokenCountSource,
+    /// Relative difference of each count with the reference, e.g. 0.05 for 5% more
+    pub chunks_error: f64,
+    pub chunk_tokens_error: f64,
+    pub retokenized_tokens_error: Option<f64>,
+    /// Responses whose per-chunk count differs from the reference
+    pub mismatched_requests: u64,
+    /// Factor correcting the reported token throughput to the reference count
+    pub throughput_correction: f64,
+}
+
+/// Factor correcting a token throughput computed with `client_tokens` to `reference_tokens`
+pub(crate) fn throughput_correction(reference_tokens: u64, client_tokens: u64) -> f64 {
+    reference_tokens as f64 / client_tokens.max(1) as f64
+}
+
+/// Responses flagged as pathologically repetitive, see [`repetition_score`]
+#[derive(Clone, Debug, Serialize)]
+pub struct RepetitionSummary {
+    pub requests: u64,
+    pub threshold: f64,
+    pub repetitive_responses: u64,
+    pub repetition_score_avg: f64,
+    pub repetition_score_max: f64,
+}
+
+/// Playback of the streamed responses by a reader at a fixed rate, see [`playback`]
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub struct PlaybackSummary {
+    pub reading_rate: f64,
+    pub requests: u64,
+    /// Responses stalling the reader at least once
+    pub stalled_requests: u64,
+    pub stalls_avg: f64,
+    #[serde(rename = "stall_time_avg_ms")]
+    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
+    pub stall_time_avg: Duration,
+    /// Average of the most tokens each response ran ahead of the reader
+    pub max_tokens_ahead_avg: f64,
+    /// Average smoothness of the responses in percent, 100 when no response stalled the reader
+    pub smooth_playback_score: f64,
+}
+
+/// N-gram size of the repetition score
+const REPETITION_NGRAM: usize = 4;
+
+/// Share of the word n-grams of a text that repeat an earlier n-gram: 0 for a text without
+/// repetition, close to 1 for a text looping over the same few words
+pub fn repetition_score(text: &str) -> f64 {
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+    if words.len() < REPETITION_NGRAM {
+        return 0.0;
+    }
+    let ngrams = words.windows(REPETITION_NGRAM).collect::<Vec<_>>();
+    let unique = ngrams.iter().collect::<std::collections::HashSet<_>>();
+    1.0 - unique.len() as f64 / ngrams.len() as f64
+}
+
+/// Reading of a streamed response by a user consuming tokens at a fixed rate from the first token
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Playback {
+    /// Times the reader caught up with the stream and waited for the next tokens
+    pub stalls: u64,
+    pub stall_time: Duration,
+    /// Most tokens received ahead of the reader
+    pub max_tokens_ahead: f64,
+    /// Reading time over the playback time including stalls, 1 for a reader who never waited
+    pub smoothness: f64,
+}
+
+/// Simulate the reading of a response at `reading_rate` tokens per second. Tokens of chunks
+/// carrying several tokens are spread evenly over the chunks.
+pub fn playback(
+    times_to_tokens: &[Duration],
+    num_tokens: u64,
+    reading_rate: f64,
+) -> Option<Playback> {
+    if times_to_tokens.is_empty() || num_tokens == 0 {
+        return None;
+    }
+    let chunk_tokens = num_tokens as f64 / times_to_tokens.len() as f64;
+    let mut buffered = chunk_tokens;
+    let mut max_tokens_ahead = buffered;
+    let mut stalls = 0;
+    let mut stall_secs = 0.0;
+    for gap in &times_to_tokens[1..] {
+        let readable = reading_rate * gap.as_secs_f64();
+        // tolerate rounding errors of chunks arriving exactly at the reading rate
+        if readable > buffered + 1e-9 {
+            stalls += 1;
+            stall_secs += (readable - buffered) / reading_rate;
+            buffered = 0.0;
+        } else {
+            buffered = (buffered - readable).max(0.0);
+        }
+        buffered += chunk_tokens;
+        max_tokens_ahead = f64::max(max_tokens_ahead, buffered);
+    }
+    let reading_secs = num_tokens as f64 / reading_rate;
+    Some(Playback {
+        stalls,
+        stall_time: Duration::from_secs_f64(stall_secs),
+        max_tokens_ahead,
+        smoothness: reading_secs / (reading_secs + stall_secs),
+    })
+}
+
+/// How generated texts are compared to the expected answers of the dataset
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnswerScoring {
+    /// The trimmed response equals the answer, ignoring case
+    Exact,
+    /// The answer is a regular expression matching somewhere in the response
+    Regex,
+}
+
+impl FromStr for AnswerScoring {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "exact" => Ok(AnswerScoring::Exact),
+            "regex" => Ok(AnswerScoring::Regex),
+            _ => Err(anyhow::anyhow!("Unknown answer scoring: {s}")),
+        }
+    }
+}
+
+impl AnswerScoring {
+    /// Whether `response` is a correct answer. With regex scoring, an answer missing from
+    /// `regexes` never matches.
+    pub fn score(&self, regexes: &AnswerRegexes, answer: &str, response: &str) -> bool {
+        match self {
+            AnswerScoring::Exact => response.trim().to_lowercase() == answer.trim().to_lowercase(),
+            AnswerScoring::Regex => match regexes.0.get(answer) {
+                Some(regex) => regex.is_match(response),
+                None => {
+                    debug!("Answer regex {answer} wasn't compiled");
+                    false
+                }
+            },
+        }
+    }
+}
+
+/// Expected answers of the dataset compiled as regular expressions, once for the whole run
+#[derive(Clone, Debug, Default)]
+pub struct AnswerRegexes(Arc<HashMap<String, Regex>>);
+
+impl AnswerRegexes {
+    /// Compile the distinct answers, failing on the first invalid regular expression
+    pub fn compile<'a>(answers: impl IntoIterator<Item = &'a str>) -> anyhow::Result<Self> {
+        let mut regexes = HashMap::new();
+        for answer in answers {
+            if !regexes.contains_key(answer) {
+                let regex = Regex::new(answer)
+                    .map_err(|e| anyhow::anyhow!("Invalid answer regex {answer}: {e}"))?;
+                regexes.insert(answer.to_string(), regex);
+            }
+        }
+        Ok(Self(Arc::new(regexes)))
+    }
+}
+
+/// Responses scored against the expected answers of the dataset
+#[derive(Clone, Debug, Serialize)]
+pub struct AccuracySummary {
+    pub scoring: AnswerScoring,
+    pub scored_responses: u64,
+    pub correct_responses: u64,
+    pub accuracy: f64,
+}
+
+/// Latency limits a request must meet to count towards goodput, e.g. `ttft=500ms,itl=50ms,e2e=20s`.
+/// The inter-token latency limit applies to the average of the request.
+#[serde_with::serde_as]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct RequestSlo {
+    #[serde(rename = "ttft_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSecondsWithFrac<f64>>")]
+    pub ttft: Option<Duration>,
+    #[serde(rename = "itl_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSecondsWithFrac<f64>>")]
+    pub itl: Option<Duration>,
+    #[serde(rename = "e2e_ms")]
+    #[serde_as(as = "Option<serde_with::DurationMilliSecondsWithFrac<f64>>")]
+    pub e2e: Option<Duration>,
+}
+
+impl FromStr for RequestSlo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut slo = RequestSlo::default();
+        for item in s.split(',') {
+            let Some((key, value)) = item.split_once('=') else {
+                return Err(anyhow::anyhow!(
+                    "Invalid request SLO: {item}, expected <limit>=<duration>"
+                ));
+            };
+            let value = Some(humantime::parse_duration(value.trim())?);
+            match key.trim() {
+                "ttft" => slo.ttft = value,
+                "itl" => slo.itl = value,
+                "e2e" => slo.e2e = value,
+                key => {
+                    return Err(anyhow::anyhow!(
+                        "Unknown request SLO limit: {key}. Available limits: ttft, itl, e2e"
+                    ))
+                }
+            }
+        }
+        Ok(slo)
+    }
+}
+
+impl Display for RequestSlo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let limits = [("TTFT", self.ttft), ("ITL", self.itl), ("E2E", self.e2e)]
+            .into_iter()
+            .filter_map(|(name, limit)| limit.map(|limit| format!("{name} <= {limit:?}")))
+            .collect::<Vec<_>>();
+        write!(f, "{}", limits.join(", "))
+    }
+}
+
+impl RequestSlo {
+    /// Whether a successful response met every limit. A response missing a measured latency
+    /// doesn't meet its limit.
+    pub fn is_met(&self, response: &TextGenerationAggregatedResponse) -> bool {
+        let within = |limit: Option<Duration>, latency: Option<Duration>| match limit {
+            Some(limit) => latency.is_some_and(|latency| latency <= limit),
+            None => true,
+        };
+        !response.failed
+            && within(self.ttft, response.time_to_first_token())
+            && within(self.itl, response.inter_token_latency())
+            && within(self.e2e, response.e2e_latency())
+    }
+}
+
+/// Throughput of the requests meeting the request SLO
+#[derive(Clone, Debug, Serialize)]
+pub struct GoodputSummary {
+    pub slo: RequestSlo,
+    pub requests_meeting_slo: u64,
+    /// Share of all the requests meeting the SLO, in percent
+    pub slo_attainment: f64,
+    /// Requests per second meeting the SLO
+    pub goodput: f64,
+    /// Generated tokens per second of the requests meeting the SLO
+    pub token_goodput_secs: f64,
+}
+
+/// Percentiles reported for the per-request metrics by default
+pub const DEFAULT_PERCENTILES: &[f64] = &[50.0, 90.0, 95.0, 99.0];
+
+/// Metric measured on each request, reported with configurable percentiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestMetric {
+    TimeToFirstToken,
+    InterTokenLatency,
+    E2eLatency,
+    /// Generated tokens over the end-to-end latency of the request
+    OutputTokensPerSec,
+}
+
+impl RequestMetric {
+    pub const ALL: [RequestMetric; 4] = [
+        RequestMetric::TimeToFirstToken,
+        RequestMetric::InterTokenLatency,
+        RequestMetric::E2eLatency,
+        RequestMetric::OutputTokensPerSec,
+    ];
+
+    /// Name in the report, with the unit of the values
+    pub fn name(&self) -> &'static str {
+        match self {
+            RequestMetric::TimeToFirstToken => "time_to_first_token_ms",
+            RequestMetric::InterTokenLatency => "inter_token_latency_ms",
+            RequestMetric::E2eLatency => "e2e_latency_ms",
+            RequestMetric::OutputTokensPerSec => "output_tokens_per_sec",
+        }
+    }
+}
+
+impl Display for RequestMetric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            RequestMetric::TimeToFirstToken => "TTFT",
+            RequestMetric::InterTokenLatency => "ITL",
+            RequestMetric::E2eLatency => "E2E Latency",
+            RequestMetric::OutputTokensPerSec => "Output tokens/s per request",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// Metrics of a benchmark step available to derived metrics, see [`BenchmarkResults::base_metrics`]
+pub const BASE_METRICS: &[&str] = &[
+    "total_requests",
+    "successful_requests",
+    "failed_requests",
+    "error_rate",
+    "request_rate",
+    "token_throughput_secs",
+    "total_tokens",
+    "total_tokens_sent",
+    "duration_secs",
+    "max_vus",
+    "rate",
+    "ttft_avg_ms",
+    "ttft_p50_ms",
+    "ttft_p90_ms",
+    "ttft_p99_ms",
+    "itl_avg_ms",
+    "itl_p50_ms",
+    "itl_p90_ms",
+    "itl_p99_ms",
+    "e2e_avg_ms",
+    "e2e_p50_ms",
+    "e2e_p90_ms",
+    "e2e_p99_ms",
+    "prefill_share",
+    "goodput",
+    "token_goodput_secs",
+];
+
 #[derive(Clone)]
 pub struct BenchmarkResults {
     pub id: String,
     aggregated_responses: Vec<TextGenerationAggregatedResponse>,
+    aggregates: ResponseAggregates,
+    sampled_responses: Vec<TextGenerationAggregatedResponse>,
     executor_type: ExecutorType,
     executor_config: ExecutorConfig,
+    blocked_response_sends: u64,
+    discarded_responses: u64,
+    cancelled_requests: u64,
+    server_metrics: Option<BTreeMap<String, f64>>,
+    vllm_stats: Option<VllmStats>,
+    assertions: Vec<AssertionResult>,
+    clamped_requests: u64,
+    stage_id: Option<String>,
+    decode_scale: Option<f64>,
+    /// Intervals the dispatch was paused during the step
+    paused: Vec<(tokio::time::Instant, tokio::time::Instant)>,
+    /// Start of the stage, the send time of requests is counted from
+    stage_start: Option<tokio::time::Instant>,
+    /// Aggregates of the parts of the stage reported apart, updated as responses arrive so they
+    /// cover all the responses when only a sample of them is stored
+    segments: BTreeMap<Segment, ResponseAggregates>,
+}
+
+/// Part of a stage reported apart
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    /// Sent during the stage warmup
+    Warmup,
+    /// Sent after the stage warmup
+    Measured,
+    /// Step of a step profile, by index
+    Step(usize),
+    /// Phase of a burst curve
+    Phase(&'static str),
+    Tenant(String),
 }
 
 impl BenchmarkResults {
@@ -36,29 +498,700 @@ impl BenchmarkResults {
         BenchmarkResults {
             id,
             aggregated_responses: Vec::new(),
+            aggregates: ResponseAggregates::new(executor_config.time_series_window),
+            sampled_responses: Vec::new(),
             executor_type,
             executor_config,
+            blocked_response_sends: 0,
+            discarded_responses: 0,
+            cancelled_requests: 0,
+            server_metrics: None,
+            vllm_stats: None,
+            assertions: Vec::new(),
+            clamped_requests: 0,
+            stage_id: None,
+            decode_scale: None,
+            paused: Vec::new(),
+            stage_start: None,
+            segments: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_response(&mut self, mut response: TextGenerationAggregatedResponse) {
+        if self.executor_config.repetition_threshold.is_some() && !response.failed {
+            response.repetition_score = Some(repetition_score(&response.generated_text));
+        }
+        if let Some(scoring) = self.executor_config.answer_scoring {
+            let answer = response
+                .request
+                .as_ref()
+                .and_then(|r| r.expected_answer.as_ref());
+            if let (Some(answer), false) = (answer, response.failed) {
+                response.correct = Some(scoring.score(
+                    &self.executor_config.answer_regexes,
+                    answer,
+                    &response.generated_text,
+                ));
+            }
+        }
+        if response.request.as_ref().is_some_and(|r| r.context_clamped) {
+            self.clamped_requests += 1;
+        }
+        self.aggregates.add(&response);
+        for segment in self.segments_of(&response) {
+            self.segments
+                .entry(segment)
+                .or_insert_with(|| ResponseAggregates::new(self.executor_config.time_series_window))
+                .add(&response);
+        }
+        self.sample_response(&response);
+        // only keep prompt and generated text for sampled responses to bound memory usage
+        response.request = None;
+        response.generated_text = String::new();
+        self.store_response(response);
+    }
+
+    /// Store the response, keeping a uniform random sample of `max_stored_responses` responses
+    /// once the cap is reached. Counters, averages and percentiles come from the aggregates of all
+    /// the responses.
+    fn store_response(&mut self, response: TextGenerationAggregatedResponse) {
+        let max_stored = self.executor_config.max_stored_responses;
+        if max_stored.is_none_or(|max| (self.aggregated_responses.len() as u64) < max) {
+            self.aggregated_responses.push(response);
+            return;
+        }
+        let i = rand::thread_rng().gen_range(0..self.aggregates.total_requests) as usize;
+        if i < self.aggregated_responses.len() {
+            self.aggregated_responses[i] = response;
+        }
+    }
+
+    /// Whether the stored responses are a sample of the received ones, `max_stored_responses`
+    /// being reached
+    fn is_sampled(&self) -> bool {
+        self.aggregates.total_requests > self.aggregated_responses.len() as u64
+    }
+
+    /// Count over the stored responses extrapolated to all the received ones
+    fn extrapolate(&self, count: u64) -> u64 {
+        if !self.is_sampled() || self.aggregated_responses.is_empty() {
+            return count;
+        }
+        let scale = self.aggregates.total_requests as f64 / self.aggregated_responses.len() as f64;
+        (count as f64 * scale).round() as u64
+    }
+
+    /// Keep a uniform random sample of `sample_responses` responses using reservoir sampling
+    fn sample_response(&mut self, response: &TextGenerationAggregatedResponse) {
+        let max_samples = self.executor_config.sample_responses as usize;
+        if max_samples == 0 {
+            return;
+        }
+        if self.sampled_responses.len() < max_samples {
+            self.sampled_responses.push(response.clone());
+            return;
+        }
+        let i = rand::thread_rng().gen_range(0..self.aggregates.total_requests) as usize;
+        if i < max_samples {
+            self.sampled_responses[i] = response.clone();
+        }
+    }
+
+    pub fn set_response_channel_stats(
+        &mut self,
+        blocked_sends: u64,
+        discarded_responses: u64,
+        cancelled_requests: u64,
+    ) {
+        self.blocked_response_sends = blocked_sends;
+        self.discarded_responses = discarded_responses;
+        self.cancelled_requests = cancelled_requests;
+    }
+
+    /// Number of responses that had to wait for free capacity in the response channel
+    pub fn blocked_response_sends(&self) -> u64 {
+        self.blocked_response_sends
+    }
+
+    /// Number of requests whose max tokens were reduced to fit the model context length
+    pub fn clamped_requests(&self) -> u64 {
+        self.clamped_requests
+    }
+
+    /// Number of responses received after the benchmark step ended, not included in results
+    pub fn discarded_responses(&self) -> u64 {
+        self.discarded_responses
+    }
+
+    /// Number of requests in flight when the benchmark was stopped. They have no response, so they
+    /// only count as `cancelled` failures, not in the failed requests
+    pub fn cancelled_requests(&self) -> u64 {
+        self.cancelled_requests
+    }
+
+    pub fn set_server_metrics(&mut self, server_metrics: Option<BTreeMap<String, f64>>) {
+        self.server_metrics = server_metrics;
+    }
+
+    /// Change of the server metrics counters over the benchmark step, if scraped
+    pub fn server_metrics(&self) -> Option<BTreeMap<String, f64>> {
+        self.server_metrics.clone()
+    }
+
+    pub fn set_stage_id(&mut self, stage_id: String) {
+        self.stage_id = Some(stage_id);
+    }
+
+    /// Id of the stage in the run, set once the stage ended
+    pub fn stage_id(&self) -> Option<String> {
+        self.stage_id.clone()
+    }
+
+    pub fn set_decode_scale(&mut self, decode_scale: Option<f64>) {
+        self.decode_scale = decode_scale;
+    }
+
+    /// Factor applied to the sampled decode targets at the end of the step, when they are adapted
+    /// to the completion lengths of the server
+    pub fn decode_scale(&self) -> Option<f64> {
+        self.decode_scale
+    }
+
+    pub fn set_stage_start(&mut self, stage_start: tokio::time::Instant) {
+        self.stage_start = Some(stage_start);
+    }
+
+    /// Time `response` was sent after the start of the stage, or after the first sent request
+    /// for results not recorded by a scheduler, without the pauses of the dispatch as the
+    /// executors follow their schedule on this clock
+    fn sent_offset(&self, response: &TextGenerationAggregatedResponse) -> Duration {
+        let start = self.stage_start.or_else(|| {
+            self.aggregated_responses
+                .iter()
+                .filter_map(|response| response.start_time)
+                .min()
+        });
+        let (Some(start), Some(sent)) = (start, response.start_time) else {
+            return Duration::ZERO;
+        };
+        // pauses are recorded at the end of the stage, the ones so far apply while it runs
+        let paused = match self.paused.is_empty() {
+            true => self.executor_config.pause.intervals(start, sent),
+            false => self.paused.clone(),
+        };
+        sent.saturating_duration_since(start)
+            .saturating_sub(paused_duration(&paused, start, sent))
+    }
+
+    /// Parts of the stage `response` is reported in
+    fn segments_of(&self, response: &TextGenerationAggregatedResponse) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        if let Some(tenant) = &response.tenant {
+            segments.push(Segment::Tenant(tenant.clone()));
+        }
+        if response.start_time.is_none() {
+            return segments;
+        }
+        let sent = self.sent_offset(response);
+        if let Some(warmup) = self.executor_config.stage_warmup {
+            segments.push(match sent < warmup {
+                true => Segment::Warmup,
+                false => Segment::Measured,
+            });
+        }
+        match &self.executor_config.rate_curve {
+            Some(RateCurve::Steps { rates, interval }) => {
+                let step = (sent.as_secs_f64() / interval.as_secs_f64()) as usize;
+                segments.push(Segment::Step(step.min(rates.len().saturating_sub(1))));
+            }
+            Some(curve) => segments.extend(curve.phase(sent).map(Segment::Phase)),
+            None => {}
+        }
+        segments
+    }
+
+    pub fn set_paused(&mut self, paused: Vec<(tokio::time::Instant, tokio::time::Instant)>) {
+        self.paused = paused;
+    }
+
+    /// Time the dispatch was paused between the first and the last response, left out of the
+    /// duration of the step
+    pub fn paused_duration(&self) -> std::time::Duration {
+        match (self.start_time(), self.end_time()) {
+            (Some(start), Some(end)) => paused_duration(&self.paused, start, end),
+            _ => std::time::Duration::ZERO,
+        }
+    }
+
+    pub fn set_vllm_stats(&mut self, vllm_stats: Option<VllmStats>) {
+        self.vllm_stats = vllm_stats;
+    }
+
+    pub fn vllm_stats(&self) -> Option<VllmStats> {
+        self.vllm_stats.clone()
+    }
+
+    pub fn set_assertions(&mut self, assertions: Vec<AssertionResult>) {
+        self.assertions = assertions;
+    }
+
+    pub fn assertions(&self) -> Vec<AssertionResult> {
+        self.assertions.clone()
+    }
+
+    pub fn failed_assertions(&self) -> Vec<AssertionResult> {
+        self.assertions
+            .iter()
+            .filter(|a| !a.passed)
+            .cloned()
+            .collect()
+    }
+
+    /// Token counts and timings reported by TGI generation details, for successful requests that
+    /// returned them
+    pub fn tgi_details(&self) -> Option<TgiDetailsSummary> {
+        let details = self
+            .get_successful_responses()
+            .into_iter()
+            .filter_map(|r| r.server_details.as_ref())
+            .collect::<Vec<_>>();
+        if details.is_empty() {
+            return None;
+        }
+        let avg = |times: Vec<f64>| {
+            (!times.is_empty()).then(|| times.iter().sum::<f64>() / times.len() as f64)
+        };
+        Some(TgiDetailsSummary {
+            requests: details.len() as u64,
+            generated_tokens: details.iter().filter_map(|d| d.generated_tokens).sum(),
+            prompt_tokens: details.iter().filter_map(|d| d.input_length).sum(),
+            queue_time_avg_ms: avg(details.iter().filter_map(|d| d.queue_time_ms).collect()),
+            inference_time_avg_ms: avg(details
+                .iter()
+                .filter_map(|d| d.inference_time_ms)
+                .collect()),
+        })
+    }
+
+    /// Number of failed requests by cause, failures without a recognized error are `other`
+    pub fn failures(&self) -> BTreeMap<ErrorClass, u64> {
+        let mut failures = BTreeMap::new();
+        for response in self.aggregated_responses.iter().filter(|r| r.failed) {
+            *failures
+                .entry(response.error_class.unwrap_or(ErrorClass::Other))
+                .or_insert(0) += 1;
+        }
+        failures
+            .values_mut()
+            .for_each(|count| *count = self.extrapolate(*count));
+        if self.cancelled_requests > 0 {
+            failures.insert(ErrorClass::Cancelled, self.cancelled_requests);
+        }
+        failures
+    }
+
+    /// Share of the sent requests failing with each cause
+    pub fn failure_rates(&self) -> BTreeMap<ErrorClass, f64> {
+        let sent = self.total_requests() as u64 + self.cancelled_requests;
+        self.failures()
+            .into_iter()
+            .map(|(class, count)| (class, count as f64 / sent as f64))
+            .collect()
+    }
+
+    /// Number of requests by HTTP status of their response: 429 and 503 responses appearing
+    /// along a sweep show where the server starts shedding load
+    pub fn status_codes(&self) -> BTreeMap<u16, u64> {
+        let mut status_codes = BTreeMap::new();
+        for status in self
+            .aggregated_responses
+            .iter()
+            .filter_map(|r| r.http_status)
+        {
+            *status_codes.entry(status).or_insert(0) += 1;
+        }
+        status_codes
+            .values_mut()
+            .for_each(|count| *count = self.extrapolate(*count));
+        status_codes
+    }
+
+    /// Number of retried attempts by cause: rate limits and server errors point to server
+    /// saturation, connection errors to the network
+    pub fn retries(&self) -> BTreeMap<ErrorClass, u64> {
+        let mut retries = BTreeMap::new();
+        for cause in self.aggregated_responses.iter().flat_map(|r| &r.retries) {
+            *retries.entry(*cause).or_insert(0) += 1;
+        }
+        retries
+            .values_mut()
+            .for_each(|count| *count = self.extrapolate(*count));
+        retries
+    }
+
+    /// Number of requests that needed at least one retry
+    pub fn retried_requests(&self) -> u64 {
+        self.extrapolate(
+            self.aggregated_responses
+                .iter()
+                .filter(|r| !r.retries.is_empty())
+                .count() as u64,
+        )
+    }
+
+    /// Finish reasons and cumulative logprobs of successful requests to vLLM's native API
+    pub fn vllm_details(&self) -> Option<VllmDetailsSummary> {
+        let details = self
+            .get_successful_responses()
+            .into_iter()
+            .filter_map(|r| r.vllm_details.as_ref())
+            .collect::<Vec<_>>();
+        if details.is_empty() {
+            return None;
+        }
+        let mut finish_reasons = BTreeMap::new();
+        for reason in details.iter().filter_map(|d| d.finish_reason.as_ref()) {
+            *finish_reasons.entry(reason.clone()).or_insert(0) += 1;
+        }
+        let logprobs = details
+            .iter()
+            .filter_map(|d| d.cumulative_logprob)
+            .collect::<Vec<f64>>();
+        Some(VllmDetailsSummary {
+            requests: details.len() as u64,
+            finish_reasons,
+            cumulative_logprob_avg: (!logprobs.is_empty())
+                .then(|| logprobs.iter().sum::<f64>() / logprobs.len() as f64),
+        })
+    }
+
+    pub fn prompt_cache(&self) -> Option<PromptCacheSummary> {
+        let responses = self
+            .get_successful_responses()
+            .into_iter()
+            .filter(|r| r.cached_prompt_tokens.is_some())
+            .collect::<Vec<_>>();
+        if responses.is_empty() {
+            return None;
+        }
+        let (cached, uncached): (Vec<_>, Vec<_>) = responses
+            .iter()
+            .partition(|r| r.cached_prompt_tokens.unwrap_or_default() > 0);
+        let ttft_avg = |responses: &[&&TextGenerationAggregatedResponse]| {
+            let ttfts = responses
+                .iter()
+                .filter_map(|r| r.time_to_first_token())
+                .collect::<Vec<_>>();
+            (!ttfts.is_empty()).then(|| ttfts.iter().sum::<Duration>() / ttfts.len() as u32)
+        };
+        Some(PromptCacheSummary {
+            requests: responses.len() as u64,
+            cache_hits: cached.len() as u64,
+            prompt_tokens: responses.iter().map(|r| r.num_prompt_tokens).sum(),
+            cached_prompt_tokens: responses
+                .iter()
+                .filter_map(|r| r.cached_prompt_tokens)
+                .sum(),
+            hit_rate: cached.len() as f64 / responses.len() as f64,
+            cached_ttft_avg: ttft_avg(&cached),
+            uncached_ttft_avg: ttft_avg(&uncached),
+        })
+    }
+
+    pub fn streaming_comparison(&self) -> Option<StreamingComparison> {
+        if !self
+            .aggregated_responses
+            .iter()
+            .any(|r| r.streamed.is_some())
+        {
+            return None;
+        }
+        let variant = |streamed: bool| {
+            let responses = self
+                .aggregated_responses
+                .iter()
+                .filter(|r| r.streamed == Some(streamed))
+                .collect::<Vec<_>>();
+            let successful = responses.iter().filter(|r| !r.failed).collect::<Vec<_>>();
+            let latencies = successful
+                .iter()
+                .filter_map(|r| r.e2e_latency())
+                .collect::<Vec<_>>();
+            StreamingVariantSummary {
+                requests: responses.len() as u64,
+                failed_requests: (responses.len() - successful.len()) as u64,
+                e2e_latency_avg: (!latencies.is_empty())
+                    .then(|| latencies.iter().sum::<Duration>() / latencies.len() as u32),
+                generated_tokens_avg: (!successful.is_empty()).then(|| {
+                    successful
+                        .iter()
+                        .map(|r| r.num_generated_tokens)
+                        .sum::<u64>() as f64
+                        / successful.len() as f64
+                }),
+                usage_mismatches: successful
+                    .iter()
+                    .filter(|r| {
+                        r.usage_completion_tokens
+                            .is_some_and(|tokens| tokens != r.num_generated_tokens)
+                    })
+                    .count() as u64,
+            }
+        };
+        let (streamed, unstreamed) = (variant(true), variant(false));
+        Some(StreamingComparison {
+            e2e_latency_overhead_ms: streamed
+                .e2e_latency_avg
+                .zip(unstreamed.e2e_latency_avg)
+                .map(|(s, u)| (s.as_secs_f64() - u.as_secs_f64()) * 1000.0),
+            generated_tokens_delta: streamed
+                .generated_tokens_avg
+                .zip(unstreamed.generated_tokens_avg)
+                .map(|(s, u)| s - u),
+            streamed,
+            unstreamed,
+        })
+    }
+
+    /// Generated token counts of the chunks, the whole texts and the server usage, when token
+    /// counts are reconciled
+    pub fn token_reconciliation(&self) -> Option<TokenReconciliation> {
+        let responses = self
+            .get_successful_responses()
+            .into_iter()
+            .filter(|r| r.retokenized_tokens.is_some())
+            .collect::<Vec<_>>();
+        let chunks = responses
+            .iter()
+            .map(|r| r.times_to_tokens.len() as u64)
+            .sum::<u64>();
+        let chunk_tokens = responses.iter().map(|r| r.num_generated_tokens).sum();
+        let retokenized_tokens = responses.iter().filter_map(|r| r.retokenized_tokens).sum();
+        let usage_tokens = responses
+            .iter()
+            .map(|r| r.usage_completion_tokens)
+            .sum::<Option<u64>>();
+        let (reference, reference_tokens) = match usage_tokens {
+            Some(tokens) => (TokenCountSource::Usage, tokens),
+            None => (TokenCountSource::Retokenized, retokenized_tokens),
+        };
+        if reference_tokens == 0 {
+            return None;
+        }
+        let error =
+            |tokens: u64| (tokens as f64 - reference_tokens as f64) / reference_tokens as f64;
+        Some(TokenReconciliation {
+            requests: responses.len() as u64,
+            chunks,
+            chunk_tokens,
+            retokenized_tokens,
+            usage_tokens,
+            reference,
+            chunks_error: error(chunks),
+            chunk_tokens_error: error(chunk_tokens),
+            retokenized_tokens_error: usage_tokens.map(|_| error(retokenized_tokens)),
+            mismatched_requests: responses
+                .iter()
+                .filter(|r| {
+                    let reference = match reference {
+                        TokenCountSource::Usage => r.usage_completion_tokens,
+                        TokenCountSource::Retokenized => r.retokenized_tokens,
+                    };
+                    reference != Some(r.num_generated_tokens)
+                })
+                .count() as u64,
+            throughput_correction: throughput_correction(reference_tokens, chunk_tokens),
+        })
+    }
+
+    pub fn repetition(&self) -> Option<RepetitionSummary> {
+        let threshold = self.executor_config.repetition_threshold?;
+        let scores = self
+            .get_successful_responses()
+            .into_iter()
+            .filter_map(|r| r.repetition_score)
+            .collect::<Vec<f64>>();
+        if scores.is_empty() {
+            return None;
+        }
+        Some(RepetitionSummary {
+            requests: scores.len() as u64,
+            threshold,
+            repetitive_responses: scores.iter().filter(|s| **s >= threshold).count() as u64,
+            repetition_score_avg: scores.iter().sum::<f64>() / scores.len() as f64,
+            repetition_score_max: scores.iter().copied().fold(0.0, f64::max),
+        })
+    }
+
+    /// Summaries of the custom scores of the successful responses, by score
+    pub fn scores(&self) -> BTreeMap<String, ScoreSummary> {
+        ScoreSummary::by_name(
+            self.get_successful_responses()
+                .into_iter()
+                .map(|response| &response.scores),
+        )
+    }
+
+    pub fn playback(&self) -> Option<PlaybackSummary> {
+        let reading_rate = self.executor_config.reading_rate?;
+        // unstreamed responses of a streaming comparison arrive in one piece
+        let playbacks = self
+            .get_successful_responses()
+            .into_iter()
+            .filter(|r| r.streamed != Some(false))
+            .filter_map(|r| playback(&r.times_to_tokens, r.num_generated_tokens, reading_rate))
+            .collect::<Vec<_>>();
+        if playbacks.is_empty() {
+            return None;
+        }
+        let n = playbacks.len() as f64;
+        Some(PlaybackSummary {
+            reading_rate,
+            requests: playbacks.len() as u64,
+            stalled_requests: playbacks.iter().filter(|p| p.stalls > 0).count() as u64,
+            stalls_avg: playbacks.iter().map(|p| p.stalls as f64).sum::<f64>() / n,
+            stall_time_avg: playbacks.iter().map(|p| p.stall_time).sum::<Duration>()
+                / playbacks.len() as u32,
+            max_tokens_ahead_avg: playbacks.iter().map(|p| p.max_tokens_ahead).sum::<f64>() / n,
+            smooth_playback_score: 100.0 * playbacks.iter().map(|p| p.smoothness).sum::<f64>() / n,
+        })
+    }
+
+    /// Throughput counting only the requests meeting the request SLO, if one is set
+    pub fn goodput(&self) -> Option<GoodputSummary> {
+        let slo = self.executor_config.request_slo?;
+        let duration = self.duration().ok()?.as_secs_f64();
+        if self.total_requests() == 0 {
+            return None;
+        }
+        let good = self
+            .get_successful_responses()
+            .into_iter()
+            .filter(|r| slo.is_met(r))
+            .collect::<Vec<_>>();
+        let requests_meeting_slo = self.extrapolate(good.len() as u64);
+        let good_tokens = self.extrapolate(good.iter().map(|r| r.num_generated_tokens).sum());
+        Some(GoodputSummary {
+            slo,
+            requests_meeting_slo,
+            slo_attainment: 100.0 * requests_meeting_slo as f64 / self.total_requests() as f64,
+            goodput: requests_meeting_slo as f64 / duration,
+            token_goodput_secs: good_tokens as f64 / duration,
+        })
+    }
+
+    /// Metrics over time windows of the step, if `time_series_window` is set
+    pub fn time_series(&self) -> Option<Vec<TimeSeriesPoint>> {
+        self.aggregates
+            .time_series(self.duration().unwrap_or_default())
+    }
+
+    /// Share of correct answers among successful responses with an expected answer
+    pub fn accuracy(&self) -> Option<AccuracySummary> {
+        let scoring = self.executor_config.answer_scoring?;
+        let scores = self
+            .get_successful_responses()
+            .into_iter()
+            .filter_map(|r| r.correct)
+            .collect::<Vec<bool>>();
+        if scores.is_empty() {
+            return None;
+        }
+        let correct = scores.iter().filter(|c| **c).count() as u64;
+        Some(AccuracySummary {
+            scoring,
+            scored_responses: scores.len() as u64,
+            correct_responses: correct,
+            accuracy: correct as f64 / scores.len() as f64,
+        })
+    }
+
+    /// Metrics derived metrics are computed from, by name. Metrics that can't be computed for the
+    /// step (e.g. `rate` for a constant VUs step) are missing.
+    pub fn base_metrics(&self) -> BTreeMap<String, f64> {
+        let mut metrics = BTreeMap::new();
+        let mut insert = |name: &str, value: anyhow::Result<f64>| {
+            if let Ok(value) = value {
+                metrics.insert(name.to_string(), value);
+            }
+        };
+        insert("total_requests", Ok(self.total_requests() as f64));
+        insert("successful_requests", Ok(self.successful_requests() as f64));
+        insert("failed_requests", Ok(self.failed_requests() as f64));
+        if self.total_requests() > 0 {
+            insert(
+                "error_rate",
+                Ok(100.0 * self.failed_requests() as f64 / self.total_requests() as f64),
+            );
+        }
+        insert("request_rate", self.successful_request_rate());
+        insert("token_throughput_secs", self.token_throughput_secs());
+        insert("total_tokens", Ok(self.total_tokens() as f64));
+        insert("total_tokens_sent", Ok(self.total_tokens_sent() as f64));
+        insert("duration_secs", self.duration().map(|d| d.as_secs_f64()));
+        insert("max_vus", Ok(self.executor_config.max_vus as f64));
+        if let Some(rate) = self.executor_config.average_rate() {
+            insert("rate", Ok(rate));
+        }
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        type Percentile<'a> = &'a dyn Fn(f64) -> anyhow::Result<Duration>;
+        let latencies: [(&str, anyhow::Result<Duration>, Percentile); 3] = [
+            ("ttft", self.time_to_first_token_avg(), &|p| {
+                self.time_to_first_token_percentile(p)
+            }),
+            ("itl", self.inter_token_latency_avg(), &|p| {
+                self.inter_token_latency_percentile(p)
+            }),
+            ("e2e", self.e2e_latency_avg(), &|p| {
+                self.e2e_latency_percentile(p)
+            }),
+        ];
+        insert("prefill_share", self.prefill_share());
+        if let Some(goodput) = self.goodput() {
+            insert("goodput", Ok(goodput.goodput));
+            insert("token_goodput_secs", Ok(goodput.token_goodput_secs));
+        }
+        for (name, avg, percentile) in latencies {
+            insert(&format!("{name}_avg_ms"), avg.map(ms));
+            for p in [50, 90, 99] {
+                insert(
+                    &format!("{name}_p{p}_ms"),
+                    percentile(p as f64 / 100.0).map(ms),
+                );
+            }
         }
+        metrics
+    }
+
+    /// Evaluate derived metrics on the base metrics, `None` for metrics that can't be computed
+    pub fn derived_metrics(&self, metrics: &[DerivedMetric]) -> BTreeMap<String, Option<f64>> {
+        let base_metrics = self.base_metrics();
+        metrics
+            .iter()
+            .map(|metric| {
+                let value = metric
+                    .evaluate(&base_metrics)
+                    .inspect_err(|e| debug!("{e} on {}", self.id))
+                    .ok();
+                (metric.name.clone(), value)
+            })
+            .collect()
     }
 
-    pub fn add_response(&mut self, response: TextGenerationAggregatedResponse) {
-        self.aggregated_responses.push(response);
+    pub fn sampled_responses(&self) -> Vec<TextGenerationAggregatedResponse> {
+        self.sampled_responses.clone()
     }
 
     pub fn total_requests(&self) -> usize {
-        self.aggregated_responses.len()
+        self.aggregates.total_requests as usize
     }
 
     pub fn start_time(&self) -> Option<tokio::time::Instant> {
-        self.aggregated_responses
-            .first()
-            .and_then(|response| response.start_time)
+        self.aggregates.start_time
     }
 
     pub fn end_time(&self) -> Option<tokio::time::Instant> {
-        self.aggregated_responses
-            .last()
-            .and_then(|response| response.end_time)
+        self.aggregates.end_time
     }
 
     fn is_ready(&self) -> bool {
@@ -66,17 +1199,11 @@ impl BenchmarkResults {
     }
 
     pub fn failed_requests(&self) -> usize {
-        self.aggregated_responses
-            .iter()
-            .filter(|response| response.failed)
-            .count()
+        self.aggregates.failed_requests as usize
     }
 
     pub fn successful_requests(&self) -> usize {
-        self.aggregated_responses
-            .iter()
-            .filter(|response| !response.failed)
-            .count()
+        self.aggregates.successful_requests() as usize
     }
 
     pub fn token_throughput_secs(&self) -> anyhow::Result<f64> {
@@ -89,17 +1216,11 @@ impl BenchmarkResults {
     }
 
     pub fn total_tokens_sent(&self) -> u64 {
-        self.get_successful_responses()
-            .iter()
-            .map(|response| response.num_prompt_tokens)
-            .sum()
+        self.aggregates.prompt_tokens
     }
 
     pub fn total_prompt_tokens(&self) -> u64 {
-        self.get_successful_responses()
-            .iter()
-            .map(|response| response.num_prompt_tokens)
-            .sum()
+        self.aggregates.prompt_tokens
     }
 
     pub fn prompt_tokens_avg(&self) -> anyhow::Result<f64> {
@@ -121,10 +1242,7 @@ impl BenchmarkResults {
     }
 
     pub fn total_tokens(&self) -> u64 {
-        self.get_successful_responses()
-            .iter()
-            .map(|response| response.num_generated_tokens)
-            .sum()
+        self.aggregates.generated_tokens
     }
 
     pub fn duration(&self) -> anyhow::Result<std::time::Duration> {
@@ -132,7 +1250,8 @@ impl BenchmarkResults {
             Ok(self
                 .end_time()
                 .unwrap()
-                .duration_since(self.start_time().unwrap()))
+                .duration_since(self.start_time().unwrap())
+                .saturating_sub(self.paused_duration()))
         } else {
             Err(anyhow::anyhow!(NoResponses))
         }
@@ -143,6 +1262,9 @@ impl BenchmarkResults {
             if self.successful_requests() == 0 {
                 return Ok(Duration::from_secs(0));
             }
+            if self.is_sampled() {
+                return Ok(self.aggregated_latency_avg(RequestMetric::E2eLatency));
+            }
             Ok(self
                 .get_successful_responses()
                 .iter()
@@ -154,7 +1276,63 @@ impl BenchmarkResults {
         }
     }
 
+    /// Values of a per-request metric over the successful responses, latencies in milliseconds
+    pub fn request_values(&self, metric: RequestMetric) -> Vec<f64> {
+        let ms = |latency: Option<Duration>| latency.unwrap_or_default().as_secs_f64() * 1000.0;
+        self.get_successful_responses()
+            .iter()
+            .filter_map(|response| match metric {
+                RequestMetric::TimeToFirstToken => Some(ms(response.time_to_first_token())),
+                RequestMetric::InterTokenLatency => Some(ms(response.inter_token_latency())),
+                RequestMetric::E2eLatency => Some(ms(response.e2e_latency())),
+                RequestMetric::OutputTokensPerSec => {
+                    let e2e = response.e2e_latency()?.as_secs_f64();
+                    (e2e > 0.0).then(|| response.num_generated_tokens as f64 / e2e)
+                }
+            })
+            .collect()
+    }
+
+    /// `percentile` (between 0 and 1) of a per-request metric
+    pub fn request_percentile(
+        &self,
+        metric: RequestMetric,
+        percentile: f64,
+    ) -> anyhow::Result<f64> {
+        self.aggregated_percentile(metric, percentile)
+    }
+
+    /// Average of a per-request metric over the successful responses, `None` without any
+    pub fn request_avg(&self, metric: RequestMetric) -> Option<f64> {
+        self.aggregates.metric(metric).mean()
+    }
+
+    /// Sample standard deviation of a per-request metric over the successful responses, `None`
+    /// under two of them
+    pub fn request_std_dev(&self, metric: RequestMetric) -> Option<f64> {
+        self.aggregates.metric(metric).std_dev()
+    }
+
+    /// Percentile of a per-request metric over all the received responses, from their histograms
+    fn aggregated_percentile(&self, metric: RequestMetric, percentile: f64) -> anyhow::Result<f64> {
+        self.aggregates
+            .metric(metric)
+            .quantile(percentile)
+            .ok_or(anyhow::anyhow!(NoResponses))
+    }
+
+    /// Average latency over all the received responses, from their histograms
+    fn aggregated_latency_avg(&self, metric: RequestMetric) -> Duration {
+        let ms = self.aggregates.metric(metric).mean().unwrap_or_default();
+        Duration::from_secs_f64(ms / 1000.0)
+    }
+
     pub fn e2e_latency_percentile(&self, percentile: f64) -> anyhow::Result<std::time::Duration> {
+        if self.is_sampled() {
+            return self
+                .aggregated_percentile(RequestMetric::E2eLatency, percentile)
+                .map(|ms| Duration::from_secs_f64(ms / 1000.0));
+        }
         let quantile = self.quantile_duration(
             self.get_successful_responses()
                 .iter()
@@ -170,6 +1348,9 @@ impl BenchmarkResults {
             if self.successful_requests() == 0 {
                 return Ok(Duration::from_secs(0));
             }
+            if self.is_sampled() {
+                return Ok(self.aggregated_latency_avg(RequestMetric::TimeToFirstToken));
+            }
             Ok(self
                 .get_successful_responses()
                 .iter()
@@ -181,7 +1362,34 @@ impl BenchmarkResults {
         }
     }
 
+    /// Share of the end-to-end latency spent waiting for the first token, in percent, averaged over
+    /// successful responses. Close to 100% when prefill dominates, as with long RAG prompts and
+    /// short answers.
+    pub fn prefill_share(&self) -> anyhow::Result<f64> {
+        if !self.is_ready() {
+            return Err(anyhow::anyhow!(NoResponses));
+        }
+        let shares = self
+            .get_successful_responses()
+            .iter()
+            .filter_map(|response| {
+                let e2e = response.e2e_latency()?.as_secs_f64();
+                let ttft = response.time_to_first_token()?.as_secs_f64();
+                (e2e > 0.0).then(|| 100.0 * ttft / e2e)
+            })
+            .collect::<Vec<f64>>();
+        if shares.is_empty() {
+            return Ok(0.0);
+        }
+        Ok(shares.iter().sum::<f64>() / shares.len() as f64)
+    }
+
     pub fn time_to_first_token_percentile(&self, percentile: f64) -> anyhow::Result<Duration> {
+        if self.is_sampled() {
+            return self
+                .aggregated_percentile(RequestMetric::TimeToFirstToken, percentile)
+                .map(|ms| Duration::from_secs_f64(ms / 1000.0));
+        }
         let quantile = self.quantile_duration(
             self.get_successful_responses()
                 .iter()
@@ -197,6 +1405,9 @@ impl BenchmarkResults {
             if self.successful_requests() == 0 {
                 return Ok(Duration::from_secs(0));
             }
+            if self.is_sampled() {
+                return Ok(self.aggregated_latency_avg(RequestMetric::InterTokenLatency));
+            }
             Ok(self
                 .get_successful_responses()
                 .iter()
@@ -209,6 +1420,11 @@ impl BenchmarkResults {
     }
 
     pub fn inter_token_latency_percentile(&self, percentile: f64) -> anyhow::Result<Duration> {
+        if self.is_sampled() {
+            return self
+                .aggregated_percentile(RequestMetric::InterTokenLatency, percentile)
+                .map(|ms| Duration::from_secs_f64(ms / 1000.0));
+        }
         let quantile = self.quantile_duration(
             self.get_successful_responses()
                 .iter()
@@ -219,6 +1435,20 @@ impl BenchmarkResults {
         Ok(Duration::from_secs_f64(quantile))
     }
 
+    /// Average number of requests in flight, derived from Little's Law (L = λW) using the
+    /// successful request rate and the average end-to-end latency as service time
+    pub fn effective_concurrency(&self) -> anyhow::Result<f64> {
+        Ok(self.successful_request_rate()? * self.e2e_latency_avg()?.as_secs_f64())
+    }
+
+    /// Fraction of the virtual users pool that was busy on average
+    pub fn utilization(&self) -> anyhow::Result<f64> {
+        if self.executor_config.max_vus == 0 {
+            return Err(anyhow::anyhow!("max_vus must be greater than 0"));
+        }
+        Ok(self.effective_concurrency()? / self.executor_config.max_vus as f64)
+    }
+
     pub fn executor_type(&self) -> ExecutorType {
         self.executor_type.clone()
     }
@@ -227,7 +1457,193 @@ impl BenchmarkResults {
         self.executor_config.clone()
     }
 
-    fn get_successful_responses(&self) -> Vec<&TextGenerationAggregatedResponse> {
+    /// Group successful responses by prompt length, using power-of-two token ranges
+    pub fn prompt_length_buckets(&self) -> Vec<LengthBucket> {
+        self.length_buckets(|response| response.num_prompt_tokens)
+    }
+
+    /// Group successful responses by number of generated tokens, using power-of-two token ranges
+    pub fn decode_length_buckets(&self) -> Vec<LengthBucket> {
+        self.length_buckets(|response| response.num_generated_tokens)
+    }
+
+    fn length_buckets<F>(&self, num_tokens: F) -> Vec<LengthBucket>
+    where
+        F: Fn(&TextGenerationAggregatedResponse) -> u64,
+    {
+        let mut buckets: BTreeMap<u32, Vec<TextGenerationAggregatedResponse>> = BTreeMap::new();
+        for response in self.get_successful_responses() {
+            // bucket k holds [2^(k-1), 2^k) tokens, bucket 0 holds empty sequences
+            let bucket = u64::BITS - num_tokens(response).leading_zeros();
+            buckets.entry(bucket).or_default().push(response.clone());
+        }
+        buckets
+            .into_iter()
+            .map(|(bucket, responses)| {
+                let (min_tokens, max_tokens) = match bucket {
+                    0 => (0, 1),
+                    _ => (1 << (bucket - 1), 1 << bucket),
+                };
+                let aggregates = ResponseAggregates::from_responses(
+                    &responses,
+                    self.executor_config.time_series_window,
+                );
+                LengthBucket {
+                    min_tokens,
+                    max_tokens,
+                    results: self.with_responses(
+                        format!("{}[{}-{})", self.id, min_tokens, max_tokens),
+                        responses,
+                        aggregates,
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Split the responses in the `n` first sent ones and the others, e.g. to compare the first
+    /// (cold) requests of the warmup with the next ones
+    pub fn split_first(&self, n: usize) -> (BenchmarkResults, BenchmarkResults) {
+        let mut responses = self.aggregated_responses.clone();
+        responses.sort_by_key(|response| response.start_time);
+        let others = responses.split_off(n.min(responses.len()));
+        let window = self.executor_config.time_series_window;
+        let (cold, warm) = (
+            ResponseAggregates::from_responses(&responses, window),
+            ResponseAggregates::from_responses(&others, window),
+        );
+        (
+            self.with_responses(format!("{}[cold]", self.id), responses, cold),
+            self.with_responses(format!("{}[warm]", self.id), others, warm),
+        )
+    }
+
+    /// Split the responses in the ones sent during the stage warmup and the measured ones, each
+    /// result keeping its share of the stage duration
+    pub fn split_warmup(&self) -> (BenchmarkResults, BenchmarkResults) {
+        let warmup = self.executor_config.stage_warmup.unwrap_or_default();
+        let mut warmup_results =
+            self.segment_results(format!("{}[warmup]", self.id), &Segment::Warmup);
+        warmup_results.executor_config.duration = warmup.min(self.executor_config.duration);
+        let mut measured = self.segment_results(self.id.clone(), &Segment::Measured);
+        measured.executor_config.duration = self.executor_config.duration.saturating_sub(warmup);
+        measured.blocked_response_sends = self.blocked_response_sends;
+        measured.discarded_responses = self.discarded_responses;
+        measured.cancelled_requests = self.cancelled_requests;
+        measured.clamped_requests = self.clamped_requests;
+        (warmup_results, measured)
+    }
+
+    /// Split the responses of a step profile stage in one result per step, on the time requests
+    /// were sent. Each step is reported as a constant arrival rate step at its rate.
+    pub fn split_steps(&self, profile: &StepProfile) -> Vec<BenchmarkResults> {
+        profile
+            .step_ids()
+            .into_iter()
+            .zip(&profile.rates)
+            .enumerate()
+            .map(|(i, (id, rate))| {
+                let mut step = self.segment_results(id, &Segment::Step(i));
+                step.executor_config.duration = profile.step_duration;
+                step.executor_config.rate = Some(*rate);
+                step.executor_config.rate_curve = None;
+                step
+            })
+            .collect()
+    }
+
+    /// Split the responses by the tenant they were sent as, to compare the latency and throughput
+    /// of tenants. Empty without tenants.
+    pub fn tenant_results(&self) -> BTreeMap<String, BenchmarkResults> {
+        self.segments
+            .keys()
+            .filter_map(|segment| match segment {
+                Segment::Tenant(tenant) => {
+                    let id = format!("{}[{}]", self.id, tenant);
+                    Some((tenant.clone(), self.segment_results(id, segment)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Split the responses of a burst stage by the phase of the burst curve they were sent in, to
+    /// compare latencies during spikes with the baseline. The duration of each phase result is the
+    /// time spent in the phase. Empty for other stages.
+    pub fn phase_results(&self) -> BTreeMap<String, BenchmarkResults> {
+        let Some(curve) = &self.executor_config.rate_curve else {
+            return BTreeMap::new();
+        };
+        self.segments
+            .keys()
+            .filter_map(|segment| match segment {
+                Segment::Phase(phase) => {
+                    let id = format!("{}[{}]", self.id, phase);
+                    let mut results = self.segment_results(id, segment);
+                    results.executor_config.duration =
+                        curve.phase_duration(phase, self.executor_config.duration);
+                    Some((phase.to_string(), results))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Results of a part of the stage: the aggregates of all its responses and its stored ones
+    fn segment_results(&self, id: String, segment: &Segment) -> BenchmarkResults {
+        let in_segment = |response: &&TextGenerationAggregatedResponse| {
+            self.segments_of(response).contains(segment)
+        };
+        let responses = self
+            .aggregated_responses
+            .iter()
+            .filter(in_segment)
+            .cloned()
+            .collect();
+        let aggregates =
+            self.segments.get(segment).cloned().unwrap_or_else(|| {
+                ResponseAggregates::new(self.executor_config.time_series_window)
+            });
+        let mut results = self.with_responses(id, responses, aggregates);
+        results.sampled_responses = self
+            .sampled_responses
+            .iter()
+            .filter(in_segment)
+            .cloned()
+            .collect();
+        results
+    }
+
+    /// Results of the same step restricted to a subset of its responses
+    fn with_responses(
+        &self,
+        id: String,
+        responses: Vec<TextGenerationAggregatedResponse>,
+        aggregates: ResponseAggregates,
+    ) -> BenchmarkResults {
+        BenchmarkResults {
+            id,
+            aggregates,
+            aggregated_responses: responses,
+            sampled_responses: Vec::new(),
+            executor_type: self.executor_type.clone(),
+            executor_config: self.executor_config.clone(),
+            blocked_response_sends: 0,
+            discarded_responses: 0,
+            cancelled_requests: 0,
+            server_metrics: None,
+            vllm_stats: None,
+            assertions: Vec::new(),
+            clamped_requests: 0,
+            stage_id: None,
+            decode_scale: self.decode_scale,
+            paused: self.paused.clone(),
+            stage_start: self.stage_start,
+            segments: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn get_successful_responses(&self) -> Vec<&TextGenerationAggregatedResponse> {
         self.aggregated_responses
             .iter()
             .filter(|response| !response.failed)
@@ -238,18 +1654,30 @@ impl BenchmarkResults {
         self.aggregated_responses.clone()
     }
 
+    /// Stored responses, without copying them
+    pub fn responses(&self) -> &[TextGenerationAggregatedResponse] {
+        &self.aggregated_responses
+    }
+
     /// Calculate the quantile of a given data set using interpolation method
     /// Results are similar to `numpy.percentile`
-    fn quantile_duration(&self, mut data: Vec<Duration>, quantile: f64) -> anyhow::Result<f64> {
-        if self.is_ready() {
-            data.sort();
+    fn quantile_duration(&self, data: Vec<Duration>, quantile: f64) -> anyhow::Result<f64> {
+        self.quantile(data.iter().map(Duration::as_secs_f64).collect(), quantile)
+    }
+
+    /// Quantile of the data, interpolated linearly between the closest ranks
+    fn quantile(&self, mut data: Vec<f64>, quantile: f64) -> anyhow::Result<f64> {
+        if self.is_ready() && !data.is_empty() {
+            data.sort_by(f64::total_cmp);
             let i = (quantile * (data.len() - 1) as f64).floor();
             let delta = (data.len() - 1) as f64 * quantile - i;
             if i as usize >= data.len() {
                 return Err(anyhow::anyhow!(NoResponses));
             }
-            let quantile = (1. - delta) * data[i as usize].as_secs_f64()
-                + delta * data[i as usize + 1].as_secs_f64();
+            if i as usize + 1 == data.len() {
+                return Ok(data[i as usize]);
+            }
+            let quantile = (1. - delta) * data[i as usize] + delta * data[i as usize + 1];
             Ok(quantile)
         } else {
             Err(anyhow::anyhow!(NoResponses))
@@ -309,11 +1737,32 @@ impl Debug for BenchmarkResults {
     }
 }
 
+/// Subset of benchmark results whose sequence length falls in `[min_tokens, max_tokens)`
+#[derive(Debug, Clone)]
+pub struct LengthBucket {
+    pub min_tokens: u64,
+    pub max_tokens: u64,
+    pub results: BenchmarkResults,
+}
+
 #[derive(Debug, Clone)]
 pub struct BenchmarkReport {
     results: Vec<BenchmarkResults>,
     start_time: Option<chrono::DateTime<Utc>>,
+    /// Monotonic clock at `start_time`, to date the responses
+    start_instant: Option<tokio::time::Instant>,
     end_time: Option<chrono::DateTime<Utc>>,
+    dropped_metrics: u64,
+    hooks: Vec<HookOutput>,
+    client_saturations: Vec<ClientSaturation>,
+    skipped_stages: Vec<String>,
+    stage_warmups: Vec<BenchmarkResults>,
+}
+
+impl Default for BenchmarkReport {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BenchmarkReport {
@@ -321,12 +1770,19 @@ impl BenchmarkReport {
         BenchmarkReport {
             results: Vec::new(),
             start_time: None,
+            start_instant: None,
             end_time: None,
+            dropped_metrics: 0,
+            hooks: Vec::new(),
+            client_saturations: Vec::new(),
+            skipped_stages: Vec::new(),
+            stage_warmups: Vec::new(),
         }
     }
 
     pub fn start(&mut self) {
         self.start_time = Some(Utc::now());
+        self.start_instant = Some(tokio::time::Instant::now());
     }
 
     pub fn end(&mut self) {
@@ -341,6 +1797,20 @@ impl BenchmarkReport {
         self.results.clone()
     }
 
+    /// Results of the steps, without copying them
+    pub fn results(&self) -> &[BenchmarkResults] {
+        &self.results
+    }
+
+    pub fn add_stage_warmup(&mut self, warmup: BenchmarkResults) {
+        self.stage_warmups.push(warmup);
+    }
+
+    /// Warmups run at the start of stages, excluded from their results
+    pub fn stage_warmups(&self) -> Vec<BenchmarkResults> {
+        self.stage_warmups.clone()
+    }
+
     pub fn start_time(&self) -> Option<chrono::DateTime<Utc>> {
         self.start_time
     }
@@ -348,11 +1818,54 @@ impl BenchmarkReport {
     pub fn end_time(&self) -> Option<chrono::DateTime<Utc>> {
         self.end_time
     }
+
+    /// Wall clock time of a response timestamp, if the benchmark started
+    pub fn wall_time(&self, instant: tokio::time::Instant) -> Option<chrono::DateTime<Utc>> {
+        let (start_time, start_instant) = (self.start_time?, self.start_instant?);
+        let elapsed = chrono::Duration::from_std(instant.saturating_duration_since(start_instant));
+        Some(start_time + elapsed.ok()?)
+    }
+
+    pub fn set_dropped_metrics(&mut self, dropped_metrics: u64) {
+        self.dropped_metrics = dropped_metrics;
+    }
+
+    /// Number of metrics skipped by metrics bus subscribers that lagged behind
+    pub fn dropped_metrics(&self) -> u64 {
+        self.dropped_metrics
+    }
+
+    pub fn set_client_saturations(&mut self, saturations: Vec<ClientSaturation>) {
+        self.client_saturations = saturations;
+    }
+
+    /// Times the client resources saturated during the run, with the VU caps applied
+    pub fn client_saturations(&self) -> Vec<ClientSaturation> {
+        self.client_saturations.clone()
+    }
+
+    pub fn add_skipped_stage(&mut self, id: String) {
+        self.skipped_stages.push(id);
+    }
+
+    /// Stages not run because the run deadline was reached
+    pub fn skipped_stages(&self) -> Vec<String> {
+        self.skipped_stages.clone()
+    }
+
+    pub fn add_hook_output(&mut self, output: HookOutput) {
+        self.hooks.push(output);
+    }
+
+    pub fn hooks(&self) -> Vec<HookOutput> {
+        self.hooks.clone()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::executors::{BASELINE_PHASE, BURST_PHASE};
     #[test]
     fn test_time_to_first_token_percentile() {
         let mut response1 = TextGenerationAggregatedResponse::default();
@@ -422,6 +1935,7 @@ mod test {
                 max_vus: 0,
                 duration: Default::default(),
                 rate: None,
+                ..Default::default()
             },
         );
         results.add_response(response1);
@@ -438,4 +1952,717 @@ mod test {
             Duration::from_millis(850)
         );
     }
+
+    #[test]
+    fn test_prefill_share() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig::default(),
+        );
+        assert!(results.prefill_share().is_err());
+        // half and all of the latency before the first token
+        for (ttft, e2e) in [(100, 200), (400, 400)] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            let start = tokio::time::Instant::now();
+            response.start_time = Some(start);
+            response.end_time = Some(start + Duration::from_millis(e2e));
+            response.times_to_tokens = vec![Duration::from_millis(ttft)];
+            results.add_response(response);
+        }
+        assert!((results.prefill_share().unwrap() - 75.0).abs() < 1e-9);
+        assert_eq!(results.base_metrics()["prefill_share"], 75.0);
+    }
+
+    #[test]
+    fn test_failure_taxonomy() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig::default(),
+        );
+        for (class, status) in [
+            (None, Some(200)),
+            (Some(ErrorClass::Quota), Some(429)),
+            (Some(ErrorClass::Quota), Some(429)),
+            (Some(ErrorClass::Server), Some(503)),
+            (Some(ErrorClass::Parse), Some(200)),
+            (None, Some(200)),
+        ] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.failed = class.is_some();
+            response.error_class = class;
+            response.http_status = status;
+            results.add_response(response);
+        }
+        results.set_response_channel_stats(0, 0, 2);
+        assert_eq!(results.failed_requests(), 4);
+        assert_eq!(
+            results.failures(),
+            BTreeMap::from([
+                (ErrorClass::Quota, 2),
+                (ErrorClass::Server, 1),
+                (ErrorClass::Parse, 1),
+                (ErrorClass::Cancelled, 2),
+            ])
+        );
+        // cancelled requests were sent too
+        assert_eq!(results.failure_rates()[&ErrorClass::Quota], 0.25);
+        assert_eq!(
+            results.status_codes(),
+            BTreeMap::from([(200, 3), (429, 2), (503, 1)])
+        );
+    }
+
+    #[test]
+    fn test_sampled_responses() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                max_vus: 0,
+                duration: Default::default(),
+                rate: None,
+                sample_responses: 5,
+                ..Default::default()
+            },
+        );
+        for i in 0..100 {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.generated_text = format!("response {}", i);
+            results.add_response(response);
+        }
+        let samples = results.sampled_responses();
+        assert_eq!(samples.len(), 5);
+        assert!(samples.iter().all(|r| !r.generated_text.is_empty()));
+        assert!(results
+            .get_responses()
+            .iter()
+            .all(|r| r.generated_text.is_empty()));
+    }
+
+    #[test]
+    fn test_answer_scoring() {
+        let regexes = AnswerRegexes::compile([r"\bParis\b", r"\bParis\b"]).unwrap();
+        assert!(AnswerScoring::Exact.score(&regexes, "Paris", " paris\n"));
+        assert!(!AnswerScoring::Exact.score(&regexes, "Paris", "It is Paris"));
+        assert!(AnswerScoring::Regex.score(&regexes, r"\bParis\b", "It is Paris."));
+        assert!(!AnswerScoring::Regex.score(&regexes, "Paris", "Paris"));
+        assert!(AnswerRegexes::compile(["4", "("]).is_err());
+        assert!("regex".parse::<AnswerScoring>().is_ok());
+        assert!("fuzzy".parse::<AnswerScoring>().is_err());
+
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                answer_scoring: Some(AnswerScoring::Exact),
+                ..Default::default()
+            },
+        );
+        for (answer, text) in [(Some("4"), "4"), (Some("4"), "5"), (None, "4")] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.request = Some(std::sync::Arc::new(
+                crate::requests::TextGenerationRequest {
+                    prompt: "2+2=".to_string(),
+                    num_prompt_tokens: 4,
+                    num_decode_tokens: None,
+                    system_prompt: None,
+                    expected_answer: answer.map(str::to_string),
+                    context_clamped: false,
+                },
+            ));
+            response.generated_text = text.to_string();
+            results.add_response(response);
+        }
+        let accuracy = results.accuracy().unwrap();
+        assert_eq!(accuracy.scored_responses, 2);
+        assert_eq!(accuracy.correct_responses, 1);
+        assert_eq!(accuracy.accuracy, 0.5);
+    }
+
+    #[test]
+    fn test_length_buckets() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                max_vus: 0,
+                duration: Default::default(),
+                rate: None,
+                ..Default::default()
+            },
+        );
+        for (num_prompt_tokens, ttft) in [(100, 10), (120, 20), (200, 30), (1000, 40)] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(tokio::time::Instant::now());
+            response.end_time =
+                Some(tokio::time::Instant::now() + tokio::time::Duration::from_millis(100));
+            response.num_prompt_tokens = num_prompt_tokens;
+            response.num_generated_tokens = 10;
+            response.times_to_tokens = vec![Duration::from_millis(ttft)];
+            results.add_response(response);
+        }
+        let buckets = results.prompt_length_buckets();
+        assert_eq!(buckets.len(), 3);
+        assert_eq!((buckets[0].min_tokens, buckets[0].max_tokens), (64, 128));
+        assert_eq!(buckets[0].results.successful_requests(), 2);
+        assert_eq!(
+            buckets[0]
+                .results
+                .time_to_first_token_percentile(0.5)
+                .unwrap(),
+            Duration::from_millis(15)
+        );
+        assert_eq!((buckets[1].min_tokens, buckets[1].max_tokens), (128, 256));
+        assert_eq!(
+            buckets[1]
+                .results
+                .time_to_first_token_percentile(0.99)
+                .unwrap(),
+            Duration::from_millis(30)
+        );
+        assert_eq!((buckets[2].min_tokens, buckets[2].max_tokens), (512, 1024));
+        let buckets = results.decode_length_buckets();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!((buckets[0].min_tokens, buckets[0].max_tokens), (8, 16));
+    }
+
+    #[test]
+    fn test_repetition_score() {
+        assert_eq!(repetition_score(""), 0.0);
+        assert_eq!(repetition_score("the quick brown fox jumps"), 0.0);
+        // 10 4-grams, the last one repeats the first one
+        let text = "a b c d e f g h i a b c d";
+        assert!((repetition_score(text) - 0.1).abs() < 1e-9);
+        let looping = "I am sorry. ".repeat(50);
+        assert!(repetition_score(&looping) > 0.9);
+    }
+
+    #[test]
+    fn test_split_first() {
+        let mut results = BenchmarkResults::new(
+            "warmup".to_string(),
+            ExecutorType::ConstantVUs,
+            ExecutorConfig::default(),
+        );
+        let start = tokio::time::Instant::now();
+        // responses are received out of order, the split follows the send order
+        for (offset, ttft) in [(2, 20), (0, 500), (1, 30), (3, 10)] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start + Duration::from_secs(offset));
+            response.end_time = Some(start + Duration::from_secs(offset + 1));
+            response.times_to_tokens = vec![Duration::from_millis(ttft)];
+            results.add_response(response);
+        }
+        let (cold, warm) = results.split_first(1);
+        assert_eq!(cold.id, "warmup[cold]");
+        assert_eq!(cold.total_requests(), 1);
+        assert_eq!(
+            cold.time_to_first_token_avg().unwrap(),
+            Duration::from_millis(500)
+        );
+        assert_eq!(warm.total_requests(), 3);
+        assert_eq!(
+            warm.time_to_first_token_avg().unwrap(),
+            Duration::from_millis(20)
+        );
+        let (cold, warm) = results.split_first(10);
+        assert_eq!((cold.total_requests(), warm.total_requests()), (4, 0));
+    }
+
+    #[test]
+    fn test_split_warmup() {
+        let mut results = BenchmarkResults::new(
+            "constant@2.00req/s".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                duration: Duration::from_secs(70),
+                rate: Some(2.0),
+                stage_warmup: Some(Duration::from_secs(10)),
+                ..Default::default()
+            },
+        );
+        let start = tokio::time::Instant::now();
+        // sent during the 10s warmup, then measured
+        for (offset, e2e) in [(0, 4000), (5, 3000), (10, 1000), (40, 1000), (69, 1000)] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start + Duration::from_secs(offset));
+            response.end_time = Some(start + Duration::from_millis(offset * 1000 + e2e));
+            results.add_response(response);
+        }
+        let (warmup, measured) = results.split_warmup();
+        assert_eq!(warmup.id, "constant@2.00req/s[warmup]");
+        assert_eq!(warmup.total_requests(), 2);
+        assert_eq!(warmup.executor_config().duration, Duration::from_secs(10));
+        assert_eq!(measured.id, "constant@2.00req/s");
+        assert_eq!(measured.total_requests(), 3);
+        assert_eq!(measured.executor_config().duration, Duration::from_secs(60));
+        assert_eq!(measured.e2e_latency_avg().unwrap(), Duration::from_secs(1));
+        assert_eq!(measured.duration().unwrap(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_split_steps() {
+        let profile: StepProfile = "1,2,4@10s".parse().unwrap();
+        let mut results = BenchmarkResults::new(
+            "steps@1.00-2.00-4.00req/s".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                rate_curve: Some(profile.rate_curve()),
+                ..Default::default()
+            },
+        );
+        let start = tokio::time::Instant::now();
+        results.set_stage_start(start);
+        // the step follows the send time from the stage start, whatever the order responses are
+        // received in and the time the first request was sent
+        for offset in [12, 3, 8, 25, 31, 21] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start + Duration::from_secs(offset));
+            response.end_time = Some(start + Duration::from_secs(offset + 1));
+            results.add_response(response);
+        }
+        let steps = results.split_steps(&profile);
+        assert_eq!(
+            steps.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(),
+            ["step1@1.00req/s", "step2@2.00req/s", "step3@4.00req/s"]
+        );
+        // requests sent after the last step are in it
+        assert_eq!(
+            steps.iter().map(|s| s.total_requests()).collect::<Vec<_>>(),
+            [2, 1, 3]
+        );
+        assert_eq!(steps[1].executor_config().rate, Some(2.0));
+        assert!(steps[1].executor_config().rate_curve.is_none());
+        assert_eq!(steps[1].executor_config().duration, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_paused_split() {
+        let profile: StepProfile = "1,2@10s".parse().unwrap();
+        let mut results = BenchmarkResults::new(
+            "steps@1.00-2.00req/s".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                rate_curve: Some(profile.rate_curve()),
+                stage_warmup: Some(Duration::from_secs(5)),
+                ..Default::default()
+            },
+        );
+        let start = tokio::time::Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        results.set_stage_start(start);
+        results.set_paused(vec![(at(5), at(15))]);
+        // 3s, 6s and 12s of active time
+        for offset in [3, 16, 22] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(at(offset));
+            response.end_time = Some(at(offset + 1));
+            results.add_response(response);
+        }
+        let steps = results.split_steps(&profile);
+        assert_eq!(
+            steps.iter().map(|s| s.total_requests()).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        let (warmup, measured) = results.split_warmup();
+        assert_eq!(warmup.total_requests(), 1);
+        assert_eq!(measured.total_requests(), 2);
+    }
+
+    #[test]
+    fn test_phase_results() {
+        let mut results = BenchmarkResults::new(
+            "burst".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                duration: Duration::from_secs(120),
+                rate_curve: Some(RateCurve::Burst {
+                    base: 0.0,
+                    burst: 20.0,
+                    burst_duration: Duration::from_secs(10),
+                    period: Duration::from_secs(60),
+                }),
+                ..Default::default()
+            },
+        );
+        let start = tokio::time::Instant::now();
+        results.set_stage_start(start);
+        // without baseline requests, the first one is sent in the first burst
+        for offset in [50, 55, 61, 110] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start + Duration::from_secs(offset));
+            response.end_time = Some(start + Duration::from_secs(offset + 1));
+            results.add_response(response);
+        }
+        let phases = results.phase_results();
+        assert_eq!(phases[BURST_PHASE].total_requests(), 3);
+        assert_eq!(phases[BASELINE_PHASE].total_requests(), 1);
+        assert_eq!(
+            phases[BURST_PHASE].executor_config().duration,
+            Duration::from_secs(20)
+        );
+    }
+
+    #[test]
+    fn test_queueing_metrics() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantVUs,
+            ExecutorConfig {
+                max_vus: 4,
+                duration: Default::default(),
+                rate: None,
+                ..Default::default()
+            },
+        );
+        // 2 requests completing in 1s window, each taking 1s: 2 requests in flight on average
+        let start = tokio::time::Instant::now();
+        for _ in 0..2 {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start);
+            response.end_time = Some(start + Duration::from_secs(1));
+            results.add_response(response);
+        }
+        assert!((results.effective_concurrency().unwrap() - 2.0).abs() < 1e-6);
+        assert!((results.utilization().unwrap() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_tgi_details() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig::default(),
+        );
+        assert!(results.tgi_details().is_none());
+        for (failed, queue_time_ms) in [(false, Some(10.0)), (false, Some(30.0)), (true, None)] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.failed = failed;
+            response.server_details = Some(crate::requests::TgiDetails {
+                finish_reason: Some("length".to_string()),
+                generated_tokens: Some(10),
+                input_length: Some(5),
+                queue_time_ms,
+                inference_time_ms: None,
+            });
+            results.add_response(response);
+        }
+        let details = results.tgi_details().unwrap();
+        assert_eq!(details.requests, 2);
+        assert_eq!(details.generated_tokens, 20);
+        assert_eq!(details.prompt_tokens, 10);
+        assert_eq!(details.queue_time_avg_ms, Some(20.0));
+        assert_eq!(details.inference_time_avg_ms, None);
+    }
+
+    #[test]
+    fn test_prompt_cache() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig::default(),
+        );
+        assert!(results.prompt_cache().is_none());
+        for (cached_tokens, ttft_ms) in [(Some(0), 100), (Some(80), 20), (Some(60), 40), (None, 0)]
+        {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(tokio::time::Instant::now());
+            response.num_prompt_tokens = 100;
+            response.times_to_tokens = vec![Duration::from_millis(ttft_ms)];
+            response.cached_prompt_tokens = cached_tokens;
+            results.add_response(response);
+        }
+        let cache = results.prompt_cache().unwrap();
+        assert_eq!(cache.requests, 3);
+        assert_eq!(cache.cache_hits, 2);
+        assert_eq!(cache.prompt_tokens, 300);
+        assert_eq!(cache.cached_prompt_tokens, 140);
+        assert_eq!(cache.cached_ttft_avg, Some(Duration::from_millis(30)));
+        assert_eq!(cache.uncached_ttft_avg, Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_token_reconciliation() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig::default(),
+        );
+        let add = |results: &mut BenchmarkResults, chunks, tokens, retokenized, usage| {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.times_to_tokens = vec![Duration::from_millis(10); chunks];
+            response.num_generated_tokens = tokens;
+            response.retokenized_tokens = retokenized;
+            response.usage_completion_tokens = usage;
+            results.add_response(response);
+        };
+        add(&mut results, 10, 10, None, Some(10));
+        assert!(results.token_reconciliation().is_none());
+        // chunks splitting tokens, and speculative decoding sending several tokens per chunk
+        add(&mut results, 10, 12, Some(10), Some(10));
+        add(&mut results, 5, 10, Some(10), Some(10));
+        let reconciliation = results.token_reconciliation().unwrap();
+        assert_eq!(reconciliation.requests, 2);
+        assert_eq!(reconciliation.chunks, 15);
+        assert_eq!(reconciliation.chunk_tokens, 22);
+        assert_eq!(reconciliation.usage_tokens, Some(20));
+        assert_eq!(reconciliation.reference, TokenCountSource::Usage);
+        assert!((reconciliation.chunks_error + 0.25).abs() < 1e-9);
+        assert!((reconciliation.chunk_tokens_error - 0.1).abs() < 1e-9);
+        assert_eq!(reconciliation.retokenized_tokens_error, Some(0.0));
+        assert_eq!(reconciliation.mismatched_requests, 1);
+        assert!((reconciliation.throughput_correction - 20.0 / 22.0).abs() < 1e-9);
+        // without usage, whole-text re-tokenization is the reference
+        add(&mut results, 10, 10, Some(10), None);
+        let reconciliation = results.token_reconciliation().unwrap();
+        assert_eq!(reconciliation.usage_tokens, None);
+        assert_eq!(reconciliation.reference, TokenCountSource::Retokenized);
+        assert_eq!(reconciliation.retokenized_tokens_error, None);
+        assert!((reconciliation.chunk_tokens_error - 2.0 / 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_streaming_comparison() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig::default(),
+        );
+        assert!(results.streaming_comparison().is_none());
+        let start = tokio::time::Instant::now();
+        for (streamed, e2e_ms, tokens, usage, failed) in [
+            (true, 300, 10, Some(10), false),
+            (true, 500, 12, Some(11), false),
+            (true, 0, 0, None, true),
+            (false, 250, 10, Some(10), false),
+            (false, 350, 10, Some(10), false),
+        ] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start);
+            response.end_time = Some(start + Duration::from_millis(e2e_ms));
+            response.num_generated_tokens = tokens;
+            response.usage_completion_tokens = usage;
+            response.failed = failed;
+            response.streamed = Some(streamed);
+            results.add_response(response);
+        }
+        let comparison = results.streaming_comparison().unwrap();
+        assert_eq!(comparison.streamed.requests, 3);
+        assert_eq!(comparison.streamed.failed_requests, 1);
+        assert_eq!(comparison.streamed.usage_mismatches, 1);
+        assert_eq!(
+            comparison.unstreamed.e2e_latency_avg,
+            Some(Duration::from_millis(300))
+        );
+        assert!((comparison.e2e_latency_overhead_ms.unwrap() - 100.0).abs() < 1e-6);
+        assert_eq!(comparison.generated_tokens_delta, Some(1.0));
+    }
+
+    #[test]
+    fn test_playback() {
+        let ms = |ms: &[u64]| {
+            ms.iter()
+                .map(|&ms| Duration::from_millis(ms))
+                .collect::<Vec<_>>()
+        };
+        // tokens arriving at the reading rate never stall the reader
+        let smooth = playback(&ms(&[300, 50, 50, 50]), 4, 20.0).unwrap();
+        assert_eq!(smooth.stalls, 0);
+        assert_eq!(smooth.smoothness, 1.0);
+        // a burst of 3 tokens then a 400ms gap: the reader waits 250ms once
+        let stalled = playback(&ms(&[100, 0, 0, 400]), 4, 20.0).unwrap();
+        assert_eq!(stalled.stalls, 1);
+        assert_eq!(stalled.stall_time, Duration::from_millis(250));
+        assert_eq!(stalled.max_tokens_ahead, 3.0);
+        assert!((stalled.smoothness - 200.0 / 450.0).abs() < 1e-9);
+        assert!(playback(&[], 0, 20.0).is_none());
+
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                reading_rate: Some(20.0),
+                ..Default::default()
+            },
+        );
+        for times_to_tokens in [ms(&[300, 50, 50, 50]), ms(&[100, 0, 0, 400])] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(tokio::time::Instant::now());
+            response.end_time = response.start_time;
+            response.num_generated_tokens = 4;
+            response.times_to_tokens = times_to_tokens;
+            results.add_response(response);
+        }
+        let summary = results.playback().unwrap();
+        assert_eq!(summary.requests, 2);
+        assert_eq!(summary.stalled_requests, 1);
+        assert_eq!(summary.stalls_avg, 0.5);
+        assert!((summary.smooth_playback_score - 50.0 * (1.0 + 200.0 / 450.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_goodput() {
+        let slo: RequestSlo = "ttft=200ms, e2e=1s".parse().unwrap();
+        assert_eq!(slo.ttft, Some(Duration::from_millis(200)));
+        assert_eq!(slo.itl, None);
+        assert_eq!(slo.to_string(), "TTFT <= 200ms, E2E <= 1s");
+        assert!("tpot=50ms".parse::<RequestSlo>().is_err());
+        assert!("ttft".parse::<RequestSlo>().is_err());
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                request_slo: Some(slo),
+                ..Default::default()
+            },
+        );
+        // meets the SLO, misses the TTFT limit, failed, misses the e2e limit
+        let start = tokio::time::Instant::now();
+        for (ttft, e2e, failed) in [
+            (100, 500, false),
+            (300, 500, false),
+            (100, 500, true),
+            (100, 2000, false),
+        ] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start);
+            response.end_time = Some(start + Duration::from_millis(e2e));
+            response.times_to_tokens = vec![Duration::from_millis(ttft)];
+            response.num_generated_tokens = 10;
+            response.failed = failed;
+            results.add_response(response);
+        }
+        let goodput = results.goodput().unwrap();
+        assert_eq!(goodput.requests_meeting_slo, 1);
+        assert_eq!(goodput.slo_attainment, 25.0);
+        // one good request of 10 tokens over the 2 seconds of the step
+        assert!((goodput.goodput - 0.5).abs() < 1e-6);
+        assert!((goodput.token_goodput_secs - 5.0).abs() < 1e-6);
+        assert_eq!(results.base_metrics()["goodput"], goodput.goodput);
+    }
+
+    #[test]
+    fn test_request_percentiles() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig::default(),
+        );
+        let start = tokio::time::Instant::now();
+        for e2e in [1000, 2000, 3000, 4000, 5000] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start);
+            response.end_time = Some(start + Duration::from_millis(e2e));
+            response.times_to_tokens = vec![Duration::from_millis(e2e / 10)];
+            response.num_generated_tokens = 100;
+            results.add_response(response);
+        }
+        // percentiles from the histograms, within their precision
+        let p = |metric, percentile| results.request_percentile(metric, percentile).unwrap();
+        let close = |value: f64, expected: f64| (value - expected).abs() <= expected * 1e-3;
+        assert!(close(p(RequestMetric::E2eLatency, 0.5), 3000.0));
+        assert!(close(p(RequestMetric::E2eLatency, 1.0), 5000.0));
+        assert!(close(p(RequestMetric::TimeToFirstToken, 0.95), 500.0));
+        // 100 tokens over 1 to 5 seconds
+        assert!(close(p(RequestMetric::OutputTokensPerSec, 0.0), 20.0));
+        assert!(close(p(RequestMetric::OutputTokensPerSec, 1.0), 100.0));
+    }
+
+    #[test]
+    fn test_time_series() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantVUs,
+            ExecutorConfig {
+                time_series_window: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+        );
+        let start = tokio::time::Instant::now();
+        let ms = |ms: u64| start + Duration::from_millis(ms);
+        // 400ms requests every 500ms, and a failed one over both windows
+        for (sent, ended, failed) in [
+            (0, 400, false),
+            (500, 900, false),
+            (200, 1600, true),
+            (1000, 1400, false),
+            (1500, 1900, false),
+        ] {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(ms(sent));
+            response.end_time = Some(ms(ended));
+            response.times_to_tokens = vec![Duration::from_millis(100)];
+            response.num_generated_tokens = 10;
+            response.failed = failed;
+            results.add_response(response);
+        }
+        let points = results.time_series().unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].start, Duration::ZERO);
+        assert_eq!(points[0].successful_requests, 2);
+        assert_eq!(points[0].failed_requests, 0);
+        assert!((points[0].request_throughput - 2.0).abs() < 1e-6);
+        assert!((points[0].token_throughput_secs - 20.0).abs() < 1e-6);
+        assert!((points[0].time_to_first_token_avg_ms.unwrap() - 100.0).abs() < 1e-6);
+        assert!((points[0].active_vus - 1.6).abs() < 1e-6);
+        // the last window is cut at the end of the step
+        assert_eq!(points[1].start, Duration::from_secs(1));
+        assert_eq!(points[1].successful_requests, 2);
+        assert_eq!(points[1].failed_requests, 1);
+        assert!((points[1].request_throughput - 2.0 / 0.9).abs() < 1e-6);
+        assert!((points[1].active_vus - 1.4 / 0.9).abs() < 1e-6);
+
+        let results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantVUs,
+            ExecutorConfig::default(),
+        );
+        assert!(results.time_series().is_none());
+    }
+
+    #[test]
+    fn test_max_stored_responses() {
+        let mut results = BenchmarkResults::new(
+            "test".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                max_stored_responses: Some(100),
+                ..Default::default()
+            },
+        );
+        let start = tokio::time::Instant::now();
+        // e2e latencies of 1 to 1000ms, one request in ten failed
+        for i in 1..=1000 {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start);
+            response.end_time = Some(start + Duration::from_millis(i));
+            response.times_to_tokens = vec![Duration::from_millis(i / 2)];
+            response.num_generated_tokens = 10;
+            response.failed = i % 10 == 0;
+            results.add_response(response);
+        }
+        assert_eq!(results.get_responses().len(), 100);
+        // counters cover all the responses
+        assert_eq!(results.total_requests(), 1000);
+        assert_eq!(results.failed_requests(), 100);
+        assert_eq!(results.total_tokens(), 9000);
+        assert_eq!(results.duration().unwrap(), Duration::from_secs(1));
+        // percentiles from the histograms, within their precision
+        let p50 = results.e2e_latency_percentile(0.5).unwrap().as_secs_f64() * 1000.0;
+        assert!((p50 - 500.0).abs() < 2.0, "p50 {p50}");
+        let max = results
+            .request_percentile(RequestMetric::E2eLatency, 1.0)
+            .unwrap();
+        assert!((max - 999.0).abs() < 1.0, "max {max}");
+        // averages are exact
+        assert!((results.request_avg(RequestMetric::E2eLatency).unwrap() - 500.0).abs() < 1e-6);
+        let avg = results.e2e_latency_avg().unwrap().as_secs_f64() * 1000.0;
+        assert!((avg - 500.0).abs() < 1e-6);
+        // failures are extrapolated from the stored sample
+        let failures = results.failures()[&ErrorClass::Other];
+        assert_eq!(failures % 10, 0);
+        assert!(failures > 0 && failures < 1000);
+    }
 }
@@ -1,46 +1,89 @@
 use crate::executors::{
-    ConstantArrivalRateExecutor, ConstantVUsExecutor, Executor, ExecutorConfig,
+    ConstantArrivalRateExecutor, ConstantVUsExecutor, Executor, ExecutorConfig, ResponseSender,
+    TraceReplayExecutor,
 };
+use crate::flux::{Metric, MetricsBus, RequestSample};
+use crate::pause::DispatchPause;
 use crate::requests::{
     TextGenerationAggregatedResponse, TextGenerationBackend, TextRequestGenerator,
 };
 use crate::results::BenchmarkErrors::NoResponses;
 use crate::results::BenchmarkResults;
 use log::{debug, trace, warn};
+use serde::Serialize;
 use std::sync::Arc;
-use tokio::sync::mpsc::{Sender, UnboundedReceiver, UnboundedSender};
+use std::time::Duration;
+use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
 
-#[derive(Clone, strum_macros::Display)]
+#[derive(Clone, Debug, strum_macros::Display, Serialize)]
 pub enum ExecutorType {
     ConstantVUs,
     ConstantArrivalRate,
+    /// Requests sent at the times recorded in a trace
+    TraceReplay,
 }
 
 pub struct Scheduler {
     id: String,
     executor: Arc<Mutex<dyn Executor + Send>>,
-    requests_generator: Arc<Mutex<dyn TextRequestGenerator + Send>>,
+    requests_generator: Arc<dyn TextRequestGenerator>,
     results: Arc<Mutex<BenchmarkResults>>,
-    progress_tx: Sender<Option<SchedulerProgress>>,
+    metrics_bus: MetricsBus,
     stop_sender: broadcast::Sender<()>,
 }
 
+/// Progress of the running stage, published on the metrics bus for every response
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
 pub struct SchedulerProgress {
+    /// Id of the running stage, e.g. `constant@5.00req/s`
+    pub id: String,
+    pub executor_type: ExecutorType,
+    /// Arrival rate the executor currently targets, `None` for VU stages
+    pub target_rate: Option<f64>,
+    #[serde(rename = "elapsed_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub elapsed: Duration,
+    #[serde(rename = "remaining_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub remaining: Duration,
+    /// Elapsed share of the stage duration, in percent
     pub progress: f64,
     pub requests_throughput: f64,
     pub successful_requests: u64,
     pub failed_requests: u64,
 }
 
+/// Liveness report of the running stage, published at a fixed interval whether or not responses
+/// arrive, so that a wedged stage (e.g. a server hang) is still visible
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub struct Heartbeat {
+    pub id: String,
+    pub executor_type: ExecutorType,
+    #[serde(rename = "elapsed_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub elapsed: Duration,
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    /// Time since the last response, or since the stage start if none arrived yet
+    #[serde(rename = "since_last_response_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub since_last_response: Duration,
+    /// No response arrived since the previous heartbeat
+    pub stalled: bool,
+}
+
 impl Scheduler {
     pub fn new(
         id: String,
         backend: Box<dyn TextGenerationBackend + Send + Sync>,
         executor_type: ExecutorType,
         config: ExecutorConfig,
-        requests_generator: Arc<Mutex<dyn TextRequestGenerator + Send>>,
-        progress_tx: Sender<Option<SchedulerProgress>>,
+        requests_generator: Arc<dyn TextRequestGenerator>,
+        metrics_bus: MetricsBus,
         stop_sender: broadcast::Sender<()>,
     ) -> Scheduler {
         match executor_type {
@@ -48,8 +91,7 @@ impl Scheduler {
                 id: id.clone(),
                 executor: Arc::from(Mutex::from(ConstantVUsExecutor::new(
                     backend.clone(),
-                    config.max_vus,
-                    config.duration,
+                    config.clone(),
                 ))),
                 results: Arc::from(Mutex::from(BenchmarkResults::new(
                     id.clone(),
@@ -57,21 +99,18 @@ impl Scheduler {
                     config,
                 ))),
                 requests_generator,
-                progress_tx,
+                metrics_bus,
                 stop_sender,
             },
             ExecutorType::ConstantArrivalRate => {
                 if config.rate.is_none() {
                     panic!("Rate must be specified for ConstantArrivalRateExecutor");
                 }
-                let rate = config.rate.unwrap();
                 Scheduler {
                     id: id.clone(),
                     executor: Arc::from(Mutex::from(ConstantArrivalRateExecutor::new(
                         backend.clone(),
-                        config.max_vus,
-                        config.duration,
-                        rate,
+                        config.clone(),
                     ))),
                     results: Arc::from(Mutex::from(BenchmarkResults::new(
                         id.clone(),
@@ -79,7 +118,27 @@ impl Scheduler {
                         config,
                     ))),
                     requests_generator,
-                    progress_tx,
+                    metrics_bus,
+                    stop_sender,
+                }
+            }
+            ExecutorType::TraceReplay => {
+                if config.trace.is_none() {
+                    panic!("Trace must be specified for TraceReplayExecutor");
+                }
+                Scheduler {
+                    id: id.clone(),
+                    executor: Arc::from(Mutex::from(TraceReplayExecutor::new(
+                        backend.clone(),
+                        config.clone(),
+                    ))),
+                    results: Arc::from(Mutex::from(BenchmarkResults::new(
+                        id.clone(),
+                        ExecutorType::TraceReplay,
+                        config,
+                    ))),
+                    requests_generator,
+                    metrics_bus,
                     stop_sender,
                 }
             }
@@ -89,12 +148,16 @@ impl Scheduler {
     pub async fn run(&mut self) -> anyhow::Result<BenchmarkResults> {
         debug!("Starting scheduler '{}'", self.id);
         // add responses to the benchmark result as they arrive
+        let config = self.results.lock().await.executor_config();
+        let capacity = config.response_channel_capacity;
         let (tx, mut rx): (
-            UnboundedSender<TextGenerationAggregatedResponse>,
-            UnboundedReceiver<TextGenerationAggregatedResponse>,
-        ) = tokio::sync::mpsc::unbounded_channel();
+            Sender<TextGenerationAggregatedResponse>,
+            Receiver<TextGenerationAggregatedResponse>,
+        ) = tokio::sync::mpsc::channel(capacity);
+        let responses_tx = ResponseSender::new(tx);
         let results = self.results.clone();
-        let progress_tx = self.progress_tx.clone();
+        let metrics_bus = self.metrics_bus.clone();
+        let id = self.id.clone();
         let mut stop_receiver = self.stop_sender.subscribe();
         tokio::spawn(async move {
             tokio::select! {
@@ -102,36 +165,66 @@ impl Scheduler {
                     debug!("Received stop signal, stopping benchmark");
                 }
                 _ = async{
-                    while let Some(response) = rx.recv().await{
+                    while let Some(mut response) = rx.recv().await{
                         let result = results.clone();
-                        let progress_tx = progress_tx.clone();
                         trace!("Received response: {:?}", response);
                         if response.ended {
                             return;
                         }
+                        metrics_bus.publish(Metric::Request(RequestSample::new(id.clone(), &response)));
+                        // processors may be slow, they don't hold the results lock
+                        config.response_processors.process(&mut response);
                         let mut result = result.lock().await;
                         result.add_response(response);
-                        let expected_duration = result.executor_config().duration.as_secs_f64();
-                        let start_time = result.start_time().unwrap_or(tokio::time::Instant::now());
-                        let _ = progress_tx.send(Some(SchedulerProgress {
-                            progress: (100.0 * (1.0 - (expected_duration - start_time.elapsed().as_secs_f64()) / expected_duration)).min(100.0),
+                        let config = result.executor_config();
+                        let elapsed = result.start_time().map(|start| config.pause.active_elapsed(start)).unwrap_or_default();
+                        // steps with a number of requests may end before their duration
+                        let completed = config.num_requests.map_or(0.0, |n| result.total_requests() as f64 / n as f64);
+                        metrics_bus.publish(Metric::Progress(SchedulerProgress {
+                            id: id.clone(),
+                            executor_type: result.executor_type(),
+                            target_rate: config.rate_at(elapsed),
+                            elapsed,
+                            remaining: config.duration.saturating_sub(elapsed),
+                            progress: (100.0 * (elapsed.as_secs_f64() / config.duration.as_secs_f64()).max(completed)).min(100.0),
                             requests_throughput: result.successful_request_rate().unwrap_or_default(),
                             successful_requests: result.successful_requests() as u64,
                             failed_requests: result.failed_requests() as u64,
-                        })).await;
+                        }));
                     }
                 }=>{}
             }
         });
+        let heartbeat = config
+            .heartbeat_interval
+            .map(|interval| self.spawn_heartbeat(interval, config.pause.clone()));
+        let start = tokio::time::Instant::now();
+        self.results.lock().await.set_stage_start(start);
         self.executor
             .lock()
             .await
             .run(
                 self.requests_generator.clone(),
-                tx,
+                responses_tx.clone(),
                 self.stop_sender.clone(),
             )
             .await;
+        if let Some(heartbeat) = heartbeat {
+            heartbeat.abort();
+        }
+        self.results
+            .lock()
+            .await
+            .set_paused(config.pause.intervals(start, tokio::time::Instant::now()));
+        self.results.lock().await.set_response_channel_stats(
+            responses_tx.blocked_sends(),
+            responses_tx.discarded_responses(),
+            responses_tx.cancelled_requests(),
+        );
+        self.results
+            .lock()
+            .await
+            .set_decode_scale(self.requests_generator.decode_scale());
         warn!("{:?}", self.results.clone());
         if self.results.lock().await.successful_requests() == 0 {
             Err(anyhow::anyhow!(NoResponses))
@@ -140,6 +233,35 @@ impl Scheduler {
         }
     }
 
+    /// Publish a heartbeat with the stage counters every `interval` until aborted
+    fn spawn_heartbeat(&self, interval: Duration, pause: DispatchPause) -> JoinHandle<()> {
+        let results = self.results.clone();
+        let metrics_bus = self.metrics_bus.clone();
+        let id = self.id.clone();
+        let start = tokio::time::Instant::now();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(start + interval, interval);
+            let mut last_total = 0;
+            loop {
+                ticker.tick().await;
+                let results = results.lock().await;
+                let total = results.total_requests();
+                let elapsed = start.elapsed();
+                metrics_bus.publish(Metric::Heartbeat(Heartbeat {
+                    id: id.clone(),
+                    executor_type: results.executor_type(),
+                    elapsed,
+                    successful_requests: results.successful_requests() as u64,
+                    failed_requests: results.failed_requests() as u64,
+                    since_last_response: results.end_time().map_or(elapsed, |end| end.elapsed()),
+                    // no responses are expected once the requests in flight completed
+                    stalled: total == last_total && !pause.is_paused(),
+                }));
+                last_total = total;
+            }
+        })
+    }
+
     pub fn get_results(&self) -> Arc<Mutex<BenchmarkResults>> {
         self.results.clone()
     }
@@ -148,21 +270,21 @@ impl Scheduler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::requests::OpenAITextGenerationBackend;
+    use crate::executors::{Trace, TraceEntry};
+    use crate::flux::Topic;
+    use crate::requests::{OpenAITextGenerationBackend, RequestOptions};
     use std::time::Duration;
     use tokenizers::Tokenizer;
     use tokio::time;
 
     #[tokio::test]
     async fn test_constant_arrival_rate_scheduler() {
-        let (progress_tx, _) = tokio::sync::mpsc::channel(10000);
+        let metrics_bus = MetricsBus::new(10000);
         let (stop_sender, _) = tokio::sync::broadcast::channel(1);
         let backend = Box::new(crate::requests::DummyTextGenerationBackend::new(
             Duration::from_secs(1),
         ));
-        let requests_generator = Arc::from(Mutex::from(
-            crate::requests::DummyTextRequestGenerator::new(),
-        ));
+        let requests_generator = Arc::new(crate::requests::DummyTextRequestGenerator::new());
         let mut scheduler = Scheduler::new(
             "test".to_string(),
             backend,
@@ -171,9 +293,10 @@ mod tests {
                 max_vus: 800,
                 duration: std::time::Duration::from_secs(10),
                 rate: Some(20.0),
+                ..Default::default()
             },
             requests_generator,
-            progress_tx,
+            metrics_bus,
             stop_sender,
         );
         let results = scheduler.run().await.unwrap();
@@ -182,14 +305,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_constant_vus_scheduler() {
-        let (progress_tx, _) = tokio::sync::mpsc::channel(10000);
+        let metrics_bus = MetricsBus::new(10000);
         let (stop_sender, _) = tokio::sync::broadcast::channel(1);
         let backend = Box::new(crate::requests::DummyTextGenerationBackend::new(
             Duration::from_secs(1),
         ));
-        let requests_generator = Arc::from(Mutex::from(
-            crate::requests::DummyTextRequestGenerator::new(),
-        ));
+        let requests_generator = Arc::new(crate::requests::DummyTextRequestGenerator::new());
         let mut scheduler = Scheduler::new(
             "test".to_string(),
             backend,
@@ -198,9 +319,10 @@ mod tests {
                 max_vus: 800,
                 duration: std::time::Duration::from_secs(10),
                 rate: None,
+                ..Default::default()
             },
             requests_generator,
-            progress_tx,
+            metrics_bus,
             stop_sender,
         );
         let results = scheduler.run().await.unwrap();
@@ -211,9 +333,212 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_num_requests() {
+        for (executor_type, rate) in [
+            (ExecutorType::ConstantVUs, None),
+            (ExecutorType::ConstantArrivalRate, Some(50.0)),
+        ] {
+            let backend = Box::new(crate::requests::DummyTextGenerationBackend::new(
+                Duration::from_millis(200),
+            ));
+            let (stop_sender, _) = tokio::sync::broadcast::channel(1);
+            let mut scheduler = Scheduler::new(
+                "test".to_string(),
+                backend,
+                executor_type,
+                ExecutorConfig {
+                    max_vus: 4,
+                    duration: Duration::from_secs(60),
+                    rate,
+                    num_requests: Some(10),
+                    ..Default::default()
+                },
+                Arc::new(crate::requests::DummyTextRequestGenerator::new()),
+                MetricsBus::new(10000),
+                stop_sender,
+            );
+            let start = std::time::Instant::now();
+            let results = scheduler.run().await.unwrap();
+            // the step ends once the requests in flight completed, well before the duration
+            assert_eq!(results.successful_requests(), 10);
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pause() {
+        let backend = Box::new(crate::requests::DummyTextGenerationBackend::new(
+            Duration::from_millis(100),
+        ));
+        let (stop_sender, _) = tokio::sync::broadcast::channel(1);
+        let pause = DispatchPause::default();
+        let mut scheduler = Scheduler::new(
+            "test".to_string(),
+            backend,
+            ExecutorType::ConstantArrivalRate,
+            ExecutorConfig {
+                max_vus: 10,
+                duration: Duration::from_secs(2),
+                rate: Some(20.0),
+                pause: pause.clone(),
+                ..Default::default()
+            },
+            Arc::new(crate::requests::DummyTextRequestGenerator::new()),
+            MetricsBus::new(10000),
+            stop_sender,
+        );
+        tokio::spawn(async move {
+            time::sleep(Duration::from_millis(500)).await;
+            pause.pause();
+            time::sleep(Duration::from_secs(1)).await;
+            pause.resume();
+        });
+        let start = std::time::Instant::now();
+        let results = scheduler.run().await.unwrap();
+        // the step runs for its duration of dispatch, no requests being sent while paused
+        assert!(start.elapsed() >= Duration::from_secs(3));
+        let requests = results.successful_requests();
+        assert!((35..=40).contains(&requests), "{requests} requests");
+        let paused = results.paused_duration();
+        assert!(
+            paused >= Duration::from_millis(900) && paused <= Duration::from_millis(1100),
+            "{paused:?}"
+        );
+        assert!(results.duration().unwrap() < Duration::from_millis(2200));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat() {
+        let metrics_bus = MetricsBus::new(10000);
+        let mut heartbeats = metrics_bus.subscribe(&[Topic::Heartbeat]);
+        let (stop_sender, _) = tokio::sync::broadcast::channel(1);
+        let backend = Box::new(crate::requests::DummyTextGenerationBackend::new(
+            Duration::from_millis(1500),
+        ));
+        let mut scheduler = Scheduler::new(
+            "test".to_string(),
+            backend,
+            ExecutorType::ConstantVUs,
+            ExecutorConfig {
+                max_vus: 1,
+                duration: Duration::from_secs(2),
+                heartbeat_interval: Some(Duration::from_millis(500)),
+                ..Default::default()
+            },
+            Arc::new(crate::requests::DummyTextRequestGenerator::new()),
+            metrics_bus,
+            stop_sender,
+        );
+        scheduler.run().await.unwrap();
+        let mut received = vec![];
+        while let Ok(Some(Metric::Heartbeat(heartbeat))) =
+            time::timeout(Duration::from_millis(10), heartbeats.recv()).await
+        {
+            received.push(heartbeat);
+        }
+        // heartbeats are emitted while no response arrives, then report the first one
+        assert!(received.len() >= 3, "got {} heartbeats", received.len());
+        assert!(received[0].stalled);
+        assert_eq!(received[0].successful_requests, 0);
+        assert_eq!(received[0].since_last_response, received[0].elapsed);
+        let last = received.last().unwrap();
+        assert_eq!(last.successful_requests, 1);
+        assert!(last.since_last_response < last.elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_constant_vus_stagger() {
+        let metrics_bus = MetricsBus::new(10000);
+        let (stop_sender, _) = tokio::sync::broadcast::channel(1);
+        let backend = Box::new(crate::requests::DummyTextGenerationBackend::new(
+            Duration::from_secs(1),
+        ));
+        let requests_generator = Arc::new(crate::requests::DummyTextRequestGenerator::new());
+        let mut scheduler = Scheduler::new(
+            "test".to_string(),
+            backend,
+            ExecutorType::ConstantVUs,
+            ExecutorConfig {
+                max_vus: 2,
+                duration: std::time::Duration::from_secs(3),
+                vu_stagger: std::time::Duration::from_secs(1),
+                ..Default::default()
+            },
+            requests_generator,
+            metrics_bus,
+            stop_sender,
+        );
+        let results = scheduler.run().await.unwrap();
+        let mut start_times = results
+            .get_responses()
+            .iter()
+            .filter_map(|r| r.start_time)
+            .collect::<Vec<_>>();
+        start_times.sort();
+        // the second VU starts half the stagger after the first one
+        let gap = start_times[1].duration_since(start_times[0]);
+        assert!(
+            gap >= Duration::from_millis(400),
+            "VUs started {gap:?} apart"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trace_replay_scheduler() {
+        let metrics_bus = MetricsBus::new(10000);
+        let (stop_sender, _) = tokio::sync::broadcast::channel(1);
+        let backend = Box::new(crate::requests::DummyTextGenerationBackend::new(
+            Duration::from_millis(100),
+        ));
+        let requests_generator = Arc::new(crate::requests::DummyTextRequestGenerator::new());
+        let entry = |timestamp: f64, output_tokens| TraceEntry {
+            timestamp,
+            prompt_tokens: 2,
+            output_tokens,
+            prompt: None,
+        };
+        let mut scheduler = Scheduler::new(
+            "test".to_string(),
+            backend,
+            ExecutorType::TraceReplay,
+            ExecutorConfig {
+                max_vus: 10,
+                duration: Duration::from_secs(1),
+                trace: Some(Trace {
+                    path: "trace.jsonl".to_string(),
+                    time_scale: 0.5,
+                    entries: vec![entry(0.0, 4), entry(1.0, 8), entry(2.0, 16)],
+                }),
+                ..Default::default()
+            },
+            requests_generator,
+            metrics_bus,
+            stop_sender,
+        );
+        let results = scheduler.run().await.unwrap();
+        assert_eq!(results.successful_requests(), 3);
+        let mut responses = results.get_responses();
+        responses.sort_by_key(|r| r.start_time);
+        let start = responses[0].start_time.unwrap();
+        // requests are sent at the scaled trace timestamps, with the recorded output lengths
+        let gap = responses[2].start_time.unwrap().duration_since(start);
+        assert!(
+            gap >= Duration::from_millis(950) && gap < Duration::from_millis(1500),
+            "last request sent {gap:?} after the first one"
+        );
+        assert_eq!(
+            responses
+                .iter()
+                .map(|r| r.num_generated_tokens)
+                .collect::<Vec<_>>(),
+            vec![4, 8, 16]
+        );
+    }
+
     #[tokio::test]
     async fn test_constant_arrival_rate_openai_backend() {
-        let (progress_tx, _) = tokio::sync::mpsc::channel(10000);
+        let metrics_bus = MetricsBus::new(10000);
         let (stop_sender, _) = tokio::sync::broadcast::channel(1);
         let mut s = mockito::Server::new_async().await;
         s.mock("POST", "/v1/chat/completions")
@@ -234,11 +559,10 @@ mod tests {
             "gpt2".to_string(),
             tokenizer,
             time::Duration::from_secs(10),
+            RequestOptions::default(),
         )
         .unwrap();
-        let requests_generator = Arc::from(Mutex::from(
-            crate::requests::DummyTextRequestGenerator::new(),
-        ));
+        let requests_generator = Arc::new(crate::requests::DummyTextRequestGenerator::new());
         let mut scheduler = Scheduler::new(
             "test".to_string(),
             Box::new(backend),
@@ -247,9 +571,10 @@ mod tests {
                 max_vus: 800,
                 duration: std::time::Duration::from_secs(10),
                 rate: Some(50.0),
+                ..Default::default()
             },
             requests_generator,
-            progress_tx,
+            metrics_bus,
             stop_sender,
         );
         let results = scheduler.run().await.unwrap();
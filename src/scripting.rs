@@ -0,0 +1,348 @@
+use crate::processors::ResponseProcessor;
+use crate::requests::TextGenerationAggregatedResponse;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::path::Path;
+use std::sync::Arc;
+
+const ON_REQUEST: &str = "on_request";
+const CLASSIFY_RESPONSE: &str = "classify_response";
+const SCORE_RESPONSE: &str = "score_response";
+
+/// A Rhai script customizing each request, for gateway protocols not covered by the OpenAI API.
+/// It may define:
+/// - `fn on_request(request)`: receives `#{body, headers}` and returns it with the JSON payload
+///   and HTTP headers modified
+/// - `fn classify_response(response)`: receives `#{text, generated_tokens, failed}` and returns
+///   whether the response is successful
+/// - `fn score_response(response)`: receives `#{text, prompt_tokens, generated_tokens,
+///   requested_tokens}` of a successful response and returns a map of custom scores, see
+///   [`ResponseProcessor`]
+#[derive(Clone)]
+pub struct RequestScript {
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    path: String,
+}
+
+impl Debug for RequestScript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestScript")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// Request as seen and returned by `on_request`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptedRequest {
+    pub body: serde_json::Value,
+    pub headers: BTreeMap<String, String>,
+}
+
+impl RequestScript {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let script = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read script {}: {e}", path.display()))?;
+        Self::compile(&script, path.display().to_string())
+    }
+
+    fn compile(script: &str, path: String) -> anyhow::Result<Self> {
+        let engine = Engine::new();
+        let ast = engine
+            .compile(script)
+            .map_err(|e| anyhow::anyhow!("Failed to compile script {path}: {e}"))?;
+        if ![ON_REQUEST, CLASSIFY_RESPONSE, SCORE_RESPONSE]
+            .iter()
+            .any(|name| has_fn(&ast, name))
+        {
+            return Err(anyhow::anyhow!(
+                "Script {path} must define `{ON_REQUEST}`, `{CLASSIFY_RESPONSE}` and/or `{SCORE_RESPONSE}`"
+            ));
+        }
+        Ok(Self {
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+            path,
+        })
+    }
+
+    /// Run `on_request` if defined, the request is returned unchanged otherwise
+    pub fn transform_request(&self, request: ScriptedRequest) -> anyhow::Result<ScriptedRequest> {
+        if !has_fn(&self.ast, ON_REQUEST) {
+            return Ok(request);
+        }
+        let mut arg = Map::new();
+        arg.insert("body".into(), rhai::serde::to_dynamic(&request.body)?);
+        arg.insert("headers".into(), rhai::serde::to_dynamic(&request.headers)?);
+        let result: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, ON_REQUEST, (arg,))
+            .map_err(|e| anyhow::anyhow!("{ON_REQUEST} failed: {e}"))?;
+        let mut result = result
+            .try_cast::<Map>()
+            .ok_or_else(|| anyhow::anyhow!("{ON_REQUEST} must return a map"))?;
+        let body = result.remove("body").unwrap_or_default();
+        let headers = result.remove("headers").unwrap_or_default();
+        Ok(ScriptedRequest {
+            body: rhai::serde::from_dynamic(&body)?,
+            headers: if headers.is_unit() {
+                BTreeMap::new()
+            } else {
+                rhai::serde::from_dynamic(&headers)?
+            },
+        })
+    }
+
+    /// Run `classify_response` if defined, returning whether the response is successful
+    pub fn classify_response(
+        &self,
+        text: &str,
+        generated_tokens: u64,
+        failed: bool,
+    ) -> anyhow::Result<Option<bool>> {
+        if !has_fn(&self.ast, CLASSIFY_RESPONSE) {
+            return Ok(None);
+        }
+        let mut arg = Map::new();
+        arg.insert("text".into(), text.into());
+        arg.insert("generated_tokens".into(), (generated_tokens as i64).into());
+        arg.insert("failed".into(), failed.into());
+        let success: bool = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, CLASSIFY_RESPONSE, (arg,))
+            .map_err(|e| anyhow::anyhow!("{CLASSIFY_RESPONSE} failed: {e}"))?;
+        Ok(Some(success))
+    }
+}
+
+impl RequestScript {
+    /// Whether the script defines `score_response`, to be run as a response processor
+    pub fn scores_responses(&self) -> bool {
+        has_fn(&self.ast, SCORE_RESPONSE)
+    }
+}
+
+impl ResponseProcessor for RequestScript {
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    fn process(
+        &self,
+        response: &TextGenerationAggregatedResponse,
+    ) -> anyhow::Result<BTreeMap<String, f64>> {
+        let mut arg = Map::new();
+        arg.insert("text".into(), response.generated_text.clone().into());
+        arg.insert(
+            "prompt_tokens".into(),
+            (response.num_prompt_tokens as i64).into(),
+        );
+        arg.insert(
+            "generated_tokens".into(),
+            (response.num_generated_tokens as i64).into(),
+        );
+        let requested = response
+            .request
+            .as_ref()
+            .and_then(|request| request.num_decode_tokens);
+        arg.insert(
+            "requested_tokens".into(),
+            requested.map_or(Dynamic::UNIT, |tokens| (tokens as i64).into()),
+        );
+        let scores: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, SCORE_RESPONSE, (arg,))
+            .map_err(|e| anyhow::anyhow!("{SCORE_RESPONSE} failed: {e}"))?;
+        let scores = scores
+            .try_cast::<Map>()
+            .ok_or_else(|| anyhow::anyhow!("{SCORE_RESPONSE} must return a map"))?;
+        scores
+            .into_iter()
+            .map(|(name, value)| {
+                let value = value
+                    .as_float()
+                    .or_else(|_| value.as_int().map(|v| v as f64))
+                    .or_else(|_| value.as_bool().map(|v| f64::from(v as u8)))
+                    .map_err(|_| anyhow::anyhow!("score {name} must be a number or a boolean"))?;
+                Ok((name.to_string(), value))
+            })
+            .collect()
+    }
+}
+
+/// Metric computed for each benchmark step from its base metrics with a Rhai expression, defined
+/// as `name=expression`, e.g. `tokens_per_dollar=token_throughput_secs * 3600 / 2.5`
+#[derive(Clone)]
+pub struct DerivedMetric {
+    pub name: String,
+    pub expression: String,
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+}
+
+impl Debug for DerivedMetric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.expression)
+    }
+}
+
+impl serde::Serialize for DerivedMetric {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{}={}", self.name, self.expression))
+    }
+}
+
+impl std::str::FromStr for DerivedMetric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, expression) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Derived metric must be `name=expression`: {s}"))?;
+        let (name, expression) = (name.trim(), expression.trim());
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow::anyhow!("Invalid derived metric name: {name}"));
+        }
+        let engine = Engine::new();
+        let ast = engine
+            .compile_expression(expression)
+            .map_err(|e| anyhow::anyhow!("Failed to compile derived metric {name}: {e}"))?;
+        Ok(Self {
+            name: name.to_string(),
+            expression: expression.to_string(),
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+        })
+    }
+}
+
+impl DerivedMetric {
+    /// Evaluate the expression with the base metrics as variables
+    pub fn evaluate(&self, metrics: &BTreeMap<String, f64>) -> anyhow::Result<f64> {
+        let mut scope = Scope::new();
+        for (name, value) in metrics {
+            scope.push_constant(name.as_str(), *value);
+        }
+        let value: Dynamic = self
+            .engine
+            .eval_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| anyhow::anyhow!("Failed to evaluate derived metric {}: {e}", self.name))?;
+        value
+            .as_float()
+            .or_else(|_| value.as_int().map(|v| v as f64))
+            .map_err(|t| anyhow::anyhow!("Derived metric {} is a {t}, not a number", self.name))
+    }
+}
+
+fn has_fn(ast: &AST, name: &str) -> bool {
+    ast.iter_functions().any(|f| f.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_script() {
+        let script = RequestScript::compile(
+            r#"
+            fn on_request(request) {
+                request.body.temperature = 0.7;
+                request.headers["X-Model"] = request.body.model;
+                request
+            }
+            fn classify_response(response) {
+                !response.failed && !response.text.contains("rate limited")
+            }
+            "#,
+            "test.rhai".to_string(),
+        )
+        .unwrap();
+        let request = script
+            .transform_request(ScriptedRequest {
+                body: serde_json::json!({"model": "llama", "temperature": 0.0}),
+                headers: BTreeMap::new(),
+            })
+            .unwrap();
+        assert_eq!(
+            request.body,
+            serde_json::json!({"model": "llama", "temperature": 0.7})
+        );
+        assert_eq!(request.headers["X-Model"], "llama");
+        assert_eq!(
+            script.classify_response("hello", 1, false).unwrap(),
+            Some(true)
+        );
+        assert_eq!(
+            script
+                .classify_response("you are rate limited", 4, false)
+                .unwrap(),
+            Some(false)
+        );
+        assert!(RequestScript::compile("let x = 1;", "empty.rhai".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_derived_metric() {
+        let metric: DerivedMetric = "tokens_per_dollar = token_throughput_secs * 3600 / 2.5"
+            .parse()
+            .unwrap();
+        assert_eq!(metric.name, "tokens_per_dollar");
+        let metrics = BTreeMap::from([("token_throughput_secs".to_string(), 100.0)]);
+        assert_eq!(metric.evaluate(&metrics).unwrap(), 144000.0);
+        let metric: DerivedMetric = "count=2 + 3".parse().unwrap();
+        assert_eq!(metric.evaluate(&metrics).unwrap(), 5.0);
+        let metric: DerivedMetric = "ttft_rate=ttft_avg_ms * rate".parse().unwrap();
+        assert!(metric.evaluate(&metrics).is_err());
+        assert!("no_expression".parse::<DerivedMetric>().is_err());
+        assert!("bad name=1".parse::<DerivedMetric>().is_err());
+        assert!("x=1 +".parse::<DerivedMetric>().is_err());
+    }
+
+    #[test]
+    fn test_score_response() {
+        let script = RequestScript::compile(
+            r#"
+            fn score_response(response) {
+                #{
+                    words: response.text.split(" ").len(),
+                    long: response.generated_tokens > response.requested_tokens,
+                    ratio: response.generated_tokens / 4.0,
+                }
+            }
+            "#,
+            "score.rhai".to_string(),
+        )
+        .unwrap();
+        assert!(script.scores_responses());
+        let mut response = TextGenerationAggregatedResponse::default();
+        response.generated_text = "three short words".to_string();
+        response.num_generated_tokens = 6;
+        response.request = Some(std::sync::Arc::new(
+            crate::requests::TextGenerationRequest {
+                prompt: String::new(),
+                num_prompt_tokens: 1,
+                num_decode_tokens: Some(5),
+                system_prompt: None,
+                expected_answer: None,
+                context_clamped: false,
+            },
+        ));
+        assert_eq!(
+            script.process(&response).unwrap(),
+            BTreeMap::from([
+                ("long".to_string(), 1.0),
+                ("ratio".to_string(), 1.5),
+                ("words".to_string(), 3.0),
+            ])
+        );
+        let invalid = RequestScript::compile(
+            r#"fn score_response(response) { #{ label: "toxic" } }"#,
+            "invalid.rhai".to_string(),
+        )
+        .unwrap();
+        assert!(invalid.process(&response).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Mutex};
+use tokio::task::JoinHandle;
+
+/// Scrapes a Prometheus `/metrics` endpoint of the benchmarked server
+#[derive(Clone)]
+pub struct ServerMetricsScraper {
+    client: reqwest::Client,
+    url: String,
+    selected: Option<Vec<String>>,
+}
+
+impl ServerMetricsScraper {
+    /// `selected` restricts the scraped metric names, all counters are kept otherwise
+    pub fn new(url: String, selected: Option<Vec<String>>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+            selected,
+        }
+    }
+
+    pub async fn scrape(&self) -> anyhow::Result<MetricsSnapshot> {
+        let response = self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?;
+        let text = response.text().await?;
+        Ok(MetricsSnapshot::parse(&text, self.selected.as_deref()))
+    }
+}
+
+/// Values of the scraped series, keyed by series name including labels
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsSnapshot(BTreeMap<String, f64>);
+
+impl MetricsSnapshot {
+    /// Parse the Prometheus text exposition format
+    pub fn parse(text: &str, selected: Option<&[String]>) -> Self {
+        let mut counters = HashSet::new();
+        let mut values = BTreeMap::new();
+        for line in text.lines().map(|l| l.trim()) {
+            if let Some(type_line) = line.strip_prefix("# TYPE ") {
+                let mut parts = type_line.split_whitespace();
+                if let (Some(name), Some("counter")) = (parts.next(), parts.next()) {
+                    counters.insert(name.to_string());
+                }
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // series may have labels containing spaces, the value follows the closing brace
+            let (series, rest) = match line.rfind('}') {
+                Some(i) => (&line[..=i], &line[i + 1..]),
+                None => match line.split_once(char::is_whitespace) {
+                    Some((series, rest)) => (series, rest),
+                    None => continue,
+                },
+            };
+            let name = series.split('{').next().unwrap_or_default();
+            let keep = match selected {
+                Some(selected) => selected.iter().any(|s| s == name),
+                None => {
+                    counters.contains(name)
+                        || counters.contains(name.trim_end_matches("_total"))
+                        || name.ends_with("_total")
+                }
+            };
+            if !keep {
+                continue;
+            }
+            if let Some(Ok(value)) = rest.split_whitespace().next().map(|v| v.parse::<f64>()) {
+                values.insert(series.to_string(), value);
+            }
+        }
+        MetricsSnapshot(values)
+    }
+
+    /// Sum of the values of a metric over all its label sets
+    pub fn sum(&self, name: &str) -> Option<f64> {
+        let values = self
+            .0
+            .iter()
+            .filter(|(series, _)| series.split('{').next() == Some(name))
+            .map(|(_, v)| *v)
+            .collect::<Vec<f64>>();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.iter().sum())
+        }
+    }
+
+    /// Difference between this snapshot and an earlier one, for series present in both
+    pub fn diff(&self, start: &MetricsSnapshot) -> BTreeMap<String, f64> {
+        self.0
+            .iter()
+            .filter_map(|(series, end)| start.0.get(series).map(|s| (series.clone(), end - s)))
+            .collect()
+    }
+}
+
+const VLLM_NUM_REQUESTS_RUNNING: &str = "vllm:num_requests_running";
+const VLLM_NUM_REQUESTS_WAITING: &str = "vllm:num_requests_waiting";
+// renamed to kv_cache_usage_perc in vLLM V1
+const VLLM_KV_CACHE_USAGE: [&str; 2] = ["vllm:gpu_cache_usage_perc", "vllm:kv_cache_usage_perc"];
+const VLLM_NUM_PREEMPTIONS: &str = "vllm:num_preemptions_total";
+const VLLM_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+fn vllm_metrics() -> Vec<String> {
+    let mut metrics = vec![
+        VLLM_NUM_REQUESTS_RUNNING.to_string(),
+        VLLM_NUM_REQUESTS_WAITING.to_string(),
+        VLLM_NUM_PREEMPTIONS.to_string(),
+    ];
+    metrics.extend(VLLM_KV_CACHE_USAGE.iter().map(|m| m.to_string()));
+    metrics
+}
+
+/// Periodically samples vLLM engine stats from its metrics endpoint during a benchmark step
+#[derive(Clone)]
+pub struct VllmStatsCollector {
+    scraper: ServerMetricsScraper,
+}
+
+impl VllmStatsCollector {
+    pub fn new(url: String) -> Self {
+        Self {
+            scraper: ServerMetricsScraper::new(url, Some(vllm_metrics())),
+        }
+    }
+
+    pub fn start(&self) -> VllmStatsCollection {
+        let (stop_tx, mut stop_rx) = oneshot::channel();
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let scraper = self.scraper.clone();
+        let samples_clone = samples.clone();
+        let handle = tokio::spawn(async move {
+            let start = Instant::now();
+            let mut interval = tokio::time::interval(VLLM_STATS_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = &mut stop_rx => break,
+                    _ = interval.tick() => {
+                        match scraper.scrape().await {
+                            Ok(snapshot) => samples_clone
+                                .lock()
+                                .await
+                                .push(VllmStatsSample::new(start.elapsed(), &snapshot)),
+                            Err(e) => log::warn!("Failed to scrape vLLM stats: {e}"),
+                        }
+                    }
+                }
+            }
+        });
+        VllmStatsCollection {
+            stop_tx,
+            handle,
+            samples,
+        }
+    }
+}
+
+pub struct VllmStatsCollection {
+    stop_tx: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+    samples: Arc<Mutex<Vec<VllmStatsSample>>>,
+}
+
+impl VllmStatsCollection {
+    pub async fn stop(self) -> VllmStats {
+        let _ = self.stop_tx.send(());
+        let _ = self.handle.await;
+        let samples = self.samples.lock().await.clone();
+        VllmStats::new(samples)
+    }
+}
+
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub struct VllmStatsSample {
+    #[serde(rename = "elapsed_ms")]
+    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
+    pub elapsed: Duration,
+    pub num_requests_running: Option<f64>,
+    pub num_requests_waiting: Option<f64>,
+    pub kv_cache_usage: Option<f64>,
+    pub num_preemptions: Option<f64>,
+}
+
+impl VllmStatsSample {
+    fn new(elapsed: Duration, snapshot: &MetricsSnapshot) -> Self {
+        Self {
+            elapsed,
+            num_requests_running: snapshot.sum(VLLM_NUM_REQUESTS_RUNNING),
+            num_requests_waiting: snapshot.sum(VLLM_NUM_REQUESTS_WAITING),
+            kv_cache_usage: VLLM_KV_CACHE_USAGE.iter().find_map(|m| snapshot.sum(m)),
+            num_preemptions: snapshot.sum(VLLM_NUM_PREEMPTIONS),
+        }
+    }
+}
+
+/// Summary of the vLLM engine stats sampled over a benchmark step
+#[derive(Clone, Debug, Serialize)]
+pub struct VllmStats {
+    pub num_requests_running_avg: Option<f64>,
+    pub num_requests_running_max: Option<f64>,
+    pub num_requests_waiting_avg: Option<f64>,
+    pub num_requests_waiting_max: Option<f64>,
+    pub kv_cache_usage_avg: Option<f64>,
+    pub kv_cache_usage_max: Option<f64>,
+    pub preemptions: Option<f64>,
+    pub samples: Vec<VllmStatsSample>,
+}
+
+impl VllmStats {
+    fn new(samples: Vec<VllmStatsSample>) -> Self {
+        let values = |f: fn(&VllmStatsSample) -> Option<f64>| -> Vec<f64> {
+            samples.iter().filter_map(f).collect()
+        };
+        let avg = |v: Vec<f64>| (!v.is_empty()).then(|| v.iter().sum::<f64>() / v.len() as f64);
+        let max = |v: Vec<f64>| v.into_iter().reduce(f64::max);
+        let preemptions = values(|s| s.num_preemptions);
+        Self {
+            num_requests_running_avg: avg(values(|s| s.num_requests_running)),
+            num_requests_running_max: max(values(|s| s.num_requests_running)),
+            num_requests_waiting_avg: avg(values(|s| s.num_requests_waiting)),
+            num_requests_waiting_max: max(values(|s| s.num_requests_waiting)),
+            kv_cache_usage_avg: avg(values(|s| s.kv_cache_usage)),
+            kv_cache_usage_max: max(values(|s| s.kv_cache_usage)),
+            preemptions: match (preemptions.first(), preemptions.last()) {
+                (Some(first), Some(last)) => Some(last - first),
+                _ => None,
+            },
+            samples,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_diff() {
+        let start = MetricsSnapshot::parse(
+            r#"# HELP vllm:num_preemptions_total Cumulative number of preemption.
+# TYPE vllm:num_preemptions_total counter
+vllm:num_preemptions_total{model_name="llama"} 2.0
+# TYPE vllm:gpu_cache_usage_perc gauge
+vllm:gpu_cache_usage_perc{model_name="llama"} 0.5
+# TYPE tgi_request_count counter
+tgi_request_count 10
+"#,
+            None,
+        );
+        let end = MetricsSnapshot::parse(
+            r#"# TYPE vllm:num_preemptions_total counter
+vllm:num_preemptions_total{model_name="llama"} 7.0
+# TYPE tgi_request_count counter
+tgi_request_count 25 1700000000
+new_counter_total 3
+"#,
+            None,
+        );
+        let diff = end.diff(&start);
+        assert_eq!(diff.len(), 2);
+        assert_eq!(
+            diff["vllm:num_preemptions_total{model_name=\"llama\"}"],
+            5.0
+        );
+        assert_eq!(diff["tgi_request_count"], 15.0);
+
+        let selected = MetricsSnapshot::parse(
+            "vllm:gpu_cache_usage_perc 0.5\ntgi_request_count 10\n",
+            Some(&["vllm:gpu_cache_usage_perc".to_string()]),
+        );
+        assert_eq!(
+            selected,
+            MetricsSnapshot(BTreeMap::from([(
+                "vllm:gpu_cache_usage_perc".to_string(),
+                0.5
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_vllm_stats() {
+        let sample = |running: f64, kv_cache: f64, preemptions: f64| {
+            let snapshot = MetricsSnapshot::parse(
+                &format!(
+                    "vllm:num_requests_running{{model_name=\"a\"}} {running}\n\
+                     vllm:kv_cache_usage_perc{{model_name=\"a\"}} {kv_cache}\n\
+                     vllm:num_preemptions_total{{model_name=\"a\"}} {preemptions}\n"
+                ),
+                Some(&vllm_metrics()),
+            );
+            VllmStatsSample::new(Duration::default(), &snapshot)
+        };
+        let stats = VllmStats::new(vec![sample(2.0, 0.2, 1.0), sample(4.0, 0.6, 4.0)]);
+        assert_eq!(stats.num_requests_running_avg, Some(3.0));
+        assert_eq!(stats.num_requests_running_max, Some(4.0));
+        assert_eq!(stats.num_requests_waiting_avg, None);
+        assert_eq!(stats.kv_cache_usage_max, Some(0.6));
+        assert_eq!(stats.preemptions, Some(3.0));
+    }
+}
@@ -1,4 +1,8 @@
-use crate::results::BenchmarkReport;
+use crate::analysis::{CapacityPoint, ParetoPoint};
+use crate::batch::BatchResults;
+use crate::format::{LatencyUnit, OutputFormat};
+use crate::results::{BenchmarkReport, RequestMetric};
+use crate::scripting::DerivedMetric;
 use crate::BenchmarkConfig;
 use tabled::builder::Builder;
 
@@ -17,6 +21,12 @@ pub fn parameters_table(benchmark: BenchmarkConfig) -> anyhow::Result<tabled::Ta
         .extra_metadata
         .map_or("N/A".to_string(), |e| format!("{:?}", e));
     builder.set_header(vec!["Parameter", "Value"]);
+    if let Some(name) = &benchmark.name {
+        builder.push_record(vec!["Name", name.as_str()]);
+    }
+    if let Some(description) = &benchmark.description {
+        builder.push_record(vec!["Description", description.as_str()]);
+    }
     builder.push_record(vec!["Max VUs", benchmark.max_vus.to_string().as_str()]);
     builder.push_record(vec![
         "Duration",
@@ -37,13 +47,19 @@ pub fn parameters_table(benchmark: BenchmarkConfig) -> anyhow::Result<tabled::Ta
     builder.push_record(vec!["Tokenizer", benchmark.tokenizer.to_string().as_str()]);
     builder.push_record(vec!["Extra Metadata", extra_metadata.as_str()]);
     let mut table = builder.build();
-    table.with(tabled::settings::Style::sharp());
+    benchmark.output_format.style(&mut table);
     Ok(table)
 }
 
-pub fn results_table(benchmark: BenchmarkReport) -> anyhow::Result<tabled::Table> {
+pub fn results_table(
+    benchmark: BenchmarkReport,
+    format: &OutputFormat,
+) -> anyhow::Result<tabled::Table> {
     let mut builder = Builder::default();
-    builder.set_header(vec![
+    let results = benchmark.get_results();
+    // the accuracy column is only shown when answers are scored
+    let with_accuracy = results.iter().any(|r| r.accuracy().is_some());
+    let mut header = vec![
         "Benchmark",
         "QPS",
         "E2E Latency (avg)",
@@ -54,45 +70,194 @@ pub fn results_table(benchmark: BenchmarkReport) -> anyhow::Result<tabled::Table
         "Successful Requests",
         "Prompt tokens per req (avg)",
         "Decoded tokens per req (avg)",
-    ]);
-    let results = benchmark.get_results();
+    ];
+    if with_accuracy {
+        header.push("Accuracy");
+    }
+    builder.set_header(header);
     for result in results {
-        let qps = format!("{:.2} req/s", result.successful_request_rate()?);
-        let e2e = format!("{:.2} sec", result.e2e_latency_avg()?.as_secs_f64());
-        let ttft = format!(
-            "{:.2} ms",
-            result.time_to_first_token_avg()?.as_micros() as f64 / 1000.0
-        );
-        let itl = format!(
-            "{:.2} ms",
-            result.inter_token_latency_avg()?.as_micros() as f64 / 1000.0
-        );
-        let throughput = format!("{:.2} tokens/sec", result.token_throughput_secs()?);
+        let qps = format.throughput(result.successful_request_rate()?, "req/s");
+        let e2e = format.latency(result.e2e_latency_avg()?, LatencyUnit::Seconds);
+        let ttft = format.latency(result.time_to_first_token_avg()?, LatencyUnit::Milliseconds);
+        let itl = format.latency(result.inter_token_latency_avg()?, LatencyUnit::Milliseconds);
+        let throughput = format.throughput(result.token_throughput_secs()?, "tokens/sec");
         let error_rate = result.failed_requests() as f64 / result.total_requests() as f64 * 100.0;
-        let error_rate = format!("{:.2}%", error_rate);
-        builder.push_record(vec![
-            result.id.as_str(),
-            qps.as_str(),
-            e2e.as_str(),
-            ttft.as_str(),
-            itl.as_str(),
-            throughput.as_str(),
-            error_rate.as_str(),
+        let error_rate = format.percent(error_rate);
+        let mut record = vec![
+            result.id.clone(),
+            qps,
+            e2e,
+            ttft,
+            itl,
+            throughput,
+            error_rate,
             format!(
                 "{}/{}",
                 result.successful_requests(),
                 result.total_requests()
-            )
-            .as_str(),
-            format!("{:.2}", result.prompt_tokens_avg()?).as_str(),
-            format!(
-                "{:.2}",
-                result.total_tokens() as f64 / result.successful_requests() as f64
-            )
-            .as_str(),
+            ),
+            format.number(result.prompt_tokens_avg()?, 2),
+            format.number(
+                result.total_tokens() as f64 / result.successful_requests() as f64,
+                2,
+            ),
+        ];
+        if with_accuracy {
+            record.push(
+                result
+                    .accuracy()
+                    .map_or("N/A".to_string(), |a| format.percent(100.0 * a.accuracy)),
+            );
+        }
+        builder.push_record(record);
+    }
+    let mut table = builder.build();
+    format.style(&mut table);
+    Ok(table)
+}
+
+pub fn percentiles_table(
+    benchmark: BenchmarkReport,
+    percentiles: &[f64],
+    format: &OutputFormat,
+) -> anyhow::Result<tabled::Table> {
+    let mut builder = Builder::default();
+    let mut header = vec!["Benchmark".to_string(), "Metric".to_string()];
+    header.extend(percentiles.iter().map(|p| format!("p{p}")));
+    header.push("max".to_string());
+    builder.set_header(header);
+    for result in benchmark.get_results() {
+        for metric in RequestMetric::ALL {
+            let value = |percentile: f64| match result.request_percentile(metric, percentile) {
+                Ok(value) => match metric {
+                    RequestMetric::TimeToFirstToken | RequestMetric::InterTokenLatency => {
+                        format.latency_secs(value / 1000.0, LatencyUnit::Milliseconds)
+                    }
+                    RequestMetric::E2eLatency => {
+                        format.latency_secs(value / 1000.0, LatencyUnit::Seconds)
+                    }
+                    RequestMetric::OutputTokensPerSec => format.number(value, 2),
+                },
+                Err(_) => "N/A".to_string(),
+            };
+            let mut record = vec![result.id.clone(), metric.to_string()];
+            record.extend(percentiles.iter().map(|p| value(p / 100.0)));
+            record.push(value(1.0));
+            builder.push_record(record);
+        }
+    }
+    let mut table = builder.build();
+    format.style(&mut table);
+    Ok(table)
+}
+
+pub fn pareto_table(
+    frontier: Vec<ParetoPoint>,
+    format: &OutputFormat,
+) -> anyhow::Result<tabled::Table> {
+    let mut builder = Builder::default();
+    builder.set_header(vec!["Benchmark", "Throughput", "E2E Latency (p99)"]);
+    for point in frontier {
+        builder.push_record(vec![
+            point.id.as_str(),
+            format
+                .throughput(point.token_throughput_secs, "tokens/sec")
+                .as_str(),
+            format
+                .latency_secs(point.e2e_latency_p99_secs, LatencyUnit::Seconds)
+                .as_str(),
+        ]);
+    }
+    let mut table = builder.build();
+    format.style(&mut table);
+    Ok(table)
+}
+
+pub fn derived_metrics_table(
+    benchmark: BenchmarkReport,
+    metrics: &[DerivedMetric],
+    format: &OutputFormat,
+) -> anyhow::Result<tabled::Table> {
+    let mut builder = Builder::default();
+    let mut header = vec!["Benchmark".to_string()];
+    header.extend(metrics.iter().map(|metric| metric.name.clone()));
+    builder.set_header(header);
+    for result in benchmark.get_results() {
+        let values = result.derived_metrics(metrics);
+        let mut record = vec![result.id.clone()];
+        record.extend(
+            metrics.iter().map(|metric| {
+                values[&metric.name].map_or("N/A".to_string(), |v| format.number(v, 2))
+            }),
+        );
+        builder.push_record(record);
+    }
+    let mut table = builder.build();
+    format.style(&mut table);
+    Ok(table)
+}
+
+pub fn capacity_table(
+    points: Vec<CapacityPoint>,
+    format: &OutputFormat,
+) -> anyhow::Result<tabled::Table> {
+    let mut builder = Builder::default();
+    builder.set_header(vec![
+        "Rate",
+        "Requests",
+        "Failed Requests",
+        "Throughput",
+        "E2E Latency (p50)",
+        "E2E Latency (p99)",
+    ]);
+    for point in points {
+        builder.push_record(vec![
+            format.throughput(point.rate, "req/s").as_str(),
+            point.requests.to_string().as_str(),
+            point.failed_requests.to_string().as_str(),
+            format
+                .throughput(point.token_throughput_secs, "tokens/sec")
+                .as_str(),
+            format
+                .latency_secs(point.e2e_latency_p50_secs, LatencyUnit::Seconds)
+                .as_str(),
+            format
+                .latency_secs(point.e2e_latency_p99_secs, LatencyUnit::Seconds)
+                .as_str(),
         ]);
     }
     let mut table = builder.build();
-    table.with(tabled::settings::Style::sharp());
+    format.style(&mut table);
+    Ok(table)
+}
+
+pub fn batch_table(results: &BatchResults, format: &OutputFormat) -> anyhow::Result<tabled::Table> {
+    let mut builder = Builder::default();
+    builder.set_header(vec![
+        "Batch",
+        "Status",
+        "Successful Requests",
+        "Failed Requests",
+        "Turnaround",
+        "Request Throughput",
+        "Throughput",
+    ]);
+    builder.push_record(vec![
+        results.batch_id.as_str(),
+        results.status.as_str(),
+        results.successful_requests.to_string().as_str(),
+        results.failed_requests.to_string().as_str(),
+        format
+            .latency(results.turnaround, LatencyUnit::Seconds)
+            .as_str(),
+        format
+            .throughput(results.request_throughput_secs, "req/s")
+            .as_str(),
+        format
+            .throughput(results.token_throughput_secs, "tokens/sec")
+            .as_str(),
+    ]);
+    let mut table = builder.build();
+    format.style(&mut table);
     Ok(table)
 }
@@ -0,0 +1,122 @@
+use crate::requests::TextGenerationAggregatedResponse;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Metrics of the responses of a step over a time window
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Serialize)]
+pub struct TimeSeriesPoint {
+    /// Start of the window from the start of the step
+    #[serde(rename = "start_secs")]
+    #[serde_as(as = "serde_with::DurationSeconds<f64>")]
+    pub start: Duration,
+    /// Requests completed in the window
+    pub successful_requests: u64,
+    pub failed_requests: u64,
+    pub request_throughput: f64,
+    pub token_throughput_secs: f64,
+    /// Average over the requests completed in the window
+    pub time_to_first_token_avg_ms: Option<f64>,
+    pub inter_token_latency_avg_ms: Option<f64>,
+    /// Average number of requests in flight over the window
+    pub active_vus: f64,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Window {
+    successful_requests: u64,
+    failed_requests: u64,
+    generated_tokens: u64,
+    time_to_first_token_sum: Duration,
+    inter_token_latency_sum: Duration,
+    /// Time spent by requests in flight during the window
+    busy: Duration,
+}
+
+/// Response metrics bucketed in fixed time windows over the step, updated as responses arrive.
+/// Responses count in the window they completed in, and in the active VUs of every window they
+/// were in flight during.
+#[derive(Clone, Debug)]
+pub(crate) struct TimeSeries {
+    window: Duration,
+    windows: Vec<Window>,
+}
+
+impl TimeSeries {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            windows: Vec::new(),
+        }
+    }
+
+    fn index(&self, offset: Duration) -> usize {
+        (offset.as_secs_f64() / self.window.as_secs_f64()) as usize
+    }
+
+    fn window_mut(&mut self, index: usize) -> &mut Window {
+        if index >= self.windows.len() {
+            self.windows.resize(index + 1, Window::default());
+        }
+        &mut self.windows[index]
+    }
+
+    /// Add a response of the step started at `start`
+    pub(crate) fn add(&mut self, start: Instant, response: &TextGenerationAggregatedResponse) {
+        let (Some(sent), Some(ended)) = (response.start_time, response.end_time) else {
+            return;
+        };
+        let (sent, ended) = (sent.duration_since(start), ended.duration_since(start));
+        let completed = self.index(ended);
+        let window = self.window_mut(completed);
+        if response.failed {
+            window.failed_requests += 1;
+        } else {
+            window.successful_requests += 1;
+            window.generated_tokens += response.num_generated_tokens;
+            window.time_to_first_token_sum += response.time_to_first_token().unwrap_or_default();
+            window.inter_token_latency_sum += response.inter_token_latency().unwrap_or_default();
+        }
+        for index in self.index(sent)..=completed {
+            let window_start = self.window * index as u32;
+            let overlap = ended
+                .min(window_start + self.window)
+                .saturating_sub(sent.max(window_start));
+            self.window_mut(index).busy += overlap;
+        }
+    }
+
+    /// Points of the windows over a step lasting `duration`, the last window being cut at its end
+    pub(crate) fn points(&self, duration: Duration) -> Vec<TimeSeriesPoint> {
+        let ms = |sum: Duration, n: u64| (n > 0).then(|| sum.as_secs_f64() * 1000.0 / n as f64);
+        self.windows
+            .iter()
+            .enumerate()
+            .map(|(index, window)| {
+                let start = self.window * index as u32;
+                let length = duration
+                    .saturating_sub(start)
+                    .min(self.window)
+                    .max(Duration::from_millis(1))
+                    .as_secs_f64();
+                TimeSeriesPoint {
+                    start,
+                    successful_requests: window.successful_requests,
+                    failed_requests: window.failed_requests,
+                    request_throughput: window.successful_requests as f64 / length,
+                    token_throughput_secs: window.generated_tokens as f64 / length,
+                    time_to_first_token_avg_ms: ms(
+                        window.time_to_first_token_sum,
+                        window.successful_requests,
+                    ),
+                    inter_token_latency_avg_ms: ms(
+                        window.inter_token_latency_sum,
+                        window.successful_requests,
+                    ),
+                    active_vus: window.busy.as_secs_f64() / length,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,369 @@
+use crate::assertions::Assertion;
+use crate::event::{terminal_event_task, AppEvent};
+use crate::presets::Preset;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::Stylize,
+    symbols::border,
+    text::{Line, Span, Text},
+    widgets::{block::Title, Block, Paragraph, Widget},
+    DefaultTerminal,
+};
+use reqwest::Url;
+use tokio::sync::{broadcast, mpsc};
+
+/// Answers of the first-time configuration wizard
+#[derive(Clone, Debug, PartialEq)]
+pub struct WizardAnswers {
+    pub url: String,
+    pub model: String,
+    pub preset: Option<Preset>,
+    pub slos: Vec<String>,
+}
+
+impl WizardAnswers {
+    /// Equivalent command line, for reuse. SLOs are left out when they are the preset defaults, and
+    /// cleared with an empty `--assert` when the preset ones were all removed
+    pub fn command_line(&self) -> String {
+        let mut args = vec![
+            "inference-benchmarker".to_string(),
+            "--url".to_string(),
+            shell_quote(&self.url),
+            "--tokenizer-name".to_string(),
+            shell_quote(&self.model),
+        ];
+        if let Some(preset) = &self.preset {
+            args.push("--preset".to_string());
+            args.push(preset.to_string());
+        }
+        let preset_slos = self.preset.map(|preset| preset.slos()).unwrap_or_default();
+        if self.slos != preset_slos {
+            for slo in &self.slos {
+                args.push("--assert".to_string());
+                args.push(shell_quote(slo));
+            }
+            if self.slos.is_empty() {
+                args.push("--assert".to_string());
+                args.push(shell_quote(""));
+            }
+        }
+        args.join(" ")
+    }
+}
+
+/// Single-quote a shell word unless it only has safe characters
+fn shell_quote(s: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !s.is_empty() && s.chars().all(safe) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Step {
+    Url,
+    Model,
+    Preset,
+    Slos,
+    Review,
+}
+
+struct Wizard {
+    step: Step,
+    url: String,
+    model: String,
+    /// Index in the preset choices, the last one being no preset
+    preset: usize,
+    slos: String,
+    error: Option<String>,
+    done: Option<bool>,
+}
+
+impl Wizard {
+    fn new() -> Wizard {
+        Wizard {
+            step: Step::Url,
+            url: "http://localhost:8000".to_string(),
+            model: String::new(),
+            preset: 0,
+            slos: String::new(),
+            error: None,
+            done: None,
+        }
+    }
+
+    fn preset(&self) -> Option<Preset> {
+        Preset::ALL.get(self.preset).copied()
+    }
+
+    fn slos(&self) -> Vec<String> {
+        self.slos
+            .split(',')
+            .map(str::trim)
+            .filter(|slo| !slo.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn answers(&self) -> WizardAnswers {
+        WizardAnswers {
+            url: self.url.trim().to_string(),
+            model: self.model.trim().to_string(),
+            preset: self.preset(),
+            slos: self.slos(),
+        }
+    }
+
+    fn input(&mut self) -> Option<&mut String> {
+        match self.step {
+            Step::Url => Some(&mut self.url),
+            Step::Model => Some(&mut self.model),
+            Step::Slos => Some(&mut self.slos),
+            Step::Preset | Step::Review => None,
+        }
+    }
+
+    /// Validate the current answer and move to the next step
+    fn next(&mut self) {
+        let validation = match self.step {
+            Step::Url => Url::parse(self.url.trim())
+                .map(|_| ())
+                .map_err(|e| format!("Invalid URL: {e}")),
+            Step::Model if self.model.trim().is_empty() => Err("Model is required".to_string()),
+            Step::Slos => self
+                .slos()
+                .iter()
+                .try_for_each(|slo| slo.parse::<Assertion>().map(|_| ()))
+                .map_err(|e| e.to_string()),
+            _ => Ok(()),
+        };
+        if let Err(e) = validation {
+            self.error = Some(e);
+            return;
+        }
+        self.error = None;
+        self.step = match self.step {
+            Step::Url => Step::Model,
+            Step::Model => Step::Preset,
+            Step::Preset => {
+                self.slos = self
+                    .preset()
+                    .map(|preset| preset.slos().join(", "))
+                    .unwrap_or_default();
+                Step::Slos
+            }
+            Step::Slos => Step::Review,
+            Step::Review => {
+                self.done = Some(true);
+                Step::Review
+            }
+        };
+    }
+
+    fn back(&mut self) {
+        self.error = None;
+        self.step = match self.step {
+            Step::Url => {
+                self.done = Some(false);
+                Step::Url
+            }
+            Step::Model => Step::Url,
+            Step::Preset => Step::Model,
+            Step::Slos => Step::Preset,
+            Step::Review => Step::Slos,
+        };
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+        match key_event.code {
+            KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.done = Some(false)
+            }
+            KeyCode::Enter => self.next(),
+            KeyCode::Esc => self.back(),
+            KeyCode::Up if self.step == Step::Preset => {
+                self.preset = self.preset.checked_sub(1).unwrap_or(Preset::ALL.len())
+            }
+            KeyCode::Down if self.step == Step::Preset => {
+                self.preset = (self.preset + 1) % (Preset::ALL.len() + 1)
+            }
+            KeyCode::Backspace => {
+                if let Some(input) = self.input() {
+                    input.pop();
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some(input) = self.input() {
+                    input.push(c);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Widget for &Wizard {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let answer = |label: &str, value: String| {
+            Line::from(vec![format!("{label}: ").bold(), Span::raw(value)])
+        };
+        let prompt = |label: &str, value: &str| {
+            Line::from(vec![
+                format!("{label}: ").bold().yellow(),
+                Span::raw(format!("{value}_")),
+            ])
+        };
+        let mut lines = vec![];
+        let steps = [Step::Url, Step::Model, Step::Preset, Step::Slos];
+        for step in steps.into_iter().take_while(|step| *step != self.step) {
+            lines.push(match step {
+                Step::Url => answer("URL", self.url.clone()),
+                Step::Model => answer("Model (tokenizer)", self.model.clone()),
+                Step::Preset => answer(
+                    "Workload preset",
+                    self.preset()
+                        .map(|preset| preset.to_string())
+                        .unwrap_or("none".to_string()),
+                ),
+                Step::Slos | Step::Review => answer("SLOs", self.slos.clone()),
+            });
+        }
+        match self.step {
+            Step::Url => lines.push(prompt("URL of the inference server", &self.url)),
+            Step::Model => lines.push(prompt(
+                "Model (Hugging Face tokenizer name or tokenizer.json path)",
+                &self.model,
+            )),
+            Step::Preset => {
+                lines.push(Line::from("Workload preset:".bold().yellow()));
+                let choices = Preset::ALL
+                    .iter()
+                    .map(|preset| preset.to_string())
+                    .chain(["none".to_string()]);
+                for (i, choice) in choices.enumerate() {
+                    lines.push(if i == self.preset {
+                        Line::from(format!("> {choice}").bold().cyan())
+                    } else {
+                        Line::from(format!("  {choice}"))
+                    });
+                }
+            }
+            Step::Slos => lines.push(prompt(
+                "SLOs, comma separated (e.g. p90_ttft < 500ms, error_rate < 1%)",
+                &self.slos,
+            )),
+            Step::Review => {
+                lines.push(Line::default());
+                lines.push(Line::from("Equivalent command:".bold().yellow()));
+                lines.push(Line::from(self.answers().command_line()));
+            }
+        }
+        if let Some(error) = &self.error {
+            lines.push(Line::default());
+            lines.push(Line::from(error.clone().red()));
+        }
+        lines.push(Line::default());
+        let help = match self.step {
+            Step::Preset => "↑/↓: select | Enter: next | Esc: back | Ctrl-C: quit",
+            Step::Review => "Enter: done | Esc: back | Ctrl-C: quit",
+            _ => "Enter: next | Esc: back | Ctrl-C: quit",
+        };
+        lines.push(Line::from(help.gray()));
+        let title = Title::from(" Inference Benchmarker setup ".bold());
+        let block = Block::bordered()
+            .title(title.alignment(Alignment::Center))
+            .border_set(border::THICK);
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .render(area, buf);
+    }
+}
+
+async fn run(wizard: &mut Wizard, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+    let (app_tx, mut app_rx) = mpsc::channel(8);
+    let (stop_sender, stop_receiver) = broadcast::channel(1);
+    tokio::spawn(terminal_event_task(30, app_tx, stop_receiver));
+    while wizard.done.is_none() {
+        terminal.draw(|frame| frame.render_widget(&*wizard, frame.area()))?;
+        match app_rx.recv().await {
+            Some(AppEvent::Key(key_event)) => wizard.handle_key_event(key_event),
+            Some(AppEvent::Tick | AppEvent::Resize) => {}
+            None => break,
+        }
+    }
+    let _ = stop_sender.send(());
+    Ok(())
+}
+
+/// Ask for the URL, model, workload preset and SLOs of a first benchmark in the console.
+/// `None` if the user quit before the end.
+pub async fn run_wizard() -> anyhow::Result<Option<WizardAnswers>> {
+    let mut wizard = Wizard::new();
+    let result = run(&mut wizard, &mut ratatui::init()).await;
+    ratatui::restore();
+    result?;
+    Ok((wizard.done == Some(true)).then(|| wizard.answers()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(wizard: &mut Wizard, keys: &[KeyCode]) {
+        for key in keys {
+            wizard.handle_key_event(KeyEvent::from(*key));
+        }
+    }
+
+    #[test]
+    fn test_wizard() {
+        let mut wizard = Wizard::new();
+        press(&mut wizard, &[KeyCode::Enter, KeyCode::Enter]);
+        // the model is required
+        assert_eq!(wizard.step, Step::Model);
+        assert!(wizard.error.is_some());
+        let model = "gpt2".chars().map(KeyCode::Char).collect::<Vec<_>>();
+        press(&mut wizard, &model);
+        press(
+            &mut wizard,
+            &[KeyCode::Enter, KeyCode::Down, KeyCode::Enter],
+        );
+        // the SLOs default to the preset ones
+        assert_eq!(wizard.step, Step::Slos);
+        assert_eq!(wizard.slos(), Preset::RagLongContext.slos());
+        press(&mut wizard, &[KeyCode::Enter, KeyCode::Enter]);
+        assert_eq!(wizard.done, Some(true));
+        assert_eq!(
+            wizard.answers().command_line(),
+            "inference-benchmarker --url http://localhost:8000 --tokenizer-name gpt2 --preset rag-long-context"
+        );
+
+        let answers = WizardAnswers {
+            url: "http://localhost:8080".to_string(),
+            model: "meta-llama/Llama-3.1-8B-Instruct".to_string(),
+            preset: None,
+            slos: vec!["p99_ttft < 1s".to_string()],
+        };
+        assert_eq!(
+            answers.command_line(),
+            "inference-benchmarker --url http://localhost:8080 --tokenizer-name meta-llama/Llama-3.1-8B-Instruct --assert 'p99_ttft < 1s'"
+        );
+        // the SLOs of the preset were all removed
+        let answers = WizardAnswers {
+            preset: Some(Preset::RagLongContext),
+            slos: Vec::new(),
+            ..answers
+        };
+        assert_eq!(
+            answers.command_line(),
+            "inference-benchmarker --url http://localhost:8080 --tokenizer-name meta-llama/Llama-3.1-8B-Instruct --preset rag-long-context --assert ''"
+        );
+    }
+}
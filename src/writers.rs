@@ -1,9 +1,30 @@
-use crate::results::{BenchmarkReport, BenchmarkResults};
-use crate::{executors, table, BenchmarkConfig};
+use crate::analysis::{
+    CapacityCurve, CapacityPoint, Hint, OperatingPoint, ParetoPoint, SloCapacity, TokenizerCheck,
+};
+use crate::assertions::AssertionResult;
+use crate::batch::BatchResults;
+use crate::format::LatencyUnit;
+use crate::headroom::ClientSaturation;
+use crate::hooks::HookOutput;
+use crate::processors::ScoreSummary;
+use crate::requests::{ErrorClass, TextGenerationAggregatedResponse};
+use crate::results::{
+    AccuracySummary, BenchmarkReport, BenchmarkResults, GoodputSummary, LengthBucket,
+    PlaybackSummary, PromptCacheSummary, RepetitionSummary, RequestMetric, StreamingComparison,
+    TgiDetailsSummary, TokenCountSource, TokenReconciliation, VllmDetailsSummary,
+};
+use crate::scripting::DerivedMetric;
+use crate::server_metrics::VllmStats;
+use crate::timeseries::TimeSeriesPoint;
+use crate::{analysis, executors, html, labels, table, BenchmarkConfig};
+use log::warn;
 use serde::Serialize;
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use sysinfo::{CpuRefreshKind, MemoryRefreshKind, System};
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 
 #[derive(Serialize)]
 pub struct PercentilesWriter {
@@ -17,68 +38,463 @@ pub struct PercentilesWriter {
     pub avg: f64,
 }
 
+impl PercentilesWriter {
+    pub fn try_new<F>(percentile: F, avg: Duration) -> anyhow::Result<PercentilesWriter>
+    where
+        F: Fn(f64) -> anyhow::Result<Duration>,
+    {
+        Ok(PercentilesWriter {
+            p50: percentile(0.5)?.as_micros() as f64 / 1000.,
+            p60: percentile(0.6)?.as_micros() as f64 / 1000.,
+            p70: percentile(0.7)?.as_micros() as f64 / 1000.,
+            p80: percentile(0.8)?.as_micros() as f64 / 1000.,
+            p90: percentile(0.9)?.as_micros() as f64 / 1000.,
+            p95: percentile(0.95)?.as_micros() as f64 / 1000.,
+            p99: percentile(0.99)?.as_micros() as f64 / 1000.,
+            avg: avg.as_micros() as f64 / 1000.,
+        })
+    }
+}
+
+/// Configured percentiles, max, average and standard deviation of a per-request metric, keyed
+/// `p<percentile>`, `max`, `avg` and `stddev`
+fn request_percentiles(
+    results: &BenchmarkResults,
+    metric: RequestMetric,
+    percentiles: &[f64],
+) -> BTreeMap<String, f64> {
+    let mut values = BTreeMap::new();
+    for percentile in percentiles {
+        if let Ok(value) = results.request_percentile(metric, percentile / 100.0) {
+            values.insert(format!("p{percentile}"), value);
+        }
+    }
+    if let Ok(max) = results.request_percentile(metric, 1.0) {
+        values.insert("max".to_string(), max);
+    }
+    if let Some(avg) = results.request_avg(metric) {
+        values.insert("avg".to_string(), avg);
+    }
+    if let Some(std_dev) = results.request_std_dev(metric) {
+        values.insert("stddev".to_string(), std_dev);
+    }
+    values
+}
+
+#[derive(Serialize)]
+pub struct LengthBucketWriter {
+    pub min_tokens: u64,
+    pub max_tokens: u64,
+    pub successful_requests: u64,
+    pub time_to_first_token_ms: PercentilesWriter,
+    pub inter_token_latency_ms: PercentilesWriter,
+    pub e2e_latency_ms: PercentilesWriter,
+}
+
+impl LengthBucketWriter {
+    pub fn new(bucket: LengthBucket) -> anyhow::Result<LengthBucketWriter> {
+        let results = bucket.results;
+        Ok(LengthBucketWriter {
+            min_tokens: bucket.min_tokens,
+            max_tokens: bucket.max_tokens,
+            successful_requests: results.successful_requests() as u64,
+            time_to_first_token_ms: PercentilesWriter::try_new(
+                |p| results.time_to_first_token_percentile(p),
+                results.time_to_first_token_avg()?,
+            )?,
+            inter_token_latency_ms: PercentilesWriter::try_new(
+                |p| results.inter_token_latency_percentile(p),
+                results.inter_token_latency_avg()?,
+            )?,
+            e2e_latency_ms: PercentilesWriter::try_new(
+                |p| results.e2e_latency_percentile(p),
+                results.e2e_latency_avg()?,
+            )?,
+        })
+    }
+}
+
+/// Metrics of the requests sent as a tenant. Latencies are missing when all its requests failed,
+/// e.g. on a tenant rate limited by the gateway.
+#[derive(Serialize)]
+pub struct TenantWriter {
+    pub requests: u64,
+    pub failed_requests: u64,
+    pub failures: BTreeMap<ErrorClass, u64>,
+    pub request_rate: Option<f64>,
+    pub token_throughput_secs: Option<f64>,
+    pub time_to_first_token_ms: Option<PercentilesWriter>,
+    pub e2e_latency_ms: Option<PercentilesWriter>,
+    pub scores: BTreeMap<String, ScoreSummary>,
+}
+
+impl TenantWriter {
+    pub fn new(results: &BenchmarkResults) -> TenantWriter {
+        let has_latencies = results.successful_requests() > 0;
+        TenantWriter {
+            requests: results.total_requests() as u64,
+            failed_requests: results.failed_requests() as u64,
+            failures: results.failures(),
+            request_rate: results.successful_request_rate().ok(),
+            token_throughput_secs: results.token_throughput_secs().ok(),
+            time_to_first_token_ms: results
+                .time_to_first_token_avg()
+                .and_then(|avg| {
+                    PercentilesWriter::try_new(|p| results.time_to_first_token_percentile(p), avg)
+                })
+                .ok()
+                .filter(|_| has_latencies),
+            e2e_latency_ms: results
+                .e2e_latency_avg()
+                .and_then(|avg| {
+                    PercentilesWriter::try_new(|p| results.e2e_latency_percentile(p), avg)
+                })
+                .ok()
+                .filter(|_| has_latencies),
+            scores: results.scores(),
+        }
+    }
+}
+
+/// Metrics of the requests sent in a phase of a burst stage. The request rate is averaged over the
+/// time spent in the phase.
+#[derive(Serialize)]
+pub struct BurstPhaseWriter {
+    pub requests: u64,
+    pub failed_requests: u64,
+    pub failures: BTreeMap<ErrorClass, u64>,
+    pub request_rate: f64,
+    pub time_to_first_token_ms: Option<PercentilesWriter>,
+    pub inter_token_latency_ms: Option<PercentilesWriter>,
+    pub e2e_latency_ms: Option<PercentilesWriter>,
+}
+
+impl BurstPhaseWriter {
+    pub fn new(results: &BenchmarkResults) -> BurstPhaseWriter {
+        let has_latencies = results.successful_requests() > 0;
+        let duration = results.executor_config().duration.as_secs_f64();
+        BurstPhaseWriter {
+            requests: results.total_requests() as u64,
+            failed_requests: results.failed_requests() as u64,
+            failures: results.failures(),
+            request_rate: if duration > 0.0 {
+                results.successful_requests() as f64 / duration
+            } else {
+                0.0
+            },
+            time_to_first_token_ms: results
+                .time_to_first_token_avg()
+                .and_then(|avg| {
+                    PercentilesWriter::try_new(|p| results.time_to_first_token_percentile(p), avg)
+                })
+                .ok()
+                .filter(|_| has_latencies),
+            inter_token_latency_ms: results
+                .inter_token_latency_avg()
+                .and_then(|avg| {
+                    PercentilesWriter::try_new(|p| results.inter_token_latency_percentile(p), avg)
+                })
+                .ok()
+                .filter(|_| has_latencies),
+            e2e_latency_ms: results
+                .e2e_latency_avg()
+                .and_then(|avg| {
+                    PercentilesWriter::try_new(|p| results.e2e_latency_percentile(p), avg)
+                })
+                .ok()
+                .filter(|_| has_latencies),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct WarmupPhaseWriter {
+    pub requests: u64,
+    pub failed_requests: u64,
+    pub time_to_first_token_ms: PercentilesWriter,
+    pub e2e_latency_ms: PercentilesWriter,
+}
+
+impl WarmupPhaseWriter {
+    pub fn new(results: &BenchmarkResults) -> anyhow::Result<WarmupPhaseWriter> {
+        Ok(WarmupPhaseWriter {
+            requests: results.total_requests() as u64,
+            failed_requests: results.failed_requests() as u64,
+            time_to_first_token_ms: PercentilesWriter::try_new(
+                |p| results.time_to_first_token_percentile(p),
+                results.time_to_first_token_avg()?,
+            )?,
+            e2e_latency_ms: PercentilesWriter::try_new(
+                |p| results.e2e_latency_percentile(p),
+                results.e2e_latency_avg()?,
+            )?,
+        })
+    }
+}
+
+/// Warmup run at the start of a stage, excluded from the stage results
+#[derive(Serialize)]
+pub struct StageWarmupWriter {
+    pub id: String,
+    #[serde(flatten)]
+    pub warmup: WarmupPhaseWriter,
+}
+
+/// Warmup requests split in the first (cold) ones and the next (warm) ones, to quantify the
+/// overhead of cold caches, compilation or graph capture on the first requests
+#[derive(Serialize)]
+pub struct WarmupWriter {
+    pub cold: WarmupPhaseWriter,
+    pub warm: WarmupPhaseWriter,
+    /// Average cold minus average warm latency
+    pub time_to_first_token_overhead_ms: f64,
+    pub e2e_latency_overhead_ms: f64,
+}
+
+impl WarmupWriter {
+    pub fn new(warmup: &BenchmarkResults, cold_requests: u64) -> anyhow::Result<WarmupWriter> {
+        let (cold, warm) = warmup.split_first(cold_requests as usize);
+        let overhead_ms =
+            |cold: Duration, warm: Duration| (cold.as_secs_f64() - warm.as_secs_f64()) * 1000.;
+        Ok(WarmupWriter {
+            time_to_first_token_overhead_ms: overhead_ms(
+                cold.time_to_first_token_avg()?,
+                warm.time_to_first_token_avg()?,
+            ),
+            e2e_latency_overhead_ms: overhead_ms(cold.e2e_latency_avg()?, warm.e2e_latency_avg()?),
+            cold: WarmupPhaseWriter::new(&cold)?,
+            warm: WarmupPhaseWriter::new(&warm)?,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct QueueingMetricsWriter {
+    pub service_time_ms: f64,
+    pub effective_concurrency: f64,
+    pub utilization: f64,
+}
+
+impl QueueingMetricsWriter {
+    pub fn new(results: &BenchmarkResults) -> anyhow::Result<QueueingMetricsWriter> {
+        Ok(QueueingMetricsWriter {
+            service_time_ms: results.e2e_latency_avg()?.as_micros() as f64 / 1000.,
+            effective_concurrency: results.effective_concurrency()?,
+            utilization: results.utilization()?,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct ResponseSampleWriter {
+    pub system_prompt: Option<String>,
+    pub prompt: String,
+    pub response: String,
+    pub num_prompt_tokens: u64,
+    pub num_generated_tokens: u64,
+    pub failed: bool,
+    pub idempotency_key: Option<String>,
+    pub repetition_score: Option<f64>,
+    pub expected_answer: Option<String>,
+    pub correct: Option<bool>,
+}
+
+impl ResponseSampleWriter {
+    pub fn new(response: TextGenerationAggregatedResponse) -> ResponseSampleWriter {
+        let (system_prompt, prompt, expected_answer) = match response.request {
+            Some(request) => (
+                request.system_prompt.clone(),
+                request.prompt.clone(),
+                request.expected_answer.clone(),
+            ),
+            None => (None, "".to_string(), None),
+        };
+        ResponseSampleWriter {
+            system_prompt,
+            prompt,
+            response: response.generated_text,
+            num_prompt_tokens: response.num_prompt_tokens,
+            num_generated_tokens: response.num_generated_tokens,
+            failed: response.failed,
+            idempotency_key: response.idempotency_key,
+            repetition_score: response.repetition_score,
+            expected_answer,
+            correct: response.correct,
+        }
+    }
+}
+
+/// Optional data collected from the benchmarked server
+#[derive(Serialize)]
+pub struct ServerSideWriter {
+    metrics_diff: Option<BTreeMap<String, f64>>,
+    vllm: Option<VllmStats>,
+    tgi: Option<TgiDetailsSummary>,
+    vllm_details: Option<VllmDetailsSummary>,
+    prompt_cache: Option<PromptCacheSummary>,
+}
+
+impl ServerSideWriter {
+    pub fn new(results: &BenchmarkResults) -> Option<ServerSideWriter> {
+        let writer = ServerSideWriter {
+            metrics_diff: results.server_metrics(),
+            vllm: results.vllm_stats(),
+            tgi: results.tgi_details(),
+            vllm_details: results.vllm_details(),
+            prompt_cache: results.prompt_cache(),
+        };
+        if writer.metrics_diff.is_none()
+            && writer.vllm.is_none()
+            && writer.tgi.is_none()
+            && writer.vllm_details.is_none()
+            && writer.prompt_cache.is_none()
+        {
+            return None;
+        }
+        Some(writer)
+    }
+}
+
 #[derive(Serialize)]
 pub struct BenchmarkResultsWriter {
     id: String,
+    stage_id: Option<String>,
+    /// Labels of the stage, see `--stage-label`
+    labels: BTreeMap<String, String>,
     executor_type: String,
     config: executors::ExecutorConfig,
     total_requests: u64,
     total_tokens: u64,
     token_throughput_secs: f64,
     duration_ms: u128,
+    /// Time the dispatch was paused, not part of the duration
+    paused_ms: u128,
     time_to_first_token_ms: PercentilesWriter,
     inter_token_latency_ms: PercentilesWriter,
     failed_requests: u64,
+    failures: BTreeMap<ErrorClass, u64>,
+    failure_rates: BTreeMap<ErrorClass, f64>,
+    status_codes: BTreeMap<u16, u64>,
+    retried_requests: u64,
+    retries: BTreeMap<ErrorClass, u64>,
     successful_requests: u64,
     request_rate: f64,
+    goodput: Option<GoodputSummary>,
+    time_series: Option<Vec<TimeSeriesPoint>>,
     total_tokens_sent: u64,
     e2e_latency_ms: PercentilesWriter,
+    /// Configured percentiles of the per-request metrics, by metric
+    request_percentiles: BTreeMap<String, BTreeMap<String, f64>>,
+    prefill_share: f64,
+    queueing: QueueingMetricsWriter,
+    prompt_length_buckets: Vec<LengthBucketWriter>,
+    decode_length_buckets: Vec<LengthBucketWriter>,
+    tenants: BTreeMap<String, TenantWriter>,
+    burst_phases: BTreeMap<String, BurstPhaseWriter>,
+    streaming_comparison: Option<StreamingComparison>,
+    token_reconciliation: Option<TokenReconciliation>,
+    response_samples: Vec<ResponseSampleWriter>,
+    blocked_response_sends: u64,
+    discarded_responses: u64,
+    clamped_requests: u64,
+    decode_target_scale: Option<f64>,
+    server_side: Option<ServerSideWriter>,
+    repetition: Option<RepetitionSummary>,
+    playback: Option<PlaybackSummary>,
+    accuracy: Option<AccuracySummary>,
+    /// Custom scores of the response processors
+    scores: BTreeMap<String, ScoreSummary>,
+    assertions: Vec<AssertionResult>,
+    derived_metrics: BTreeMap<String, Option<f64>>,
 }
 
 impl BenchmarkResultsWriter {
-    pub fn new(results: BenchmarkResults) -> anyhow::Result<BenchmarkResultsWriter> {
+    pub fn new(
+        results: BenchmarkResults,
+        derived_metrics: &[DerivedMetric],
+        percentiles: &[f64],
+    ) -> anyhow::Result<BenchmarkResultsWriter> {
         Ok(BenchmarkResultsWriter {
             id: results.id.clone(),
+            stage_id: results.stage_id(),
+            labels: BTreeMap::new(),
             executor_type: results.executor_type().to_string(),
             config: results.executor_config(),
             total_requests: results.total_requests() as u64,
             total_tokens: results.total_tokens(),
             token_throughput_secs: results.token_throughput_secs()?,
             duration_ms: results.duration().ok().unwrap().as_micros() / 1000,
-            time_to_first_token_ms: PercentilesWriter {
-                p50: results.time_to_first_token_percentile(0.5)?.as_micros() as f64 / 1000.,
-                p60: results.time_to_first_token_percentile(0.6)?.as_micros() as f64 / 1000.,
-                p70: results.time_to_first_token_percentile(0.7)?.as_micros() as f64 / 1000.,
-                p80: results.time_to_first_token_percentile(0.8)?.as_micros() as f64 / 1000.,
-                p90: results.time_to_first_token_percentile(0.9)?.as_micros() as f64 / 1000.,
-                p95: results.time_to_first_token_percentile(0.95)?.as_micros() as f64 / 1000.,
-                p99: results.time_to_first_token_percentile(0.99)?.as_micros() as f64 / 1000.,
-                avg: results.time_to_first_token_avg().ok().unwrap().as_micros() as f64 / 1000.,
-            },
-            inter_token_latency_ms: PercentilesWriter {
-                p50: results.inter_token_latency_percentile(0.5)?.as_micros() as f64 / 1000.,
-                p60: results.inter_token_latency_percentile(0.6)?.as_micros() as f64 / 1000.,
-                p70: results.inter_token_latency_percentile(0.7)?.as_micros() as f64 / 1000.,
-                p80: results.inter_token_latency_percentile(0.8)?.as_micros() as f64 / 1000.,
-                p90: results.inter_token_latency_percentile(0.9)?.as_micros() as f64 / 1000.,
-                p95: results.inter_token_latency_percentile(0.95)?.as_micros() as f64 / 1000.,
-                p99: results.inter_token_latency_percentile(0.99)?.as_micros() as f64 / 1000.,
-                avg: results.inter_token_latency_avg().ok().unwrap().as_micros() as f64 / 1000.,
-            },
+            paused_ms: results.paused_duration().as_millis(),
+            time_to_first_token_ms: PercentilesWriter::try_new(
+                |p| results.time_to_first_token_percentile(p),
+                results.time_to_first_token_avg()?,
+            )?,
+            inter_token_latency_ms: PercentilesWriter::try_new(
+                |p| results.inter_token_latency_percentile(p),
+                results.inter_token_latency_avg()?,
+            )?,
             failed_requests: results.failed_requests() as u64,
+            failures: results.failures(),
+            failure_rates: results.failure_rates(),
+            status_codes: results.status_codes(),
+            retried_requests: results.retried_requests(),
+            retries: results.retries(),
             successful_requests: results.successful_requests() as u64,
             request_rate: results.successful_request_rate()?,
+            goodput: results.goodput(),
+            time_series: results.time_series(),
             total_tokens_sent: results.total_tokens_sent(),
-            e2e_latency_ms: PercentilesWriter {
-                p50: results.e2e_latency_percentile(0.5)?.as_micros() as f64 / 1000.,
-                p60: results.e2e_latency_percentile(0.6)?.as_micros() as f64 / 1000.,
-                p70: results.e2e_latency_percentile(0.7)?.as_micros() as f64 / 1000.,
-                p80: results.e2e_latency_percentile(0.8)?.as_micros() as f64 / 1000.,
-                p90: results.e2e_latency_percentile(0.9)?.as_micros() as f64 / 1000.,
-                p95: results.e2e_latency_percentile(0.95)?.as_micros() as f64 / 1000.,
-                p99: results.e2e_latency_percentile(0.99)?.as_micros() as f64 / 1000.,
-                avg: results.e2e_latency_avg().ok().unwrap().as_micros() as f64 / 1000.,
-            },
+            e2e_latency_ms: PercentilesWriter::try_new(
+                |p| results.e2e_latency_percentile(p),
+                results.e2e_latency_avg()?,
+            )?,
+            request_percentiles: RequestMetric::ALL
+                .iter()
+                .map(|metric| {
+                    let values = request_percentiles(&results, *metric, percentiles);
+                    (metric.name().to_string(), values)
+                })
+                .collect(),
+            prefill_share: results.prefill_share()?,
+            queueing: QueueingMetricsWriter::new(&results)?,
+            prompt_length_buckets: results
+                .prompt_length_buckets()
+                .into_iter()
+                .map(LengthBucketWriter::new)
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            decode_length_buckets: results
+                .decode_length_buckets()
+                .into_iter()
+                .map(LengthBucketWriter::new)
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            tenants: results
+                .tenant_results()
+                .iter()
+                .map(|(tenant, results)| (tenant.clone(), TenantWriter::new(results)))
+                .collect(),
+            burst_phases: results
+                .phase_results()
+                .iter()
+                .map(|(phase, results)| (phase.clone(), BurstPhaseWriter::new(results)))
+                .collect(),
+            streaming_comparison: results.streaming_comparison(),
+            token_reconciliation: results.token_reconciliation(),
+            response_samples: results
+                .sampled_responses()
+                .into_iter()
+                .map(ResponseSampleWriter::new)
+                .collect(),
+            blocked_response_sends: results.blocked_response_sends(),
+            discarded_responses: results.discarded_responses(),
+            clamped_requests: results.clamped_requests(),
+            decode_target_scale: results.decode_scale(),
+            server_side: ServerSideWriter::new(&results),
+            repetition: results.repetition(),
+            playback: results.playback(),
+            accuracy: results.accuracy(),
+            scores: results.scores(),
+            assertions: results.assertions(),
+            derived_metrics: results.derived_metrics(derived_metrics),
         })
     }
 }
@@ -119,10 +535,114 @@ impl SystemInfo {
     }
 }
 
+#[derive(Serialize)]
+pub struct OperatingPointWriter {
+    pub id: String,
+    pub rate: f64,
+    pub token_throughput_secs: f64,
+    pub e2e_latency_avg_ms: f64,
+}
+
+impl OperatingPointWriter {
+    pub fn new(point: OperatingPoint) -> OperatingPointWriter {
+        OperatingPointWriter {
+            id: point.id,
+            rate: point.rate,
+            token_throughput_secs: point.token_throughput_secs,
+            e2e_latency_avg_ms: point.e2e_latency_avg_secs * 1000.,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ParetoPointWriter {
+    pub id: String,
+    pub token_throughput_secs: f64,
+    pub e2e_latency_p99_ms: f64,
+}
+
+impl ParetoPointWriter {
+    pub fn new(point: ParetoPoint) -> ParetoPointWriter {
+        ParetoPointWriter {
+            id: point.id,
+            token_throughput_secs: point.token_throughput_secs,
+            e2e_latency_p99_ms: point.e2e_latency_p99_secs * 1000.,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CapacityPointWriter {
+    pub rate: f64,
+    pub requests: u64,
+    pub failed_requests: u64,
+    pub token_throughput_secs: f64,
+    pub e2e_latency_p50_ms: f64,
+    pub e2e_latency_p99_ms: f64,
+    pub meets_slo: bool,
+}
+
+impl CapacityPointWriter {
+    pub fn new(point: CapacityPoint) -> CapacityPointWriter {
+        CapacityPointWriter {
+            rate: point.rate,
+            requests: point.requests,
+            failed_requests: point.failed_requests,
+            token_throughput_secs: point.token_throughput_secs,
+            e2e_latency_p50_ms: point.e2e_latency_p50_secs * 1000.,
+            e2e_latency_p99_ms: point.e2e_latency_p99_secs * 1000.,
+            meets_slo: point.meets_slo,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CapacityCurveWriter {
+    pub id: String,
+    pub start_rate: f64,
+    pub end_rate: f64,
+    pub knee_rate: Option<f64>,
+    /// Highest rate bin meeting the SLO objectives, if any
+    pub slo_rate: Option<f64>,
+    pub points: Vec<CapacityPointWriter>,
+}
+
+impl CapacityCurveWriter {
+    pub fn new(curve: CapacityCurve) -> CapacityCurveWriter {
+        CapacityCurveWriter {
+            knee_rate: curve.knee().map(|p| p.rate),
+            slo_rate: curve.max_rate_at_slo().map(|p| p.rate),
+            points: curve
+                .points()
+                .into_iter()
+                .map(CapacityPointWriter::new)
+                .collect(),
+            id: curve.id,
+            start_rate: curve.start_rate,
+            end_rate: curve.end_rate,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct BenchmarkReportWriter {
+    run_id: String,
     config: BenchmarkConfig,
+    config_hash: String,
+    effective_config: Option<serde_json::Value>,
     results: Vec<BenchmarkResultsWriter>,
+    recommended_operating_point: Option<OperatingPointWriter>,
+    pareto_frontier: Vec<ParetoPointWriter>,
+    capacity_curve: Option<CapacityCurveWriter>,
+    slo_capacity: Option<SloCapacity>,
+    warmup: Option<WarmupWriter>,
+    stage_warmups: Vec<StageWarmupWriter>,
+    dropped_metrics: u64,
+    client_saturations: Vec<ClientSaturation>,
+    skipped_stages: Vec<String>,
+    tokenizer_check: Option<TokenizerCheck>,
+    hints: Vec<Hint>,
+    hooks: Vec<HookOutput>,
     start_time: String,
     end_time: String,
     system: SystemInfo,
@@ -137,12 +657,52 @@ impl BenchmarkReportWriter {
     ) -> anyhow::Result<BenchmarkReportWriter> {
         let mut results: Vec<BenchmarkResultsWriter> = Vec::new();
         for result in report.get_results() {
-            let writer = BenchmarkResultsWriter::new(result)?;
+            let mut writer =
+                BenchmarkResultsWriter::new(result, &config.derived_metrics, &config.percentiles)?;
+            writer.labels =
+                labels::stage_labels(&config.stage_labels, &writer.id, writer.stage_id.as_deref());
             results.push(writer);
         }
         Ok(BenchmarkReportWriter {
+            run_id: config.run_id.clone(),
+            config_hash: config.config_hash(),
+            effective_config: config.effective_config.clone(),
+            capacity_curve: analysis::capacity_curve(
+                &report,
+                config.num_rates as usize,
+                &config.slo,
+            )
+            .map(CapacityCurveWriter::new),
+            slo_capacity: analysis::slo_capacity(&report, &config.slo),
+            warmup: warmup_writer(&config, &report),
+            stage_warmups: report
+                .stage_warmups()
+                .iter()
+                .filter_map(|warmup| match WarmupPhaseWriter::new(warmup) {
+                    Ok(writer) => Some(StageWarmupWriter {
+                        id: warmup.id.clone(),
+                        warmup: writer,
+                    }),
+                    Err(e) => {
+                        warn!("Can't report {}: {e}", warmup.id);
+                        None
+                    }
+                })
+                .collect(),
             config,
             results,
+            recommended_operating_point: analysis::recommended_operating_point(&report)
+                .map(OperatingPointWriter::new),
+            pareto_frontier: analysis::pareto_frontier(&report)
+                .into_iter()
+                .map(ParetoPointWriter::new)
+                .collect(),
+            dropped_metrics: report.dropped_metrics(),
+            client_saturations: report.client_saturations(),
+            skipped_stages: report.skipped_stages(),
+            tokenizer_check: analysis::tokenizer_check(&report),
+            hints: analysis::bottleneck_hints(&report),
+            hooks: report.hooks(),
             start_time: report
                 .start_time()
                 .ok_or(anyhow::anyhow!("start_time not set"))?
@@ -155,25 +715,1054 @@ impl BenchmarkReportWriter {
             report,
         })
     }
-    pub async fn json(&self, path: &Path) -> anyhow::Result<()> {
-        // write the benchmark report to json
-        let report = serde_json::to_string(&self)?;
+    /// Write the benchmark report to json, returns the path actually written
+    pub async fn json(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        write_report(path, serde_json::to_string(&self)?).await
+    }
 
-        // create path hierarchy if it doesn't exist
-        if !path.exists() {
-            if let Some(parent) = path.parent() {
-                fs::create_dir_all(parent).await?;
-            }
+    /// Write a Markdown summary of the report to `path`, returns the path actually written
+    pub async fn markdown(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        write_report(path, self.markdown_summary()?).await
+    }
+
+    /// Summary of the run in GitHub-flavored Markdown: its parameters, results and per-request
+    /// percentiles tables
+    pub fn markdown_summary(&self) -> anyhow::Result<String> {
+        let mut config = self.config.clone();
+        config.output_format.markdown = true;
+        let format = &config.output_format;
+        let name = config.name.as_ref().unwrap_or(&config.tokenizer);
+        let results_table = table::results_table(self.report.clone(), format)?;
+        let percentiles_table =
+            table::percentiles_table(self.report.clone(), &config.percentiles, format)?;
+        let param_table = table::parameters_table(config.clone())?;
+        Ok(format!(
+            "# {name}\n\nRun `{}`\n\n## Results\n\n{results_table}\n\n\
+            ## Per-request percentiles\n\n{percentiles_table}\n\n\
+            ## Parameters\n\n{param_table}\n",
+            self.run_id
+        ))
+    }
+
+    /// Write a self-contained HTML report with charts to `path`, returns the path actually written
+    pub async fn html(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        write_report(path, html::report_html(&serde_json::to_value(self)?)).await
+    }
+
+    /// Write a CSV summary of the report to `path`, one row per step, returns the path actually
+    /// written
+    pub async fn csv(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        write_report(path, summary_csv(&self.run_id, &self.results)).await
+    }
+
+    /// Names of the scores of all steps, a column of the raw requests each
+    fn score_names(&self) -> BTreeSet<String> {
+        self.results
+            .iter()
+            .flat_map(|results| results.scores.keys().cloned())
+            .collect()
+    }
+
+    /// Metrics of every stored request, step by step
+    fn raw_requests(&self) -> impl Iterator<Item = RawRequestWriter> + '_ {
+        // every row has the same columns
+        let score_names = self.score_names();
+        self.report.results().iter().flat_map(move |results| {
+            let score_names = score_names.clone();
+            results.responses().iter().map(move |response| {
+                RawRequestWriter::new(&self.report, &self.run_id, results, response, &score_names)
+            })
+        })
+    }
+
+    /// Write the per-step summary to `path` and the metrics of every stored request next to it,
+    /// e.g. `report.requests.parquet`, as Parquet tables. Returns the paths actually written.
+    #[cfg(feature = "parquet")]
+    pub async fn parquet(&self, path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let (columns, rows) = summary_table(&self.run_id, &self.results);
+        let steps = rows
+            .into_iter()
+            .map(|row| columns.iter().cloned().zip(row).collect())
+            .collect::<Vec<_>>();
+        let requests = self
+            .raw_requests()
+            .map(|record| serde_json::to_value(&record))
+            .collect::<Result<Vec<_>, _>>()?;
+        let steps = parquet_table(summary_schema(&columns), &steps)?;
+        let mut paths = vec![write_report(path, steps).await?];
+        if !requests.is_empty() {
+            let requests_path = path.with_extension("requests.parquet");
+            let requests = parquet_table(raw_requests_schema(&self.score_names()), &requests)?;
+            paths.push(write_report(&requests_path, requests).await?);
         }
-        fs::write(path, report).await?;
-        Ok(())
+        Ok(paths)
+    }
+
+    /// Write the metrics of every stored request next to the report at `report_path`, e.g.
+    /// `report.requests.jsonl`, returns the path actually written
+    pub async fn raw_results(
+        &self,
+        format: RawResultsFormat,
+        report_path: &Path,
+    ) -> anyhow::Result<PathBuf> {
+        let path = report_path.with_extension(format!("requests.{format}"));
+        let (path, file) = create_report(&path).await?;
+        // written line by line, the requests of long runs don't fit in a single string
+        let mut file = tokio::io::BufWriter::new(file);
+        for (i, record) in self.raw_requests().enumerate() {
+            let line = match format {
+                RawResultsFormat::Jsonl => serde_json::to_string(&record)? + "\n",
+                RawResultsFormat::Csv => raw_request_csv(&record, i == 0)?,
+            };
+            file.write_all(line.as_bytes()).await?;
+        }
+        file.flush().await?;
+        Ok(path)
     }
 
     pub async fn stdout(&self) -> anyhow::Result<()> {
         let param_table = table::parameters_table(self.config.clone())?;
         println!("\n{param_table}\n");
-        let results_table = table::results_table(self.report.clone())?;
+        println!("Run {}", self.run_id);
+        let format = &self.config.output_format;
+        let results_table = table::results_table(self.report.clone(), format)?;
         println!("\n{results_table}\n");
+        let percentiles_table =
+            table::percentiles_table(self.report.clone(), &self.config.percentiles, format)?;
+        println!("Per-request percentiles:\n{percentiles_table}\n");
+        if let Some(warmup) = &self.warmup {
+            println!(
+                "Warmup: {} cold requests | TTFT (avg) {} cold vs {} warm | E2E latency (avg) {} cold vs {} warm\n",
+                warmup.cold.requests,
+                format.latency_secs(warmup.cold.time_to_first_token_ms.avg / 1000.0, LatencyUnit::Milliseconds),
+                format.latency_secs(warmup.warm.time_to_first_token_ms.avg / 1000.0, LatencyUnit::Milliseconds),
+                format.latency_secs(warmup.cold.e2e_latency_ms.avg / 1000.0, LatencyUnit::Seconds),
+                format.latency_secs(warmup.warm.e2e_latency_ms.avg / 1000.0, LatencyUnit::Seconds),
+            );
+        }
+        for results in self.report.get_results() {
+            let failures = results.failures();
+            if !failures.is_empty() {
+                let rates = results.failure_rates();
+                let failures = failures
+                    .iter()
+                    .map(|(class, count)| {
+                        format!("{count} {class} ({})", format.percent(rates[class] * 100.0))
+                    })
+                    .collect::<Vec<_>>();
+                println!("Failed requests on {}: {}", results.id, failures.join(", "));
+            }
+            let status_codes = results.status_codes();
+            if status_codes.keys().any(|status| *status >= 300) {
+                let status_codes = status_codes
+                    .iter()
+                    .map(|(status, count)| format!("{count} {status}"))
+                    .collect::<Vec<_>>();
+                println!(
+                    "HTTP statuses on {}: {}",
+                    results.id,
+                    status_codes.join(", ")
+                );
+            }
+            if let Some(repetition) = results.repetition().filter(|r| r.repetitive_responses > 0) {
+                println!(
+                    "{} of {} responses are repetitive on {} (max score {})",
+                    repetition.repetitive_responses,
+                    repetition.requests,
+                    results.id,
+                    format.number(repetition.repetition_score_max, 2)
+                );
+            }
+            if let Some(goodput) = results.goodput() {
+                println!(
+                    "Goodput on {} ({}): {} | {} | {} of requests met the SLO",
+                    results.id,
+                    goodput.slo,
+                    format.throughput(goodput.goodput, "req/sec"),
+                    format.throughput(goodput.token_goodput_secs, "tokens/sec"),
+                    format.percent(goodput.slo_attainment)
+                );
+            }
+            if let Some(playback) = results.playback() {
+                println!(
+                    "Reading at {} on {}: smooth playback score {} | {} of {} responses stalled the reader | {} tokens ahead (avg of max)",
+                    format.throughput(playback.reading_rate, "tokens/sec"),
+                    results.id,
+                    format.number(playback.smooth_playback_score, 1),
+                    playback.stalled_requests,
+                    playback.requests,
+                    format.number(playback.max_tokens_ahead_avg, 1)
+                );
+            }
+            for (tenant, tenant_results) in results.tenant_results() {
+                let writer = TenantWriter::new(&tenant_results);
+                let na = || "N/A".to_string();
+                println!(
+                    "Tenant {tenant} on {}: {} requests, {} failed | {} | {} | TTFT (p50) {} | E2E latency (p50) {}",
+                    results.id,
+                    writer.requests,
+                    writer.failed_requests,
+                    writer.request_rate.map_or_else(na, |r| format.throughput(r, "req/sec")),
+                    writer
+                        .token_throughput_secs
+                        .map_or_else(na, |t| format.throughput(t, "tokens/sec")),
+                    writer.time_to_first_token_ms.as_ref().map_or_else(na, |l| {
+                        format.latency_secs(l.p50 / 1000.0, LatencyUnit::Milliseconds)
+                    }),
+                    writer.e2e_latency_ms.as_ref().map_or_else(na, |l| {
+                        format.latency_secs(l.p50 / 1000.0, LatencyUnit::Seconds)
+                    }),
+                );
+            }
+            for (phase, phase_results) in results.phase_results() {
+                let writer = BurstPhaseWriter::new(&phase_results);
+                let na = || "N/A".to_string();
+                println!(
+                    "Phase {phase} of {}: {} requests, {} failed | {} | TTFT (p50) {} | E2E latency (p50) {}",
+                    results.id,
+                    writer.requests,
+                    writer.failed_requests,
+                    format.throughput(writer.request_rate, "req/sec"),
+                    writer.time_to_first_token_ms.as_ref().map_or_else(na, |l| {
+                        format.latency_secs(l.p50 / 1000.0, LatencyUnit::Milliseconds)
+                    }),
+                    writer.e2e_latency_ms.as_ref().map_or_else(na, |l| {
+                        format.latency_secs(l.p50 / 1000.0, LatencyUnit::Seconds)
+                    }),
+                );
+            }
+            if let Some(comparison) = results.streaming_comparison() {
+                let na = || "N/A".to_string();
+                let latency = |latency: Option<Duration>| {
+                    latency.map_or_else(na, |l| {
+                        format.latency_secs(l.as_secs_f64(), LatencyUnit::Seconds)
+                    })
+                };
+                println!(
+                    "Streaming on {}: E2E latency (avg) {} streamed vs {} unstreamed, overhead {} | generated tokens (avg) {} streamed vs {} unstreamed",
+                    results.id,
+                    latency(comparison.streamed.e2e_latency_avg),
+                    latency(comparison.unstreamed.e2e_latency_avg),
+                    comparison.e2e_latency_overhead_ms.map_or_else(na, |o| {
+                        format.latency_secs(o / 1000.0, LatencyUnit::Milliseconds)
+                    }),
+                    comparison
+                        .streamed
+                        .generated_tokens_avg
+                        .map_or_else(na, |t| format.number(t, 1)),
+                    comparison
+                        .unstreamed
+                        .generated_tokens_avg
+                        .map_or_else(na, |t| format.number(t, 1)),
+                );
+            }
+            if let Some(tokens) = results.token_reconciliation() {
+                let error = |error: f64| format!("{:+.1}%", error * 100.0);
+                println!(
+                    "Token accounting on {}: {} chunks ({}), {} tokens counted per chunk ({}), {} re-tokenized ({}), {} reported by the server | token throughput x{:.3} to match the {} count",
+                    results.id,
+                    tokens.chunks,
+                    error(tokens.chunks_error),
+                    tokens.chunk_tokens,
+                    error(tokens.chunk_tokens_error),
+                    tokens.retokenized_tokens,
+                    tokens.retokenized_tokens_error.map_or("reference".to_string(), error),
+                    tokens.usage_tokens.map_or("N/A".to_string(), |t| t.to_string()),
+                    tokens.throughput_correction,
+                    match tokens.reference {
+                        TokenCountSource::Usage => "server",
+                        TokenCountSource::Retokenized => "re-tokenized",
+                    },
+                );
+            }
+            let scores = results.scores();
+            if !scores.is_empty() {
+                let scores = scores
+                    .iter()
+                    .map(|(name, score)| {
+                        format!(
+                            "{name} {} avg ({} responses, {} to {})",
+                            format.number(score.avg, 3),
+                            score.responses,
+                            format.number(score.min, 3),
+                            format.number(score.max, 3)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                println!("Scores on {}: {}", results.id, scores.join(" | "));
+            }
+            for assertion in results.failed_assertions() {
+                println!(
+                    "Assertion `{}` failed on {}: {}",
+                    assertion.expression,
+                    results.id,
+                    assertion
+                        .value
+                        .map_or("N/A".to_string(), |v| format.number(v, 2))
+                );
+            }
+        }
+        if !self.skipped_stages.is_empty() {
+            println!(
+                "Skipped past the run deadline: {}",
+                self.skipped_stages.join(", ")
+            );
+        }
+        if !self.config.derived_metrics.is_empty() {
+            let derived_table = table::derived_metrics_table(
+                self.report.clone(),
+                &self.config.derived_metrics,
+                format,
+            )?;
+            println!("Derived metrics:\n{derived_table}\n");
+        }
+        let frontier = analysis::pareto_frontier(&self.report);
+        if frontier.len() > 1 {
+            let pareto_table = table::pareto_table(frontier, format)?;
+            println!("Pareto frontier (throughput vs p99 latency):\n{pareto_table}\n");
+        }
+        if let Some(curve) = analysis::capacity_curve(
+            &self.report,
+            self.config.num_rates as usize,
+            &self.config.slo,
+        ) {
+            let capacity_table = table::capacity_table(curve.points(), format)?;
+            println!("Capacity curve ({}):\n{capacity_table}\n", curve.id);
+            match curve.knee() {
+                Some(knee) => println!(
+                    "Latency knee: {} | {} | E2E latency (p50) {}\n",
+                    format.throughput(knee.rate, "req/s"),
+                    format.throughput(knee.token_throughput_secs, "tokens/s"),
+                    format.latency_secs(knee.e2e_latency_p50_secs, LatencyUnit::Milliseconds)
+                ),
+                None => println!(
+                    "No latency knee up to {}\n",
+                    format.throughput(curve.end_rate, "req/s")
+                ),
+            }
+            if let Some(point) = curve.max_rate_at_slo() {
+                println!(
+                    "Max rate meeting the SLO: {} | {} | E2E latency (p50) {}\n",
+                    format.throughput(point.rate, "req/s"),
+                    format.throughput(point.token_throughput_secs, "tokens/s"),
+                    format.latency_secs(point.e2e_latency_p50_secs, LatencyUnit::Milliseconds)
+                );
+            }
+        }
+        if let Some(capacity) = &self.slo_capacity {
+            let slo = capacity.slo.join(", ");
+            match (capacity.rate, capacity.token_throughput_secs) {
+                (Some(rate), Some(token_throughput_secs)) => println!(
+                    "Max rate meeting the SLO ({slo}): {} | {}\n",
+                    format.throughput(rate, "req/s"),
+                    format.throughput(token_throughput_secs, "tokens/s")
+                ),
+                _ => println!("No rate met the SLO ({slo})\n"),
+            }
+        }
+        if let Some(point) = &self.recommended_operating_point {
+            println!(
+                "Recommended max rate: {} ({}) | {} | E2E latency (avg) {}\n",
+                format.throughput(point.rate, "req/s"),
+                point.id,
+                format.throughput(point.token_throughput_secs, "tokens/s"),
+                format.latency_secs(point.e2e_latency_avg_ms / 1000.0, LatencyUnit::Milliseconds)
+            );
+        }
+        if !self.hints.is_empty() {
+            println!("Hints:");
+            for hint in &self.hints {
+                println!("- {}", hint.message);
+            }
+            println!();
+        }
+        if let Some(baseline) = &self.config.baseline {
+            let changes = baseline.compare(&self.report.get_results());
+            if changes.is_empty() {
+                println!(
+                    "No step in common with the baseline {}\n",
+                    baseline.path().display()
+                );
+            } else {
+                println!(
+                    "Comparison with the baseline {}:\n",
+                    baseline.path().display()
+                );
+                for change in changes {
+                    println!("{change}\n");
+                }
+            }
+        }
+        if let Some(check) = self.tokenizer_check.as_ref().filter(|check| check.mismatch) {
+            println!("WARNING: {}\n", check.warning());
+        }
+        Ok(())
+    }
+}
+
+/// Cold-vs-warm characterization of the warmup, if enabled and the warmup has enough requests
+fn warmup_writer(config: &BenchmarkConfig, report: &BenchmarkReport) -> Option<WarmupWriter> {
+    if config.warmup_cold_requests == 0 {
+        return None;
+    }
+    let warmup = report
+        .get_results()
+        .into_iter()
+        .find(|r| r.id == "warmup")?;
+    match WarmupWriter::new(&warmup, config.warmup_cold_requests) {
+        Ok(writer) => Some(writer),
+        Err(e) => {
+            warn!(
+                "Can't compare the {} first warmup requests with the next ones: {e}",
+                config.warmup_cold_requests
+            );
+            None
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BatchReportWriter {
+    run_id: String,
+    config: BenchmarkConfig,
+    config_hash: String,
+    effective_config: Option<serde_json::Value>,
+    batch: BatchResults,
+    start_time: String,
+    end_time: String,
+    system: SystemInfo,
+}
+
+impl BatchReportWriter {
+    pub fn new(
+        config: BenchmarkConfig,
+        batch: BatchResults,
+        start_time: chrono::DateTime<chrono::Utc>,
+    ) -> BatchReportWriter {
+        BatchReportWriter {
+            run_id: config.run_id.clone(),
+            config_hash: config.config_hash(),
+            effective_config: config.effective_config.clone(),
+            config,
+            batch,
+            start_time: start_time.to_rfc3339(),
+            end_time: chrono::Utc::now().to_rfc3339(),
+            system: SystemInfo::new(),
+        }
+    }
+
+    pub async fn json(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        write_report(path, serde_json::to_string(&self)?).await
+    }
+
+    pub fn stdout(&self) -> anyhow::Result<()> {
+        let param_table = table::parameters_table(self.config.clone())?;
+        println!("\n{param_table}\n");
+        let batch_table = table::batch_table(&self.batch, &self.config.output_format)?;
+        println!("\n{batch_table}\n");
         Ok(())
     }
 }
+
+/// File format of the per-request raw results
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawResultsFormat {
+    /// One JSON object per line
+    Jsonl,
+    /// One row per request, inter-token latencies separated by spaces
+    Csv,
+}
+
+impl std::str::FromStr for RawResultsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "jsonl" => Ok(RawResultsFormat::Jsonl),
+            "csv" => Ok(RawResultsFormat::Csv),
+            _ => Err(anyhow::anyhow!("Unknown raw results format: {s}")),
+        }
+    }
+}
+
+impl std::fmt::Display for RawResultsFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawResultsFormat::Jsonl => write!(f, "jsonl"),
+            RawResultsFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+/// Metrics of a single request in the raw results
+#[derive(Serialize)]
+pub struct RawRequestWriter {
+    run_id: String,
+    stage: String,
+    /// Stage position and name, e.g. `03-warmup`, the same across runs of a configuration
+    stage_id: Option<String>,
+    start_time: Option<String>,
+    end_time: Option<String>,
+    failed: bool,
+    error_class: Option<ErrorClass>,
+    http_status: Option<u16>,
+    retries: usize,
+    idempotency_key: Option<String>,
+    /// Keys of the retried attempts, the same as `idempotency_key` when retries reuse it
+    retry_idempotency_keys: Vec<String>,
+    tenant: Option<String>,
+    prompt_tokens: u64,
+    generated_tokens: u64,
+    time_to_first_token_ms: Option<f64>,
+    inter_token_latency_avg_ms: Option<f64>,
+    e2e_latency_ms: Option<f64>,
+    /// Server-side queue and inference times, when reported by TGI
+    server_queue_time_ms: Option<f64>,
+    server_inference_time_ms: Option<f64>,
+    /// Time between each generated token and the previous one
+    inter_token_latencies_ms: Vec<f64>,
+    /// Custom scores, a `score_<name>` column per score of the report
+    #[serde(flatten)]
+    scores: BTreeMap<String, Option<f64>>,
+}
+
+impl RawRequestWriter {
+    fn new(
+        report: &BenchmarkReport,
+        run_id: &str,
+        results: &BenchmarkResults,
+        response: &TextGenerationAggregatedResponse,
+        score_names: &BTreeSet<String>,
+    ) -> RawRequestWriter {
+        let ms = |latency: Duration| latency.as_secs_f64() * 1000.0;
+        let time = |instant: Option<tokio::time::Instant>| {
+            instant
+                .and_then(|instant| report.wall_time(instant))
+                .map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Micros, true))
+        };
+        let details = response.server_details.as_ref();
+        RawRequestWriter {
+            run_id: run_id.to_string(),
+            stage: results.id.clone(),
+            stage_id: results.stage_id(),
+            start_time: time(response.start_time),
+            end_time: time(response.end_time),
+            failed: response.failed,
+            error_class: response.error_class,
+            http_status: response.http_status,
+            retries: response.retries.len(),
+            idempotency_key: response.idempotency_key.clone(),
+            retry_idempotency_keys: response.retry_idempotency_keys.clone(),
+            tenant: response.tenant.clone(),
+            prompt_tokens: response.num_prompt_tokens,
+            generated_tokens: response.num_generated_tokens,
+            time_to_first_token_ms: response.time_to_first_token().map(ms),
+            inter_token_latency_avg_ms: response.inter_token_latency().map(ms),
+            e2e_latency_ms: response.e2e_latency().map(ms),
+            server_queue_time_ms: details.and_then(|d| d.queue_time_ms),
+            server_inference_time_ms: details.and_then(|d| d.inference_time_ms),
+            inter_token_latencies_ms: response
+                .times_to_tokens
+                .iter()
+                .skip(1)
+                .copied()
+                .map(ms)
+                .collect(),
+            scores: score_names
+                .iter()
+                .map(|name| (format!("score_{name}"), response.scores.get(name).copied()))
+                .collect(),
+        }
+    }
+}
+
+/// Columns and rows of the per-step summary: rate or VUs, throughputs, error rate, latencies,
+/// a `label_<key>` column per stage label and a `score_<name>_avg` column per custom score
+fn summary_table(
+    run_id: &str,
+    results: &[BenchmarkResultsWriter],
+) -> (Vec<String>, Vec<Vec<serde_json::Value>>) {
+    let mut columns = vec![
+        "run_id",
+        "id",
+        "stage_id",
+        "executor_type",
+        "rate",
+        "max_vus",
+        "request_rate",
+        "token_throughput_secs",
+        "successful_requests",
+        "failed_requests",
+        "error_rate",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect::<Vec<_>>();
+    for metric in [
+        "time_to_first_token_ms",
+        "inter_token_latency_ms",
+        "e2e_latency_ms",
+    ] {
+        for stat in ["avg", "p50", "p90", "p95", "p99"] {
+            columns.push(format!("{metric}_{stat}"));
+        }
+    }
+    let label_keys = results
+        .iter()
+        .flat_map(|result| result.labels.keys())
+        .collect::<BTreeSet<_>>();
+    columns.extend(label_keys.iter().map(|key| format!("label_{key}")));
+    let score_names = results
+        .iter()
+        .flat_map(|result| result.scores.keys())
+        .collect::<BTreeSet<_>>();
+    columns.extend(score_names.iter().map(|name| format!("score_{name}_avg")));
+    let rows = results
+        .iter()
+        .map(|result| {
+            let error_rate = match result.total_requests {
+                0 => 0.0,
+                total => result.failed_requests as f64 / total as f64,
+            };
+            let mut row = vec![
+                run_id.into(),
+                result.id.clone().into(),
+                result.stage_id.clone().into(),
+                result.executor_type.clone().into(),
+                result.config.rate.into(),
+                result.config.max_vus.into(),
+                result.request_rate.into(),
+                result.token_throughput_secs.into(),
+                result.successful_requests.into(),
+                result.failed_requests.into(),
+                error_rate.into(),
+            ];
+            for latency in [
+                &result.time_to_first_token_ms,
+                &result.inter_token_latency_ms,
+                &result.e2e_latency_ms,
+            ] {
+                for value in [
+                    latency.avg,
+                    latency.p50,
+                    latency.p90,
+                    latency.p95,
+                    latency.p99,
+                ] {
+                    row.push(value.into());
+                }
+            }
+            for key in &label_keys {
+                row.push(result.labels.get(*key).cloned().into());
+            }
+            for name in &score_names {
+                row.push(result.scores.get(*name).map(|score| score.avg).into());
+            }
+            row
+        })
+        .collect();
+    (columns, rows)
+}
+
+/// CSV of the key metrics of the steps, one row per step, latencies in milliseconds
+fn summary_csv(run_id: &str, results: &[BenchmarkResultsWriter]) -> String {
+    let (columns, rows) = summary_table(run_id, results);
+    let mut csv = columns.join(",") + "\n";
+    for row in rows {
+        let row = row
+            .iter()
+            .map(|value| match value.as_f64() {
+                // `2` rather than JSON's `2.0`
+                Some(number) => number.to_string(),
+                None => csv_field(value),
+            })
+            .collect::<Vec<_>>();
+        csv += &row.join(",");
+        csv += "\n";
+    }
+    csv
+}
+
+/// Arrow schema of the columns of [`summary_table`]
+#[cfg(feature = "parquet")]
+fn summary_schema(columns: &[String]) -> arrow_schema::Schema {
+    use arrow_schema::{DataType, Field};
+
+    let fields = columns.iter().map(|name| {
+        let data_type = match name.as_str() {
+            "run_id" | "id" | "stage_id" | "executor_type" => DataType::Utf8,
+            "max_vus" | "successful_requests" | "failed_requests" => DataType::UInt64,
+            label if label.starts_with("label_") => DataType::Utf8,
+            // rates, throughputs, latencies and scores
+            _ => DataType::Float64,
+        };
+        Field::new(name, data_type, true)
+    });
+    arrow_schema::Schema::new(fields.collect::<Vec<_>>())
+}
+
+/// Arrow schema of [`RawRequestWriter`] rows, with a `score_<name>` column per score
+#[cfg(feature = "parquet")]
+fn raw_requests_schema(score_names: &BTreeSet<String>) -> arrow_schema::Schema {
+    use arrow_schema::{DataType, Field};
+
+    let list = |data_type| DataType::List(Field::new_list_field(data_type, true).into());
+    let mut fields = vec![
+        Field::new("run_id", DataType::Utf8, false),
+        Field::new("stage", DataType::Utf8, false),
+        Field::new("stage_id", DataType::Utf8, true),
+        Field::new("start_time", DataType::Utf8, true),
+        Field::new("end_time", DataType::Utf8, true),
+        Field::new("failed", DataType::Boolean, false),
+        Field::new("error_class", DataType::Utf8, true),
+        Field::new("http_status", DataType::UInt16, true),
+        Field::new("retries", DataType::UInt64, false),
+        Field::new("idempotency_key", DataType::Utf8, true),
+        Field::new("retry_idempotency_keys", list(DataType::Utf8), false),
+        Field::new("tenant", DataType::Utf8, true),
+        Field::new("prompt_tokens", DataType::UInt64, false),
+        Field::new("generated_tokens", DataType::UInt64, false),
+        Field::new("time_to_first_token_ms", DataType::Float64, true),
+        Field::new("inter_token_latency_avg_ms", DataType::Float64, true),
+        Field::new("e2e_latency_ms", DataType::Float64, true),
+        Field::new("server_queue_time_ms", DataType::Float64, true),
+        Field::new("server_inference_time_ms", DataType::Float64, true),
+        Field::new("inter_token_latencies_ms", list(DataType::Float64), false),
+    ];
+    fields.extend(
+        score_names
+            .iter()
+            .map(|name| Field::new(format!("score_{name}"), DataType::Float64, true)),
+    );
+    arrow_schema::Schema::new(fields)
+}
+
+/// Parquet file of JSON object rows with the columns of `schema`
+#[cfg(feature = "parquet")]
+fn parquet_table(
+    schema: arrow_schema::Schema,
+    rows: &[serde_json::Value],
+) -> anyhow::Result<Vec<u8>> {
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+
+    const BATCH_SIZE: usize = 1024;
+    let schema = std::sync::Arc::new(schema);
+    // fields missing from the schema are an error rather than silently dropped
+    let mut decoder = arrow_json::ReaderBuilder::new(schema.clone())
+        .with_batch_size(BATCH_SIZE)
+        .with_strict_mode(true)
+        .build_decoder()?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut buffer = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buffer, schema, Some(properties))?;
+    for chunk in rows.chunks(BATCH_SIZE) {
+        decoder.serialize(chunk)?;
+        if let Some(batch) = decoder.flush()? {
+            writer.write(&batch)?;
+        }
+    }
+    writer.close()?;
+    Ok(buffer)
+}
+
+/// CSV row of a raw request, preceded by a header row of the field names if `header`
+fn raw_request_csv(record: &RawRequestWriter, header: bool) -> anyhow::Result<String> {
+    let serde_json::Value::Object(fields) = serde_json::to_value(record)? else {
+        return Err(anyhow::anyhow!("raw request is not a JSON object"));
+    };
+    let mut csv = String::new();
+    if header {
+        csv += &fields.keys().cloned().collect::<Vec<_>>().join(",");
+        csv += "\n";
+    }
+    let row = fields.values().map(csv_field).collect::<Vec<_>>();
+    csv += &row.join(",");
+    csv += "\n";
+    Ok(csv)
+}
+
+fn csv_field(value: &serde_json::Value) -> String {
+    let field = match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(values) => {
+            values.iter().map(csv_field).collect::<Vec<_>>().join(" ")
+        }
+        value => value.to_string(),
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+/// What to do when a report with the same configuration hash already exists
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Fail before running the benchmark
+    Error,
+    /// Run and replace the existing report
+    Overwrite,
+    /// Run and write a new report next to the existing one
+    Suffix,
+    /// Don't run the benchmark
+    Skip,
+}
+
+impl std::str::FromStr for DuplicatePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(DuplicatePolicy::Error),
+            "overwrite" => Ok(DuplicatePolicy::Overwrite),
+            "suffix" => Ok(DuplicatePolicy::Suffix),
+            "skip" => Ok(DuplicatePolicy::Skip),
+            _ => Err(anyhow::anyhow!("Unknown duplicate policy: {s}")),
+        }
+    }
+}
+
+/// Find a JSON report in `dir` written for the configuration with hash `config_hash`
+pub fn find_duplicate(dir: &Path, config_hash: &str) -> Option<PathBuf> {
+    let mut paths = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.into_iter().find(|path| {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .is_some_and(|report| report["config_hash"] == config_hash)
+    })
+}
+
+/// Write a report, creating its parent directories. If that fails (e.g. read-only working
+/// directory), the report is written to the system temp directory rather than lost at the end of
+/// a long run.
+async fn write_report(path: &Path, report: impl AsRef<[u8]>) -> anyhow::Result<PathBuf> {
+    let (path, mut file) = create_report(path).await?;
+    file.write_all(report.as_ref()).await?;
+    file.flush().await?;
+    Ok(path)
+}
+
+/// Create the report file at `path`, or in the temporary directory if it can't be written there.
+/// Returns the path actually created.
+async fn create_report(path: &Path) -> anyhow::Result<(PathBuf, fs::File)> {
+    let err = match create_file(path).await {
+        Ok(file) => return Ok((path.to_path_buf(), file)),
+        Err(e) => e,
+    };
+    let fallback = std::env::temp_dir()
+        .join("inference-benchmarker")
+        .join(path.file_name().unwrap_or("report.json".as_ref()));
+    warn!(
+        "Can't write report to {}: {err}. Writing it to {} instead",
+        path.display(),
+        fallback.display()
+    );
+    let file = create_file(&fallback).await.map_err(|e| {
+        anyhow::anyhow!(
+            "Can't write report to {} ({err}) nor {} ({e})",
+            path.display(),
+            fallback.display()
+        )
+    })?;
+    Ok((fallback, file))
+}
+
+async fn create_file(path: &Path) -> std::io::Result<fs::File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::File::create(path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::ExecutorType;
+
+    #[tokio::test]
+    async fn test_write_report_fallback() {
+        let dir = std::env::temp_dir().join(format!("ib-writers-{}", std::process::id()));
+        let path = dir.join("results").join("report.json");
+        assert_eq!(write_report(&path, "{}".to_string()).await.unwrap(), path);
+        // a file in place of the results directory makes it unwritable
+        let blocked = dir.join("blocked");
+        std::fs::write(&blocked, "").unwrap();
+        let name = format!("report-{}.json", std::process::id());
+        let written = write_report(&blocked.join(&name), "{}".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            written,
+            std::env::temp_dir()
+                .join("inference-benchmarker")
+                .join(&name)
+        );
+        assert_eq!(std::fs::read_to_string(&written).unwrap(), "{}");
+        std::fs::remove_file(written).unwrap();
+        std::fs::write(dir.join("results").join("other.json"), "not json").unwrap();
+        std::fs::write(&path, r#"{"config_hash": "abc"}"#).unwrap();
+        assert_eq!(find_duplicate(&dir.join("results"), "abc"), Some(path));
+        assert_eq!(find_duplicate(&dir.join("results"), "def"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_raw_results() {
+        let mut report = BenchmarkReport::new();
+        report.start();
+        let start = tokio::time::Instant::now();
+        let mut response = TextGenerationAggregatedResponse::default();
+        response.start_time = Some(start);
+        response.end_time = Some(start + Duration::from_millis(200));
+        response.times_to_tokens = vec![
+            Duration::from_millis(100),
+            Duration::from_millis(20),
+            Duration::from_millis(40),
+        ];
+        response.num_generated_tokens = 3;
+        response.num_prompt_tokens = 50;
+        response.tenant = Some("a,b".to_string());
+        response.idempotency_key = Some("k2".to_string());
+        response.retries = vec![ErrorClass::Quota];
+        response.retry_idempotency_keys = vec!["k1".to_string()];
+        response.scores = BTreeMap::from([("json_valid".to_string(), 1.0)]);
+        let score_names = BTreeSet::from(["json_valid".to_string(), "words".to_string()]);
+        let mut results = BenchmarkResults::new(
+            "warmup".to_string(),
+            ExecutorType::ConstantVUs,
+            executors::ExecutorConfig::default(),
+        );
+        results.set_stage_id("00-warmup".to_string());
+        let record = RawRequestWriter::new(&report, "run", &results, &response, &score_names);
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["run_id"], "run");
+        assert_eq!(json["stage"], "warmup");
+        assert_eq!(json["stage_id"], "00-warmup");
+        assert_eq!(json["score_json_valid"], 1.0);
+        assert!(json["score_words"].is_null());
+        assert_eq!(json["prompt_tokens"], 50);
+        assert_eq!(json["retries"], 1);
+        assert_eq!(json["idempotency_key"], "k2");
+        assert_eq!(json["retry_idempotency_keys"], serde_json::json!(["k1"]));
+        assert_eq!(json["time_to_first_token_ms"], 100.0);
+        assert_eq!(json["inter_token_latency_avg_ms"], 30.0);
+        assert_eq!(
+            json["inter_token_latencies_ms"],
+            serde_json::json!([20.0, 40.0])
+        );
+        let start_time =
+            chrono::DateTime::parse_from_rfc3339(json["start_time"].as_str().unwrap()).unwrap();
+        let end_time =
+            chrono::DateTime::parse_from_rfc3339(json["end_time"].as_str().unwrap()).unwrap();
+        assert_eq!((end_time - start_time).num_milliseconds(), 200);
+
+        let csv = raw_request_csv(&record, true).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let header = lines[0].split(',').collect::<Vec<_>>();
+        assert!(header.contains(&"inter_token_latencies_ms"));
+        assert!(lines[1].contains(",20.0 40.0,"));
+        assert!(lines[1].contains(",\"a,b\","));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_table() {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let report = BenchmarkReport::new();
+        let results = BenchmarkResults::new(
+            "warmup".to_string(),
+            ExecutorType::ConstantVUs,
+            executors::ExecutorConfig::default(),
+        );
+        let mut response = TextGenerationAggregatedResponse::default();
+        response.times_to_tokens = vec![Duration::from_millis(100), Duration::from_millis(20)];
+        response.num_generated_tokens = 2;
+        let mut failed = TextGenerationAggregatedResponse::default();
+        failed.failed = true;
+        failed.error_class = Some(ErrorClass::Timeout);
+        // more rows than a record batch
+        let rows = std::iter::repeat_n(response, 1500)
+            .chain([failed])
+            .map(|r| {
+                serde_json::to_value(RawRequestWriter::new(
+                    &report,
+                    "run",
+                    &results,
+                    &r,
+                    &BTreeSet::new(),
+                ))
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let path = std::env::temp_dir().join(format!("ib-parquet-{}.parquet", std::process::id()));
+        let schema = raw_requests_schema(&BTreeSet::new());
+        std::fs::write(&path, parquet_table(schema, &rows).unwrap()).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1501);
+        let schema = batches[0].schema();
+        let data_type = |name| schema.field_with_name(name).unwrap().data_type().clone();
+        assert!(data_type("generated_tokens").is_integer());
+        assert!(data_type("inter_token_latencies_ms").is_nested());
+        // typed even when null on all rows
+        assert_eq!(data_type("tenant"), arrow_schema::DataType::Utf8);
+    }
+
+    #[test]
+    fn test_summary_csv() {
+        let mut results = BenchmarkResults::new(
+            "constant_arrival_rate@2.00req/s".to_string(),
+            ExecutorType::ConstantArrivalRate,
+            executors::ExecutorConfig {
+                rate: Some(2.0),
+                ..Default::default()
+            },
+        );
+        let start = tokio::time::Instant::now();
+        for i in 1..=4 {
+            let mut response = TextGenerationAggregatedResponse::default();
+            response.start_time = Some(start);
+            response.end_time = Some(start + Duration::from_secs(i));
+            response.times_to_tokens = vec![Duration::from_millis(100), Duration::from_millis(20)];
+            response.num_generated_tokens = 2;
+            response.failed = i == 4;
+            results.add_response(response);
+        }
+        let mut writer =
+            BenchmarkResultsWriter::new(results, &[], crate::results::DEFAULT_PERCENTILES).unwrap();
+        writer.labels = BTreeMap::from([("kv_cache".to_string(), "on".to_string())]);
+        let csv = summary_csv("run", &[writer]);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let row = lines[0]
+            .split(',')
+            .zip(lines[1].split(','))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(row["run_id"], "run");
+        assert_eq!(row["id"], "constant_arrival_rate@2.00req/s");
+        assert_eq!(row["rate"], "2");
+        assert_eq!(row["successful_requests"], "3");
+        assert_eq!(row["error_rate"], "0.25");
+        assert_eq!(row["time_to_first_token_ms_p50"], "100");
+        assert_eq!(row["inter_token_latency_ms_avg"], "20");
+        assert_eq!(row["label_kv_cache"], "on");
+    }
+}